@@ -0,0 +1,490 @@
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::Result;
+use crate::cli::{
+    BootstrapArgs, BootstrapCommands, BootstrapDiffArgs, BootstrapHistoryArgs, BootstrapShowArgs,
+};
+use crate::core::bootstrap::format_stamp_age;
+use crate::core::events::Event;
+use crate::core::history::{BootstrapRunLog, BootstrapRunSummary, Change};
+use crate::core::operations;
+use crate::core::options::{
+    BootstrapDiffOptions, BootstrapHistoryOptions, BootstrapOptions, BootstrapShowOptions,
+};
+use crate::core::outcome::{BootstrapDiffOutcome, BootstrapHistoryOutcome};
+use crate::core::project::format_config_warnings;
+use crate::core::reporter::Reporter;
+
+use super::common::{config_load_options, emit_diagnostics, split_config_warnings};
+use super::up::{
+    format_bytes, format_duration_ms, hash_snippet, render_bootstrap_event, render_bootstrap_runs,
+};
+
+pub fn handle_bootstrap(
+    args: BootstrapArgs,
+    config_override: Option<&PathBuf>,
+    profile: Option<&str>,
+) -> Result<()> {
+    let BootstrapArgs {
+        command,
+        skip_discovery,
+        workspace,
+        vms,
+        stages,
+        force,
+    } = args;
+    match command {
+        Some(BootstrapCommands::History(args)) => {
+            return handle_history(args, config_override, profile);
+        }
+        Some(BootstrapCommands::Show(args)) => return handle_show(args, config_override, profile),
+        Some(BootstrapCommands::Diff(args)) => return handle_diff(args, config_override, profile),
+        None => {}
+    }
+
+    let options = BootstrapOptions {
+        config: config_load_options(config_override, profile, skip_discovery, "bootstrap")?,
+        workspace,
+        vms,
+        stages,
+        force,
+    };
+
+    let mut printer = LivePrinter;
+    let output = operations::bootstrap(options, Some(&mut printer))?;
+
+    let (config_warnings, other) = split_config_warnings(&output.diagnostics);
+    if let Some(message) = format_config_warnings(&config_warnings) {
+        eprint!("{message}");
+    }
+    emit_diagnostics(&other);
+
+    render_bootstrap_runs(&output.value.runs);
+    Ok(())
+}
+
+/// Prints bootstrap progress as it happens; reruns have no launch phase to
+/// batch output behind.
+struct LivePrinter;
+
+impl Reporter for LivePrinter {
+    fn report(&mut self, event: Event) {
+        render_bootstrap_event(&event);
+    }
+}
+
+fn handle_history(
+    args: BootstrapHistoryArgs,
+    config_override: Option<&PathBuf>,
+    profile: Option<&str>,
+) -> Result<()> {
+    let options = BootstrapHistoryOptions {
+        config: config_load_options(config_override, profile, args.skip_discovery, "bootstrap")?,
+        workspace: args.workspace,
+        vms: args.vms,
+    };
+    let output = operations::bootstrap_history(options)?;
+    emit_diagnostics(&output.diagnostics);
+    print!("{}", render_history(&output.value, unix_now()));
+    Ok(())
+}
+
+fn handle_show(
+    args: BootstrapShowArgs,
+    config_override: Option<&PathBuf>,
+    profile: Option<&str>,
+) -> Result<()> {
+    let options = BootstrapShowOptions {
+        config: config_load_options(config_override, profile, args.skip_discovery, "bootstrap")?,
+        workspace: args.workspace,
+        run: args.run,
+    };
+    let output = operations::bootstrap_show(options)?;
+    emit_diagnostics(&output.diagnostics);
+    print!(
+        "{}",
+        render_run(&output.value.run, &output.value.log, unix_now())
+    );
+    Ok(())
+}
+
+fn handle_diff(
+    args: BootstrapDiffArgs,
+    config_override: Option<&PathBuf>,
+    profile: Option<&str>,
+) -> Result<()> {
+    let options = BootstrapDiffOptions {
+        config: config_load_options(config_override, profile, args.skip_discovery, "bootstrap")?,
+        workspace: args.workspace,
+        before: args.before,
+        after: args.after,
+    };
+    let output = operations::bootstrap_diff(options)?;
+    emit_diagnostics(&output.diagnostics);
+    print!("{}", render_diff(&output.value));
+    Ok(())
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+}
+
+fn format_when(timestamp: u64, now: u64) -> String {
+    let age = Duration::from_secs(now.saturating_sub(timestamp));
+    format!("{} ago", format_stamp_age(age))
+}
+
+fn format_delta_ms(before: u64, after: u64) -> String {
+    if after >= before {
+        format!("+{}", format_duration_ms(after - before))
+    } else {
+        format!("-{}", format_duration_ms(before - after))
+    }
+}
+
+fn format_stage(stage: Option<&str>) -> String {
+    stage.map(|stage| format!("[{stage}] ")).unwrap_or_default()
+}
+
+fn commit_note(commit: Option<&str>) -> String {
+    commit
+        .map(|commit| format!("; git commit {commit}"))
+        .unwrap_or_default()
+}
+
+fn render_history(outcome: &BootstrapHistoryOutcome, now: u64) -> String {
+    let mut out = String::new();
+    if outcome.runs.is_empty() {
+        writeln!(
+            out,
+            "No bootstrap runs recorded under {}.",
+            outcome.log_root.join("bootstrap").display()
+        )
+        .unwrap();
+        return out;
+    }
+
+    let rows: Vec<[String; 5]> = outcome
+        .runs
+        .iter()
+        .map(|run| {
+            [
+                run.id.clone(),
+                run.vm.clone(),
+                run.status.clone(),
+                format_duration_ms(run.duration_ms),
+                format_when(run.timestamp, now),
+            ]
+        })
+        .collect();
+    let header = ["RUN", "VM", "STATUS", "DURATION", "WHEN"];
+    let widths: Vec<usize> = (0..header.len())
+        .map(|column| {
+            rows.iter()
+                .map(|row| row[column].chars().count())
+                .chain([header[column].len()])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    for row in [header.map(str::to_string)].iter().chain(&rows) {
+        let line: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect();
+        writeln!(out, "{}", line.join("  ").trim_end()).unwrap();
+    }
+    out
+}
+
+fn render_run(run: &BootstrapRunSummary, log: &BootstrapRunLog, now: u64) -> String {
+    let mut out = String::new();
+    writeln!(
+        out,
+        "Run {} ({}): {} in {}, {}.",
+        run.id,
+        run.vm,
+        log.status,
+        format_duration_ms(log.duration_ms),
+        format_when(run.timestamp, now)
+    )
+    .unwrap();
+    writeln!(out, "   log: {}", run.path.display()).unwrap();
+    if let Some(stamp) = &log.stamp {
+        writeln!(out, "   stamp: {stamp}").unwrap();
+    }
+    writeln!(
+        out,
+        "   artifact: {} (base image {})",
+        hash_snippet(&log.artifact_hash),
+        hash_snippet(&log.base_hash)
+    )
+    .unwrap();
+    if log.stages.is_empty() {
+        write!(out, "   script: {}", log.script_source).unwrap();
+        if let Some(sha) = &log.script_sha256 {
+            write!(out, " (sha256 {})", hash_snippet(sha)).unwrap();
+        }
+        out.push('\n');
+        if let Some(payload) = &log.payload_source {
+            writeln!(
+                out,
+                "   payload: {payload} ({} files, {}{})",
+                log.payload_files.len(),
+                format_bytes(log.payload_bytes),
+                commit_note(log.payload_commit.as_deref())
+            )
+            .unwrap();
+        }
+    } else {
+        writeln!(out, "   stages:").unwrap();
+        for stage in &log.stages {
+            write!(
+                out,
+                "     {}: {} (run_if {}); script {}",
+                stage.name, stage.status, stage.run_if, stage.script_source
+            )
+            .unwrap();
+            if let Some(sha) = &stage.script_sha256 {
+                write!(out, " (sha256 {})", hash_snippet(sha)).unwrap();
+            }
+            out.push('\n');
+            if let Some(payload) = &stage.payload_source {
+                writeln!(
+                    out,
+                    "       payload: {payload} ({} files, {}{})",
+                    stage.payload_files.len(),
+                    format_bytes(stage.payload_bytes),
+                    commit_note(stage.payload_commit.as_deref())
+                )
+                .unwrap();
+            }
+        }
+    }
+    writeln!(
+        out,
+        "   ssh: {}@{}:{}",
+        log.ssh_user, log.ssh_host, log.ssh_port
+    )
+    .unwrap();
+    if !log.env.is_empty() {
+        let env: Vec<String> = log
+            .env
+            .iter()
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect();
+        writeln!(out, "   env: {}", env.join(", ")).unwrap();
+    }
+    if !log.env_secrets.is_empty() {
+        let keys: Vec<&str> = log.env_secrets.keys().map(String::as_str).collect();
+        writeln!(out, "   secrets: {}", keys.join(", ")).unwrap();
+    }
+    writeln!(out, "   steps:").unwrap();
+    for step in &log.steps {
+        write!(
+            out,
+            "     {}{}: {} in {}",
+            format_stage(step.stage.as_deref()),
+            step.step,
+            step.status,
+            format_duration_ms(step.duration_ms)
+        )
+        .unwrap();
+        if let Some(attempts) = step.attempts {
+            write!(out, " over {attempts} attempts").unwrap();
+        }
+        if let Some(skipped) = step.bytes_skipped {
+            write!(out, " ({} unchanged)", format_bytes(skipped)).unwrap();
+        }
+        if let Some(detail) = &step.detail {
+            write!(out, " — {detail}").unwrap();
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn render_diff(outcome: &BootstrapDiffOutcome) -> String {
+    let BootstrapDiffOutcome {
+        before,
+        after,
+        diff,
+    } = outcome;
+    let mut out = String::new();
+    writeln!(
+        out,
+        "{} ({}, {}) → {} ({}, {}): {}.",
+        before.id,
+        before.status,
+        format_duration_ms(diff.before_ms),
+        after.id,
+        after.status,
+        format_duration_ms(diff.after_ms),
+        format_delta_ms(diff.before_ms, diff.after_ms)
+    )
+    .unwrap();
+    if diff.base_hash_changed {
+        writeln!(out, "   base image changed.").unwrap();
+    }
+    if !diff.artifact_hash_changed {
+        writeln!(out, "   bootstrap inputs unchanged.").unwrap();
+    }
+
+    for script in &diff.scripts {
+        let stage = format_stage(script.stage.as_deref());
+        match (&script.before, &script.after) {
+            (Some(old), Some(new)) => writeln!(
+                out,
+                "   {stage}script changed: {} → {}",
+                hash_snippet(old),
+                hash_snippet(new)
+            ),
+            (None, _) => writeln!(out, "   {stage}stage added."),
+            (_, None) => writeln!(out, "   {stage}stage removed."),
+        }
+        .unwrap();
+    }
+
+    if !diff.payload.is_empty() {
+        writeln!(out, "   payload:").unwrap();
+        for change in &diff.payload {
+            writeln!(
+                out,
+                "     {} {}{}",
+                change_marker(change.change),
+                format_stage(change.stage.as_deref()),
+                change.path
+            )
+            .unwrap();
+        }
+    }
+
+    if !diff.env.is_empty() {
+        writeln!(out, "   env:").unwrap();
+        for change in &diff.env {
+            let marker = change_marker(change.change);
+            if change.secret {
+                writeln!(out, "     {marker} {} (secret)", change.key).unwrap();
+                continue;
+            }
+            match (&change.before, &change.after) {
+                (Some(old), Some(new)) => {
+                    writeln!(out, "     {marker} {}: {old} → {new}", change.key)
+                }
+                (_, Some(value)) | (Some(value), None) => {
+                    writeln!(out, "     {marker} {}={value}", change.key)
+                }
+                (None, None) => writeln!(out, "     {marker} {}", change.key),
+            }
+            .unwrap();
+        }
+    }
+
+    writeln!(out, "   steps:").unwrap();
+    for step in &diff.steps {
+        let name = format!("{}{}", format_stage(step.stage.as_deref()), step.step);
+        match (step.before_ms, step.after_ms) {
+            (Some(old), Some(new)) => writeln!(
+                out,
+                "     {name}: {} → {} ({}){}",
+                format_duration_ms(old),
+                format_duration_ms(new),
+                format_delta_ms(old, new),
+                if step.regressed() { "  ← slower" } else { "" }
+            ),
+            (None, Some(new)) => writeln!(out, "     {name}: new, {}", format_duration_ms(new)),
+            (Some(old), None) => writeln!(
+                out,
+                "     {name}: not run (was {})",
+                format_duration_ms(old)
+            ),
+            (None, None) => Ok(()),
+        }
+        .unwrap();
+    }
+    out
+}
+
+fn change_marker(change: Change) -> char {
+    match change {
+        Change::Added => '+',
+        Change::Removed => '-',
+        Change::Modified => '~',
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::history::{BootstrapRunDiff, EnvChange, PayloadChange, StepTiming};
+
+    fn summary(id: &str, status: &str) -> BootstrapRunSummary {
+        BootstrapRunSummary {
+            id: id.to_string(),
+            vm: "web".to_string(),
+            timestamp: 1_000,
+            status: status.to_string(),
+            duration_ms: 0,
+            path: PathBuf::from(format!("logs/bootstrap/{id}.json")),
+        }
+    }
+
+    #[test]
+    fn render_diff_flags_changes_and_slow_steps() {
+        let outcome = BootstrapDiffOutcome {
+            before: summary("web-100", "success"),
+            after: summary("web-200", "failed"),
+            diff: BootstrapRunDiff {
+                artifact_hash_changed: true,
+                base_hash_changed: false,
+                scripts: Vec::new(),
+                payload: vec![PayloadChange {
+                    stage: Some("deps".to_string()),
+                    path: "requirements.txt".to_string(),
+                    change: Change::Modified,
+                }],
+                env: vec![EnvChange {
+                    key: "MODE".to_string(),
+                    change: Change::Modified,
+                    secret: false,
+                    before: Some("dev".to_string()),
+                    after: Some("prod".to_string()),
+                }],
+                steps: vec![
+                    StepTiming {
+                        stage: Some("deps".to_string()),
+                        step: "apply".to_string(),
+                        before_ms: Some(20_000),
+                        after_ms: Some(45_000),
+                    },
+                    StepTiming {
+                        stage: None,
+                        step: "verify".to_string(),
+                        before_ms: Some(1_000),
+                        after_ms: None,
+                    },
+                ],
+                before_ms: 21_000,
+                after_ms: 45_000,
+            },
+        };
+        let rendered = render_diff(&outcome);
+        assert_eq!(
+            rendered,
+            "web-100 (success, 21s) → web-200 (failed, 45s): +24s.\n\
+             \x20  payload:\n\
+             \x20    ~ [deps] requirements.txt\n\
+             \x20  env:\n\
+             \x20    ~ MODE: dev → prod\n\
+             \x20  steps:\n\
+             \x20    [deps] apply: 20s → 45s (+25s)  ← slower\n\
+             \x20    verify: not run (was 1s)\n"
+        );
+    }
+}
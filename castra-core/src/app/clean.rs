@@ -2,6 +2,7 @@ use std::path::PathBuf;
 
 use crate::Result;
 use crate::cli::CleanArgs;
+use crate::core::image_store::default_image_store_root;
 use crate::core::operations;
 use crate::core::options::{CleanOptions, CleanScope, ProjectSelector};
 use crate::core::outcome::{CleanOutcome, CleanupAction, SkipReason};
@@ -9,16 +10,22 @@ use crate::core::project::{default_projects_root, format_config_warnings};
 
 use super::common::{config_load_options, emit_diagnostics, split_config_warnings};
 
-pub fn handle_clean(args: CleanArgs, config_override: Option<&PathBuf>) -> Result<()> {
+pub fn handle_clean(
+    args: CleanArgs,
+    config_override: Option<&PathBuf>,
+    profile: Option<&str>,
+) -> Result<()> {
     let scope = if args.global {
         CleanScope::Global {
             projects_root: default_projects_root(),
+            image_store_root: default_image_store_root(),
         }
     } else {
         let selector = if let Some(root) = args.state_root.clone() {
             ProjectSelector::StateRoot(root)
         } else {
-            let config = config_load_options(config_override, args.skip_discovery, "clean")?;
+            let config =
+                config_load_options(config_override, profile, args.skip_discovery, "clean")?;
             ProjectSelector::Config(config)
         };
         CleanScope::Workspace(selector)
@@ -45,7 +52,7 @@ pub fn handle_clean(args: CleanArgs, config_override: Option<&PathBuf>) -> Resul
 }
 
 fn render_clean(outcome: &CleanOutcome) {
-    if outcome.state_roots.is_empty() {
+    if outcome.state_roots.is_empty() && outcome.image_store.is_none() {
         if outcome.dry_run {
             println!("Dry run complete; no matching state roots found.");
         } else {
@@ -67,31 +74,16 @@ fn render_clean(outcome: &CleanOutcome) {
         }
         println!("  Reclaimed: {}", format_bytes(cleanup.reclaimed_bytes));
         total_reclaimed += cleanup.reclaimed_bytes;
-        if cleanup.actions.is_empty() {
-            println!("  Actions: none");
-        } else {
-            println!("  Actions:");
-            for action in &cleanup.actions {
-                match action {
-                    CleanupAction::Removed { path, bytes, kind } => {
-                        println!(
-                            "    removed {:<15} {} ({})",
-                            kind.describe(),
-                            path.display(),
-                            format_bytes(*bytes)
-                        );
-                    }
-                    CleanupAction::Skipped { path, reason, kind } => {
-                        println!(
-                            "    skipped {:<15} {} ({})",
-                            kind.describe(),
-                            path.display(),
-                            format_skip_reason(reason)
-                        );
-                    }
-                }
-            }
-        }
+        render_actions(&cleanup.actions);
+        println!();
+    }
+
+    if let Some(store) = &outcome.image_store {
+        println!("Image store: {}", store.root.display());
+        println!("  Reclaimed: {}", format_bytes(store.reclaimed_bytes));
+        println!("  Retained: {} referenced image(s)", store.retained);
+        total_reclaimed += store.reclaimed_bytes;
+        render_actions(&store.actions);
         println!();
     }
 
@@ -102,6 +94,34 @@ fn render_clean(outcome: &CleanOutcome) {
     );
 }
 
+fn render_actions(actions: &[CleanupAction]) {
+    if actions.is_empty() {
+        println!("  Actions: none");
+        return;
+    }
+    println!("  Actions:");
+    for action in actions {
+        match action {
+            CleanupAction::Removed { path, bytes, kind } => {
+                println!(
+                    "    removed {:<15} {} ({})",
+                    kind.describe(),
+                    path.display(),
+                    format_bytes(*bytes)
+                );
+            }
+            CleanupAction::Skipped { path, reason, kind } => {
+                println!(
+                    "    skipped {:<15} {} ({})",
+                    kind.describe(),
+                    path.display(),
+                    format_skip_reason(reason)
+                );
+            }
+        }
+    }
+}
+
 fn format_bytes(bytes: u64) -> String {
     const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
     let mut value = bytes as f64;
@@ -0,0 +1,86 @@
+use std::path::PathBuf;
+
+use crate::cli::CollectArgs;
+use crate::core::events::Event;
+use crate::core::operations;
+use crate::core::options::CollectOptions;
+use crate::core::project::format_config_warnings;
+use crate::{Error, Result};
+
+use super::common::{config_load_options, emit_diagnostics, split_config_warnings};
+use super::up::format_bytes;
+
+pub fn handle_collect(
+    args: CollectArgs,
+    config_override: Option<&PathBuf>,
+    profile: Option<&str>,
+) -> Result<()> {
+    let CollectArgs {
+        skip_discovery,
+        workspace,
+        vms,
+    } = args;
+
+    let options = CollectOptions {
+        config: config_load_options(config_override, profile, skip_discovery, "collect")?,
+        workspace,
+        vms,
+    };
+
+    let output = operations::collect(options, None)?;
+
+    let (config_warnings, other) = split_config_warnings(&output.diagnostics);
+    if let Some(message) = format_config_warnings(&config_warnings) {
+        eprint!("{message}");
+    }
+    emit_diagnostics(&other);
+
+    for event in &output.events {
+        render_artifact_event(event);
+    }
+
+    let failed: Vec<String> = output
+        .value
+        .artifacts
+        .iter()
+        .filter(|artifact| artifact.error.is_some())
+        .map(|artifact| format!("{}:{}", artifact.vm, artifact.guest))
+        .collect();
+    if let Some(first) = output
+        .value
+        .artifacts
+        .iter()
+        .find(|artifact| artifact.error.is_some())
+    {
+        return Err(Error::CollectFailed {
+            vm: first.vm.clone(),
+            message: format!("could not collect {}.", failed.join(", ")),
+        });
+    }
+
+    Ok(())
+}
+
+/// Render `collect` results shared by `up`, `bootstrap`, `down` and `collect`.
+pub(super) fn render_artifact_event(event: &Event) {
+    match event {
+        Event::ArtifactsCollected {
+            vm,
+            guest,
+            path,
+            files,
+            bytes,
+        } => {
+            println!(
+                "→ {vm}: collected {guest} → {} ({} file(s), {}).",
+                path.display(),
+                files.len(),
+                format_bytes(*bytes)
+            );
+        }
+        Event::ArtifactCollectionFailed { vm, guest, error } => {
+            eprintln!("Warning: could not collect {guest} from `{vm}`: {error}");
+        }
+        _ => {}
+    }
+}
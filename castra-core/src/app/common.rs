@@ -13,6 +13,7 @@ pub fn config_source(config_override: Option<&PathBuf>) -> ConfigSource {
 
 pub fn config_load_options(
     config_override: Option<&PathBuf>,
+    profile: Option<&str>,
     skip_discovery: bool,
     command: &'static str,
 ) -> Result<ConfigLoadOptions> {
@@ -20,13 +21,15 @@ pub fn config_load_options(
         return Err(Error::SkipDiscoveryRequiresConfig { command });
     }
 
+    let profile = profile.map(ToString::to_string);
     match config_override.map(|p| ConfigSource::Explicit(p.clone())) {
         Some(source) => Ok(ConfigLoadOptions {
             source,
             allow_synthetic: !skip_discovery,
             search_root: None,
+            profile,
         }),
-        None => Ok(ConfigLoadOptions::discover(!skip_discovery)),
+        None => Ok(ConfigLoadOptions::discover(!skip_discovery).with_profile(profile)),
     }
 }
 
@@ -76,7 +79,7 @@ mod tests {
 
     #[test]
     fn skip_discovery_requires_explicit_config() {
-        let err = config_load_options(None, true, "status").unwrap_err();
+        let err = config_load_options(None, None, true, "status").unwrap_err();
         match err {
             Error::SkipDiscoveryRequiresConfig { command } => {
                 assert_eq!(command, "status");
@@ -87,7 +90,7 @@ mod tests {
 
     #[test]
     fn clean_skip_discovery_requires_explicit_config() {
-        let err = config_load_options(None, true, "clean").unwrap_err();
+        let err = config_load_options(None, None, true, "clean").unwrap_err();
         match err {
             Error::SkipDiscoveryRequiresConfig { command } => {
                 assert_eq!(command, "clean");
@@ -99,18 +102,21 @@ mod tests {
     #[test]
     fn explicit_config_passthrough() {
         let path = PathBuf::from("castra.toml");
-        let opts = config_load_options(Some(&path), true, "up").expect("config options");
+        let opts =
+            config_load_options(Some(&path), Some("ci"), true, "up").expect("config options");
         match &opts.source {
             ConfigSource::Explicit(explicit) => assert_eq!(explicit, &path),
             _ => panic!("expected explicit source"),
         }
         assert!(!opts.allow_synthetic);
+        assert_eq!(opts.profile.as_deref(), Some("ci"));
     }
 
     #[test]
     fn discovery_allowed_when_skip_disabled() {
-        let opts = config_load_options(None, false, "ports").expect("config options");
+        let opts = config_load_options(None, None, false, "ports").expect("config options");
         assert!(matches!(opts.source, ConfigSource::Discover));
         assert!(opts.allow_synthetic);
+        assert!(opts.profile.is_none());
     }
 }
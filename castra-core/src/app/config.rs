@@ -0,0 +1,452 @@
+use std::fs;
+use std::path::PathBuf;
+
+use serde_json::json;
+
+use crate::cli::{
+    ConfigArgs, ConfigCommands, ConfigMigrateArgs, ConfigSchemaArgs, ConfigShowArgs,
+    ConfigValidateArgs,
+};
+use crate::core::diagnostics::{Diagnostic, Severity};
+use crate::core::operations;
+use crate::core::options::{ConfigMigrateOptions, ConfigShowOptions, ConfigValidateOptions};
+use crate::core::outcome::{ConfigShowOutcome, ConfigValidateOutcome};
+use crate::core::project::format_config_warnings;
+use crate::{Error, Result};
+use castra::{BaseImageProvenance, PayloadSource, ProjectConfig, VmDefinition, config_json_schema};
+
+use super::common::{config_load_options, emit_diagnostics, split_config_warnings};
+
+pub fn handle_config(
+    args: ConfigArgs,
+    config_override: Option<&PathBuf>,
+    profile: Option<&str>,
+) -> Result<()> {
+    match args.command {
+        ConfigCommands::Show(show) => handle_config_show(show, config_override, profile),
+        ConfigCommands::Validate(validate) => {
+            handle_config_validate(validate, config_override, profile)
+        }
+        ConfigCommands::Schema(schema) => handle_config_schema(schema),
+        ConfigCommands::Migrate(migrate) => handle_config_migrate(migrate, config_override),
+    }
+}
+
+fn handle_config_migrate(args: ConfigMigrateArgs, config_override: Option<&PathBuf>) -> Result<()> {
+    let options = ConfigMigrateOptions {
+        config: config_load_options(config_override, None, args.skip_discovery, "config migrate")?,
+        write: args.write,
+    };
+    let output = operations::config_migrate(options)?;
+    let outcome = &output.value;
+    let path = outcome.config_path.display();
+
+    if outcome.changes.is_empty() {
+        println!("{path} is already up to date.");
+        return Ok(());
+    }
+
+    if outcome.written {
+        println!("Migrated {path}:");
+    } else {
+        print!(
+            "{}",
+            unified_diff(&outcome.original, &outcome.migrated, &path.to_string())
+        );
+        println!();
+        println!("Pending changes for {path}:");
+    }
+    for change in &outcome.changes {
+        println!("  • {change}");
+    }
+    emit_diagnostics(&output.diagnostics);
+    if !outcome.written {
+        println!("Re-run with `castra config migrate --write` to apply.");
+    }
+    Ok(())
+}
+
+fn handle_config_schema(args: ConfigSchemaArgs) -> Result<()> {
+    let rendered = serde_json::to_string_pretty(&config_json_schema())
+        .expect("config schema serializes to JSON");
+    match args.output {
+        Some(path) => {
+            fs::write(&path, format!("{rendered}\n")).map_err(|source| Error::WriteConfig {
+                path: path.clone(),
+                source,
+            })?;
+            println!("Wrote castra.toml schema to {}.", path.display());
+        }
+        None => println!("{rendered}"),
+    }
+    Ok(())
+}
+
+fn handle_config_show(
+    args: ConfigShowArgs,
+    config_override: Option<&PathBuf>,
+    profile: Option<&str>,
+) -> Result<()> {
+    let options = ConfigShowOptions {
+        config: config_load_options(config_override, profile, args.skip_discovery, "config show")?,
+    };
+    let output = operations::config_show(options)?;
+
+    let (config_warnings, other) = split_config_warnings(&output.diagnostics);
+    if let Some(message) = format_config_warnings(&config_warnings) {
+        eprint!("{message}");
+    }
+    emit_diagnostics(&other);
+
+    if args.resolved {
+        render_resolved(&output.value);
+    } else {
+        match &output.value.source {
+            Some(source) => print!("{source}"),
+            None => println!(
+                "No castra.toml found; showing the synthetic defaults requires `castra config show --resolved`."
+            ),
+        }
+    }
+    Ok(())
+}
+
+fn handle_config_validate(
+    args: ConfigValidateArgs,
+    config_override: Option<&PathBuf>,
+    profile: Option<&str>,
+) -> Result<()> {
+    let options = ConfigValidateOptions {
+        config: config_load_options(
+            config_override,
+            profile,
+            args.skip_discovery,
+            "config validate",
+        )?,
+    };
+    let outcome = operations::config_validate(options)?.value;
+
+    if args.json {
+        println!("{}", render_validate_json(&outcome));
+    } else {
+        render_validate(&outcome);
+    }
+
+    if outcome.valid {
+        Ok(())
+    } else {
+        let errors = outcome
+            .issues
+            .iter()
+            .filter(|issue| issue.severity == Severity::Error)
+            .count();
+        Err(Error::InvalidConfig {
+            path: outcome.config_path,
+            message: format!("{errors} error(s) reported"),
+        })
+    }
+}
+
+fn render_resolved(outcome: &ConfigShowOutcome) {
+    let project = &outcome.project;
+    println!(
+        "Project: {} (version {})",
+        project.project_name, project.version
+    );
+    if outcome.synthetic {
+        println!("Config: synthetic defaults (no castra.toml found)");
+    } else {
+        println!("Config: {}", outcome.config_path.display());
+    }
+    if let Some(profile) = &outcome.profile {
+        println!("Profile: {profile}");
+    }
+    println!("State root: {}", project.state_root.display());
+    render_project_defaults(project);
+    println!();
+    println!("VMs ({}):", project.vms.len());
+    for vm in &project.vms {
+        render_vm(vm);
+    }
+}
+
+fn render_project_defaults(project: &ProjectConfig) {
+    println!(
+        "Bootstrap: mode {}, handshake {}s, remote dir {}, transport {}{}",
+        project.bootstrap.mode.as_str(),
+        project.bootstrap.handshake_timeout_secs,
+        project.bootstrap.remote_dir.display(),
+        project.bootstrap.transport.as_str(),
+        if project.bootstrap.bake { ", bake" } else { "" }
+    );
+    println!(
+        "Lifecycle: graceful {}s, sigterm {}s, sigkill {}s",
+        project.lifecycle.graceful_wait().as_secs(),
+        project.lifecycle.sigterm_wait().as_secs(),
+        project.lifecycle.sigkill_wait().as_secs()
+    );
+}
+
+fn render_vm(vm: &VmDefinition) {
+    println!();
+    println!(
+        "  {} (role {}, replica {})",
+        vm.name, vm.role_name, vm.replica_index
+    );
+    if let Some(description) = &vm.description {
+        println!("    description: {description}");
+    }
+    let provenance = match vm.base_image.provenance() {
+        BaseImageProvenance::Explicit => "explicit",
+        BaseImageProvenance::DefaultAlpine => "default alpine",
+    };
+    println!(
+        "    base image:  {} ({provenance})",
+        vm.base_image.path().display()
+    );
+    println!("    overlay:     {}", vm.overlay.display());
+    println!("    cpus:        {}", vm.cpus);
+    println!("    memory:      {}", vm.memory.original());
+    if vm.port_forwards.is_empty() {
+        println!("    forwards:    —");
+    } else {
+        let forwards: Vec<String> = vm
+            .port_forwards
+            .iter()
+            .map(|forward| format!("{}→{}/{}", forward.host, forward.guest, forward.protocol))
+            .collect();
+        println!("    forwards:    {}", forwards.join(", "));
+    }
+    if !vm.depends_on.is_empty() {
+        let dependencies: Vec<String> = vm
+            .depends_on
+            .iter()
+            .map(|dependency| format!("{} ({})", dependency.vm, dependency.condition))
+            .collect();
+        println!("    depends on:  {}", dependencies.join(", "));
+    }
+
+    let bootstrap = &vm.bootstrap;
+    println!(
+        "    bootstrap:   mode {}, handshake {}s{}",
+        bootstrap.mode.as_str(),
+        bootstrap.handshake_timeout_secs,
+        if bootstrap.bake { ", bake" } else { "" }
+    );
+    for stage in &bootstrap.stages {
+        println!(
+            "      stage:   {} → {} (run_if {})",
+            stage.name,
+            stage.script.display(),
+            stage.run_if.as_str()
+        );
+    }
+    if bootstrap.stages.is_empty() {
+        println!(
+            "      script:  {}",
+            bootstrap
+                .script
+                .as_ref()
+                .map(|path| path.display().to_string())
+                .unwrap_or_else(|| "—".to_string())
+        );
+        println!(
+            "      payload: {}",
+            bootstrap
+                .payload
+                .as_ref()
+                .map(PayloadSource::describe)
+                .unwrap_or_else(|| "—".to_string())
+        );
+    }
+    println!("      remote:  {}", bootstrap.remote_dir.display());
+    if !bootstrap.env.is_empty() {
+        let mut keys: Vec<&String> = bootstrap.env.keys().collect();
+        keys.sort();
+        let keys: Vec<&str> = keys.into_iter().map(String::as_str).collect();
+        println!("      env:     {}", keys.join(", "));
+    }
+    if !bootstrap.env_secrets.is_empty() {
+        let mut secrets: Vec<String> = bootstrap
+            .env_secrets
+            .iter()
+            .map(|(key, source)| format!("{key} ({})", source.describe()))
+            .collect();
+        secrets.sort();
+        println!("      secrets: {}", secrets.join(", "));
+    }
+    if let Some(verify) = &bootstrap.verify {
+        if let Some(command) = &verify.command {
+            println!("      verify:  {command}");
+        }
+        if let Some(path) = &verify.path {
+            println!("      verify:  {}", path.display());
+        }
+    }
+    if let Some(provision) = &vm.provision {
+        println!(
+            "    provision:   {} package(s), {} file(s), {} user(s), {} service(s), {} sysctl(s)",
+            provision.packages.len(),
+            provision.files.len(),
+            provision.users.len(),
+            provision.services.len(),
+            provision.sysctls.len()
+        );
+    }
+}
+
+fn render_validate(outcome: &ConfigValidateOutcome) {
+    for issue in &outcome.issues {
+        match issue.severity {
+            Severity::Error => eprintln!("Error: {}", issue.message),
+            Severity::Warning => eprintln!("Warning: {}", issue.message),
+            Severity::Info => println!("{}", issue.message),
+        }
+        if let Some(help) = &issue.help {
+            eprintln!("         {help}");
+        }
+    }
+    if outcome.valid {
+        let warnings = outcome
+            .issues
+            .iter()
+            .filter(|issue| issue.severity == Severity::Warning)
+            .count();
+        println!(
+            "{} is valid ({warnings} warning(s)).",
+            outcome.config_path.display()
+        );
+    }
+}
+
+fn render_validate_json(outcome: &ConfigValidateOutcome) -> serde_json::Value {
+    json!({
+        "config": outcome.config_path,
+        "profile": outcome.profile,
+        "valid": outcome.valid,
+        "diagnostics": outcome.issues.iter().map(diagnostic_json).collect::<Vec<_>>(),
+    })
+}
+
+fn diagnostic_json(diagnostic: &Diagnostic) -> serde_json::Value {
+    let severity = match diagnostic.severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Info => "info",
+    };
+    json!({
+        "severity": severity,
+        "message": diagnostic.message,
+        "path": diagnostic.path,
+        "help": diagnostic.help,
+    })
+}
+
+const DIFF_CONTEXT: usize = 3;
+
+/// Line-based unified diff of `old` against `new`.
+fn unified_diff(old: &str, new: &str, label: &str) -> String {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // Longest common subsequence table, filled from the end.
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    // (tag, old index, new index, line)
+    let mut ops = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            ops.push((' ', i, j, old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            ops.push(('-', i, j, old[i]));
+            i += 1;
+        } else {
+            ops.push(('+', i, j, new[j]));
+            j += 1;
+        }
+    }
+
+    let mut out = format!("--- {label}\n+++ {label} (migrated)\n");
+    let mut idx = 0;
+    while idx < ops.len() {
+        let Some(first_change) = ops[idx..].iter().position(|op| op.0 != ' ') else {
+            break;
+        };
+        let start = (idx + first_change).saturating_sub(DIFF_CONTEXT);
+        let mut end = idx + first_change;
+        let mut last_change = end;
+        while end < ops.len() && end <= last_change + 2 * DIFF_CONTEXT {
+            if ops[end].0 != ' ' {
+                last_change = end;
+            }
+            end += 1;
+        }
+        let end = (last_change + DIFF_CONTEXT + 1).min(ops.len());
+        let hunk = &ops[start..end];
+        let old_len = hunk.iter().filter(|op| op.0 != '+').count();
+        let new_len = hunk.iter().filter(|op| op.0 != '-').count();
+        out.push_str(&format!(
+            "@@ -{},{old_len} +{},{new_len} @@\n",
+            hunk[0].1 + 1,
+            hunk[0].2 + 1
+        ));
+        for (tag, _, _, line) in hunk {
+            out.push_str(&format!("{tag}{line}\n"));
+        }
+        idx = end;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unified_diff_groups_changes_into_hunks() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\n";
+        let new = "a\nB\nc\nd\ne\nf\ng\nh\ni\nj\nk\nl\nm\n";
+        let diff = unified_diff(old, new, "castra.toml");
+        assert_eq!(
+            diff,
+            "--- castra.toml\n+++ castra.toml (migrated)\n\
+             @@ -1,5 +1,5 @@\n a\n-b\n+B\n c\n d\n e\n\
+             @@ -10,3 +10,4 @@\n j\n k\n l\n+m\n"
+        );
+    }
+
+    #[test]
+    fn validate_json_lists_diagnostics_with_severity() {
+        let outcome = ConfigValidateOutcome {
+            config_path: PathBuf::from("/work/castra.toml"),
+            profile: Some("ci".to_string()),
+            valid: false,
+            issues: vec![
+                Diagnostic::new(Severity::Error, "bad port")
+                    .with_path(PathBuf::from("/work/castra.toml"))
+                    .with_help("pick another"),
+                Diagnostic::new(Severity::Warning, "unknown key"),
+            ],
+        };
+        let value = render_validate_json(&outcome);
+        assert_eq!(value["valid"], false);
+        assert_eq!(value["profile"], "ci");
+        assert_eq!(value["diagnostics"][0]["severity"], "error");
+        assert_eq!(value["diagnostics"][0]["path"], "/work/castra.toml");
+        assert_eq!(value["diagnostics"][0]["help"], "pick another");
+        assert_eq!(value["diagnostics"][1]["severity"], "warning");
+        assert!(value["diagnostics"][1]["path"].is_null());
+    }
+}
@@ -11,9 +11,14 @@ use crate::core::options::DownOptions;
 use crate::core::project::format_config_warnings;
 use crate::{Error, Result};
 
+use super::collect::render_artifact_event;
 use super::common::{config_load_options, emit_diagnostics, split_config_warnings};
 
-pub fn handle_down(args: DownArgs, config_override: Option<&PathBuf>) -> Result<()> {
+pub fn handle_down(
+    args: DownArgs,
+    config_override: Option<&PathBuf>,
+    profile: Option<&str>,
+) -> Result<()> {
     if let Some(value) = args.sigkill_wait_secs {
         if value == 0 {
             return Err(Error::PreflightFailed {
@@ -24,7 +29,7 @@ pub fn handle_down(args: DownArgs, config_override: Option<&PathBuf>) -> Result<
     }
 
     let options = DownOptions {
-        config: config_load_options(config_override, args.skip_discovery, "down")?,
+        config: config_load_options(config_override, profile, args.skip_discovery, "down")?,
         workspace: args.workspace.clone(),
         graceful_wait: args.graceful_wait_secs.map(Duration::from_secs),
         sigterm_wait: args.sigterm_wait_secs.map(Duration::from_secs),
@@ -70,6 +75,23 @@ fn render_down(events: &[Event]) {
             Event::ShutdownRequested { vm } => {
                 println!("→ {vm}: shutdown requested.");
             }
+            Event::ArtifactsCollected { .. } | Event::ArtifactCollectionFailed { .. } => {
+                render_artifact_event(event);
+            }
+            Event::HookStarted { hook, vm, command } => {
+                println!("→ {vm}: running {hook} hook `{command}`.");
+            }
+            Event::HookCompleted {
+                hook,
+                vm,
+                duration_ms,
+                ..
+            } => {
+                println!(
+                    "→ {vm}: {hook} hook finished in {}.",
+                    format_duration_ms(*duration_ms)
+                );
+            }
             Event::CooperativeAttempted {
                 vm,
                 method,
@@ -168,7 +190,7 @@ fn render_down(events: &[Event]) {
                 reason,
             } => match reason {
                 EphemeralCleanupReason::Shutdown => println!(
-                    "→ {vm}: ephemeral changes discarded (removed {} – {}). Export via SSH before `castra down` if you need to retain data.",
+                    "→ {vm}: ephemeral changes discarded (removed {} – {}). Keep guest files with `collect = [...]` or `castra collect`.",
                     overlay_path.display(),
                     format_bytes(*reclaimed_bytes)
                 ),
@@ -22,6 +22,9 @@ pub fn exit_code(err: &Error) -> ExitCode {
         Error::LaunchFailed { .. } => ExitCode::from(70),
         Error::ShutdownFailed { .. } => ExitCode::from(70),
         Error::BootstrapFailed { .. } => ExitCode::from(70),
+        Error::CollectFailed { .. } => ExitCode::from(70),
+        Error::GuestCommandFailed { code, .. } => ExitCode::from(*code),
+        Error::HookFailed { .. } => ExitCode::from(70),
         Error::LogReadFailed { .. } => ExitCode::from(74),
         Error::Deprecated { .. } => ExitCode::from(64),
     }
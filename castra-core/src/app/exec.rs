@@ -0,0 +1,123 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::cli::ExecArgs;
+use crate::core::diagnostics::{Diagnostic, Severity};
+use crate::core::events::Event;
+use crate::core::operations;
+use crate::core::options::ExecOptions;
+use crate::core::outcome::GuestCommandResult;
+use crate::core::project::format_config_warnings;
+use crate::core::reporter::Reporter;
+use crate::core::transport::OutputStream;
+use crate::{Error, Result};
+
+use super::common::{config_load_options, emit_diagnostics, split_config_warnings};
+
+/// Exit status for a VM whose command never completed, as `ssh` uses.
+const UNREACHABLE_EXIT_CODE: u8 = 255;
+
+pub fn handle_exec(
+    args: ExecArgs,
+    config_override: Option<&PathBuf>,
+    profile: Option<&str>,
+) -> Result<()> {
+    let ExecArgs {
+        skip_discovery,
+        workspace,
+        vm,
+        vms,
+        timeout,
+        command,
+    } = args;
+
+    // A single named VM prints its output untouched, like `ssh` would.
+    let prefixed = vm.is_none() || !vms.is_empty();
+    let options = ExecOptions {
+        config: config_load_options(config_override, profile, skip_discovery, "exec")?,
+        workspace,
+        vms: vm.into_iter().chain(vms).collect(),
+        command,
+        timeout: timeout.map(Duration::from_secs),
+    };
+
+    let mut printer = OutputPrinter { prefixed };
+    let output = operations::exec(options, Some(&mut printer))?;
+
+    let (config_warnings, other) = split_config_warnings(&output.diagnostics);
+    if let Some(message) = format_config_warnings(&config_warnings) {
+        eprint!("{message}");
+    }
+    emit_diagnostics(&without_info(other));
+
+    let failed: Vec<&GuestCommandResult> = output
+        .value
+        .results
+        .iter()
+        .filter(|result| !result.success())
+        .collect();
+    let Some(first) = failed.first() else {
+        return Ok(());
+    };
+    if failed.len() > 1 {
+        for result in &failed {
+            eprintln!("{}: {}", result.vm, describe_failure(result));
+        }
+    }
+    let mut message = describe_failure(first);
+    if failed.len() > 1 {
+        message.push_str(&format!(
+            " {} of {} VMs failed.",
+            failed.len(),
+            output.value.results.len()
+        ));
+    }
+    Err(Error::GuestCommandFailed {
+        vm: first.vm.clone(),
+        code: first
+            .exit_code
+            .and_then(|code| u8::try_from(code).ok())
+            .filter(|code| *code != 0)
+            .unwrap_or(UNREACHABLE_EXIT_CODE),
+        message,
+    })
+}
+
+/// Drop informational diagnostics so stdout carries only guest output.
+pub(super) fn without_info(diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+    diagnostics
+        .into_iter()
+        .filter(|diagnostic| !matches!(diagnostic.severity, Severity::Info))
+        .collect()
+}
+
+fn describe_failure(result: &GuestCommandResult) -> String {
+    match (&result.error, result.exit_code) {
+        (Some(error), _) => error.clone(),
+        (None, Some(code)) => format!("exited with code {code}."),
+        (None, None) => "did not complete.".to_string(),
+    }
+}
+
+/// Prints guest output as it arrives, prefixed with the VM name when
+/// several VMs may be involved.
+struct OutputPrinter {
+    prefixed: bool,
+}
+
+impl Reporter for OutputPrinter {
+    fn report(&mut self, event: Event) {
+        let Event::GuestOutput { vm, stream, line } = event else {
+            return;
+        };
+        let line = if self.prefixed {
+            format!("{vm} | {line}")
+        } else {
+            line
+        };
+        match stream {
+            OutputStream::Stdout => println!("{line}"),
+            OutputStream::Stderr => eprintln!("{line}"),
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use std::path::PathBuf;
+
+use crate::Result;
+use crate::cli::{ImageArgs, ImageCommands, ImageCommitArgs};
+use crate::core::operations;
+use crate::core::options::ImageCommitOptions;
+use crate::core::outcome::ImageCommitOutcome;
+use crate::core::project::format_config_warnings;
+
+use super::common::{config_load_options, emit_diagnostics, split_config_warnings};
+
+pub fn handle_image(
+    args: ImageArgs,
+    config_override: Option<&PathBuf>,
+    profile: Option<&str>,
+) -> Result<()> {
+    match args.command {
+        ImageCommands::Commit(commit) => handle_image_commit(commit, config_override, profile),
+    }
+}
+
+fn handle_image_commit(
+    args: ImageCommitArgs,
+    config_override: Option<&PathBuf>,
+    profile: Option<&str>,
+) -> Result<()> {
+    let options = ImageCommitOptions {
+        config: config_load_options(
+            config_override,
+            profile,
+            args.skip_discovery,
+            "image commit",
+        )?,
+        vm: args.vm,
+        name: args.name,
+    };
+
+    let output = operations::image_commit(options, None)?;
+
+    let (config_warnings, other) = split_config_warnings(&output.diagnostics);
+    if let Some(message) = format_config_warnings(&config_warnings) {
+        eprint!("{message}");
+    }
+    emit_diagnostics(&other);
+
+    render_image_commit(&output.value);
+    Ok(())
+}
+
+fn render_image_commit(outcome: &ImageCommitOutcome) {
+    if outcome.stopped_vm {
+        println!("Stopped VM `{}` to capture its disk.", outcome.vm);
+    }
+    println!("Committed `{}` as image `{}`.", outcome.vm, outcome.name);
+    println!("  Digest: sha256:{}", outcome.digest);
+    println!("  Path: {}", outcome.image_path.display());
+    match &outcome.golden_tag {
+        Some(_) => println!(
+            "  The next `castra up` boots `{}` from this image and skips bootstrap while its inputs are unchanged.",
+            outcome.vm
+        ),
+        None => println!(
+            "  Set `base_image = \"{}\"` to boot from this image.",
+            outcome.image_path.display()
+        ),
+    }
+}
@@ -12,9 +12,13 @@ use crate::core::project::format_config_warnings;
 
 use super::common::{config_load_options, emit_diagnostics, split_config_warnings};
 
-pub fn handle_logs(args: LogsArgs, config_override: Option<&PathBuf>) -> Result<()> {
+pub fn handle_logs(
+    args: LogsArgs,
+    config_override: Option<&PathBuf>,
+    profile: Option<&str>,
+) -> Result<()> {
     let options = LogsOptions {
-        config: config_load_options(config_override, args.skip_discovery, "logs")?,
+        config: config_load_options(config_override, profile, args.skip_discovery, "logs")?,
         tail: args.tail,
         follow: args.follow,
     };
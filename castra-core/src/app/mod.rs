@@ -1,21 +1,33 @@
+pub mod bootstrap;
 pub mod broker;
 pub mod bus;
 pub mod clean;
+pub mod collect;
 pub mod common;
+pub mod config;
 pub mod down;
 pub mod error;
+pub mod exec;
+pub mod image;
 pub mod init;
 pub mod logs;
 pub mod ports;
+pub mod ssh;
 pub mod status;
 pub mod up;
 
+pub use bootstrap::handle_bootstrap;
 pub use broker::handle_broker;
 pub use bus::handle_bus;
 pub use clean::handle_clean;
+pub use collect::handle_collect;
+pub use config::handle_config;
 pub use down::handle_down;
+pub use exec::handle_exec;
+pub use image::handle_image;
 pub use init::handle_init;
 pub use logs::handle_logs;
 pub use ports::handle_ports;
+pub use ssh::handle_ssh;
 pub use status::handle_status;
 pub use up::handle_up;
@@ -12,9 +12,13 @@ use crate::core::project::format_config_warnings;
 
 use super::common::{config_load_options, emit_diagnostics, split_config_warnings};
 
-pub fn handle_ports(args: PortsArgs, config_override: Option<&PathBuf>) -> Result<()> {
+pub fn handle_ports(
+    args: PortsArgs,
+    config_override: Option<&PathBuf>,
+    profile: Option<&str>,
+) -> Result<()> {
     let options = PortsOptions {
-        config: config_load_options(config_override, args.skip_discovery, "ports")?,
+        config: config_load_options(config_override, profile, args.skip_discovery, "ports")?,
         verbose: args.verbose,
         view: if args.active {
             PortsView::Active
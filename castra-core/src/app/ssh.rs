@@ -0,0 +1,47 @@
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::Command;
+
+use crate::cli::SshArgs;
+use crate::core::operations;
+use crate::core::options::SshOptions;
+use crate::core::project::format_config_warnings;
+use crate::{Error, Result};
+
+use super::common::{config_load_options, emit_diagnostics, split_config_warnings};
+
+pub fn handle_ssh(
+    args: SshArgs,
+    config_override: Option<&PathBuf>,
+    profile: Option<&str>,
+) -> Result<()> {
+    let SshArgs {
+        skip_discovery,
+        workspace,
+        vm,
+        command,
+    } = args;
+
+    let options = SshOptions {
+        config: config_load_options(config_override, profile, skip_discovery, "ssh")?,
+        workspace,
+        vm,
+        command,
+    };
+
+    let output = operations::ssh(options)?;
+
+    let (config_warnings, other) = split_config_warnings(&output.diagnostics);
+    if let Some(message) = format_config_warnings(&config_warnings) {
+        eprint!("{message}");
+    }
+    emit_diagnostics(&super::exec::without_info(other));
+
+    // Replaces this process, so the session's exit status is ours.
+    let err = Command::new(&output.value.program)
+        .args(&output.value.args)
+        .exec();
+    Err(Error::PreflightFailed {
+        message: format!("Failed to run `{}`: {err}", output.value.program),
+    })
+}
@@ -1,5 +1,6 @@
 use crate::Result;
 use crate::cli::StatusArgs;
+use crate::core::bootstrap::format_stamp_age;
 use crate::core::operations;
 use crate::core::options::StatusOptions;
 use crate::core::outcome::{ProjectStatusOutcome, StatusOutcome};
@@ -8,12 +9,17 @@ use crate::core::status::format_uptime;
 use std::fmt::Write as _;
 use std::io::{self, IsTerminal};
 use std::path::PathBuf;
+use std::time::Duration;
 
 use super::common::{config_load_options, emit_diagnostics, split_config_warnings};
 
-pub fn handle_status(args: StatusArgs, config_override: Option<&PathBuf>) -> Result<()> {
+pub fn handle_status(
+    args: StatusArgs,
+    config_override: Option<&PathBuf>,
+    profile: Option<&str>,
+) -> Result<()> {
     let options = StatusOptions {
-        config: config_load_options(config_override, args.skip_discovery, "status")?,
+        config: config_load_options(config_override, profile, args.skip_discovery, "status")?,
         workspace: args.workspace.clone(),
     };
 
@@ -126,19 +132,32 @@ fn render_project_body(project: &ProjectStatusOutcome, use_color: bool) -> Strin
         .max()
         .unwrap_or(1)
         .max("UPTIME".len());
+    let bootstrapped: Vec<String> = project
+        .rows
+        .iter()
+        .map(|row| format_bootstrap_age(row.bootstrap_age))
+        .collect();
+    let bootstrapped_width = bootstrapped
+        .iter()
+        .map(|value| value.chars().count())
+        .max()
+        .unwrap_or(1)
+        .max("BOOTSTRAPPED".len());
 
     writeln!(
         out,
-        "{:<vm_width$}  {:<state_width$}  {:>cpu_mem_width$}  {:>uptime_width$}  {}",
+        "{:<vm_width$}  {:<state_width$}  {:>cpu_mem_width$}  {:>uptime_width$}  {:>bootstrapped_width$}  {}",
         "VM",
         "STATE",
         "CPU/MEM",
         "UPTIME",
+        "BOOTSTRAPPED",
         "FORWARDS",
         vm_width = vm_width,
         state_width = state_width,
         cpu_mem_width = cpu_mem_width,
         uptime_width = uptime_width,
+        bootstrapped_width = bootstrapped_width,
     )
     .unwrap();
 
@@ -146,15 +165,17 @@ fn render_project_body(project: &ProjectStatusOutcome, use_color: bool) -> Strin
         let state = style_state(&row.state, state_width, use_color);
         writeln!(
             out,
-            "{:<vm_width$}  {}  {:>cpu_mem_width$}  {:>uptime_width$}  {}",
+            "{:<vm_width$}  {}  {:>cpu_mem_width$}  {:>uptime_width$}  {:>bootstrapped_width$}  {}",
             row.name,
             state,
             cpu_mem[idx],
             format_uptime(row.uptime),
+            bootstrapped[idx],
             row.forwards,
             vm_width = vm_width,
             cpu_mem_width = cpu_mem_width,
             uptime_width = uptime_width,
+            bootstrapped_width = bootstrapped_width,
         )
         .unwrap();
     }
@@ -162,9 +183,17 @@ fn render_project_body(project: &ProjectStatusOutcome, use_color: bool) -> Strin
     out
 }
 
+fn format_bootstrap_age(age: Option<Duration>) -> String {
+    match age {
+        Some(age) => format!("{} ago", format_stamp_age(age)),
+        None => "—".to_string(),
+    }
+}
+
 fn render_status_legend() -> String {
     "Legend: STATE derives from VM pidfiles; CPU/MEM reflect configured values.\n\
-UPTIME shows hh:mm:ss based on host monotonic time; FORWARDS lists host→guest ports.\n\
+UPTIME shows hh:mm:ss based on host monotonic time; BOOTSTRAPPED shows the age of the last successful bootstrap stamp.\n\
+FORWARDS lists host→guest ports.\n\
 Exit codes: 0 on success; non-zero if any VM reports an error state.\n"
         .to_string()
 }
@@ -201,6 +230,7 @@ mod tests {
             memory: "512 MiB".to_string(),
             uptime: Some(Duration::from_secs(5)),
             forwards: "—".to_string(),
+            bootstrap_age: Some(Duration::from_secs(7_200)),
         }
     }
 
@@ -229,6 +259,8 @@ mod tests {
         assert!(rendered.contains("Project: demo (/tmp/demo.toml)"));
         assert!(!rendered.contains("=== demo"));
         assert!(rendered.contains("Guests: Running VMs detected."));
+        assert!(rendered.contains("BOOTSTRAPPED"));
+        assert!(rendered.contains("2h ago"));
     }
 
     #[test]
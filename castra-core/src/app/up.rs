@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::io::{self, IsTerminal, Write};
 use std::path::{Path, PathBuf};
 
 use crate::Error;
@@ -11,32 +12,47 @@ use crate::core::events::{
 };
 use crate::core::operations;
 use crate::core::options::{BootstrapOverrides, UpOptions, VmLaunchMode};
-use crate::core::outcome::{BootstrapRunStatus, UpOutcome};
+use crate::core::outcome::{BootstrapRunOutcome, BootstrapRunStatus, UpOutcome};
 use crate::core::project::format_config_warnings;
+use crate::core::reporter::Reporter;
 use castra::PortProtocol;
 
+use super::collect::render_artifact_event;
 use super::common::{config_load_options, emit_diagnostics, split_config_warnings};
 
-pub fn handle_up(args: UpArgs, config_override: Option<&PathBuf>) -> Result<()> {
+pub fn handle_up(
+    args: UpArgs,
+    config_override: Option<&PathBuf>,
+    profile: Option<&str>,
+) -> Result<()> {
     let UpArgs {
         skip_discovery,
         force,
         plan,
         qcow,
+        offline,
         bootstrap,
     } = args;
 
     let bootstrap_overrides = build_bootstrap_overrides(&bootstrap)?;
     let options = UpOptions {
-        config: config_load_options(config_override, skip_discovery, "up")?,
+        config: config_load_options(config_override, profile, skip_discovery, "up")?,
         force,
         bootstrap: bootstrap_overrides,
         launch_mode: VmLaunchMode::Daemonize,
         plan,
         alpine_qcow_override: qcow,
+        offline,
     };
 
-    let output = operations::up(options, None)?;
+    let mut progress = DownloadProgressBar::default();
+    let reporter: Option<&mut dyn Reporter> = if io::stderr().is_terminal() {
+        Some(&mut progress)
+    } else {
+        None
+    };
+    let output = operations::up(options, reporter)?;
+    progress.finish();
 
     let (config_warnings, other) = split_config_warnings(&output.diagnostics);
     if let Some(message) = format_config_warnings(&config_warnings) {
@@ -68,6 +84,75 @@ pub fn handle_up(args: UpArgs, config_override: Option<&PathBuf>) -> Result<()>
     Ok(())
 }
 
+/// Draws image download progress on stderr while `up` runs. Everything else is
+/// rendered from the collected events once the operation returns. Dropping it
+/// ends an unfinished bar, so an error mid-download starts on a fresh line.
+#[derive(Default)]
+struct DownloadProgressBar {
+    drawing: bool,
+}
+
+impl DownloadProgressBar {
+    fn finish(&mut self) {
+        if self.drawing {
+            eprintln!();
+            self.drawing = false;
+        }
+    }
+}
+
+impl Drop for DownloadProgressBar {
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+impl Reporter for DownloadProgressBar {
+    fn report(&mut self, event: Event) {
+        match event {
+            Event::DownloadProgress {
+                bytes,
+                total,
+                bytes_per_sec,
+                ..
+            } => {
+                let mut stderr = io::stderr();
+                let _ = write!(
+                    stderr,
+                    "\r{}",
+                    format_download_progress(bytes, total, bytes_per_sec)
+                );
+                let _ = stderr.flush();
+                self.drawing = true;
+                if total.is_some_and(|total| bytes >= total) {
+                    self.finish();
+                }
+            }
+            _ => self.finish(),
+        }
+    }
+}
+
+fn format_download_progress(bytes: u64, total: Option<u64>, bytes_per_sec: u64) -> String {
+    const WIDTH: usize = 30;
+    let rate = format!("{}/s", format_bytes(bytes_per_sec));
+    match total {
+        Some(total) if total > 0 => {
+            let ratio = (bytes as f64 / total as f64).clamp(0.0, 1.0);
+            let filled = (ratio * WIDTH as f64).round() as usize;
+            format!(
+                "[{}{}] {:>5.1}% {} / {} @ {rate}",
+                "#".repeat(filled),
+                "-".repeat(WIDTH - filled),
+                ratio * 100.0,
+                format_bytes(bytes),
+                format_bytes(total)
+            )
+        }
+        _ => format!("Downloaded {} @ {rate}", format_bytes(bytes)),
+    }
+}
+
 fn build_bootstrap_overrides(inputs: &[BootstrapOverrideArg]) -> Result<BootstrapOverrides> {
     let mut overrides = BootstrapOverrides::default();
 
@@ -120,6 +205,7 @@ fn render_up(outcome: &UpOutcome, events: &[Event]) {
                 trigger,
                 script_path,
                 payload_path,
+                payload_commit,
                 payload_bytes,
                 handshake_timeout_secs,
                 remote_dir,
@@ -129,6 +215,7 @@ fn render_up(outcome: &UpOutcome, events: &[Event]) {
                 artifact_hash,
                 metadata_path,
                 warnings,
+                stages,
                 ..
             } => {
                 let mode_text = mode.as_str();
@@ -139,13 +226,38 @@ fn render_up(outcome: &UpOutcome, events: &[Event]) {
                     BootstrapPlanAction::WouldSkip => {
                         println!("→ {}: plan would skip ({}; {}).", vm, mode_text, reason);
                     }
+                    BootstrapPlanAction::UpToDate => {
+                        println!("→ {}: plan is up-to-date ({}; {}).", vm, mode_text, reason);
+                    }
                     BootstrapPlanAction::Error => {
                         eprintln!("→ {}: plan would error ({}; {}).", vm, mode_text, reason);
                     }
                 }
 
-                if let Some(path) = script_path {
-                    println!("   script: {}", path.display());
+                if stages.is_empty() {
+                    if let Some(path) = script_path {
+                        println!("   script: {}", path.display());
+                    }
+                } else {
+                    println!("   stages:");
+                    for stage in stages {
+                        let note = match &stage.carried_over {
+                            Some(reason) => format!("carried over; {reason}"),
+                            None => format!("runs; run_if {}", stage.run_if.as_str()),
+                        };
+                        println!(
+                            "     {}: {} ({})",
+                            stage.name,
+                            stage.script_path.display(),
+                            note
+                        );
+                        if let Some(payload) = &stage.payload_path {
+                            println!("       payload: {}", payload.display());
+                        }
+                        if let Some(commit) = &stage.payload_commit {
+                            println!("       payload commit: {commit}");
+                        }
+                    }
                 }
 
                 if let Some(seconds) = handshake_timeout_secs {
@@ -176,6 +288,10 @@ fn render_up(outcome: &UpOutcome, events: &[Event]) {
                     _ => {}
                 }
 
+                if let Some(commit) = payload_commit {
+                    println!("   payload commit: {commit}");
+                }
+
                 if !env_keys.is_empty() {
                     println!("   env keys: {}", env_keys.join(", "));
                 }
@@ -241,79 +357,23 @@ fn render_up(outcome: &UpOutcome, events: &[Event]) {
                 let pidfile = outcome.state_root.join(format!("{vm}.pid"));
                 println!("→ {vm}: launched (pidfile {}).", pidfile.display());
             }
-            Event::BootstrapStarted {
-                vm,
-                base_hash,
-                artifact_hash,
-                trigger,
-            } => {
-                println!(
-                    "→ {}: bootstrap started (artifact {}, base {}) [{}].",
-                    vm,
-                    hash_snippet(artifact_hash),
-                    hash_snippet(base_hash),
-                    format_bootstrap_trigger(trigger)
-                );
+            Event::HookStarted { hook, vm, command } => {
+                println!("→ {}: running {} hook `{}`.", vm, hook, command);
             }
-            Event::BootstrapStep {
+            Event::HookCompleted {
+                hook,
                 vm,
-                step,
-                status,
-                duration_ms,
-                detail,
-            } => {
-                let duration = format_duration_ms(*duration_ms);
-                match detail {
-                    Some(text) if !text.is_empty() => println!(
-                        "   - {} {}: {} in {} ({}).",
-                        vm,
-                        format_step_kind(step),
-                        format_step_status(status),
-                        duration,
-                        text
-                    ),
-                    _ => println!(
-                        "   - {} {}: {} in {}.",
-                        vm,
-                        format_step_kind(step),
-                        format_step_status(status),
-                        duration
-                    ),
-                }
-            }
-            Event::BootstrapCompleted {
-                vm,
-                status,
                 duration_ms,
                 ..
             } => {
-                let duration = format_duration_ms(*duration_ms);
-                match status {
-                    BootstrapStatus::Success => {
-                        println!("→ {}: bootstrap completed in {}.", vm, duration);
-                    }
-                    BootstrapStatus::NoOp => {
-                        println!("→ {}: bootstrap runner reported no changes.", vm);
-                    }
-                }
-            }
-            Event::BootstrapFailed {
-                vm,
-                duration_ms,
-                error,
-            } => {
-                let duration = format_duration_ms(*duration_ms);
-                eprintln!(
-                    "Bootstrap failed for `{}` after {}: {}",
-                    vm, duration, error
+                println!(
+                    "→ {}: {} hook finished in {}.",
+                    vm,
+                    hook,
+                    format_duration_ms(*duration_ms)
                 );
             }
-            Event::Message { severity, text } => match severity {
-                Severity::Info => println!("{}", text),
-                Severity::Warning => eprintln!("Warning: {}", text),
-                Severity::Error => eprintln!("Error: {}", text),
-            },
-            _ => {}
+            event => render_bootstrap_event(event),
         }
     }
 
@@ -328,32 +388,22 @@ fn render_up(outcome: &UpOutcome, events: &[Event]) {
             .iter()
             .filter(|plan| plan.action == BootstrapPlanAction::WouldSkip)
             .count();
+        let current = outcome
+            .plans
+            .iter()
+            .filter(|plan| plan.action == BootstrapPlanAction::UpToDate)
+            .count();
         let errors = outcome
             .plans
             .iter()
             .filter(|plan| plan.action == BootstrapPlanAction::Error)
             .count();
         println!(
-            "Bootstrap plan summary: {run} would run, {skip} would skip, {errors} would error."
+            "Bootstrap plan summary: {run} would run, {skip} would skip, {current} up-to-date, {errors} would error."
         );
     }
 
-    if !outcome.bootstraps.is_empty() {
-        for run in &outcome.bootstraps {
-            match run.status {
-                BootstrapRunStatus::Success => match &run.log_path {
-                    Some(path) => println!("→ {}: bootstrap log at {}.", run.vm, path.display()),
-                    None => println!("→ {}: bootstrap completed.", run.vm),
-                },
-                BootstrapRunStatus::NoOp => {
-                    println!("→ {}: bootstrap runner reported no changes.", run.vm);
-                }
-                BootstrapRunStatus::Skipped => {
-                    println!("→ {}: bootstrap skipped.", run.vm);
-                }
-            }
-        }
-    }
+    render_bootstrap_runs(&outcome.bootstraps);
 
     if !outcome.launched_vms.is_empty() {
         let bootstrap_logs: HashMap<&str, &Path> = outcome
@@ -400,6 +450,120 @@ fn render_up(outcome: &UpOutcome, events: &[Event]) {
     }
 }
 
+/// Render bootstrap lifecycle events shared by `up` and `bootstrap`.
+pub(super) fn render_bootstrap_event(event: &Event) {
+    match event {
+        Event::BootstrapStarted {
+            vm,
+            base_hash,
+            artifact_hash,
+            trigger,
+        } => {
+            println!(
+                "→ {}: bootstrap started (artifact {}, base {}) [{}].",
+                vm,
+                hash_snippet(artifact_hash),
+                hash_snippet(base_hash),
+                format_bootstrap_trigger(trigger)
+            );
+        }
+        Event::BootstrapStep {
+            vm,
+            stage,
+            step,
+            status,
+            duration_ms,
+            detail,
+            attempts,
+            ..
+        } => {
+            let mut duration = format_duration_ms(*duration_ms);
+            if *attempts > 1 {
+                duration.push_str(&format!(" over {attempts} attempts"));
+            }
+            let vm = match stage {
+                Some(stage) => format!("{vm} [{stage}]"),
+                None => vm.clone(),
+            };
+            match detail {
+                Some(text) if !text.is_empty() => println!(
+                    "   - {} {}: {} in {} ({}).",
+                    vm,
+                    format_step_kind(step),
+                    format_step_status(status),
+                    duration,
+                    text
+                ),
+                _ => println!(
+                    "   - {} {}: {} in {}.",
+                    vm,
+                    format_step_kind(step),
+                    format_step_status(status),
+                    duration
+                ),
+            }
+        }
+        Event::BootstrapCompleted {
+            vm,
+            status,
+            duration_ms,
+            ..
+        } => {
+            let duration = format_duration_ms(*duration_ms);
+            match status {
+                BootstrapStatus::Success => {
+                    println!("→ {}: bootstrap completed in {}.", vm, duration);
+                }
+                BootstrapStatus::NoOp => {
+                    println!("→ {}: bootstrap runner reported no changes.", vm);
+                }
+            }
+        }
+        Event::BootstrapFailed {
+            vm,
+            duration_ms,
+            error,
+        } => {
+            let duration = format_duration_ms(*duration_ms);
+            eprintln!(
+                "Bootstrap failed for `{}` after {}: {}",
+                vm, duration, error
+            );
+        }
+        Event::Message { severity, text } => match severity {
+            Severity::Info => println!("{}", text),
+            Severity::Warning => eprintln!("Warning: {}", text),
+            Severity::Error => eprintln!("Error: {}", text),
+        },
+        Event::ArtifactsCollected { .. } | Event::ArtifactCollectionFailed { .. } => {
+            render_artifact_event(event);
+        }
+        _ => {}
+    }
+}
+
+/// Print the per-VM bootstrap summary (log paths, stamps, skips).
+pub(super) fn render_bootstrap_runs(runs: &[BootstrapRunOutcome]) {
+    for run in runs {
+        match run.status {
+            BootstrapRunStatus::Success => match &run.log_path {
+                Some(path) => println!("→ {}: bootstrap log at {}.", run.vm, path.display()),
+                None => println!("→ {}: bootstrap completed.", run.vm),
+            },
+            BootstrapRunStatus::NoOp if run.log_path.is_none() => match &run.stamp {
+                Some(stamp) => println!("→ {}: bootstrap up-to-date (stamp {stamp}).", run.vm),
+                None => println!("→ {}: bootstrap up-to-date.", run.vm),
+            },
+            BootstrapRunStatus::NoOp => {
+                println!("→ {}: bootstrap runner reported no changes.", run.vm);
+            }
+            BootstrapRunStatus::Skipped => {
+                println!("→ {}: bootstrap skipped.", run.vm);
+            }
+        }
+    }
+}
+
 fn format_step_kind(kind: &BootstrapStepKind) -> &'static str {
     match kind {
         BootstrapStepKind::WaitHandshake => "wait-handshake",
@@ -418,7 +582,7 @@ fn format_step_status(status: &BootstrapStepStatus) -> &'static str {
     }
 }
 
-fn format_duration_ms(ms: u64) -> String {
+pub(super) fn format_duration_ms(ms: u64) -> String {
     if ms == 0 {
         return "0s".to_string();
     }
@@ -435,7 +599,7 @@ fn format_duration_ms(ms: u64) -> String {
     }
 }
 
-fn format_bytes(bytes: u64) -> String {
+pub(super) fn format_bytes(bytes: u64) -> String {
     const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
     let mut value = bytes as f64;
     let mut unit = 0;
@@ -450,7 +614,7 @@ fn format_bytes(bytes: u64) -> String {
     }
 }
 
-fn hash_snippet(value: &str) -> String {
+pub(super) fn hash_snippet(value: &str) -> String {
     if value.len() <= 12 {
         value.to_string()
     } else {
@@ -462,6 +626,7 @@ fn format_bootstrap_trigger(trigger: &BootstrapTrigger) -> &'static str {
     match trigger {
         BootstrapTrigger::Auto => "auto",
         BootstrapTrigger::Always => "always",
+        BootstrapTrigger::Manual => "manual",
     }
 }
 
@@ -530,6 +695,19 @@ mod tests {
         }
     }
 
+    #[test]
+    fn format_download_progress_renders_bar_and_rate() {
+        let line = format_download_progress(512 * 1024, Some(1024 * 1024), 256 * 1024);
+        assert_eq!(
+            line,
+            "[###############---------------]  50.0% 512.0 KiB / 1.0 MiB @ 256.0 KiB/s"
+        );
+        assert_eq!(
+            format_download_progress(2048, None, 0),
+            "Downloaded 2.0 KiB @ 0 B/s"
+        );
+    }
+
     #[test]
     fn up_options_default_launch_mode_is_daemonize() {
         assert_eq!(UpOptions::default().launch_mode, VmLaunchMode::Daemonize);
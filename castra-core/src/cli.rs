@@ -51,6 +51,15 @@ pub struct Cli {
     )]
     pub config: Option<PathBuf>,
 
+    /// Configuration profile (`[profiles.<name>]`) to overlay on the base config.
+    #[arg(
+        global = true,
+        long = "profile",
+        value_name = "NAME",
+        help = "Apply the [profiles.NAME] overrides from castra.toml (cpus, memory, counts, images, bootstrap modes)."
+    )]
+    pub profile: Option<String>,
+
     #[command(subcommand)]
     pub command: Option<Commands>,
 }
@@ -61,6 +70,8 @@ pub enum Commands {
     Init(InitArgs),
     /// Boot the configured virtual machines.
     Up(UpArgs),
+    /// Rerun bootstrap pipelines on running VMs without restarting them.
+    Bootstrap(BootstrapArgs),
     /// Shut down running virtual machines. Emits lifecycle events (ShutdownRequested → CooperativeAttempted → CooperativeSucceeded|CooperativeTimedOut → Escalation(SIGTERM)? → Escalation(SIGKILL)? → ShutdownComplete) while attempting a QMP powerdown before signals (timeouts configurable via CLI flags or [lifecycle]).
     Down(DownArgs),
     /// Inspect the state of managed virtual machines.
@@ -69,8 +80,18 @@ pub enum Commands {
     Ports(PortsArgs),
     /// Tail orchestrator and guest logs.
     Logs(LogsArgs),
+    /// Copy `collect` paths from running VMs to the host.
+    Collect(CollectArgs),
+    /// Open an SSH session to a running VM, or run one command there.
+    Ssh(SshArgs),
+    /// Run a command on one or more running VMs and pass on its exit status.
+    Exec(ExecArgs),
     /// Reclaim cached images and workspace state safely.
     Clean(CleanArgs),
+    /// Inspect and validate the project configuration.
+    Config(ConfigArgs),
+    /// Manage images in the shared image store.
+    Image(ImageArgs),
     #[command(hide = true)]
     Bus(BusArgs),
     #[command(hide = true)]
@@ -135,6 +156,13 @@ pub struct UpArgs {
     )]
     pub qcow: Option<PathBuf>,
 
+    /// Never touch the network; fail if a required image is not cached.
+    #[arg(
+        long,
+        help = "Refuse network fetches. Fails preflight if a required image is not already cached (see --qcow and `[images] mirrors`)."
+    )]
+    pub offline: bool,
+
     /// Override bootstrap behavior without editing castra.toml.
     #[arg(
         long,
@@ -145,6 +173,259 @@ pub struct UpArgs {
     pub bootstrap: Vec<BootstrapOverrideArg>,
 }
 
+#[derive(Debug, Args, Default)]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct BootstrapArgs {
+    #[command(subcommand)]
+    pub command: Option<BootstrapCommands>,
+
+    /// Only use the explicit --config path instead of searching parent directories.
+    #[arg(
+        long,
+        help = "Skip config discovery; requires --config <PATH> (e.g. --config ./castra.toml)."
+    )]
+    pub skip_discovery: bool,
+
+    /// Operate on a specific workspace discovered via the registry.
+    #[arg(
+        long,
+        value_name = "ID",
+        conflicts_with = "skip_discovery",
+        help = "Select a workspace by ID when rerunning bootstrap on its running VMs."
+    )]
+    pub workspace: Option<String>,
+
+    /// Limit the rerun to these VMs (repeatable or comma-separated).
+    #[arg(
+        long = "vm",
+        value_name = "VM",
+        value_delimiter = ',',
+        help = "Only bootstrap the named VMs; they must already be running (repeatable or comma-separated)."
+    )]
+    pub vms: Vec<String>,
+
+    /// Limit the rerun to these pipeline stages (repeatable or comma-separated).
+    #[arg(
+        long = "stage",
+        value_name = "STAGE",
+        value_delimiter = ',',
+        help = "Only run the named bootstrap stages; other stages keep their recorded state (repeatable or comma-separated)."
+    )]
+    pub stages: Vec<String>,
+
+    /// Rerun selected stages even when their stamps are current.
+    #[arg(
+        long,
+        help = "Ignore up-to-date stamps and rerun every selected stage."
+    )]
+    pub force: bool,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum BootstrapCommands {
+    /// List recorded bootstrap runs with their status and duration.
+    History(BootstrapHistoryArgs),
+    /// Print the steps, inputs and environment of one recorded run.
+    Show(BootstrapShowArgs),
+    /// Compare two recorded runs: payload files, environment, script and step timings.
+    Diff(BootstrapDiffArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct BootstrapHistoryArgs {
+    /// Only use the explicit --config path instead of searching parent directories.
+    #[arg(
+        long,
+        help = "Skip config discovery; requires --config <PATH> (e.g. --config ./castra.toml)."
+    )]
+    pub skip_discovery: bool,
+
+    /// Operate on a specific workspace discovered via the registry.
+    #[arg(
+        long,
+        value_name = "ID",
+        conflicts_with = "skip_discovery",
+        help = "Select a workspace by ID whose bootstrap logs should be read."
+    )]
+    pub workspace: Option<String>,
+
+    /// Limit the listing to these VMs (repeatable or comma-separated).
+    #[arg(
+        long = "vm",
+        value_name = "VM",
+        value_delimiter = ',',
+        help = "Only list runs of the named VMs (repeatable or comma-separated)."
+    )]
+    pub vms: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct BootstrapShowArgs {
+    /// Only use the explicit --config path instead of searching parent directories.
+    #[arg(
+        long,
+        help = "Skip config discovery; requires --config <PATH> (e.g. --config ./castra.toml)."
+    )]
+    pub skip_discovery: bool,
+
+    /// Operate on a specific workspace discovered via the registry.
+    #[arg(
+        long,
+        value_name = "ID",
+        conflicts_with = "skip_discovery",
+        help = "Select a workspace by ID whose bootstrap logs should be read."
+    )]
+    pub workspace: Option<String>,
+
+    /// Run to print.
+    #[arg(
+        value_name = "RUN",
+        help = "Run id from `castra bootstrap history` (e.g. web-1700000000), a VM name for its latest run, or <vm>~<n> for the run n before the latest"
+    )]
+    pub run: String,
+}
+
+#[derive(Debug, Args)]
+pub struct BootstrapDiffArgs {
+    /// Only use the explicit --config path instead of searching parent directories.
+    #[arg(
+        long,
+        help = "Skip config discovery; requires --config <PATH> (e.g. --config ./castra.toml)."
+    )]
+    pub skip_discovery: bool,
+
+    /// Operate on a specific workspace discovered via the registry.
+    #[arg(
+        long,
+        value_name = "ID",
+        conflicts_with = "skip_discovery",
+        help = "Select a workspace by ID whose bootstrap logs should be read."
+    )]
+    pub workspace: Option<String>,
+
+    /// Earlier run.
+    #[arg(
+        value_name = "BEFORE",
+        help = "Earlier run, selected like `castra bootstrap show` (e.g. web~1)"
+    )]
+    pub before: String,
+
+    /// Later run.
+    #[arg(
+        value_name = "AFTER",
+        help = "Later run, selected like `castra bootstrap show` (e.g. web)"
+    )]
+    pub after: String,
+}
+
+#[derive(Debug, Args, Default)]
+pub struct CollectArgs {
+    /// Only use the explicit --config path instead of searching parent directories.
+    #[arg(
+        long,
+        help = "Skip config discovery; requires --config <PATH> (e.g. --config ./castra.toml)."
+    )]
+    pub skip_discovery: bool,
+
+    /// Operate on a specific workspace discovered via the registry.
+    #[arg(
+        long,
+        value_name = "ID",
+        conflicts_with = "skip_discovery",
+        help = "Select a workspace by ID when collecting from its running VMs."
+    )]
+    pub workspace: Option<String>,
+
+    /// Limit collection to these VMs (repeatable or comma-separated).
+    #[arg(
+        long = "vm",
+        value_name = "VM",
+        value_delimiter = ',',
+        help = "Only collect from the named VMs; they must already be running (repeatable or comma-separated)."
+    )]
+    pub vms: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct SshArgs {
+    /// Only use the explicit --config path instead of searching parent directories.
+    #[arg(
+        long,
+        help = "Skip config discovery; requires --config <PATH> (e.g. --config ./castra.toml)."
+    )]
+    pub skip_discovery: bool,
+
+    /// Operate on a specific workspace discovered via the registry.
+    #[arg(
+        long,
+        value_name = "ID",
+        conflicts_with = "skip_discovery",
+        help = "Select a workspace by ID when connecting to its VMs."
+    )]
+    pub workspace: Option<String>,
+
+    /// VM to connect to.
+    #[arg(value_name = "VM", help = "Running VM to connect to.")]
+    pub vm: String,
+
+    /// Command to run instead of a login shell.
+    #[arg(
+        last = true,
+        value_name = "CMD",
+        help = "Command to run on the VM instead of a login shell (after `--`)."
+    )]
+    pub command: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct ExecArgs {
+    /// Only use the explicit --config path instead of searching parent directories.
+    #[arg(
+        long,
+        help = "Skip config discovery; requires --config <PATH> (e.g. --config ./castra.toml)."
+    )]
+    pub skip_discovery: bool,
+
+    /// Operate on a specific workspace discovered via the registry.
+    #[arg(
+        long,
+        value_name = "ID",
+        conflicts_with = "skip_discovery",
+        help = "Select a workspace by ID when running commands on its VMs."
+    )]
+    pub workspace: Option<String>,
+
+    /// VM to run the command on.
+    #[arg(value_name = "VM", help = "Running VM to run the command on.")]
+    pub vm: Option<String>,
+
+    /// Further VMs, by name or pattern (repeatable or comma-separated).
+    #[arg(
+        long = "vm",
+        value_name = "PATTERN",
+        value_delimiter = ',',
+        help = "Run on VMs matching a name or `*`/`?` pattern such as 'web-*'; every match must be running (repeatable or comma-separated)."
+    )]
+    pub vms: Vec<String>,
+
+    /// Stop the command after this many seconds.
+    #[arg(
+        long,
+        value_name = "SECS",
+        help = "Stop the command on a VM after this many seconds."
+    )]
+    pub timeout: Option<u64>,
+
+    /// Command to run.
+    #[arg(
+        last = true,
+        required = true,
+        value_name = "CMD",
+        help = "Command to run on each VM (after `--`)."
+    )]
+    pub command: Vec<String>,
+}
+
 /// Parsed representation of a bootstrap override request from the CLI.
 #[derive(Debug, Clone)]
 pub enum BootstrapOverrideArg {
@@ -365,11 +646,12 @@ pub struct CleanArgs {
     )]
     pub skip_discovery: bool,
 
-    /// Clean the global managed image cache under ~/.castra/projects instead of the active workspace.
+    /// Clean every workspace under ~/.castra/projects and garbage-collect unreferenced images
+    /// from the shared store under ~/.castra/images.
     #[arg(
         long,
         conflicts_with = "state_root",
-        help = "Purge managed image caches under the shared projects root."
+        help = "Purge managed image caches under the shared projects root and remove shared-store images no workspace references."
     )]
     pub global: bool,
 
@@ -405,6 +687,120 @@ pub struct CleanArgs {
     pub force: bool,
 }
 
+#[derive(Debug, Args)]
+pub struct ConfigArgs {
+    #[command(subcommand)]
+    pub command: ConfigCommands,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigCommands {
+    /// Print the configuration after includes, environment interpolation and profiles.
+    Show(ConfigShowArgs),
+    /// Check the configuration and report every error and warning.
+    Validate(ConfigValidateArgs),
+    /// Print the JSON Schema describing castra.toml.
+    Schema(ConfigSchemaArgs),
+    /// Upgrade an older castra.toml, preserving comments and layout.
+    Migrate(ConfigMigrateArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct ConfigShowArgs {
+    /// Only use the explicit --config path instead of searching parent directories.
+    #[arg(
+        long,
+        help = "Skip config discovery; requires --config <PATH> (e.g. --config ./castra.toml)."
+    )]
+    pub skip_discovery: bool,
+
+    /// Print the fully expanded project instead of the resolved TOML.
+    #[arg(
+        long,
+        help = "Show every VM after replica expansion with its overlay, base image provenance, bootstrap inputs and port forwards"
+    )]
+    pub resolved: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ConfigValidateArgs {
+    /// Only use the explicit --config path instead of searching parent directories.
+    #[arg(
+        long,
+        help = "Skip config discovery; requires --config <PATH> (e.g. --config ./castra.toml)."
+    )]
+    pub skip_discovery: bool,
+
+    /// Emit machine-readable diagnostics.
+    #[arg(long, help = "Print diagnostics as JSON for editor integrations")]
+    pub json: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ConfigMigrateArgs {
+    /// Only use the explicit --config path instead of searching parent directories.
+    #[arg(
+        long,
+        help = "Skip config discovery; requires --config <PATH> (e.g. --config ./castra.toml)."
+    )]
+    pub skip_discovery: bool,
+
+    /// Rewrite the file in place instead of printing a diff.
+    #[arg(
+        long,
+        help = "Rewrite castra.toml in place; without it the migration is printed as a diff"
+    )]
+    pub write: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ConfigSchemaArgs {
+    /// Write the schema to a file instead of stdout.
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Write the schema to PATH (e.g. --output castra.schema.json) instead of stdout"
+    )]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct ImageArgs {
+    #[command(subcommand)]
+    pub command: ImageCommands,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ImageCommands {
+    /// Stop a VM and flatten its overlay into a named image reused by later runs.
+    Commit(ImageCommitArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct ImageCommitArgs {
+    /// Only use the explicit --config path instead of searching parent directories.
+    #[arg(
+        long,
+        help = "Skip config discovery; requires --config <PATH> (e.g. --config ./castra.toml)."
+    )]
+    pub skip_discovery: bool,
+
+    /// VM whose current disk state should be captured.
+    #[arg(
+        value_name = "VM",
+        help = "Name of the VM to commit (e.g. devbox or api-0)"
+    )]
+    pub vm: String,
+
+    /// Name recorded for the image in the shared store.
+    #[arg(
+        long,
+        value_name = "IMAGE",
+        help = "Tag the flattened image as IMAGE in the shared image store"
+    )]
+    pub name: String,
+}
+
 #[derive(Debug, Args)]
 pub struct BusArgs {
     #[command(subcommand)]
@@ -556,6 +952,122 @@ mod tests {
         assert!(!args.plan);
     }
 
+    #[test]
+    fn parse_bootstrap_selectors() {
+        let cli = Cli::try_parse_from([
+            "castra",
+            "bootstrap",
+            "--vm",
+            "web,db",
+            "--stage",
+            "deps",
+            "--stage",
+            "app",
+            "--force",
+        ])
+        .expect("parse bootstrap");
+        let Commands::Bootstrap(args) = cli.command.expect("bootstrap command present") else {
+            panic!("expected bootstrap command");
+        };
+        assert_eq!(args.vms, ["web", "db"]);
+        assert_eq!(args.stages, ["deps", "app"]);
+        assert!(args.force);
+        assert!(args.workspace.is_none());
+        assert!(args.command.is_none());
+
+        let cli = Cli::try_parse_from(["castra", "bootstrap", "diff", "web~1", "web"])
+            .expect("parse bootstrap diff");
+        let Some(Commands::Bootstrap(BootstrapArgs {
+            command: Some(BootstrapCommands::Diff(diff)),
+            ..
+        })) = cli.command
+        else {
+            panic!("expected bootstrap diff");
+        };
+        assert_eq!(
+            (diff.before.as_str(), diff.after.as_str()),
+            ("web~1", "web")
+        );
+        assert!(Cli::try_parse_from(["castra", "bootstrap", "--force", "history"]).is_err());
+    }
+
+    #[test]
+    fn parse_ssh_and_exec_commands() {
+        let cli = Cli::try_parse_from(["castra", "ssh", "web-0", "--", "uname", "-a"])
+            .expect("parse ssh");
+        let Some(Commands::Ssh(args)) = cli.command else {
+            panic!("expected ssh command");
+        };
+        assert_eq!(args.vm, "web-0");
+        assert_eq!(args.command, ["uname", "-a"]);
+
+        let cli = Cli::try_parse_from(["castra", "ssh", "web-0"]).expect("parse ssh shell");
+        let Some(Commands::Ssh(args)) = cli.command else {
+            panic!("expected ssh command");
+        };
+        assert!(args.command.is_empty());
+
+        let cli = Cli::try_parse_from([
+            "castra",
+            "exec",
+            "--vm",
+            "web-*,db",
+            "--timeout",
+            "30",
+            "--",
+            "systemctl",
+            "is-active",
+            "nginx",
+        ])
+        .expect("parse exec");
+        let Some(Commands::Exec(args)) = cli.command else {
+            panic!("expected exec command");
+        };
+        assert_eq!(args.vm, None);
+        assert_eq!(args.vms, ["web-*", "db"]);
+        assert_eq!(args.timeout, Some(30));
+        assert_eq!(args.command, ["systemctl", "is-active", "nginx"]);
+
+        assert!(Cli::try_parse_from(["castra", "exec", "web-0"]).is_err());
+    }
+
+    #[test]
+    fn parse_global_profile_before_and_after_subcommand() {
+        let cli = Cli::try_parse_from(["castra", "--profile", "ci", "up"]).expect("parse profile");
+        assert_eq!(cli.profile.as_deref(), Some("ci"));
+        let cli =
+            Cli::try_parse_from(["castra", "down", "--profile", "beefy"]).expect("parse profile");
+        assert_eq!(cli.profile.as_deref(), Some("beefy"));
+        assert!(matches!(cli.command, Some(Commands::Down(_))));
+    }
+
+    #[test]
+    fn parse_config_migrate_write() {
+        let cli = Cli::try_parse_from(["castra", "config", "migrate", "--write"])
+            .expect("parse config migrate");
+        let Commands::Config(args) = cli.command.expect("config command present") else {
+            panic!("expected config command");
+        };
+        let ConfigCommands::Migrate(migrate) = args.command else {
+            panic!("expected migrate subcommand");
+        };
+        assert!(migrate.write);
+        assert!(!migrate.skip_discovery);
+    }
+
+    #[test]
+    fn parse_config_schema_output() {
+        let cli = Cli::try_parse_from(["castra", "config", "schema", "--output", "schema.json"])
+            .expect("parse config schema");
+        let Commands::Config(args) = cli.command.expect("config command present") else {
+            panic!("expected config command");
+        };
+        let ConfigCommands::Schema(schema) = args.command else {
+            panic!("expected schema subcommand");
+        };
+        assert_eq!(schema.output, Some(PathBuf::from("schema.json")));
+    }
+
     #[test]
     fn parse_logs_tail_defaults() {
         let cli = Cli::try_parse_from(["castra", "logs", "--tail", "50"]).expect("parse logs tail");
@@ -1,5 +1,5 @@
-use std::collections::{HashMap, HashSet};
-use std::path::{Path, PathBuf};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Component, Path, PathBuf};
 use std::str::FromStr;
 use std::time::Duration;
 use std::{env, fs};
@@ -9,16 +9,27 @@ use sha2::{Digest, Sha256};
 
 use crate::error::Error;
 
+mod migrate;
+mod schema;
+
+pub use migrate::{CURRENT_CONFIG_VERSION, ConfigMigration, migrate_config_source};
+pub use schema::config_json_schema;
+
 pub const DEFAULT_IMAGE_SUBDIR: &str = "images";
 pub const DEFAULT_ALPINE_IMAGE_FILENAME: &str = "alpine-x86_64.qcow2";
 const DEFAULT_OVERLAY_SUBDIR: &str = "overlays";
 const DEFAULT_OVERLAY_SUFFIX: &str = "overlay";
 const DEFAULT_OVERLAY_EXTENSION: &str = "qcow2";
+const MANAGED_SSH_SUBDIR: &str = "ssh";
+const SECRET_SALT_FILE: &str = "bootstrap/secret.salt";
+const GIT_PAYLOAD_SUBDIR: &str = "git-payloads";
 
 pub const DEFAULT_GRACEFUL_SHUTDOWN_WAIT_SECS: u64 = 20;
 pub const DEFAULT_SIGTERM_WAIT_SECS: u64 = 10;
 pub const DEFAULT_SIGKILL_WAIT_SECS: u64 = 5;
 pub const DEFAULT_BOOTSTRAP_HANDSHAKE_WAIT_SECS: u64 = 120;
+pub const DEFAULT_CONNECT_RETRIES: u32 = 4;
+pub const DEFAULT_STEP_BACKOFF_MS: u64 = 1000;
 
 pub const BROKERLESS_MIGRATION_DOC: &str = "docs/migration/brokerless-core.md";
 #[derive(Debug, Clone)]
@@ -71,12 +82,55 @@ pub struct ProjectConfig {
     pub workflows: Workflows,
     pub lifecycle: LifecycleConfig,
     pub bootstrap: BootstrapConfig,
+    pub images: ImagesConfig,
     pub warnings: Vec<String>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub struct ProjectFeatures;
 
+/// Settings from the `[images]` table.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImagesConfig {
+    /// Base URLs tried in order before the upstream release when fetching managed images.
+    pub mirrors: Vec<String>,
+    /// Keys allowed to sign managed images. When non-empty, unsigned images are rejected.
+    pub trusted_keys: Vec<TrustedKeyConfig>,
+}
+
+/// Public key declared under `[[images.trusted_keys]]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrustedKeyConfig {
+    pub name: String,
+    pub scheme: SignatureScheme,
+    /// Encoded public key (minisign base64, or base64/hex for raw ed25519).
+    pub key: String,
+}
+
+/// Detached signature formats accepted for managed images.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureScheme {
+    Minisign,
+    Ed25519,
+}
+
+impl SignatureScheme {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SignatureScheme::Minisign => "minisign",
+            SignatureScheme::Ed25519 => "ed25519",
+        }
+    }
+
+    /// Suffix appended to an image URL or path to locate its signature.
+    pub fn extension(self) -> &'static str {
+        match self {
+            SignatureScheme::Minisign => "minisig",
+            SignatureScheme::Ed25519 => "sig",
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct LifecycleConfig {
     pub graceful_shutdown_wait_secs: u64,
@@ -146,12 +200,58 @@ impl Default for BootstrapMode {
     }
 }
 
+/// How castra reaches guests over SSH during bootstrap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BootstrapTransport {
+    /// Spawn the system `ssh` and `scp` binaries for every step.
+    #[default]
+    OpenSsh,
+    /// Speak SSH in-process, reusing one session per VM. Requires the
+    /// `native-ssh` feature.
+    Native,
+}
+
+impl BootstrapTransport {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::OpenSsh => "openssh",
+            Self::Native => "native",
+        }
+    }
+}
+
+impl FromStr for BootstrapTransport {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "openssh" | "ssh" => Ok(Self::OpenSsh),
+            "native" => Ok(Self::Native),
+            _ => Err(format!(
+                "Unknown bootstrap transport `{value}`. Supported values: openssh, native."
+            )),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct BootstrapConfig {
     pub mode: BootstrapMode,
     pub handshake_timeout_secs: u64,
     pub remote_dir: PathBuf,
     pub env: HashMap<String, String>,
+    /// Variables whose values are read from the host only when a pipeline runs.
+    pub env_secrets: HashMap<String, SecretSource>,
+    /// Flatten successfully bootstrapped overlays into golden images.
+    pub bake: bool,
+    pub transport: BootstrapTransport,
+    /// Generate a workspace SSH keypair and use it for every VM. `None`
+    /// enables it for every VM except those booting the default Alpine image,
+    /// which cannot read the key from fw_cfg.
+    pub managed_keys: Option<bool>,
+    /// Most VMs bootstrapped at once; `None` runs every pipeline together.
+    pub max_parallel: Option<usize>,
+    pub steps: BootstrapStepPolicies,
 }
 
 impl Default for BootstrapConfig {
@@ -161,19 +261,266 @@ impl Default for BootstrapConfig {
             handshake_timeout_secs: DEFAULT_BOOTSTRAP_HANDSHAKE_WAIT_SECS,
             remote_dir: PathBuf::from("/tmp/castra-bootstrap"),
             env: HashMap::new(),
+            env_secrets: HashMap::new(),
+            bake: false,
+            transport: BootstrapTransport::default(),
+            managed_keys: None,
+            max_parallel: None,
+            steps: BootstrapStepPolicies::default(),
+        }
+    }
+}
+
+/// Retry and timeout settings for each bootstrap step, from
+/// `[bootstrap.steps.<step>]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BootstrapStepPolicies {
+    pub connect: BootstrapStepPolicy,
+    pub transfer: BootstrapStepPolicy,
+    pub apply: BootstrapStepPolicy,
+    pub verify: BootstrapStepPolicy,
+}
+
+impl Default for BootstrapStepPolicies {
+    fn default() -> Self {
+        Self {
+            connect: BootstrapStepPolicy {
+                retries: DEFAULT_CONNECT_RETRIES,
+                ..BootstrapStepPolicy::default()
+            },
+            transfer: BootstrapStepPolicy::default(),
+            apply: BootstrapStepPolicy::default(),
+            verify: BootstrapStepPolicy::default(),
+        }
+    }
+}
+
+/// How often one bootstrap step is retried and how long an attempt may take.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BootstrapStepPolicy {
+    /// Attempts after the first failed one.
+    pub retries: u32,
+    /// Wait before the first retry; doubled for each later one.
+    pub backoff: Duration,
+    /// Upper bound on a single attempt.
+    pub timeout: Option<Duration>,
+}
+
+impl Default for BootstrapStepPolicy {
+    fn default() -> Self {
+        Self {
+            retries: 0,
+            backoff: Duration::from_millis(DEFAULT_STEP_BACKOFF_MS),
+            timeout: None,
         }
     }
 }
 
+impl BootstrapStepPolicy {
+    /// Wait after failed attempt number `attempt` (1-based).
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 1u32 << attempt.saturating_sub(1).min(16);
+        self.backoff.saturating_mul(factor)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct VmBootstrapConfig {
     pub mode: BootstrapMode,
     pub script: Option<PathBuf>,
-    pub payload: Option<PathBuf>,
+    pub payload: Option<PayloadSource>,
     pub handshake_timeout_secs: u64,
     pub remote_dir: PathBuf,
     pub env: HashMap<String, String>,
+    pub env_secrets: HashMap<String, SecretSource>,
+    /// Workspace salt mixed into the digests that stand in for secret values.
+    pub secret_salt: PathBuf,
+    pub verify: Option<BootstrapVerifyConfig>,
+    /// Bake the bootstrapped overlay into a golden image reused by later runs.
+    pub bake: bool,
+    /// Ordered pipeline stages. When non-empty they replace `script`,
+    /// `payload` and `verify`.
+    pub stages: Vec<BootstrapStage>,
+    /// Workspace keypair injected at boot and used for bootstrap SSH;
+    /// `None` when `managed_keys = false`.
+    pub managed_keys: Option<ManagedSshKeys>,
+    /// Values for `{{ name }}` placeholders in `.tmpl` bootstrap scripts and
+    /// payload files, describing this VM and its peers.
+    pub template_vars: BTreeMap<String, String>,
+}
+
+/// Skipped bootstrap with nothing to run; tests set the fields they
+/// exercise.
+#[cfg(test)]
+impl Default for VmBootstrapConfig {
+    fn default() -> Self {
+        Self {
+            mode: BootstrapMode::Skip,
+            script: None,
+            payload: None,
+            handshake_timeout_secs: DEFAULT_BOOTSTRAP_HANDSHAKE_WAIT_SECS,
+            remote_dir: PathBuf::from("/tmp/castra-bootstrap"),
+            env: HashMap::new(),
+            env_secrets: HashMap::new(),
+            secret_salt: PathBuf::new(),
+            verify: None,
+            bake: false,
+            stages: Vec::new(),
+            managed_keys: None,
+            template_vars: BTreeMap::new(),
+        }
+    }
+}
+
+/// SSH credentials Castra generates once per workspace under the state root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManagedSshKeys {
+    /// Private ed25519 key. The public half sits next to it as `<identity>.pub`.
+    pub identity: PathBuf,
+    /// Guest host keys recorded on first connection after each launch.
+    pub known_hosts: PathBuf,
+}
+
+impl ManagedSshKeys {
+    pub fn for_state_root(state_root: &Path) -> Self {
+        let dir = state_root.join(MANAGED_SSH_SUBDIR);
+        Self {
+            identity: dir.join("id_ed25519"),
+            known_hosts: dir.join("known_hosts"),
+        }
+    }
+
+    pub fn public_key(&self) -> PathBuf {
+        self.identity.with_extension("pub")
+    }
+}
+
+/// Where a bootstrap payload comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PayloadSource {
+    /// Host directory copied as-is.
+    Dir(PathBuf),
+    /// Tree exported from a local git repository at a pinned commit.
+    Git(GitPayload),
+}
+
+impl PayloadSource {
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Dir(path) => path.display().to_string(),
+            Self::Git(git) => {
+                let mut text = format!("git {} @ {}", git.repo.display(), git.rev);
+                if let Some(subdir) = &git.subdir {
+                    text.push_str(&format!(" ({})", subdir.display()));
+                }
+                text
+            }
+        }
+    }
+}
+
+/// `payload = { git = "...", rev = "...", subdir = "..." }`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GitPayload {
+    pub repo: PathBuf,
+    /// Branch, tag or commit, resolved each time the bootstrap is planned.
+    pub rev: String,
+    /// Directory inside the repository exported instead of its root.
+    pub subdir: Option<PathBuf>,
+    /// Exported trees, cached under the state root by git tree id.
+    pub cache_dir: PathBuf,
+}
+
+/// Host-side origin of a secret bootstrap variable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SecretSource {
+    /// Contents of a file, minus one trailing newline.
+    File(PathBuf),
+    /// A variable in castra's own environment.
+    Env(String),
+}
+
+impl SecretSource {
+    /// Read the current value. Errors never include the value itself.
+    pub fn resolve(&self) -> Result<String, String> {
+        match self {
+            Self::File(path) => {
+                let mut value = fs::read_to_string(path).map_err(|err| {
+                    format!("Failed to read secret file {}: {err}", path.display())
+                })?;
+                if value.ends_with('\n') {
+                    value.pop();
+                    if value.ends_with('\r') {
+                        value.pop();
+                    }
+                }
+                Ok(value)
+            }
+            Self::Env(name) => env::var(name)
+                .ok()
+                .filter(|value| !value.is_empty())
+                .ok_or_else(|| format!("Host environment variable `{name}` is not set.")),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Self::File(path) => format!("file {}", path.display()),
+            Self::Env(name) => format!("env ${name}"),
+        }
+    }
+}
+
+/// One stage of a multi-stage bootstrap pipeline.
+#[derive(Debug, Clone)]
+pub struct BootstrapStage {
+    pub name: String,
+    pub script: PathBuf,
+    pub payload: Option<PayloadSource>,
+    /// Stage-specific variables layered over the VM's bootstrap env.
+    pub env: HashMap<String, String>,
+    pub env_secrets: HashMap<String, SecretSource>,
+    /// Upper bound on the stage's apply step.
+    pub timeout_secs: Option<u64>,
     pub verify: Option<BootstrapVerifyConfig>,
+    pub run_if: BootstrapRunIf,
+}
+
+/// When a stage runs relative to the state the guest already carries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BootstrapRunIf {
+    /// Run unless this stage and every earlier one already applied the same inputs.
+    #[default]
+    Changed,
+    /// Run on every bootstrap pass.
+    Always,
+    /// Run only if the stage has never completed, even when its inputs change.
+    Once,
+}
+
+impl BootstrapRunIf {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Changed => "changed",
+            Self::Always => "always",
+            Self::Once => "once",
+        }
+    }
+}
+
+impl FromStr for BootstrapRunIf {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "changed" => Ok(Self::Changed),
+            "always" => Ok(Self::Always),
+            "once" => Ok(Self::Once),
+            _ => Err(format!(
+                "Unknown run_if `{value}`. Supported values: changed, always, once."
+            )),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -199,6 +546,59 @@ impl ProjectConfig {
             })
             .collect()
     }
+
+    /// Indices into `vms` grouped into startup waves. Every VM only depends
+    /// on VMs from earlier waves; config order is kept within a wave.
+    pub fn startup_waves(&self) -> Vec<Vec<usize>> {
+        let index_of: HashMap<&str, usize> = self
+            .vms
+            .iter()
+            .enumerate()
+            .map(|(index, vm)| (vm.name.as_str(), index))
+            .collect();
+        let mut depth: Vec<Option<usize>> = vec![None; self.vms.len()];
+        let mut remaining = self.vms.len();
+        while remaining > 0 {
+            let mut progressed = false;
+            for (index, vm) in self.vms.iter().enumerate() {
+                if depth[index].is_some() {
+                    continue;
+                }
+                let mut wave = 0;
+                let mut ready = true;
+                for dependency in &vm.depends_on {
+                    match index_of.get(dependency.vm.as_str()).map(|&dep| depth[dep]) {
+                        Some(Some(dep_depth)) => wave = wave.max(dep_depth + 1),
+                        Some(None) => ready = false,
+                        None => {}
+                    }
+                }
+                if ready {
+                    depth[index] = Some(wave);
+                    remaining -= 1;
+                    progressed = true;
+                }
+            }
+            if !progressed {
+                // Cycles are rejected while loading; keep any leftovers last.
+                let last = depth.iter().flatten().max().map_or(0, |max| max + 1);
+                for slot in depth.iter_mut().filter(|slot| slot.is_none()) {
+                    *slot = Some(last);
+                }
+                break;
+            }
+        }
+
+        let mut waves: Vec<Vec<usize>> = Vec::new();
+        for (index, wave) in depth.into_iter().enumerate() {
+            let wave = wave.unwrap_or_default();
+            if waves.len() <= wave {
+                waves.resize_with(wave + 1, Vec::new);
+            }
+            waves[wave].push(index);
+        }
+        waves
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -213,6 +613,152 @@ pub struct VmDefinition {
     pub memory: MemorySpec,
     pub port_forwards: Vec<PortForward>,
     pub bootstrap: VmBootstrapConfig,
+    /// VMs (by expanded instance name) that must reach a condition before
+    /// this VM launches.
+    pub depends_on: Vec<VmDependency>,
+    /// Declarative guest state from `[vms.provision]`, applied as the first
+    /// bootstrap stage.
+    pub provision: Option<ProvisionConfig>,
+    /// Guest paths copied to the host after bootstrap, on `castra collect`
+    /// and before shutdown.
+    pub collect: Vec<CollectRule>,
+}
+
+#[cfg(test)]
+impl VmDefinition {
+    /// Single-CPU, 512 MiB replica 0 of role `name` with no port forwards and
+    /// a [`VmBootstrapConfig::default`] bootstrap. Tests override the fields
+    /// they exercise with struct update syntax.
+    pub(crate) fn for_test(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            role_name: name.to_string(),
+            replica_index: 0,
+            description: None,
+            base_image: BaseImageSource::from_explicit("/tmp/base.qcow2"),
+            overlay: PathBuf::from(format!("/tmp/{name}.qcow2")),
+            cpus: 1,
+            memory: MemorySpec::new("512 MiB", Some(512 * 1024 * 1024)),
+            port_forwards: Vec::new(),
+            bootstrap: VmBootstrapConfig::default(),
+            depends_on: Vec::new(),
+            provision: None,
+            collect: Vec::new(),
+        }
+    }
+}
+
+/// Guest path copied to the host by a `collect` rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CollectRule {
+    /// Absolute guest file or directory.
+    pub guest: String,
+    /// Host directory the guest path is copied into, with `{vm}` expanded.
+    pub host: PathBuf,
+}
+
+/// Name of the bootstrap stage rendered from `[vms.provision]`.
+pub const PROVISION_STAGE: &str = "provision";
+
+/// Address at which guests reach the host, and through its port forwards
+/// every other VM, under QEMU user networking.
+pub const GUEST_HOST_ADDRESS: &str = "10.0.2.2";
+
+/// Declarative guest state rendered into an idempotent bootstrap stage.
+#[derive(Debug, Clone, Default)]
+pub struct ProvisionConfig {
+    /// Config file declaring the section; reported as the stage's script.
+    pub source: PathBuf,
+    pub packages: Vec<ProvisionPackage>,
+    pub files: Vec<ProvisionFile>,
+    pub users: Vec<ProvisionUser>,
+    pub services: Vec<String>,
+    pub sysctls: BTreeMap<String, String>,
+}
+
+/// Package to install, named per package manager. A manager without a name
+/// skips the package.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProvisionPackage {
+    pub apk: Option<String>,
+    pub apt: Option<String>,
+    pub dnf: Option<String>,
+}
+
+/// File written on the guest.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProvisionFile {
+    pub path: String,
+    pub contents: ProvisionFileContents,
+    /// Permission bits, e.g. `0o644`.
+    pub mode: Option<u32>,
+    /// `user` or `user:group`.
+    pub owner: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProvisionFileContents {
+    Inline(String),
+    /// Host file read when the stage is rendered.
+    Source(PathBuf),
+}
+
+/// User account ensured on the guest.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProvisionUser {
+    pub name: String,
+    pub groups: Vec<String>,
+    pub shell: Option<String>,
+    pub authorized_keys: Vec<String>,
+}
+
+/// Readiness a dependency must reach before its dependents launch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum DependencyCondition {
+    /// The dependency's QEMU process has started.
+    Launched,
+    /// The dependency's bootstrap pipeline finished (or was skipped).
+    #[default]
+    Bootstrapped,
+    /// Bootstrapped, reachable over SSH, and passing its verify checks.
+    Healthy,
+}
+
+impl DependencyCondition {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Launched => "launched",
+            Self::Bootstrapped => "bootstrapped",
+            Self::Healthy => "healthy",
+        }
+    }
+}
+
+impl FromStr for DependencyCondition {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "launched" => Ok(Self::Launched),
+            "bootstrapped" => Ok(Self::Bootstrapped),
+            "healthy" => Ok(Self::Healthy),
+            _ => Err(format!(
+                "Unknown dependency condition `{value}`. Supported values: launched, bootstrapped, healthy."
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for DependencyCondition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VmDependency {
+    pub vm: String,
+    pub condition: DependencyCondition,
 }
 
 #[derive(Debug, Clone)]
@@ -270,9 +816,103 @@ impl std::fmt::Display for PortProtocol {
     }
 }
 
-#[derive(Debug, Clone)]
+/// Lifecycle points at which `[workflows]` host commands run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HookPoint {
+    /// Before any VM is launched by `up` (after preflight checks).
+    PreUp,
+    /// After a VM process has been launched.
+    PostLaunch,
+    /// After a VM's bootstrap pipeline finished (including skips and no-ops).
+    PostBootstrap,
+    /// Before a running VM is shut down by `down`.
+    PreDown,
+    /// After a VM has been shut down by `down`.
+    PostDown,
+}
+
+impl HookPoint {
+    pub const ALL: [HookPoint; 5] = [
+        HookPoint::PreUp,
+        HookPoint::PostLaunch,
+        HookPoint::PostBootstrap,
+        HookPoint::PreDown,
+        HookPoint::PostDown,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            HookPoint::PreUp => "pre_up",
+            HookPoint::PostLaunch => "post_launch",
+            HookPoint::PostBootstrap => "post_bootstrap",
+            HookPoint::PreDown => "pre_down",
+            HookPoint::PostDown => "post_down",
+        }
+    }
+}
+
+impl std::fmt::Display for HookPoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Variables available to `{{ name }}` placeholders in hook commands.
+pub const HOOK_TEMPLATE_VARIABLES: &[&str] = &[
+    "vm",
+    "project",
+    "state_root",
+    "overlay",
+    "base_image",
+    "ssh_port",
+    "hook",
+];
+
+/// Host commands run at lifecycle hook points, once per VM.
+#[derive(Debug, Clone, Default)]
 pub struct Workflows {
-    pub init: Vec<String>,
+    pub pre_up: Vec<String>,
+    pub post_launch: Vec<String>,
+    pub post_bootstrap: Vec<String>,
+    pub pre_down: Vec<String>,
+    pub post_down: Vec<String>,
+}
+
+impl Workflows {
+    pub fn commands(&self, point: HookPoint) -> &[String] {
+        match point {
+            HookPoint::PreUp => &self.pre_up,
+            HookPoint::PostLaunch => &self.post_launch,
+            HookPoint::PostBootstrap => &self.post_bootstrap,
+            HookPoint::PreDown => &self.pre_down,
+            HookPoint::PostDown => &self.post_down,
+        }
+    }
+}
+
+/// Names referenced by `{{ name }}` placeholders in `template`.
+pub fn hook_template_placeholders(template: &str) -> Result<Vec<String>, String> {
+    template_placeholders(template).map_err(|err| format!("{err} in `{template}`"))
+}
+
+/// Names referenced by `{{ name }}` placeholders in `template`, with errors
+/// that leave quoting the template to the caller.
+pub fn template_placeholders(template: &str) -> Result<Vec<String>, String> {
+    let mut names = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let after = &rest[start + 2..];
+        let end = after
+            .find("}}")
+            .ok_or_else(|| "unterminated `{{`".to_string())?;
+        let name = after[..end].trim();
+        if name.is_empty() {
+            return Err("empty placeholder".to_string());
+        }
+        names.push(name.to_string());
+        rest = &after[end + 2..];
+    }
+    Ok(names)
 }
 
 #[derive(Debug, Clone)]
@@ -282,15 +922,16 @@ pub struct PortConflict {
 }
 
 pub fn load_project_config(path: &Path) -> Result<ProjectConfig, Error> {
-    let contents = fs::read_to_string(path).map_err(|source| Error::ReadConfig {
-        path: path.to_path_buf(),
-        source,
-    })?;
+    load_project_config_with_profile(path, None)
+}
 
-    let value: toml::Value = toml::from_str(&contents).map_err(|source| Error::ParseConfig {
-        path: path.to_path_buf(),
-        source,
-    })?;
+/// Load `path` with `${VAR}` interpolation, `include` merging and, when
+/// `profile` is set, the matching `[profiles.<name>]` overlay applied.
+pub fn load_project_config_with_profile(
+    path: &Path,
+    profile: Option<&str>,
+) -> Result<ProjectConfig, Error> {
+    let value = resolve_config_value(path, profile)?;
 
     let legacy_keys = find_legacy_broker_keys(&value);
     if !legacy_keys.is_empty() {
@@ -319,1255 +960,2358 @@ pub fn load_project_config(path: &Path) -> Result<ProjectConfig, Error> {
     raw.into_validated(path, &mut warnings)
 }
 
-fn invalid_config(path: &Path, message: impl Into<String>) -> Error {
-    Error::InvalidConfig {
-        path: path.to_path_buf(),
-        message: message.into(),
-    }
+/// Render the fully resolved configuration (includes merged, environment
+/// interpolated, profile applied) back to TOML, e.g. for workspace snapshots.
+pub fn resolve_config_source(path: &Path, profile: Option<&str>) -> Result<String, Error> {
+    let value = resolve_config_value(path, profile)?;
+    toml::to_string_pretty(&value).map_err(|err| {
+        invalid_config(
+            path,
+            format!("Failed to render resolved configuration: {err}"),
+        )
+    })
 }
 
-fn detect_unknown_fields(value: &toml::Value) -> Vec<String> {
-    let mut warnings = Vec::new();
-    let allowed_root = [
-        "version",
-        "project",
-        "vms",
-        "workflows",
-        "lifecycle",
-        "bootstrap",
-    ];
-
-    if let toml::Value::Table(table) = value {
-        warn_table(table, &allowed_root, "root", &mut warnings);
-
-        if let Some(project) = table.get("project") {
-            if let toml::Value::Table(project_table) = project {
-                warn_table(
-                    project_table,
-                    &["name", "state_dir", "features"],
-                    "[project]",
-                    &mut warnings,
-                );
-
-                if let Some(features) = project_table.get("features") {
-                    if let toml::Value::Table(features_table) = features {
-                        warn_table(
-                            features_table,
-                            &["enable_vm_vizier"],
-                            "[project.features]",
-                            &mut warnings,
-                        );
-                    } else {
-                        warnings.push("Expected [project.features] to be a table.".to_string());
-                    }
-                }
-            } else {
-                warnings.push("Expected [project] to be a table.".to_string());
-            }
-        }
+fn resolve_config_value(path: &Path, profile: Option<&str>) -> Result<toml::Value, Error> {
+    let mut stack = Vec::new();
+    let mut value = read_config_document(path, &mut stack)?;
 
-        if let Some(vms) = table.get("vms") {
-            if let toml::Value::Array(vm_entries) = vms {
-                for (idx, entry) in vm_entries.iter().enumerate() {
-                    if let toml::Value::Table(vm_table) = entry {
-                        warn_table(
-                            vm_table,
-                            &[
-                                "name",
-                                "description",
-                                "base_image",
-                                "managed_image",
-                                "overlay",
-                                "cpus",
-                                "memory",
-                                "port_forwards",
-                                "count",
-                                "instances",
-                                "bootstrap",
-                            ],
-                            &format!("[[vms]] #{idx}"),
-                            &mut warnings,
-                        );
-
-                        if let Some(managed_image) = vm_table.get("managed_image") {
-                            if let toml::Value::Table(managed_table) = managed_image {
-                                warn_table(
-                                    managed_table,
-                                    &["name", "version", "disk", "checksum", "size_bytes"],
-                                    &format!("[[vms]] #{idx}.managed_image"),
-                                    &mut warnings,
-                                );
-                            } else {
-                                warnings.push(format!(
-                                    "`managed_image` on [[vms]] entry #{idx} must be a table."
-                                ));
-                            }
-                        }
+    let profiles = match &mut value {
+        toml::Value::Table(table) => table.remove("profiles"),
+        _ => None,
+    };
+    if let Some(name) = profile {
+        apply_profile(path, &mut value, profiles.as_ref(), name)?;
+    }
+    Ok(value)
+}
 
-                        if let Some(port_forwards) = vm_table.get("port_forwards") {
-                            if let toml::Value::Array(tables) = port_forwards {
-                                for (pf_idx, pf) in tables.iter().enumerate() {
-                                    if let toml::Value::Table(pf_table) = pf {
-                                        warn_table(
-                                            pf_table,
-                                            &["host", "guest", "protocol"],
-                                            &format!("[[vms.port_forwards]] #{pf_idx}"),
-                                            &mut warnings,
-                                        );
-                                    } else {
-                                        warnings.push(format!(
-                                            "[[vms.port_forwards]] entry #{pf_idx} must be a table."
-                                        ));
-                                    }
-                                }
-                            } else {
-                                warnings.push(
-                                    "`port_forwards` must be an array of tables.".to_string(),
-                                );
-                            }
-                        }
+/// Parse `path`, interpolate environment references and merge its includes
+/// beneath it. `stack` tracks the include chain to reject cycles.
+fn read_config_document(path: &Path, stack: &mut Vec<PathBuf>) -> Result<toml::Value, Error> {
+    let contents = fs::read_to_string(path).map_err(|source| Error::ReadConfig {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let mut value: toml::Value =
+        toml::from_str(&contents).map_err(|source| Error::ParseConfig {
+            path: path.to_path_buf(),
+            source,
+        })?;
+    interpolate_env(path, &mut value, "")?;
 
-                        if let Some(bootstrap) = vm_table.get("bootstrap") {
-                            if let toml::Value::Table(bootstrap_table) = bootstrap {
-                                warn_table(
-                                    bootstrap_table,
-                                    &[
-                                        "mode",
-                                        "script",
-                                        "payload",
-                                        "handshake_timeout_secs",
-                                        "remote_dir",
-                                        "env",
-                                        "verify_command",
-                                        "verify_path",
-                                    ],
-                                    &format!("[[vms]] #{idx}.bootstrap"),
-                                    &mut warnings,
-                                );
-                                if let Some(env) = bootstrap_table.get("env") {
-                                    if !env.is_table() {
-                                        warnings.push(format!(
-                                            "`env` on [[vms]] entry #{idx}.bootstrap must be a table mapping keys to values."
-                                        ));
-                                    }
-                                }
-                            } else {
-                                warnings.push(format!(
-                                    "`bootstrap` on [[vms]] entry #{idx} must be a table."
-                                ));
-                            }
-                        }
+    let includes = match &mut value {
+        toml::Value::Table(table) => table.remove("include"),
+        _ => None,
+    };
+    let Some(includes) = includes else {
+        return Ok(value);
+    };
+    let toml::Value::Array(entries) = includes else {
+        return Err(invalid_config(
+            path,
+            "`include` must be an array of file paths.",
+        ));
+    };
 
-                        if let Some(instances) = vm_table.get("instances") {
-                            if let toml::Value::Array(instance_entries) = instances {
-                                for (inst_idx, instance) in instance_entries.iter().enumerate() {
-                                    if let toml::Value::Table(instance_table) = instance {
-                                        warn_table(
-                                            instance_table,
-                                            &[
-                                                "id",
-                                                "description",
-                                                "base_image",
-                                                "managed_image",
-                                                "overlay",
-                                                "cpus",
-                                                "memory",
-                                                "port_forwards",
-                                            ],
-                                            &format!("[[vms.instances]] #{inst_idx}"),
-                                            &mut warnings,
-                                        );
-
-                                        if let Some(managed_image) =
-                                            instance_table.get("managed_image")
-                                        {
-                                            if let toml::Value::Table(managed_table) = managed_image
-                                            {
-                                                warn_table(
-                                                    managed_table,
-                                                    &[
-                                                        "name",
-                                                        "version",
-                                                        "disk",
-                                                        "checksum",
-                                                        "size_bytes",
-                                                    ],
-                                                    &format!(
-                                                        "[[vms.instances]] #{inst_idx}.managed_image"
-                                                    ),
-                                                    &mut warnings,
-                                                );
-                                            } else {
-                                                warnings.push(format!(
-                                                    "`managed_image` on [[vms.instances]] entry #{inst_idx} must be a table."
-                                                ));
-                                            }
-                                        }
-
-                                        if let Some(port_forwards) =
-                                            instance_table.get("port_forwards")
-                                        {
-                                            if let toml::Value::Array(tables) = port_forwards {
-                                                for (pf_idx, pf) in tables.iter().enumerate() {
-                                                    if let toml::Value::Table(pf_table) = pf {
-                                                        warn_table(
-                                                            pf_table,
-                                                            &["host", "guest", "protocol"],
-                                                            &format!(
-                                                                "[[vms.instances.port_forwards]] #{pf_idx}"
-                                                            ),
-                                                            &mut warnings,
-                                                        );
-                                                    } else {
-                                                        warnings.push(format!(
-                                                            "[[vms.instances.port_forwards]] entry #{pf_idx} must be a table."
-                                                        ));
-                                                    }
-                                                }
-                                            } else {
-                                                warnings.push(
-                                                    "`port_forwards` under [[vms.instances]] must be an array of tables."
-                                                        .to_string(),
-                                                );
-                                            }
-                                        }
-                                    } else {
-                                        warnings.push(format!(
-                                            "[[vms.instances]] entry #{inst_idx} must be a table."
-                                        ));
-                                    }
-                                }
-                            } else {
-                                warnings
-                                    .push("`instances` must be an array of tables.".to_string());
-                            }
-                        }
-                    } else {
-                        warnings.push(format!("[[vms]] entry #{idx} must be a table."));
-                    }
-                }
-            } else {
-                warnings.push("`vms` must be an array of tables.".to_string());
-            }
-        }
+    let canonical = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    if stack.contains(&canonical) {
+        return Err(invalid_config(
+            path,
+            format!("Include cycle detected via {}.", canonical.display()),
+        ));
+    }
+    stack.push(canonical);
 
-        if let Some(workflows) = table.get("workflows") {
-            if let toml::Value::Table(workflows_table) = workflows {
-                warn_table(workflows_table, &["init"], "[workflows]", &mut warnings);
-            } else {
-                warnings.push("Expected [workflows] to be a table.".to_string());
-            }
-        }
+    let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let mut merged = toml::Value::Table(toml::map::Map::new());
+    for entry in entries {
+        let toml::Value::String(raw) = entry else {
+            return Err(invalid_config(path, "`include` entries must be strings."));
+        };
+        let included = read_config_document(&resolve_path(base_dir, PathBuf::from(raw)), stack)?;
+        merge_config_values(&mut merged, included);
+    }
+    stack.pop();
 
-        if let Some(lifecycle) = table.get("lifecycle") {
-            if let toml::Value::Table(lifecycle_table) = lifecycle {
-                warn_table(
-                    lifecycle_table,
-                    &[
-                        "graceful_shutdown_wait_secs",
-                        "sigterm_wait_secs",
-                        "sigkill_wait_secs",
-                    ],
-                    "[lifecycle]",
-                    &mut warnings,
-                );
-            } else {
-                warnings.push("Expected [lifecycle] to be a table.".to_string());
-            }
-        }
+    merge_config_values(&mut merged, value);
+    Ok(merged)
+}
 
-        if let Some(bootstrap) = table.get("bootstrap") {
-            if let toml::Value::Table(bootstrap_table) = bootstrap {
-                warn_table(
-                    bootstrap_table,
-                    &["mode", "handshake_timeout_secs", "remote_dir", "env"],
-                    "[bootstrap]",
-                    &mut warnings,
-                );
-                if let Some(env) = bootstrap_table.get("env") {
-                    if !env.is_table() {
-                        warnings.push(
-                            "`[bootstrap].env` must be a table mapping environment variables."
-                                .to_string(),
-                        );
+/// Overlay `overlay` onto `base`: tables merge recursively, `vms` entries
+/// merge by `name`, and any other value replaces the base value.
+fn merge_config_values(base: &mut toml::Value, overlay: toml::Value) {
+    match (base, overlay) {
+        (toml::Value::Table(base), toml::Value::Table(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) if key == "vms" => merge_vm_lists(existing, value),
+                    Some(existing) => merge_config_values(existing, value),
+                    None => {
+                        base.insert(key, value);
                     }
                 }
-            } else {
-                warnings.push("Expected [bootstrap] to be a table.".to_string());
             }
         }
+        (base, overlay) => *base = overlay,
     }
-
-    warnings
-}
-
-fn find_legacy_broker_keys(value: &toml::Value) -> Vec<String> {
-    let mut keys = Vec::new();
-    if let toml::Value::Table(table) = value {
-        if table.contains_key("broker") {
-            keys.push("[broker]".to_string());
-        }
-    }
-    keys
 }
 
-fn warn_table(
-    table: &toml::map::Map<String, toml::Value>,
-    allowed: &[&str],
-    context: &str,
-    warnings: &mut Vec<String>,
-) {
-    for key in table.keys() {
-        if !allowed.contains(&key.as_str()) {
-            warnings.push(format!(
-                "Unknown field `{key}` at {context}; this value will be ignored."
-            ));
+fn merge_vm_lists(base: &mut toml::Value, overlay: toml::Value) {
+    let (toml::Value::Array(base_vms), toml::Value::Array(overlay_vms)) = (&mut *base, &overlay)
+    else {
+        *base = overlay;
+        return;
+    };
+    for vm in overlay_vms {
+        let existing = vm_entry_name(vm).and_then(|name| {
+            base_vms
+                .iter_mut()
+                .find(|candidate| vm_entry_name(candidate) == Some(name))
+        });
+        match existing {
+            Some(existing) => merge_config_values(existing, vm.clone()),
+            None => base_vms.push(vm.clone()),
         }
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-enum SchemaKind {
-    Legacy,
-    MultiInstance,
-}
-
-impl SchemaKind {
-    fn supports_multi_instance(self) -> bool {
-        matches!(self, SchemaKind::MultiInstance)
-    }
-}
-
-fn classify_schema_version(raw: &str) -> Result<(SchemaKind, bool), String> {
-    let (major, minor, _patch) = parse_version_components(raw).ok_or_else(|| {
-        format!(
-            "Configuration version `{raw}` is not a valid semantic version (expected formats like \"0.2.0\")."
-        )
-    })?;
-
-    match (major, minor) {
-        (0, 1) => Ok((SchemaKind::Legacy, false)),
-        (0, 2) => Ok((SchemaKind::MultiInstance, false)),
-        (0, m) if m > 2 => Ok((SchemaKind::MultiInstance, true)),
-        (m, _) if m > 0 => Ok((SchemaKind::MultiInstance, true)),
-        _ => Err(format!(
-            "Configuration version `{raw}` is not supported; use at least \"0.1.0\"."
-        )),
-    }
-}
-
-fn parse_version_components(raw: &str) -> Option<(u64, u64, u64)> {
-    let mut parts = raw.split('.');
-    let major = parts.next()?.parse().ok()?;
-    let minor = parts.next().unwrap_or("0").parse().ok()?;
-    let patch = parts.next().unwrap_or("0").parse().ok()?;
-    if parts.next().is_some() {
-        return None;
-    }
-    Some((major, minor, patch))
+fn vm_entry_name(value: &toml::Value) -> Option<&str> {
+    value.get("name").and_then(toml::Value::as_str)
 }
 
-fn resolve_base_image(
+fn apply_profile(
     path: &Path,
-    context: &str,
-    config_root: &Path,
-    state_root: &Path,
-    base_image: Option<PathBuf>,
-    managed_image: Option<RawManagedImage>,
-) -> Result<BaseImageSource, Error> {
-    if managed_image.is_some() {
+    value: &mut toml::Value,
+    profiles: Option<&toml::Value>,
+    name: &str,
+) -> Result<(), Error> {
+    let available: Vec<&str> = profiles
+        .and_then(toml::Value::as_table)
+        .map(|table| table.keys().map(String::as_str).collect())
+        .unwrap_or_default();
+    let Some(overlay) = profiles.and_then(|profiles| profiles.get(name)) else {
+        let help = if available.is_empty() {
+            "no [profiles] are defined".to_string()
+        } else {
+            format!("available profiles: {}", available.join(", "))
+        };
         return Err(invalid_config(
             path,
-            format!(
-                "{context} declares `managed_image`, which is no longer supported. Remove it and set `base_image` or rely on the default Alpine image."
-            ),
+            format!("Unknown profile `{name}`; {help}."),
         ));
-    }
-
-    if let Some(path_buf) = base_image {
-        return Ok(BaseImageSource::new(
-            resolve_path(config_root, path_buf),
-            BaseImageProvenance::Explicit,
+    };
+    let Some(overlay) = overlay.as_table() else {
+        return Err(invalid_config(
+            path,
+            format!("[profiles.{name}] must be a table."),
         ));
-    }
+    };
+    let toml::Value::Table(root) = value else {
+        return Ok(());
+    };
 
-    Ok(BaseImageSource::new(
-        default_alpine_base_image_path(state_root),
-        BaseImageProvenance::DefaultAlpine,
-    ))
+    for (key, section) in overlay {
+        if !schema::PROFILE.iter().any(|field| field.name == key) {
+            return Err(invalid_config(
+                path,
+                format!(
+                    "[profiles.{name}] cannot override `{key}`; profiles may set {}.",
+                    schema::field_names(schema::PROFILE).join(", ")
+                ),
+            ));
+        }
+        if key != "vms" {
+            match root.get_mut(key) {
+                Some(existing) => merge_config_values(existing, section.clone()),
+                None => {
+                    root.insert(key.clone(), section.clone());
+                }
+            }
+            continue;
+        }
+
+        let Some(vm_overrides) = section.as_table() else {
+            return Err(invalid_config(
+                path,
+                format!("[profiles.{name}.vms] must be a table keyed by VM name."),
+            ));
+        };
+        for (vm_name, overrides) in vm_overrides {
+            let Some(overrides) = overrides.as_table() else {
+                return Err(invalid_config(
+                    path,
+                    format!("[profiles.{name}.vms.{vm_name}] must be a table."),
+                ));
+            };
+            if let Some(key) = overrides.keys().find(|key| {
+                !schema::PROFILE_VM
+                    .iter()
+                    .any(|field| field.name == key.as_str())
+            }) {
+                return Err(invalid_config(
+                    path,
+                    format!(
+                        "[profiles.{name}.vms.{vm_name}] cannot override `{key}`; profiles may set {}.",
+                        schema::field_names(schema::PROFILE_VM).join(", ")
+                    ),
+                ));
+            }
+            let target = root
+                .get_mut("vms")
+                .and_then(toml::Value::as_array_mut)
+                .and_then(|vms| {
+                    vms.iter_mut()
+                        .find(|vm| vm_entry_name(vm) == Some(vm_name.as_str()))
+                })
+                .and_then(toml::Value::as_table_mut)
+                .ok_or_else(|| {
+                    invalid_config(
+                        path,
+                        format!(
+                            "[profiles.{name}.vms.{vm_name}] does not match any [[vms]] entry."
+                        ),
+                    )
+                })?;
+            // An image override replaces the other image source rather than conflicting with it.
+            if overrides.contains_key("base_image") {
+                target.remove("managed_image");
+            }
+            if overrides.contains_key("managed_image") {
+                target.remove("base_image");
+            }
+            for (key, value) in overrides {
+                match target.get_mut(key) {
+                    Some(existing) => merge_config_values(existing, value.clone()),
+                    None => {
+                        target.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
 }
 
-pub fn default_alpine_base_image_path(state_root: &Path) -> PathBuf {
-    state_root
-        .join(DEFAULT_IMAGE_SUBDIR)
-        .join(DEFAULT_ALPINE_IMAGE_FILENAME)
+/// Expand `${VAR}` and `${VAR:-default}` references in every string value
+/// outside [`is_shell_field`]. `$${` escapes a literal `${`.
+fn interpolate_env(path: &Path, value: &mut toml::Value, location: &str) -> Result<(), Error> {
+    match value {
+        toml::Value::String(text) if text.contains("${") => {
+            *text = interpolate_str(text)
+                .map_err(|err| invalid_config(path, format!("{err} (at `{location}`).")))?;
+        }
+        toml::Value::Array(items) => {
+            for (index, item) in items.iter_mut().enumerate() {
+                interpolate_env(path, item, &format!("{location}[{index}]"))?;
+            }
+        }
+        toml::Value::Table(table) => {
+            for (key, item) in table.iter_mut() {
+                let location = if location.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{location}.{key}")
+                };
+                if is_shell_field(&location) {
+                    continue;
+                }
+                interpolate_env(path, item, &location)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
 }
 
-pub(crate) fn default_overlay_base_path(state_root: &Path, role_name: &str) -> PathBuf {
-    let mut base = state_root.join(DEFAULT_OVERLAY_SUBDIR);
-    let slug = overlay_role_slug(role_name);
-    let file_name = format!(
-        "{slug}-{}.{}",
-        DEFAULT_OVERLAY_SUFFIX, DEFAULT_OVERLAY_EXTENSION
-    );
-    base.push(file_name);
-    base
+/// Whether the value at `location` is shell code run later (`[workflows]`
+/// commands and `verify_command`). Its `${...}` belongs to that shell, so
+/// interpolation leaves it alone.
+fn is_shell_field(location: &str) -> bool {
+    location == "workflows"
+        || location.starts_with("workflows.")
+        || location == "verify_command"
+        || location.ends_with(".verify_command")
 }
 
-fn overlay_role_slug(role_name: &str) -> String {
-    let mut slug = String::with_capacity(role_name.len());
-    let mut last_was_dash = true;
-    for ch in role_name.chars() {
-        if ch.is_ascii_alphanumeric() {
-            slug.push(ch.to_ascii_lowercase());
-            last_was_dash = false;
-        } else if !last_was_dash {
-            slug.push('-');
-            last_was_dash = true;
+fn interpolate_str(input: &str) -> Result<String, String> {
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(start) = rest.find('$') {
+        output.push_str(&rest[..start]);
+        let after = &rest[start..];
+        if let Some(escaped) = after.strip_prefix("$${") {
+            output.push_str("${");
+            rest = escaped;
+            continue;
+        }
+        let Some(body) = after.strip_prefix("${") else {
+            output.push('$');
+            rest = &after[1..];
+            continue;
+        };
+        let end = body
+            .find('}')
+            .ok_or_else(|| format!("Unterminated `${{` in `{input}`"))?;
+        let expr = &body[..end];
+        let (name, default) = match expr.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (expr, None),
+        };
+        let valid_name = !name.is_empty()
+            && name
+                .chars()
+                .all(|ch| ch.is_ascii_alphanumeric() || ch == '_');
+        if !valid_name {
+            return Err(format!("Invalid environment reference `${{{expr}}}`"));
         }
+        match (
+            env::var(name).ok().filter(|value| !value.is_empty()),
+            default,
+        ) {
+            (Some(value), _) => output.push_str(&value),
+            (None, Some(default)) => output.push_str(default),
+            (None, None) => {
+                return Err(format!(
+                    "Environment variable `{name}` is not set; use `${{{name}:-default}}` to provide a fallback"
+                ));
+            }
+        }
+        rest = &body[end + 1..];
     }
+    output.push_str(rest);
+    Ok(output)
+}
 
-    let trimmed = slug.trim_matches('-');
-    let base = if trimmed.is_empty() {
-        "vm".to_string()
-    } else {
-        trimmed.to_string()
-    };
-
-    let mut hasher = Sha256::new();
-    hasher.update(role_name.as_bytes());
-    let digest = hasher.finalize();
-    let suffix = hex::encode(&digest[..3]);
+fn invalid_config(path: &Path, message: impl Into<String>) -> Error {
+    Error::InvalidConfig {
+        path: path.to_path_buf(),
+        message: message.into(),
+    }
+}
 
-    format!("{base}-{suffix}")
+fn detect_unknown_fields(value: &toml::Value) -> Vec<String> {
+    schema::unknown_field_warnings(value)
 }
 
-fn parse_port_forwards_list(
+/// Resolve a role's `bootstrap.stages` list against the shared
+/// `[bootstrap.stages]` definitions.
+fn resolve_stages(
     path: &Path,
-    scope: &str,
-    raw_forwards: &[RawPortForward],
-    warnings: &mut Vec<String>,
-) -> Result<Vec<PortForward>, Error> {
-    let mut forwards = Vec::with_capacity(raw_forwards.len());
-    for forward in raw_forwards {
-        let host = forward.host.ok_or_else(|| {
+    role_name: &str,
+    project_root: &Path,
+    state_root: &Path,
+    shared: &BTreeMap<String, RawStage>,
+    refs: Vec<RawStageRef>,
+) -> Result<Vec<BootstrapStage>, Error> {
+    let mut stages: Vec<BootstrapStage> = Vec::new();
+    for raw in refs {
+        let raw = match raw {
+            RawStageRef::Name(name) => {
+                let base = shared.get(&name).ok_or_else(|| {
+                    invalid_config(
+                        path,
+                        format!(
+                            "VM `{role_name}` references unknown bootstrap stage `{name}`. Define it under `[bootstrap.stages.{name}]` or inline it with a `script`."
+                        ),
+                    )
+                })?;
+                RawStage {
+                    name: Some(name),
+                    ..base.clone()
+                }
+            }
+            RawStageRef::Inline(inline) => {
+                match inline.name.as_ref().and_then(|name| shared.get(name)) {
+                    Some(base) => inline.over(base),
+                    None => *inline,
+                }
+            }
+        };
+
+        let name = raw.name.ok_or_else(|| {
             invalid_config(
                 path,
                 format!(
-                    "Port forward on {scope} is missing required `host` port. Example: `host = 2222`."
+                    "VM `{role_name}` has a bootstrap stage without `name`. Example: `stages = [{{ name = \"toolchain\", script = \"bootstrap/toolchain.sh\" }}]`."
                 ),
             )
         })?;
-        if host == 0 {
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|ch| ch.is_ascii_alphanumeric() || matches!(ch, '-' | '_' | '.'))
+        {
             return Err(invalid_config(
-                path,
-                format!("Port forward on {scope} must use a host port between 1 and 65535."),
-            ));
-        }
-
-        let guest = forward.guest.ok_or_else(|| {
-            invalid_config(
                 path,
                 format!(
-                    "Port forward on {scope} is missing required `guest` port. Example: `guest = 22`."
+                    "VM `{role_name}` has bootstrap stage `{name}`; stage names may only use letters, digits, `-`, `_` and `.`."
                 ),
-            )
-        })?;
-        if guest == 0 {
+            ));
+        }
+        if stages.iter().any(|stage| stage.name == name) {
             return Err(invalid_config(
                 path,
-                format!("Port forward on {scope} must use a guest port between 1 and 65535."),
+                format!("VM `{role_name}` lists bootstrap stage `{name}` more than once."),
             ));
         }
-
-        let protocol_raw = forward.protocol.clone();
-        let protocol = protocol_raw
-            .as_deref()
-            .map(PortProtocol::from_str)
-            .unwrap_or(Some(PortProtocol::Tcp))
+        let script = raw
+            .script
+            .filter(|script| !script.as_os_str().is_empty())
             .ok_or_else(|| {
                 invalid_config(
                     path,
-                    format!(
-                        "Port forward on {scope} has unsupported protocol `{}`. Supported values: `tcp`, `udp`.",
-                        protocol_raw.unwrap()
-                    ),
+                    format!("Bootstrap stage `{name}` for VM `{role_name}` must set `script`."),
                 )
             })?;
+        if raw.timeout_secs == Some(0) {
+            return Err(invalid_config(
+                path,
+                format!(
+                    "Bootstrap stage `{name}` for VM `{role_name}` declares `timeout_secs = 0`; specify at least 1 second."
+                ),
+            ));
+        }
+        let run_if = match raw.run_if {
+            Some(value) => value.parse().map_err(|err: String| {
+                invalid_config(
+                    path,
+                    format!("Bootstrap stage `{name}` for VM `{role_name}`: {err}"),
+                )
+            })?,
+            None => BootstrapRunIf::default(),
+        };
+        let verify = if raw.verify_command.is_some() || raw.verify_path.is_some() {
+            Some(BootstrapVerifyConfig {
+                command: raw.verify_command,
+                path: raw.verify_path,
+            })
+        } else {
+            None
+        };
 
-        forwards.push(PortForward {
-            host,
-            guest,
-            protocol,
+        let context = format!("Bootstrap stage `{name}` for VM `{role_name}`");
+        let env_secrets = resolve_env_secrets(path, &context, project_root, raw.env_secrets)?;
+        let payload = match raw.payload {
+            Some(payload) => resolve_payload(path, &context, project_root, state_root, payload)?,
+            None => None,
+        };
+
+        stages.push(BootstrapStage {
+            name,
+            script: resolve_path(project_root, script),
+            payload,
+            env: raw.env,
+            env_secrets,
+            timeout_secs: raw.timeout_secs,
+            verify,
+            run_if,
         });
     }
+    Ok(stages)
+}
 
-    let mut guest_port_usage: HashMap<(u16, PortProtocol), usize> = HashMap::new();
-    for forward in &forwards {
-        let counter = guest_port_usage
-            .entry((forward.guest, forward.protocol))
-            .or_default();
-        *counter += 1;
-    }
-    for ((guest_port, protocol), count) in guest_port_usage {
-        if count > 1 {
-            warnings.push(format!(
-                "{scope} declares {count} forwards for guest port {guest_port}/{protocol}; consider consolidating."
+/// Validate a role's `collect` rules, returning `(guest, host)` pairs whose
+/// host side still holds the `{vm}` placeholder.
+fn resolve_collect(
+    path: &Path,
+    role_name: &str,
+    raw: Vec<RawCollectRule>,
+) -> Result<Vec<(String, String)>, Error> {
+    let invalid =
+        |message: String| invalid_config(path, format!("VM `{role_name}` collect: {message}"));
+    let mut rules = Vec::with_capacity(raw.len());
+    for rule in raw {
+        let (Some(guest), Some(host)) = (rule.guest, rule.host) else {
+            return Err(invalid(
+                "every rule needs `guest` and `host`. Example: `collect = [{ guest = \"/var/log/app\", host = \"artifacts/{vm}\" }]`.".to_string(),
             ));
+        };
+        let trimmed = guest.trim_end_matches('/');
+        if !guest.starts_with('/') || trimmed.is_empty() || guest.contains('\0') {
+            return Err(invalid(format!(
+                "guest path `{guest}` must be an absolute path below `/`."
+            )));
+        }
+        if trimmed
+            .split('/')
+            .any(|component| component == "." || component == "..")
+        {
+            return Err(invalid(format!(
+                "guest path `{guest}` must not contain `.` or `..` components."
+            )));
+        }
+        if host.trim().is_empty() {
+            return Err(invalid(format!("host directory for `{guest}` is empty.")));
         }
+        rules.push((trimmed.to_string(), host));
     }
-
-    Ok(forwards)
+    Ok(rules)
 }
 
-fn parse_replica_index(role_name: &str, id: &str) -> Result<usize, String> {
-    let prefix = format!("{role_name}-");
-    if !id.starts_with(&prefix) {
-        return Err(format!(
-            "Replica override id `{id}` must match `<{role_name}>-<index>`."
-        ));
+/// Validate a role's `[vms.provision]` section. Returns `None` when it
+/// declares nothing.
+fn resolve_provision(
+    path: &Path,
+    role_name: &str,
+    project_root: &Path,
+    raw: RawProvision,
+) -> Result<Option<ProvisionConfig>, Error> {
+    let invalid = |message: String| {
+        invalid_config(path, format!("VM `{role_name}` [vms.provision]: {message}"))
+    };
+    let check_word = |kind: &str, value: &str, extra: &[char]| {
+        if value.is_empty()
+            || !value
+                .chars()
+                .all(|ch| ch.is_ascii_alphanumeric() || "._-".contains(ch) || extra.contains(&ch))
+        {
+            return Err(invalid(format!("invalid {kind} `{value}`.")));
+        }
+        Ok(())
+    };
+
+    let mut packages = Vec::new();
+    for raw in raw.packages {
+        let package = match raw {
+            RawProvisionPackage::Name(name) => ProvisionPackage {
+                apk: Some(name.clone()),
+                apt: Some(name.clone()),
+                dnf: Some(name),
+            },
+            RawProvisionPackage::PerManager(RawPackageNames {
+                name,
+                apk,
+                apt,
+                dnf,
+            }) => ProvisionPackage {
+                apk: apk.or_else(|| name.clone()),
+                apt: apt.or_else(|| name.clone()),
+                dnf: dnf.or(name),
+            },
+        };
+        let names: Vec<&String> = [&package.apk, &package.apt, &package.dnf]
+            .into_iter()
+            .flatten()
+            .collect();
+        if names.is_empty() {
+            return Err(invalid(
+                "package entries need `name` or at least one of `apk`, `apt`, `dnf`.".to_string(),
+            ));
+        }
+        for name in names {
+            check_word("package name", name, &['+', ':', '=', '~'])?;
+        }
+        packages.push(package);
     }
 
-    let suffix = &id[prefix.len()..];
-    if suffix.is_empty() {
-        return Err(format!(
-            "Replica override id `{id}` is missing the numeric `<index>` portion."
-        ));
+    let mut files: Vec<ProvisionFile> = Vec::new();
+    for raw in raw.files {
+        let file_path = raw
+            .path
+            .ok_or_else(|| invalid("every file needs `path`.".to_string()))?;
+        if !file_path.starts_with('/') || file_path.ends_with('/') || file_path.contains('\0') {
+            return Err(invalid(format!(
+                "file path `{file_path}` must be an absolute path to a file."
+            )));
+        }
+        if files.iter().any(|file| file.path == file_path) {
+            return Err(invalid(format!(
+                "file `{file_path}` is declared more than once."
+            )));
+        }
+        let contents = match (raw.content, raw.source) {
+            (Some(content), None) => ProvisionFileContents::Inline(content),
+            (None, Some(source)) => {
+                ProvisionFileContents::Source(resolve_path(project_root, source))
+            }
+            _ => {
+                return Err(invalid(format!(
+                    "file `{file_path}` must set exactly one of `content` or `source`."
+                )));
+            }
+        };
+        let mode = match raw.mode {
+            Some(mode) => Some(
+                u32::from_str_radix(mode.trim_start_matches("0o"), 8)
+                    .ok()
+                    .filter(|bits| *bits <= 0o7777)
+                    .ok_or_else(|| {
+                        invalid(format!(
+                            "file `{file_path}` has mode `{mode}`; use octal such as `0644`."
+                        ))
+                    })?,
+            ),
+            None => None,
+        };
+        if let Some(owner) = raw.owner.as_deref() {
+            for part in owner.splitn(2, ':') {
+                check_word("owner", part, &[])?;
+            }
+        }
+        files.push(ProvisionFile {
+            path: file_path,
+            contents,
+            mode,
+            owner: raw.owner,
+        });
     }
 
-    let index: usize = suffix
-        .parse()
-        .map_err(|_| format!("Replica override id `{id}` contains a non-numeric index."))?;
+    let mut users: Vec<ProvisionUser> = Vec::new();
+    for raw in raw.users {
+        let name = raw
+            .name
+            .ok_or_else(|| invalid("every user needs `name`.".to_string()))?;
+        check_word("user name", &name, &[])?;
+        if users.iter().any(|user| user.name == name) {
+            return Err(invalid(format!(
+                "user `{name}` is declared more than once."
+            )));
+        }
+        for group in &raw.groups {
+            check_word("group", group, &[])?;
+        }
+        if raw.authorized_keys.iter().any(|key| key.contains('\n')) {
+            return Err(invalid(format!(
+                "authorized_keys for user `{name}` must be single-line keys."
+            )));
+        }
+        users.push(ProvisionUser {
+            name,
+            groups: raw.groups,
+            shell: raw.shell.filter(|shell| !shell.is_empty()),
+            authorized_keys: raw.authorized_keys,
+        });
+    }
 
-    let canonical = format!("{role_name}-{index}");
-    if canonical != id {
-        return Err(format!(
-            "Replica override id `{id}` must not include leading zeros; expected `{canonical}`."
-        ));
+    for service in &raw.services {
+        check_word("service", service, &['@'])?;
+    }
+    for (key, value) in &raw.sysctls {
+        check_word("sysctl key", key, &['/'])?;
+        if value.contains('\n') {
+            return Err(invalid(format!(
+                "sysctl `{key}` must be a single-line value."
+            )));
+        }
     }
 
-    Ok(index)
+    if packages.is_empty()
+        && files.is_empty()
+        && users.is_empty()
+        && raw.services.is_empty()
+        && raw.sysctls.is_empty()
+    {
+        return Ok(None);
+    }
+    Ok(Some(ProvisionConfig {
+        source: path.to_path_buf(),
+        packages,
+        files,
+        users,
+        services: raw.services,
+        sysctls: raw.sysctls,
+    }))
 }
 
-fn derive_overlay_for_instance(
-    base: &Path,
-    index: usize,
-    total: usize,
-    multi_enabled: bool,
-) -> PathBuf {
-    if !multi_enabled || total <= 1 || index == 0 {
-        return base.to_path_buf();
+/// Fill every VM's `bootstrap.template_vars` once all replicas are expanded.
+///
+/// Peers are reached through the host's forwards, so each VM is described by
+/// [`GUEST_HOST_ADDRESS`] and its forwarded host ports.
+fn resolve_template_vars(project_name: &str, vms: &mut [VmDefinition]) {
+    let ssh_port = |vm: &VmDefinition| {
+        vm.port_forwards
+            .iter()
+            .find(|forward| forward.protocol == PortProtocol::Tcp && forward.guest == 22)
+            .map(|forward| forward.host.to_string())
+            .unwrap_or_default()
+    };
+    let names: Vec<&str> = vms.iter().map(|vm| vm.name.as_str()).collect();
+    let mut shared = BTreeMap::from([
+        ("project".to_string(), project_name.to_string()),
+        ("host_address".to_string(), GUEST_HOST_ADDRESS.to_string()),
+        ("vms".to_string(), names.join(" ")),
+    ]);
+    let mut role_members: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for vm in vms.iter() {
+        let prefix = format!("vms.{}", vm.name);
+        shared.insert(format!("{prefix}.address"), GUEST_HOST_ADDRESS.to_string());
+        shared.insert(format!("{prefix}.ssh_port"), ssh_port(vm));
+        for forward in &vm.port_forwards {
+            shared.insert(
+                format!("{prefix}.{}.{}", forward.protocol, forward.guest),
+                forward.host.to_string(),
+            );
+        }
+        role_members
+            .entry(vm.role_name.as_str())
+            .or_default()
+            .push(vm.name.as_str());
+    }
+    let role_members: BTreeMap<String, Vec<String>> = role_members
+        .into_iter()
+        .map(|(role, members)| {
+            (
+                role.to_string(),
+                members.into_iter().map(str::to_string).collect(),
+            )
+        })
+        .collect();
+
+    for vm in vms.iter_mut() {
+        let members = &role_members[&vm.role_name];
+        let mut vars = shared.clone();
+        vars.extend([
+            ("vm".to_string(), vm.name.clone()),
+            ("role".to_string(), vm.role_name.clone()),
+            ("replica".to_string(), vm.replica_index.to_string()),
+            ("replicas".to_string(), members.len().to_string()),
+            ("role_members".to_string(), members.join(" ")),
+            ("ssh_port".to_string(), ssh_port(vm)),
+        ]);
+        vm.bootstrap.template_vars = vars;
     }
+}
 
-    if let Some(file_name) = base.file_name() {
-        if let Some(name) = file_name.to_str() {
-            let (stem, ext) = split_file_name(name);
-            let new_file = if let Some(ext) = ext {
-                format!("{stem}-{index}.{ext}")
-            } else {
-                format!("{stem}-{index}")
+/// Resolve `depends_on` entries (role or instance names) onto the expanded
+/// VMs and reject unknown names and cycles.
+fn resolve_dependencies(
+    path: &Path,
+    vms: &mut [VmDefinition],
+    role_dependencies: Vec<(String, Vec<RawDependency>)>,
+) -> Result<(), Error> {
+    for (role_name, raw_dependencies) in role_dependencies {
+        let mut resolved: Vec<VmDependency> = Vec::new();
+        for raw in raw_dependencies {
+            let (target, condition) = match raw {
+                RawDependency::Name(name) => (name, DependencyCondition::default()),
+                RawDependency::Detailed(RawDependencyTarget { vm, condition }) => {
+                    let target = vm.ok_or_else(|| {
+                        invalid_config(
+                            path,
+                            format!(
+                                "VM `{role_name}` has a `depends_on` entry without `vm`. Example: `depends_on = [{{ vm = \"db\", condition = \"healthy\" }}]`."
+                            ),
+                        )
+                    })?;
+                    let condition = match condition {
+                        Some(raw) => raw.parse().map_err(|err: String| {
+                            invalid_config(path, format!("VM `{role_name}`: {err}"))
+                        })?,
+                        None => DependencyCondition::default(),
+                    };
+                    (target, condition)
+                }
             };
-            return base.with_file_name(new_file);
+
+            if target == role_name {
+                return Err(invalid_config(
+                    path,
+                    format!("VM `{role_name}` cannot depend on itself."),
+                ));
+            }
+            let targets: Vec<String> = vms
+                .iter()
+                .filter(|vm| vm.role_name == target || vm.name == target)
+                .map(|vm| vm.name.clone())
+                .collect();
+            if targets.is_empty() {
+                return Err(invalid_config(
+                    path,
+                    format!(
+                        "VM `{role_name}` depends on unknown VM `{target}`. Use a `[[vms]]` name or an expanded replica name such as `{target}-0`."
+                    ),
+                ));
+            }
+            for vm in targets {
+                match resolved.iter_mut().find(|existing| existing.vm == vm) {
+                    Some(existing) => existing.condition = existing.condition.max(condition),
+                    None => resolved.push(VmDependency { vm, condition }),
+                }
+            }
         }
 
-        let mut os_name = file_name.to_os_string();
-        os_name.push(format!("-{index}"));
-        return base.with_file_name(PathBuf::from(os_name));
+        for vm in vms.iter_mut().filter(|vm| vm.role_name == role_name) {
+            vm.depends_on = resolved
+                .iter()
+                .filter(|dependency| dependency.vm != vm.name)
+                .cloned()
+                .collect();
+        }
     }
 
-    base.join(format!("{index}"))
+    if let Some(cycle) = find_dependency_cycle(vms) {
+        return Err(invalid_config(
+            path,
+            format!("`depends_on` forms a cycle: {}.", cycle.join(" → ")),
+        ));
+    }
+    Ok(())
 }
 
-fn split_file_name(name: &str) -> (&str, Option<&str>) {
-    if let Some((stem, ext)) = name.rsplit_once('.') {
-        if !stem.is_empty() {
-            return (stem, Some(ext));
+/// First dependency cycle found, as VM names ending where it started.
+fn find_dependency_cycle(vms: &[VmDefinition]) -> Option<Vec<String>> {
+    fn visit(
+        index: usize,
+        vms: &[VmDefinition],
+        state: &mut [u8],
+        stack: &mut Vec<usize>,
+    ) -> Option<Vec<String>> {
+        state[index] = 1;
+        stack.push(index);
+        for dependency in &vms[index].depends_on {
+            let Some(next) = vms.iter().position(|vm| vm.name == dependency.vm) else {
+                continue;
+            };
+            match state[next] {
+                0 => {
+                    if let Some(cycle) = visit(next, vms, state, stack) {
+                        return Some(cycle);
+                    }
+                }
+                1 => {
+                    let start = stack.iter().position(|&entry| entry == next).unwrap_or(0);
+                    let mut cycle: Vec<String> = stack[start..]
+                        .iter()
+                        .map(|&entry| vms[entry].name.clone())
+                        .collect();
+                    cycle.push(vms[next].name.clone());
+                    return Some(cycle);
+                }
+                _ => {}
+            }
         }
+        stack.pop();
+        state[index] = 2;
+        None
     }
-    (name, None)
-}
 
-#[derive(Debug, Deserialize)]
-struct RawConfig {
-    version: Option<String>,
-    project: Option<RawProject>,
-    #[serde(default)]
-    vms: Vec<RawVm>,
-    #[serde(default)]
-    workflows: RawWorkflows,
-    #[serde(default)]
-    lifecycle: Option<RawLifecycle>,
-    #[serde(default)]
-    bootstrap: Option<RawBootstrap>,
+    let mut state = vec![0u8; vms.len()];
+    let mut stack = Vec::new();
+    for index in 0..vms.len() {
+        if state[index] == 0
+            && let Some(cycle) = visit(index, vms, &mut state, &mut stack)
+        {
+            return Some(cycle);
+        }
+    }
+    None
 }
 
-#[derive(Debug, Deserialize)]
-struct RawProject {
-    name: Option<String>,
-    #[serde(default)]
-    state_dir: Option<PathBuf>,
-    #[serde(default)]
-    features: RawProjectFeatures,
+fn find_legacy_broker_keys(value: &toml::Value) -> Vec<String> {
+    let mut keys = Vec::new();
+    if let toml::Value::Table(table) = value {
+        if table.contains_key("broker") {
+            keys.push("[broker]".to_string());
+        }
+    }
+    keys
 }
 
-#[derive(Debug, Deserialize, Default)]
-struct RawProjectFeatures {
-    #[serde(default)]
-    enable_vm_vizier: Option<bool>,
+#[derive(Debug, Clone, Copy)]
+enum SchemaKind {
+    Legacy,
+    MultiInstance,
 }
 
-impl RawProjectFeatures {
-    fn into_features(self, warnings: &mut Vec<String>) -> ProjectFeatures {
-        if self.enable_vm_vizier.is_some() {
-            warnings.push(
-                "[project.features] enable_vm_vizier is deprecated and ignored; the Vizier service has been removed."
-                    .to_string(),
-            );
-        }
-        ProjectFeatures
+impl SchemaKind {
+    fn supports_multi_instance(self) -> bool {
+        matches!(self, SchemaKind::MultiInstance)
     }
 }
 
-#[derive(Debug, Deserialize)]
-struct RawVm {
-    name: Option<String>,
-    #[serde(default)]
-    description: Option<String>,
-    base_image: Option<PathBuf>,
-    #[serde(default)]
-    managed_image: Option<RawManagedImage>,
-    overlay: Option<PathBuf>,
-    #[serde(default)]
-    cpus: Option<u32>,
-    #[serde(default)]
-    memory: Option<String>,
-    #[serde(default, rename = "port_forwards")]
-    port_forwards: Vec<RawPortForward>,
-    #[serde(default)]
-    count: Option<u32>,
-    #[serde(default)]
-    instances: Vec<RawVmInstance>,
-    #[serde(default)]
-    bootstrap: Option<RawVmBootstrap>,
-}
+fn classify_schema_version(raw: &str) -> Result<(SchemaKind, bool), String> {
+    let (major, minor, _patch) = parse_version_components(raw).ok_or_else(|| {
+        format!(
+            "Configuration version `{raw}` is not a valid semantic version (expected formats like \"0.2.0\")."
+        )
+    })?;
 
-#[derive(Debug, Deserialize)]
-struct RawPortForward {
-    host: Option<u16>,
-    guest: Option<u16>,
-    #[serde(default)]
-    protocol: Option<String>,
+    match (major, minor) {
+        (0, 1) => Ok((SchemaKind::Legacy, false)),
+        (0, 2) => Ok((SchemaKind::MultiInstance, false)),
+        (0, m) if m > 2 => Ok((SchemaKind::MultiInstance, true)),
+        (m, _) if m > 0 => Ok((SchemaKind::MultiInstance, true)),
+        _ => Err(format!(
+            "Configuration version `{raw}` is not supported; use at least \"0.1.0\"."
+        )),
+    }
 }
 
-#[derive(Debug, Deserialize)]
-struct RawVmInstance {
-    id: Option<String>,
-    #[serde(default)]
-    description: Option<String>,
-    #[serde(default)]
-    base_image: Option<PathBuf>,
-    #[serde(default)]
-    managed_image: Option<RawManagedImage>,
-    #[serde(default)]
-    overlay: Option<PathBuf>,
-    #[serde(default)]
-    cpus: Option<u32>,
-    #[serde(default)]
-    memory: Option<String>,
-    #[serde(default, rename = "port_forwards")]
-    port_forwards: Option<Vec<RawPortForward>>,
+fn parse_version_components(raw: &str) -> Option<(u64, u64, u64)> {
+    let mut parts = raw.split('.');
+    let major = parts.next()?.parse().ok()?;
+    let minor = parts.next().unwrap_or("0").parse().ok()?;
+    let patch = parts.next().unwrap_or("0").parse().ok()?;
+    if parts.next().is_some() {
+        return None;
+    }
+    Some((major, minor, patch))
 }
 
-#[derive(Debug, Deserialize)]
-#[allow(dead_code)]
-struct RawManagedImage {
-    name: Option<String>,
-    version: Option<String>,
-    #[serde(default)]
-    disk: Option<String>,
-    #[serde(default)]
-    checksum: Option<String>,
-    #[serde(default)]
-    size_bytes: Option<u64>,
-}
+fn resolve_base_image(
+    path: &Path,
+    context: &str,
+    config_root: &Path,
+    state_root: &Path,
+    base_image: Option<PathBuf>,
+    managed_image: Option<RawManagedImage>,
+) -> Result<BaseImageSource, Error> {
+    if managed_image.is_some() {
+        return Err(invalid_config(
+            path,
+            format!(
+                "{context} declares `managed_image`, which is no longer supported. Remove it and set `base_image` or rely on the default Alpine image."
+            ),
+        ));
+    }
 
-#[derive(Debug, Deserialize)]
-struct RawBootstrap {
-    #[serde(default)]
-    mode: Option<String>,
-    #[serde(default)]
-    handshake_timeout_secs: Option<u64>,
-    #[serde(default)]
-    remote_dir: Option<PathBuf>,
-    #[serde(default)]
-    env: HashMap<String, String>,
-}
+    if let Some(path_buf) = base_image {
+        return Ok(BaseImageSource::new(
+            resolve_path(config_root, path_buf),
+            BaseImageProvenance::Explicit,
+        ));
+    }
 
-#[derive(Debug, Deserialize)]
-struct RawVmBootstrap {
-    #[serde(default)]
-    mode: Option<String>,
-    #[serde(default)]
-    script: Option<PathBuf>,
-    #[serde(default)]
-    payload: Option<PathBuf>,
-    #[serde(default)]
-    handshake_timeout_secs: Option<u64>,
-    #[serde(default)]
-    remote_dir: Option<PathBuf>,
-    #[serde(default)]
-    env: HashMap<String, String>,
-    #[serde(default)]
-    verify_command: Option<String>,
-    #[serde(default)]
-    verify_path: Option<PathBuf>,
+    Ok(BaseImageSource::new(
+        default_alpine_base_image_path(state_root),
+        BaseImageProvenance::DefaultAlpine,
+    ))
 }
 
-#[derive(Debug)]
-struct InstanceOverride {
-    id: String,
-    data: InstanceOverrideData,
+pub fn default_alpine_base_image_path(state_root: &Path) -> PathBuf {
+    state_root
+        .join(DEFAULT_IMAGE_SUBDIR)
+        .join(DEFAULT_ALPINE_IMAGE_FILENAME)
 }
 
-#[derive(Debug)]
-struct InstanceOverrideData {
-    description: Option<String>,
-    base_image: Option<PathBuf>,
-    managed_image: Option<RawManagedImage>,
-    overlay: Option<PathBuf>,
-    cpus: Option<u32>,
-    memory: Option<String>,
-    port_forwards: Option<Vec<RawPortForward>>,
+pub(crate) fn default_overlay_base_path(state_root: &Path, role_name: &str) -> PathBuf {
+    let mut base = state_root.join(DEFAULT_OVERLAY_SUBDIR);
+    let slug = overlay_role_slug(role_name);
+    let file_name = format!(
+        "{slug}-{}.{}",
+        DEFAULT_OVERLAY_SUFFIX, DEFAULT_OVERLAY_EXTENSION
+    );
+    base.push(file_name);
+    base
 }
 
-#[derive(Debug, Deserialize, Default)]
-struct RawWorkflows {
-    #[serde(default)]
-    init: Vec<String>,
-}
+fn overlay_role_slug(role_name: &str) -> String {
+    let mut slug = String::with_capacity(role_name.len());
+    let mut last_was_dash = true;
+    for ch in role_name.chars() {
+        if ch.is_ascii_alphanumeric() {
+            slug.push(ch.to_ascii_lowercase());
+            last_was_dash = false;
+        } else if !last_was_dash {
+            slug.push('-');
+            last_was_dash = true;
+        }
+    }
 
-#[derive(Debug, Deserialize)]
-struct RawLifecycle {
-    #[serde(default)]
-    graceful_shutdown_wait_secs: Option<u64>,
-    #[serde(default)]
-    sigterm_wait_secs: Option<u64>,
-    #[serde(default)]
-    sigkill_wait_secs: Option<u64>,
-}
+    let trimmed = slug.trim_matches('-');
+    let base = if trimmed.is_empty() {
+        "vm".to_string()
+    } else {
+        trimmed.to_string()
+    };
 
-impl RawLifecycle {
-    fn into_config(self, path: &Path) -> Result<LifecycleConfig, Error> {
-        let graceful = self
-            .graceful_shutdown_wait_secs
-            .unwrap_or(DEFAULT_GRACEFUL_SHUTDOWN_WAIT_SECS);
-        let sigterm = self.sigterm_wait_secs.unwrap_or(DEFAULT_SIGTERM_WAIT_SECS);
-        let sigkill = self.sigkill_wait_secs.unwrap_or(DEFAULT_SIGKILL_WAIT_SECS);
+    let mut hasher = Sha256::new();
+    hasher.update(role_name.as_bytes());
+    let digest = hasher.finalize();
+    let suffix = hex::encode(&digest[..3]);
 
-        if sigkill == 0 {
+    format!("{base}-{suffix}")
+}
+
+fn parse_port_forwards_list(
+    path: &Path,
+    scope: &str,
+    raw_forwards: &[RawPortForward],
+    warnings: &mut Vec<String>,
+) -> Result<Vec<PortForward>, Error> {
+    let mut forwards = Vec::with_capacity(raw_forwards.len());
+    for forward in raw_forwards {
+        let host = forward.host.ok_or_else(|| {
+            invalid_config(
+                path,
+                format!(
+                    "Port forward on {scope} is missing required `host` port. Example: `host = 2222`."
+                ),
+            )
+        })?;
+        if host == 0 {
             return Err(invalid_config(
                 path,
-                "`[lifecycle].sigkill_wait_secs` must be at least 1 to allow the orchestrator to confirm process exit.",
+                format!("Port forward on {scope} must use a host port between 1 and 65535."),
             ));
         }
 
-        Ok(LifecycleConfig {
-            graceful_shutdown_wait_secs: graceful,
-            sigterm_wait_secs: sigterm,
-            sigkill_wait_secs: sigkill,
-        })
-    }
-}
-
-impl RawBootstrap {
-    fn into_config(self, path: &Path) -> Result<BootstrapConfig, Error> {
-        let mode = match self.mode.as_deref() {
-            Some(value) => value
-                .parse::<BootstrapMode>()
-                .map_err(|err| invalid_config(path, err))?,
-            None => BootstrapMode::default(),
-        };
-
-        let handshake_timeout_secs = match self.handshake_timeout_secs {
-            Some(0) => {
-                return Err(invalid_config(
-                    path,
-                    "`[bootstrap].handshake_timeout_secs` must be at least 1 second.",
-                ));
-            }
-            Some(value) => value,
-            None => DEFAULT_BOOTSTRAP_HANDSHAKE_WAIT_SECS,
-        };
+        let guest = forward.guest.ok_or_else(|| {
+            invalid_config(
+                path,
+                format!(
+                    "Port forward on {scope} is missing required `guest` port. Example: `guest = 22`."
+                ),
+            )
+        })?;
+        if guest == 0 {
+            return Err(invalid_config(
+                path,
+                format!("Port forward on {scope} must use a guest port between 1 and 65535."),
+            ));
+        }
 
-        let remote_dir = match self.remote_dir {
-            Some(dir) if dir.as_os_str().is_empty() => {
-                return Err(invalid_config(
+        let protocol_raw = forward.protocol.clone();
+        let protocol = protocol_raw
+            .as_deref()
+            .map(PortProtocol::from_str)
+            .unwrap_or(Some(PortProtocol::Tcp))
+            .ok_or_else(|| {
+                invalid_config(
                     path,
-                    "`[bootstrap].remote_dir` must not be empty.",
-                ));
-            }
-            Some(dir) => dir,
-            None => PathBuf::from("/tmp/castra-bootstrap"),
-        };
+                    format!(
+                        "Port forward on {scope} has unsupported protocol `{}`. Supported values: `tcp`, `udp`.",
+                        protocol_raw.unwrap()
+                    ),
+                )
+            })?;
 
-        Ok(BootstrapConfig {
-            mode,
-            handshake_timeout_secs,
-            remote_dir,
-            env: self.env,
-        })
+        forwards.push(PortForward {
+            host,
+            guest,
+            protocol,
+        });
     }
-}
 
-impl RawVmBootstrap {
-    fn resolve_mode(&self, path: &Path, context: &str) -> Result<Option<BootstrapMode>, Error> {
-        match self.mode.as_deref() {
-            Some(value) => value
-                .parse::<BootstrapMode>()
-                .map_err(|_| {
-                    invalid_config(
-                        path,
-                        format!(
-                            "{context} has unknown bootstrap.mode `{value}`. Supported values: auto, skip, always."
-                        ),
-                    )
-                })
-                .map(Some),
-            None => Ok(None),
+    let mut guest_port_usage: HashMap<(u16, PortProtocol), usize> = HashMap::new();
+    for forward in &forwards {
+        let counter = guest_port_usage
+            .entry((forward.guest, forward.protocol))
+            .or_default();
+        *counter += 1;
+    }
+    for ((guest_port, protocol), count) in guest_port_usage {
+        if count > 1 {
+            warnings.push(format!(
+                "{scope} declares {count} forwards for guest port {guest_port}/{protocol}; consider consolidating."
+            ));
         }
     }
+
+    Ok(forwards)
 }
 
-impl RawConfig {
-    fn into_validated(
-        self,
-        path: &Path,
-        warnings: &mut Vec<String>,
-    ) -> Result<ProjectConfig, Error> {
-        let RawConfig {
-            version,
-            project,
-            vms,
-            workflows,
-            lifecycle,
-            bootstrap: bootstrap_raw,
-        } = self;
+fn parse_replica_index(role_name: &str, id: &str) -> Result<usize, String> {
+    let prefix = format!("{role_name}-");
+    if !id.starts_with(&prefix) {
+        return Err(format!(
+            "Replica override id `{id}` must match `<{role_name}>-<index>`."
+        ));
+    }
 
-        let version = version.ok_or_else(|| {
-            invalid_config(
-                path,
-                "Missing required top-level field `version`. Example: `version = \"0.1.0\"`.",
-            )
-        })?;
+    let suffix = &id[prefix.len()..];
+    if suffix.is_empty() {
+        return Err(format!(
+            "Replica override id `{id}` is missing the numeric `<index>` portion."
+        ));
+    }
 
-        let (schema, warn_future) =
-            classify_schema_version(&version).map_err(|message| invalid_config(path, message))?;
-        if warn_future {
-            warnings.push(format!(
-                "Configuration version `{version}` is not fully supported yet; proceeding anyway."
-            ));
-        }
+    let index: usize = suffix
+        .parse()
+        .map_err(|_| format!("Replica override id `{id}` contains a non-numeric index."))?;
 
-        let raw_project = project.ok_or_else(|| {
-            invalid_config(
-                path,
-                "Missing required table `[project]`. Example:\n\
-                 [project]\n\
-                 name = \"devbox\"",
-            )
-        })?;
+    let canonical = format!("{role_name}-{index}");
+    if canonical != id {
+        return Err(format!(
+            "Replica override id `{id}` must not include leading zeros; expected `{canonical}`."
+        ));
+    }
 
-        let project_name = raw_project.name.ok_or_else(|| {
-            invalid_config(
-                path,
-                "Missing required field `project.name`. Example: `name = \"devbox\"`.",
-            )
-        })?;
+    Ok(index)
+}
 
-        let features = raw_project.features.into_features(warnings);
+fn derive_overlay_for_instance(
+    base: &Path,
+    index: usize,
+    total: usize,
+    multi_enabled: bool,
+) -> PathBuf {
+    if !multi_enabled || total <= 1 || index == 0 {
+        return base.to_path_buf();
+    }
 
-        let project_root = path.parent().map(Path::to_path_buf).unwrap_or_else(|| {
-            warnings.push(
-                "Unable to determine config directory; assuming current working directory."
-                    .to_string(),
-            );
-            PathBuf::from(".")
-        });
+    if let Some(file_name) = base.file_name() {
+        if let Some(name) = file_name.to_str() {
+            let (stem, ext) = split_file_name(name);
+            let new_file = if let Some(ext) = ext {
+                format!("{stem}-{index}.{ext}")
+            } else {
+                format!("{stem}-{index}")
+            };
+            return base.with_file_name(new_file);
+        }
 
-        let state_root = raw_project
-            .state_dir
-            .map(|dir| resolve_path(&project_root, dir))
-            .unwrap_or_else(|| default_state_root(&project_name, path));
+        let mut os_name = file_name.to_os_string();
+        os_name.push(format!("-{index}"));
+        return base.with_file_name(PathBuf::from(os_name));
+    }
 
-        let bootstrap_config = match bootstrap_raw {
-            Some(raw) => raw.into_config(path)?,
-            None => BootstrapConfig::default(),
-        };
+    base.join(format!("{index}"))
+}
 
-        if vms.is_empty() {
-            return Err(invalid_config(
-                path,
-                "At least one `[[vms]]` entry is required. Example:\n\
-                 [[vms]]\n\
-                 name = \"devbox\"\n\
-                 base_image = \"images/devbox.qcow2\"\n\
-                 overlay = \".castra/devbox-overlay.qcow2\"\n\
-                 (Tip: run `castra init` to scaffold a starter config.)",
-            ));
+fn split_file_name(name: &str) -> (&str, Option<&str>) {
+    if let Some((stem, ext)) = name.rsplit_once('.') {
+        if !stem.is_empty() {
+            return (stem, Some(ext));
         }
+    }
+    (name, None)
+}
 
-        let root_dir = project_root.clone();
-        let supports_multi = schema.supports_multi_instance();
+#[derive(Debug, Deserialize)]
+struct RawConfig {
+    version: Option<String>,
+    project: Option<RawProject>,
+    #[serde(default)]
+    vms: Vec<RawVm>,
+    #[serde(default)]
+    workflows: RawWorkflows,
+    #[serde(default)]
+    lifecycle: Option<RawLifecycle>,
+    #[serde(default)]
+    bootstrap: Option<RawBootstrap>,
+    #[serde(default)]
+    images: Option<RawImages>,
+}
 
-        let mut seen_roles = HashSet::new();
-        let mut seen_instances = HashSet::new();
-        let mut expanded_vms = Vec::new();
+#[derive(Debug, Deserialize)]
+struct RawProject {
+    name: Option<String>,
+    #[serde(default)]
+    state_dir: Option<PathBuf>,
+    #[serde(default)]
+    features: RawProjectFeatures,
+}
 
-        for vm in vms {
-            let RawVm {
-                name,
-                description,
-                base_image: raw_base_image,
-                managed_image: raw_managed_image,
-                overlay: raw_overlay,
-                cpus,
-                memory,
-                port_forwards,
-                count,
-                instances,
-                bootstrap,
-            } = vm;
+#[derive(Debug, Deserialize, Default)]
+struct RawProjectFeatures {
+    #[serde(default)]
+    enable_vm_vizier: Option<bool>,
+}
 
-            let role_name = name.ok_or_else(|| {
-                invalid_config(
-                    path,
-                    "Each `[[vms]]` entry must define `name`. Example: `name = \"devbox\"`.",
-                )
-            })?;
+impl RawProjectFeatures {
+    fn into_features(self, warnings: &mut Vec<String>) -> ProjectFeatures {
+        if self.enable_vm_vizier.is_some() {
+            warnings.push(
+                "[project.features] enable_vm_vizier is deprecated and ignored; the Vizier service has been removed."
+                    .to_string(),
+            );
+        }
+        ProjectFeatures
+    }
+}
 
-            if !seen_roles.insert(role_name.clone()) {
-                return Err(invalid_config(
-                    path,
-                    format!(
-                        "Duplicate VM role `{role_name}` detected. Each role must have a unique `name`."
-                    ),
-                ));
-            }
+#[derive(Debug, Deserialize)]
+struct RawVm {
+    name: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+    base_image: Option<PathBuf>,
+    #[serde(default)]
+    managed_image: Option<RawManagedImage>,
+    overlay: Option<PathBuf>,
+    #[serde(default)]
+    cpus: Option<u32>,
+    #[serde(default)]
+    memory: Option<String>,
+    #[serde(default, rename = "port_forwards")]
+    port_forwards: Vec<RawPortForward>,
+    #[serde(default)]
+    count: Option<u32>,
+    #[serde(default)]
+    instances: Vec<RawVmInstance>,
+    #[serde(default)]
+    bootstrap: Option<RawVmBootstrap>,
+    #[serde(default)]
+    depends_on: Vec<RawDependency>,
+    #[serde(default)]
+    provision: Option<RawProvision>,
+    #[serde(default)]
+    collect: Vec<RawCollectRule>,
+}
 
-            if !supports_multi && (count.is_some() || !instances.is_empty()) {
-                return Err(invalid_config(
-                    path,
-                    format!(
-                        "VM `{role_name}` uses replica fields but config version `{version}` only supports single-instance roles. \
-                         Remove `count`/`[[vms.instances]]` or set `version = \"0.2.0\"`."
-                    ),
-                ));
-            }
+#[derive(Debug, Deserialize)]
+struct RawCollectRule {
+    guest: Option<String>,
+    host: Option<String>,
+}
 
-            let count_value = count.unwrap_or(1);
-            if count_value == 0 {
-                return Err(invalid_config(
-                    path,
-                    format!("VM `{role_name}` declares `count = 0`. Specify at least one replica."),
-                ));
-            }
-            if !supports_multi && count_value != 1 {
-                return Err(invalid_config(
-                    path,
-                    format!(
-                        "VM `{role_name}` declares `count = {count_value}` but config version `{version}` only supports single-instance roles."
-                    ),
-                ));
-            }
-            let count_usize = count_value as usize;
-
-            let context = format!("VM `{role_name}`");
-            let base_image = resolve_base_image(
-                path,
-                &context,
-                &root_dir,
-                &state_root,
-                raw_base_image,
-                raw_managed_image,
-            )?;
+#[derive(Debug, Default, Deserialize)]
+struct RawProvision {
+    #[serde(default)]
+    packages: Vec<RawProvisionPackage>,
+    #[serde(default)]
+    files: Vec<RawProvisionFile>,
+    #[serde(default)]
+    users: Vec<RawProvisionUser>,
+    #[serde(default)]
+    services: Vec<String>,
+    #[serde(default)]
+    sysctls: BTreeMap<String, String>,
+}
 
-            let base_overlay = match raw_overlay {
-                Some(overlay) => resolve_overlay_path(&root_dir, &state_root, overlay),
-                None => default_overlay_base_path(&state_root, &role_name),
-            };
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RawProvisionPackage {
+    Name(String),
+    PerManager(RawPackageNames),
+}
 
-            let base_cpus = cpus.unwrap_or(2);
-            if base_cpus == 0 {
-                return Err(invalid_config(
-                    path,
-                    format!("VM `{role_name}` must request at least one CPU. Example: `cpus = 2`."),
-                ));
-            }
+#[derive(Debug, Deserialize)]
+struct RawPackageNames {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    apk: Option<String>,
+    #[serde(default)]
+    apt: Option<String>,
+    #[serde(default)]
+    dnf: Option<String>,
+}
 
-            let memory_string = memory.unwrap_or_else(|| "2048 MiB".to_string());
-            let base_memory = parse_memory(&memory_string).map_err(|msg| {
-                invalid_config(
-                    path,
-                    format!(
-                        "VM `{role_name}` has invalid memory specification `{memory_string}`: {msg}. \
-                         Example values: `2048 MiB`, `2 GiB`."
-                    ),
-                )
-            })?;
+#[derive(Debug, Deserialize)]
+struct RawProvisionFile {
+    path: Option<String>,
+    #[serde(default)]
+    content: Option<String>,
+    #[serde(default)]
+    source: Option<PathBuf>,
+    #[serde(default)]
+    mode: Option<String>,
+    #[serde(default)]
+    owner: Option<String>,
+}
 
-            let base_description = description;
+#[derive(Debug, Deserialize)]
+struct RawProvisionUser {
+    name: Option<String>,
+    #[serde(default)]
+    groups: Vec<String>,
+    #[serde(default)]
+    shell: Option<String>,
+    #[serde(default)]
+    authorized_keys: Vec<String>,
+}
 
-            let base_forwards = parse_port_forwards_list(
-                path,
-                &format!("VM `{role_name}`"),
-                &port_forwards,
-                warnings,
-            )?;
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum RawDependency {
+    Name(String),
+    Detailed(RawDependencyTarget),
+}
 
-            let base_bootstrap_mode = match bootstrap.as_ref() {
-                Some(config) => config
-                    .resolve_mode(path, &format!("VM `{role_name}`"))?
-                    .unwrap_or(bootstrap_config.mode),
-                None => bootstrap_config.mode,
-            };
+#[derive(Debug, Clone, Deserialize)]
+struct RawDependencyTarget {
+    vm: Option<String>,
+    #[serde(default)]
+    condition: Option<String>,
+}
 
-            let mut overrides = HashMap::new();
-            if supports_multi {
-                for raw_override in instances {
-                    let RawVmInstance {
-                        id,
-                        description,
-                        base_image,
-                        managed_image,
-                        overlay,
-                        cpus,
-                        memory,
-                        port_forwards,
-                    } = raw_override;
+#[derive(Debug, Deserialize)]
+struct RawPortForward {
+    host: Option<u16>,
+    guest: Option<u16>,
+    #[serde(default)]
+    protocol: Option<String>,
+}
 
-                    let id = id.ok_or_else(|| {
-                        invalid_config(
-                            path,
-                            format!(
-                                "Replica override under VM `{role_name}` is missing `id`. Expected `id = \"{role_name}-0\"`, etc."
-                            ),
-                        )
-                    })?;
+#[derive(Debug, Deserialize)]
+struct RawVmInstance {
+    id: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    base_image: Option<PathBuf>,
+    #[serde(default)]
+    managed_image: Option<RawManagedImage>,
+    #[serde(default)]
+    overlay: Option<PathBuf>,
+    #[serde(default)]
+    cpus: Option<u32>,
+    #[serde(default)]
+    memory: Option<String>,
+    #[serde(default, rename = "port_forwards")]
+    port_forwards: Option<Vec<RawPortForward>>,
+}
 
-                    let index = parse_replica_index(&role_name, &id)
-                        .map_err(|msg| invalid_config(path, msg))?;
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+struct RawManagedImage {
+    name: Option<String>,
+    version: Option<String>,
+    #[serde(default)]
+    disk: Option<String>,
+    #[serde(default)]
+    checksum: Option<String>,
+    #[serde(default)]
+    size_bytes: Option<u64>,
+}
 
-                    if index >= count_usize {
-                        return Err(invalid_config(
-                            path,
-                            format!(
-                                "Replica override `{id}` exceeds declared `count = {count_value}` for VM `{role_name}`."
-                            ),
-                        ));
-                    }
+#[derive(Debug, Deserialize)]
+struct RawBootstrap {
+    #[serde(default)]
+    mode: Option<String>,
+    #[serde(default)]
+    handshake_timeout_secs: Option<u64>,
+    #[serde(default)]
+    remote_dir: Option<PathBuf>,
+    #[serde(default)]
+    env: HashMap<String, String>,
+    #[serde(default)]
+    env_secrets: HashMap<String, RawSecretSource>,
+    #[serde(default)]
+    bake: Option<bool>,
+    #[serde(default)]
+    transport: Option<String>,
+    #[serde(default)]
+    managed_keys: Option<bool>,
+    #[serde(default)]
+    max_parallel: Option<usize>,
+    #[serde(default)]
+    steps: RawStepPolicies,
+    #[serde(default)]
+    stages: BTreeMap<String, RawStage>,
+}
 
-                    if overrides
-                        .insert(
-                            index,
-                            InstanceOverride {
-                                id,
-                                data: InstanceOverrideData {
-                                    description,
-                                    base_image,
-                                    managed_image,
-                                    overlay,
-                                    cpus,
-                                    memory,
-                                    port_forwards,
-                                },
-                            },
-                        )
-                        .is_some()
-                    {
-                        return Err(invalid_config(
-                            path,
-                            format!(
-                                "Replica override `{role_name}-{index}` declared multiple times. Each replica can only appear once."
-                            ),
-                        ));
-                    }
-                }
-            }
+#[derive(Debug, Default, Deserialize)]
+struct RawStepPolicies {
+    #[serde(default)]
+    connect: Option<RawStepPolicy>,
+    #[serde(default)]
+    transfer: Option<RawStepPolicy>,
+    #[serde(default)]
+    apply: Option<RawStepPolicy>,
+    #[serde(default)]
+    verify: Option<RawStepPolicy>,
+}
 
-            for idx in 0..count_usize {
-                let instance_name = if supports_multi {
-                    format!("{role_name}-{idx}")
-                } else {
-                    role_name.clone()
-                };
+#[derive(Debug, Deserialize)]
+struct RawStepPolicy {
+    #[serde(default)]
+    retries: Option<u32>,
+    #[serde(default)]
+    backoff_ms: Option<u64>,
+    #[serde(default)]
+    timeout_secs: Option<u64>,
+}
 
-                if !seen_instances.insert(instance_name.clone()) {
-                    return Err(invalid_config(
-                        path,
-                        format!("Duplicate VM name `{instance_name}` detected after expansion."),
-                    ));
-                }
+impl RawStepPolicy {
+    /// Layer `self` over `base`, the step's default policy.
+    fn over(
+        self,
+        path: &Path,
+        step: &str,
+        base: BootstrapStepPolicy,
+    ) -> Result<BootstrapStepPolicy, Error> {
+        if self.timeout_secs == Some(0) {
+            return Err(invalid_config(
+                path,
+                format!(
+                    "`[bootstrap.steps.{step}].timeout_secs` must be at least 1 second; omit it for no limit."
+                ),
+            ));
+        }
+        Ok(BootstrapStepPolicy {
+            retries: self.retries.unwrap_or(base.retries),
+            backoff: self
+                .backoff_ms
+                .map(Duration::from_millis)
+                .unwrap_or(base.backoff),
+            timeout: self.timeout_secs.map(Duration::from_secs).or(base.timeout),
+        })
+    }
+}
 
-                let (description, image_source, cpus, memory_spec, overlay_path, forwards) =
-                    if let Some(InstanceOverride { id, data }) = overrides.remove(&idx) {
-                        let InstanceOverrideData {
-                            description,
-                            base_image: override_base_image,
-                            managed_image: override_managed_image,
-                            overlay: override_overlay,
-                            cpus: override_cpus,
-                            memory: override_memory,
-                            port_forwards: override_forwards,
-                        } = data;
+impl RawStepPolicies {
+    fn into_policies(self, path: &Path) -> Result<BootstrapStepPolicies, Error> {
+        let defaults = BootstrapStepPolicies::default();
+        let resolve = |raw: Option<RawStepPolicy>, step: &str, base: BootstrapStepPolicy| match raw
+        {
+            Some(raw) => raw.over(path, step, base),
+            None => Ok(base),
+        };
+        Ok(BootstrapStepPolicies {
+            connect: resolve(self.connect, "connect", defaults.connect)?,
+            transfer: resolve(self.transfer, "transfer", defaults.transfer)?,
+            apply: resolve(self.apply, "apply", defaults.apply)?,
+            verify: resolve(self.verify, "verify", defaults.verify)?,
+        })
+    }
+}
 
-                        let image_source =
-                            if override_base_image.is_none() && override_managed_image.is_none() {
-                                base_image.clone()
-                            } else {
-                                resolve_base_image(
-                                    path,
-                                    &format!("Replica `{id}`"),
-                                    &root_dir,
-                                    &state_root,
-                                    override_base_image,
-                                    override_managed_image,
-                                )?
-                            };
+#[derive(Debug, Deserialize)]
+struct RawVmBootstrap {
+    #[serde(default)]
+    mode: Option<String>,
+    #[serde(default)]
+    script: Option<PathBuf>,
+    #[serde(default)]
+    payload: Option<RawPayload>,
+    #[serde(default)]
+    handshake_timeout_secs: Option<u64>,
+    #[serde(default)]
+    remote_dir: Option<PathBuf>,
+    #[serde(default)]
+    env: HashMap<String, String>,
+    #[serde(default)]
+    env_secrets: HashMap<String, RawSecretSource>,
+    #[serde(default)]
+    verify_command: Option<String>,
+    #[serde(default)]
+    verify_path: Option<PathBuf>,
+    #[serde(default)]
+    bake: Option<bool>,
+    #[serde(default)]
+    managed_keys: Option<bool>,
+    #[serde(default)]
+    stages: Vec<RawStageRef>,
+}
 
-                        let overlay_path = override_overlay
-                            .map(|overlay| resolve_overlay_path(&root_dir, &state_root, overlay))
-                            .unwrap_or_else(|| {
-                                derive_overlay_for_instance(
-                                    &base_overlay,
-                                    idx,
-                                    count_usize,
-                                    supports_multi,
-                                )
-                            });
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum RawStageRef {
+    Name(String),
+    Inline(Box<RawStage>),
+}
 
-                        let cpus = override_cpus.unwrap_or(base_cpus);
-                        if cpus == 0 {
-                            return Err(invalid_config(
-                                path,
-                                format!("Replica `{id}` must request at least one CPU."),
-                            ));
-                        }
+#[derive(Debug, Clone, Default, Deserialize)]
+struct RawStage {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    script: Option<PathBuf>,
+    #[serde(default)]
+    payload: Option<RawPayload>,
+    #[serde(default)]
+    env: HashMap<String, String>,
+    #[serde(default)]
+    env_secrets: HashMap<String, RawSecretSource>,
+    #[serde(default)]
+    timeout_secs: Option<u64>,
+    #[serde(default)]
+    verify_command: Option<String>,
+    #[serde(default)]
+    verify_path: Option<PathBuf>,
+    #[serde(default)]
+    run_if: Option<String>,
+}
 
-                        let memory_spec = match override_memory {
-                            Some(value) => parse_memory(&value).map_err(|msg| {
-                                invalid_config(
-                                    path,
-                                    format!(
-                                        "Replica `{id}` has invalid memory specification `{value}`: {msg}. \
-                                         Example values: `2048 MiB`, `2 GiB`."
-                                    ),
-                                )
-                            })?,
-                            None => base_memory.clone(),
-                        };
+impl RawStage {
+    /// Layer `self` over a shared definition of the same stage.
+    fn over(self, base: &RawStage) -> RawStage {
+        let mut env = base.env.clone();
+        env.extend(self.env);
+        let mut env_secrets = base.env_secrets.clone();
+        env_secrets.extend(self.env_secrets);
+        RawStage {
+            name: self.name.or_else(|| base.name.clone()),
+            script: self.script.or_else(|| base.script.clone()),
+            payload: self.payload.or_else(|| base.payload.clone()),
+            env,
+            env_secrets,
+            timeout_secs: self.timeout_secs.or(base.timeout_secs),
+            verify_command: self.verify_command.or_else(|| base.verify_command.clone()),
+            verify_path: self.verify_path.or_else(|| base.verify_path.clone()),
+            run_if: self.run_if.or_else(|| base.run_if.clone()),
+        }
+    }
+}
 
-                        let description = description.or_else(|| base_description.clone());
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum RawPayload {
+    Dir(PathBuf),
+    Git(RawGitPayload),
+}
 
-                        let forwards = match override_forwards {
-                            Some(raw) => parse_port_forwards_list(
-                                path,
-                                &format!("Replica `{id}`"),
-                                &raw,
-                                warnings,
-                            )?,
-                            None => base_forwards.clone(),
-                        };
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawGitPayload {
+    git: PathBuf,
+    #[serde(default)]
+    rev: Option<String>,
+    #[serde(default)]
+    subdir: Option<PathBuf>,
+}
 
-                        (
-                            description,
-                            image_source,
-                            cpus,
-                            memory_spec,
-                            overlay_path,
-                            forwards,
-                        )
-                    } else {
-                        (
-                            base_description.clone(),
-                            base_image.clone(),
-                            base_cpus,
-                            base_memory.clone(),
-                            derive_overlay_for_instance(
-                                &base_overlay,
-                                idx,
-                                count_usize,
-                                supports_multi,
-                            ),
-                            base_forwards.clone(),
-                        )
-                    };
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawSecretSource {
+    #[serde(default)]
+    file: Option<PathBuf>,
+    #[serde(default)]
+    env: Option<String>,
+}
 
-                let bootstrap_override = bootstrap.as_ref();
+/// Validate an `env_secrets` table. `context` names the table in errors.
+fn resolve_env_secrets(
+    path: &Path,
+    context: &str,
+    project_root: &Path,
+    raw: HashMap<String, RawSecretSource>,
+) -> Result<HashMap<String, SecretSource>, Error> {
+    let mut secrets = HashMap::with_capacity(raw.len());
+    for (key, source) in raw {
+        let valid_key = key
+            .chars()
+            .next()
+            .is_some_and(|ch| ch.is_ascii_alphabetic() || ch == '_')
+            && key
+                .chars()
+                .all(|ch| ch.is_ascii_alphanumeric() || ch == '_');
+        if !valid_key {
+            return Err(invalid_config(
+                path,
+                format!(
+                    "{context} declares secret `{key}`; names must be valid shell variable names."
+                ),
+            ));
+        }
+        let source = match (source.file, source.env) {
+            (Some(file), None) if !file.as_os_str().is_empty() => {
+                SecretSource::File(resolve_path(project_root, file))
+            }
+            (None, Some(name)) if !name.trim().is_empty() => SecretSource::Env(name),
+            _ => {
+                return Err(invalid_config(
+                    path,
+                    format!(
+                        "{context} secret `{key}` must set exactly one of `file` or `env`. Example: `{key} = {{ env = \"{key}\" }}`."
+                    ),
+                ));
+            }
+        };
+        secrets.insert(key, source);
+    }
+    Ok(secrets)
+}
 
-                if let Some(cfg) = bootstrap_override {
-                    if let Some(script_path) = cfg.script.as_ref() {
-                        if script_path.as_os_str().is_empty() {
-                            return Err(invalid_config(
-                                path,
-                                format!(
-                                    "VM `{instance_name}` declares an empty `bootstrap.script`; specify a script path or omit the field."
-                                ),
-                            ));
-                        }
-                    }
-                    if let Some(payload_path) = cfg.payload.as_ref() {
-                        if payload_path.as_os_str().is_empty() {
-                            return Err(invalid_config(
-                                path,
-                                format!(
-                                    "VM `{instance_name}` declares an empty `bootstrap.payload`; specify a payload directory or omit the field."
-                                ),
-                            ));
-                        }
-                    }
-                }
+/// Resolve a `payload` entry. An empty directory path means no payload.
+fn resolve_payload(
+    path: &Path,
+    context: &str,
+    project_root: &Path,
+    state_root: &Path,
+    raw: RawPayload,
+) -> Result<Option<PayloadSource>, Error> {
+    let git = match raw {
+        RawPayload::Dir(dir) if dir.as_os_str().is_empty() => return Ok(None),
+        RawPayload::Dir(dir) => {
+            return Ok(Some(PayloadSource::Dir(resolve_path(project_root, dir))));
+        }
+        RawPayload::Git(git) => git,
+    };
+    if git.git.as_os_str().is_empty() {
+        return Err(invalid_config(
+            path,
+            format!("{context} declares a git payload with an empty `git` path."),
+        ));
+    }
+    let rev = git.rev.unwrap_or_else(|| "HEAD".to_string());
+    if rev.trim().is_empty() || rev.starts_with('-') {
+        return Err(invalid_config(
+            path,
+            format!(
+                "{context} declares git payload rev `{rev}`; name a branch, tag or commit. Example: `rev = \"main\"`."
+            ),
+        ));
+    }
+    let subdir = git.subdir.filter(|subdir| !subdir.as_os_str().is_empty());
+    if let Some(subdir) = &subdir
+        && !subdir
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+    {
+        return Err(invalid_config(
+            path,
+            format!(
+                "{context} declares git payload subdir `{}`; it must be a relative path inside the repository.",
+                subdir.display()
+            ),
+        ));
+    }
+    Ok(Some(PayloadSource::Git(GitPayload {
+        repo: resolve_path(project_root, git.git),
+        rev,
+        subdir,
+        cache_dir: state_root.join(GIT_PAYLOAD_SUBDIR),
+    })))
+}
 
-                let script_override = bootstrap_override
-                    .and_then(|cfg| cfg.script.as_ref())
-                    .cloned()
-                    .map(|path| resolve_path(&project_root, path));
-                let default_script_path = project_root
-                    .join("bootstrap")
-                    .join(&instance_name)
-                    .join("run.sh");
-                let script_path = script_override.unwrap_or(default_script_path);
+#[derive(Debug)]
+struct InstanceOverride {
+    id: String,
+    data: InstanceOverrideData,
+}
 
-                let payload_override = bootstrap_override
-                    .and_then(|cfg| cfg.payload.as_ref())
-                    .cloned()
-                    .map(|path| resolve_path(&project_root, path));
-                let default_payload_path = project_root
-                    .join("bootstrap")
-                    .join(&instance_name)
-                    .join("payload");
-                let payload_path = payload_override.unwrap_or(default_payload_path);
+#[derive(Debug)]
+struct InstanceOverrideData {
+    description: Option<String>,
+    base_image: Option<PathBuf>,
+    managed_image: Option<RawManagedImage>,
+    overlay: Option<PathBuf>,
+    cpus: Option<u32>,
+    memory: Option<String>,
+    port_forwards: Option<Vec<RawPortForward>>,
+}
 
-                let handshake_timeout_secs = match bootstrap_override
-                    .and_then(|cfg| cfg.handshake_timeout_secs)
+#[derive(Debug, Deserialize, Default)]
+struct RawWorkflows {
+    #[serde(default)]
+    init: Option<Vec<String>>,
+    #[serde(default)]
+    pre_up: Vec<String>,
+    #[serde(default)]
+    post_launch: Vec<String>,
+    #[serde(default)]
+    post_bootstrap: Vec<String>,
+    #[serde(default)]
+    pre_down: Vec<String>,
+    #[serde(default)]
+    post_down: Vec<String>,
+}
+
+impl RawWorkflows {
+    fn into_config(self, path: &Path, warnings: &mut Vec<String>) -> Result<Workflows, Error> {
+        if self.init.is_some() {
+            warnings.push(
+                "`[workflows].init` is no longer supported and will be ignored; overlays are managed by Castra. Use `pre_up`, `post_launch`, `post_bootstrap`, `pre_down` or `post_down` hooks instead."
+                    .to_string(),
+            );
+        }
+        let workflows = Workflows {
+            pre_up: self.pre_up,
+            post_launch: self.post_launch,
+            post_bootstrap: self.post_bootstrap,
+            pre_down: self.pre_down,
+            post_down: self.post_down,
+        };
+        for point in HookPoint::ALL {
+            for (idx, command) in workflows.commands(point).iter().enumerate() {
+                if command.trim().is_empty() {
+                    return Err(invalid_config(
+                        path,
+                        format!("[workflows].{point}[{idx}] must not be empty."),
+                    ));
+                }
+                let names = hook_template_placeholders(command).map_err(|err| {
+                    invalid_config(path, format!("[workflows].{point}[{idx}]: {err}."))
+                })?;
+                if let Some(unknown) = names
+                    .iter()
+                    .find(|name| !HOOK_TEMPLATE_VARIABLES.contains(&name.as_str()))
                 {
-                    Some(0) => {
-                        return Err(invalid_config(
-                            path,
-                            format!(
-                                "VM `{instance_name}` declares `bootstrap.handshake_timeout_secs = 0`; specify at least 1 second.",
-                            ),
-                        ));
-                    }
-                    Some(value) => value,
-                    None => bootstrap_config.handshake_timeout_secs,
-                };
+                    return Err(invalid_config(
+                        path,
+                        format!(
+                            "[workflows].{point}[{idx}] references unknown variable `{{{{ {unknown} }}}}`; available: {}.",
+                            HOOK_TEMPLATE_VARIABLES.join(", ")
+                        ),
+                    ));
+                }
+            }
+        }
+        Ok(workflows)
+    }
+}
 
-                let remote_dir = match bootstrap_override.and_then(|cfg| cfg.remote_dir.as_ref()) {
-                    Some(dir) if dir.as_os_str().is_empty() => {
-                        return Err(invalid_config(
-                            path,
-                            format!(
-                                "VM `{instance_name}` declares an empty `bootstrap.remote_dir`; specify a non-empty path.",
+#[derive(Debug, Deserialize)]
+struct RawImages {
+    #[serde(default)]
+    mirrors: Vec<String>,
+    #[serde(default)]
+    trusted_keys: Vec<RawTrustedKey>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawTrustedKey {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    minisign: Option<String>,
+    #[serde(default)]
+    ed25519: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawLifecycle {
+    #[serde(default)]
+    graceful_shutdown_wait_secs: Option<u64>,
+    #[serde(default)]
+    sigterm_wait_secs: Option<u64>,
+    #[serde(default)]
+    sigkill_wait_secs: Option<u64>,
+}
+
+impl RawLifecycle {
+    fn into_config(self, path: &Path) -> Result<LifecycleConfig, Error> {
+        let graceful = self
+            .graceful_shutdown_wait_secs
+            .unwrap_or(DEFAULT_GRACEFUL_SHUTDOWN_WAIT_SECS);
+        let sigterm = self.sigterm_wait_secs.unwrap_or(DEFAULT_SIGTERM_WAIT_SECS);
+        let sigkill = self.sigkill_wait_secs.unwrap_or(DEFAULT_SIGKILL_WAIT_SECS);
+
+        if sigkill == 0 {
+            return Err(invalid_config(
+                path,
+                "`[lifecycle].sigkill_wait_secs` must be at least 1 to allow the orchestrator to confirm process exit.",
+            ));
+        }
+
+        Ok(LifecycleConfig {
+            graceful_shutdown_wait_secs: graceful,
+            sigterm_wait_secs: sigterm,
+            sigkill_wait_secs: sigkill,
+        })
+    }
+}
+
+impl RawImages {
+    fn into_config(self, path: &Path) -> Result<ImagesConfig, Error> {
+        let mut mirrors = Vec::with_capacity(self.mirrors.len());
+        for raw in self.mirrors {
+            let mirror = raw.trim();
+            if !(mirror.starts_with("http://") || mirror.starts_with("https://")) {
+                return Err(invalid_config(
+                    path,
+                    format!(
+                        "`[images].mirrors` entry `{raw}` must be an http:// or https:// base URL. Example: `mirrors = [\"https://mirror.internal/castra\"]`."
+                    ),
+                ));
+            }
+            mirrors.push(mirror.trim_end_matches('/').to_string());
+        }
+
+        let mut trusted_keys = Vec::with_capacity(self.trusted_keys.len());
+        for (idx, raw) in self.trusted_keys.into_iter().enumerate() {
+            let name = raw
+                .name
+                .filter(|name| !name.trim().is_empty())
+                .unwrap_or_else(|| format!("trusted key #{}", idx + 1));
+            let (scheme, key) = match (raw.minisign, raw.ed25519) {
+                (Some(key), None) => (SignatureScheme::Minisign, key),
+                (None, Some(key)) => (SignatureScheme::Ed25519, key),
+                _ => {
+                    return Err(invalid_config(
+                        path,
+                        format!(
+                            "`[[images.trusted_keys]]` entry `{name}` must set exactly one of `minisign` or `ed25519`."
+                        ),
+                    ));
+                }
+            };
+            let key = key.trim().to_string();
+            if key.is_empty() {
+                return Err(invalid_config(
+                    path,
+                    format!("`[[images.trusted_keys]]` entry `{name}` has an empty key."),
+                ));
+            }
+            trusted_keys.push(TrustedKeyConfig { name, scheme, key });
+        }
+
+        Ok(ImagesConfig {
+            mirrors,
+            trusted_keys,
+        })
+    }
+}
+
+impl RawBootstrap {
+    fn into_config(self, path: &Path) -> Result<BootstrapConfig, Error> {
+        let mode = match self.mode.as_deref() {
+            Some(value) => value
+                .parse::<BootstrapMode>()
+                .map_err(|err| invalid_config(path, err))?,
+            None => BootstrapMode::default(),
+        };
+
+        let handshake_timeout_secs = match self.handshake_timeout_secs {
+            Some(0) => {
+                return Err(invalid_config(
+                    path,
+                    "`[bootstrap].handshake_timeout_secs` must be at least 1 second.",
+                ));
+            }
+            Some(value) => value,
+            None => DEFAULT_BOOTSTRAP_HANDSHAKE_WAIT_SECS,
+        };
+
+        let remote_dir = match self.remote_dir {
+            Some(dir) if dir.as_os_str().is_empty() => {
+                return Err(invalid_config(
+                    path,
+                    "`[bootstrap].remote_dir` must not be empty.",
+                ));
+            }
+            Some(dir) => dir,
+            None => PathBuf::from("/tmp/castra-bootstrap"),
+        };
+
+        let transport = match self.transport.as_deref() {
+            Some(value) => value
+                .parse::<BootstrapTransport>()
+                .map_err(|err| invalid_config(path, err))?,
+            None => BootstrapTransport::default(),
+        };
+        if transport == BootstrapTransport::Native && !cfg!(feature = "native-ssh") {
+            return Err(invalid_config(
+                path,
+                "`[bootstrap].transport = \"native\"` requires castra built with the `native-ssh` feature.",
+            ));
+        }
+
+        if self.max_parallel == Some(0) {
+            return Err(invalid_config(
+                path,
+                "`[bootstrap].max_parallel` must be at least 1; omit it to bootstrap every VM at once.",
+            ));
+        }
+        let steps = self.steps.into_policies(path)?;
+
+        let project_root = path.parent().unwrap_or_else(|| Path::new("."));
+        let env_secrets =
+            resolve_env_secrets(path, "`[bootstrap]`", project_root, self.env_secrets)?;
+
+        Ok(BootstrapConfig {
+            mode,
+            handshake_timeout_secs,
+            remote_dir,
+            env: self.env,
+            env_secrets,
+            bake: self.bake.unwrap_or(false),
+            transport,
+            managed_keys: self.managed_keys,
+            max_parallel: self.max_parallel,
+            steps,
+        })
+    }
+}
+
+impl RawVmBootstrap {
+    fn resolve_mode(&self, path: &Path, context: &str) -> Result<Option<BootstrapMode>, Error> {
+        match self.mode.as_deref() {
+            Some(value) => value
+                .parse::<BootstrapMode>()
+                .map_err(|_| {
+                    invalid_config(
+                        path,
+                        format!(
+                            "{context} has unknown bootstrap.mode `{value}`. Supported values: auto, skip, always."
+                        ),
+                    )
+                })
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+impl RawConfig {
+    fn into_validated(
+        self,
+        path: &Path,
+        warnings: &mut Vec<String>,
+    ) -> Result<ProjectConfig, Error> {
+        let RawConfig {
+            version,
+            project,
+            vms,
+            workflows,
+            lifecycle,
+            bootstrap: bootstrap_raw,
+            images,
+        } = self;
+
+        let version = version.ok_or_else(|| {
+            invalid_config(
+                path,
+                "Missing required top-level field `version`. Example: `version = \"0.1.0\"`.",
+            )
+        })?;
+
+        let (schema, warn_future) =
+            classify_schema_version(&version).map_err(|message| invalid_config(path, message))?;
+        if warn_future {
+            warnings.push(format!(
+                "Configuration version `{version}` is not fully supported yet; proceeding anyway."
+            ));
+        }
+
+        let raw_project = project.ok_or_else(|| {
+            invalid_config(
+                path,
+                "Missing required table `[project]`. Example:\n\
+                 [project]\n\
+                 name = \"devbox\"",
+            )
+        })?;
+
+        let project_name = raw_project.name.ok_or_else(|| {
+            invalid_config(
+                path,
+                "Missing required field `project.name`. Example: `name = \"devbox\"`.",
+            )
+        })?;
+
+        let features = raw_project.features.into_features(warnings);
+
+        let project_root = path.parent().map(Path::to_path_buf).unwrap_or_else(|| {
+            warnings.push(
+                "Unable to determine config directory; assuming current working directory."
+                    .to_string(),
+            );
+            PathBuf::from(".")
+        });
+
+        let state_root = raw_project
+            .state_dir
+            .map(|dir| resolve_path(&project_root, dir))
+            .unwrap_or_else(|| default_state_root(&project_name, path));
+
+        let (bootstrap_config, shared_stages) = match bootstrap_raw {
+            Some(mut raw) => {
+                let stages = std::mem::take(&mut raw.stages);
+                (raw.into_config(path)?, stages)
+            }
+            None => (BootstrapConfig::default(), BTreeMap::new()),
+        };
+
+        if vms.is_empty() {
+            return Err(invalid_config(
+                path,
+                "At least one `[[vms]]` entry is required. Example:\n\
+                 [[vms]]\n\
+                 name = \"devbox\"\n\
+                 base_image = \"images/devbox.qcow2\"\n\
+                 overlay = \".castra/devbox-overlay.qcow2\"\n\
+                 (Tip: run `castra init` to scaffold a starter config.)",
+            ));
+        }
+
+        let root_dir = project_root.clone();
+        let supports_multi = schema.supports_multi_instance();
+
+        let mut seen_roles = HashSet::new();
+        let mut seen_instances = HashSet::new();
+        let mut expanded_vms = Vec::new();
+        let mut role_dependencies = Vec::new();
+
+        for vm in vms {
+            let RawVm {
+                name,
+                description,
+                base_image: raw_base_image,
+                managed_image: raw_managed_image,
+                overlay: raw_overlay,
+                cpus,
+                memory,
+                port_forwards,
+                count,
+                instances,
+                bootstrap,
+                depends_on,
+                provision,
+                collect,
+            } = vm;
+
+            let role_name = name.ok_or_else(|| {
+                invalid_config(
+                    path,
+                    "Each `[[vms]]` entry must define `name`. Example: `name = \"devbox\"`.",
+                )
+            })?;
+
+            role_dependencies.push((role_name.clone(), depends_on));
+
+            let mut bootstrap = bootstrap;
+            let stage_refs = bootstrap
+                .as_mut()
+                .map(|cfg| std::mem::take(&mut cfg.stages))
+                .unwrap_or_default();
+            if !stage_refs.is_empty()
+                && let Some(cfg) = bootstrap.as_ref()
+                && (cfg.script.is_some()
+                    || cfg.payload.is_some()
+                    || cfg.verify_command.is_some()
+                    || cfg.verify_path.is_some())
+            {
+                return Err(invalid_config(
+                    path,
+                    format!(
+                        "VM `{role_name}` sets `bootstrap.stages` together with `bootstrap.script`, `payload` or `verify_*`; move them into a stage."
+                    ),
+                ));
+            }
+            let stages = resolve_stages(
+                path,
+                &role_name,
+                &project_root,
+                &state_root,
+                &shared_stages,
+                stage_refs,
+            )?;
+            let provision = match provision {
+                Some(raw) => resolve_provision(path, &role_name, &project_root, raw)?,
+                None => None,
+            };
+            let collect = resolve_collect(path, &role_name, collect)?;
+            if provision.is_some() && stages.iter().any(|stage| stage.name == PROVISION_STAGE) {
+                return Err(invalid_config(
+                    path,
+                    format!(
+                        "VM `{role_name}` declares `[vms.provision]` and a bootstrap stage named `{PROVISION_STAGE}`; rename the stage."
+                    ),
+                ));
+            }
+
+            if !seen_roles.insert(role_name.clone()) {
+                return Err(invalid_config(
+                    path,
+                    format!(
+                        "Duplicate VM role `{role_name}` detected. Each role must have a unique `name`."
+                    ),
+                ));
+            }
+
+            if !supports_multi && (count.is_some() || !instances.is_empty()) {
+                return Err(invalid_config(
+                    path,
+                    format!(
+                        "VM `{role_name}` uses replica fields but config version `{version}` only supports single-instance roles. \
+                         Remove `count`/`[[vms.instances]]` or set `version = \"0.2.0\"`."
+                    ),
+                ));
+            }
+
+            let count_value = count.unwrap_or(1);
+            if count_value == 0 {
+                return Err(invalid_config(
+                    path,
+                    format!("VM `{role_name}` declares `count = 0`. Specify at least one replica."),
+                ));
+            }
+            if !supports_multi && count_value != 1 {
+                return Err(invalid_config(
+                    path,
+                    format!(
+                        "VM `{role_name}` declares `count = {count_value}` but config version `{version}` only supports single-instance roles."
+                    ),
+                ));
+            }
+            let count_usize = count_value as usize;
+
+            let context = format!("VM `{role_name}`");
+            let base_image = resolve_base_image(
+                path,
+                &context,
+                &root_dir,
+                &state_root,
+                raw_base_image,
+                raw_managed_image,
+            )?;
+
+            let base_overlay = match raw_overlay {
+                Some(overlay) => resolve_overlay_path(&root_dir, &state_root, overlay),
+                None => default_overlay_base_path(&state_root, &role_name),
+            };
+
+            let base_cpus = cpus.unwrap_or(2);
+            if base_cpus == 0 {
+                return Err(invalid_config(
+                    path,
+                    format!("VM `{role_name}` must request at least one CPU. Example: `cpus = 2`."),
+                ));
+            }
+
+            let memory_string = memory.unwrap_or_else(|| "2048 MiB".to_string());
+            let base_memory = parse_memory(&memory_string).map_err(|msg| {
+                invalid_config(
+                    path,
+                    format!(
+                        "VM `{role_name}` has invalid memory specification `{memory_string}`: {msg}. \
+                         Example values: `2048 MiB`, `2 GiB`."
+                    ),
+                )
+            })?;
+
+            let base_description = description;
+
+            let base_forwards = parse_port_forwards_list(
+                path,
+                &format!("VM `{role_name}`"),
+                &port_forwards,
+                warnings,
+            )?;
+
+            let base_bootstrap_mode = match bootstrap.as_ref() {
+                Some(config) => config
+                    .resolve_mode(path, &format!("VM `{role_name}`"))?
+                    .unwrap_or(bootstrap_config.mode),
+                None => bootstrap_config.mode,
+            };
+
+            let mut overrides = HashMap::new();
+            if supports_multi {
+                for raw_override in instances {
+                    let RawVmInstance {
+                        id,
+                        description,
+                        base_image,
+                        managed_image,
+                        overlay,
+                        cpus,
+                        memory,
+                        port_forwards,
+                    } = raw_override;
+
+                    let id = id.ok_or_else(|| {
+                        invalid_config(
+                            path,
+                            format!(
+                                "Replica override under VM `{role_name}` is missing `id`. Expected `id = \"{role_name}-0\"`, etc."
+                            ),
+                        )
+                    })?;
+
+                    let index = parse_replica_index(&role_name, &id)
+                        .map_err(|msg| invalid_config(path, msg))?;
+
+                    if index >= count_usize {
+                        return Err(invalid_config(
+                            path,
+                            format!(
+                                "Replica override `{id}` exceeds declared `count = {count_value}` for VM `{role_name}`."
+                            ),
+                        ));
+                    }
+
+                    if overrides
+                        .insert(
+                            index,
+                            InstanceOverride {
+                                id,
+                                data: InstanceOverrideData {
+                                    description,
+                                    base_image,
+                                    managed_image,
+                                    overlay,
+                                    cpus,
+                                    memory,
+                                    port_forwards,
+                                },
+                            },
+                        )
+                        .is_some()
+                    {
+                        return Err(invalid_config(
+                            path,
+                            format!(
+                                "Replica override `{role_name}-{index}` declared multiple times. Each replica can only appear once."
+                            ),
+                        ));
+                    }
+                }
+            }
+
+            for idx in 0..count_usize {
+                let instance_name = if supports_multi {
+                    format!("{role_name}-{idx}")
+                } else {
+                    role_name.clone()
+                };
+
+                if !seen_instances.insert(instance_name.clone()) {
+                    return Err(invalid_config(
+                        path,
+                        format!("Duplicate VM name `{instance_name}` detected after expansion."),
+                    ));
+                }
+
+                let (description, image_source, cpus, memory_spec, overlay_path, forwards) =
+                    if let Some(InstanceOverride { id, data }) = overrides.remove(&idx) {
+                        let InstanceOverrideData {
+                            description,
+                            base_image: override_base_image,
+                            managed_image: override_managed_image,
+                            overlay: override_overlay,
+                            cpus: override_cpus,
+                            memory: override_memory,
+                            port_forwards: override_forwards,
+                        } = data;
+
+                        let image_source =
+                            if override_base_image.is_none() && override_managed_image.is_none() {
+                                base_image.clone()
+                            } else {
+                                resolve_base_image(
+                                    path,
+                                    &format!("Replica `{id}`"),
+                                    &root_dir,
+                                    &state_root,
+                                    override_base_image,
+                                    override_managed_image,
+                                )?
+                            };
+
+                        let overlay_path = override_overlay
+                            .map(|overlay| resolve_overlay_path(&root_dir, &state_root, overlay))
+                            .unwrap_or_else(|| {
+                                derive_overlay_for_instance(
+                                    &base_overlay,
+                                    idx,
+                                    count_usize,
+                                    supports_multi,
+                                )
+                            });
+
+                        let cpus = override_cpus.unwrap_or(base_cpus);
+                        if cpus == 0 {
+                            return Err(invalid_config(
+                                path,
+                                format!("Replica `{id}` must request at least one CPU."),
+                            ));
+                        }
+
+                        let memory_spec = match override_memory {
+                            Some(value) => parse_memory(&value).map_err(|msg| {
+                                invalid_config(
+                                    path,
+                                    format!(
+                                        "Replica `{id}` has invalid memory specification `{value}`: {msg}. \
+                                         Example values: `2048 MiB`, `2 GiB`."
+                                    ),
+                                )
+                            })?,
+                            None => base_memory.clone(),
+                        };
+
+                        let description = description.or_else(|| base_description.clone());
+
+                        let forwards = match override_forwards {
+                            Some(raw) => parse_port_forwards_list(
+                                path,
+                                &format!("Replica `{id}`"),
+                                &raw,
+                                warnings,
+                            )?,
+                            None => base_forwards.clone(),
+                        };
+
+                        (
+                            description,
+                            image_source,
+                            cpus,
+                            memory_spec,
+                            overlay_path,
+                            forwards,
+                        )
+                    } else {
+                        (
+                            base_description.clone(),
+                            base_image.clone(),
+                            base_cpus,
+                            base_memory.clone(),
+                            derive_overlay_for_instance(
+                                &base_overlay,
+                                idx,
+                                count_usize,
+                                supports_multi,
+                            ),
+                            base_forwards.clone(),
+                        )
+                    };
+
+                let bootstrap_override = bootstrap.as_ref();
+
+                if let Some(cfg) = bootstrap_override {
+                    if let Some(script_path) = cfg.script.as_ref() {
+                        if script_path.as_os_str().is_empty() {
+                            return Err(invalid_config(
+                                path,
+                                format!(
+                                    "VM `{instance_name}` declares an empty `bootstrap.script`; specify a script path or omit the field."
+                                ),
+                            ));
+                        }
+                    }
+                    if let Some(RawPayload::Dir(payload_path)) = cfg.payload.as_ref() {
+                        if payload_path.as_os_str().is_empty() {
+                            return Err(invalid_config(
+                                path,
+                                format!(
+                                    "VM `{instance_name}` declares an empty `bootstrap.payload`; specify a payload directory or omit the field."
+                                ),
+                            ));
+                        }
+                    }
+                }
+
+                let script_override = bootstrap_override
+                    .and_then(|cfg| cfg.script.as_ref())
+                    .cloned()
+                    .map(|path| resolve_path(&project_root, path));
+                let default_script_path = project_root
+                    .join("bootstrap")
+                    .join(&instance_name)
+                    .join("run.sh");
+                let script_path = script_override.unwrap_or(default_script_path);
+
+                let payload_override = match bootstrap_override.and_then(|cfg| cfg.payload.clone())
+                {
+                    Some(payload) => resolve_payload(
+                        path,
+                        &format!("VM `{instance_name}`"),
+                        &project_root,
+                        &state_root,
+                        payload,
+                    )?,
+                    None => None,
+                };
+                let default_payload_path = project_root
+                    .join("bootstrap")
+                    .join(&instance_name)
+                    .join("payload");
+                let payload_path =
+                    payload_override.unwrap_or(PayloadSource::Dir(default_payload_path));
+                let (script_path, payload_path) = if stages.is_empty() {
+                    (Some(script_path), Some(payload_path))
+                } else {
+                    (None, None)
+                };
+
+                let handshake_timeout_secs = match bootstrap_override
+                    .and_then(|cfg| cfg.handshake_timeout_secs)
+                {
+                    Some(0) => {
+                        return Err(invalid_config(
+                            path,
+                            format!(
+                                "VM `{instance_name}` declares `bootstrap.handshake_timeout_secs = 0`; specify at least 1 second.",
+                            ),
+                        ));
+                    }
+                    Some(value) => value,
+                    None => bootstrap_config.handshake_timeout_secs,
+                };
+
+                let remote_dir = match bootstrap_override.and_then(|cfg| cfg.remote_dir.as_ref()) {
+                    Some(dir) if dir.as_os_str().is_empty() => {
+                        return Err(invalid_config(
+                            path,
+                            format!(
+                                "VM `{instance_name}` declares an empty `bootstrap.remote_dir`; specify a non-empty path.",
                             ),
                         ));
                     }
@@ -1575,524 +3319,1056 @@ impl RawConfig {
                     None => bootstrap_config.remote_dir.clone(),
                 };
 
-                let mut env = bootstrap_config.env.clone();
-                if let Some(cfg) = bootstrap_override {
-                    for (key, value) in &cfg.env {
-                        env.insert(key.clone(), value.clone());
-                    }
-                }
+                let mut env = bootstrap_config.env.clone();
+                let mut env_secrets = bootstrap_config.env_secrets.clone();
+                if let Some(cfg) = bootstrap_override {
+                    for (key, value) in &cfg.env {
+                        env.insert(key.clone(), value.clone());
+                    }
+                    env_secrets.extend(resolve_env_secrets(
+                        path,
+                        &format!("VM `{instance_name}`"),
+                        &project_root,
+                        cfg.env_secrets.clone(),
+                    )?);
+                }
+
+                let verify = bootstrap_override.and_then(|cfg| {
+                    if cfg.verify_command.is_some() || cfg.verify_path.is_some() {
+                        Some(BootstrapVerifyConfig {
+                            command: cfg.verify_command.clone(),
+                            path: cfg.verify_path.clone(),
+                        })
+                    } else {
+                        None
+                    }
+                });
+
+                let bake = bootstrap_override
+                    .and_then(|cfg| cfg.bake)
+                    .unwrap_or(bootstrap_config.bake);
+                let default_alpine =
+                    image_source.provenance() == BaseImageProvenance::DefaultAlpine;
+                let managed_keys = match bootstrap_override
+                    .and_then(|cfg| cfg.managed_keys)
+                    .or(bootstrap_config.managed_keys)
+                {
+                    Some(true) if default_alpine => {
+                        if idx == 0 {
+                            warnings.push(format!(
+                                "VM `{role_name}` enables `managed_keys` but boots the default Alpine image, which does not read the workspace key from fw_cfg; bootstrap will fail to authenticate unless the image installs it."
+                            ));
+                        }
+                        true
+                    }
+                    Some(enabled) => enabled,
+                    None => !default_alpine,
+                }
+                .then(|| ManagedSshKeys::for_state_root(&state_root));
+
+                let collect_rules = collect
+                    .iter()
+                    .map(|(guest, host)| CollectRule {
+                        guest: guest.clone(),
+                        host: resolve_path(
+                            &project_root,
+                            PathBuf::from(host.replace("{vm}", &instance_name)),
+                        ),
+                    })
+                    .collect();
+
+                expanded_vms.push(VmDefinition {
+                    name: instance_name,
+                    role_name: role_name.clone(),
+                    replica_index: idx,
+                    description,
+                    base_image: image_source,
+                    overlay: overlay_path,
+                    cpus,
+                    memory: memory_spec,
+                    port_forwards: forwards,
+                    bootstrap: VmBootstrapConfig {
+                        mode: base_bootstrap_mode,
+                        script: script_path,
+                        payload: payload_path,
+                        handshake_timeout_secs,
+                        remote_dir,
+                        env,
+                        env_secrets,
+                        secret_salt: state_root.join(SECRET_SALT_FILE),
+                        verify,
+                        bake,
+                        stages: stages.clone(),
+                        managed_keys,
+                        template_vars: BTreeMap::new(),
+                    },
+                    depends_on: Vec::new(),
+                    provision: provision.clone(),
+                    collect: collect_rules,
+                });
+            }
+
+            if let Some(extra) = overrides.into_values().next() {
+                return Err(invalid_config(
+                    path,
+                    format!(
+                        "Replica override `{}` was not applied. Verify the ID matches `<{role_name}>-<index>` within the declared count.",
+                        extra.id
+                    ),
+                ));
+            }
+        }
+
+        resolve_dependencies(path, &mut expanded_vms, role_dependencies)?;
+        resolve_template_vars(&project_name, &mut expanded_vms);
+
+        let workflows = workflows.into_config(path, warnings)?;
+
+        let lifecycle = match lifecycle {
+            Some(raw) => raw.into_config(path)?,
+            None => LifecycleConfig::default(),
+        };
+
+        let images = match images {
+            Some(raw) => raw.into_config(path)?,
+            None => ImagesConfig::default(),
+        };
+
+        Ok(ProjectConfig {
+            file_path: path.to_path_buf(),
+            project_root,
+            version,
+            project_name,
+            features,
+            vms: expanded_vms,
+            state_root,
+            workflows,
+            lifecycle,
+            bootstrap: bootstrap_config,
+            images,
+            warnings: warnings.clone(),
+        })
+    }
+}
+
+fn resolve_path(base: &Path, input: PathBuf) -> PathBuf {
+    if input.is_absolute() {
+        input
+    } else if base.is_absolute() {
+        base.join(input)
+    } else {
+        match env::current_dir() {
+            Ok(cwd) => cwd.join(base).join(input),
+            Err(_) => base.join(input),
+        }
+    }
+}
+
+fn resolve_overlay_path(config_root: &Path, state_root: &Path, overlay: PathBuf) -> PathBuf {
+    if overlay.is_absolute() {
+        overlay
+    } else if overlay.starts_with(".castra") {
+        match overlay.strip_prefix(".castra") {
+            Ok(remainder) if remainder.as_os_str().is_empty() => state_root.to_path_buf(),
+            Ok(remainder) => state_root.join(remainder),
+            Err(_) => state_root.to_path_buf(),
+        }
+    } else {
+        resolve_path(config_root, overlay)
+    }
+}
+
+fn parse_memory(input: &str) -> Result<MemorySpec, String> {
+    let trimmed = input.trim();
+    if trimmed.is_empty() {
+        return Err("memory value cannot be empty".to_string());
+    }
+
+    let mut parts = trimmed.split_whitespace();
+    let amount = parts
+        .next()
+        .ok_or_else(|| "memory value is missing numeric component".to_string())?;
+    let unit = parts.next();
+    if parts.next().is_some() {
+        return Err("memory value contains unexpected extra tokens".to_string());
+    }
+
+    let amount_value: f64 = amount.parse().map_err(|_| {
+        format!("could not parse `{amount}` as a number; try values like `2048 MiB` or `2 GiB`")
+    })?;
+
+    let bytes = match unit.map(|u| u.to_ascii_lowercase()) {
+        Some(ref u) if u == "mib" || u == "mb" => (amount_value * 1024.0 * 1024.0) as u64,
+        Some(ref u) if u == "gib" || u == "gb" => (amount_value * 1024.0 * 1024.0 * 1024.0) as u64,
+        Some(ref u) if u == "kib" || u == "kb" => (amount_value * 1024.0) as u64,
+        Some(ref u) if u == "b" || u == "bytes" => amount_value as u64,
+        Some(ref u) => {
+            return Err(format!(
+                "unsupported memory unit `{u}`; supported units are B, KiB, MiB, GiB."
+            ));
+        }
+        None => (amount_value * 1024.0 * 1024.0) as u64,
+    };
+
+    Ok(MemorySpec {
+        original: trimmed.to_string(),
+        bytes: Some(bytes),
+    })
+}
+
+pub(crate) fn default_state_root(project_name: &str, config_path: &Path) -> PathBuf {
+    let home = user_home_dir().unwrap_or_else(|| PathBuf::from("."));
+    let projects_root = home.join(".castra").join("projects");
+    let slug = slugify_project_name(project_name);
+    let unique = derive_project_id(config_path);
+    projects_root.join(format!("{slug}-{unique}"))
+}
+
+fn slugify_project_name(name: &str) -> String {
+    let mut slug = String::with_capacity(name.len());
+    let mut last_was_dash = true;
+    for ch in name.chars() {
+        if ch.is_ascii_alphanumeric() {
+            slug.push(ch.to_ascii_lowercase());
+            last_was_dash = false;
+        } else if !last_was_dash {
+            slug.push('-');
+            last_was_dash = true;
+        }
+    }
+
+    let trimmed = slug.trim_matches('-');
+    if trimmed.is_empty() {
+        "project".to_string()
+    } else {
+        trimmed.to_string()
+    }
+}
+
+fn derive_project_id(config_path: &Path) -> String {
+    let parent = config_path.parent().unwrap_or_else(|| Path::new("."));
+    let repr = parent.to_string_lossy();
+    let mut hasher = Sha256::new();
+    hasher.update(repr.as_bytes());
+    let digest = hasher.finalize();
+    hex::encode(&digest[..8])
+}
+
+pub(crate) fn user_home_dir() -> Option<PathBuf> {
+    if let Some(home) = std::env::var_os("HOME") {
+        if !home.is_empty() {
+            return Some(PathBuf::from(home));
+        }
+    }
+
+    #[cfg(windows)]
+    {
+        if let Some(profile) = std::env::var_os("USERPROFILE") {
+            if !profile.is_empty() {
+                return Some(PathBuf::from(profile));
+            }
+        }
+
+        let drive = std::env::var_os("HOMEDRIVE");
+        let path = std::env::var_os("HOMEPATH");
+        if let (Some(drive), Some(path)) = (drive, path) {
+            if !drive.is_empty() && !path.is_empty() {
+                let mut combined = PathBuf::from(drive);
+                combined.push(path);
+                if combined.components().next().is_some() {
+                    return Some(combined);
+                }
+            }
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use regex::Regex;
+    use std::path::{Path, PathBuf};
+    use std::time::Duration;
+    use temp_env::with_var;
+    use tempfile::tempdir;
+
+    fn write_config(dir: &tempfile::TempDir, contents: &str) -> PathBuf {
+        let path = dir.path().join("castra.toml");
+        fs::write(&path, contents).expect("write config");
+        path
+    }
+
+    fn minimal_config(contents: &str) -> String {
+        format!(
+            r#"
+version = "0.1.0"
+
+[project]
+name = "demo"
+state_dir = ".castra"
+
+{contents}
+"#
+        )
+    }
+
+    fn minimal_config_v02(contents: &str) -> String {
+        format!(
+            r#"
+version = "0.2.0"
+
+[project]
+name = "demo"
+state_dir = ".castra/state"
+
+{contents}
+"#
+        )
+    }
+
+    #[test]
+    fn load_config_with_local_base_image() {
+        let dir = tempdir().unwrap();
+        let path = write_config(
+            &dir,
+            &minimal_config(
+                r#"
+[[vms]]
+name = "devbox"
+base_image = "images/devbox.qcow2"
+overlay = ".castra/devbox-overlay.qcow2"
+cpus = 2
+memory = "2048 MiB"
+"#,
+            ),
+        );
+
+        let config = load_project_config(&path).expect("load config");
+        assert_eq!(config.project_name, "demo");
+        assert_eq!(config.vms.len(), 1);
+        let vm = &config.vms[0];
+        assert_eq!(vm.name, "devbox");
+        assert_eq!(vm.role_name, "devbox");
+        assert_eq!(vm.replica_index, 0);
+        assert_eq!(
+            vm.base_image.path(),
+            dir.path().join("images/devbox.qcow2").as_path(),
+            "base image path is resolved relative to file"
+        );
+        assert_eq!(
+            vm.base_image.provenance(),
+            BaseImageProvenance::Explicit,
+            "explicit base image marked as explicit provenance"
+        );
+        assert_eq!(
+            vm.overlay,
+            dir.path().join(".castra/devbox-overlay.qcow2"),
+            "overlay resolves under explicit state_dir"
+        );
+        assert_eq!(vm.cpus, 2);
+        assert_eq!(vm.memory.original(), "2048 MiB");
+        assert_eq!(vm.memory.bytes(), Some(2048 * 1024 * 1024));
+        assert!(config.warnings.is_empty());
+
+        let conflicts = config.port_conflicts();
+        assert!(conflicts.is_empty());
+        assert_eq!(
+            config.state_root,
+            dir.path().join(".castra"),
+            "state root uses project override"
+        );
+    }
+
+    #[test]
+    fn load_config_defaults_base_image_and_overlay() {
+        let dir = tempdir().unwrap();
+        let path = write_config(
+            &dir,
+            &minimal_config_v02(
+                r#"
+[[vms]]
+name = "devbox"
+cpus = 2
+memory = "1024 MiB"
+"#,
+            ),
+        );
+
+        let config = load_project_config(&path).expect("load config with defaults");
+        assert_eq!(config.vms.len(), 1);
+        let vm = &config.vms[0];
+        let expected_path = default_alpine_base_image_path(&config.state_root);
+        assert_eq!(
+            vm.base_image.path(),
+            expected_path.as_path(),
+            "default base image points at cached alpine image"
+        );
+        assert_eq!(
+            vm.base_image.provenance(),
+            BaseImageProvenance::DefaultAlpine,
+            "default base image provenance recorded"
+        );
+
+        assert_eq!(
+            vm.overlay,
+            default_overlay_base_path(&config.state_root, "devbox")
+        );
+        let overlay_name = vm
+            .overlay
+            .file_name()
+            .and_then(|name| name.to_str())
+            .expect("overlay file name");
+        let re = Regex::new(r"^devbox-[0-9a-f]{6}-overlay\.qcow2$").unwrap();
+        assert!(
+            re.is_match(overlay_name),
+            "unexpected overlay file name {overlay_name}"
+        );
+    }
+
+    #[test]
+    fn load_config_defaults_multi_instance_overlays() {
+        let dir = tempdir().unwrap();
+        let path = write_config(
+            &dir,
+            &minimal_config_v02(
+                r#"
+[[vms]]
+name = "Web App"
+cpus = 2
+memory = "1024 MiB"
+count = 2
+"#,
+            ),
+        );
+
+        let config = load_project_config(&path).expect("load multi-instance config with defaults");
+        assert_eq!(config.vms.len(), 2);
+        let base_overlay = default_overlay_base_path(&config.state_root, "Web App");
+        for (idx, vm) in config.vms.iter().enumerate() {
+            assert_eq!(vm.replica_index, idx);
+            let expected_path = default_alpine_base_image_path(&config.state_root);
+            assert_eq!(vm.base_image.path(), expected_path.as_path());
+            assert_eq!(
+                vm.base_image.provenance(),
+                BaseImageProvenance::DefaultAlpine
+            );
+            let expected_overlay = derive_overlay_for_instance(&base_overlay, idx, 2, true);
+            assert_eq!(vm.overlay, expected_overlay);
+        }
+    }
+
+    #[test]
+    fn overlay_role_slug_sanitizes_names() {
+        let slug = overlay_role_slug("API Box!");
+        let re = Regex::new(r"^api-box-[0-9a-f]{6}$").unwrap();
+        assert!(re.is_match(&slug), "unexpected slug {slug}");
+
+        let fallback = overlay_role_slug("???");
+        let fallback_re = Regex::new(r"^vm-[0-9a-f]{6}$").unwrap();
+        assert!(
+            fallback_re.is_match(&fallback),
+            "unexpected fallback {fallback}"
+        );
+    }
+
+    #[test]
+    fn bootstrap_mode_from_str_supports_aliases() {
+        assert_eq!(
+            "auto".parse::<BootstrapMode>().unwrap(),
+            BootstrapMode::Auto
+        );
+        assert_eq!(
+            "Automatic".parse::<BootstrapMode>().unwrap(),
+            BootstrapMode::Auto
+        );
+        assert_eq!(
+            "enabled".parse::<BootstrapMode>().unwrap(),
+            BootstrapMode::Auto
+        );
+        assert_eq!(
+            "skip".parse::<BootstrapMode>().unwrap(),
+            BootstrapMode::Skip
+        );
+        assert_eq!("off".parse::<BootstrapMode>().unwrap(), BootstrapMode::Skip);
+        assert_eq!(
+            "disabled".parse::<BootstrapMode>().unwrap(),
+            BootstrapMode::Skip
+        );
+        assert_eq!(
+            "always".parse::<BootstrapMode>().unwrap(),
+            BootstrapMode::Always
+        );
+        assert_eq!(
+            "force".parse::<BootstrapMode>().unwrap(),
+            BootstrapMode::Always
+        );
+        assert!("bogus".parse::<BootstrapMode>().is_err());
+    }
+
+    #[test]
+    fn parse_bootstrap_defaults_and_overrides() {
+        let dir = tempdir().unwrap();
+        let config_path = write_config(
+            &dir,
+            r#"
+version = "0.2.0"
 
-                let verify = bootstrap_override.and_then(|cfg| {
-                    if cfg.verify_command.is_some() || cfg.verify_path.is_some() {
-                        Some(BootstrapVerifyConfig {
-                            command: cfg.verify_command.clone(),
-                            path: cfg.verify_path.clone(),
-                        })
-                    } else {
-                        None
-                    }
-                });
+[project]
+name = "demo"
 
-                expanded_vms.push(VmDefinition {
-                    name: instance_name,
-                    role_name: role_name.clone(),
-                    replica_index: idx,
-                    description,
-                    base_image: image_source,
-                    overlay: overlay_path,
-                    cpus,
-                    memory: memory_spec,
-                    port_forwards: forwards,
-                    bootstrap: VmBootstrapConfig {
-                        mode: base_bootstrap_mode,
-                        script: Some(script_path),
-                        payload: Some(payload_path),
-                        handshake_timeout_secs,
-                        remote_dir,
-                        env,
-                        verify,
-                    },
-                });
-            }
+[bootstrap]
+mode = "always"
+handshake_timeout_secs = 120
+remote_dir = "/opt/bootstrap"
+[bootstrap.env]
+SHARED = "true"
+FOO = "top"
 
-            if let Some(extra) = overrides.into_values().next() {
-                return Err(invalid_config(
-                    path,
-                    format!(
-                        "Replica override `{}` was not applied. Verify the ID matches `<{role_name}>-<index>` within the declared count.",
-                        extra.id
-                    ),
-                ));
-            }
-        }
+[[vms]]
+name = "dev"
+base_image = "images/dev.qcow2"
+overlay = ".castra/dev.qcow2"
 
-        let workflows = Workflows {
-            init: workflows.init,
-        };
+  [vms.bootstrap]
+  mode = "skip"
+  script = "bootstrap/dev/run.sh"
+  payload = "bootstrap/dev/payload"
+  handshake_timeout_secs = 15
+  remote_dir = "/opt/dev"
+  verify_command = "check"
+  verify_path = "/status/ok"
 
-        let lifecycle = match lifecycle {
-            Some(raw) => raw.into_config(path)?,
-            None => LifecycleConfig::default(),
-        };
+    [vms.bootstrap.env]
+    LOCAL = "yes"
+    FOO = "vm"
+"#,
+        );
 
-        Ok(ProjectConfig {
-            file_path: path.to_path_buf(),
-            project_root,
-            version,
-            project_name,
-            features,
-            vms: expanded_vms,
-            state_root,
-            workflows,
-            lifecycle,
-            bootstrap: bootstrap_config,
-            warnings: warnings.clone(),
-        })
-    }
-}
+        let project =
+            load_project_config(&config_path).expect("configuration with bootstrap overrides");
 
-fn resolve_path(base: &Path, input: PathBuf) -> PathBuf {
-    if input.is_absolute() {
-        input
-    } else if base.is_absolute() {
-        base.join(input)
-    } else {
-        match env::current_dir() {
-            Ok(cwd) => cwd.join(base).join(input),
-            Err(_) => base.join(input),
-        }
-    }
-}
+        assert_eq!(project.bootstrap.mode, BootstrapMode::Always);
+        assert_eq!(project.bootstrap.handshake_timeout_secs, 120);
+        assert_eq!(
+            project.bootstrap.remote_dir,
+            PathBuf::from("/opt/bootstrap")
+        );
+        assert_eq!(
+            project.bootstrap.env.get("SHARED"),
+            Some(&"true".to_string())
+        );
+        assert_eq!(project.bootstrap.env.get("FOO"), Some(&"top".to_string()));
 
-fn resolve_overlay_path(config_root: &Path, state_root: &Path, overlay: PathBuf) -> PathBuf {
-    if overlay.is_absolute() {
-        overlay
-    } else if overlay.starts_with(".castra") {
-        match overlay.strip_prefix(".castra") {
-            Ok(remainder) if remainder.as_os_str().is_empty() => state_root.to_path_buf(),
-            Ok(remainder) => state_root.join(remainder),
-            Err(_) => state_root.to_path_buf(),
-        }
-    } else {
-        resolve_path(config_root, overlay)
+        assert_eq!(project.vms.len(), 1);
+        let vm = &project.vms[0];
+        assert_eq!(vm.bootstrap.mode, BootstrapMode::Skip);
+        let project_root = config_path.parent().unwrap();
+        assert_eq!(
+            vm.bootstrap.script.as_ref().expect("script path").as_path(),
+            &project_root.join("bootstrap/dev/run.sh")
+        );
+        assert_eq!(
+            vm.bootstrap.payload,
+            Some(PayloadSource::Dir(
+                project_root.join("bootstrap/dev/payload")
+            ))
+        );
+        assert_eq!(vm.bootstrap.handshake_timeout_secs, 15);
+        assert_eq!(vm.bootstrap.remote_dir, PathBuf::from("/opt/dev"));
+        assert_eq!(vm.bootstrap.env.get("SHARED"), Some(&"true".to_string()));
+        assert_eq!(vm.bootstrap.env.get("LOCAL"), Some(&"yes".to_string()));
+        assert_eq!(vm.bootstrap.env.get("FOO"), Some(&"vm".to_string()));
+        let verify = vm.bootstrap.verify.as_ref().expect("verify config");
+        assert_eq!(verify.command.as_deref(), Some("check"));
+        assert_eq!(
+            verify.path.as_ref().map(PathBuf::as_path),
+            Some(Path::new("/status/ok"))
+        );
     }
-}
 
-fn parse_memory(input: &str) -> Result<MemorySpec, String> {
-    let trimmed = input.trim();
-    if trimmed.is_empty() {
-        return Err("memory value cannot be empty".to_string());
-    }
+    #[test]
+    fn lifecycle_defaults_applied() {
+        let dir = tempdir().unwrap();
+        let path = write_config(
+            &dir,
+            &minimal_config(
+                r#"
+[[vms]]
+name = "devbox"
+base_image = "images/devbox.qcow2"
+overlay = ".castra/devbox-overlay.qcow2"
+cpus = 2
+memory = "2048 MiB"
+"#,
+            ),
+        );
 
-    let mut parts = trimmed.split_whitespace();
-    let amount = parts
-        .next()
-        .ok_or_else(|| "memory value is missing numeric component".to_string())?;
-    let unit = parts.next();
-    if parts.next().is_some() {
-        return Err("memory value contains unexpected extra tokens".to_string());
+        let config = load_project_config(&path).expect("load config");
+        assert_eq!(
+            config.lifecycle.graceful_shutdown_wait_secs,
+            DEFAULT_GRACEFUL_SHUTDOWN_WAIT_SECS
+        );
+        assert_eq!(
+            config.lifecycle.sigterm_wait_secs,
+            DEFAULT_SIGTERM_WAIT_SECS
+        );
+        assert_eq!(
+            config.lifecycle.sigkill_wait_secs,
+            DEFAULT_SIGKILL_WAIT_SECS
+        );
     }
 
-    let amount_value: f64 = amount.parse().map_err(|_| {
-        format!("could not parse `{amount}` as a number; try values like `2048 MiB` or `2 GiB`")
-    })?;
+    #[test]
+    fn lifecycle_overrides_parse() {
+        let dir = tempdir().unwrap();
+        let path = write_config(
+            &dir,
+            &minimal_config(
+                r#"
+[lifecycle]
+graceful_shutdown_wait_secs = 5
+sigterm_wait_secs = 2
+sigkill_wait_secs = 7
 
-    let bytes = match unit.map(|u| u.to_ascii_lowercase()) {
-        Some(ref u) if u == "mib" || u == "mb" => (amount_value * 1024.0 * 1024.0) as u64,
-        Some(ref u) if u == "gib" || u == "gb" => (amount_value * 1024.0 * 1024.0 * 1024.0) as u64,
-        Some(ref u) if u == "kib" || u == "kb" => (amount_value * 1024.0) as u64,
-        Some(ref u) if u == "b" || u == "bytes" => amount_value as u64,
-        Some(ref u) => {
-            return Err(format!(
-                "unsupported memory unit `{u}`; supported units are B, KiB, MiB, GiB."
-            ));
-        }
-        None => (amount_value * 1024.0 * 1024.0) as u64,
-    };
+[[vms]]
+name = "devbox"
+base_image = "images/devbox.qcow2"
+overlay = ".castra/devbox-overlay.qcow2"
+cpus = 2
+memory = "2048 MiB"
+"#,
+            ),
+        );
 
-    Ok(MemorySpec {
-        original: trimmed.to_string(),
-        bytes: Some(bytes),
-    })
-}
+        let config = load_project_config(&path).expect("load config");
+        assert_eq!(config.lifecycle.graceful_shutdown_wait_secs, 5);
+        assert_eq!(config.lifecycle.sigterm_wait_secs, 2);
+        assert_eq!(config.lifecycle.sigkill_wait_secs, 7);
+        assert_eq!(config.lifecycle.graceful_wait(), Duration::from_secs(5));
+        assert_eq!(config.lifecycle.sigterm_wait(), Duration::from_secs(2));
+        assert_eq!(config.lifecycle.sigkill_wait(), Duration::from_secs(7));
+    }
 
-pub(crate) fn default_state_root(project_name: &str, config_path: &Path) -> PathBuf {
-    let home = user_home_dir().unwrap_or_else(|| PathBuf::from("."));
-    let projects_root = home.join(".castra").join("projects");
-    let slug = slugify_project_name(project_name);
-    let unique = derive_project_id(config_path);
-    projects_root.join(format!("{slug}-{unique}"))
-}
+    #[test]
+    fn images_trusted_keys_require_exactly_one_scheme() {
+        let dir = tempdir().unwrap();
+        let vm = r#"
+[[vms]]
+name = "devbox"
+base_image = "images/devbox.qcow2"
+"#;
+        let path = write_config(
+            &dir,
+            &minimal_config(&format!(
+                "[[images.trusted_keys]]\nname = \"release\"\nminisign = \"RWQkey\"\n\n[[images.trusted_keys]]\ned25519 = \"abcd\"\n{vm}"
+            )),
+        );
+        let config = load_project_config(&path).unwrap();
+        assert_eq!(config.images.trusted_keys.len(), 2);
+        assert_eq!(config.images.trusted_keys[0].name, "release");
+        assert_eq!(
+            config.images.trusted_keys[0].scheme,
+            SignatureScheme::Minisign
+        );
+        assert_eq!(config.images.trusted_keys[1].name, "trusted key #2");
+        assert_eq!(
+            config.images.trusted_keys[1].scheme,
+            SignatureScheme::Ed25519
+        );
 
-fn slugify_project_name(name: &str) -> String {
-    let mut slug = String::with_capacity(name.len());
-    let mut last_was_dash = true;
-    for ch in name.chars() {
-        if ch.is_ascii_alphanumeric() {
-            slug.push(ch.to_ascii_lowercase());
-            last_was_dash = false;
-        } else if !last_was_dash {
-            slug.push('-');
-            last_was_dash = true;
+        let path = write_config(
+            &dir,
+            &minimal_config(&format!(
+                "[[images.trusted_keys]]\nminisign = \"a\"\ned25519 = \"b\"\n{vm}"
+            )),
+        );
+        match load_project_config(&path).unwrap_err() {
+            Error::InvalidConfig { message, .. } => {
+                assert!(message.contains("exactly one"), "{message}");
+            }
+            other => panic!("unexpected error: {other:?}"),
         }
     }
 
-    let trimmed = slug.trim_matches('-');
-    if trimmed.is_empty() {
-        "project".to_string()
-    } else {
-        trimmed.to_string()
-    }
-}
+    #[test]
+    fn lifecycle_sigkill_requires_positive_duration() {
+        let dir = tempdir().unwrap();
+        let path = write_config(
+            &dir,
+            &minimal_config(
+                r#"
+[lifecycle]
+sigkill_wait_secs = 0
 
-fn derive_project_id(config_path: &Path) -> String {
-    let parent = config_path.parent().unwrap_or_else(|| Path::new("."));
-    let repr = parent.to_string_lossy();
-    let mut hasher = Sha256::new();
-    hasher.update(repr.as_bytes());
-    let digest = hasher.finalize();
-    hex::encode(&digest[..8])
-}
+[[vms]]
+name = "devbox"
+base_image = "images/devbox.qcow2"
+overlay = ".castra/devbox-overlay.qcow2"
+cpus = 2
+memory = "2048 MiB"
+"#,
+            ),
+        );
 
-pub(crate) fn user_home_dir() -> Option<PathBuf> {
-    if let Some(home) = std::env::var_os("HOME") {
-        if !home.is_empty() {
-            return Some(PathBuf::from(home));
+        let err = load_project_config(&path).unwrap_err();
+        match err {
+            Error::InvalidConfig { message, .. } => {
+                assert!(
+                    message.contains("sigkill_wait_secs"),
+                    "unexpected message: {message}"
+                );
+            }
+            other => panic!("unexpected error: {other:?}"),
         }
     }
 
-    #[cfg(windows)]
-    {
-        if let Some(profile) = std::env::var_os("USERPROFILE") {
-            if !profile.is_empty() {
-                return Some(PathBuf::from(profile));
-            }
-        }
+    #[test]
+    fn images_mirrors_parse_and_validate() {
+        let dir = tempdir().unwrap();
+        let vm = r#"
+[[vms]]
+name = "devbox"
+base_image = "images/devbox.qcow2"
+overlay = ".castra/devbox-overlay.qcow2"
+"#;
+        let path = write_config(
+            &dir,
+            &minimal_config(&format!(
+                "[images]\nmirrors = [\"https://mirror.internal/castra/\", \"http://10.0.0.5:8080\"]\n{vm}"
+            )),
+        );
+        let config = load_project_config(&path).unwrap();
+        assert_eq!(
+            config.images.mirrors,
+            vec![
+                "https://mirror.internal/castra".to_string(),
+                "http://10.0.0.5:8080".to_string()
+            ]
+        );
 
-        let drive = std::env::var_os("HOMEDRIVE");
-        let path = std::env::var_os("HOMEPATH");
-        if let (Some(drive), Some(path)) = (drive, path) {
-            if !drive.is_empty() && !path.is_empty() {
-                let mut combined = PathBuf::from(drive);
-                combined.push(path);
-                if combined.components().next().is_some() {
-                    return Some(combined);
-                }
+        let path = write_config(
+            &dir,
+            &minimal_config(&format!("[images]\nmirrors = [\"/srv/images\"]\n{vm}")),
+        );
+        match load_project_config(&path).unwrap_err() {
+            Error::InvalidConfig { message, .. } => {
+                assert!(message.contains("[images].mirrors"), "{message}");
             }
+            other => panic!("unexpected error: {other:?}"),
         }
     }
 
-    None
-}
+    #[test]
+    fn bootstrap_bake_defaults_and_per_vm_override() {
+        let dir = tempdir().unwrap();
+        let path = write_config(
+            &dir,
+            &minimal_config(
+                r#"
+[bootstrap]
+bake = true
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::error::Error;
-    use regex::Regex;
-    use std::path::{Path, PathBuf};
-    use std::time::Duration;
-    use temp_env::with_var;
-    use tempfile::tempdir;
+[[vms]]
+name = "baked"
+base_image = "images/devbox.qcow2"
+overlay = ".castra/baked-overlay.qcow2"
 
-    fn write_config(dir: &tempfile::TempDir, contents: &str) -> PathBuf {
-        let path = dir.path().join("castra.toml");
-        fs::write(&path, contents).expect("write config");
-        path
+[[vms]]
+name = "fresh"
+base_image = "images/devbox.qcow2"
+overlay = ".castra/fresh-overlay.qcow2"
+
+[vms.bootstrap]
+bake = false
+"#,
+            ),
+        );
+        let config = load_project_config(&path).unwrap();
+        assert!(config.bootstrap.bake);
+        assert!(config.vms[0].bootstrap.bake);
+        assert!(!config.vms[1].bootstrap.bake);
     }
 
-    fn minimal_config(contents: &str) -> String {
-        format!(
-            r#"
-version = "0.1.0"
+    #[test]
+    fn managed_keys_default_off_for_the_default_alpine_image() {
+        let dir = tempdir().unwrap();
+        let path = write_config(
+            &dir,
+            &minimal_config_v02(
+                r#"
+[[vms]]
+name = "alpine"
 
-[project]
-name = "demo"
-state_dir = ".castra"
+[[vms]]
+name = "forced"
+count = 2
 
-{contents}
-"#
-        )
+[vms.bootstrap]
+managed_keys = true
+"#,
+            ),
+        );
+        let config = load_project_config(&path).unwrap();
+        assert_eq!(
+            config.vms[0].base_image.provenance(),
+            BaseImageProvenance::DefaultAlpine
+        );
+        assert!(config.vms[0].bootstrap.managed_keys.is_none());
+        assert!(config.vms[1].bootstrap.managed_keys.is_some());
+        let warnings: Vec<&String> = config
+            .warnings
+            .iter()
+            .filter(|warning| warning.contains("managed_keys"))
+            .collect();
+        assert_eq!(warnings.len(), 1, "{:?}", config.warnings);
+        assert!(warnings[0].contains("`forced`"), "{}", warnings[0]);
     }
 
-    fn minimal_config_v02(contents: &str) -> String {
-        format!(
-            r#"
-version = "0.2.0"
+    #[test]
+    fn managed_keys_default_on_with_per_vm_opt_out() {
+        let dir = tempdir().unwrap();
+        let path = write_config(
+            &dir,
+            &minimal_config(
+                r#"
+[[vms]]
+name = "managed"
+base_image = "images/devbox.qcow2"
+overlay = ".castra/managed-overlay.qcow2"
 
-[project]
-name = "demo"
-state_dir = ".castra/state"
+[[vms]]
+name = "manual"
+base_image = "images/devbox.qcow2"
+overlay = ".castra/manual-overlay.qcow2"
 
-{contents}
-"#
-        )
+[vms.bootstrap]
+managed_keys = false
+"#,
+            ),
+        );
+        let config = load_project_config(&path).unwrap();
+        let keys = config.vms[0]
+            .bootstrap
+            .managed_keys
+            .as_ref()
+            .expect("managed keys enabled by default");
+        assert_eq!(keys.identity, config.state_root.join("ssh/id_ed25519"));
+        assert_eq!(
+            keys.public_key(),
+            config.state_root.join("ssh/id_ed25519.pub")
+        );
+        assert_eq!(keys.known_hosts, config.state_root.join("ssh/known_hosts"));
+        assert!(config.vms[1].bootstrap.managed_keys.is_none());
     }
 
     #[test]
-    fn load_config_with_local_base_image() {
+    fn env_secrets_layer_and_resolve_file_paths() {
         let dir = tempdir().unwrap();
         let path = write_config(
             &dir,
             &minimal_config(
                 r#"
+[bootstrap.env_secrets]
+TOKEN = { env = "REGISTRY_TOKEN" }
+
 [[vms]]
 name = "devbox"
 base_image = "images/devbox.qcow2"
 overlay = ".castra/devbox-overlay.qcow2"
-cpus = 2
-memory = "2048 MiB"
+
+[vms.bootstrap.env_secrets]
+DEPLOY_KEY = { file = "secrets/deploy_key" }
 "#,
             ),
         );
-
-        let config = load_project_config(&path).expect("load config");
-        assert_eq!(config.project_name, "demo");
-        assert_eq!(config.vms.len(), 1);
-        let vm = &config.vms[0];
-        assert_eq!(vm.name, "devbox");
-        assert_eq!(vm.role_name, "devbox");
-        assert_eq!(vm.replica_index, 0);
-        assert_eq!(
-            vm.base_image.path(),
-            dir.path().join("images/devbox.qcow2").as_path(),
-            "base image path is resolved relative to file"
-        );
+        let config = load_project_config(&path).unwrap();
+        let bootstrap = &config.vms[0].bootstrap;
         assert_eq!(
-            vm.base_image.provenance(),
-            BaseImageProvenance::Explicit,
-            "explicit base image marked as explicit provenance"
+            bootstrap.env_secrets.get("TOKEN"),
+            Some(&SecretSource::Env("REGISTRY_TOKEN".to_string()))
         );
         assert_eq!(
-            vm.overlay,
-            dir.path().join(".castra/devbox-overlay.qcow2"),
-            "overlay resolves under explicit state_dir"
+            bootstrap.env_secrets.get("DEPLOY_KEY"),
+            Some(&SecretSource::File(dir.path().join("secrets/deploy_key")))
         );
-        assert_eq!(vm.cpus, 2);
-        assert_eq!(vm.memory.original(), "2048 MiB");
-        assert_eq!(vm.memory.bytes(), Some(2048 * 1024 * 1024));
-        assert!(config.warnings.is_empty());
-
-        let conflicts = config.port_conflicts();
-        assert!(conflicts.is_empty());
         assert_eq!(
-            config.state_root,
-            dir.path().join(".castra"),
-            "state root uses project override"
+            bootstrap.secret_salt,
+            config.state_root.join("bootstrap/secret.salt")
         );
-    }
 
-    #[test]
-    fn load_config_defaults_base_image_and_overlay() {
-        let dir = tempdir().unwrap();
         let path = write_config(
             &dir,
-            &minimal_config_v02(
+            &minimal_config(
                 r#"
+[bootstrap.env_secrets]
+TOKEN = { env = "A", file = "b" }
+
 [[vms]]
 name = "devbox"
-cpus = 2
-memory = "1024 MiB"
+base_image = "images/devbox.qcow2"
+overlay = ".castra/devbox-overlay.qcow2"
 "#,
             ),
         );
+        match load_project_config(&path).unwrap_err() {
+            Error::InvalidConfig { message, .. } => {
+                assert!(
+                    message.contains("exactly one of `file` or `env`"),
+                    "unexpected message: {message}"
+                );
+            }
+            other => panic!("unexpected error: {other:?}"),
+        }
+    }
 
-        let config = load_project_config(&path).expect("load config with defaults");
-        assert_eq!(config.vms.len(), 1);
-        let vm = &config.vms[0];
-        let expected_path = default_alpine_base_image_path(&config.state_root);
-        assert_eq!(
-            vm.base_image.path(),
-            expected_path.as_path(),
-            "default base image points at cached alpine image"
-        );
+    #[test]
+    fn env_interpolation_expands_defaults_and_rejects_unset_variables() {
+        let path_value = std::env::var("PATH").expect("PATH set");
+        assert_eq!(interpolate_str("${PATH}").unwrap(), path_value);
         assert_eq!(
-            vm.base_image.provenance(),
-            BaseImageProvenance::DefaultAlpine,
-            "default base image provenance recorded"
+            interpolate_str("${CASTRA_TEST_UNSET_VAR:-4096 MiB}").unwrap(),
+            "4096 MiB"
         );
-
         assert_eq!(
-            vm.overlay,
-            default_overlay_base_path(&config.state_root, "devbox")
-        );
-        let overlay_name = vm
-            .overlay
-            .file_name()
-            .and_then(|name| name.to_str())
-            .expect("overlay file name");
-        let re = Regex::new(r"^devbox-[0-9a-f]{6}-overlay\.qcow2$").unwrap();
-        assert!(
-            re.is_match(overlay_name),
-            "unexpected overlay file name {overlay_name}"
+            interpolate_str("cost $5 $${literal}").unwrap(),
+            "cost $5 ${literal}"
         );
-    }
+        assert!(interpolate_str("${CASTRA_TEST_UNSET_VAR}").is_err());
+        assert!(interpolate_str("${not valid}").is_err());
 
-    #[test]
-    fn load_config_defaults_multi_instance_overlays() {
         let dir = tempdir().unwrap();
         let path = write_config(
             &dir,
-            &minimal_config_v02(
+            &minimal_config(
                 r#"
 [[vms]]
-name = "Web App"
-cpus = 2
-memory = "1024 MiB"
-count = 2
+name = "devbox"
+base_image = "images/devbox.qcow2"
+overlay = ".castra/devbox-overlay.qcow2"
+memory = "${CASTRA_TEST_UNSET_VAR}"
 "#,
             ),
         );
-
-        let config = load_project_config(&path).expect("load multi-instance config with defaults");
-        assert_eq!(config.vms.len(), 2);
-        let base_overlay = default_overlay_base_path(&config.state_root, "Web App");
-        for (idx, vm) in config.vms.iter().enumerate() {
-            assert_eq!(vm.replica_index, idx);
-            let expected_path = default_alpine_base_image_path(&config.state_root);
-            assert_eq!(vm.base_image.path(), expected_path.as_path());
-            assert_eq!(
-                vm.base_image.provenance(),
-                BaseImageProvenance::DefaultAlpine
-            );
-            let expected_overlay = derive_overlay_for_instance(&base_overlay, idx, 2, true);
-            assert_eq!(vm.overlay, expected_overlay);
-        }
+        let message = load_project_config(&path).unwrap_err().to_string();
+        assert!(message.contains("CASTRA_TEST_UNSET_VAR"), "{message}");
+        assert!(message.contains("vms[0].memory"), "{message}");
     }
 
     #[test]
-    fn overlay_role_slug_sanitizes_names() {
-        let slug = overlay_role_slug("API Box!");
-        let re = Regex::new(r"^api-box-[0-9a-f]{6}$").unwrap();
-        assert!(re.is_match(&slug), "unexpected slug {slug}");
+    fn env_interpolation_leaves_shell_commands_alone() {
+        let dir = tempdir().unwrap();
+        let path = write_config(
+            &dir,
+            &minimal_config(
+                r#"
+[workflows]
+pre_up = ["echo \"${CASTRA_TEST_UNSET_VAR:-none}\" > \"${TMPDIR}/{{ vm }}\""]
 
-        let fallback = overlay_role_slug("???");
-        let fallback_re = Regex::new(r"^vm-[0-9a-f]{6}$").unwrap();
-        assert!(
-            fallback_re.is_match(&fallback),
-            "unexpected fallback {fallback}"
-        );
-    }
+[[vms]]
+name = "devbox"
+base_image = "images/devbox.qcow2"
+overlay = ".castra/devbox-overlay.qcow2"
+memory = "${CASTRA_TEST_UNSET_VAR:-1024 MiB}"
 
-    #[test]
-    fn bootstrap_mode_from_str_supports_aliases() {
-        assert_eq!(
-            "auto".parse::<BootstrapMode>().unwrap(),
-            BootstrapMode::Auto
-        );
-        assert_eq!(
-            "Automatic".parse::<BootstrapMode>().unwrap(),
-            BootstrapMode::Auto
-        );
-        assert_eq!(
-            "enabled".parse::<BootstrapMode>().unwrap(),
-            BootstrapMode::Auto
-        );
-        assert_eq!(
-            "skip".parse::<BootstrapMode>().unwrap(),
-            BootstrapMode::Skip
-        );
-        assert_eq!("off".parse::<BootstrapMode>().unwrap(), BootstrapMode::Skip);
-        assert_eq!(
-            "disabled".parse::<BootstrapMode>().unwrap(),
-            BootstrapMode::Skip
-        );
-        assert_eq!(
-            "always".parse::<BootstrapMode>().unwrap(),
-            BootstrapMode::Always
+  [vms.bootstrap]
+  verify_command = "test -d \"${HOME}/app\""
+"#,
+            ),
         );
+
+        let config = load_project_config(&path).unwrap();
         assert_eq!(
-            "force".parse::<BootstrapMode>().unwrap(),
-            BootstrapMode::Always
+            config.workflows.pre_up,
+            ["echo \"${CASTRA_TEST_UNSET_VAR:-none}\" > \"${TMPDIR}/{{ vm }}\""]
         );
-        assert!("bogus".parse::<BootstrapMode>().is_err());
+        let verify = config.vms[0].bootstrap.verify.as_ref().unwrap();
+        assert_eq!(verify.command.as_deref(), Some("test -d \"${HOME}/app\""));
+        assert_eq!(config.vms[0].memory.original(), "1024 MiB");
     }
 
     #[test]
-    fn parse_bootstrap_defaults_and_overrides() {
+    fn includes_merge_and_profiles_overlay_vm_settings() {
         let dir = tempdir().unwrap();
-        let config_path = write_config(
-            &dir,
+        fs::write(
+            dir.path().join("common.toml"),
             r#"
 version = "0.2.0"
 
 [project]
 name = "demo"
+state_dir = ".castra"
 
 [bootstrap]
-mode = "always"
-handshake_timeout_secs = 120
-remote_dir = "/opt/bootstrap"
-[bootstrap.env]
-SHARED = "true"
-FOO = "top"
+mode = "auto"
 
 [[vms]]
-name = "dev"
-base_image = "images/dev.qcow2"
-overlay = ".castra/dev.qcow2"
-
-  [vms.bootstrap]
-  mode = "skip"
-  script = "bootstrap/dev/run.sh"
-  payload = "bootstrap/dev/payload"
-  handshake_timeout_secs = 15
-  remote_dir = "/opt/dev"
-  verify_command = "check"
-  verify_path = "/status/ok"
+name = "api"
+base_image = "images/api.qcow2"
+cpus = 1
+memory = "1024 MiB"
 
-    [vms.bootstrap.env]
-    LOCAL = "yes"
-    FOO = "vm"
+[[vms]]
+name = "db"
+base_image = "images/db.qcow2"
 "#,
-        );
+        )
+        .unwrap();
+        let path = write_config(
+            &dir,
+            r#"
+include = ["common.toml"]
 
-        let project =
-            load_project_config(&config_path).expect("configuration with bootstrap overrides");
+[[vms]]
+name = "api"
+cpus = 2
 
-        assert_eq!(project.bootstrap.mode, BootstrapMode::Always);
-        assert_eq!(project.bootstrap.handshake_timeout_secs, 120);
-        assert_eq!(
-            project.bootstrap.remote_dir,
-            PathBuf::from("/opt/bootstrap")
-        );
-        assert_eq!(
-            project.bootstrap.env.get("SHARED"),
-            Some(&"true".to_string())
-        );
-        assert_eq!(project.bootstrap.env.get("FOO"), Some(&"top".to_string()));
+[profiles.ci]
+bootstrap = { mode = "always" }
 
-        assert_eq!(project.vms.len(), 1);
-        let vm = &project.vms[0];
-        assert_eq!(vm.bootstrap.mode, BootstrapMode::Skip);
-        let project_root = config_path.parent().unwrap();
-        assert_eq!(
-            vm.bootstrap.script.as_ref().expect("script path").as_path(),
-            &project_root.join("bootstrap/dev/run.sh")
-        );
-        assert_eq!(
-            vm.bootstrap
-                .payload
-                .as_ref()
-                .expect("payload path")
-                .as_path(),
-            &project_root.join("bootstrap/dev/payload")
-        );
-        assert_eq!(vm.bootstrap.handshake_timeout_secs, 15);
-        assert_eq!(vm.bootstrap.remote_dir, PathBuf::from("/opt/dev"));
-        assert_eq!(vm.bootstrap.env.get("SHARED"), Some(&"true".to_string()));
-        assert_eq!(vm.bootstrap.env.get("LOCAL"), Some(&"yes".to_string()));
-        assert_eq!(vm.bootstrap.env.get("FOO"), Some(&"vm".to_string()));
-        let verify = vm.bootstrap.verify.as_ref().expect("verify config");
-        assert_eq!(verify.command.as_deref(), Some("check"));
-        assert_eq!(
-            verify.path.as_ref().map(PathBuf::as_path),
-            Some(Path::new("/status/ok"))
+[profiles.ci.vms.api]
+count = 3
+memory = "${CASTRA_TEST_UNSET_VAR:-4096 MiB}"
+base_image = "images/ci.qcow2"
+
+[profiles.ci.vms.db.bootstrap]
+mode = "skip"
+"#,
         );
+
+        let base = load_project_config(&path).unwrap();
+        assert_eq!(base.vms.len(), 2);
+        assert_eq!(base.vms[0].cpus, 2);
+        assert_eq!(base.vms[0].memory.original(), "1024 MiB");
+        assert!(base.warnings.is_empty(), "{:?}", base.warnings);
+
+        let ci = load_project_config_with_profile(&path, Some("ci")).unwrap();
+        assert_eq!(ci.bootstrap.mode, BootstrapMode::Always);
+        let api: Vec<_> = ci.vms.iter().filter(|vm| vm.role_name == "api").collect();
+        assert_eq!(api.len(), 3);
+        assert_eq!(api[0].cpus, 2);
+        assert_eq!(api[0].memory.original(), "4096 MiB");
+        assert!(api[0].base_image.path().ends_with("images/ci.qcow2"));
+        let db = ci.vms.iter().find(|vm| vm.role_name == "db").unwrap();
+        assert_eq!(db.bootstrap.mode, BootstrapMode::Skip);
+
+        let snapshot = dir.path().join("snapshot.toml");
+        fs::write(&snapshot, resolve_config_source(&path, Some("ci")).unwrap()).unwrap();
+        let reloaded = load_project_config(&snapshot).unwrap();
+        assert_eq!(reloaded.vms.len(), ci.vms.len());
+        assert_eq!(reloaded.bootstrap.mode, BootstrapMode::Always);
+
+        let message = load_project_config_with_profile(&path, Some("laptop"))
+            .unwrap_err()
+            .to_string();
+        assert!(message.contains("Unknown profile `laptop`"), "{message}");
+        assert!(message.contains("available profiles: ci"), "{message}");
     }
 
     #[test]
-    fn lifecycle_defaults_applied() {
+    fn profiles_reject_unsupported_overrides_and_include_cycles() {
         let dir = tempdir().unwrap();
         let path = write_config(
             &dir,
@@ -2101,89 +4377,83 @@ overlay = ".castra/dev.qcow2"
 [[vms]]
 name = "devbox"
 base_image = "images/devbox.qcow2"
-overlay = ".castra/devbox-overlay.qcow2"
-cpus = 2
-memory = "2048 MiB"
+
+[profiles.ci.vms.devbox]
+overlay = "elsewhere.qcow2"
+
+[profiles.ci.vms.ghost]
+cpus = 4
 "#,
             ),
         );
-
-        let config = load_project_config(&path).expect("load config");
-        assert_eq!(
-            config.lifecycle.graceful_shutdown_wait_secs,
-            DEFAULT_GRACEFUL_SHUTDOWN_WAIT_SECS
-        );
-        assert_eq!(
-            config.lifecycle.sigterm_wait_secs,
-            DEFAULT_SIGTERM_WAIT_SECS
-        );
-        assert_eq!(
-            config.lifecycle.sigkill_wait_secs,
-            DEFAULT_SIGKILL_WAIT_SECS
-        );
+        let message = load_project_config_with_profile(&path, Some("ci"))
+            .unwrap_err()
+            .to_string();
+        assert!(message.contains("cannot override `overlay`"), "{message}");
+
+        fs::write(dir.path().join("a.toml"), "include = [\"b.toml\"]\n").unwrap();
+        fs::write(dir.path().join("b.toml"), "include = [\"a.toml\"]\n").unwrap();
+        let message = load_project_config(&dir.path().join("a.toml"))
+            .unwrap_err()
+            .to_string();
+        assert!(message.contains("Include cycle"), "{message}");
     }
 
     #[test]
-    fn lifecycle_overrides_parse() {
+    fn workflows_parse_hooks_and_warn_on_legacy_init() {
         let dir = tempdir().unwrap();
         let path = write_config(
             &dir,
             &minimal_config(
                 r#"
-[lifecycle]
-graceful_shutdown_wait_secs = 5
-sigterm_wait_secs = 2
-sigkill_wait_secs = 7
-
 [[vms]]
 name = "devbox"
 base_image = "images/devbox.qcow2"
 overlay = ".castra/devbox-overlay.qcow2"
-cpus = 2
-memory = "2048 MiB"
+
+[workflows]
+init = ["qemu-img create"]
+pre_up = ["echo {{ vm }} {{ssh_port}}"]
+post_down = ["rm -f {{ state_root }}/marker"]
 "#,
             ),
         );
-
-        let config = load_project_config(&path).expect("load config");
-        assert_eq!(config.lifecycle.graceful_shutdown_wait_secs, 5);
-        assert_eq!(config.lifecycle.sigterm_wait_secs, 2);
-        assert_eq!(config.lifecycle.sigkill_wait_secs, 7);
-        assert_eq!(config.lifecycle.graceful_wait(), Duration::from_secs(5));
-        assert_eq!(config.lifecycle.sigterm_wait(), Duration::from_secs(2));
-        assert_eq!(config.lifecycle.sigkill_wait(), Duration::from_secs(7));
+        let config = load_project_config(&path).unwrap();
+        assert_eq!(
+            config.workflows.commands(HookPoint::PreUp),
+            ["echo {{ vm }} {{ssh_port}}".to_string()]
+        );
+        assert_eq!(config.workflows.post_down.len(), 1);
+        assert!(config.workflows.post_launch.is_empty());
+        assert!(
+            config
+                .warnings
+                .iter()
+                .any(|warning| warning.contains("`[workflows].init`"))
+        );
     }
 
     #[test]
-    fn lifecycle_sigkill_requires_positive_duration() {
+    fn workflows_reject_unknown_template_variables() {
         let dir = tempdir().unwrap();
         let path = write_config(
             &dir,
             &minimal_config(
                 r#"
-[lifecycle]
-sigkill_wait_secs = 0
-
 [[vms]]
 name = "devbox"
 base_image = "images/devbox.qcow2"
-overlay = ".castra/devbox-overlay.qcow2"
-cpus = 2
-memory = "2048 MiB"
-"#,
-            ),
-        );
-
-        let err = load_project_config(&path).unwrap_err();
-        match err {
-            Error::InvalidConfig { message, .. } => {
-                assert!(
-                    message.contains("sigkill_wait_secs"),
-                    "unexpected message: {message}"
-                );
-            }
-            other => panic!("unexpected error: {other:?}"),
-        }
+overlay = ".castra/devbox-overlay.qcow2"
+
+[workflows]
+post_launch = ["echo {{ hostname }}"]
+"#,
+            ),
+        );
+        let err = load_project_config(&path).unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("post_launch[0]"), "{message}");
+        assert!(message.contains("hostname"), "{message}");
     }
 
     #[test]
@@ -2398,6 +4668,541 @@ count = 0
         }
     }
 
+    #[test]
+    fn depends_on_expands_roles_and_orders_startup_waves() {
+        let dir = tempdir().unwrap();
+        let path = write_config(
+            &dir,
+            &minimal_config_v02(
+                r#"
+[[vms]]
+name = "app"
+count = 2
+depends_on = ["db", { vm = "cache-0", condition = "launched" }]
+
+[[vms]]
+name = "db"
+depends_on = [{ vm = "cache", condition = "healthy" }]
+
+[[vms]]
+name = "cache"
+"#,
+            ),
+        );
+
+        let config = load_project_config(&path).expect("load config");
+        let app = &config.vms[0];
+        assert_eq!(
+            app.depends_on,
+            vec![
+                VmDependency {
+                    vm: "db-0".to_string(),
+                    condition: DependencyCondition::Bootstrapped,
+                },
+                VmDependency {
+                    vm: "cache-0".to_string(),
+                    condition: DependencyCondition::Launched,
+                },
+            ]
+        );
+        assert_eq!(config.vms[1].depends_on, app.depends_on);
+        assert_eq!(
+            config.vms[2].depends_on[0].condition,
+            DependencyCondition::Healthy
+        );
+
+        let waves: Vec<Vec<&str>> = config
+            .startup_waves()
+            .into_iter()
+            .map(|wave| {
+                wave.into_iter()
+                    .map(|index| config.vms[index].name.as_str())
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            waves,
+            vec![vec!["cache-0"], vec!["db-0"], vec!["app-0", "app-1"]]
+        );
+    }
+
+    #[test]
+    fn depends_on_rejects_cycles_and_unknown_vms() {
+        let dir = tempdir().unwrap();
+        let path = write_config(
+            &dir,
+            &minimal_config_v02(
+                r#"
+[[vms]]
+name = "app"
+depends_on = ["db"]
+
+[[vms]]
+name = "db"
+depends_on = [{ vm = "app", condition = "launched" }]
+"#,
+            ),
+        );
+        match load_project_config(&path).expect_err("cycle should be rejected") {
+            Error::InvalidConfig { message, .. } => {
+                assert!(
+                    message.contains("cycle: app-0 → db-0 → app-0"),
+                    "unexpected message: {message}"
+                );
+            }
+            other => panic!("unexpected error: {other:?}"),
+        }
+
+        let path = write_config(
+            &dir,
+            &minimal_config_v02(
+                r#"
+[[vms]]
+name = "app"
+depends_on = [{ vm = "dbx", condition = "ready" }]
+"#,
+            ),
+        );
+        match load_project_config(&path).expect_err("bad condition should be rejected") {
+            Error::InvalidConfig { message, .. } => {
+                assert!(
+                    message.contains("Unknown dependency condition `ready`"),
+                    "{message}"
+                );
+            }
+            other => panic!("unexpected error: {other:?}"),
+        }
+
+        let path = write_config(
+            &dir,
+            &minimal_config_v02("[[vms]]\nname = \"app\"\ndepends_on = [\"dbx\"]\n"),
+        );
+        match load_project_config(&path).expect_err("unknown VM should be rejected") {
+            Error::InvalidConfig { message, .. } => {
+                assert!(message.contains("unknown VM `dbx`"), "{message}");
+            }
+            other => panic!("unexpected error: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn bootstrap_stages_resolve_shared_and_inline_definitions() {
+        let dir = tempdir().unwrap();
+        let path = write_config(
+            &dir,
+            &minimal_config_v02(
+                r#"
+[bootstrap.stages.base]
+script = "bootstrap/base.sh"
+run_if = "once"
+timeout_secs = 600
+
+[[vms]]
+name = "app"
+
+[vms.bootstrap]
+stages = [
+  "base",
+  { name = "toolchain", script = "bootstrap/toolchain.sh", payload = "bootstrap/toolchain", env = { RUST = "stable" } },
+]
+
+[[vms]]
+name = "db"
+
+[vms.bootstrap]
+stages = [{ name = "base", run_if = "always" }]
+"#,
+            ),
+        );
+
+        let config = load_project_config(&path).expect("load config");
+        let app = &config.vms[0].bootstrap;
+        assert!(app.script.is_none());
+        let names: Vec<&str> = app.stages.iter().map(|stage| stage.name.as_str()).collect();
+        assert_eq!(names, ["base", "toolchain"]);
+        assert_eq!(app.stages[0].run_if, BootstrapRunIf::Once);
+        assert_eq!(app.stages[0].timeout_secs, Some(600));
+        assert_eq!(app.stages[0].script, dir.path().join("bootstrap/base.sh"));
+        assert_eq!(
+            app.stages[1].payload,
+            Some(PayloadSource::Dir(dir.path().join("bootstrap/toolchain")))
+        );
+        assert_eq!(
+            app.stages[1].env.get("RUST").map(String::as_str),
+            Some("stable")
+        );
+
+        let db = &config.vms[1].bootstrap;
+        assert_eq!(db.stages.len(), 1);
+        assert_eq!(db.stages[0].run_if, BootstrapRunIf::Always);
+        assert_eq!(db.stages[0].timeout_secs, Some(600));
+        assert_eq!(db.stages[0].script, app.stages[0].script);
+    }
+
+    #[test]
+    fn bootstrap_stages_reject_invalid_definitions() {
+        let dir = tempdir().unwrap();
+        let cases = [
+            (
+                "[[vms]]\nname = \"app\"\n[vms.bootstrap]\nstages = [\"missing\"]\n",
+                "unknown bootstrap stage `missing`",
+            ),
+            (
+                "[[vms]]\nname = \"app\"\n[vms.bootstrap]\nstages = [{ name = \"a\", script = \"a.sh\" }, { name = \"a\", script = \"b.sh\" }]\n",
+                "lists bootstrap stage `a` more than once",
+            ),
+            (
+                "[[vms]]\nname = \"app\"\n[vms.bootstrap]\nscript = \"run.sh\"\nstages = [{ name = \"a\", script = \"a.sh\" }]\n",
+                "together with `bootstrap.script`",
+            ),
+            (
+                "[[vms]]\nname = \"app\"\n[vms.bootstrap]\nstages = [{ name = \"a\", script = \"a.sh\", run_if = \"sometimes\" }]\n",
+                "Unknown run_if `sometimes`",
+            ),
+        ];
+        for (body, expected) in cases {
+            let path = write_config(&dir, &minimal_config_v02(body));
+            match load_project_config(&path).expect_err("invalid stages should be rejected") {
+                Error::InvalidConfig { message, .. } => {
+                    assert!(message.contains(expected), "{message}");
+                }
+                other => panic!("unexpected error: {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn provision_section_resolves_primitives() {
+        let dir = tempdir().unwrap();
+        let path = write_config(
+            &dir,
+            &minimal_config_v02(
+                r#"
+[[vms]]
+name = "app"
+
+[vms.provision]
+packages = ["git", { name = "openssh", apt = "openssh-server", dnf = "openssh-server" }, { apk = "doas" }]
+services = ["sshd"]
+sysctls = { "net.ipv4.ip_forward" = "1" }
+
+[[vms.provision.files]]
+path = "/etc/motd"
+content = "hello {{ vm }}"
+mode = "0644"
+owner = "root:root"
+
+[[vms.provision.files]]
+path = "/etc/app.conf"
+source = "files/app.conf"
+
+[[vms.provision.users]]
+name = "dev"
+groups = ["wheel"]
+authorized_keys = ["ssh-ed25519 AAAA dev@host"]
+"#,
+            ),
+        );
+
+        let config = load_project_config(&path).expect("load config");
+        let provision = config.vms[0].provision.as_ref().expect("provision");
+        assert_eq!(provision.source, path);
+        assert_eq!(
+            provision.packages[1],
+            ProvisionPackage {
+                apk: Some("openssh".to_string()),
+                apt: Some("openssh-server".to_string()),
+                dnf: Some("openssh-server".to_string()),
+            }
+        );
+        assert_eq!(provision.packages[2].apt, None);
+        assert_eq!(provision.files[0].mode, Some(0o644));
+        assert_eq!(
+            provision.files[1].contents,
+            ProvisionFileContents::Source(dir.path().join("files/app.conf"))
+        );
+        assert_eq!(provision.users[0].groups, ["wheel"]);
+        assert_eq!(provision.services, ["sshd"]);
+        assert_eq!(
+            provision
+                .sysctls
+                .get("net.ipv4.ip_forward")
+                .map(String::as_str),
+            Some("1")
+        );
+    }
+
+    #[test]
+    fn template_vars_describe_vm_and_peers() {
+        let dir = tempdir().unwrap();
+        let path = write_config(
+            &dir,
+            &minimal_config_v02(
+                r#"
+[[vms]]
+name = "db"
+
+  [[vms.port_forwards]]
+  host = 15432
+  guest = 5432
+
+[[vms]]
+name = "web"
+count = 2
+"#,
+            ),
+        );
+
+        let config = load_project_config(&path).expect("load config");
+        let vars = &config.vms[2].bootstrap.template_vars;
+        let get = |name: &str| vars.get(name).map(String::as_str);
+        assert_eq!(get("vm"), Some("web-1"));
+        assert_eq!(get("role"), Some("web"));
+        assert_eq!(get("replica"), Some("1"));
+        assert_eq!(get("replicas"), Some("2"));
+        assert_eq!(get("role_members"), Some("web-0 web-1"));
+        assert_eq!(get("vms"), Some("db-0 web-0 web-1"));
+        assert_eq!(get("project"), Some("demo"));
+        assert_eq!(get("vms.db-0.address"), Some(GUEST_HOST_ADDRESS));
+        assert_eq!(get("vms.db-0.tcp.5432"), Some("15432"));
+        assert_eq!(get("vms.db-0.ssh_port"), Some(""));
+        assert_eq!(config.vms[0].bootstrap.template_vars["replicas"], "1");
+    }
+
+    #[test]
+    fn collect_rules_expand_per_replica() {
+        let dir = tempdir().unwrap();
+        let path = write_config(
+            &dir,
+            &minimal_config_v02(
+                r#"
+[[vms]]
+name = "api"
+count = 2
+collect = [
+    { guest = "/var/log/app/", host = "artifacts/{vm}" },
+    { guest = "/srv/report.xml", host = "/tmp/reports" },
+]
+"#,
+            ),
+        );
+
+        let config = load_project_config(&path).expect("load config");
+        assert_eq!(
+            config.vms[1].collect,
+            [
+                CollectRule {
+                    guest: "/var/log/app".to_string(),
+                    host: dir.path().join("artifacts/api-1"),
+                },
+                CollectRule {
+                    guest: "/srv/report.xml".to_string(),
+                    host: PathBuf::from("/tmp/reports"),
+                },
+            ]
+        );
+
+        for (body, expected) in [
+            (
+                "collect = [{ guest = \"var/log\", host = \"out\" }]",
+                "absolute path",
+            ),
+            (
+                "collect = [{ guest = \"/\", host = \"out\" }]",
+                "absolute path",
+            ),
+            (
+                "collect = [{ guest = \"/var/log/..\", host = \"out\" }]",
+                "`.` or `..`",
+            ),
+            (
+                "collect = [{ guest = \"/srv/./report.xml\", host = \"out\" }]",
+                "`.` or `..`",
+            ),
+            (
+                "collect = [{ guest = \"/var/log\" }]",
+                "needs `guest` and `host`",
+            ),
+        ] {
+            let path = write_config(
+                &dir,
+                &minimal_config_v02(&format!("[[vms]]\nname = \"app\"\n{body}\n")),
+            );
+            match load_project_config(&path).expect_err("invalid collect should be rejected") {
+                Error::InvalidConfig { message, .. } => {
+                    assert!(message.contains(expected), "{message}");
+                }
+                other => panic!("unexpected error: {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn bootstrap_step_policies_layer_over_defaults() {
+        let dir = tempdir().unwrap();
+        let path = write_config(
+            &dir,
+            &minimal_config_v02(
+                r#"
+[bootstrap]
+max_parallel = 4
+
+[bootstrap.steps.connect]
+backoff_ms = 250
+
+[bootstrap.steps.transfer]
+retries = 2
+timeout_secs = 300
+
+[[vms]]
+name = "web"
+"#,
+            ),
+        );
+
+        let config = load_project_config(&path).expect("load config");
+        let bootstrap = &config.bootstrap;
+        assert_eq!(bootstrap.max_parallel, Some(4));
+        assert_eq!(bootstrap.steps.connect.retries, DEFAULT_CONNECT_RETRIES);
+        assert_eq!(
+            bootstrap.steps.connect.backoff(3),
+            Duration::from_millis(1000)
+        );
+        assert_eq!(bootstrap.steps.transfer.retries, 2);
+        assert_eq!(
+            bootstrap.steps.transfer.timeout,
+            Some(Duration::from_secs(300))
+        );
+        assert_eq!(bootstrap.steps.apply, BootstrapStepPolicy::default());
+
+        for (body, expected) in [
+            (
+                "max_parallel = 0",
+                "`[bootstrap].max_parallel` must be at least 1",
+            ),
+            (
+                "steps = { verify = { timeout_secs = 0 } }",
+                "`[bootstrap.steps.verify].timeout_secs` must be at least 1 second",
+            ),
+        ] {
+            let path = write_config(
+                &dir,
+                &minimal_config_v02(&format!("[bootstrap]\n{body}\n\n[[vms]]\nname = \"web\"\n")),
+            );
+            match load_project_config(&path).expect_err("invalid policy should be rejected") {
+                Error::InvalidConfig { message, .. } => {
+                    assert!(message.contains(expected), "{message}");
+                }
+                other => panic!("unexpected error: {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn git_payloads_resolve_against_project_and_state_roots() {
+        let dir = tempdir().unwrap();
+        let path = write_config(
+            &dir,
+            &minimal_config_v02(
+                r#"
+[[vms]]
+name = "api"
+[vms.bootstrap]
+payload = { git = "../service", rev = "main", subdir = "deploy" }
+
+[[vms]]
+name = "worker"
+[vms.bootstrap]
+stages = [{ name = "app", script = "app.sh", payload = { git = "service" } }]
+"#,
+            ),
+        );
+        let config = load_project_config(&path).expect("load config");
+        let cache_dir = config.state_root.join(GIT_PAYLOAD_SUBDIR);
+        assert_eq!(
+            config.vms[0].bootstrap.payload,
+            Some(PayloadSource::Git(GitPayload {
+                repo: dir.path().join("../service"),
+                rev: "main".to_string(),
+                subdir: Some(PathBuf::from("deploy")),
+                cache_dir: cache_dir.clone(),
+            }))
+        );
+        assert_eq!(
+            config.vms[1].bootstrap.stages[0].payload,
+            Some(PayloadSource::Git(GitPayload {
+                repo: dir.path().join("service"),
+                rev: "HEAD".to_string(),
+                subdir: None,
+                cache_dir,
+            }))
+        );
+
+        for (payload, expected) in [
+            (r#"{ git = "", rev = "main" }"#, "empty `git` path"),
+            (
+                r#"{ git = "svc", rev = "--all" }"#,
+                "name a branch, tag or commit",
+            ),
+            (
+                r#"{ git = "svc", subdir = "../etc" }"#,
+                "relative path inside",
+            ),
+        ] {
+            let path = write_config(
+                &dir,
+                &minimal_config_v02(&format!(
+                    "[[vms]]\nname = \"api\"\n[vms.bootstrap]\npayload = {payload}\n"
+                )),
+            );
+            match load_project_config(&path).expect_err("invalid git payload should be rejected") {
+                Error::InvalidConfig { message, .. } => {
+                    assert!(message.contains(expected), "{message}");
+                }
+                other => panic!("unexpected error: {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn provision_section_rejects_invalid_entries() {
+        let dir = tempdir().unwrap();
+        let cases = [
+            (
+                "[vms.provision]\npackages = [\"git; rm -rf /\"]\n",
+                "invalid package name",
+            ),
+            (
+                "[[vms.provision.files]]\npath = \"etc/motd\"\ncontent = \"x\"\n",
+                "must be an absolute path",
+            ),
+            (
+                "[[vms.provision.files]]\npath = \"/etc/motd\"\n",
+                "exactly one of `content` or `source`",
+            ),
+            (
+                "[[vms.provision.files]]\npath = \"/etc/motd\"\ncontent = \"x\"\nmode = \"rwx\"\n",
+                "use octal",
+            ),
+            (
+                "[vms.bootstrap]\nstages = [{ name = \"provision\", script = \"p.sh\" }]\n[vms.provision]\nservices = [\"sshd\"]\n",
+                "bootstrap stage named `provision`",
+            ),
+        ];
+        for (body, expected) in cases {
+            let path = write_config(
+                &dir,
+                &minimal_config_v02(&format!("[[vms]]\nname = \"app\"\n{body}")),
+            );
+            match load_project_config(&path).expect_err("invalid provision should be rejected") {
+                Error::InvalidConfig { message, .. } => {
+                    assert!(message.contains(expected), "{message}");
+                }
+                other => panic!("unexpected error: {other:?}"),
+            }
+        }
+    }
+
     #[test]
     fn load_config_rejects_replicas_on_legacy_schema() {
         let dir = tempdir().unwrap();
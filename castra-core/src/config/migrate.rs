@@ -0,0 +1,342 @@
+//! Format-preserving upgrade of older `castra.toml` files.
+//!
+//! Edits are applied with `toml_edit` so comments, key order and whitespace
+//! survive; only the deprecated constructs themselves are rewritten.
+
+use std::path::Path;
+
+use toml_edit::{DocumentMut, Item, TableLike};
+
+use crate::error::Error;
+
+use super::{SchemaKind, classify_schema_version, invalid_config};
+
+/// Version written by `castra config migrate`.
+pub const CURRENT_CONFIG_VERSION: &str = "0.2.0";
+
+/// Result of migrating one config file.
+#[derive(Debug, Clone)]
+pub struct ConfigMigration {
+    /// Migrated source; identical to the input when nothing changed.
+    pub source: String,
+    /// One entry per rewrite that was applied.
+    pub changes: Vec<String>,
+    /// Follow-ups the migration could not perform on its own.
+    pub notes: Vec<String>,
+}
+
+impl ConfigMigration {
+    pub fn is_noop(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+/// Rewrite deprecated constructs in `source` (read from `path`) to their
+/// modern equivalents.
+pub fn migrate_config_source(path: &Path, source: &str) -> Result<ConfigMigration, Error> {
+    let mut doc: DocumentMut = source.parse().map_err(|err| {
+        invalid_config(
+            path,
+            format!("Unable to parse configuration for migration: {err}"),
+        )
+    })?;
+    let mut changes = Vec::new();
+    let mut notes = Vec::new();
+
+    for key in ["broker", "bus"] {
+        if doc.remove(key).is_some() {
+            changes.push(format!(
+                "Removed `[{key}]`; the broker and bus were retired in favour of direct SSH (see {}).",
+                super::BROKERLESS_MIGRATION_DOC
+            ));
+        }
+    }
+
+    if let Some(project) = doc.get_mut("project").and_then(Item::as_table_like_mut)
+        && let Some(features) = project
+            .get_mut("features")
+            .and_then(Item::as_table_like_mut)
+        && features.remove("enable_vm_vizier").is_some()
+    {
+        changes.push(
+            "Removed `[project.features].enable_vm_vizier`; the Vizier service no longer exists."
+                .to_string(),
+        );
+        if features.is_empty() {
+            project.remove("features");
+        }
+    }
+
+    if let Some(workflows) = doc.get_mut("workflows").and_then(Item::as_table_like_mut)
+        && workflows.remove("init").is_some()
+    {
+        changes.push(
+            "Removed `[workflows].init`; overlays are managed by Castra. Move custom steps to `pre_up` or `post_launch` hooks."
+                .to_string(),
+        );
+        notes.push(
+            "Review the removed `[workflows].init` commands and port any that are still needed to lifecycle hooks."
+                .to_string(),
+        );
+        if workflows.is_empty() {
+            doc.remove("workflows");
+        }
+    }
+
+    if let Some(vms) = doc.get_mut("vms") {
+        for_each_table(vms, &mut |idx, vm| {
+            let name = table_name(vm, "name").unwrap_or_else(|| format!("#{idx}"));
+            drop_managed_image(vm, &format!("VM `{name}`"), &mut changes, &mut notes);
+            if let Some(instances) = vm.get_mut("instances") {
+                for_each_table(instances, &mut |inst_idx, instance| {
+                    let id = table_name(instance, "id")
+                        .unwrap_or_else(|| format!("{name} instance #{inst_idx}"));
+                    drop_managed_image(
+                        instance,
+                        &format!("Replica `{id}`"),
+                        &mut changes,
+                        &mut notes,
+                    );
+                });
+            }
+        });
+    }
+
+    if let Some(profiles) = doc.get_mut("profiles").and_then(Item::as_table_like_mut) {
+        for (profile, overlay) in profiles.iter_mut() {
+            let Some(vms) = overlay
+                .as_table_like_mut()
+                .and_then(|overlay| overlay.get_mut("vms"))
+                .and_then(Item::as_table_like_mut)
+            else {
+                continue;
+            };
+            for (vm, overrides) in vms.iter_mut() {
+                if let Some(overrides) = overrides.as_table_like_mut() {
+                    drop_managed_image(
+                        overrides,
+                        &format!("Profile `{profile}` override for VM `{vm}`"),
+                        &mut changes,
+                        &mut notes,
+                    );
+                }
+            }
+        }
+    }
+
+    bump_version(&mut doc, &mut changes, &mut notes);
+
+    if doc.contains_key("include") {
+        notes.push(
+            "Included files are not rewritten; run `castra config migrate --config <file>` on each of them."
+                .to_string(),
+        );
+    }
+
+    let source = if changes.is_empty() {
+        source.to_string()
+    } else {
+        doc.to_string()
+    };
+    Ok(ConfigMigration {
+        source,
+        changes,
+        notes,
+    })
+}
+
+fn bump_version(doc: &mut DocumentMut, changes: &mut Vec<String>, notes: &mut Vec<String>) {
+    let current = doc
+        .get("version")
+        .and_then(Item::as_str)
+        .map(str::to_string);
+    let legacy = match current.as_deref() {
+        None => true,
+        Some(raw) => matches!(classify_schema_version(raw), Ok((SchemaKind::Legacy, _))),
+    };
+    if !legacy {
+        return;
+    }
+
+    match doc.get_mut("version").and_then(Item::as_value_mut) {
+        Some(value) => {
+            let decor = value.decor().clone();
+            *value = CURRENT_CONFIG_VERSION.into();
+            *value.decor_mut() = decor;
+        }
+        None => {
+            doc.insert("version", toml_edit::value(CURRENT_CONFIG_VERSION));
+        }
+    }
+    changes.push(match current {
+        Some(previous) => {
+            format!("Bumped `version` from \"{previous}\" to \"{CURRENT_CONFIG_VERSION}\".")
+        }
+        None => format!("Added `version = \"{CURRENT_CONFIG_VERSION}\"`."),
+    });
+    notes.push(format!(
+        "Version {CURRENT_CONFIG_VERSION} names VMs `<name>-<index>` (e.g. `devbox` becomes `devbox-0`); run `castra down` with the old config before the next `castra up`."
+    ));
+}
+
+fn drop_managed_image(
+    table: &mut dyn TableLike,
+    context: &str,
+    changes: &mut Vec<String>,
+    notes: &mut Vec<String>,
+) {
+    let Some(removed) = table.remove("managed_image") else {
+        return;
+    };
+    let image_name = removed
+        .as_table_like()
+        .and_then(|image| image.get("name"))
+        .and_then(Item::as_str)
+        .map(str::to_string);
+
+    if table.contains_key("base_image") {
+        changes.push(format!(
+            "Removed `managed_image` from {context}; its `base_image` is kept."
+        ));
+        return;
+    }
+    changes.push(format!(
+        "Removed `managed_image` from {context}; it now uses the default Alpine base image."
+    ));
+    if let Some(name) = image_name
+        && !name.to_ascii_lowercase().contains("alpine")
+    {
+        notes.push(format!(
+            "{context} used managed image `{name}`; set `base_image` to an equivalent qcow2 if Alpine is not a substitute."
+        ));
+    }
+}
+
+fn table_name(table: &dyn TableLike, key: &str) -> Option<String> {
+    table.get(key).and_then(Item::as_str).map(str::to_string)
+}
+
+/// Visit every table in an array of tables, or every inline table in an
+/// inline array.
+fn for_each_table(item: &mut Item, visit: &mut dyn FnMut(usize, &mut dyn TableLike)) {
+    if let Some(tables) = item.as_array_of_tables_mut() {
+        for (idx, table) in tables.iter_mut().enumerate() {
+            visit(idx, table);
+        }
+    } else if let Some(array) = item.as_array_mut() {
+        for (idx, value) in array.iter_mut().enumerate() {
+            if let Some(table) = value.as_inline_table_mut() {
+                visit(idx, table);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEGACY: &str = r#"# Team devbox
+version = "0.1.0" # schema
+
+[project]
+name = "demo"
+
+[project.features]
+enable_vm_vizier = true
+
+[broker]
+port = 7070
+
+[workflows]
+init = ["qemu-img create -f qcow2 -b base.qcow2 overlay.qcow2"]
+
+# The main VM
+[[vms]]
+name = "devbox"
+cpus = 2 # keep small
+managed_image = { name = "alpine-minimal", version = "v1" }
+
+[[vms]]
+name = "db"
+base_image = "images/db.qcow2"
+
+[vms.managed_image]
+name = "postgres"
+"#;
+
+    #[test]
+    fn migrate_rewrites_legacy_keys_and_keeps_comments() {
+        let migration = migrate_config_source(Path::new("castra.toml"), LEGACY).unwrap();
+        let migrated = &migration.source;
+
+        assert!(migrated.starts_with("# Team devbox\nversion = \"0.2.0\" # schema\n"));
+        assert!(migrated.contains("# The main VM\n[[vms]]"));
+        assert!(migrated.contains("cpus = 2 # keep small"));
+        assert!(migrated.contains("base_image = \"images/db.qcow2\""));
+        for removed in [
+            "managed_image",
+            "enable_vm_vizier",
+            "[project.features]",
+            "[broker]",
+            "[workflows]",
+        ] {
+            assert!(
+                !migrated.contains(removed),
+                "{removed} left in:\n{migrated}"
+            );
+        }
+        assert_eq!(migration.changes.len(), 6, "{:?}", migration.changes);
+        assert!(
+            migration
+                .notes
+                .iter()
+                .any(|note| note.contains("`devbox` becomes `devbox-0`"))
+        );
+
+        let reparsed: toml::Value = toml::from_str(migrated).unwrap();
+        assert!(super::super::detect_unknown_fields(&reparsed).is_empty());
+    }
+
+    #[test]
+    fn migrate_is_a_noop_for_current_configs() {
+        let source = "version = \"0.2.0\"\n\n[[vms]]\nname = \"devbox\"\n";
+        let migration = migrate_config_source(Path::new("castra.toml"), source).unwrap();
+        assert!(migration.is_noop());
+        assert_eq!(migration.source, source);
+    }
+
+    #[test]
+    fn migrate_handles_replicas_and_profile_overrides() {
+        let source = r#"version = "0.2.0"
+
+[[vms]]
+name = "web"
+count = 2
+
+  [[vms.instances]]
+  id = "web-1"
+  managed_image = { name = "debian" }
+
+[profiles.ci.vms.web]
+cpus = 1
+managed_image = { name = "alpine" }
+"#;
+        let migration = migrate_config_source(Path::new("castra.toml"), source).unwrap();
+        assert!(!migration.source.contains("managed_image"));
+        assert!(
+            migration
+                .changes
+                .iter()
+                .any(|c| c.contains("Replica `web-1`"))
+        );
+        assert!(
+            migration
+                .changes
+                .iter()
+                .any(|c| c.contains("Profile `ci` override for VM `web`"))
+        );
+        assert_eq!(migration.notes.len(), 1, "{:?}", migration.notes);
+        assert!(migration.notes[0].contains("`debian`"));
+    }
+}
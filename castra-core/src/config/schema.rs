@@ -0,0 +1,1146 @@
+//! Single description of the `castra.toml` layout.
+//!
+//! The same tree renders the published JSON Schema (`castra config schema`)
+//! and drives the unknown-field warnings emitted while loading a config, so
+//! editor validation and runtime warnings cannot drift apart.
+
+use serde_json::{Map, Value, json};
+
+/// Shape of a value in `castra.toml`.
+#[derive(Debug)]
+pub(super) enum Node {
+    String,
+    Integer {
+        minimum: i64,
+        maximum: Option<i64>,
+    },
+    Boolean,
+    Enum(&'static [&'static str]),
+    Array(&'static Node),
+    Table(&'static [Field]),
+    /// Table with free-form keys whose values share one shape.
+    Map(&'static Node),
+    /// Value matching any of several shapes.
+    AnyOf(&'static [Node]),
+}
+
+/// Named entry of a [`Node::Table`].
+#[derive(Debug)]
+pub(super) struct Field {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub node: Node,
+    /// Accepted for compatibility but ignored or rejected during validation.
+    pub deprecated: bool,
+}
+
+const fn field(name: &'static str, description: &'static str, node: Node) -> Field {
+    Field {
+        name,
+        description,
+        node,
+        deprecated: false,
+    }
+}
+
+const fn deprecated(name: &'static str, description: &'static str, node: Node) -> Field {
+    Field {
+        name,
+        description,
+        node,
+        deprecated: true,
+    }
+}
+
+const SECONDS: Node = Node::Integer {
+    minimum: 0,
+    maximum: None,
+};
+const PORT: Node = Node::Integer {
+    minimum: 1,
+    maximum: Some(65535),
+};
+const BOOTSTRAP_MODES: &[&str] = &[
+    "auto",
+    "always",
+    "skip",
+    "automatic",
+    "enabled",
+    "force",
+    "disabled",
+    "off",
+];
+const HOOK_COMMANDS: Node = Node::Array(&Node::String);
+
+const PROJECT_FEATURES: &[Field] = &[deprecated(
+    "enable_vm_vizier",
+    "Ignored; the Vizier service has been removed.",
+    Node::Boolean,
+)];
+
+const PROJECT: &[Field] = &[
+    field("name", "Human-readable project name.", Node::String),
+    field(
+        "state_dir",
+        "Workspace directory for overlays, logs and metadata (absolute or relative to this file).",
+        Node::String,
+    ),
+    field(
+        "features",
+        "Legacy feature toggles.",
+        Node::Table(PROJECT_FEATURES),
+    ),
+];
+
+const MANAGED_IMAGE: &[Field] = &[
+    field("name", "Managed image name.", Node::String),
+    field("version", "Managed image version.", Node::String),
+    field("disk", "Disk artifact to use.", Node::String),
+    field("checksum", "Expected checksum of the disk.", Node::String),
+    field(
+        "size_bytes",
+        "Expected size of the disk in bytes.",
+        Node::Integer {
+            minimum: 0,
+            maximum: None,
+        },
+    ),
+];
+
+const PORT_FORWARD: &[Field] = &[
+    field("host", "Host port to listen on.", PORT),
+    field("guest", "Guest port to forward to.", PORT),
+    field(
+        "protocol",
+        "Transport protocol.",
+        Node::Enum(&["tcp", "udp"]),
+    ),
+];
+
+const VM_BOOTSTRAP: &[Field] = &[
+    field(
+        "mode",
+        "Bootstrap mode for this VM.",
+        Node::Enum(BOOTSTRAP_MODES),
+    ),
+    field(
+        "script",
+        "Host script copied to and run on the guest.",
+        Node::String,
+    ),
+    field(
+        "payload",
+        "Host directory copied next to the script, or a git repository exported at a pinned commit.",
+        PAYLOAD,
+    ),
+    field(
+        "handshake_timeout_secs",
+        "Seconds to wait for the guest to accept SSH.",
+        SECONDS,
+    ),
+    field(
+        "remote_dir",
+        "Guest directory receiving the script and payload.",
+        Node::String,
+    ),
+    field(
+        "env",
+        "Environment variables passed to the script.",
+        Node::Map(&Node::String),
+    ),
+    field(
+        "env_secrets",
+        "Secret variables read from the host at run time and redacted from logs.",
+        Node::Map(&Node::Table(SECRET_SOURCE)),
+    ),
+    field(
+        "verify_command",
+        "Guest command confirming bootstrap success.",
+        Node::String,
+    ),
+    field(
+        "verify_path",
+        "Guest path that must exist after bootstrap.",
+        Node::String,
+    ),
+    field(
+        "bake",
+        "Flatten the bootstrapped overlay into a reusable golden image.",
+        Node::Boolean,
+    ),
+    field(
+        "managed_keys",
+        "Use the workspace SSH keypair for this VM.",
+        Node::Boolean,
+    ),
+    field(
+        "stages",
+        "Ordered pipeline stages: names of `[bootstrap.stages]` entries or inline stage tables.",
+        Node::Array(&Node::AnyOf(&[Node::String, Node::Table(STAGE)])),
+    ),
+];
+
+const PAYLOAD: Node = Node::AnyOf(&[Node::String, Node::Table(GIT_PAYLOAD)]);
+
+const GIT_PAYLOAD: &[Field] = &[
+    field("git", "Path to a local git repository.", Node::String),
+    field(
+        "rev",
+        "Branch, tag or commit to export; defaults to `HEAD`.",
+        Node::String,
+    ),
+    field(
+        "subdir",
+        "Directory inside the repository to export instead of its root.",
+        Node::String,
+    ),
+];
+
+const SECRET_SOURCE: &[Field] = &[
+    field(
+        "file",
+        "Host file holding the value; a trailing newline is dropped.",
+        Node::String,
+    ),
+    field(
+        "env",
+        "Host environment variable holding the value.",
+        Node::String,
+    ),
+];
+
+const STAGE: &[Field] = &[
+    field(
+        "name",
+        "Stage name; inline stages named after a shared stage override its fields.",
+        Node::String,
+    ),
+    field("script", "Host script run on the guest.", Node::String),
+    field(
+        "payload",
+        "Host directory copied next to the script, or a git repository exported at a pinned commit.",
+        PAYLOAD,
+    ),
+    field(
+        "env",
+        "Environment variables layered over the VM's bootstrap env.",
+        Node::Map(&Node::String),
+    ),
+    field(
+        "env_secrets",
+        "Secret variables layered over the VM's bootstrap secrets.",
+        Node::Map(&Node::Table(SECRET_SOURCE)),
+    ),
+    field(
+        "timeout_secs",
+        "Seconds the stage's script may run before it is aborted.",
+        SECONDS,
+    ),
+    field(
+        "verify_command",
+        "Guest command confirming the stage succeeded.",
+        Node::String,
+    ),
+    field(
+        "verify_path",
+        "Guest path that must exist after the stage.",
+        Node::String,
+    ),
+    field(
+        "run_if",
+        "When the stage runs (default `changed`).",
+        Node::Enum(&["changed", "always", "once"]),
+    ),
+];
+
+const PROVISION_PACKAGE: &[Field] = &[
+    field(
+        "name",
+        "Package name used by every package manager without its own entry.",
+        Node::String,
+    ),
+    field("apk", "Package name on Alpine (`apk`).", Node::String),
+    field(
+        "apt",
+        "Package name on Debian/Ubuntu (`apt-get`).",
+        Node::String,
+    ),
+    field("dnf", "Package name on Fedora/RHEL (`dnf`).", Node::String),
+];
+
+const PROVISION_FILE: &[Field] = &[
+    field("path", "Absolute guest path of the file.", Node::String),
+    field(
+        "content",
+        "File contents; `{{ name }}` placeholders take the `.tmpl` bootstrap variables.",
+        Node::String,
+    ),
+    field(
+        "source",
+        "Host file whose contents are templated like `content`.",
+        Node::String,
+    ),
+    field("mode", "Octal file mode, e.g. `0644`.", Node::String),
+    field("owner", "Owner as `user` or `user:group`.", Node::String),
+];
+
+const PROVISION_USER: &[Field] = &[
+    field("name", "Login name.", Node::String),
+    field(
+        "groups",
+        "Supplementary groups, created when missing.",
+        Node::Array(&Node::String),
+    ),
+    field(
+        "shell",
+        "Login shell for a newly created user.",
+        Node::String,
+    ),
+    field(
+        "authorized_keys",
+        "Public keys written to the user's `~/.ssh/authorized_keys`.",
+        Node::Array(&Node::String),
+    ),
+];
+
+const PROVISION: &[Field] = &[
+    field(
+        "packages",
+        "Packages installed with the guest's package manager.",
+        Node::Array(&Node::AnyOf(&[
+            Node::String,
+            Node::Table(PROVISION_PACKAGE),
+        ])),
+    ),
+    field(
+        "files",
+        "Files written on the guest.",
+        Node::Array(&Node::Table(PROVISION_FILE)),
+    ),
+    field(
+        "users",
+        "Users created on the guest.",
+        Node::Array(&Node::Table(PROVISION_USER)),
+    ),
+    field(
+        "services",
+        "Services enabled at boot and started (OpenRC or systemd).",
+        Node::Array(&Node::String),
+    ),
+    field(
+        "sysctls",
+        "Kernel parameters applied now and persisted in `/etc/sysctl.d`.",
+        Node::Map(&Node::String),
+    ),
+];
+
+const DEPENDENCY: &[Field] = &[
+    field("vm", "VM name or expanded replica name.", Node::String),
+    field(
+        "condition",
+        "Readiness required before launching the dependent VM (default `bootstrapped`).",
+        Node::Enum(&["launched", "bootstrapped", "healthy"]),
+    ),
+];
+
+const COLLECT: &[Field] = &[
+    field(
+        "guest",
+        "Absolute guest file or directory to copy.",
+        Node::String,
+    ),
+    field(
+        "host",
+        "Host directory receiving the copy; `{vm}` expands to the VM name.",
+        Node::String,
+    ),
+];
+
+const VM_INSTANCE: &[Field] = &[
+    field("id", "Replica identifier (`<role>-<index>`).", Node::String),
+    field("description", "Replica description.", Node::String),
+    field("base_image", "Base image override.", Node::String),
+    deprecated(
+        "managed_image",
+        "No longer supported; use base_image.",
+        Node::Table(MANAGED_IMAGE),
+    ),
+    field("overlay", "Overlay path override.", Node::String),
+    field(
+        "cpus",
+        "vCPU override.",
+        Node::Integer {
+            minimum: 1,
+            maximum: None,
+        },
+    ),
+    field(
+        "memory",
+        "Memory override (e.g. \"2048 MiB\").",
+        Node::String,
+    ),
+    field(
+        "port_forwards",
+        "Port forwards replacing the role's forwards.",
+        Node::Array(&Node::Table(PORT_FORWARD)),
+    ),
+];
+
+const VM: &[Field] = &[
+    field("name", "VM (or role) name.", Node::String),
+    field("description", "Free-form description.", Node::String),
+    field(
+        "base_image",
+        "Base qcow2 image; defaults to the managed Alpine image.",
+        Node::String,
+    ),
+    deprecated(
+        "managed_image",
+        "No longer supported; use base_image.",
+        Node::Table(MANAGED_IMAGE),
+    ),
+    field(
+        "overlay",
+        "Overlay path; `.castra/` prefixes are rebased into the workspace.",
+        Node::String,
+    ),
+    field(
+        "cpus",
+        "Number of vCPUs.",
+        Node::Integer {
+            minimum: 1,
+            maximum: None,
+        },
+    ),
+    field("memory", "Guest memory (e.g. \"2048 MiB\").", Node::String),
+    field(
+        "port_forwards",
+        "Host to guest port forwards.",
+        Node::Array(&Node::Table(PORT_FORWARD)),
+    ),
+    field(
+        "count",
+        "Number of replicas (version 0.2+).",
+        Node::Integer {
+            minimum: 1,
+            maximum: None,
+        },
+    ),
+    field(
+        "instances",
+        "Per-replica overrides (version 0.2+).",
+        Node::Array(&Node::Table(VM_INSTANCE)),
+    ),
+    field(
+        "bootstrap",
+        "Per-VM bootstrap settings.",
+        Node::Table(VM_BOOTSTRAP),
+    ),
+    field(
+        "depends_on",
+        "VMs that must be ready before this one launches.",
+        Node::Array(&Node::AnyOf(&[Node::String, Node::Table(DEPENDENCY)])),
+    ),
+    field(
+        "provision",
+        "Declarative guest state applied as a `provision` bootstrap stage.",
+        Node::Table(PROVISION),
+    ),
+    field(
+        "collect",
+        "Guest paths copied to the host after bootstrap, on `castra collect` and before shutdown.",
+        Node::Array(&Node::Table(COLLECT)),
+    ),
+];
+
+const WORKFLOWS: &[Field] = &[
+    deprecated(
+        "init",
+        "Ignored; overlays are managed by Castra.",
+        HOOK_COMMANDS,
+    ),
+    field(
+        "pre_up",
+        "Host commands run per VM before launch.",
+        HOOK_COMMANDS,
+    ),
+    field(
+        "post_launch",
+        "Host commands run per VM after its process starts.",
+        HOOK_COMMANDS,
+    ),
+    field(
+        "post_bootstrap",
+        "Host commands run per VM after bootstrap.",
+        HOOK_COMMANDS,
+    ),
+    field(
+        "pre_down",
+        "Host commands run per running VM before shutdown.",
+        HOOK_COMMANDS,
+    ),
+    field(
+        "post_down",
+        "Host commands run per VM after it stopped.",
+        HOOK_COMMANDS,
+    ),
+];
+
+const LIFECYCLE: &[Field] = &[
+    field(
+        "graceful_shutdown_wait_secs",
+        "Seconds to wait for a cooperative shutdown.",
+        SECONDS,
+    ),
+    field(
+        "sigterm_wait_secs",
+        "Seconds to wait after SIGTERM.",
+        SECONDS,
+    ),
+    field(
+        "sigkill_wait_secs",
+        "Seconds to wait after SIGKILL.",
+        SECONDS,
+    ),
+];
+
+const BOOTSTRAP: &[Field] = &[
+    field(
+        "mode",
+        "Default bootstrap mode.",
+        Node::Enum(BOOTSTRAP_MODES),
+    ),
+    field(
+        "handshake_timeout_secs",
+        "Default SSH handshake wait.",
+        SECONDS,
+    ),
+    field(
+        "remote_dir",
+        "Default guest directory for bootstrap inputs.",
+        Node::String,
+    ),
+    field(
+        "env",
+        "Environment variables passed to every bootstrap script.",
+        Node::Map(&Node::String),
+    ),
+    field(
+        "env_secrets",
+        "Secret variables for every bootstrap script, read from the host at run time.",
+        Node::Map(&Node::Table(SECRET_SOURCE)),
+    ),
+    field(
+        "bake",
+        "Bake bootstrapped overlays into golden images.",
+        Node::Boolean,
+    ),
+    field(
+        "managed_keys",
+        "Generate a workspace SSH keypair, inject it at boot, and verify guest host keys (default: on unless the VM boots the default Alpine image).",
+        Node::Boolean,
+    ),
+    field(
+        "transport",
+        "How guests are reached over SSH: `openssh` binaries or the in-process `native` client.",
+        Node::Enum(&["openssh", "native"]),
+    ),
+    field(
+        "max_parallel",
+        "Most VMs bootstrapped at once; unlimited when omitted.",
+        Node::Integer {
+            minimum: 1,
+            maximum: None,
+        },
+    ),
+    field(
+        "steps",
+        "Retry and timeout settings per bootstrap step.",
+        Node::Table(BOOTSTRAP_STEPS),
+    ),
+    field(
+        "stages",
+        "Named bootstrap stages that VMs reference from `bootstrap.stages`.",
+        Node::Map(&Node::Table(STAGE)),
+    ),
+];
+
+const BOOTSTRAP_STEPS: &[Field] = &[
+    field(
+        "connect",
+        "SSH connectivity check.",
+        Node::Table(STEP_POLICY),
+    ),
+    field(
+        "transfer",
+        "Script and payload upload.",
+        Node::Table(STEP_POLICY),
+    ),
+    field("apply", "Bootstrap script run.", Node::Table(STEP_POLICY)),
+    field("verify", "Verification checks.", Node::Table(STEP_POLICY)),
+];
+
+const STEP_POLICY: &[Field] = &[
+    field(
+        "retries",
+        "Attempts after the first failed one.",
+        Node::Integer {
+            minimum: 0,
+            maximum: None,
+        },
+    ),
+    field(
+        "backoff_ms",
+        "Milliseconds before the first retry; doubled for each later one.",
+        Node::Integer {
+            minimum: 0,
+            maximum: None,
+        },
+    ),
+    field(
+        "timeout_secs",
+        "Upper bound on a single attempt.",
+        Node::Integer {
+            minimum: 1,
+            maximum: None,
+        },
+    ),
+];
+
+const TRUSTED_KEY: &[Field] = &[
+    field("name", "Label for the key.", Node::String),
+    field("minisign", "Minisign public key.", Node::String),
+    field("ed25519", "Raw ed25519 public key (base64).", Node::String),
+];
+
+const IMAGES: &[Field] = &[
+    field(
+        "mirrors",
+        "Mirror base URLs tried before upstream.",
+        Node::Array(&Node::String),
+    ),
+    field(
+        "trusted_keys",
+        "Keys accepted for image signatures.",
+        Node::Array(&Node::Table(TRUSTED_KEY)),
+    ),
+];
+
+/// VM keys a profile may override.
+pub(super) const PROFILE_VM: &[Field] = &[
+    field(
+        "cpus",
+        "vCPU override.",
+        Node::Integer {
+            minimum: 1,
+            maximum: None,
+        },
+    ),
+    field("memory", "Memory override.", Node::String),
+    field(
+        "count",
+        "Replica count override.",
+        Node::Integer {
+            minimum: 1,
+            maximum: None,
+        },
+    ),
+    field("base_image", "Base image override.", Node::String),
+    deprecated(
+        "managed_image",
+        "No longer supported; use base_image.",
+        Node::Table(MANAGED_IMAGE),
+    ),
+    field(
+        "bootstrap",
+        "Bootstrap overrides.",
+        Node::Table(VM_BOOTSTRAP),
+    ),
+];
+
+/// Sections a profile may override.
+pub(super) const PROFILE: &[Field] = &[
+    field(
+        "vms",
+        "Per-VM overrides keyed by VM name.",
+        Node::Map(&Node::Table(PROFILE_VM)),
+    ),
+    field("bootstrap", "Bootstrap overrides.", Node::Table(BOOTSTRAP)),
+    field("images", "Image overrides.", Node::Table(IMAGES)),
+    field("lifecycle", "Lifecycle overrides.", Node::Table(LIFECYCLE)),
+];
+
+pub(super) const ROOT: &[Field] = &[
+    field(
+        "version",
+        "Configuration schema version (e.g. \"0.2.0\").",
+        Node::String,
+    ),
+    field(
+        "include",
+        "Files merged beneath this one, relative to it.",
+        Node::Array(&Node::String),
+    ),
+    field("project", "Project settings.", Node::Table(PROJECT)),
+    field("vms", "Virtual machines.", Node::Array(&Node::Table(VM))),
+    field(
+        "workflows",
+        "Lifecycle hook commands.",
+        Node::Table(WORKFLOWS),
+    ),
+    field("lifecycle", "Shutdown timeouts.", Node::Table(LIFECYCLE)),
+    field(
+        "bootstrap",
+        "Project-wide bootstrap defaults.",
+        Node::Table(BOOTSTRAP),
+    ),
+    field("images", "Image download settings.", Node::Table(IMAGES)),
+    field(
+        "profiles",
+        "Named overlays selected with `--profile`.",
+        Node::Map(&Node::Table(PROFILE)),
+    ),
+];
+
+/// Names of the fields in `fields`, in declaration order.
+pub(super) fn field_names(fields: &[Field]) -> Vec<&'static str> {
+    fields.iter().map(|field| field.name).collect()
+}
+
+/// JSON Schema (draft-07) describing `castra.toml`.
+pub fn config_json_schema() -> Value {
+    let mut schema = table_schema(ROOT);
+    if let Value::Object(object) = &mut schema {
+        object.insert(
+            "$schema".to_string(),
+            json!("http://json-schema.org/draft-07/schema#"),
+        );
+        object.insert("title".to_string(), json!("castra.toml"));
+        object.insert(
+            "description".to_string(),
+            json!("Castra project configuration."),
+        );
+    }
+    schema
+}
+
+fn node_schema(node: &Node) -> Value {
+    match node {
+        Node::String => json!({ "type": "string" }),
+        Node::Integer { minimum, maximum } => {
+            let mut object = Map::new();
+            object.insert("type".to_string(), json!("integer"));
+            object.insert("minimum".to_string(), json!(minimum));
+            if let Some(maximum) = maximum {
+                object.insert("maximum".to_string(), json!(maximum));
+            }
+            Value::Object(object)
+        }
+        Node::Boolean => json!({ "type": "boolean" }),
+        Node::Enum(values) => json!({ "type": "string", "enum": values }),
+        Node::Array(item) => json!({ "type": "array", "items": node_schema(item) }),
+        Node::Table(fields) => table_schema(fields),
+        Node::Map(value) => json!({
+            "type": "object",
+            "additionalProperties": node_schema(value),
+        }),
+        Node::AnyOf(options) => json!({
+            "anyOf": options.iter().map(node_schema).collect::<Vec<_>>(),
+        }),
+    }
+}
+
+fn table_schema(fields: &[Field]) -> Value {
+    let mut properties = Map::new();
+    for field in fields {
+        let mut schema = node_schema(&field.node);
+        if let Value::Object(object) = &mut schema {
+            object.insert("description".to_string(), json!(field.description));
+            if field.deprecated {
+                object.insert("deprecated".to_string(), json!(true));
+            }
+        }
+        properties.insert(field.name.to_string(), schema);
+    }
+    json!({
+        "type": "object",
+        "properties": properties,
+        "additionalProperties": false,
+    })
+}
+
+/// Warnings for keys the schema does not know and for values whose table
+/// structure does not match it.
+pub(super) fn unknown_field_warnings(value: &toml::Value) -> Vec<String> {
+    let mut warnings = Vec::new();
+    if let toml::Value::Table(table) = value {
+        walk_table(table, ROOT, &Location::Root, &mut warnings);
+    }
+    warnings
+}
+
+/// Where a table sits, mirroring how the TOML would be written.
+enum Location {
+    Root,
+    /// Table reached through `[a.b]` headers.
+    Header(String),
+    /// Entry of an array of tables, e.g. `[[vms]] #0`; `path` is `vms`.
+    Entry {
+        path: String,
+        label: String,
+    },
+}
+
+impl Location {
+    fn label(&self) -> String {
+        match self {
+            Location::Root => "root".to_string(),
+            Location::Header(path) => format!("[{path}]"),
+            Location::Entry { label, .. } => label.clone(),
+        }
+    }
+
+    fn dotted(&self, key: &str) -> String {
+        match self {
+            Location::Root => key.to_string(),
+            Location::Header(path) | Location::Entry { path, .. } => format!("{path}.{key}"),
+        }
+    }
+
+    fn child_table(&self, key: &str) -> Location {
+        match self {
+            Location::Entry { path, label } => Location::Entry {
+                path: format!("{path}.{key}"),
+                label: format!("{label}.{key}"),
+            },
+            _ => Location::Header(self.dotted(key)),
+        }
+    }
+}
+
+fn walk_table(
+    table: &toml::map::Map<String, toml::Value>,
+    fields: &[Field],
+    location: &Location,
+    warnings: &mut Vec<String>,
+) {
+    for (key, value) in table {
+        let Some(field) = fields.iter().find(|field| field.name == key) else {
+            warnings.push(format!(
+                "Unknown field `{key}` at {}; this value will be ignored.",
+                location.label()
+            ));
+            continue;
+        };
+        walk_value(key, value, &field.node, location, warnings);
+    }
+}
+
+fn walk_value(
+    key: &str,
+    value: &toml::Value,
+    node: &Node,
+    location: &Location,
+    warnings: &mut Vec<String>,
+) {
+    match node {
+        Node::Table(fields) => match value {
+            toml::Value::Table(table) => {
+                walk_table(table, fields, &location.child_table(key), warnings)
+            }
+            _ => warnings.push(format!("`{key}` at {} must be a table.", location.label())),
+        },
+        Node::Map(inner) => match value {
+            toml::Value::Table(table) => {
+                let child = location.child_table(key);
+                for (entry_key, entry) in table {
+                    walk_value(entry_key, entry, inner, &child, warnings);
+                }
+            }
+            _ => warnings.push(format!(
+                "`{key}` at {} must be a table mapping keys to values.",
+                location.label()
+            )),
+        },
+        Node::Array(Node::Table(fields)) => match value {
+            toml::Value::Array(entries) => {
+                let path = location.dotted(key);
+                for (idx, entry) in entries.iter().enumerate() {
+                    let label = format!("[[{path}]] #{idx}");
+                    match entry {
+                        toml::Value::Table(table) => walk_table(
+                            table,
+                            fields,
+                            &Location::Entry {
+                                path: path.clone(),
+                                label,
+                            },
+                            warnings,
+                        ),
+                        _ => warnings.push(format!("{label} must be a table.")),
+                    }
+                }
+            }
+            _ => warnings.push(format!(
+                "`{key}` at {} must be an array of tables.",
+                location.label()
+            )),
+        },
+        Node::Array(Node::AnyOf(options)) => {
+            let (toml::Value::Array(entries), Some(Node::Table(fields))) = (
+                value,
+                options
+                    .iter()
+                    .find(|option| matches!(option, Node::Table(_))),
+            ) else {
+                return;
+            };
+            let path = location.dotted(key);
+            for (idx, entry) in entries.iter().enumerate() {
+                if let toml::Value::Table(table) = entry {
+                    let label = format!("[[{path}]] #{idx}");
+                    walk_table(
+                        table,
+                        fields,
+                        &Location::Entry {
+                            path: path.clone(),
+                            label,
+                        },
+                        warnings,
+                    );
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::de::{self, DeserializeOwned, Visitor};
+    use serde::{Deserialize, forward_to_deserialize_any};
+
+    #[test]
+    fn json_schema_describes_nested_tables_and_deprecations() {
+        let schema = config_json_schema();
+        assert_eq!(schema["title"], "castra.toml");
+        let vm = &schema["properties"]["vms"]["items"];
+        assert_eq!(vm["additionalProperties"], false);
+        assert_eq!(
+            vm["properties"]["port_forwards"]["items"]["properties"]["guest"]["maximum"],
+            65535
+        );
+        assert_eq!(vm["properties"]["managed_image"]["deprecated"], true);
+        assert!(
+            vm["properties"]["bootstrap"]["properties"]["mode"]["enum"]
+                .as_array()
+                .unwrap()
+                .contains(&json!("always"))
+        );
+        assert_eq!(
+            schema["properties"]["profiles"]["additionalProperties"]["properties"]["vms"]["additionalProperties"]
+                ["properties"]["count"]["type"],
+            "integer"
+        );
+    }
+
+    /// Sample value exercising every non-deprecated key below `node`.
+    fn sample(node: &Node) -> toml::Value {
+        match node {
+            Node::String => toml::Value::String("sample".to_string()),
+            Node::Integer { minimum, .. } => toml::Value::Integer((*minimum).max(1)),
+            Node::Boolean => toml::Value::Boolean(true),
+            Node::Enum(values) => toml::Value::String(values[0].to_string()),
+            Node::Array(item) => toml::Value::Array(vec![sample(item)]),
+            Node::Table(fields) => toml::Value::Table(
+                fields
+                    .iter()
+                    .filter(|field| !field.deprecated)
+                    .map(|field| (field.name.to_string(), sample(&field.node)))
+                    .collect(),
+            ),
+            Node::Map(value) => toml::Value::Table(
+                [("SAMPLE".to_string(), sample(value))]
+                    .into_iter()
+                    .collect(),
+            ),
+            Node::AnyOf(options) => sample(options.last().expect("AnyOf lists options")),
+        }
+    }
+
+    #[test]
+    fn every_schema_key_is_accepted_by_the_loader() {
+        let mut value = sample(&Node::Table(ROOT));
+        assert!(unknown_field_warnings(&value).is_empty());
+
+        let table = value.as_table_mut().unwrap();
+        table.remove("include");
+        table.remove("profiles");
+        super::super::RawConfig::deserialize(value).expect("schema sample deserializes");
+    }
+
+    /// Deserializer that fails with the field names of the struct asked of it.
+    struct FieldNames;
+
+    impl<'de> de::Deserializer<'de> for FieldNames {
+        type Error = de::value::Error;
+
+        fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+            Err(de::Error::custom("not a struct"))
+        }
+
+        fn deserialize_struct<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            fields: &'static [&'static str],
+            _visitor: V,
+        ) -> Result<V::Value, Self::Error> {
+            Err(de::Error::custom(fields.join(" ")))
+        }
+
+        forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+            bytes byte_buf option unit unit_struct newtype_struct seq tuple
+            tuple_struct map enum identifier ignored_any
+        }
+    }
+
+    fn raw_fields<T: DeserializeOwned>() -> Vec<String> {
+        let Err(err) = T::deserialize(FieldNames) else {
+            unreachable!("FieldNames never produces a value");
+        };
+        err.to_string().split(' ').map(str::to_string).collect()
+    }
+
+    /// The reverse of `every_schema_key_is_accepted_by_the_loader`: a field
+    /// the loader reads but the schema lacks would be warned about as unknown.
+    #[test]
+    fn schema_tables_match_the_loader_fields() {
+        use super::super::*;
+
+        let tables: [(&str, Vec<String>, &[Field]); 24] = [
+            ("RawConfig", raw_fields::<RawConfig>(), ROOT),
+            ("RawProject", raw_fields::<RawProject>(), PROJECT),
+            (
+                "RawProjectFeatures",
+                raw_fields::<RawProjectFeatures>(),
+                PROJECT_FEATURES,
+            ),
+            ("RawVm", raw_fields::<RawVm>(), VM),
+            ("RawVmInstance", raw_fields::<RawVmInstance>(), VM_INSTANCE),
+            (
+                "RawManagedImage",
+                raw_fields::<RawManagedImage>(),
+                MANAGED_IMAGE,
+            ),
+            (
+                "RawPortForward",
+                raw_fields::<RawPortForward>(),
+                PORT_FORWARD,
+            ),
+            (
+                "RawDependencyTarget",
+                raw_fields::<RawDependencyTarget>(),
+                DEPENDENCY,
+            ),
+            ("RawCollectRule", raw_fields::<RawCollectRule>(), COLLECT),
+            ("RawProvision", raw_fields::<RawProvision>(), PROVISION),
+            (
+                "RawPackageNames",
+                raw_fields::<RawPackageNames>(),
+                PROVISION_PACKAGE,
+            ),
+            (
+                "RawProvisionFile",
+                raw_fields::<RawProvisionFile>(),
+                PROVISION_FILE,
+            ),
+            (
+                "RawProvisionUser",
+                raw_fields::<RawProvisionUser>(),
+                PROVISION_USER,
+            ),
+            ("RawBootstrap", raw_fields::<RawBootstrap>(), BOOTSTRAP),
+            (
+                "RawStepPolicies",
+                raw_fields::<RawStepPolicies>(),
+                BOOTSTRAP_STEPS,
+            ),
+            ("RawStepPolicy", raw_fields::<RawStepPolicy>(), STEP_POLICY),
+            (
+                "RawVmBootstrap",
+                raw_fields::<RawVmBootstrap>(),
+                VM_BOOTSTRAP,
+            ),
+            ("RawStage", raw_fields::<RawStage>(), STAGE),
+            ("RawGitPayload", raw_fields::<RawGitPayload>(), GIT_PAYLOAD),
+            (
+                "RawSecretSource",
+                raw_fields::<RawSecretSource>(),
+                SECRET_SOURCE,
+            ),
+            ("RawWorkflows", raw_fields::<RawWorkflows>(), WORKFLOWS),
+            ("RawLifecycle", raw_fields::<RawLifecycle>(), LIFECYCLE),
+            ("RawImages", raw_fields::<RawImages>(), IMAGES),
+            ("RawTrustedKey", raw_fields::<RawTrustedKey>(), TRUSTED_KEY),
+        ];
+        for (raw, loader, schema) in tables {
+            for name in &loader {
+                assert!(
+                    schema.iter().any(|field| field.name == name),
+                    "`{name}` of {raw} is missing from the schema"
+                );
+            }
+            // `include` and `profiles` are resolved before deserializing.
+            for field in schema {
+                assert!(
+                    loader.iter().any(|name| name == field.name)
+                        || ["include", "profiles"].contains(&field.name),
+                    "schema field `{}` is not read by {raw}",
+                    field.name
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn warnings_follow_the_schema_with_toml_style_locations() {
+        let value: toml::Value = toml::from_str(
+            r#"
+version = "0.2.0"
+[project.features]
+bogus = 1
+
+[[vms]]
+name = "api"
+extra = true
+bootstrap = { mode = "auto", nope = 1, env = "oops" }
+
+  [[vms.instances]]
+  id = "api-0"
+  port_forwards = [{ host = 1, guest = 2, sctp = true }]
+
+[[images.trusted_keys]]
+name = "k"
+fingerprint = "x"
+"#,
+        )
+        .unwrap();
+        let warnings = unknown_field_warnings(&value);
+        for expected in [
+            "Unknown field `bogus` at [project.features]",
+            "Unknown field `extra` at [[vms]] #0",
+            "Unknown field `nope` at [[vms]] #0.bootstrap",
+            "`env` at [[vms]] #0.bootstrap must be a table",
+            "Unknown field `sctp` at [[vms.instances.port_forwards]] #0",
+            "Unknown field `fingerprint` at [[images.trusted_keys]] #0",
+        ] {
+            assert!(
+                warnings.iter().any(|warning| warning.contains(expected)),
+                "missing `{expected}` in {warnings:?}"
+            );
+        }
+        assert_eq!(warnings.len(), 6, "{warnings:?}");
+    }
+}
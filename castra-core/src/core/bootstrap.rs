@@ -1,9 +1,10 @@
 use std::collections::{BTreeMap, HashMap};
 use std::fs::{self, File};
 use std::io::{self, Read};
+use std::os::unix::fs::PermissionsExt;
 use std::panic;
 use std::path::{Path, PathBuf};
-use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::mpsc;
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
@@ -12,124 +13,350 @@ use serde::{Deserialize, Serialize};
 use crate::config::PortProtocol;
 #[cfg(test)]
 use crate::config::ProjectFeatures;
-use crate::config::{BootstrapMode, LifecycleConfig, ProjectConfig, VmDefinition};
+use crate::config::{
+    BootstrapMode, BootstrapRunIf, BootstrapStepPolicies, BootstrapStepPolicy,
+    BootstrapVerifyConfig, LifecycleConfig, ManagedSshKeys, PROVISION_STAGE, PayloadSource,
+    ProjectConfig, ProvisionConfig, SecretSource, VmDefinition, template_placeholders,
+};
+use crate::core::collect;
 use crate::core::diagnostics::{Diagnostic, Severity};
 use crate::core::events::{
-    BootstrapPlanAction, BootstrapPlanSsh, BootstrapPlanVerify, BootstrapStatus, BootstrapStepKind,
-    BootstrapStepStatus, BootstrapTrigger, Event,
+    BootstrapPlanAction, BootstrapPlanSsh, BootstrapPlanStage, BootstrapPlanVerify,
+    BootstrapStatus, BootstrapStepKind, BootstrapStepStatus, BootstrapTrigger, Event,
 };
+use crate::core::git_payload;
+use crate::core::history::{BootstrapRunLog, LOG_SUBDIR, StageRecord, StepRecord, write_run_log};
+use crate::core::hooks;
 #[cfg(test)]
 use crate::core::options::VmLaunchMode;
 use crate::core::outcome::{BootstrapPlanOutcome, BootstrapRunOutcome, BootstrapRunStatus};
+use crate::core::project::config_state_root;
+use crate::core::provision;
 use crate::core::reporter::Reporter;
-use crate::core::runtime::{AssetPreparation, RuntimeContext, ShutdownTimeouts, shutdown_vm};
+use crate::core::runtime::{
+    RuntimeContext, ShutdownTimeouts, shutdown_vm, shutdown_vm_retaining_overlay,
+};
+use crate::core::secrets::SecretEnv;
+use crate::core::ssh_keys;
 use crate::core::status::HANDSHAKE_FRESHNESS;
+use crate::core::transport::{self, ExecOutput, SshConfig, Transport};
 use crate::error::{Error, Result};
 use sha2::{Digest, Sha256};
 
-const LOG_SUBDIR: &str = "bootstrap";
+const STAMP_SUBDIR: &str = "stamps";
 const STAGING_SUBDIR: &str = "bootstrap";
 const STAGED_SCRIPT_NAME: &str = "run.sh";
 const STAGED_PAYLOAD_DIR: &str = "payload";
+/// Scripts and payload files ending in this are rendered with the VM's
+/// template variables; payload files are staged without it.
+const TEMPLATE_SUFFIX: &str = ".tmpl";
+/// Manifest of the payload last synced into a guest remote dir.
+const PAYLOAD_MANIFEST_NAME: &str = ".castra-payload.json";
+/// Printed by the transfer preparation when the guest has `rsync`.
+const RSYNC_MARKER: &str = "castra:rsync";
+/// Paths per remote `rm`/`mkdir`/`chmod` invocation.
+const REMOTE_BATCH: usize = 200;
+/// Stage name given to `bootstrap.script` when it runs after `[vms.provision]`.
+const SCRIPT_STAGE: &str = "script";
 const DEFAULT_REMOTE_BASE: &str = "/tmp/castra-bootstrap";
 const DEFAULT_SSH_USER: &str = "root";
 const DEFAULT_SSH_HOST: &str = "127.0.0.1";
 const DEFAULT_SSH_PORT: u16 = 22;
 const DEFAULT_SSH_OPTIONS: [&str; 2] = ["StrictHostKeyChecking=no", "UserKnownHostsFile=/dev/null"];
-const CONNECTIVITY_ATTEMPTS: usize = 5;
 const CONNECTIVITY_RETRY_DELAY_MS: u64 = 1000;
 const SENTINEL_NOOP: &str = "Castra:noop";
 const SENTINEL_ERROR_PREFIX: &str = "Castra:error:";
+/// Evaluates the secret `export`s sent on standard input, so their values
+/// never appear on the host's or the guest's command line. Escaped for the
+/// double quotes transports wrap scripts in.
+const READ_SECRET_EXPORTS: &str = r#"eval \"\$(cat)\";"#;
+
+/// Execute bootstrap pipelines concurrently for the VMs at `indices` of
+/// `project.vms`, returning summaries in the same order.
+pub fn run_selected(
+    project: &ProjectConfig,
+    context: &RuntimeContext,
+    indices: &[usize],
+    reporter: &mut dyn Reporter,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<Vec<BootstrapRunOutcome>> {
+    let transport = transport::for_kind(project.bootstrap.transport);
+    run_selected_with(
+        project,
+        context,
+        indices,
+        transport.as_ref(),
+        reporter,
+        diagnostics,
+    )
+}
 
-/// Execute bootstrap pipelines for all VMs in the project, returning per-VM summaries.
-pub fn run_all(
+/// Like [`run_selected`], reaching guests through `transport` instead of the
+/// one configured in `[bootstrap].transport`.
+pub fn run_selected_with(
     project: &ProjectConfig,
     context: &RuntimeContext,
-    preparations: &[AssetPreparation],
+    indices: &[usize],
+    transport: &dyn Transport,
+    reporter: &mut dyn Reporter,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<Vec<BootstrapRunOutcome>> {
+    let jobs: Vec<(usize, Option<Live<'_>>)> = indices.iter().map(|&index| (index, None)).collect();
+    run_jobs(
+        project,
+        &context.state_root,
+        &context.log_root,
+        &jobs,
+        transport,
+        reporter,
+        diagnostics,
+    )
+}
+
+/// How `castra bootstrap` reruns pipelines on VMs that are already running.
+#[derive(Debug, Clone, Default)]
+pub struct RerunOptions {
+    /// Stages to run; empty runs the whole pipeline. Other stages are left
+    /// as they are on the guest.
+    pub stages: Vec<String>,
+    /// Run the selected stages even when stamps or `run_if` would skip them.
+    pub force: bool,
+}
+
+/// A run against a VM that was already up before the run started.
+#[derive(Debug, Clone, Copy)]
+struct Live<'a> {
+    /// Unix time (seconds) the VM booted. Stamps written since describe the
+    /// running guest.
+    booted_at: u64,
+    options: &'a RerunOptions,
+}
+
+impl Live<'_> {
+    fn selects(&self, stage: Option<&str>) -> bool {
+        self.options.stages.is_empty()
+            || stage.is_some_and(|stage| self.options.stages.iter().any(|name| name == stage))
+    }
+}
+
+/// Rerun the pipelines of running VMs without restarting them. `targets`
+/// pairs an index into `project.vms` with the Unix time the VM booted.
+/// Failures leave the VM running so the pipeline can be fixed and rerun.
+pub fn rerun_selected(
+    project: &ProjectConfig,
+    state_root: &Path,
+    targets: &[(usize, u64)],
+    options: &RerunOptions,
+    reporter: &mut dyn Reporter,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<Vec<BootstrapRunOutcome>> {
+    let transport = transport::for_kind(project.bootstrap.transport);
+    rerun_selected_with(
+        project,
+        state_root,
+        targets,
+        options,
+        transport.as_ref(),
+        reporter,
+        diagnostics,
+    )
+}
+
+fn rerun_selected_with(
+    project: &ProjectConfig,
+    state_root: &Path,
+    targets: &[(usize, u64)],
+    options: &RerunOptions,
+    transport: &dyn Transport,
     reporter: &mut dyn Reporter,
     diagnostics: &mut Vec<Diagnostic>,
 ) -> Result<Vec<BootstrapRunOutcome>> {
-    if project.vms.len() != preparations.len() {
+    let mut jobs = Vec::with_capacity(targets.len());
+    let mut known_stages: Vec<String> = Vec::new();
+    for &(index, booted_at) in targets {
+        let vm = &project.vms[index];
+        let stages: Vec<String> = stage_specs(vm)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|spec| spec.name)
+            .collect();
+        let live = Live { booted_at, options };
+        if !options.stages.is_empty() && !stages.iter().any(|stage| live.selects(Some(stage))) {
+            diagnostics.push(Diagnostic::new(
+                Severity::Info,
+                format!(
+                    "VM `{}` has none of the selected stages; leaving it untouched.",
+                    vm.name
+                ),
+            ));
+        } else {
+            jobs.push((index, Some(live)));
+        }
+        for stage in stages {
+            if !known_stages.contains(&stage) {
+                known_stages.push(stage);
+            }
+        }
+    }
+    if let Some(unknown) = options
+        .stages
+        .iter()
+        .find(|stage| !known_stages.contains(stage))
+    {
         return Err(Error::PreflightFailed {
-            message: format!(
-                "Bootstrap preparation mismatch: expected {} VMs but received {} asset sets.",
-                project.vms.len(),
-                preparations.len()
-            ),
+            message: if known_stages.is_empty() {
+                format!(
+                    "Unknown bootstrap stage `{unknown}`; the selected VMs run a single bootstrap script."
+                )
+            } else {
+                format!(
+                    "Unknown bootstrap stage `{unknown}`. Stages of the selected VMs: {}.",
+                    known_stages.join(", ")
+                )
+            },
         });
     }
 
-    let active_vm_names: Vec<String> = project
-        .vms
+    run_jobs(
+        project,
+        state_root,
+        &state_root.join("logs"),
+        &jobs,
+        transport,
+        reporter,
+        diagnostics,
+    )
+}
+
+/// Run the pipelines of `jobs` (indices into `project.vms`) concurrently, at
+/// most `[bootstrap].max_parallel` at a time, returning summaries in the same
+/// order.
+fn run_jobs(
+    project: &ProjectConfig,
+    state_root: &Path,
+    log_root: &Path,
+    jobs: &[(usize, Option<Live<'_>>)],
+    transport: &dyn Transport,
+    reporter: &mut dyn Reporter,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<Vec<BootstrapRunOutcome>> {
+    let selected: Vec<(&VmDefinition, Option<Live<'_>>)> = jobs
+        .iter()
+        .map(|&(index, live)| (&project.vms[index], live))
+        .collect();
+
+    let active_vm_names: Vec<String> = selected
         .iter()
-        .filter(|vm| !matches!(vm.bootstrap.mode, BootstrapMode::Skip))
-        .map(|vm| vm.name.clone())
+        .filter(|(vm, _)| !matches!(vm.bootstrap.mode, BootstrapMode::Skip))
+        .map(|(vm, _)| vm.name.clone())
         .collect();
+    let workers = worker_count(project.bootstrap.max_parallel, selected.len());
     if !active_vm_names.is_empty() {
         let list = active_vm_names.join(", ");
+        let limit = if workers < active_vm_names.len() {
+            format!(" ({workers} at a time)")
+        } else {
+            String::new()
+        };
         reporter.report(Event::Message {
             severity: Severity::Info,
             text: format!(
-                "Bootstrap starting for {} VM(s): {}.",
+                "Bootstrap starting for {} VM(s){limit}: {}.",
                 active_vm_names.len(),
                 list
             ),
         });
     }
 
-    let (event_tx, event_rx) = mpsc::channel::<Event>();
+    let lifecycle = &project.lifecycle;
+    let steps = project.bootstrap.steps;
+    let finished = run_pool(&selected, workers, reporter, |&(vm, live), emit_event| {
+        let mut local_diagnostics = Vec::new();
+        let outcome = run_for_vm(
+            state_root,
+            log_root,
+            vm,
+            lifecycle.clone(),
+            &steps,
+            live,
+            transport,
+            emit_event,
+            &mut local_diagnostics,
+        );
+        (outcome, local_diagnostics)
+    });
+
     let mut first_error: Option<Error> = None;
-    let mut vm_slots: Vec<Option<BootstrapRunOutcome>> =
-        (0..project.vms.len()).map(|_| None).collect();
+    let mut outcomes = Vec::with_capacity(finished.len());
+    for (outcome_result, local_diagnostics) in finished {
+        diagnostics.extend(local_diagnostics);
+        match outcome_result {
+            Ok(outcome) => outcomes.push(outcome),
+            Err(err) => {
+                if first_error.is_none() {
+                    first_error = Some(err);
+                }
+            }
+        }
+    }
 
-    let state_root = context.state_root.clone();
-    let log_root = context.log_root.clone();
-    let lifecycle = project.lifecycle.clone();
+    match first_error {
+        Some(err) => Err(err),
+        None => Ok(outcomes),
+    }
+}
 
-    std::thread::scope(|scope| {
-        let mut handles = Vec::new();
-
-        for (index, (vm, _prep)) in project.vms.iter().zip(preparations.iter()).enumerate() {
-            let tx_clone = event_tx.clone();
-            let state_root = state_root.clone();
-            let log_root = log_root.clone();
-            let lifecycle = lifecycle.clone();
-            handles.push(scope.spawn(move || {
-                let mut local_diagnostics = Vec::new();
-                let mut emit_event = |event: Event| {
-                    let _ = tx_clone.send(event);
-                };
-                let outcome = run_for_vm(
-                    &state_root,
-                    &log_root,
-                    vm,
-                    lifecycle,
-                    &mut emit_event,
-                    &mut local_diagnostics,
-                );
-                (index, outcome, local_diagnostics)
-            }));
-        }
+/// Threads to run `jobs` jobs with: `max_parallel` when set, otherwise one per
+/// job.
+pub(crate) fn worker_count(max_parallel: Option<usize>, jobs: usize) -> usize {
+    max_parallel.unwrap_or(jobs).clamp(1, jobs.max(1))
+}
+
+/// Run `job` on every item with `workers` threads, each taking the next
+/// unstarted item until none are left. Events the jobs emit are relayed to
+/// `reporter` as they arrive; results come back in `items` order.
+pub(crate) fn run_pool<T: Sync, R: Send>(
+    items: &[T],
+    workers: usize,
+    reporter: &mut dyn Reporter,
+    job: impl Fn(&T, &mut dyn FnMut(Event)) -> R + Sync,
+) -> Vec<R> {
+    let (event_tx, event_rx) = mpsc::channel::<Event>();
+    let next_job = AtomicUsize::new(0);
+    let mut slots: Vec<Option<R>> = items.iter().map(|_| None).collect();
 
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                let event_tx = event_tx.clone();
+                let next_job = &next_job;
+                let job = &job;
+                scope.spawn(move || {
+                    let mut finished = Vec::new();
+                    let mut emit_event = |event: Event| {
+                        let _ = event_tx.send(event);
+                    };
+                    loop {
+                        let index = next_job.fetch_add(1, Ordering::Relaxed);
+                        let Some(item) = items.get(index) else {
+                            break;
+                        };
+                        finished.push((index, job(item, &mut emit_event)));
+                    }
+                    finished
+                })
+            })
+            .collect();
         drop(event_tx);
 
         while let Ok(event) = event_rx.recv() {
             reporter.report(event);
         }
-
         for handle in handles {
             match handle.join() {
-                Ok((index, outcome_result, local_diagnostics)) => {
-                    diagnostics.extend(local_diagnostics);
-                    match outcome_result {
-                        Ok(outcome) => {
-                            vm_slots[index] = Some(outcome);
-                        }
-                        Err(err) => {
-                            if first_error.is_none() {
-                                first_error = Some(err);
-                            }
-                        }
+                Ok(finished) => {
+                    for (index, result) in finished {
+                        slots[index] = Some(result);
                     }
                 }
                 Err(payload) => panic::resume_unwind(payload),
@@ -137,18 +364,10 @@ pub fn run_all(
         }
     });
 
-    if let Some(err) = first_error {
-        return Err(err);
-    }
-
-    Ok(vm_slots
+    slots
         .into_iter()
-        .map(|slot| {
-            slot.unwrap_or_else(|| {
-                panic!("bootstrap worker did not produce a result for configured VM")
-            })
-        })
-        .collect())
+        .map(|slot| slot.unwrap_or_else(|| panic!("worker pool did not finish every job")))
+        .collect()
 }
 
 /// Produce dry-run summaries for bootstrap pipelines without side effects.
@@ -159,8 +378,9 @@ pub fn plan_all(
 ) -> Result<Vec<BootstrapPlanOutcome>> {
     let mut plans = Vec::new();
 
+    let state_root = config_state_root(project);
     for vm in &project.vms {
-        let plan = plan_for_vm(vm);
+        let plan = plan_for_vm(&state_root, vm, None);
         if plan.action.is_error() {
             diagnostics.push(
                 Diagnostic::new(
@@ -184,6 +404,7 @@ pub fn plan_all(
             trigger: plan.trigger,
             script_path: plan.script_path.clone(),
             payload_path: plan.payload_path.clone(),
+            payload_commit: plan.payload_commit.clone(),
             payload_bytes: plan.payload_bytes,
             handshake_timeout_secs: plan.handshake_timeout_secs,
             remote_dir: plan.remote_dir.clone(),
@@ -193,6 +414,7 @@ pub fn plan_all(
             artifact_hash: plan.artifact_hash.clone(),
             metadata_path: plan.metadata_path.clone(),
             warnings: plan.warnings.clone(),
+            stages: plan.stages.clone(),
         });
 
         plans.push(plan);
@@ -201,173 +423,253 @@ pub fn plan_all(
     Ok(plans)
 }
 
-fn plan_for_vm(vm: &VmDefinition) -> BootstrapPlanOutcome {
+/// `live_since` is the boot time of a VM that is already running, whose
+/// stamps since then describe the guest as it is now.
+fn plan_for_vm(
+    state_root: &Path,
+    vm: &VmDefinition,
+    live_since: Option<u64>,
+) -> BootstrapPlanOutcome {
     let mode = vm.bootstrap.mode;
-    match mode {
-        BootstrapMode::Skip => {
-            return BootstrapPlanOutcome {
-                vm: vm.name.clone(),
-                mode,
-                action: BootstrapPlanAction::WouldSkip,
-                trigger: None,
-                reason: "Bootstrap skipped via configuration.".to_string(),
-                script_path: vm.bootstrap.script.clone(),
-                payload_path: None,
-                payload_bytes: None,
-                handshake_timeout_secs: None,
-                remote_dir: None,
-                ssh: None,
-                env_keys: Vec::new(),
-                verify: None,
-                artifact_hash: None,
-                metadata_path: None,
-                warnings: Vec::new(),
-            };
-        }
-        _ => {}
+    let skeleton = |action: BootstrapPlanAction,
+                    trigger: Option<BootstrapTrigger>,
+                    reason: String,
+                    script_path: Option<PathBuf>,
+                    payload_path: Option<PathBuf>| BootstrapPlanOutcome {
+        vm: vm.name.clone(),
+        mode,
+        action,
+        trigger,
+        reason,
+        script_path,
+        payload_path,
+        payload_commit: None,
+        payload_bytes: None,
+        handshake_timeout_secs: None,
+        remote_dir: None,
+        ssh: None,
+        env_keys: Vec::new(),
+        verify: None,
+        artifact_hash: None,
+        metadata_path: None,
+        warnings: Vec::new(),
+        stages: Vec::new(),
+    };
+
+    if matches!(mode, BootstrapMode::Skip) {
+        return skeleton(
+            BootstrapPlanAction::WouldSkip,
+            None,
+            "Bootstrap skipped via configuration.".to_string(),
+            vm.bootstrap.script.clone(),
+            None,
+        );
     }
 
-    let configured_payload = vm.bootstrap.payload.clone();
     let trigger = trigger_for_mode(mode);
+    let missing_action = if matches!(mode, BootstrapMode::Always) {
+        BootstrapPlanAction::Error
+    } else {
+        BootstrapPlanAction::WouldSkip
+    };
 
-    let script_path = match vm.bootstrap.script.as_ref() {
-        Some(path) => path.clone(),
-        None => {
-            let reason = "Bootstrap script not configured.".to_string();
-            let action = if matches!(mode, BootstrapMode::Always) {
-                BootstrapPlanAction::Error
-            } else {
-                BootstrapPlanAction::WouldSkip
-            };
-            return BootstrapPlanOutcome {
-                vm: vm.name.clone(),
-                mode,
-                action,
-                trigger,
-                reason,
-                script_path: None,
-                payload_path: None,
-                payload_bytes: None,
-                handshake_timeout_secs: None,
-                remote_dir: None,
-                ssh: None,
-                env_keys: Vec::new(),
-                verify: None,
-                artifact_hash: None,
-                metadata_path: None,
-                warnings: Vec::new(),
-            };
-        }
+    let Some(specs) = stage_specs(vm) else {
+        return skeleton(
+            missing_action,
+            trigger,
+            "Bootstrap script not configured.".to_string(),
+            None,
+            None,
+        );
     };
 
-    if !script_path.is_file() {
-        let reason = format!("Bootstrap script not found at {}.", script_path.display());
-        let action = if matches!(mode, BootstrapMode::Always) {
-            BootstrapPlanAction::Error
-        } else {
-            BootstrapPlanAction::WouldSkip
+    let existing_payload = |spec: &StageSpec| match &spec.payload {
+        Some(PayloadSource::Dir(path)) if path.is_dir() => Some(path.clone()),
+        _ => None,
+    };
+
+    if let Some(missing) = specs.iter().find(|spec| !spec.script_available()) {
+        let reason = match missing.name.as_deref() {
+            Some(stage) => format!(
+                "Bootstrap script for stage `{stage}` not found at {}.",
+                missing.script.display()
+            ),
+            None => format!(
+                "Bootstrap script not found at {}.",
+                missing.script.display()
+            ),
         };
-        return BootstrapPlanOutcome {
-            vm: vm.name.clone(),
-            mode,
-            action,
+        return skeleton(
+            missing_action,
             trigger,
             reason,
-            script_path: Some(script_path),
-            payload_path: configured_payload
-                .as_ref()
-                .filter(|p| p.exists() && p.is_dir())
-                .cloned(),
-            payload_bytes: None,
-            handshake_timeout_secs: None,
-            remote_dir: None,
-            ssh: None,
-            env_keys: Vec::new(),
-            verify: None,
-            artifact_hash: None,
-            metadata_path: None,
-            warnings: Vec::new(),
-        };
+            Some(missing.script.clone()),
+            existing_payload(missing),
+        );
     }
 
-    let inputs = match resolve_blueprint_inputs(vm, &script_path, configured_payload.as_ref()) {
-        Ok(inputs) => inputs,
-        Err(err) => {
-            return BootstrapPlanOutcome {
-                vm: vm.name.clone(),
-                mode,
-                action: BootstrapPlanAction::Error,
+    let stages = match resolve_pipeline(vm, &specs) {
+        Ok(stages) => stages,
+        Err((index, err)) => {
+            return skeleton(
+                BootstrapPlanAction::Error,
                 trigger,
-                reason: err,
-                script_path: Some(script_path),
-                payload_path: configured_payload
-                    .as_ref()
-                    .filter(|p| p.exists() && p.is_dir())
-                    .cloned(),
-                payload_bytes: None,
-                handshake_timeout_secs: None,
-                remote_dir: None,
-                ssh: None,
-                env_keys: Vec::new(),
-                verify: None,
-                artifact_hash: None,
-                metadata_path: None,
-                warnings: Vec::new(),
-            };
+                err,
+                Some(specs[index].script.clone()),
+                existing_payload(&specs[index]),
+            );
         }
     };
+    let primary = &stages[0];
+    let staged = primary.stage.is_some();
+    let artifact_hash = pipeline_artifact_hash(
+        stages
+            .iter()
+            .map(|stage| (stage.stage.as_deref(), stage.artifact_hash.as_str())),
+    );
 
-    let mut env_keys: Vec<String> = inputs.env.keys().cloned().collect();
+    let mut env_keys: Vec<String> = stages
+        .iter()
+        .flat_map(|stage| stage.env.keys().chain(stage.secrets.keys()).cloned())
+        .collect();
     env_keys.sort();
+    env_keys.dedup();
 
-    let ssh_plan = Some(BootstrapPlanSsh {
-        user: inputs.ssh.user.clone(),
-        host: inputs.ssh.host.clone(),
-        port: inputs.ssh.port,
-        identity: inputs.ssh.identity.clone(),
-        options: inputs.ssh.options.clone(),
-    });
+    let mut warnings: Vec<String> = Vec::new();
+    for warning in stages.iter().flat_map(|stage| &stage.warnings) {
+        if !warnings.contains(warning) {
+            warnings.push(warning.clone());
+        }
+    }
 
-    let verify_plan = if inputs.verify.command.is_some() || inputs.verify.path.is_some() {
-        Some(BootstrapPlanVerify {
-            command: inputs.verify.command.clone(),
-            path: inputs.verify.path.clone(),
-            path_is_relative: inputs.verify.path_is_relative,
-        })
-    } else {
-        None
-    };
+    let ssh_plan = Some(plan_ssh_from_config(&primary.ssh));
+
+    let verify_plan =
+        if !staged && (primary.verify.command.is_some() || primary.verify.path.is_some()) {
+            Some(BootstrapPlanVerify {
+                command: primary.verify.command.clone(),
+                path: primary.verify.path.clone(),
+                path_is_relative: primary.verify.path_is_relative,
+            })
+        } else {
+            None
+        };
+
+    let carried = carried_stages(state_root, vm, live_since);
+    let decisions = decide_stages(
+        stages.iter().map(|stage| {
+            (
+                stage.stage.as_deref(),
+                stage.run_if,
+                stage.artifact_hash.as_str(),
+            )
+        }),
+        &carried,
+    );
 
+    let mut action = BootstrapPlanAction::WouldRun;
     let reason = match mode {
-        BootstrapMode::Auto => {
-            "Policy `auto`; pipeline runs after handshake unless the runner reports Castra:noop."
-                .to_string()
-        }
+        BootstrapMode::Auto => match matching_stamp(
+            state_root,
+            vm,
+            &artifact_hash,
+            pipeline_env(vm, &primary.env),
+        ) {
+            Some(stamp) if stamp.durable => {
+                action = BootstrapPlanAction::UpToDate;
+                format!(
+                    "Up-to-date; base image and bootstrap inputs unchanged since the last success {} ago.",
+                    format_stamp_age(stamp.age())
+                )
+            }
+            Some(stamp) if live_since.is_some_and(|booted| stamp.completed_at >= booted) => {
+                action = BootstrapPlanAction::UpToDate;
+                format!(
+                    "Up-to-date; bootstrap inputs unchanged since the last success on the running VM {} ago.",
+                    format_stamp_age(stamp.age())
+                )
+            }
+            Some(stamp) => format!(
+                "Policy `auto`; inputs match the last success {} ago, but that run lived in a discarded overlay so the pipeline runs again.",
+                format_stamp_age(stamp.age())
+            ),
+            None => {
+                "Policy `auto`; pipeline runs after handshake unless the runner reports Castra:noop."
+                    .to_string()
+            }
+        },
         BootstrapMode::Always => "Policy `always`; pipeline runs on every invocation.".to_string(),
         BootstrapMode::Skip => unreachable!(),
     };
+    let reason = match (&carried.origin, action) {
+        (CarriedOrigin::Resume { failed_stage }, BootstrapPlanAction::WouldRun) => format!(
+            "{reason} Resuming the run that failed at stage `{failed_stage}`; {} of {} stages carry over.",
+            decisions
+                .iter()
+                .filter(|decision| matches!(decision, StageDecision::Carry(_)))
+                .count(),
+            stages.len()
+        ),
+        _ => reason,
+    };
+
+    let plan_stages = if staged {
+        stages
+            .iter()
+            .zip(&decisions)
+            .map(|(stage, decision)| BootstrapPlanStage {
+                name: stage.stage.clone().unwrap_or_default(),
+                script_path: stage.script_source.clone(),
+                payload_path: stage.payload_source.clone(),
+                payload_commit: stage.payload_commit.clone(),
+                run_if: stage.run_if,
+                artifact_hash: stage.artifact_hash.clone(),
+                carried_over: match decision {
+                    StageDecision::Run => None,
+                    StageDecision::Carry(reason) => Some(reason.clone()),
+                },
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
 
+    let payload_bytes: u64 = stages.iter().map(|stage| stage.payload_bytes).sum();
     BootstrapPlanOutcome {
         vm: vm.name.clone(),
         mode,
-        action: BootstrapPlanAction::WouldRun,
+        action,
         trigger,
         reason,
-        script_path: Some(script_path),
-        payload_path: inputs.payload_source.clone(),
-        payload_bytes: if inputs.payload_source.is_some() {
-            Some(inputs.payload_bytes)
+        script_path: Some(primary.script_source.clone()),
+        payload_path: if staged {
+            None
+        } else {
+            primary.payload_source.clone()
+        },
+        payload_commit: if staged {
+            None
+        } else {
+            primary.payload_commit.clone()
+        },
+        payload_bytes: if stages.iter().any(|stage| stage.payload_source.is_some()) {
+            Some(payload_bytes)
         } else {
             None
         },
-        handshake_timeout_secs: Some(inputs.handshake_timeout.as_secs()),
-        remote_dir: Some(inputs.remote_dir.clone()),
+        handshake_timeout_secs: Some(primary.handshake_timeout.as_secs()),
+        remote_dir: Some(if staged {
+            pipeline_remote_dir(&primary.remote_dir)
+        } else {
+            primary.remote_dir.clone()
+        }),
         ssh: ssh_plan,
         env_keys,
         verify: verify_plan,
-        artifact_hash: Some(inputs.artifact_hash.clone()),
-        metadata_path: inputs.metadata_path.clone(),
-        warnings: inputs.warnings.clone(),
+        artifact_hash: Some(artifact_hash),
+        metadata_path: primary.metadata_path.clone(),
+        warnings,
+        stages: plan_stages,
     }
 }
 
@@ -379,11 +681,15 @@ fn trigger_for_mode(mode: BootstrapMode) -> Option<BootstrapTrigger> {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn run_for_vm(
     state_root: &Path,
     log_root: &Path,
     vm: &VmDefinition,
     lifecycle: LifecycleConfig,
+    steps: &BootstrapStepPolicies,
+    live: Option<Live<'_>>,
+    transport: &dyn Transport,
     emit_event: &mut dyn FnMut(Event),
     diagnostics: &mut Vec<Diagnostic>,
 ) -> Result<BootstrapRunOutcome> {
@@ -399,13 +705,14 @@ fn run_for_vm(
                 stamp: None,
                 log_path: None,
                 ssh: None,
+                collected: Vec::new(),
             });
         }
         BootstrapMode::Auto | BootstrapMode::Always => {}
     }
 
-    let script_source = match vm.bootstrap.script.as_ref() {
-        Some(path) => path.clone(),
+    let specs = match stage_specs(vm) {
+        Some(specs) => specs,
         None => {
             let message = format!("Bootstrap script not configured for VM `{}`.", vm.name);
             diagnostics.push(
@@ -426,22 +733,37 @@ fn run_for_vm(
                 stamp: None,
                 log_path: None,
                 ssh: None,
+                collected: Vec::new(),
             });
         }
     };
 
-    if !script_source.is_file() {
-        let message = format!(
-            "Bootstrap script for VM `{}` not found at {}.",
-            vm.name,
-            script_source.display()
-        );
-        diagnostics.push(
-            Diagnostic::new(Severity::Info, message.clone()).with_help(format!(
-                "Create the script or adjust `bootstrap.script` for VM `{}`.",
-                vm.name
-            )),
-        );
+    if let Some(missing) = specs.iter().find(|spec| !spec.script_available()) {
+        let (message, help) = match missing.name.as_deref() {
+            Some(stage) => (
+                format!(
+                    "Bootstrap script for VM `{}` stage `{stage}` not found at {}.",
+                    vm.name,
+                    missing.script.display()
+                ),
+                format!(
+                    "Create the script or adjust the `script` of stage `{stage}` for VM `{}`.",
+                    vm.name
+                ),
+            ),
+            None => (
+                format!(
+                    "Bootstrap script for VM `{}` not found at {}.",
+                    vm.name,
+                    missing.script.display()
+                ),
+                format!(
+                    "Create the script or adjust `bootstrap.script` for VM `{}`.",
+                    vm.name
+                ),
+            ),
+        };
+        diagnostics.push(Diagnostic::new(Severity::Info, message.clone()).with_help(help));
         if matches!(vm.bootstrap.mode, BootstrapMode::Always) {
             return Err(Error::BootstrapFailed {
                 vm: vm.name.clone(),
@@ -454,18 +776,27 @@ fn run_for_vm(
             stamp: None,
             log_path: None,
             ssh: None,
+            collected: Vec::new(),
         });
     }
 
-    let payload_source = vm.bootstrap.payload.as_ref().cloned();
-
-    let blueprint = assemble_blueprint(state_root, vm, &script_source, payload_source.as_ref())
-        .map_err(|err| Error::BootstrapFailed {
-            vm: vm.name.clone(),
-            message: err,
-        })?;
+    let mut blueprints = Vec::with_capacity(specs.len());
+    for spec in &specs {
+        let blueprint =
+            assemble_blueprint(state_root, vm, spec).map_err(|err| Error::BootstrapFailed {
+                vm: vm.name.clone(),
+                message: err,
+            })?;
+        blueprints.push(blueprint);
+    }
 
-    for warning in &blueprint.warnings {
+    let mut warnings: Vec<&String> = Vec::new();
+    for warning in blueprints.iter().flat_map(|blueprint| &blueprint.warnings) {
+        if !warnings.contains(&warning) {
+            warnings.push(warning);
+        }
+    }
+    for warning in warnings {
         diagnostics.push(
             Diagnostic::new(
                 Severity::Warning,
@@ -475,7 +806,8 @@ fn run_for_vm(
         );
     }
 
-    let plan = plan_for_vm(vm);
+    let live_since = live.map(|live| live.booted_at);
+    let plan = plan_for_vm(state_root, vm, live_since);
     emit_event(Event::BootstrapPlanned {
         vm: plan.vm.clone(),
         mode: plan.mode,
@@ -484,6 +816,7 @@ fn run_for_vm(
         trigger: plan.trigger,
         script_path: plan.script_path.clone(),
         payload_path: plan.payload_path.clone(),
+        payload_commit: plan.payload_commit.clone(),
         payload_bytes: plan.payload_bytes,
         handshake_timeout_secs: plan.handshake_timeout_secs,
         remote_dir: plan.remote_dir.clone(),
@@ -493,10 +826,27 @@ fn run_for_vm(
         artifact_hash: plan.artifact_hash.clone(),
         metadata_path: plan.metadata_path.clone(),
         warnings: plan.warnings.clone(),
+        stages: plan.stages.clone(),
     });
 
+    let force = live.is_some_and(|live| live.options.force);
     match plan.action {
         BootstrapPlanAction::WouldRun => {}
+        BootstrapPlanAction::UpToDate if force => {}
+        BootstrapPlanAction::UpToDate => {
+            diagnostics.push(Diagnostic::new(
+                Severity::Info,
+                format!("Bootstrap up-to-date for VM `{}`: {}", vm.name, plan.reason),
+            ));
+            return Ok(BootstrapRunOutcome {
+                vm: vm.name.clone(),
+                status: BootstrapRunStatus::NoOp,
+                stamp: load_stamp(state_root, &vm.name).map(|stamp| stamp.id()),
+                log_path: None,
+                ssh: None,
+                collected: Vec::new(),
+            });
+        }
         BootstrapPlanAction::WouldSkip => {
             diagnostics.push(Diagnostic::new(
                 Severity::Info,
@@ -508,6 +858,7 @@ fn run_for_vm(
                 stamp: None,
                 log_path: None,
                 ssh: None,
+                collected: Vec::new(),
             });
         }
         BootstrapPlanAction::Error => {
@@ -518,64 +869,82 @@ fn run_for_vm(
         }
     }
 
+    let primary = &blueprints[0];
+    let artifact_hash = plan
+        .artifact_hash
+        .clone()
+        .unwrap_or_else(|| primary.artifact_hash.clone());
     let handshake_path = state_root.join("handshakes").join(format!(
         "{}.json",
-        sanitize_handshake_identity(&blueprint.handshake_identity)
+        sanitize_handshake_identity(&primary.handshake_identity)
     ));
     let mut context_parts = Vec::new();
-    context_parts.push(format!("script {}", blueprint.script_source.display()));
-    context_parts.push(format!("staged {}", blueprint.staged_script.display()));
-    context_parts.push(format!("remote script {}", blueprint.remote_script));
-    context_parts.push(format!("remote dir {}", blueprint.remote_dir));
-    if let Some(payload_source) = blueprint.payload_source.as_ref() {
+    for blueprint in &blueprints {
+        let prefix = blueprint
+            .stage
+            .as_ref()
+            .map(|stage| format!("stage `{stage}` "))
+            .unwrap_or_default();
         context_parts.push(format!(
-            "payload {} ({} bytes)",
-            payload_source.display(),
-            blueprint.payload_bytes
+            "{prefix}script {}",
+            blueprint.script_source.display()
         ));
-    } else {
-        context_parts.push("payload none".to_string());
-    }
-    if let Some(remote_payload_dir) = blueprint.remote_payload_dir.as_ref() {
-        context_parts.push(format!("remote payload dir {}", remote_payload_dir));
+        context_parts.push(format!(
+            "{prefix}staged {}",
+            blueprint.staged_script.display()
+        ));
+        context_parts.push(format!("{prefix}remote script {}", blueprint.remote_script));
+        context_parts.push(format!("{prefix}remote dir {}", blueprint.remote_dir));
+        if let Some(payload_source) = blueprint.payload_source.as_ref() {
+            context_parts.push(format!(
+                "{prefix}payload {} ({} bytes)",
+                payload_source.display(),
+                blueprint.payload_bytes
+            ));
+        } else {
+            context_parts.push(format!("{prefix}payload none"));
+        }
+        if let Some(remote_payload_dir) = blueprint.remote_payload_dir.as_ref() {
+            context_parts.push(format!("{prefix}remote payload dir {}", remote_payload_dir));
+        }
     }
     context_parts.push(format!(
         "handshake `{}` -> {} (timeout {}s; freshness {}s)",
-        blueprint.handshake_identity,
+        primary.handshake_identity,
         handshake_path.display(),
-        blueprint.handshake_timeout.as_secs(),
+        primary.handshake_timeout.as_secs(),
         HANDSHAKE_FRESHNESS.as_secs()
     ));
     let mut ssh_context = Vec::new();
     ssh_context.push(format!(
         "{}@{}:{}",
-        blueprint.ssh.user, blueprint.ssh.host, blueprint.ssh.port
+        primary.ssh.user, primary.ssh.host, primary.ssh.port
     ));
-    if let Some(identity) = blueprint.ssh.identity.as_ref() {
+    if let Some(identity) = primary.ssh.identity.as_ref() {
         ssh_context.push(format!("identity {}", identity.display()));
     }
-    if !blueprint.ssh.options.is_empty() {
-        ssh_context.push(format!("options {}", blueprint.ssh.options.join(", ")));
+    if !primary.ssh.options.is_empty() {
+        ssh_context.push(format!("options {}", primary.ssh.options.join(", ")));
     }
     context_parts.push(format!("ssh {}", ssh_context.join("; ")));
-    if !blueprint.env.is_empty() {
-        let mut env_keys: Vec<_> = blueprint.env.keys().cloned().collect();
-        env_keys.sort();
-        context_parts.push(format!("env keys [{}]", env_keys.join(", ")));
+    if !plan.env_keys.is_empty() {
+        context_parts.push(format!("env keys [{}]", plan.env_keys.join(", ")));
     }
-    if let Some(metadata) = blueprint.metadata_path.as_ref() {
+    if let Some(metadata) = primary.metadata_path.as_ref() {
         context_parts.push(format!("metadata {}", metadata.display()));
     }
-    if let Some(command) = blueprint.verify.command.as_ref() {
-        context_parts.push(format!("verify command {}", command));
-    }
-    if let Some(path) = blueprint.verify.path.as_ref() {
-        let scope = if blueprint.verify.path_is_relative {
-            "relative"
-        } else {
-            "absolute"
-        };
-        context_parts.push(format!("verify path {} ({scope})", path));
+    if let Some(verify) = plan.verify.as_ref() {
+        if let Some(command) = verify.command.as_ref() {
+            context_parts.push(format!("verify command {}", command));
+        }
+        if let Some(path) = verify.path.as_ref() {
+            let scope = if verify.path_is_relative {
+                "relative"
+            } else {
+                "absolute"
+            };
+            context_parts.push(format!("verify path {} ({scope})", path));
+        }
     }
     emit_event(Event::Message {
         severity: Severity::Info,
@@ -587,7 +956,9 @@ fn run_for_vm(
     });
 
     let base_hash = derive_base_hash(vm)?;
-    let trigger = if matches!(vm.bootstrap.mode, BootstrapMode::Always) {
+    let trigger = if live.is_some() {
+        BootstrapTrigger::Manual
+    } else if matches!(vm.bootstrap.mode, BootstrapMode::Always) {
         BootstrapTrigger::Always
     } else {
         BootstrapTrigger::Auto
@@ -596,11 +967,11 @@ fn run_for_vm(
     emit_event(Event::BootstrapStarted {
         vm: vm.name.clone(),
         base_hash: base_hash.clone(),
-        artifact_hash: blueprint.artifact_hash.clone(),
+        artifact_hash: artifact_hash.clone(),
         trigger,
     });
 
-    let ssh_identity_detail = blueprint
+    let ssh_identity_detail = primary
         .ssh
         .identity
         .as_ref()
@@ -611,97 +982,84 @@ fn run_for_vm(
         text: format!(
             "→ {}: waiting for bootstrap readiness: handshake `{}` at {} or SSH {}@{}:{}{} (timeout {}s; freshness {}s).",
             vm.name,
-            blueprint.handshake_identity,
+            primary.handshake_identity,
             handshake_path.display(),
-            blueprint.ssh.user,
-            blueprint.ssh.host,
-            blueprint.ssh.port,
+            primary.ssh.user,
+            primary.ssh.host,
+            primary.ssh.port,
             ssh_identity_detail,
-            blueprint.handshake_timeout.as_secs(),
+            primary.handshake_timeout.as_secs(),
             HANDSHAKE_FRESHNESS.as_secs()
         ),
     });
 
-    let log_dir = log_root.join(LOG_SUBDIR);
-    let mut steps = Vec::new();
-    let start = Instant::now();
+    let mut run = RunRecorder {
+        vm,
+        state_root,
+        lifecycle: &lifecycle,
+        log_dir: log_root.join(LOG_SUBDIR),
+        blueprints: &blueprints,
+        artifact_hash: &artifact_hash,
+        base_hash: &base_hash,
+        steps: Vec::new(),
+        stage_status: vec![StageRunStatus::Pending; blueprints.len()],
+        completed: Vec::new(),
+        start: Instant::now(),
+        live: live.is_some(),
+        transport,
+        connected: false,
+    };
 
     let handshake_start = Instant::now();
     let handshake_result = wait_for_handshake(
+        transport,
         &vm.name,
-        &blueprint.handshake_identity,
+        &primary.handshake_identity,
         &handshake_path,
-        &blueprint.ssh,
-        blueprint.handshake_timeout,
+        &primary.ssh,
+        primary.handshake_timeout,
     );
-    let handshake_duration = handshake_start.elapsed();
-
-    match handshake_result {
-        Ok(success) => {
-            steps.push(StepLog::success(
-                BootstrapStepKind::WaitHandshake,
-                handshake_duration,
-                Some(success.detail.clone()),
-            ));
-            emit_event(Event::BootstrapStep {
-                vm: vm.name.clone(),
-                step: BootstrapStepKind::WaitHandshake,
-                status: BootstrapStepStatus::Success,
-                duration_ms: elapsed_ms(handshake_duration),
-                detail: Some(success.detail.clone()),
-            });
-        }
-        Err(err) => {
-            let failure_detail = match &err {
-                Error::BootstrapFailed { message, .. } => message.clone(),
+    let handshake_outcome = match handshake_result {
+        Ok(success) => CommandOutcome {
+            status: BootstrapStepStatus::Success,
+            duration: handshake_start.elapsed(),
+            detail: Some(success.detail),
+            bytes_skipped: None,
+        },
+        Err(err) => CommandOutcome {
+            status: BootstrapStepStatus::Failed,
+            duration: handshake_start.elapsed(),
+            detail: Some(match err {
+                Error::BootstrapFailed { message, .. } => message,
                 other => other.to_string(),
-            };
-            steps.push(StepLog::from_result(
-                BootstrapStepKind::WaitHandshake,
-                BootstrapStepStatus::Failed,
-                handshake_duration,
-                Some(failure_detail.clone()),
-            ));
-            emit_event(Event::BootstrapStep {
-                vm: vm.name.clone(),
-                step: BootstrapStepKind::WaitHandshake,
-                status: BootstrapStepStatus::Failed,
-                duration_ms: elapsed_ms(handshake_duration),
-                detail: Some(failure_detail.clone()),
-            });
-            let duration_ms = elapsed_ms(start.elapsed());
-            emit_event(Event::BootstrapFailed {
-                vm: vm.name.clone(),
-                duration_ms,
-                error: failure_detail.clone(),
-            });
-            let log_record = BootstrapRunLog::failure(
-                &vm.name,
-                &blueprint,
-                &base_hash,
-                steps,
-                duration_ms,
-                failure_detail.clone(),
-            );
-            write_run_log(&log_dir, &log_record).map_err(|io_err| Error::BootstrapFailed {
-                vm: vm.name.clone(),
-                message: format!("Failed to persist bootstrap log: {io_err}"),
-            })?;
-            teardown_bootstrap_vm(vm, state_root, &lifecycle, emit_event, diagnostics);
-            return Err(err);
-        }
+            }),
+            bytes_skipped: None,
+        },
+    };
+    run.record(
+        emit_event,
+        None,
+        BootstrapStepKind::WaitHandshake,
+        &handshake_outcome,
+    );
+    if !matches!(handshake_outcome.status, BootstrapStepStatus::Success) {
+        return Err(run.abort(
+            handshake_outcome.detail.unwrap_or_default(),
+            emit_event,
+            diagnostics,
+        ));
     }
 
     let mut connectivity_context = Vec::new();
     connectivity_context.push(format!(
         "{}@{}:{}",
-        blueprint.ssh.user, blueprint.ssh.host, blueprint.ssh.port
+        primary.ssh.user, primary.ssh.host, primary.ssh.port
     ));
-    if let Some(identity) = blueprint.ssh.identity.as_ref() {
+    if let Some(identity) = primary.ssh.identity.as_ref() {
         connectivity_context.push(format!("identity {}", identity.display()));
     }
-    if !blueprint.ssh.options.is_empty() {
-        connectivity_context.push(format!("options {}", blueprint.ssh.options.join(", ")));
+    if !primary.ssh.options.is_empty() {
+        connectivity_context.push(format!("options {}", primary.ssh.options.join(", ")));
     }
     emit_event(Event::Message {
         severity: Severity::Info,
@@ -712,288 +1070,317 @@ fn run_for_vm(
         ),
     });
 
-    let connect_outcome = check_connectivity(&blueprint.ssh);
-    let connect_duration = connect_outcome.duration;
-    emit_event(Event::BootstrapStep {
-        vm: vm.name.clone(),
-        step: BootstrapStepKind::Connect,
-        status: connect_outcome.status,
-        duration_ms: elapsed_ms(connect_duration),
-        detail: connect_outcome.detail.clone(),
-    });
-    steps.push(StepLog::from_result(
+    let (connect_outcome, attempts) = retry_step(
+        &steps.connect,
+        &vm.name,
         BootstrapStepKind::Connect,
-        connect_outcome.status,
-        connect_duration,
-        connect_outcome.detail.clone(),
-    ));
+        emit_event,
+        |timeout| check_connectivity(transport, &primary.ssh, timeout),
+        |outcome| outcome,
+    );
+    run.record_attempts(
+        emit_event,
+        None,
+        BootstrapStepKind::Connect,
+        &connect_outcome,
+        attempts,
+    );
     if !matches!(connect_outcome.status, BootstrapStepStatus::Success) {
-        let duration_ms = elapsed_ms(start.elapsed());
         let failure_detail = connect_outcome
             .detail
-            .clone()
             .unwrap_or_else(|| "Failed to establish SSH connectivity.".to_string());
-        emit_event(Event::BootstrapFailed {
-            vm: vm.name.clone(),
-            duration_ms,
-            error: failure_detail.clone(),
-        });
-        let log_record = BootstrapRunLog::failure(
-            &vm.name,
-            &blueprint,
-            &base_hash,
-            steps,
-            duration_ms,
-            failure_detail.clone(),
-        );
-        write_run_log(&log_dir, &log_record).map_err(|io_err| Error::BootstrapFailed {
-            vm: vm.name.clone(),
-            message: format!("Failed to persist bootstrap log: {io_err}"),
-        })?;
-
-        teardown_bootstrap_vm(vm, state_root, &lifecycle, emit_event, diagnostics);
-        return Err(Error::BootstrapFailed {
-            vm: vm.name.clone(),
-            message: failure_detail,
+        return Err(run.abort(failure_detail, emit_event, diagnostics));
+    }
+    run.connected = true;
+
+    let carried = carried_stages(state_root, vm, live_since);
+    let mut decisions = decide_stages(
+        blueprints.iter().map(|blueprint| {
+            (
+                blueprint.stage.as_deref(),
+                blueprint.run_if,
+                blueprint.artifact_hash.as_str(),
+            )
+        }),
+        &carried,
+    );
+    if force {
+        decisions.fill(StageDecision::Run);
+    }
+    if let CarriedOrigin::Resume { failed_stage } = &carried.origin {
+        emit_event(Event::Message {
+            severity: Severity::Info,
+            text: format!(
+                "→ {}: resuming bootstrap after the previous run failed at stage `{failed_stage}`.",
+                vm.name
+            ),
         });
     }
 
-    let mut transfer_context = Vec::new();
-    transfer_context.push(format!(
-        "{} -> {}",
-        blueprint.staged_script.display(),
-        blueprint.remote_script
-    ));
-    if let Some(staged_payload) = blueprint.staged_payload.as_ref() {
-        let remote_payload = blueprint
-            .remote_payload_dir
-            .as_deref()
-            .unwrap_or(&blueprint.remote_dir);
+    let mut applied_changes = false;
+    for (index, (blueprint, decision)) in blueprints.iter().zip(decisions).enumerate() {
+        let who = match blueprint.stage.as_ref() {
+            Some(stage) => format!("{} stage `{stage}`", vm.name),
+            None => vm.name.clone(),
+        };
+
+        if let Some(live) = live.filter(|live| !live.selects(blueprint.stage.as_deref())) {
+            // Left as it is on the guest; a stage the guest already carried
+            // keeps its stamp.
+            let previous = blueprint.stage.as_deref().and_then(|name| {
+                carried
+                    .stages
+                    .iter()
+                    .find(|stage| stage.name == name)
+                    .cloned()
+            });
+            let skipped = CommandOutcome {
+                status: BootstrapStepStatus::Skipped,
+                duration: Duration::ZERO,
+                detail: Some(format!(
+                    "Not selected for this run (--stage {}).",
+                    live.options.stages.join(",")
+                )),
+                bytes_skipped: None,
+            };
+            run.record(emit_event, Some(index), BootstrapStepKind::Apply, &skipped);
+            if let Some(previous) = previous {
+                run.stage_status[index] = StageRunStatus::Carried;
+                run.completed.push(previous);
+            }
+            continue;
+        }
+
+        if let StageDecision::Carry(reason) = decision {
+            let skipped = CommandOutcome {
+                status: BootstrapStepStatus::Skipped,
+                duration: Duration::ZERO,
+                detail: Some(reason),
+                bytes_skipped: None,
+            };
+            run.record(emit_event, Some(index), BootstrapStepKind::Apply, &skipped);
+            run.finish_stage(index, StageRunStatus::Carried);
+            continue;
+        }
+
+        let mut transfer_context = Vec::new();
         transfer_context.push(format!(
-            "{} -> {} ({} bytes)",
-            staged_payload.display(),
-            remote_payload,
-            blueprint.payload_bytes
+            "{} -> {}",
+            blueprint.staged_script.display(),
+            blueprint.remote_script
         ));
-    } else {
-        transfer_context.push("no payload staging".to_string());
-    }
-    emit_event(Event::Message {
-        severity: Severity::Info,
-        text: format!(
-            "→ {}: transferring bootstrap assets: {}.",
-            vm.name,
-            transfer_context.join("; ")
-        ),
-    });
-
-    let transfer_outcome = transfer_artifacts(&blueprint);
-    let transfer_duration = transfer_outcome.duration;
-    emit_event(Event::BootstrapStep {
-        vm: vm.name.clone(),
-        step: BootstrapStepKind::Transfer,
-        status: transfer_outcome.status,
-        duration_ms: elapsed_ms(transfer_duration),
-        detail: transfer_outcome.detail.clone(),
-    });
-    steps.push(StepLog::from_result(
-        BootstrapStepKind::Transfer,
-        transfer_outcome.status,
-        transfer_duration,
-        transfer_outcome.detail.clone(),
-    ));
-    if !matches!(transfer_outcome.status, BootstrapStepStatus::Success) {
-        let duration_ms = elapsed_ms(start.elapsed());
-        let failure_detail = transfer_outcome
-            .detail
-            .clone()
-            .unwrap_or_else(|| "Failed to transfer bootstrap artifacts.".to_string());
-        emit_event(Event::BootstrapFailed {
-            vm: vm.name.clone(),
-            duration_ms,
-            error: failure_detail.clone(),
+        if let Some(staged_payload) = blueprint.staged_payload.as_ref() {
+            let remote_payload = blueprint
+                .remote_payload_dir
+                .as_deref()
+                .unwrap_or(&blueprint.remote_dir);
+            transfer_context.push(format!(
+                "{} -> {} ({} bytes)",
+                staged_payload.display(),
+                remote_payload,
+                blueprint.payload_bytes
+            ));
+        } else {
+            transfer_context.push("no payload staging".to_string());
+        }
+        emit_event(Event::Message {
+            severity: Severity::Info,
+            text: format!(
+                "→ {who}: transferring bootstrap assets: {}.",
+                transfer_context.join("; ")
+            ),
         });
-        let log_record = BootstrapRunLog::failure(
-            &vm.name,
-            &blueprint,
-            &base_hash,
-            steps,
-            duration_ms,
-            failure_detail.clone(),
+
+        let (transfer_outcome, attempts) = retry_step(
+            &steps.transfer,
+            &who,
+            BootstrapStepKind::Transfer,
+            emit_event,
+            |timeout| transfer_artifacts(transport, blueprint, timeout),
+            |outcome| outcome,
         );
-        write_run_log(&log_dir, &log_record).map_err(|io_err| Error::BootstrapFailed {
-            vm: vm.name.clone(),
-            message: format!("Failed to persist bootstrap log: {io_err}"),
-        })?;
-        teardown_bootstrap_vm(vm, state_root, &lifecycle, emit_event, diagnostics);
-        return Err(Error::BootstrapFailed {
-            vm: vm.name.clone(),
-            message: failure_detail,
-        });
-    }
+        run.record_attempts(
+            emit_event,
+            Some(index),
+            BootstrapStepKind::Transfer,
+            &transfer_outcome,
+            attempts,
+        );
+        if !matches!(transfer_outcome.status, BootstrapStepStatus::Success) {
+            let failure_detail = transfer_outcome
+                .detail
+                .unwrap_or_else(|| "Failed to transfer bootstrap artifacts.".to_string());
+            run.finish_stage(index, StageRunStatus::Failed);
+            return Err(run.abort(failure_detail, emit_event, diagnostics));
+        }
 
-    let mut apply_context = Vec::new();
-    apply_context.push(format!("remote dir {}", blueprint.remote_dir));
-    apply_context.push(format!("script {}", blueprint.remote_script));
-    if let Some(remote_payload_dir) = blueprint.remote_payload_dir.as_ref() {
-        apply_context.push(format!("payload dir {}", remote_payload_dir));
-    }
-    if !blueprint.env.is_empty() {
+        let mut apply_context = Vec::new();
+        apply_context.push(format!("remote dir {}", blueprint.remote_dir));
+        apply_context.push(format!("script {}", blueprint.remote_script));
+        if let Some(remote_payload_dir) = blueprint.remote_payload_dir.as_ref() {
+            apply_context.push(format!("payload dir {}", remote_payload_dir));
+        }
         let mut env_keys: Vec<_> = blueprint.env.keys().cloned().collect();
         env_keys.sort();
         apply_context.push(format!("env keys [{}]", env_keys.join(", ")));
-    } else {
-        apply_context.push("env keys []".to_string());
-    }
-    emit_event(Event::Message {
-        severity: Severity::Info,
-        text: format!(
-            "→ {}: executing remote bootstrap script ({})",
-            vm.name,
-            apply_context.join("; ")
-        ),
-    });
-
-    let apply_outcome = execute_remote(&blueprint);
-    let apply_duration = apply_outcome.command.duration;
-    emit_event(Event::BootstrapStep {
-        vm: vm.name.clone(),
-        step: BootstrapStepKind::Apply,
-        status: apply_outcome.command.status,
-        duration_ms: elapsed_ms(apply_duration),
-        detail: apply_outcome.command.detail.clone(),
-    });
-    steps.push(StepLog::from_result(
-        BootstrapStepKind::Apply,
-        apply_outcome.command.status,
-        apply_duration,
-        apply_outcome.command.detail.clone(),
-    ));
-    if !matches!(apply_outcome.command.status, BootstrapStepStatus::Success) {
-        let duration_ms = elapsed_ms(start.elapsed());
-        let failure_detail = apply_outcome
-            .command
-            .detail
-            .clone()
-            .unwrap_or_else(|| "Remote bootstrap execution failed.".to_string());
-        emit_event(Event::BootstrapFailed {
-            vm: vm.name.clone(),
-            duration_ms,
-            error: failure_detail.clone(),
-        });
-        let log_record = BootstrapRunLog::failure(
-            &vm.name,
-            &blueprint,
-            &base_hash,
-            steps,
-            duration_ms,
-            failure_detail.clone(),
-        );
-        write_run_log(&log_dir, &log_record).map_err(|io_err| Error::BootstrapFailed {
-            vm: vm.name.clone(),
-            message: format!("Failed to persist bootstrap log: {io_err}"),
-        })?;
-        teardown_bootstrap_vm(vm, state_root, &lifecycle, emit_event, diagnostics);
-        return Err(Error::BootstrapFailed {
-            vm: vm.name.clone(),
-            message: failure_detail,
+        // A stage's own `timeout_secs` wins over `[bootstrap.steps.apply]`.
+        let apply_policy = BootstrapStepPolicy {
+            timeout: blueprint.apply_timeout.or(steps.apply.timeout),
+            ..steps.apply
+        };
+        if let Some(timeout) = apply_policy.timeout {
+            apply_context.push(format!("timeout {}s", timeout.as_secs()));
+        }
+        emit_event(Event::Message {
+            severity: Severity::Info,
+            text: format!(
+                "→ {who}: executing remote bootstrap script ({})",
+                apply_context.join("; ")
+            ),
         });
-    }
 
-    let mut verify_context = Vec::new();
-    if let Some(command) = blueprint.verify.command.as_ref() {
-        verify_context.push(format!("command {}", command));
-    }
-    if let Some(path) = blueprint.verify.path.as_ref() {
-        let scope = if blueprint.verify.path_is_relative {
-            "relative"
-        } else {
-            "absolute"
-        };
-        verify_context.push(format!("path {} ({scope})", path));
-    }
-    if verify_context.is_empty() {
-        verify_context.push("no verification checks configured".to_string());
-    }
-    emit_event(Event::Message {
-        severity: Severity::Info,
-        text: format!(
-            "→ {}: verifying remote state ({})",
-            vm.name,
-            verify_context.join("; ")
-        ),
-    });
+        let (apply_outcome, attempts) = retry_step(
+            &apply_policy,
+            &who,
+            BootstrapStepKind::Apply,
+            emit_event,
+            |timeout| execute_remote(transport, blueprint, timeout),
+            |outcome| &mut outcome.command,
+        );
+        run.record_attempts(
+            emit_event,
+            Some(index),
+            BootstrapStepKind::Apply,
+            &apply_outcome.command,
+            attempts,
+        );
+        if !matches!(apply_outcome.command.status, BootstrapStepStatus::Success) {
+            let failure_detail = apply_outcome
+                .command
+                .detail
+                .unwrap_or_else(|| "Remote bootstrap execution failed.".to_string());
+            run.finish_stage(index, StageRunStatus::Failed);
+            return Err(run.abort(failure_detail, emit_event, diagnostics));
+        }
 
-    let verify_outcome = verify_remote(&blueprint);
-    let verify_duration = verify_outcome.duration;
-    emit_event(Event::BootstrapStep {
-        vm: vm.name.clone(),
-        step: BootstrapStepKind::Verify,
-        status: verify_outcome.status,
-        duration_ms: elapsed_ms(verify_duration),
-        detail: verify_outcome.detail.clone(),
-    });
-    steps.push(StepLog::from_result(
-        BootstrapStepKind::Verify,
-        verify_outcome.status,
-        verify_duration,
-        verify_outcome.detail.clone(),
-    ));
-    if !matches!(verify_outcome.status, BootstrapStepStatus::Success) {
-        let duration_ms = elapsed_ms(start.elapsed());
-        let failure_detail = verify_outcome
-            .detail
-            .clone()
-            .unwrap_or_else(|| "Bootstrap verification failed.".to_string());
-        emit_event(Event::BootstrapFailed {
-            vm: vm.name.clone(),
-            duration_ms,
-            error: failure_detail.clone(),
+        let mut verify_context = Vec::new();
+        if let Some(command) = blueprint.verify.command.as_ref() {
+            verify_context.push(format!("command {}", command));
+        }
+        if let Some(path) = blueprint.verify.path.as_ref() {
+            let scope = if blueprint.verify.path_is_relative {
+                "relative"
+            } else {
+                "absolute"
+            };
+            verify_context.push(format!("path {} ({scope})", path));
+        }
+        if verify_context.is_empty() {
+            verify_context.push("no verification checks configured".to_string());
+        }
+        emit_event(Event::Message {
+            severity: Severity::Info,
+            text: format!(
+                "→ {who}: verifying remote state ({})",
+                verify_context.join("; ")
+            ),
         });
-        let log_record = BootstrapRunLog::failure(
-            &vm.name,
-            &blueprint,
-            &base_hash,
-            steps,
-            duration_ms,
-            failure_detail.clone(),
+
+        let (verify_outcome, attempts) = retry_step(
+            &steps.verify,
+            &who,
+            BootstrapStepKind::Verify,
+            emit_event,
+            |timeout| verify_remote(transport, blueprint, timeout),
+            |outcome| outcome,
         );
-        write_run_log(&log_dir, &log_record).map_err(|io_err| Error::BootstrapFailed {
-            vm: vm.name.clone(),
-            message: format!("Failed to persist bootstrap log: {io_err}"),
-        })?;
-        teardown_bootstrap_vm(vm, state_root, &lifecycle, emit_event, diagnostics);
-        return Err(Error::BootstrapFailed {
-            vm: vm.name.clone(),
-            message: failure_detail,
-        });
+        run.record_attempts(
+            emit_event,
+            Some(index),
+            BootstrapStepKind::Verify,
+            &verify_outcome,
+            attempts,
+        );
+        if !matches!(verify_outcome.status, BootstrapStepStatus::Success) {
+            let failure_detail = verify_outcome
+                .detail
+                .unwrap_or_else(|| "Bootstrap verification failed.".to_string());
+            run.finish_stage(index, StageRunStatus::Failed);
+            return Err(run.abort(failure_detail, emit_event, diagnostics));
+        }
+
+        match apply_outcome.completion {
+            ApplyCompletion::NoOp => run.finish_stage(index, StageRunStatus::NoOp),
+            ApplyCompletion::Success => {
+                applied_changes = true;
+                run.finish_stage(index, StageRunStatus::Success);
+            }
+        }
     }
 
-    // Vizier service management removed; UI now owns agent connectivity.
+    let final_status = if applied_changes {
+        BootstrapStatus::Success
+    } else {
+        BootstrapStatus::NoOp
+    };
 
-    let total_ms = elapsed_ms(start.elapsed());
-    let final_status = match apply_outcome.completion {
-        ApplyCompletion::NoOp => BootstrapStatus::NoOp,
-        ApplyCompletion::Success => BootstrapStatus::Success,
+    // With unselected stages the stamp reflects what the guest actually has.
+    let stamp_hash = if live.is_some_and(|live| !live.options.stages.is_empty()) {
+        pipeline_artifact_hash(
+            run.completed
+                .iter()
+                .map(|stage| (Some(stage.name.as_str()), stage.artifact_hash.as_str())),
+        )
+    } else {
+        artifact_hash.clone()
+    };
+    let mut stamp = BootstrapStamp::new(
+        &vm.name,
+        &base_hash,
+        &stamp_hash,
+        pipeline_env(vm, &primary.env),
+        false,
+    );
+    if primary.stage.is_some() {
+        stamp.stages = run.completed.clone();
+    }
+    let stamp_id = match write_stamp(state_root, &stamp) {
+        Ok(()) => Some(stamp.id()),
+        Err(err) => {
+            diagnostics.push(
+                Diagnostic::new(
+                    Severity::Warning,
+                    format!(
+                        "Failed to record bootstrap stamp for VM `{}`: {err}",
+                        vm.name
+                    ),
+                )
+                .with_help("Later runs cannot detect unchanged inputs for this VM until a stamp is written."),
+            );
+            None
+        }
     };
+    clear_progress(state_root, &vm.name);
 
+    let total_ms = elapsed_ms(run.start.elapsed());
     emit_event(Event::BootstrapCompleted {
         vm: vm.name.clone(),
         status: final_status,
         duration_ms: total_ms,
-        stamp: None,
+        stamp: stamp_id.clone(),
     });
+    let collected = collect::collect_vm(vm, &primary.ssh, transport, emit_event);
 
-    let log_record = BootstrapRunLog::success(
-        &vm.name,
-        &blueprint,
-        &base_hash,
-        steps,
-        total_ms,
-        final_status,
+    let mut log_record = run.log(
+        match final_status {
+            BootstrapStatus::Success => "success",
+            BootstrapStatus::NoOp => "noop",
+        },
+        None,
     );
+    log_record.stamp = stamp_id.clone();
     let log_path =
-        write_run_log(&log_dir, &log_record).map_err(|io_err| Error::BootstrapFailed {
+        write_run_log(&run.log_dir, &log_record).map_err(|io_err| Error::BootstrapFailed {
             vm: vm.name.clone(),
             message: format!("Failed to persist bootstrap log: {io_err}"),
         })?;
@@ -1006,83 +1393,671 @@ fn run_for_vm(
     Ok(BootstrapRunOutcome {
         vm: vm.name.clone(),
         status: run_status,
-        stamp: None,
+        stamp: stamp_id,
         log_path: Some(log_path),
-        ssh: Some(plan_ssh_from_config(&blueprint.ssh)),
+        ssh: Some(plan_ssh_from_config(&primary.ssh)),
+        collected,
     })
 }
 
-#[derive(Debug)]
-struct HandshakeSuccess {
-    detail: String,
+/// Progress of one bootstrap run: the steps taken so far and which stages
+/// completed, so failures can be logged and resumed consistently.
+struct RunRecorder<'a> {
+    vm: &'a VmDefinition,
+    state_root: &'a Path,
+    lifecycle: &'a LifecycleConfig,
+    log_dir: PathBuf,
+    blueprints: &'a [BootstrapBlueprint],
+    artifact_hash: &'a str,
+    base_hash: &'a str,
+    steps: Vec<StepLog>,
+    stage_status: Vec<StageRunStatus>,
+    completed: Vec<StageStamp>,
+    start: Instant,
+    /// The VM was already running; failures leave it up.
+    live: bool,
+    transport: &'a dyn Transport,
+    /// SSH connectivity was confirmed, so artifacts can still be collected
+    /// when a later step fails.
+    connected: bool,
 }
 
-fn wait_for_handshake(
-    vm: &str,
-    handshake_identity: &str,
-    handshake_path: &Path,
-    ssh: &SshConfig,
-    timeout: Duration,
-) -> Result<HandshakeSuccess> {
-    let deadline = Instant::now() + timeout;
-    let mut last_probe: Option<Instant> = None;
-    let mut attempted_connectivity = false;
-    let mut last_connectivity_error: Option<String> = None;
+impl RunRecorder<'_> {
+    fn record(
+        &mut self,
+        emit_event: &mut dyn FnMut(Event),
+        stage: Option<usize>,
+        kind: BootstrapStepKind,
+        outcome: &CommandOutcome,
+    ) {
+        self.record_attempts(emit_event, stage, kind, outcome, 1);
+    }
 
-    loop {
-        if let Some(timestamp) = read_handshake_timestamp(handshake_path)? {
-            let now = SystemTime::now();
-            if now
-                .duration_since(timestamp)
-                .unwrap_or_else(|_| Duration::from_secs(0))
-                <= HANDSHAKE_FRESHNESS
-            {
-                let detail = format!(
-                    "Handshake file {} updated at {:?} (identity `{}`).",
-                    handshake_path.display(),
-                    timestamp,
-                    handshake_identity
-                );
-                return Ok(HandshakeSuccess { detail });
-            }
-        }
+    /// Like [`RunRecorder::record`], for a step that took `attempts` tries.
+    fn record_attempts(
+        &mut self,
+        emit_event: &mut dyn FnMut(Event),
+        stage: Option<usize>,
+        kind: BootstrapStepKind,
+        outcome: &CommandOutcome,
+        attempts: u32,
+    ) {
+        let stage = stage.and_then(|index| self.blueprints[index].stage.clone());
+        emit_event(Event::BootstrapStep {
+            vm: self.vm.name.clone(),
+            stage: stage.clone(),
+            step: kind,
+            status: outcome.status,
+            duration_ms: elapsed_ms(outcome.duration),
+            detail: outcome.detail.clone(),
+            bytes_skipped: outcome.bytes_skipped,
+            attempts,
+        });
+        self.steps
+            .push(StepLog::from_result(stage, kind, outcome, attempts));
+    }
 
-        let now = Instant::now();
-        if now >= deadline {
-            let mut message = format!(
-                "Timed out waiting for bootstrap readiness. Expected handshake `{}` at {} within {}s (freshness window {}s).",
-                handshake_identity,
-                handshake_path.display(),
-                timeout.as_secs(),
-                HANDSHAKE_FRESHNESS.as_secs()
-            );
-            if let Some(err) = last_connectivity_error {
-                message.push_str(&format!(" Last SSH probe error: {err}"));
-            } else if attempted_connectivity {
-                message.push_str(" SSH connectivity probes did not succeed within the deadline.");
-            } else {
-                message.push_str(" SSH connectivity probes were not attempted.");
-            }
-            return Err(Error::BootstrapFailed {
-                vm: vm.to_string(),
-                message,
+    fn finish_stage(&mut self, index: usize, status: StageRunStatus) {
+        self.stage_status[index] = status;
+        if status == StageRunStatus::Failed {
+            return;
+        }
+        if let Some(name) = self.blueprints[index].stage.clone() {
+            self.completed.push(StageStamp {
+                name,
+                artifact_hash: self.blueprints[index].artifact_hash.clone(),
             });
         }
+    }
 
-        let should_probe = last_probe
-            .map(|stamp| {
-                now.duration_since(stamp) >= Duration::from_millis(CONNECTIVITY_RETRY_DELAY_MS)
-            })
-            .unwrap_or(true);
-        if should_probe {
-            attempted_connectivity = true;
-            let outcome = check_connectivity(ssh);
-            if matches!(outcome.status, BootstrapStepStatus::Success) {
-                let mut detail = outcome.detail.unwrap_or_else(|| {
-                    format!(
-                        "SSH connectivity confirmed for {}@{}:{}.",
-                        ssh.user, ssh.host, ssh.port
-                    )
+    fn log(&self, status: &str, error: Option<String>) -> BootstrapRunLog {
+        BootstrapRunLog::new(self, status, error)
+    }
+
+    /// Report the failure, persist the run log and any resumable progress,
+    /// and stop the VM. Stages that completed keep their overlay so the next
+    /// run resumes at the failed stage.
+    fn abort(
+        &self,
+        failure_detail: String,
+        emit_event: &mut dyn FnMut(Event),
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Error {
+        emit_event(Event::BootstrapFailed {
+            vm: self.vm.name.clone(),
+            duration_ms: elapsed_ms(self.start.elapsed()),
+            error: failure_detail.clone(),
+        });
+        let log_result = write_run_log(
+            &self.log_dir,
+            &self.log("failed", Some(failure_detail.clone())),
+        );
+
+        let failed_stage = self
+            .stage_status
+            .iter()
+            .position(|status| *status == StageRunStatus::Failed)
+            .and_then(|index| self.blueprints[index].stage.clone());
+        let resumable = match failed_stage {
+            Some(failed_stage) if !self.completed.is_empty() => {
+                let progress = BootstrapProgress {
+                    vm: self.vm.name.clone(),
+                    base_hash: self.base_hash.to_string(),
+                    completed: self.completed.clone(),
+                    failed_stage,
+                    recorded_at: unix_now(),
+                };
+                match write_progress(self.state_root, &progress) {
+                    Ok(()) => true,
+                    Err(err) => {
+                        diagnostics.push(Diagnostic::new(
+                            Severity::Warning,
+                            format!(
+                                "Failed to record bootstrap progress for VM `{}`: {err}",
+                                self.vm.name
+                            ),
+                        ));
+                        false
+                    }
+                }
+            }
+            _ => {
+                clear_progress(self.state_root, &self.vm.name);
+                false
+            }
+        };
+        if self.connected {
+            collect::collect_vm(self.vm, &self.blueprints[0].ssh, self.transport, emit_event);
+        }
+        if self.live {
+            emit_event(Event::Message {
+                severity: Severity::Warning,
+                text: format!(
+                    "→ {}: bootstrap failed; the VM keeps running so the pipeline can be fixed and rerun with `castra bootstrap`.",
+                    self.vm.name
+                ),
+            });
+        } else {
+            teardown_bootstrap_vm(
+                self.vm,
+                self.state_root,
+                self.lifecycle,
+                resumable,
+                emit_event,
+                diagnostics,
+            );
+        }
+
+        match log_result {
+            Ok(_) => Error::BootstrapFailed {
+                vm: self.vm.name.clone(),
+                message: failure_detail,
+            },
+            Err(io_err) => Error::BootstrapFailed {
+                vm: self.vm.name.clone(),
+                message: format!("Failed to persist bootstrap log: {io_err}"),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StageRunStatus {
+    Pending,
+    Carried,
+    NoOp,
+    Success,
+    Failed,
+}
+
+impl StageRunStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Carried => "carried-over",
+            Self::NoOp => "noop",
+            Self::Success => "success",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+/// One entry of a VM's bootstrap pipeline. Single-script VMs yield a single
+/// unnamed stage built from `bootstrap.script`, `payload` and `verify_*`.
+#[derive(Debug, Clone)]
+struct StageSpec {
+    name: Option<String>,
+    /// Host script, or the config file declaring `[vms.provision]` for the
+    /// rendered provision stage.
+    script: PathBuf,
+    payload: Option<PayloadSource>,
+    env: HashMap<String, String>,
+    env_secrets: HashMap<String, SecretSource>,
+    verify: Option<BootstrapVerifyConfig>,
+    timeout: Option<Duration>,
+    run_if: BootstrapRunIf,
+    /// Declarative state the stage's script is rendered from.
+    provision: Option<ProvisionConfig>,
+    /// Directory searched for `bootstrap.toml`.
+    metadata_dir: Option<PathBuf>,
+}
+
+impl StageSpec {
+    fn from_script(
+        name: Option<String>,
+        script: PathBuf,
+        payload: Option<PayloadSource>,
+        env: HashMap<String, String>,
+        verify: Option<BootstrapVerifyConfig>,
+    ) -> Self {
+        Self {
+            name,
+            metadata_dir: script.parent().map(Path::to_path_buf),
+            script,
+            payload,
+            env,
+            env_secrets: HashMap::new(),
+            verify,
+            timeout: None,
+            run_if: BootstrapRunIf::default(),
+            provision: None,
+        }
+    }
+
+    fn script_available(&self) -> bool {
+        self.provision.is_some() || self.script.is_file()
+    }
+}
+
+/// Pipeline stages configured for `vm`, or `None` when it has no script.
+///
+/// `[vms.provision]` always runs first. Next to it a single `bootstrap.script`
+/// becomes a stage named [`SCRIPT_STAGE`].
+fn stage_specs(vm: &VmDefinition) -> Option<Vec<StageSpec>> {
+    let mut specs: Vec<StageSpec> = vm
+        .bootstrap
+        .stages
+        .iter()
+        .map(|stage| StageSpec {
+            timeout: stage.timeout_secs.map(Duration::from_secs),
+            run_if: stage.run_if,
+            env_secrets: stage.env_secrets.clone(),
+            ..StageSpec::from_script(
+                Some(stage.name.clone()),
+                stage.script.clone(),
+                stage.payload.clone(),
+                stage.env.clone(),
+                stage.verify.clone(),
+            )
+        })
+        .collect();
+    if specs.is_empty()
+        && let Some(script) = vm.bootstrap.script.clone()
+    {
+        specs.push(StageSpec::from_script(
+            vm.provision.as_ref().map(|_| SCRIPT_STAGE.to_string()),
+            script,
+            vm.bootstrap.payload.clone(),
+            HashMap::new(),
+            vm.bootstrap.verify.clone(),
+        ));
+    }
+    if let Some(provision) = &vm.provision {
+        // Share the scripts' `bootstrap.toml` so every stage uses the same SSH target.
+        let metadata_dir = specs.first().and_then(|spec| spec.metadata_dir.clone());
+        specs.insert(
+            0,
+            StageSpec {
+                provision: Some(provision.clone()),
+                metadata_dir,
+                ..StageSpec::from_script(
+                    Some(PROVISION_STAGE.to_string()),
+                    provision.source.clone(),
+                    None,
+                    HashMap::new(),
+                    None,
+                )
+            },
+        );
+    }
+    (!specs.is_empty()).then_some(specs)
+}
+
+/// Whether `vm` runs a named, multi-stage pipeline.
+fn is_staged(vm: &VmDefinition) -> bool {
+    !vm.bootstrap.stages.is_empty() || vm.provision.is_some()
+}
+
+/// Resolve every stage, reporting the index of the first one that fails.
+fn resolve_pipeline(
+    vm: &VmDefinition,
+    specs: &[StageSpec],
+) -> std::result::Result<Vec<BootstrapBlueprintInputs>, (usize, String)> {
+    specs
+        .iter()
+        .enumerate()
+        .map(|(index, spec)| resolve_blueprint_inputs(vm, spec).map_err(|err| (index, err)))
+        .collect()
+}
+
+/// Hash identifying the whole pipeline. A single-script pipeline keeps its
+/// script's artifact hash so existing stamps stay valid.
+fn pipeline_artifact_hash<'a>(
+    stages: impl IntoIterator<Item = (Option<&'a str>, &'a str)>,
+) -> String {
+    let stages: Vec<_> = stages.into_iter().collect();
+    if let [(None, hash)] = stages.as_slice() {
+        return hash.to_string();
+    }
+    let mut hasher = Sha256::new();
+    for (name, hash) in stages {
+        hasher.update(b"stage\0");
+        hasher.update(name.unwrap_or_default().as_bytes());
+        hasher.update(b"\0");
+        hasher.update(hash.as_bytes());
+        hasher.update(b"\0");
+    }
+    hex::encode(hasher.finalize())
+}
+
+/// Environment recorded in the stamp: the resolved script env for
+/// single-script pipelines, the VM-level env for staged ones (stage env is
+/// already part of each stage's artifact hash).
+fn pipeline_env<'a>(
+    vm: &'a VmDefinition,
+    primary_env: &'a HashMap<String, String>,
+) -> &'a HashMap<String, String> {
+    if !is_staged(vm) {
+        primary_env
+    } else {
+        &vm.bootstrap.env
+    }
+}
+
+/// Guest directory holding every stage directory of a staged pipeline.
+fn pipeline_remote_dir(stage_remote_dir: &str) -> String {
+    match stage_remote_dir.rsplit_once('/') {
+        Some(("", _)) => "/".to_string(),
+        Some((parent, _)) => parent.to_string(),
+        None => stage_remote_dir.to_string(),
+    }
+}
+
+/// Stage state the guest already carries before a run starts.
+struct CarriedStages {
+    stages: Vec<StageStamp>,
+    origin: CarriedOrigin,
+}
+
+enum CarriedOrigin {
+    Nothing,
+    /// The overlay of a run that failed part-way was kept for resuming.
+    Resume {
+        failed_stage: String,
+    },
+    /// The base image is a golden image baked from an earlier run.
+    Durable,
+    /// The VM is still running with the stages of an earlier run this boot.
+    Live,
+}
+
+fn carried_stages(state_root: &Path, vm: &VmDefinition, live_since: Option<u64>) -> CarriedStages {
+    let nothing = CarriedStages {
+        stages: Vec::new(),
+        origin: CarriedOrigin::Nothing,
+    };
+    if !is_staged(vm) {
+        return nothing;
+    }
+    if let Some(progress) = resumable_progress(state_root, vm) {
+        return CarriedStages {
+            stages: progress.completed,
+            origin: CarriedOrigin::Resume {
+                failed_stage: progress.failed_stage,
+            },
+        };
+    }
+    if !matches!(vm.bootstrap.mode, BootstrapMode::Auto) {
+        return nothing;
+    }
+    let Some(stamp) = load_stamp(state_root, &vm.name) else {
+        return nothing;
+    };
+    if live_since.is_some_and(|booted| stamp.completed_at >= booted) {
+        return CarriedStages {
+            stages: stamp.stages,
+            origin: CarriedOrigin::Live,
+        };
+    }
+    if stamp.durable
+        && compute_file_sha256(vm.base_image.path()).ok() == Some(stamp.base_hash.clone())
+    {
+        return CarriedStages {
+            stages: stamp.stages,
+            origin: CarriedOrigin::Durable,
+        };
+    }
+    nothing
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum StageDecision {
+    Run,
+    /// Skip the stage; its effects are already present. Holds the reason.
+    Carry(String),
+}
+
+/// Apply each stage's `run_if` policy to the carried state. A `changed`
+/// stage only carries over while every earlier stage did too, since a
+/// rerun stage may alter what later ones build on.
+fn decide_stages<'a>(
+    stages: impl IntoIterator<Item = (Option<&'a str>, BootstrapRunIf, &'a str)>,
+    carried: &CarriedStages,
+) -> Vec<StageDecision> {
+    let source = match carried.origin {
+        CarriedOrigin::Resume { .. } => "before the previous run failed",
+        CarriedOrigin::Durable => "in the base image",
+        CarriedOrigin::Live => "on the running VM",
+        CarriedOrigin::Nothing => "",
+    };
+    let mut intact = true;
+    stages
+        .into_iter()
+        .map(|(name, run_if, artifact_hash)| {
+            let previous =
+                name.and_then(|name| carried.stages.iter().find(|stage| stage.name == name));
+            match (run_if, previous) {
+                (BootstrapRunIf::Always, _) => StageDecision::Run,
+                (BootstrapRunIf::Once, Some(_)) => {
+                    StageDecision::Carry(format!("run_if `once`; already applied {source}."))
+                }
+                (BootstrapRunIf::Changed, Some(previous))
+                    if intact && previous.artifact_hash == artifact_hash =>
+                {
+                    StageDecision::Carry(format!("Inputs unchanged; already applied {source}."))
+                }
+                _ => {
+                    intact = false;
+                    StageDecision::Run
+                }
+            }
+        })
+        .collect()
+}
+
+#[derive(Debug)]
+struct HandshakeSuccess {
+    detail: String,
+}
+
+/// SSH target and verify checks used to decide whether a VM is healthy.
+struct HealthProbe {
+    vm: String,
+    ssh: SshConfig,
+    checks: Vec<HealthCheck>,
+}
+
+/// Verify checks of one pipeline stage, run from that stage's directory.
+struct HealthCheck {
+    remote_dir: String,
+    remote_payload_dir: Option<String>,
+    env: HashMap<String, String>,
+    secrets: SecretEnv,
+    verify: BootstrapVerifyPlan,
+}
+
+/// SSH settings bootstrap resolves for `vm`, for other guest operations.
+pub(crate) fn ssh_for_vm(vm: &VmDefinition) -> std::result::Result<SshConfig, String> {
+    HealthProbe::for_vm(vm).map(|probe| probe.ssh)
+}
+
+impl HealthProbe {
+    fn for_vm(vm: &VmDefinition) -> std::result::Result<Self, String> {
+        if let Some(specs) =
+            stage_specs(vm).filter(|specs| specs.iter().all(|spec| spec.script_available()))
+        {
+            let stages = resolve_pipeline(vm, &specs).map_err(|(_, err)| err)?;
+            let ssh = stages[0].ssh.clone();
+            return Ok(Self {
+                vm: vm.name.clone(),
+                ssh,
+                checks: stages
+                    .into_iter()
+                    .map(|stage| HealthCheck {
+                        remote_dir: stage.remote_dir,
+                        remote_payload_dir: stage.remote_payload_dir,
+                        env: stage.env,
+                        secrets: stage.secrets,
+                        verify: stage.verify,
+                    })
+                    .collect(),
+            });
+        }
+
+        let mut ssh = SshConfig {
+            user: DEFAULT_SSH_USER.to_string(),
+            host: DEFAULT_SSH_HOST.to_string(),
+            port: DEFAULT_SSH_PORT,
+            identity: None,
+            options: Vec::new(),
+            known_hosts: None,
+        };
+        apply_ssh_defaults(&mut ssh, vm.bootstrap.managed_keys.as_ref());
+        if let Some(forward) = vm
+            .port_forwards
+            .iter()
+            .find(|pf| pf.protocol == PortProtocol::Tcp && pf.guest == 22)
+        {
+            ssh.port = forward.host;
+        }
+        let verify = vm.bootstrap.verify.as_ref();
+        Ok(Self {
+            vm: vm.name.clone(),
+            ssh,
+            checks: vec![HealthCheck {
+                remote_dir: normalize_remote_dir(&vm.bootstrap.remote_dir.to_string_lossy()),
+                remote_payload_dir: None,
+                env: vm.bootstrap.env.clone(),
+                secrets: SecretEnv::resolve(&vm.bootstrap.env_secrets, &vm.bootstrap.secret_salt)?,
+                verify: BootstrapVerifyPlan {
+                    command: verify.and_then(|verify| verify.command.clone()),
+                    path: verify
+                        .and_then(|verify| verify.path.as_ref())
+                        .map(|path| path.to_string_lossy().into_owned()),
+                    path_is_relative: verify
+                        .and_then(|verify| verify.path.as_ref())
+                        .is_some_and(|path| !path.is_absolute()),
+                },
+            }],
+        })
+    }
+
+    fn check(&self, transport: &dyn Transport) -> std::result::Result<(), String> {
+        transport
+            .connect(&self.ssh, None)
+            .map_err(|err| format!("SSH unreachable: {err}"))?;
+        for check in &self.checks {
+            if let Some(command) = check.verify.command.as_ref() {
+                let script = build_verify_command(
+                    &self.vm,
+                    &check.remote_dir,
+                    check.remote_payload_dir.as_deref(),
+                    &check.env,
+                    &check.secrets,
+                    command,
+                );
+                transport.exec(&self.ssh, &script, None).map_err(|err| {
+                    check
+                        .secrets
+                        .redact(&format!("verify command failed: {err}"))
+                })?;
+            }
+            if let Some(path) = check.verify.path.as_ref() {
+                let resolved = if check.verify.path_is_relative {
+                    format!("{}/{}", check.remote_dir, path)
+                } else {
+                    path.clone()
+                };
+                transport
+                    .exec(
+                        &self.ssh,
+                        &format!("test -e {}", shell_quote(&resolved)),
+                        None,
+                    )
+                    .map_err(|err| format!("verify path {resolved} missing: {err}"))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Poll `vm` until it accepts SSH and passes its verify checks, returning
+/// how long that took or why it never did within `timeout`.
+pub fn wait_until_healthy(
+    vm: &VmDefinition,
+    timeout: Duration,
+    transport: &dyn Transport,
+) -> std::result::Result<Duration, String> {
+    let probe = HealthProbe::for_vm(vm)?;
+    let start = Instant::now();
+    loop {
+        match probe.check(transport) {
+            Ok(()) => return Ok(start.elapsed()),
+            Err(err) if start.elapsed() >= timeout => {
+                return Err(format!("not healthy after {}s ({err})", timeout.as_secs()));
+            }
+            Err(_) => std::thread::sleep(Duration::from_millis(CONNECTIVITY_RETRY_DELAY_MS)),
+        }
+    }
+}
+
+fn wait_for_handshake(
+    transport: &dyn Transport,
+    vm: &str,
+    handshake_identity: &str,
+    handshake_path: &Path,
+    ssh: &SshConfig,
+    timeout: Duration,
+) -> Result<HandshakeSuccess> {
+    let deadline = Instant::now() + timeout;
+    let mut last_probe: Option<Instant> = None;
+    let mut attempted_connectivity = false;
+    let mut last_connectivity_error: Option<String> = None;
+
+    loop {
+        if let Some(timestamp) = read_handshake_timestamp(handshake_path)? {
+            let now = SystemTime::now();
+            if now
+                .duration_since(timestamp)
+                .unwrap_or_else(|_| Duration::from_secs(0))
+                <= HANDSHAKE_FRESHNESS
+            {
+                let detail = format!(
+                    "Handshake file {} updated at {:?} (identity `{}`).",
+                    handshake_path.display(),
+                    timestamp,
+                    handshake_identity
+                );
+                return Ok(HandshakeSuccess { detail });
+            }
+        }
+
+        let now = Instant::now();
+        if now >= deadline {
+            let mut message = format!(
+                "Timed out waiting for bootstrap readiness. Expected handshake `{}` at {} within {}s (freshness window {}s).",
+                handshake_identity,
+                handshake_path.display(),
+                timeout.as_secs(),
+                HANDSHAKE_FRESHNESS.as_secs()
+            );
+            if let Some(err) = last_connectivity_error {
+                message.push_str(&format!(" Last SSH probe error: {err}"));
+            } else if attempted_connectivity {
+                message.push_str(" SSH connectivity probes did not succeed within the deadline.");
+            } else {
+                message.push_str(" SSH connectivity probes were not attempted.");
+            }
+            return Err(Error::BootstrapFailed {
+                vm: vm.to_string(),
+                message,
+            });
+        }
+
+        let should_probe = last_probe
+            .map(|stamp| {
+                now.duration_since(stamp) >= Duration::from_millis(CONNECTIVITY_RETRY_DELAY_MS)
+            })
+            .unwrap_or(true);
+        if should_probe {
+            attempted_connectivity = true;
+            let outcome = check_connectivity(transport, ssh, remaining(Some(deadline)));
+            if matches!(outcome.status, BootstrapStepStatus::Success) {
+                let mut detail = outcome.detail.unwrap_or_else(|| {
+                    format!(
+                        "SSH connectivity confirmed for {}@{}:{}.",
+                        ssh.user, ssh.host, ssh.port
+                    )
                 });
                 detail.push(' ');
                 detail.push_str(&format!(
@@ -1115,15 +2090,23 @@ fn teardown_bootstrap_vm(
     vm: &VmDefinition,
     state_root: &Path,
     lifecycle: &LifecycleConfig,
+    retain_overlay: bool,
     emit_event: &mut dyn FnMut(Event),
     diagnostics: &mut Vec<Diagnostic>,
 ) {
     emit_event(Event::Message {
         severity: Severity::Warning,
-        text: format!(
-            "→ {}: bootstrap failed; shutting down VM to reset state.",
-            vm.name
-        ),
+        text: if retain_overlay {
+            format!(
+                "→ {}: bootstrap failed; shutting down VM and keeping its overlay so the next run resumes at the failed stage.",
+                vm.name
+            )
+        } else {
+            format!(
+                "→ {}: bootstrap failed; shutting down VM to reset state.",
+                vm.name
+            )
+        },
     });
 
     let timeouts = ShutdownTimeouts::new(
@@ -1132,7 +2115,12 @@ fn teardown_bootstrap_vm(
         lifecycle.sigkill_wait(),
     );
 
-    match shutdown_vm(vm, state_root, timeouts, None) {
+    let shutdown = if retain_overlay {
+        shutdown_vm_retaining_overlay(vm, state_root, timeouts, None)
+    } else {
+        shutdown_vm(vm, state_root, timeouts, None)
+    };
+    match shutdown {
         Ok(report) => {
             for event in report.events {
                 emit_event(event);
@@ -1205,18 +2193,250 @@ fn sanitize_handshake_identity(identity: &str) -> String {
     sanitized
 }
 
-fn derive_base_hash(vm: &VmDefinition) -> Result<String> {
-    compute_file_sha256(vm.base_image.path()).map_err(|err| Error::BootstrapFailed {
-        vm: vm.name.clone(),
-        message: err,
-    })
+/// Record of the last successful bootstrap run for a VM.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BootstrapStamp {
+    pub vm: String,
+    /// sha256 of the base image the run was applied on top of.
+    pub base_hash: String,
+    pub artifact_hash: String,
+    /// sha256 over the resolved bootstrap environment.
+    pub env_digest: String,
+    /// Unix timestamp (seconds) of the successful run.
+    pub completed_at: u64,
+    /// Whether the bootstrapped state lives in the base image itself (a baked
+    /// golden image) rather than in an overlay discarded on shutdown.
+    #[serde(default)]
+    pub durable: bool,
+    /// Per-stage artifact hashes for staged pipelines.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stages: Vec<StageStamp>,
 }
 
-fn compute_file_sha256(path: &Path) -> std::result::Result<String, String> {
-    let mut file = File::open(path).map_err(|err| {
-        format!(
-            "Failed to open base image {} for hashing: {err}",
-            path.display()
+/// A pipeline stage that completed with the given inputs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StageStamp {
+    pub name: String,
+    pub artifact_hash: String,
+}
+
+/// Stages completed by a run that failed part-way. Kept next to the stamp
+/// while the VM's overlay is retained so the next run resumes there.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BootstrapProgress {
+    pub vm: String,
+    /// sha256 of the base image beneath the retained overlay.
+    pub base_hash: String,
+    pub completed: Vec<StageStamp>,
+    pub failed_stage: String,
+    /// Unix timestamp (seconds) of the failed run.
+    pub recorded_at: u64,
+}
+
+impl BootstrapStamp {
+    fn new(
+        vm: &str,
+        base_hash: &str,
+        artifact_hash: &str,
+        env: &HashMap<String, String>,
+        durable: bool,
+    ) -> Self {
+        Self {
+            vm: vm.to_string(),
+            base_hash: base_hash.to_string(),
+            artifact_hash: artifact_hash.to_string(),
+            env_digest: env_digest(env),
+            completed_at: unix_now(),
+            durable,
+            stages: Vec::new(),
+        }
+    }
+
+    /// Short identifier combining the artifact hash and completion time.
+    pub fn id(&self) -> String {
+        let prefix = self.artifact_hash.get(..12).unwrap_or(&self.artifact_hash);
+        format!("{prefix}@{}", self.completed_at)
+    }
+
+    /// Time elapsed since the run completed.
+    pub fn age(&self) -> Duration {
+        Duration::from_secs(unix_now().saturating_sub(self.completed_at))
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0)
+}
+
+/// Location of the stamp file for `vm_name` beneath the state root.
+pub fn stamp_path(state_root: &Path, vm_name: &str) -> PathBuf {
+    state_root
+        .join(STAMP_SUBDIR)
+        .join(format!("{vm_name}.json"))
+}
+
+/// Load the stamp recorded for `vm_name`, ignoring missing or unreadable files.
+pub fn load_stamp(state_root: &Path, vm_name: &str) -> Option<BootstrapStamp> {
+    let contents = fs::read_to_string(stamp_path(state_root, vm_name)).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+fn write_stamp(state_root: &Path, stamp: &BootstrapStamp) -> io::Result<()> {
+    let path = stamp_path(state_root, &stamp.vm);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let json = serde_json::to_string_pretty(stamp).map_err(io::Error::other)?;
+    fs::write(path, json)
+}
+
+/// Location of the resume record for `vm_name` beneath the state root.
+pub fn progress_path(state_root: &Path, vm_name: &str) -> PathBuf {
+    state_root
+        .join(STAMP_SUBDIR)
+        .join(format!("{vm_name}.progress.json"))
+}
+
+/// Load the resume record for `vm_name`, ignoring missing or unreadable files.
+pub fn load_progress(state_root: &Path, vm_name: &str) -> Option<BootstrapProgress> {
+    let contents = fs::read_to_string(progress_path(state_root, vm_name)).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+/// Resume record for `vm` whose retained overlay still sits on an unchanged
+/// base image.
+pub fn resumable_progress(state_root: &Path, vm: &VmDefinition) -> Option<BootstrapProgress> {
+    let progress = load_progress(state_root, &vm.name)?;
+    if !vm.overlay.is_file() {
+        return None;
+    }
+    let base_hash = compute_file_sha256(vm.base_image.path()).ok()?;
+    (base_hash == progress.base_hash).then_some(progress)
+}
+
+/// Forget any resume record for `vm_name`.
+pub fn clear_progress(state_root: &Path, vm_name: &str) {
+    let _ = fs::remove_file(progress_path(state_root, vm_name));
+}
+
+fn write_progress(state_root: &Path, progress: &BootstrapProgress) -> io::Result<()> {
+    let path = progress_path(state_root, &progress.vm);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let json = serde_json::to_string_pretty(progress).map_err(io::Error::other)?;
+    fs::write(path, json)
+}
+
+/// Record that `vm` boots from `base_hash`, an image that already carries its
+/// bootstrap (e.g. a baked golden image), so `auto` runs report up-to-date.
+pub fn record_durable_stamp(
+    state_root: &Path,
+    vm: &VmDefinition,
+    base_hash: &str,
+) -> Result<BootstrapStamp> {
+    let failed = |message: String| Error::BootstrapFailed {
+        vm: vm.name.clone(),
+        message,
+    };
+    let specs = stage_specs(vm)
+        .filter(|specs| specs.iter().all(|spec| spec.script_available()))
+        .ok_or_else(|| failed("Bootstrap script is not available to stamp.".to_string()))?;
+    let stages = resolve_pipeline(vm, &specs).map_err(|(_, err)| failed(err))?;
+    let mut stamp = BootstrapStamp::new(
+        &vm.name,
+        base_hash,
+        &pipeline_artifact_hash(
+            stages
+                .iter()
+                .map(|stage| (stage.stage.as_deref(), stage.artifact_hash.as_str())),
+        ),
+        pipeline_env(vm, &stages[0].env),
+        true,
+    );
+    stamp.stages = stages
+        .iter()
+        .filter_map(|stage| {
+            Some(StageStamp {
+                name: stage.stage.clone()?,
+                artifact_hash: stage.artifact_hash.clone(),
+            })
+        })
+        .collect();
+    write_stamp(state_root, &stamp)
+        .map_err(|err| failed(format!("Failed to write bootstrap stamp: {err}")))?;
+    Ok(stamp)
+}
+
+/// Stamp whose artifact and environment match the current inputs. Durable
+/// stamps must also match the base image digest.
+fn matching_stamp(
+    state_root: &Path,
+    vm: &VmDefinition,
+    artifact_hash: &str,
+    env: &HashMap<String, String>,
+) -> Option<BootstrapStamp> {
+    let stamp = load_stamp(state_root, &vm.name)?;
+    if stamp.artifact_hash != artifact_hash || stamp.env_digest != env_digest(env) {
+        return None;
+    }
+    if stamp.durable && compute_file_sha256(vm.base_image.path()).ok()? != stamp.base_hash {
+        return None;
+    }
+    Some(stamp)
+}
+
+fn env_digest(env: &HashMap<String, String>) -> String {
+    let mut pairs: Vec<_> = env.iter().collect();
+    pairs.sort();
+    let mut hasher = Sha256::new();
+    for (key, value) in pairs {
+        hasher.update(key.as_bytes());
+        hasher.update(b"=");
+        hasher.update(value.as_bytes());
+        hasher.update(b"\0");
+    }
+    hex::encode(hasher.finalize())
+}
+
+/// Compact rendering of a stamp age (e.g. `42s`, `5m`, `3h`, `2d`).
+pub fn format_stamp_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    match secs {
+        0..=59 => format!("{secs}s"),
+        60..=3_599 => format!("{}m", secs / 60),
+        3_600..=86_399 => format!("{}h", secs / 3_600),
+        _ => format!("{}d", secs / 86_400),
+    }
+}
+
+/// Artifact hash of the bootstrap inputs for `vm`, when its script resolves.
+pub fn artifact_hash_for_vm(vm: &VmDefinition) -> Option<String> {
+    let specs = stage_specs(vm)?;
+    if !specs.iter().all(|spec| spec.script_available()) {
+        return None;
+    }
+    let stages = resolve_pipeline(vm, &specs).ok()?;
+    Some(pipeline_artifact_hash(stages.iter().map(|stage| {
+        (stage.stage.as_deref(), stage.artifact_hash.as_str())
+    })))
+}
+
+fn derive_base_hash(vm: &VmDefinition) -> Result<String> {
+    compute_file_sha256(vm.base_image.path()).map_err(|err| Error::BootstrapFailed {
+        vm: vm.name.clone(),
+        message: err,
+    })
+}
+
+fn compute_file_sha256(path: &Path) -> std::result::Result<String, String> {
+    let mut file = File::open(path).map_err(|err| {
+        format!(
+            "Failed to open base image {} for hashing: {err}",
+            path.display()
         )
     })?;
     let mut hasher = Sha256::new();
@@ -1236,16 +2456,26 @@ fn compute_file_sha256(path: &Path) -> std::result::Result<String, String> {
 #[derive(Debug)]
 struct BootstrapBlueprintInputs {
     vm: String,
+    stage: Option<String>,
+    run_if: BootstrapRunIf,
+    apply_timeout: Option<Duration>,
     handshake_identity: String,
     script_source: PathBuf,
+    /// Script contents rendered from `[vms.provision]` instead of read from
+    /// `script_source`.
+    rendered_script: Option<String>,
     payload_source: Option<PathBuf>,
+    /// Commit a git payload was exported from.
+    payload_commit: Option<String>,
     payload_bytes: u64,
+    payload_manifest: PayloadManifest,
     handshake_timeout: Duration,
     remote_dir: String,
     remote_script: String,
     remote_payload_dir: Option<String>,
     ssh: SshConfig,
     env: HashMap<String, String>,
+    secrets: SecretEnv,
     verify: BootstrapVerifyPlan,
     artifact_hash: String,
     metadata_path: Option<PathBuf>,
@@ -1255,33 +2485,32 @@ struct BootstrapBlueprintInputs {
 #[derive(Debug)]
 struct BootstrapBlueprint {
     vm: String,
+    stage: Option<String>,
+    run_if: BootstrapRunIf,
+    apply_timeout: Option<Duration>,
     handshake_identity: String,
     script_source: PathBuf,
     staged_script: PathBuf,
+    /// sha256 of the staged script, recorded in run logs.
+    script_sha256: String,
     payload_source: Option<PathBuf>,
+    payload_commit: Option<String>,
     staged_payload: Option<PathBuf>,
     payload_bytes: u64,
+    payload_manifest: PayloadManifest,
     handshake_timeout: Duration,
     remote_dir: String,
     remote_script: String,
     remote_payload_dir: Option<String>,
     ssh: SshConfig,
     env: HashMap<String, String>,
+    secrets: SecretEnv,
     verify: BootstrapVerifyPlan,
     artifact_hash: String,
     metadata_path: Option<PathBuf>,
     warnings: Vec<String>,
 }
 
-#[derive(Debug, Clone)]
-struct SshConfig {
-    user: String,
-    host: String,
-    port: u16,
-    identity: Option<PathBuf>,
-    options: Vec<String>,
-}
-
 fn plan_ssh_from_config(ssh: &SshConfig) -> BootstrapPlanSsh {
     BootstrapPlanSsh {
         user: ssh.user.clone(),
@@ -1340,29 +2569,43 @@ struct MetadataVerify {
 fn assemble_blueprint(
     state_root: &Path,
     vm: &VmDefinition,
-    script_source: &Path,
-    payload_source: Option<&PathBuf>,
+    spec: &StageSpec,
 ) -> std::result::Result<BootstrapBlueprint, String> {
-    let inputs = resolve_blueprint_inputs(vm, script_source, payload_source)?;
-    let staging_root = state_root.join(STAGING_SUBDIR).join(&vm.name);
+    let inputs = resolve_blueprint_inputs(vm, spec)?;
+    let mut staging_root = state_root.join(STAGING_SUBDIR).join(&vm.name);
+    if let Some(stage) = spec.name.as_ref() {
+        staging_root.push(stage);
+    }
     let (staged_script, staged_payload, payload_bytes) = stage_local_assets(
         &inputs.script_source,
+        inputs.rendered_script.as_deref(),
         inputs.payload_source.as_deref(),
+        &vm.bootstrap.template_vars,
         &staging_root,
     )?;
+    let script_sha256 = fs::read(&staged_script)
+        .map(|contents| hex::encode(Sha256::digest(&contents)))
+        .map_err(|err| format!("Failed to hash {}: {err}", staged_script.display()))?;
 
     let BootstrapBlueprintInputs {
         vm,
+        stage,
+        run_if,
+        apply_timeout,
         handshake_identity,
         script_source: resolved_script,
+        rendered_script: _,
         payload_source,
+        payload_commit,
         payload_bytes: _,
+        payload_manifest,
         handshake_timeout,
         remote_dir,
         remote_script,
         remote_payload_dir,
         ssh,
         env,
+        secrets,
         verify,
         artifact_hash,
         metadata_path,
@@ -1371,18 +2614,25 @@ fn assemble_blueprint(
 
     Ok(BootstrapBlueprint {
         vm,
+        stage,
+        run_if,
+        apply_timeout,
         script_source: resolved_script,
         handshake_identity,
         staged_script,
+        script_sha256,
         payload_source,
+        payload_commit,
         staged_payload,
         payload_bytes,
+        payload_manifest,
         handshake_timeout,
         remote_dir,
         remote_script,
         remote_payload_dir,
         ssh,
         env,
+        secrets,
         verify,
         artifact_hash,
         metadata_path,
@@ -1392,14 +2642,24 @@ fn assemble_blueprint(
 
 fn resolve_blueprint_inputs(
     vm: &VmDefinition,
-    script_source: &Path,
-    payload_source: Option<&PathBuf>,
+    spec: &StageSpec,
 ) -> std::result::Result<BootstrapBlueprintInputs, String> {
     let mut warnings = Vec::new();
+    let script_source = spec.script.as_path();
+    let payload_source = spec.payload.as_ref();
+
+    let rendered_script = match &spec.provision {
+        Some(provision) => Some(provision::render_script(vm, provision)?),
+        None if script_source.to_string_lossy().ends_with(TEMPLATE_SUFFIX) => {
+            Some(render_template(script_source, &vm.bootstrap.template_vars)?)
+        }
+        None => None,
+    };
 
-    let metadata_path = script_source
-        .parent()
-        .map(|parent| parent.join("bootstrap.toml"))
+    let metadata_path = spec
+        .metadata_dir
+        .as_ref()
+        .map(|dir| dir.join("bootstrap.toml"))
         .filter(|path| path.is_file());
 
     let metadata = match metadata_path.as_ref() {
@@ -1457,10 +2717,19 @@ fn resolve_blueprint_inputs(
     }
 
     remote_dir = normalize_remote_dir(&remote_dir);
+    if let Some(stage) = spec.name.as_ref() {
+        remote_dir = format!("{}/{stage}", remote_dir.trim_end_matches('/'));
+    }
     let remote_script = format!("{remote_dir}/{}", STAGED_SCRIPT_NAME);
 
+    let mut payload_commit = None;
     let payload_source_resolved = match payload_source {
-        Some(path) if path.exists() => {
+        Some(PayloadSource::Git(git)) => {
+            let export = git_payload::export(git)?;
+            payload_commit = Some(export.commit);
+            Some(export.dir)
+        }
+        Some(PayloadSource::Dir(path)) if path.exists() => {
             if path.is_dir() {
                 Some(path.clone())
             } else {
@@ -1471,7 +2740,7 @@ fn resolve_blueprint_inputs(
                 ));
             }
         }
-        Some(path) => {
+        Some(PayloadSource::Dir(path)) => {
             warnings.push(format!(
                 "Payload directory not found at {}; continuing without payload.",
                 path.display()
@@ -1485,18 +2754,19 @@ fn resolve_blueprint_inputs(
         .as_ref()
         .map(|_| format!("{remote_dir}/{}", STAGED_PAYLOAD_DIR));
 
-    let payload_bytes = match payload_source_resolved.as_ref() {
-        Some(path) => calculate_payload_bytes(path)?,
-        None => 0,
-    };
-
     let mut env = metadata
         .as_ref()
         .map(|meta| meta.env.clone())
         .unwrap_or_default();
-    for (key, value) in &vm.bootstrap.env {
+    for (key, value) in vm.bootstrap.env.iter().chain(&spec.env) {
         env.insert(key.clone(), value.clone());
     }
+    let mut secret_sources = vm.bootstrap.env_secrets.clone();
+    secret_sources.extend(spec.env_secrets.clone());
+    // A secret shadows a plain variable of the same name.
+    env.retain(|key, _| !secret_sources.contains_key(key));
+    let secrets = SecretEnv::resolve(&secret_sources, &vm.bootstrap.secret_salt)
+        .map_err(|err| format!("Bootstrap env for `{}`: {err}", vm.name))?;
 
     let mut verify_command = metadata
         .as_ref()
@@ -1511,7 +2781,7 @@ fn resolve_blueprint_inputs(
         .map(|path| !path.starts_with('/'))
         .unwrap_or(false);
 
-    if let Some(config_verify) = &vm.bootstrap.verify {
+    if let Some(config_verify) = &spec.verify {
         if let Some(cmd) = config_verify.command.as_ref() {
             verify_command = Some(cmd.clone());
         }
@@ -1533,6 +2803,7 @@ fn resolve_blueprint_inputs(
         port: DEFAULT_SSH_PORT,
         identity: None,
         options: Vec::new(),
+        known_hosts: None,
     };
 
     if let Some(meta_ssh) = metadata.as_ref().and_then(|meta| meta.ssh.as_ref()) {
@@ -1570,7 +2841,7 @@ fn resolve_blueprint_inputs(
         }
     }
 
-    ensure_default_ssh_options(&mut ssh.options);
+    apply_ssh_defaults(&mut ssh, vm.bootstrap.managed_keys.as_ref());
 
     if ssh.port == DEFAULT_SSH_PORT && ssh.host == DEFAULT_SSH_HOST {
         if let Some(forward) = vm
@@ -1587,26 +2858,41 @@ fn resolve_blueprint_inputs(
         }
     }
 
-    let artifact_hash = compute_artifact_hash(
+    let payload_entries = match payload_source_resolved.as_deref() {
+        Some(path) => staged_payload_entries(path, &vm.bootstrap.template_vars)?,
+        None => Vec::new(),
+    };
+    let (artifact_hash, payload_manifest) = compute_artifact_hash(
         script_source,
-        payload_source_resolved.as_deref(),
+        rendered_script.as_deref(),
+        payload_entries,
+        payload_commit.as_deref(),
         &env,
+        &secrets,
         &remote_dir,
         &verify,
     )?;
+    let payload_bytes = payload_manifest.file_bytes();
 
     Ok(BootstrapBlueprintInputs {
         vm: vm.name.clone(),
+        stage: spec.name.clone(),
+        run_if: spec.run_if,
+        apply_timeout: spec.timeout,
         handshake_identity,
         script_source: script_source.to_path_buf(),
+        rendered_script,
         payload_source: payload_source_resolved,
+        payload_commit,
         payload_bytes,
+        payload_manifest,
         handshake_timeout,
         remote_dir,
         remote_script,
         remote_payload_dir,
         ssh,
         env,
+        secrets,
         verify,
         artifact_hash,
         metadata_path,
@@ -1645,17 +2931,13 @@ fn normalize_remote_dir(input: &str) -> String {
 
 fn stage_local_assets(
     script_source: &Path,
+    rendered_script: Option<&str>,
     payload_source: Option<&Path>,
+    template_vars: &BTreeMap<String, String>,
     staging_root: &Path,
 ) -> std::result::Result<(PathBuf, Option<PathBuf>, u64), String> {
-    if staging_root.exists() {
-        fs::remove_dir_all(staging_root).map_err(|err| {
-            format!(
-                "Failed to clear bootstrap staging directory {}: {err}",
-                staging_root.display()
-            )
-        })?;
-    }
+    // The staged payload is kept between runs and synced in place.
+    clear_staging_root(staging_root, payload_source.map(|_| STAGED_PAYLOAD_DIR))?;
     fs::create_dir_all(staging_root).map_err(|err| {
         format!(
             "Failed to create bootstrap staging directory {}: {err}",
@@ -1672,14 +2954,27 @@ fn stage_local_assets(
             )
         })?;
     }
-    fs::copy(script_source, &staged_script).map_err(|err| {
-        format!(
-            "Failed to copy bootstrap script from {} to {}: {err}",
-            script_source.display(),
-            staged_script.display()
-        )
-    })?;
-    if let Ok(metadata) = fs::metadata(script_source) {
+    if let Some(contents) = rendered_script {
+        fs::write(&staged_script, contents)
+            .and_then(|()| fs::set_permissions(&staged_script, fs::Permissions::from_mode(0o755)))
+            .map_err(|err| {
+                format!(
+                    "Failed to write rendered bootstrap script {}: {err}",
+                    staged_script.display()
+                )
+            })?;
+    } else {
+        fs::copy(script_source, &staged_script).map_err(|err| {
+            format!(
+                "Failed to copy bootstrap script from {} to {}: {err}",
+                script_source.display(),
+                staged_script.display()
+            )
+        })?;
+    }
+    if rendered_script.is_none()
+        && let Ok(metadata) = fs::metadata(script_source)
+    {
         if let Err(err) = fs::set_permissions(&staged_script, metadata.permissions()) {
             return Err(format!(
                 "Failed to set permissions on staged script {}: {err}",
@@ -1697,7 +2992,7 @@ fn stage_local_assets(
                 dest.display()
             )
         })?;
-        payload_bytes = copy_payload_dir(source, &dest)?;
+        payload_bytes = sync_payload_dir(source, &dest, template_vars)?;
         Some(dest)
     } else {
         None
@@ -1706,97 +3001,176 @@ fn stage_local_assets(
     Ok((staged_script, staged_payload, payload_bytes))
 }
 
-fn copy_payload_dir(source: &Path, dest: &Path) -> std::result::Result<u64, String> {
-    let mut stack = vec![source.to_path_buf()];
-    let mut total = 0u64;
-
-    while let Some(current) = stack.pop() {
-        let metadata = fs::symlink_metadata(&current).map_err(|err| {
+/// Remove everything under `staging_root` except the entry named `keep`.
+fn clear_staging_root(staging_root: &Path, keep: Option<&str>) -> std::result::Result<(), String> {
+    let entries = match fs::read_dir(staging_root) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => {
+            return Err(format!(
+                "Failed to read bootstrap staging directory {}: {err}",
+                staging_root.display()
+            ));
+        }
+    };
+    for entry in entries {
+        let path = entry
+            .map_err(|err| {
+                format!(
+                    "Failed to read bootstrap staging directory {}: {err}",
+                    staging_root.display()
+                )
+            })?
+            .path();
+        if keep.is_some_and(|keep| path.file_name() == Some(keep.as_ref())) {
+            continue;
+        }
+        let removed = if path.is_dir() && !path.is_symlink() {
+            fs::remove_dir_all(&path)
+        } else {
+            fs::remove_file(&path)
+        };
+        removed.map_err(|err| {
             format!(
-                "Failed to inspect payload entry {}: {err}",
-                current.display()
+                "Failed to clear bootstrap staging entry {}: {err}",
+                path.display()
             )
         })?;
+    }
+    Ok(())
+}
 
-        let rel = current
-            .strip_prefix(source)
-            .map_err(|_| format!("Failed to compute relative path for {}", current.display()))?;
-        let target = dest.join(rel);
+/// Mirror `source` into `dest`, copying only files whose size or
+/// modification time changed (or, for templates, whose rendered contents
+/// changed) and deleting entries `source` no longer has. Returns the payload
+/// size in bytes.
+fn sync_payload_dir(
+    source: &Path,
+    dest: &Path,
+    template_vars: &BTreeMap<String, String>,
+) -> std::result::Result<u64, String> {
+    fs::create_dir_all(dest).map_err(|err| {
+        format!(
+            "Failed to create payload staging directory {}: {err}",
+            dest.display()
+        )
+    })?;
+    let wanted = staged_payload_entries(source, template_vars)?;
+    let existing = collect_payload_entries(dest)?;
+    let wanted_paths: HashMap<&str, PayloadEntryKind> = wanted
+        .iter()
+        .map(|entry| (entry.rel_path.as_str(), entry.kind))
+        .collect();
 
-        if metadata.is_dir() {
-            fs::create_dir_all(&target).map_err(|err| {
-                format!(
-                    "Failed to create payload directory {}: {err}",
-                    target.display()
-                )
-            })?;
-            for entry in fs::read_dir(&current).map_err(|err| {
-                format!(
-                    "Failed to read payload directory {}: {err}",
-                    current.display()
-                )
-            })? {
-                let entry = entry.map_err(|err| {
+    // Deepest first, so a removed directory is still there for its children.
+    for entry in existing.iter().rev() {
+        if wanted_paths.get(entry.rel_path.as_str()) == Some(&entry.kind) {
+            continue;
+        }
+        let removed = match entry.kind {
+            PayloadEntryKind::Directory => fs::remove_dir_all(&entry.source_path),
+            PayloadEntryKind::File => fs::remove_file(&entry.source_path),
+        };
+        match removed {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => {
+                return Err(format!(
+                    "Failed to remove stale staged payload entry {}: {err}",
+                    entry.source_path.display()
+                ));
+            }
+        }
+    }
+
+    let mut total = 0u64;
+    for entry in &wanted {
+        let target = dest.join(&entry.rel_path);
+        match entry.kind {
+            PayloadEntryKind::Directory => {
+                fs::create_dir_all(&target).map_err(|err| {
                     format!(
-                        "Failed to read payload entry in {}: {err}",
-                        current.display()
+                        "Failed to create payload directory {}: {err}",
+                        target.display()
                     )
                 })?;
-                stack.push(entry.path());
             }
-        } else if metadata.is_file() {
-            if let Some(parent) = target.parent() {
-                fs::create_dir_all(parent).map_err(|err| {
+            PayloadEntryKind::File => {
+                total = total.saturating_add(entry.size);
+                let metadata = fs::metadata(&entry.source_path).map_err(|err| {
+                    format!(
+                        "Failed to inspect payload entry {}: {err}",
+                        entry.source_path.display()
+                    )
+                })?;
+                if let Some(rendered) = &entry.rendered {
+                    if fs::read(&target).ok().as_deref() != Some(rendered.as_bytes()) {
+                        fs::write(&target, rendered).map_err(|err| {
+                            format!(
+                                "Failed to write rendered payload file {}: {err}",
+                                target.display()
+                            )
+                        })?;
+                    }
+                    fs::set_permissions(&target, metadata.permissions()).map_err(|err| {
+                        format!(
+                            "Failed to set permissions on staged payload file {}: {err}",
+                            target.display()
+                        )
+                    })?;
+                    continue;
+                }
+                let modified = metadata.modified().ok();
+                let unchanged = fs::metadata(&target).is_ok_and(|staged| {
+                    staged.len() == metadata.len()
+                        && modified.is_some()
+                        && staged.modified().ok() == modified
+                });
+                if !unchanged {
+                    fs::copy(&entry.source_path, &target).map_err(|err| {
+                        format!(
+                            "Failed to copy payload file {}: {err}",
+                            entry.source_path.display()
+                        )
+                    })?;
+                    if let Some(modified) = modified {
+                        File::options()
+                            .write(true)
+                            .open(&target)
+                            .and_then(|file| file.set_modified(modified))
+                            .map_err(|err| {
+                                format!(
+                                    "Failed to stamp staged payload file {}: {err}",
+                                    target.display()
+                                )
+                            })?;
+                    }
+                }
+                fs::set_permissions(&target, metadata.permissions()).map_err(|err| {
                     format!(
-                        "Failed to create payload parent directory {}: {err}",
-                        parent.display()
+                        "Failed to set permissions on staged payload file {}: {err}",
+                        target.display()
                     )
                 })?;
             }
-            fs::copy(&current, &target).map_err(|err| {
-                format!("Failed to copy payload file {}: {err}", current.display())
-            })?;
-            fs::set_permissions(&target, metadata.permissions()).map_err(|err| {
-                format!(
-                    "Failed to set permissions on staged payload file {}: {err}",
-                    target.display()
-                )
-            })?;
-            total = total.saturating_add(metadata.len());
-        } else {
-            return Err(format!(
-                "Unsupported payload entry type at {}; only files and directories are supported.",
-                current.display()
-            ));
         }
     }
 
     Ok(total)
 }
 
-fn calculate_payload_bytes(root: &Path) -> std::result::Result<u64, String> {
-    let entries = collect_payload_entries(root)?;
-    Ok(entries
-        .iter()
-        .filter_map(|entry| {
-            if matches!(entry.kind, PayloadEntryKind::File) {
-                Some(entry.size)
-            } else {
-                None
-            }
-        })
-        .sum())
-}
-
 #[derive(Debug)]
 struct PayloadEntry {
     rel_path: String,
     source_path: PathBuf,
     kind: PayloadEntryKind,
     size: u64,
+    mode: u32,
+    /// Contents rendered from a `.tmpl` source, staged in place of its bytes.
+    rendered: Option<String>,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum PayloadEntryKind {
     File,
     Directory,
@@ -1825,6 +3199,8 @@ fn collect_payload_entries(root: &Path) -> std::result::Result<Vec<PayloadEntry>
                     source_path: current.clone(),
                     kind: PayloadEntryKind::Directory,
                     size: 0,
+                    mode: metadata.permissions().mode() & 0o7777,
+                    rendered: None,
                 });
             } else if metadata.is_file() {
                 entries.push(PayloadEntry {
@@ -1832,6 +3208,8 @@ fn collect_payload_entries(root: &Path) -> std::result::Result<Vec<PayloadEntry>
                     source_path: current.clone(),
                     kind: PayloadEntryKind::File,
                     size: metadata.len(),
+                    mode: metadata.permissions().mode() & 0o7777,
+                    rendered: None,
                 });
             } else {
                 return Err(format!(
@@ -1863,6 +3241,184 @@ fn collect_payload_entries(root: &Path) -> std::result::Result<Vec<PayloadEntry>
     Ok(entries)
 }
 
+/// Entries of the payload at `root` as they are staged: `.tmpl` files are
+/// rendered and lose the suffix.
+fn staged_payload_entries(
+    root: &Path,
+    template_vars: &BTreeMap<String, String>,
+) -> std::result::Result<Vec<PayloadEntry>, String> {
+    let mut entries = collect_payload_entries(root)?;
+    for entry in &mut entries {
+        if entry.kind != PayloadEntryKind::File {
+            continue;
+        }
+        let Some(rel_path) = entry.rel_path.strip_suffix(TEMPLATE_SUFFIX) else {
+            continue;
+        };
+        if rel_path.is_empty() || rel_path.ends_with('/') {
+            continue;
+        }
+        let rendered = render_template(&entry.source_path, template_vars)?;
+        entry.rel_path = rel_path.to_string();
+        entry.size = rendered.len() as u64;
+        entry.rendered = Some(rendered);
+    }
+    entries.sort_by(|a, b| a.rel_path.cmp(&b.rel_path));
+    if let Some(pair) = entries
+        .windows(2)
+        .find(|pair| pair[0].rel_path == pair[1].rel_path)
+    {
+        return Err(format!(
+            "Payload {} has both `{path}` and `{path}{TEMPLATE_SUFFIX}`; keep one of them.",
+            root.display(),
+            path = pair[0].rel_path
+        ));
+    }
+    Ok(entries)
+}
+
+/// Render the template at `path`. Every placeholder must name one of
+/// `template_vars`, so typos fail the plan instead of reaching the guest.
+fn render_template(
+    path: &Path,
+    template_vars: &BTreeMap<String, String>,
+) -> std::result::Result<String, String> {
+    let template = fs::read_to_string(path)
+        .map_err(|err| format!("Failed to read template {}: {err}", path.display()))?;
+    render_template_text(
+        &format!("Template {}", path.display()),
+        &template,
+        template_vars,
+    )
+}
+
+/// Render `template`, described as `origin` in errors, under the same rules
+/// as [`render_template`].
+pub(crate) fn render_template_text(
+    origin: &str,
+    template: &str,
+    template_vars: &BTreeMap<String, String>,
+) -> std::result::Result<String, String> {
+    let names = template_placeholders(template).map_err(|err| format!("{origin}: {err}."))?;
+    if let Some(unknown) = names
+        .iter()
+        .find(|name| !template_vars.contains_key(name.as_str()))
+    {
+        let known: Vec<&str> = template_vars
+            .keys()
+            .filter(|name| !name.starts_with("vms."))
+            .map(String::as_str)
+            .collect();
+        return Err(format!(
+            "{origin} references unknown variable `{{{{ {unknown} }}}}`; available: {}, and vms.<vm>.address, vms.<vm>.ssh_port and vms.<vm>.<tcp|udp>.<guest port> for each VM.",
+            known.join(", ")
+        ));
+    }
+    Ok(hooks::render(template, template_vars))
+}
+
+/// Entries of a payload directory keyed by relative path. The guest keeps a
+/// copy next to the synced payload so the next transfer only sends what
+/// changed.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct PayloadManifest {
+    entries: BTreeMap<String, ManifestEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum ManifestEntry {
+    Directory,
+    File {
+        sha256: String,
+        size: u64,
+        mode: u32,
+    },
+}
+
+/// What a transfer has to do to turn the guest's payload into the local one.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct PayloadDiff {
+    /// Guest paths to delete, parents before children.
+    remove: Vec<String>,
+    /// Directories to create.
+    create_dirs: Vec<String>,
+    /// Files to upload, with their mode.
+    upload: Vec<(String, u32)>,
+    bytes_uploaded: u64,
+    bytes_skipped: u64,
+}
+
+impl PayloadDiff {
+    fn has_changes(&self) -> bool {
+        !(self.remove.is_empty() && self.create_dirs.is_empty() && self.upload.is_empty())
+    }
+}
+
+impl PayloadManifest {
+    fn file_bytes(&self) -> u64 {
+        self.entries
+            .values()
+            .map(|entry| match entry {
+                ManifestEntry::File { size, .. } => *size,
+                ManifestEntry::Directory => 0,
+            })
+            .sum()
+    }
+
+    /// sha256 of every file, keyed by payload-relative path.
+    fn file_digests(&self) -> BTreeMap<String, String> {
+        self.entries
+            .iter()
+            .filter_map(|(path, entry)| match entry {
+                ManifestEntry::File { sha256, .. } => Some((path.clone(), sha256.clone())),
+                ManifestEntry::Directory => None,
+            })
+            .collect()
+    }
+
+    /// Changes needed to bring a guest holding `remote` in line with `self`.
+    fn diff(&self, remote: &PayloadManifest) -> PayloadDiff {
+        let mut diff = PayloadDiff::default();
+        for (path, entry) in &remote.entries {
+            let replaced = match (entry, self.entries.get(path)) {
+                (_, None) => true,
+                (ManifestEntry::Directory, Some(ManifestEntry::Directory)) => false,
+                (ManifestEntry::File { .. }, Some(ManifestEntry::File { .. })) => false,
+                _ => true,
+            };
+            // Removing a directory takes its children with it.
+            if replaced
+                && !diff
+                    .remove
+                    .iter()
+                    .any(|removed| path.starts_with(&format!("{removed}/")))
+            {
+                diff.remove.push(path.clone());
+            }
+        }
+        for (path, entry) in &self.entries {
+            let current = remote.entries.get(path);
+            match entry {
+                ManifestEntry::Directory => {
+                    if current != Some(&ManifestEntry::Directory) {
+                        diff.create_dirs.push(path.clone());
+                    }
+                }
+                ManifestEntry::File { size, mode, .. } => {
+                    if current == Some(entry) {
+                        diff.bytes_skipped += size;
+                    } else {
+                        diff.upload.push((path.clone(), *mode));
+                        diff.bytes_uploaded += size;
+                    }
+                }
+            }
+        }
+        diff
+    }
+}
+
 fn normalize_relative_path(path: &Path) -> String {
     path.components()
         .map(|component| component.as_os_str().to_string_lossy())
@@ -1870,67 +3426,104 @@ fn normalize_relative_path(path: &Path) -> String {
         .join("/")
 }
 
+#[allow(clippy::too_many_arguments)]
 fn compute_artifact_hash(
     script: &Path,
-    payload: Option<&Path>,
+    rendered_script: Option<&str>,
+    payload: Vec<PayloadEntry>,
+    payload_commit: Option<&str>,
     env: &HashMap<String, String>,
+    secrets: &SecretEnv,
     remote_dir: &str,
     verify: &BootstrapVerifyPlan,
-) -> std::result::Result<String, String> {
+) -> std::result::Result<(String, PayloadManifest), String> {
     let mut hasher = Sha256::new();
+    let mut manifest = PayloadManifest::default();
 
     hasher.update(b"script\0");
-    let mut file = File::open(script)
-        .map_err(|err| format!("Failed to read staged script {}: {err}", script.display()))?;
     let mut buffer = [0u8; 131_072];
-    loop {
-        let read = file
-            .read(&mut buffer)
-            .map_err(|err| format!("Failed to hash staged script {}: {err}", script.display()))?;
-        if read == 0 {
-            break;
+    if let Some(contents) = rendered_script {
+        hasher.update(contents.as_bytes());
+    } else {
+        let mut file = File::open(script)
+            .map_err(|err| format!("Failed to read staged script {}: {err}", script.display()))?;
+        loop {
+            let read = file.read(&mut buffer).map_err(|err| {
+                format!("Failed to hash staged script {}: {err}", script.display())
+            })?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
         }
-        hasher.update(&buffer[..read]);
     }
     hasher.update(b"\0");
 
     hasher.update(b"payload\0");
-    if let Some(payload_path) = payload {
-        let entries = collect_payload_entries(payload_path)?;
-        for entry in entries {
-            match entry.kind {
-                PayloadEntryKind::Directory => {
-                    hasher.update(b"dir\0");
-                    hasher.update(entry.rel_path.as_bytes());
-                    hasher.update(b"\0");
+    for entry in payload {
+        match entry.kind {
+            PayloadEntryKind::Directory => {
+                hasher.update(b"dir\0");
+                hasher.update(entry.rel_path.as_bytes());
+                hasher.update(b"\0");
+                manifest
+                    .entries
+                    .insert(entry.rel_path, ManifestEntry::Directory);
+            }
+            PayloadEntryKind::File => {
+                hasher.update(b"file\0");
+                hasher.update(entry.rel_path.as_bytes());
+                hasher.update(b"\0");
+                let mut file_hasher = Sha256::new();
+                if let Some(rendered) = &entry.rendered {
+                    hasher.update(rendered.as_bytes());
+                    file_hasher.update(rendered.as_bytes());
+                    manifest.entries.insert(
+                        entry.rel_path,
+                        ManifestEntry::File {
+                            sha256: hex::encode(file_hasher.finalize()),
+                            size: entry.size,
+                            mode: entry.mode,
+                        },
+                    );
+                    continue;
                 }
-                PayloadEntryKind::File => {
-                    hasher.update(b"file\0");
-                    hasher.update(entry.rel_path.as_bytes());
-                    hasher.update(b"\0");
-                    let mut file = File::open(&entry.source_path).map_err(|err| {
+                let mut file = File::open(&entry.source_path).map_err(|err| {
+                    format!(
+                        "Failed to read staged payload file {}: {err}",
+                        entry.source_path.display()
+                    )
+                })?;
+                loop {
+                    let read = file.read(&mut buffer).map_err(|err| {
                         format!(
-                            "Failed to read staged payload file {}: {err}",
+                            "Failed to hash staged payload file {}: {err}",
                             entry.source_path.display()
                         )
                     })?;
-                    loop {
-                        let read = file.read(&mut buffer).map_err(|err| {
-                            format!(
-                                "Failed to hash staged payload file {}: {err}",
-                                entry.source_path.display()
-                            )
-                        })?;
-                        if read == 0 {
-                            break;
-                        }
-                        hasher.update(&buffer[..read]);
+                    if read == 0 {
+                        break;
                     }
+                    hasher.update(&buffer[..read]);
+                    file_hasher.update(&buffer[..read]);
                 }
+                manifest.entries.insert(
+                    entry.rel_path,
+                    ManifestEntry::File {
+                        sha256: hex::encode(file_hasher.finalize()),
+                        size: entry.size,
+                        mode: entry.mode,
+                    },
+                );
             }
         }
     }
     hasher.update(b"\0");
+    if let Some(commit) = payload_commit {
+        hasher.update(b"payload-commit\0");
+        hasher.update(commit.as_bytes());
+        hasher.update(b"\0");
+    }
 
     hasher.update(b"env\0");
     let mut env_pairs: Vec<_> = env.iter().collect();
@@ -1943,6 +3536,18 @@ fn compute_artifact_hash(
     }
     hasher.update(b"\0");
 
+    // Only present when secrets are declared, so existing stamps stay valid.
+    if !secrets.is_empty() {
+        hasher.update(b"env_secrets\0");
+        for (key, digest) in secrets.digests() {
+            hasher.update(key.as_bytes());
+            hasher.update(b"\0");
+            hasher.update(digest.as_bytes());
+            hasher.update(b"\0");
+        }
+        hasher.update(b"\0");
+    }
+
     hasher.update(b"remote_dir\0");
     hasher.update(remote_dir.as_bytes());
     hasher.update(b"\0");
@@ -1962,13 +3567,32 @@ fn compute_artifact_hash(
     }
     hasher.update(b"\0");
 
-    Ok(hex::encode(hasher.finalize()))
+    Ok((hex::encode(hasher.finalize()), manifest))
 }
 
-fn ensure_default_ssh_options(options: &mut Vec<String>) {
-    for default in DEFAULT_SSH_OPTIONS {
-        if !options.iter().any(|opt| opt == default) {
-            options.push(default.to_string());
+/// Fill in castra's SSH defaults. With managed keys the workspace identity
+/// (unless the blueprint names one) and known_hosts are used; otherwise host
+/// key checking is disabled. Options the blueprint sets take precedence.
+fn apply_ssh_defaults(ssh: &mut SshConfig, managed_keys: Option<&ManagedSshKeys>) {
+    let defaults = match managed_keys {
+        Some(keys) => {
+            ssh.identity.get_or_insert_with(|| keys.identity.clone());
+            ssh.known_hosts = Some(keys.known_hosts.clone());
+            ssh_keys::ssh_options(keys)
+        }
+        None => DEFAULT_SSH_OPTIONS.map(str::to_string).to_vec(),
+    };
+    let option_name = |option: &str| {
+        option
+            .split_once('=')
+            .map_or(option, |(name, _)| name)
+            .trim()
+            .to_ascii_lowercase()
+    };
+    for default in defaults {
+        let name = option_name(&default);
+        if !ssh.options.iter().any(|opt| option_name(opt) == name) {
+            ssh.options.push(default);
         }
     }
 }
@@ -1981,33 +3605,30 @@ fn generate_run_id() -> String {
 }
 
 struct StepLog {
+    stage: Option<String>,
     kind: BootstrapStepKind,
     status: BootstrapStepStatus,
     duration_ms: u64,
     detail: Option<String>,
+    bytes_skipped: Option<u64>,
+    attempts: u32,
 }
 
 impl StepLog {
-    fn success(kind: BootstrapStepKind, duration: Duration, detail: Option<String>) -> Self {
-        Self {
-            kind,
-            status: BootstrapStepStatus::Success,
-            duration_ms: elapsed_ms(duration),
-            detail,
-        }
-    }
-
     fn from_result(
+        stage: Option<String>,
         kind: BootstrapStepKind,
-        status: BootstrapStepStatus,
-        duration: Duration,
-        detail: Option<String>,
+        outcome: &CommandOutcome,
+        attempts: u32,
     ) -> Self {
         Self {
+            stage,
             kind,
-            status,
-            duration_ms: elapsed_ms(duration),
-            detail,
+            status: outcome.status,
+            duration_ms: elapsed_ms(outcome.duration),
+            detail: outcome.detail.clone(),
+            bytes_skipped: outcome.bytes_skipped,
+            attempts,
         }
     }
 }
@@ -2016,6 +3637,8 @@ struct CommandOutcome {
     status: BootstrapStepStatus,
     duration: Duration,
     detail: Option<String>,
+    /// Payload bytes already present on the guest; set by transfers only.
+    bytes_skipped: Option<u64>,
 }
 
 struct ApplyOutcome {
@@ -2028,12 +3651,6 @@ enum ApplyCompletion {
     NoOp,
 }
 
-struct ProcessOutput {
-    command: String,
-    stdout: String,
-    stderr: String,
-}
-
 fn blueprint_remote_payload_summary(blueprint: &BootstrapBlueprint) -> Option<String> {
     blueprint
         .staged_payload
@@ -2041,13 +3658,71 @@ fn blueprint_remote_payload_summary(blueprint: &BootstrapBlueprint) -> Option<St
         .map(|_| format!("{} bytes", blueprint.payload_bytes))
 }
 
-fn execute_remote(blueprint: &BootstrapBlueprint) -> ApplyOutcome {
+/// Run `attempt` until its step succeeds, is skipped, or `policy.retries`
+/// retries have failed, waiting `policy.backoff` between tries. Returns the
+/// last attempt's result, timed from the first, and the number of attempts.
+fn retry_step<T>(
+    policy: &BootstrapStepPolicy,
+    who: &str,
+    kind: BootstrapStepKind,
+    emit_event: &mut dyn FnMut(Event),
+    mut attempt: impl FnMut(Option<Duration>) -> T,
+    command: impl Fn(&mut T) -> &mut CommandOutcome,
+) -> (T, u32) {
+    let start = Instant::now();
+    let total = policy.retries.saturating_add(1);
+    let mut attempts = 1;
+    loop {
+        let mut result = attempt(policy.timeout);
+        let outcome = command(&mut result);
+        if !matches!(outcome.status, BootstrapStepStatus::Failed) || attempts == total {
+            if attempts > 1 {
+                outcome.duration = start.elapsed();
+                let note = match outcome.status {
+                    BootstrapStepStatus::Failed => format!("Gave up after {attempts} attempts."),
+                    _ => format!("Succeeded on attempt {attempts}."),
+                };
+                outcome.detail = Some(match outcome.detail.take() {
+                    Some(detail) => format!("{detail} {note}"),
+                    None => note,
+                });
+            }
+            return (result, attempts);
+        }
+
+        let delay = policy.backoff(attempts);
+        emit_event(Event::Message {
+            severity: Severity::Warning,
+            text: format!(
+                "→ {who}: {} attempt {attempts}/{total} failed ({}); retrying in {:.1}s.",
+                format_step(kind),
+                outcome.detail.as_deref().unwrap_or("no detail"),
+                delay.as_secs_f64()
+            ),
+        });
+        std::thread::sleep(delay);
+        attempts += 1;
+    }
+}
+
+/// Time left before `deadline`, for transport calls sharing one step timeout.
+fn remaining(deadline: Option<Instant>) -> Option<Duration> {
+    deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()))
+}
+
+fn execute_remote(
+    transport: &dyn Transport,
+    blueprint: &BootstrapBlueprint,
+    timeout: Option<Duration>,
+) -> ApplyOutcome {
     let start = Instant::now();
     let run_id = generate_run_id();
     let apply_script = build_apply_command(blueprint, &run_id);
 
-    match run_ssh_shell(&blueprint.ssh, apply_script) {
+    let secrets = secret_exports(&blueprint.secrets);
+    match transport.exec_with_input(&blueprint.ssh, &apply_script, &secrets, timeout) {
         Ok(output) => {
+            let output = blueprint.secrets.redact_output(output);
             let mut completion = ApplyCompletion::Success;
             let mut detail_parts = vec![format!(
                 "Guest bootstrap script completed via `{}`.",
@@ -2065,6 +3740,7 @@ fn execute_remote(blueprint: &BootstrapBlueprint) -> ApplyOutcome {
                                 "Bootstrap script reported error: {}",
                                 reason.trim()
                             )),
+                            bytes_skipped: None,
                         },
                         completion: ApplyCompletion::Success,
                     };
@@ -2087,6 +3763,7 @@ fn execute_remote(blueprint: &BootstrapBlueprint) -> ApplyOutcome {
                     status: BootstrapStepStatus::Success,
                     duration: start.elapsed(),
                     detail: Some(detail_parts.join(" ")),
+                    bytes_skipped: None,
                 },
                 completion,
             }
@@ -2095,28 +3772,53 @@ fn execute_remote(blueprint: &BootstrapBlueprint) -> ApplyOutcome {
             command: CommandOutcome {
                 status: BootstrapStepStatus::Failed,
                 duration: start.elapsed(),
-                detail: Some(err),
+                detail: Some(blueprint.secrets.redact(&err.to_string())),
+                bytes_skipped: None,
             },
             completion: ApplyCompletion::Success,
         },
     }
 }
 
-fn verify_remote(blueprint: &BootstrapBlueprint) -> CommandOutcome {
+fn verify_remote(
+    transport: &dyn Transport,
+    blueprint: &BootstrapBlueprint,
+    timeout: Option<Duration>,
+) -> CommandOutcome {
     let start = Instant::now();
+    let deadline = timeout.map(|timeout| start + timeout);
     let mut detail_parts = Vec::new();
 
     if let Some(command) = blueprint.verify.command.as_ref() {
-        let verify_script = build_verify_command(blueprint, command);
-        match run_ssh_shell(&blueprint.ssh, verify_script) {
-            Ok(output) => {
-                append_command_detail(&mut detail_parts, "Verification command succeeded", &output)
-            }
+        let verify_script = build_verify_command(
+            &blueprint.vm,
+            &blueprint.remote_dir,
+            blueprint.remote_payload_dir.as_deref(),
+            &blueprint.env,
+            &blueprint.secrets,
+            command,
+        );
+        match transport.exec_with_input(
+            &blueprint.ssh,
+            &verify_script,
+            &secret_exports(&blueprint.secrets),
+            remaining(deadline),
+        ) {
+            Ok(output) => append_command_detail(
+                &mut detail_parts,
+                "Verification command succeeded",
+                &blueprint.secrets.redact_output(output),
+            ),
             Err(err) => {
                 return CommandOutcome {
                     status: BootstrapStepStatus::Failed,
                     duration: start.elapsed(),
-                    detail: Some(format!("Verification command failed: {err}")),
+                    detail: Some(
+                        blueprint
+                            .secrets
+                            .redact(&format!("Verification command failed: {err}")),
+                    ),
+                    bytes_skipped: None,
                 };
             }
         }
@@ -2129,7 +3831,7 @@ fn verify_remote(blueprint: &BootstrapBlueprint) -> CommandOutcome {
             path.clone()
         };
         let script = format!("test -e {}", shell_quote(&resolved_path));
-        match run_ssh_shell(&blueprint.ssh, script) {
+        match transport.exec(&blueprint.ssh, &script, remaining(deadline)) {
             Ok(output) => append_command_detail(
                 &mut detail_parts,
                 "Verification path check succeeded",
@@ -2143,6 +3845,7 @@ fn verify_remote(blueprint: &BootstrapBlueprint) -> CommandOutcome {
                         "Verification path check failed for {}: {err}",
                         resolved_path
                     )),
+                    bytes_skipped: None,
                 };
             }
         }
@@ -2159,25 +3862,10 @@ fn verify_remote(blueprint: &BootstrapBlueprint) -> CommandOutcome {
         status: BootstrapStepStatus::Success,
         duration: start.elapsed(),
         detail,
+        bytes_skipped: None,
     }
 }
 
-fn format_cli(program: &str, args: &[String]) -> String {
-    let mut parts = Vec::with_capacity(args.len() + 1);
-    parts.push(program.to_string());
-    for arg in args {
-        if arg.chars().all(|ch| {
-            ch.is_ascii_alphanumeric()
-                || matches!(ch, '-' | '_' | '/' | '.' | ':' | '=' | ',' | '@')
-        }) {
-            parts.push(arg.clone());
-        } else {
-            parts.push(shell_quote(arg));
-        }
-    }
-    parts.join(" ")
-}
-
 fn truncate_for_log(input: &str, limit: usize) -> String {
     let mut buffer = String::new();
     let mut chars = input.chars();
@@ -2202,7 +3890,7 @@ fn summarize_output(label: &str, contents: &str) -> Option<String> {
     }
 }
 
-fn append_command_detail(parts: &mut Vec<String>, label: &str, output: &ProcessOutput) {
+fn append_command_detail(parts: &mut Vec<String>, label: &str, output: &ExecOutput) {
     parts.push(format!("{label} via `{}`.", output.command));
     if let Some(snippet) = summarize_output("stdout", &output.stdout) {
         parts.push(snippet);
@@ -2212,135 +3900,204 @@ fn append_command_detail(parts: &mut Vec<String>, label: &str, output: &ProcessO
     }
 }
 
-fn check_connectivity(ssh: &SshConfig) -> CommandOutcome {
+fn check_connectivity(
+    transport: &dyn Transport,
+    ssh: &SshConfig,
+    timeout: Option<Duration>,
+) -> CommandOutcome {
     let start = Instant::now();
-    let probe_args = vec![String::from("true")];
-    let mut attempt_errors = Vec::new();
-
-    for attempt in 1..=CONNECTIVITY_ATTEMPTS {
-        match run_ssh_command_capture(ssh, &probe_args) {
-            Ok(output) => {
-                let mut detail_parts = vec![format!(
-                    "SSH connectivity confirmed ({}@{}:{}) via `{}`.",
-                    ssh.user, ssh.host, ssh.port, output.command
-                )];
-                if let Some(identity) = ssh.identity.as_ref() {
-                    detail_parts.push(format!("identity {}", identity.display()));
-                }
-                if !ssh.options.is_empty() {
-                    detail_parts.push(format!("options {}", ssh.options.join(", ")));
-                }
-                if attempt > 1 {
-                    detail_parts.push(format!(
-                        "Connectivity confirmed after {} attempts.",
-                        attempt
-                    ));
-                }
-                if let Some(snippet) = summarize_output("stdout", &output.stdout) {
-                    detail_parts.push(snippet);
-                }
-                if let Some(snippet) = summarize_output("stderr", &output.stderr) {
-                    detail_parts.push(snippet);
-                }
-                return CommandOutcome {
-                    status: BootstrapStepStatus::Success,
-                    duration: start.elapsed(),
-                    detail: Some(detail_parts.join(" ")),
-                };
+    match transport.connect(ssh, timeout) {
+        Ok(output) => {
+            let mut detail_parts = vec![format!(
+                "SSH connectivity confirmed ({}@{}:{}) via `{}`.",
+                ssh.user, ssh.host, ssh.port, output.command
+            )];
+            if let Some(identity) = ssh.identity.as_ref() {
+                detail_parts.push(format!("identity {}", identity.display()));
             }
-            Err(err) => {
-                attempt_errors.push(format!("Attempt {}: {}", attempt, err));
-                if attempt < CONNECTIVITY_ATTEMPTS {
-                    std::thread::sleep(Duration::from_millis(CONNECTIVITY_RETRY_DELAY_MS));
-                }
+            if !ssh.options.is_empty() {
+                detail_parts.push(format!("options {}", ssh.options.join(", ")));
+            }
+            if let Some(snippet) = summarize_output("stdout", &output.stdout) {
+                detail_parts.push(snippet);
+            }
+            if let Some(snippet) = summarize_output("stderr", &output.stderr) {
+                detail_parts.push(snippet);
+            }
+            CommandOutcome {
+                status: BootstrapStepStatus::Success,
+                duration: start.elapsed(),
+                detail: Some(detail_parts.join(" ")),
+                bytes_skipped: None,
             }
         }
+        Err(err) => CommandOutcome {
+            status: BootstrapStepStatus::Failed,
+            duration: start.elapsed(),
+            detail: Some(format!("Failed to establish SSH connectivity: {err}")),
+            bytes_skipped: None,
+        },
     }
+}
+
+fn transfer_artifacts(
+    transport: &dyn Transport,
+    blueprint: &BootstrapBlueprint,
+    timeout: Option<Duration>,
+) -> CommandOutcome {
+    let start = Instant::now();
+    let deadline = timeout.map(|timeout| start + timeout);
+    let ssh = &blueprint.ssh;
+    let failed = |err: String| CommandOutcome {
+        status: BootstrapStepStatus::Failed,
+        duration: start.elapsed(),
+        detail: Some(err),
+        bytes_skipped: None,
+    };
+
+    let mut detail_parts = Vec::new();
+    let payload = blueprint
+        .staged_payload
+        .as_ref()
+        .zip(blueprint.remote_payload_dir.as_ref());
 
-    let detail = if attempt_errors.is_empty() {
+    // The previous payload and its manifest survive so only changes are sent.
+    // The manifest is taken down until the transfer completes, so a failed
+    // transfer falls back to a full upload next time.
+    let prepare = if payload.is_some() {
         format!(
-            "Failed to establish SSH connectivity after {} attempts.",
-            CONNECTIVITY_ATTEMPTS
+            "set -e; mkdir -p {dir}; cd {dir}; \
+             find . -mindepth 1 -maxdepth 1 ! -name {payload} ! -name {manifest} -exec rm -rf {{}} +; \
+             if [ -f {manifest} ] && [ -d {payload} ]; then \
+             if command -v rsync >/dev/null 2>&1; then echo {marker}; fi; \
+             cat {manifest}; rm -f {manifest}; \
+             else rm -rf {payload} {manifest}; fi",
+            dir = shell_quote(&blueprint.remote_dir),
+            payload = shell_quote(STAGED_PAYLOAD_DIR),
+            manifest = shell_quote(PAYLOAD_MANIFEST_NAME),
+            marker = RSYNC_MARKER,
         )
     } else {
         format!(
-            "Failed to establish SSH connectivity after {} attempts. {}",
-            CONNECTIVITY_ATTEMPTS,
-            attempt_errors.join("; ")
+            "rm -rf {} && mkdir -p {}",
+            shell_quote(&blueprint.remote_dir),
+            shell_quote(&blueprint.remote_dir)
         )
     };
+    let prepared = match transport.exec(ssh, &prepare, remaining(deadline)) {
+        Ok(output) => output,
+        Err(err) => return failed(err.to_string()),
+    };
+    append_command_detail(&mut detail_parts, "Prepared remote directory", &prepared);
 
-    CommandOutcome {
-        status: BootstrapStepStatus::Failed,
-        duration: start.elapsed(),
-        detail: Some(detail),
-    }
-}
-
-fn transfer_artifacts(blueprint: &BootstrapBlueprint) -> CommandOutcome {
-    let start = Instant::now();
-
-    let mut detail_parts = Vec::new();
-
-    match prepare_remote_directory(blueprint) {
-        Ok(output) => {
-            append_command_detail(&mut detail_parts, "Prepared remote directory", &output)
-        }
-        Err(err) => {
-            return CommandOutcome {
-                status: BootstrapStepStatus::Failed,
-                duration: start.elapsed(),
-                detail: Some(err),
-            };
-        }
-    }
-
-    match run_scp_path(
-        &blueprint.ssh,
+    match transport.upload(
+        ssh,
         &blueprint.staged_script,
         &blueprint.remote_script,
-        false,
+        remaining(deadline),
     ) {
         Ok(output) => append_command_detail(&mut detail_parts, "Uploaded remote script", &output),
-        Err(err) => {
-            return CommandOutcome {
-                status: BootstrapStepStatus::Failed,
-                duration: start.elapsed(),
-                detail: Some(err),
-            };
-        }
+        Err(err) => return failed(err.to_string()),
     }
 
-    if let Some(staged_payload) = blueprint.staged_payload.as_ref() {
-        match run_scp_path(&blueprint.ssh, staged_payload, &blueprint.remote_dir, true) {
-            Ok(output) => {
-                append_command_detail(&mut detail_parts, "Uploaded payload directory", &output)
+    let mut bytes_skipped = None;
+    if let Some((staged_payload, remote_payload_dir)) = payload {
+        let guest_rsync = prepared.stdout.lines().any(|line| line == RSYNC_MARKER);
+        let previous = parse_remote_manifest(&prepared.stdout);
+        if previous.is_none() && !prepared.stdout.trim().is_empty() {
+            // Unreadable manifest: start over rather than trust the guest copy.
+            if let Err(err) = transport.exec(
+                ssh,
+                &format!("rm -rf {}", shell_quote(remote_payload_dir)),
+                remaining(deadline),
+            ) {
+                return failed(err.to_string());
             }
-            Err(err) => {
-                return CommandOutcome {
-                    status: BootstrapStepStatus::Failed,
-                    duration: start.elapsed(),
-                    detail: Some(err),
+        }
+
+        match previous {
+            None => {
+                match transport.upload(ssh, staged_payload, remote_payload_dir, remaining(deadline))
+                {
+                    Ok(output) => append_command_detail(
+                        &mut detail_parts,
+                        "Uploaded payload directory",
+                        &output,
+                    ),
+                    Err(err) => return failed(err.to_string()),
+                }
+            }
+            Some(previous) => {
+                let diff = blueprint.payload_manifest.diff(&previous);
+                let synced = if guest_rsync && diff.has_changes() {
+                    transport.sync_dir(ssh, staged_payload, remote_payload_dir, remaining(deadline))
+                } else {
+                    None
                 };
+                match synced {
+                    Some(Ok(output)) => append_command_detail(
+                        &mut detail_parts,
+                        "Synced payload with rsync",
+                        &output,
+                    ),
+                    fallback => {
+                        if let Some(Err(err)) = fallback {
+                            detail_parts.push(format!(
+                                "rsync failed ({err}); copying changed files instead."
+                            ));
+                        }
+                        if let Err(err) = apply_payload_diff(
+                            transport,
+                            ssh,
+                            staged_payload,
+                            remote_payload_dir,
+                            &diff,
+                            deadline,
+                        ) {
+                            return failed(err);
+                        }
+                    }
+                }
+                detail_parts.push(format!(
+                    "Payload: {} changed file(s) ({} bytes), {} removed, {} bytes unchanged.",
+                    diff.upload.len(),
+                    diff.bytes_uploaded,
+                    diff.remove.len(),
+                    diff.bytes_skipped
+                ));
+                bytes_skipped = Some(diff.bytes_skipped);
             }
         }
     } else {
         detail_parts.push("No payload directory configured; only script uploaded.".to_string());
     }
 
-    match run_ssh_shell(
-        &blueprint.ssh,
-        format!("chmod +x {}", shell_quote(&blueprint.remote_script)),
+    match transport.exec(
+        ssh,
+        &format!("chmod +x {}", shell_quote(&blueprint.remote_script)),
+        remaining(deadline),
     ) {
         Ok(output) => append_command_detail(&mut detail_parts, "Marked script executable", &output),
-        Err(err) => {
-            return CommandOutcome {
-                status: BootstrapStepStatus::Failed,
-                duration: start.elapsed(),
-                detail: Some(err),
-            };
+        Err(err) => return failed(err.to_string()),
+    }
+
+    if let Some((staged_payload, _)) = payload {
+        let local = staged_payload.with_file_name(PAYLOAD_MANIFEST_NAME);
+        let written = serde_json::to_vec(&blueprint.payload_manifest)
+            .map_err(|err| err.to_string())
+            .and_then(|json| fs::write(&local, json).map_err(|err| err.to_string()));
+        if let Err(err) = written {
+            return failed(format!(
+                "Failed to write payload manifest {}: {err}",
+                local.display()
+            ));
+        }
+        let remote = format!("{}/{}", blueprint.remote_dir, PAYLOAD_MANIFEST_NAME);
+        if let Err(err) = transport.upload(ssh, &local, &remote, remaining(deadline)) {
+            return failed(err.to_string());
         }
     }
+
     detail_parts.push(match blueprint_remote_payload_summary(blueprint) {
         Some(bytes) => format!("Uploaded assets to {} ({}).", blueprint.remote_dir, bytes),
         None => format!("Uploaded script to {}.", blueprint.remote_dir),
@@ -2350,18 +4107,82 @@ fn transfer_artifacts(blueprint: &BootstrapBlueprint) -> CommandOutcome {
         status: BootstrapStepStatus::Success,
         duration: start.elapsed(),
         detail: Some(detail_parts.join(" ")),
+        bytes_skipped,
     }
 }
 
-fn prepare_remote_directory(
-    blueprint: &BootstrapBlueprint,
-) -> std::result::Result<ProcessOutput, String> {
-    let script = format!(
-        "rm -rf {} && mkdir -p {}",
-        shell_quote(&blueprint.remote_dir),
-        shell_quote(&blueprint.remote_dir)
-    );
-    run_ssh_shell(&blueprint.ssh, script)
+/// The manifest the transfer preparation printed, if the guest had one.
+fn parse_remote_manifest(stdout: &str) -> Option<PayloadManifest> {
+    let json: String = stdout
+        .lines()
+        .filter(|line| *line != RSYNC_MARKER)
+        .collect::<Vec<_>>()
+        .join("\n");
+    if json.trim().is_empty() {
+        return None;
+    }
+    serde_json::from_str(&json).ok()
+}
+
+/// Remove, create and upload what `diff` lists under `remote_payload_dir`.
+fn apply_payload_diff(
+    transport: &dyn Transport,
+    ssh: &SshConfig,
+    staged_payload: &Path,
+    remote_payload_dir: &str,
+    diff: &PayloadDiff,
+    deadline: Option<Instant>,
+) -> std::result::Result<(), String> {
+    let quoted = |paths: &[String]| {
+        paths
+            .iter()
+            .map(|path| shell_quote(path))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let mut commands = Vec::new();
+    for batch in diff.remove.chunks(REMOTE_BATCH) {
+        commands.push(format!("rm -rf -- {}", quoted(batch)));
+    }
+    for batch in diff.create_dirs.chunks(REMOTE_BATCH) {
+        commands.push(format!("mkdir -p -- {}", quoted(batch)));
+    }
+    let run = |commands: &[String]| -> std::result::Result<(), String> {
+        if commands.is_empty() {
+            return Ok(());
+        }
+        let script = format!(
+            "cd {} && {}",
+            shell_quote(remote_payload_dir),
+            commands.join(" && ")
+        );
+        transport
+            .exec(ssh, &script, remaining(deadline))
+            .map(|_| ())
+            .map_err(|err| err.to_string())
+    };
+    run(&commands)?;
+
+    let mut by_mode: BTreeMap<u32, Vec<String>> = BTreeMap::new();
+    for (path, mode) in &diff.upload {
+        transport
+            .upload(
+                ssh,
+                &staged_payload.join(path),
+                &format!("{remote_payload_dir}/{path}"),
+                remaining(deadline),
+            )
+            .map_err(|err| err.to_string())?;
+        by_mode.entry(*mode).or_default().push(path.clone());
+    }
+    // Overwriting an existing guest file keeps its old mode.
+    let mut chmods = Vec::new();
+    for (mode, paths) in by_mode {
+        for batch in paths.chunks(REMOTE_BATCH) {
+            chmods.push(format!("chmod {mode:o} -- {}", quoted(batch)));
+        }
+    }
+    run(&chmods)
 }
 
 fn build_apply_command(blueprint: &BootstrapBlueprint, run_id: &str) -> String {
@@ -2371,6 +4192,9 @@ fn build_apply_command(blueprint: &BootstrapBlueprint, run_id: &str) -> String {
     script.push_str(&format!("cd {};", shell_quote(&blueprint.remote_dir)));
     script.push_str(&format!("export CASTRA_VM={};", shell_quote(&blueprint.vm)));
     script.push_str(&format!("export CASTRA_RUN_ID={};", shell_quote(run_id)));
+    if let Some(stage) = &blueprint.stage {
+        script.push_str(&format!("export CASTRA_STAGE={};", shell_quote(stage)));
+    }
     let payload_dir = blueprint
         .remote_payload_dir
         .as_deref()
@@ -2384,124 +4208,52 @@ fn build_apply_command(blueprint: &BootstrapBlueprint, run_id: &str) -> String {
     for (key, value) in env_entries {
         script.push_str(&format!("export {}={};", key, shell_quote(value)));
     }
+    if !blueprint.secrets.is_empty() {
+        script.push_str(READ_SECRET_EXPORTS);
+    }
     script.push_str(&format!("./{}", STAGED_SCRIPT_NAME));
     script
 }
 
-fn build_verify_command(blueprint: &BootstrapBlueprint, command: &str) -> String {
+fn build_verify_command(
+    vm: &str,
+    remote_dir: &str,
+    remote_payload_dir: Option<&str>,
+    env: &HashMap<String, String>,
+    secrets: &SecretEnv,
+    command: &str,
+) -> String {
     let mut script = String::new();
     script.push_str("set -euo pipefail;");
-    script.push_str(&format!("cd {};", shell_quote(&blueprint.remote_dir)));
-    script.push_str(&format!("export CASTRA_VM={};", shell_quote(&blueprint.vm)));
-    let payload_dir = blueprint
-        .remote_payload_dir
-        .as_deref()
-        .unwrap_or(&blueprint.remote_dir);
+    script.push_str(&format!("cd {};", shell_quote(remote_dir)));
+    script.push_str(&format!("export CASTRA_VM={};", shell_quote(vm)));
+    let payload_dir = remote_payload_dir.unwrap_or(remote_dir);
     script.push_str(&format!(
         "export CASTRA_PAYLOAD_DIR={};",
         shell_quote(payload_dir)
     ));
-    let mut env_entries: Vec<_> = blueprint.env.iter().collect();
+    let mut env_entries: Vec<_> = env.iter().collect();
     env_entries.sort_by(|a, b| a.0.cmp(b.0));
     for (key, value) in env_entries {
         script.push_str(&format!("export {}={};", key, shell_quote(value)));
     }
+    if !secrets.is_empty() {
+        script.push_str(READ_SECRET_EXPORTS);
+    }
     script.push_str(command);
     script
 }
 
-fn run_ssh_shell(ssh: &SshConfig, script: String) -> std::result::Result<ProcessOutput, String> {
-    run_ssh_command_capture(
-        ssh,
-        &[
-            String::from("sh"),
-            String::from("-lc"),
-            format!("\"{}\"", script),
-        ],
-    )
-}
-
-fn run_ssh_command_capture(
-    ssh: &SshConfig,
-    remote_args: &[String],
-) -> std::result::Result<ProcessOutput, String> {
-    let mut args = Vec::new();
-    if let Some(identity) = ssh.identity.as_ref() {
-        args.push(String::from("-i"));
-        args.push(identity.display().to_string());
-    }
-    for option in &ssh.options {
-        args.push(String::from("-o"));
-        args.push(option.clone());
-    }
-    args.push(String::from("-p"));
-    args.push(ssh.port.to_string());
-    args.push(format!("{}@{}", ssh.user, ssh.host));
-    args.extend(remote_args.iter().cloned());
-
-    run_command("ssh", &args)
-}
-
-fn run_scp_path(
-    ssh: &SshConfig,
-    local: &Path,
-    remote_destination: &str,
-    recursive: bool,
-) -> std::result::Result<ProcessOutput, String> {
-    let mut args = Vec::new();
-    if let Some(identity) = ssh.identity.as_ref() {
-        args.push(String::from("-i"));
-        args.push(identity.display().to_string());
-    }
-    for option in &ssh.options {
-        args.push(String::from("-o"));
-        args.push(option.clone());
-    }
-    args.push(String::from("-P"));
-    args.push(ssh.port.to_string());
-    if recursive {
-        args.push(String::from("-r"));
-    }
-    args.push(local.display().to_string());
-    args.push(format!("{}@{}:{}", ssh.user, ssh.host, remote_destination,));
-
-    run_command("scp", &args)
-}
-
-fn run_command(program: &str, args: &[String]) -> std::result::Result<ProcessOutput, String> {
-    let command_repr = format_cli(program, args);
-    let mut command = Command::new(program);
-    command.args(args);
-    command.stdout(Stdio::piped());
-    command.stderr(Stdio::piped());
-
-    let output = command.output().map_err(|err| match err.kind() {
-        io::ErrorKind::NotFound => {
-            format!("Command `{program}` not found in PATH while executing bootstrap step.")
-        }
-        _ => format!("Failed to execute `{program}`: {err}"),
-    })?;
-
-    if output.status.success() {
-        Ok(ProcessOutput {
-            command: command_repr,
-            stdout: String::from_utf8_lossy(&output.stdout).to_string(),
-            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
-        })
-    } else {
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        Err(format!(
-            "`{}` exited with code {:?}. stdout: {} stderr: {}",
-            command_repr,
-            output.status.code(),
-            stdout.trim(),
-            stderr.trim()
-        ))
-    }
+/// `export` statements for `secrets`, fed to [`READ_SECRET_EXPORTS`] over
+/// standard input.
+fn secret_exports(secrets: &SecretEnv) -> String {
+    secrets
+        .values()
+        .map(|(key, value)| format!("export {}={};\n", key, shell_quote(value)))
+        .collect()
 }
 
-fn shell_quote(input: &str) -> String {
+pub(crate) fn shell_quote(input: &str) -> String {
     let mut result = String::from("'");
     for ch in input.chars() {
         if ch == '\'' {
@@ -2518,75 +4270,69 @@ fn elapsed_ms(duration: Duration) -> u64 {
     duration.as_millis().min(u128::from(u64::MAX)) as u64
 }
 
-#[derive(Serialize)]
-struct BootstrapRunLog {
-    vm: String,
-    artifact_hash: String,
-    base_hash: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    stamp: Option<String>,
-    status: String,
-    duration_ms: u64,
-    steps: Vec<StepRecord>,
-    script_source: String,
-    staged_script: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    payload_source: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    staged_payload: Option<String>,
-    payload_bytes: u64,
-    remote_dir: String,
-    ssh_user: String,
-    ssh_host: String,
-    ssh_port: u16,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    ssh_identity: Option<String>,
-    env: BTreeMap<String, String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    metadata_path: Option<String>,
-}
-
-#[derive(Serialize)]
-struct StepRecord {
-    step: String,
-    status: String,
-    duration_ms: u64,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    detail: Option<String>,
-}
-
 impl BootstrapRunLog {
-    fn success(
-        vm: &str,
-        blueprint: &BootstrapBlueprint,
-        base_hash: &str,
-        steps: Vec<StepLog>,
-        duration_ms: u64,
-        status: BootstrapStatus,
-    ) -> Self {
-        let status_str = match status {
-            BootstrapStatus::Success => "success",
-            BootstrapStatus::NoOp => "noop",
-        };
+    fn new(run: &RunRecorder<'_>, status: &str, error: Option<String>) -> Self {
+        let blueprint = &run.blueprints[0];
+        let mut records: Vec<StepRecord> = run.steps.iter().map(StepRecord::from).collect();
+        if let Some(error) = error {
+            records.push(StepRecord {
+                stage: None,
+                step: "error".to_string(),
+                status: "failed".to_string(),
+                duration_ms: 0,
+                detail: Some(error),
+                bytes_skipped: None,
+                attempts: None,
+            });
+        }
+        let stages = run
+            .blueprints
+            .iter()
+            .zip(&run.stage_status)
+            .filter_map(|(blueprint, status)| {
+                Some(StageRecord {
+                    name: blueprint.stage.clone()?,
+                    run_if: blueprint.run_if.as_str().to_string(),
+                    status: status.as_str().to_string(),
+                    artifact_hash: blueprint.artifact_hash.clone(),
+                    script_source: blueprint.script_source.display().to_string(),
+                    script_sha256: Some(blueprint.script_sha256.clone()),
+                    payload_source: blueprint
+                        .payload_source
+                        .as_ref()
+                        .map(|path| path.display().to_string()),
+                    payload_commit: blueprint.payload_commit.clone(),
+                    payload_bytes: blueprint.payload_bytes,
+                    payload_files: blueprint.payload_manifest.file_digests(),
+                    remote_dir: blueprint.remote_dir.clone(),
+                    timeout_secs: blueprint.apply_timeout.map(|timeout| timeout.as_secs()),
+                })
+            })
+            .collect();
+
         Self {
-            vm: vm.to_string(),
-            artifact_hash: blueprint.artifact_hash.clone(),
-            base_hash: base_hash.to_string(),
+            vm: run.vm.name.clone(),
+            artifact_hash: run.artifact_hash.to_string(),
+            base_hash: run.base_hash.to_string(),
             stamp: None,
-            status: status_str.to_string(),
-            duration_ms,
-            steps: steps.into_iter().map(StepRecord::from).collect(),
+            status: status.to_string(),
+            duration_ms: elapsed_ms(run.start.elapsed()),
+            steps: records,
+            stages,
             script_source: blueprint.script_source.display().to_string(),
             staged_script: blueprint.staged_script.display().to_string(),
+            script_sha256: Some(blueprint.script_sha256.clone()),
             payload_source: blueprint
                 .payload_source
                 .as_ref()
                 .map(|path| path.display().to_string()),
+            payload_commit: blueprint.payload_commit.clone(),
             staged_payload: blueprint
                 .staged_payload
                 .as_ref()
                 .map(|path| path.display().to_string()),
             payload_bytes: blueprint.payload_bytes,
+            payload_files: blueprint.payload_manifest.file_digests(),
             remote_dir: blueprint.remote_dir.clone(),
             ssh_user: blueprint.ssh.user.clone(),
             ssh_host: blueprint.ssh.host.clone(),
@@ -2601,77 +4347,30 @@ impl BootstrapRunLog {
                 .iter()
                 .map(|(k, v)| (k.clone(), v.clone()))
                 .collect(),
+            env_secrets: run
+                .blueprints
+                .iter()
+                .flat_map(|blueprint| blueprint.secrets.digests())
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
             metadata_path: blueprint
                 .metadata_path
                 .as_ref()
                 .map(|path| path.display().to_string()),
         }
     }
+}
 
-    fn failure(
-        vm: &str,
-        blueprint: &BootstrapBlueprint,
-        base_hash: &str,
-        steps: Vec<StepLog>,
-        duration_ms: u64,
-        error: String,
-    ) -> Self {
-        let mut records: Vec<StepRecord> = steps.into_iter().map(StepRecord::from).collect();
-        records.push(StepRecord {
-            step: "error".to_string(),
-            status: "failed".to_string(),
-            duration_ms: 0,
-            detail: Some(error),
-        });
-
-        Self {
-            vm: vm.to_string(),
-            artifact_hash: blueprint.artifact_hash.clone(),
-            base_hash: base_hash.to_string(),
-            stamp: None,
-            status: "failed".to_string(),
-            duration_ms,
-            steps: records,
-            script_source: blueprint.script_source.display().to_string(),
-            staged_script: blueprint.staged_script.display().to_string(),
-            payload_source: blueprint
-                .payload_source
-                .as_ref()
-                .map(|path| path.display().to_string()),
-            staged_payload: blueprint
-                .staged_payload
-                .as_ref()
-                .map(|path| path.display().to_string()),
-            payload_bytes: blueprint.payload_bytes,
-            remote_dir: blueprint.remote_dir.clone(),
-            ssh_user: blueprint.ssh.user.clone(),
-            ssh_host: blueprint.ssh.host.clone(),
-            ssh_port: blueprint.ssh.port,
-            ssh_identity: blueprint
-                .ssh
-                .identity
-                .as_ref()
-                .map(|path| path.display().to_string()),
-            env: blueprint
-                .env
-                .iter()
-                .map(|(k, v)| (k.clone(), v.clone()))
-                .collect(),
-            metadata_path: blueprint
-                .metadata_path
-                .as_ref()
-                .map(|path| path.display().to_string()),
-        }
-    }
-}
-
-impl From<StepLog> for StepRecord {
-    fn from(log: StepLog) -> Self {
+impl From<&StepLog> for StepRecord {
+    fn from(log: &StepLog) -> Self {
         Self {
+            stage: log.stage.clone(),
             step: format_step(log.kind),
             status: format_step_status(log.status),
             duration_ms: log.duration_ms,
-            detail: log.detail,
+            detail: log.detail.clone(),
+            bytes_skipped: log.bytes_skipped,
+            attempts: (log.attempts > 1).then_some(log.attempts),
         }
     }
 }
@@ -2696,36 +4395,22 @@ fn format_step_status(status: BootstrapStepStatus) -> String {
     .to_string()
 }
 
-fn write_run_log(dir: &Path, log: &BootstrapRunLog) -> io::Result<PathBuf> {
-    fs::create_dir_all(dir)?;
-    let timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_else(|_| Duration::from_secs(0))
-        .as_secs();
-    let filename = format!("{}-{}.json", log.vm, timestamp);
-    let path = dir.join(filename);
-    let payload = serde_json::to_vec_pretty(log).map_err(|err| {
-        io::Error::new(
-            io::ErrorKind::Other,
-            format!("Failed to serialize bootstrap log: {err}"),
-        )
-    })?;
-    fs::write(&path, payload)?;
-    Ok(path)
-}
-
 #[cfg(all(test, unix))]
 mod tests {
     use super::*;
     use crate::config::BaseImageSource;
     use crate::config::{
-        BootstrapConfig, BootstrapMode, LifecycleConfig, MemorySpec, PortForward, PortProtocol,
-        ProjectConfig, VmBootstrapConfig, VmDefinition, Workflows,
+        BootstrapConfig, BootstrapMode, BootstrapStage, CollectRule, ImagesConfig, LifecycleConfig,
+        PortForward, PortProtocol, ProjectConfig, ProvisionConfig, VmBootstrapConfig, VmDefinition,
+        Workflows,
     };
     use crate::core::diagnostics::{Diagnostic, Severity};
     use crate::core::events::{BootstrapPlanAction, BootstrapStatus, BootstrapTrigger, Event};
+    use crate::core::image_store::ImageStore;
     use crate::core::outcome::BootstrapRunStatus;
-    use crate::core::runtime::{AssetPreparation, ResolvedVmAssets, RuntimeContext};
+    use crate::core::runtime::{ImageSources, RuntimeContext};
+    use crate::core::signature::ImageTrust;
+    use crate::core::transport::{OutputStream, TransportError};
     use serde_json::json;
     use std::collections::HashMap;
     use std::env;
@@ -2845,27 +4530,20 @@ mod tests {
             qemu_img: None,
             accelerators: Vec::new(),
             launch_mode: VmLaunchMode::Daemonize,
+            image_store: ImageStore::new(state_root.join("image-store")),
+            image_sources: ImageSources::default(),
+            image_trust: ImageTrust::default(),
         };
 
         let vm = VmDefinition {
-            name: "devbox".to_string(),
-            role_name: "devbox".to_string(),
-            replica_index: 0,
-            description: None,
             base_image: BaseImageSource::from_explicit(base_image_path),
             overlay: state_root.join("overlays/devbox.qcow2"),
-            cpus: 2,
-            memory: MemorySpec::new("2048 MiB", Some(2 * 1024 * 1024 * 1024)),
-            port_forwards: Vec::new(),
             bootstrap: VmBootstrapConfig {
-                mode: BootstrapMode::Skip,
                 script: Some(PathBuf::from("/tmp/bootstrap-script")),
-                payload: Some(PathBuf::from("/tmp/bootstrap-payload")),
-                handshake_timeout_secs: 30,
-                remote_dir: PathBuf::from(DEFAULT_REMOTE_BASE),
-                env: HashMap::new(),
-                verify: None,
+                payload: Some(PayloadSource::Dir(PathBuf::from("/tmp/bootstrap-payload"))),
+                ..VmBootstrapConfig::default()
             },
+            ..VmDefinition::for_test("devbox")
         };
 
         let project = ProjectConfig {
@@ -2876,28 +4554,16 @@ mod tests {
             features: ProjectFeatures::default(),
             vms: vec![vm],
             state_root: state_root.clone(),
-            workflows: Workflows { init: Vec::new() },
+            workflows: Workflows::default(),
             lifecycle: LifecycleConfig::default(),
             bootstrap: BootstrapConfig::default(),
+            images: ImagesConfig::default(),
             warnings: Vec::new(),
         };
 
-        let preparations = vec![AssetPreparation {
-            assets: ResolvedVmAssets { boot: None },
-            overlay_created: false,
-            overlay_reclaimed_bytes: None,
-            events: Vec::new(),
-        }];
-
         let mut reporter = RecordingReporter::default();
         let mut diagnostics: Vec<Diagnostic> = Vec::new();
-        let outcomes = run_all(
-            &project,
-            &context,
-            &preparations,
-            &mut reporter,
-            &mut diagnostics,
-        )?;
+        let outcomes = run_selected(&project, &context, &[0], &mut reporter, &mut diagnostics)?;
 
         assert_eq!(outcomes.len(), 1);
         let outcome = &outcomes[0];
@@ -2939,14 +4605,8 @@ mod tests {
         fs::write(&base_image_path, b"base-image")?;
 
         let vm = VmDefinition {
-            name: "devbox".to_string(),
-            role_name: "devbox".to_string(),
-            replica_index: 0,
-            description: None,
             base_image: BaseImageSource::from_explicit(base_image_path),
             overlay: workspace.join("state/overlays/devbox.qcow2"),
-            cpus: 2,
-            memory: MemorySpec::new("2048 MiB", Some(2 * 1024 * 1024 * 1024)),
             port_forwards: vec![PortForward {
                 host: 2222,
                 guest: 22,
@@ -2955,12 +4615,11 @@ mod tests {
             bootstrap: VmBootstrapConfig {
                 mode: BootstrapMode::Auto,
                 script: Some(script_source.clone()),
-                payload: Some(payload_source.clone()),
+                payload: Some(PayloadSource::Dir(payload_source.clone())),
                 handshake_timeout_secs: 45,
-                remote_dir: PathBuf::from(DEFAULT_REMOTE_BASE),
-                env: HashMap::new(),
-                verify: None,
+                ..VmBootstrapConfig::default()
             },
+            ..VmDefinition::for_test("devbox")
         };
 
         let project = ProjectConfig {
@@ -2971,9 +4630,10 @@ mod tests {
             features: ProjectFeatures::default(),
             vms: vec![vm],
             state_root: workspace.join("state"),
-            workflows: Workflows { init: Vec::new() },
+            workflows: Workflows::default(),
             lifecycle: LifecycleConfig::default(),
             bootstrap: BootstrapConfig::default(),
+            images: ImagesConfig::default(),
             warnings: Vec::new(),
         };
 
@@ -3021,24 +4681,13 @@ mod tests {
         fs::write(&base_image_path, b"base-image")?;
 
         let vm = VmDefinition {
-            name: "devbox".to_string(),
-            role_name: "devbox".to_string(),
-            replica_index: 0,
-            description: None,
             base_image: BaseImageSource::from_explicit(base_image_path),
             overlay: workspace.join("state/overlays/devbox.qcow2"),
-            cpus: 2,
-            memory: MemorySpec::new("2048 MiB", Some(2 * 1024 * 1024 * 1024)),
-            port_forwards: Vec::new(),
             bootstrap: VmBootstrapConfig {
                 mode: BootstrapMode::Auto,
-                script: None,
-                payload: None,
-                handshake_timeout_secs: 30,
-                remote_dir: PathBuf::from(DEFAULT_REMOTE_BASE),
-                env: HashMap::new(),
-                verify: None,
+                ..VmBootstrapConfig::default()
             },
+            ..VmDefinition::for_test("devbox")
         };
 
         let project = ProjectConfig {
@@ -3049,9 +4698,10 @@ mod tests {
             features: ProjectFeatures::default(),
             vms: vec![vm],
             state_root: workspace.join("state"),
-            workflows: Workflows { init: Vec::new() },
+            workflows: Workflows::default(),
             lifecycle: LifecycleConfig::default(),
             bootstrap: BootstrapConfig::default(),
+            images: ImagesConfig::default(),
             warnings: Vec::new(),
         };
 
@@ -3075,6 +4725,72 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn bootstrap_plan_is_up_to_date_only_for_matching_durable_stamp()
+    -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = TempDir::new()?;
+        let workspace = temp_dir.path();
+        let state_root = workspace.join("state");
+        let script_source = workspace.join("bootstrap/devbox/run.sh");
+        fs::create_dir_all(script_source.parent().unwrap())?;
+        fs::write(&script_source, b"#!/bin/sh\nexit 0\n")?;
+        let base_image_path = workspace.join("base.img");
+        fs::write(&base_image_path, b"base-image")?;
+
+        let vm = VmDefinition {
+            base_image: BaseImageSource::from_explicit(base_image_path.clone()),
+            overlay: state_root.join("overlays/devbox.qcow2"),
+            bootstrap: VmBootstrapConfig {
+                mode: BootstrapMode::Auto,
+                script: Some(script_source),
+                env: HashMap::from([("ROLE".to_string(), "api".to_string())]),
+                ..VmBootstrapConfig::default()
+            },
+            ..VmDefinition::for_test("devbox")
+        };
+        let base_hash = compute_file_sha256(&base_image_path)?;
+
+        assert_eq!(
+            plan_for_vm(&state_root, &vm, None).action,
+            BootstrapPlanAction::WouldRun
+        );
+
+        let stamp = record_durable_stamp(&state_root, &vm, &base_hash)?;
+        assert_eq!(load_stamp(&state_root, "devbox"), Some(stamp.clone()));
+        let plan = plan_for_vm(&state_root, &vm, None);
+        assert_eq!(plan.action, BootstrapPlanAction::UpToDate);
+
+        let mut changed_env = vm.clone();
+        changed_env
+            .bootstrap
+            .env
+            .insert("ROLE".to_string(), "web".to_string());
+        assert_eq!(
+            plan_for_vm(&state_root, &changed_env, None).action,
+            BootstrapPlanAction::WouldRun
+        );
+
+        write_stamp(
+            &state_root,
+            &BootstrapStamp {
+                durable: false,
+                ..stamp
+            },
+        )?;
+        let plan = plan_for_vm(&state_root, &vm, None);
+        assert_eq!(plan.action, BootstrapPlanAction::WouldRun);
+        assert!(plan.reason.contains("discarded overlay"), "{}", plan.reason);
+
+        record_durable_stamp(&state_root, &vm, &base_hash)?;
+        fs::write(&base_image_path, b"new-base-image")?;
+        assert_eq!(
+            plan_for_vm(&state_root, &vm, None).action,
+            BootstrapPlanAction::WouldRun
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn bootstrap_pipeline_runs_successfully() -> std::result::Result<(), Box<dyn std::error::Error>>
     {
@@ -3188,17 +4904,14 @@ mod tests {
             qemu_img: None,
             accelerators: Vec::new(),
             launch_mode: VmLaunchMode::Daemonize,
+            image_store: ImageStore::new(state_root.join("image-store")),
+            image_sources: ImageSources::default(),
+            image_trust: ImageTrust::default(),
         };
 
         let vm = VmDefinition {
-            name: "devbox".to_string(),
-            role_name: "devbox".to_string(),
-            replica_index: 0,
-            description: None,
             base_image: BaseImageSource::from_explicit(base_image_path),
             overlay: state_root.join("overlays/devbox.qcow2"),
-            cpus: 2,
-            memory: MemorySpec::new("2048 MiB", Some(2 * 1024 * 1024 * 1024)),
             port_forwards: vec![PortForward {
                 host: 2222,
                 guest: 22,
@@ -3207,12 +4920,10 @@ mod tests {
             bootstrap: VmBootstrapConfig {
                 mode: BootstrapMode::Auto,
                 script: Some(script_source.clone()),
-                payload: Some(payload_source.clone()),
-                handshake_timeout_secs: 30,
-                remote_dir: PathBuf::from(DEFAULT_REMOTE_BASE),
-                env: HashMap::new(),
-                verify: None,
+                payload: Some(PayloadSource::Dir(payload_source.clone())),
+                ..VmBootstrapConfig::default()
             },
+            ..VmDefinition::for_test("devbox")
         };
 
         let project = ProjectConfig {
@@ -3223,28 +4934,16 @@ mod tests {
             features: ProjectFeatures::default(),
             vms: vec![vm],
             state_root: state_root.clone(),
-            workflows: Workflows { init: Vec::new() },
+            workflows: Workflows::default(),
             lifecycle: LifecycleConfig::default(),
             bootstrap: BootstrapConfig::default(),
+            images: ImagesConfig::default(),
             warnings: Vec::new(),
         };
 
-        let preparations = vec![AssetPreparation {
-            assets: ResolvedVmAssets { boot: None },
-            overlay_created: false,
-            overlay_reclaimed_bytes: None,
-            events: Vec::new(),
-        }];
-
         let mut reporter = RecordingReporter::default();
         let mut diagnostics: Vec<Diagnostic> = Vec::new();
-        let outcomes = run_all(
-            &project,
-            &context,
-            &preparations,
-            &mut reporter,
-            &mut diagnostics,
-        )?;
+        let outcomes = run_selected(&project, &context, &[0], &mut reporter, &mut diagnostics)?;
 
         assert_eq!(outcomes.len(), 1);
         let outcome = &outcomes[0];
@@ -3255,6 +4954,9 @@ mod tests {
             assert_eq!(outcome.status, BootstrapRunStatus::Success);
         }
         assert!(outcome.log_path.is_some());
+        let stamp = load_stamp(&state_root, "devbox").expect("stamp recorded");
+        assert!(!stamp.durable);
+        assert_eq!(outcome.stamp.as_deref(), Some(stamp.id().as_str()));
 
         let events = reporter.take();
         let completed = events.iter().find_map(|event| match event {
@@ -3273,4 +4975,942 @@ mod tests {
         }
         Ok(events)
     }
+
+    #[test]
+    fn ssh_defaults_use_managed_keys_without_overriding_blueprint() {
+        let keys = ManagedSshKeys::for_state_root(Path::new("/state"));
+        let mut ssh = SshConfig {
+            user: DEFAULT_SSH_USER.to_string(),
+            host: DEFAULT_SSH_HOST.to_string(),
+            port: 2222,
+            identity: None,
+            options: vec!["stricthostkeychecking=yes".to_string()],
+            known_hosts: None,
+        };
+        apply_ssh_defaults(&mut ssh, Some(&keys));
+        assert_eq!(ssh.identity.as_deref(), Some(keys.identity.as_path()));
+        assert_eq!(ssh.known_hosts.as_deref(), Some(keys.known_hosts.as_path()));
+        assert_eq!(
+            ssh.options,
+            [
+                "stricthostkeychecking=yes",
+                "UserKnownHostsFile=/state/ssh/known_hosts",
+                "HashKnownHosts=no",
+            ]
+        );
+
+        let mut ssh = SshConfig {
+            identity: Some(PathBuf::from("/keys/custom")),
+            options: Vec::new(),
+            known_hosts: None,
+            ..ssh
+        };
+        apply_ssh_defaults(&mut ssh, None);
+        assert_eq!(ssh.identity.as_deref(), Some(Path::new("/keys/custom")));
+        assert!(ssh.known_hosts.is_none());
+        assert_eq!(ssh.options, DEFAULT_SSH_OPTIONS);
+    }
+
+    #[test]
+    fn decide_stages_carries_unchanged_prefix_only() {
+        let carried = CarriedStages {
+            stages: vec![
+                StageStamp {
+                    name: "base".to_string(),
+                    artifact_hash: "aaa".to_string(),
+                },
+                StageStamp {
+                    name: "tools".to_string(),
+                    artifact_hash: "bbb".to_string(),
+                },
+                StageStamp {
+                    name: "users".to_string(),
+                    artifact_hash: "ccc".to_string(),
+                },
+            ],
+            origin: CarriedOrigin::Durable,
+        };
+        let decisions = decide_stages(
+            [
+                (Some("base"), BootstrapRunIf::Changed, "aaa"),
+                (Some("tools"), BootstrapRunIf::Changed, "changed"),
+                (Some("users"), BootstrapRunIf::Changed, "ccc"),
+                (Some("seed"), BootstrapRunIf::Always, "ddd"),
+            ],
+            &carried,
+        );
+        assert!(matches!(decisions[0], StageDecision::Carry(_)));
+        assert_eq!(decisions[1], StageDecision::Run);
+        assert_eq!(
+            decisions[2],
+            StageDecision::Run,
+            "stages after a rerun stage must run again"
+        );
+        assert_eq!(decisions[3], StageDecision::Run);
+
+        let decisions = decide_stages(
+            [
+                (Some("base"), BootstrapRunIf::Changed, "changed"),
+                (Some("users"), BootstrapRunIf::Once, "changed"),
+            ],
+            &carried,
+        );
+        assert_eq!(decisions[0], StageDecision::Run);
+        assert!(
+            matches!(decisions[1], StageDecision::Carry(_)),
+            "`once` stages carry over regardless of earlier stages"
+        );
+    }
+
+    #[test]
+    fn staged_pipeline_resumes_at_failed_stage()
+    -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let _env_guard = PATH_LOCK.get_or_init(|| Mutex::new(())).lock().unwrap();
+        let temp_dir = TempDir::new()?;
+        let workspace = temp_dir.path();
+
+        let state_root = workspace.join("state");
+        fs::create_dir_all(state_root.join("handshakes"))?;
+        fs::create_dir_all(state_root.join("logs"))?;
+        fs::create_dir_all(state_root.join("overlays"))?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        fs::write(
+            state_root.join("handshakes").join("devbox.json"),
+            serde_json::to_vec(&json!({ "timestamp": now }))?,
+        )?;
+        // Stands in for the overlay `castra up` keeps between runs.
+        let overlay = state_root.join("overlays/devbox.qcow2");
+        fs::write(&overlay, b"overlay")?;
+
+        let project_root = workspace.join("project");
+        let stage_dir = project_root.join("bootstrap").join("devbox");
+        fs::create_dir_all(&stage_dir)?;
+        let mut stages = Vec::new();
+        for name in ["base", "configure"] {
+            let script = stage_dir.join(format!("{name}.sh"));
+            fs::write(&script, format!("#!/bin/sh\necho {name}\n"))?;
+            stages.push(BootstrapStage {
+                name: name.to_string(),
+                script,
+                payload: None,
+                env: HashMap::new(),
+                env_secrets: HashMap::new(),
+                timeout_secs: None,
+                verify: None,
+                run_if: BootstrapRunIf::Changed,
+            });
+        }
+
+        let base_image_path = workspace.join("base.img");
+        fs::write(&base_image_path, b"base-image")?;
+
+        let bin_dir = workspace.join("bin");
+        fs::create_dir_all(&bin_dir)?;
+        write_executable(
+            &bin_dir,
+            "ssh",
+            "#!/bin/sh\necho \"$@\" >> \"$MOCK_SSH_LOG\"\nif [ -n \"$MOCK_FAIL_STAGE\" ] && printf '%s' \"$@\" | grep -q \"CASTRA_STAGE='$MOCK_FAIL_STAGE'\"; then\n  echo 'Castra:error:stage aborted'\nfi\nexit 0\n",
+        )?;
+        write_executable(&bin_dir, "scp", "#!/bin/sh\nexit 0\n")?;
+        write_executable(&bin_dir, "qemu-system-x86_64", "#!/bin/sh\nexit 0\n")?;
+        let _path_guard = PathGuard::prepend(&bin_dir);
+        let ssh_log = workspace.join("ssh.log");
+        unsafe {
+            env::set_var("MOCK_SSH_LOG", &ssh_log);
+            env::set_var("MOCK_FAIL_STAGE", "configure");
+        }
+
+        let context = RuntimeContext {
+            state_root: state_root.clone(),
+            log_root: state_root.join("logs"),
+            qemu_system: bin_dir.join("qemu-system-x86_64"),
+            qemu_img: None,
+            accelerators: Vec::new(),
+            launch_mode: VmLaunchMode::Daemonize,
+            image_store: ImageStore::new(state_root.join("image-store")),
+            image_sources: ImageSources::default(),
+            image_trust: ImageTrust::default(),
+        };
+        let project = ProjectConfig {
+            file_path: project_root.join("castra.toml"),
+            project_root: project_root.clone(),
+            version: "0.2.0".to_string(),
+            project_name: "demo".to_string(),
+            features: ProjectFeatures::default(),
+            vms: vec![VmDefinition {
+                base_image: BaseImageSource::from_explicit(base_image_path),
+                overlay: overlay.clone(),
+                port_forwards: vec![PortForward {
+                    host: 2222,
+                    guest: 22,
+                    protocol: PortProtocol::Tcp,
+                }],
+                bootstrap: VmBootstrapConfig {
+                    mode: BootstrapMode::Auto,
+                    stages,
+                    ..VmBootstrapConfig::default()
+                },
+                ..VmDefinition::for_test("devbox")
+            }],
+            state_root: state_root.clone(),
+            workflows: Workflows::default(),
+            lifecycle: LifecycleConfig::default(),
+            bootstrap: BootstrapConfig::default(),
+            images: ImagesConfig::default(),
+            warnings: Vec::new(),
+        };
+        let mut reporter = RecordingReporter::default();
+        let mut diagnostics: Vec<Diagnostic> = Vec::new();
+        let first = run_selected(&project, &context, &[0], &mut reporter, &mut diagnostics);
+        assert!(first.is_err(), "failing stage should fail the run");
+        let progress = load_progress(&state_root, "devbox").expect("progress recorded");
+        assert_eq!(progress.failed_stage, "configure");
+        assert_eq!(progress.completed.len(), 1);
+        assert_eq!(progress.completed[0].name, "base");
+        assert!(resumable_progress(&state_root, &project.vms[0]).is_some());
+
+        fs::remove_file(&ssh_log)?;
+        unsafe { env::set_var("MOCK_FAIL_STAGE", "") };
+        let mut reporter = RecordingReporter::default();
+        let mut diagnostics: Vec<Diagnostic> = Vec::new();
+        let outcomes = run_selected(&project, &context, &[0], &mut reporter, &mut diagnostics)?;
+        assert_eq!(outcomes[0].status, BootstrapRunStatus::Success);
+
+        let applied: Vec<(Option<String>, BootstrapStepStatus)> = reporter
+            .take()
+            .into_iter()
+            .filter_map(|event| match event {
+                Event::BootstrapStep {
+                    stage,
+                    step: BootstrapStepKind::Apply,
+                    status,
+                    ..
+                } => Some((stage, status)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            applied,
+            vec![
+                (Some("base".to_string()), BootstrapStepStatus::Skipped),
+                (Some("configure".to_string()), BootstrapStepStatus::Success),
+            ]
+        );
+        let ssh_calls = fs::read_to_string(&ssh_log)?;
+        assert!(!ssh_calls.contains("CASTRA_STAGE='base'"));
+        assert!(ssh_calls.contains("CASTRA_STAGE='configure'"));
+        assert!(load_progress(&state_root, "devbox").is_none());
+
+        let stamp = load_stamp(&state_root, "devbox").expect("stamp recorded");
+        let names: Vec<&str> = stamp
+            .stages
+            .iter()
+            .map(|stage| stage.name.as_str())
+            .collect();
+        assert_eq!(names, ["base", "configure"]);
+        Ok(())
+    }
+
+    #[test]
+    fn provision_runs_as_first_stage_and_feeds_artifact_hash()
+    -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = TempDir::new()?;
+        let workspace = temp_dir.path();
+        let script = workspace.join("bootstrap/app/run.sh");
+        fs::create_dir_all(script.parent().unwrap())?;
+        fs::write(&script, b"#!/bin/sh\necho app\n")?;
+
+        let mut vm = VmDefinition {
+            role_name: "app".to_string(),
+            base_image: BaseImageSource::from_explicit(workspace.join("base.img")),
+            overlay: workspace.join("overlays/app-0.qcow2"),
+            bootstrap: VmBootstrapConfig {
+                mode: BootstrapMode::Auto,
+                script: Some(script.clone()),
+                ..VmBootstrapConfig::default()
+            },
+            provision: Some(ProvisionConfig {
+                source: workspace.join("castra.toml"),
+                services: vec!["sshd".to_string()],
+                ..ProvisionConfig::default()
+            }),
+            ..VmDefinition::for_test("app-0")
+        };
+
+        let specs = stage_specs(&vm).expect("pipeline");
+        let names: Vec<Option<&str>> = specs.iter().map(|spec| spec.name.as_deref()).collect();
+        assert_eq!(names, [Some(PROVISION_STAGE), Some(SCRIPT_STAGE)]);
+        assert_eq!(
+            specs[0].metadata_dir.as_deref(),
+            script.parent(),
+            "provision shares the script's bootstrap.toml"
+        );
+        let stages = resolve_pipeline(&vm, &specs).map_err(|(_, err)| err)?;
+        assert_eq!(
+            stages[0].remote_dir,
+            "/tmp/castra-bootstrap/app-0/provision"
+        );
+        assert!(
+            stages[0]
+                .rendered_script
+                .as_deref()
+                .is_some_and(|script| script.contains("castra_service 'sshd'"))
+        );
+
+        let hash = artifact_hash_for_vm(&vm).expect("artifact hash");
+        vm.provision
+            .as_mut()
+            .unwrap()
+            .services
+            .push("docker".to_string());
+        assert_ne!(artifact_hash_for_vm(&vm).expect("artifact hash"), hash);
+        Ok(())
+    }
+
+    #[test]
+    fn templates_render_with_vm_variables_and_feed_artifact_hash()
+    -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = TempDir::new()?;
+        let workspace = temp_dir.path();
+        let script = workspace.join("bootstrap/web/run.sh.tmpl");
+        let payload = workspace.join("bootstrap/web/payload");
+        fs::create_dir_all(&payload)?;
+        fs::write(&script, "#!/bin/sh\necho {{ vm }} of {{ replicas }}\n")?;
+        fs::write(
+            payload.join("cluster.conf.tmpl"),
+            "id={{ replica }}\ndb={{ vms.db.address }}:{{ vms.db.tcp.5432 }}\n",
+        )?;
+        fs::write(payload.join("plain.txt"), "{{ untouched }}")?;
+
+        let mut vm = VmDefinition {
+            role_name: "web".to_string(),
+            replica_index: 1,
+            base_image: BaseImageSource::from_explicit(workspace.join("base.img")),
+            overlay: workspace.join("overlays/web-1.qcow2"),
+            bootstrap: VmBootstrapConfig {
+                mode: BootstrapMode::Auto,
+                script: Some(script.clone()),
+                payload: Some(PayloadSource::Dir(payload.clone())),
+                template_vars: [
+                    ("vm", "web-1"),
+                    ("replica", "1"),
+                    ("replicas", "2"),
+                    ("vms.db.address", "10.0.2.2"),
+                    ("vms.db.tcp.5432", "15432"),
+                ]
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+                ..VmBootstrapConfig::default()
+            },
+            ..VmDefinition::for_test("web-1")
+        };
+
+        let specs = stage_specs(&vm).expect("pipeline");
+        let blueprint = assemble_blueprint(&workspace.join("state"), &vm, &specs[0])?;
+        assert_eq!(
+            fs::read_to_string(&blueprint.staged_script)?,
+            "#!/bin/sh\necho web-1 of 2\n"
+        );
+        let staged = blueprint.staged_payload.as_deref().expect("staged payload");
+        let rendered = "id=1\ndb=10.0.2.2:15432\n";
+        assert_eq!(fs::read_to_string(staged.join("cluster.conf"))?, rendered);
+        assert!(!staged.join("cluster.conf.tmpl").exists());
+        assert_eq!(
+            fs::read_to_string(staged.join("plain.txt"))?,
+            "{{ untouched }}"
+        );
+        assert_eq!(
+            blueprint.payload_manifest.file_digests()["cluster.conf"],
+            hex::encode(Sha256::digest(rendered.as_bytes()))
+        );
+
+        let hash = artifact_hash_for_vm(&vm).expect("artifact hash");
+        vm.bootstrap
+            .template_vars
+            .insert("vms.db.tcp.5432".to_string(), "25432".to_string());
+        assert_ne!(artifact_hash_for_vm(&vm).expect("artifact hash"), hash);
+
+        fs::write(&script, "echo {{ vms.cache.address }}\n")?;
+        let err = resolve_pipeline(&vm, &specs)
+            .map(|_| ())
+            .expect_err("unknown variable");
+        assert!(err.1.contains("`{{ vms.cache.address }}`"), "{}", err.1);
+
+        fs::write(&script, "echo ok\n")?;
+        fs::write(payload.join("cluster.conf"), "id=static\n")?;
+        let err = resolve_pipeline(&vm, &specs)
+            .map(|_| ())
+            .expect_err("template and file collide");
+        assert!(err.1.contains("keep one of them"), "{}", err.1);
+        Ok(())
+    }
+
+    /// Transport double that records operations and the input they were
+    /// given, fails the apply step while `fail_apply` is set and refuses the
+    /// next `connect_failures` connections.
+    #[derive(Default)]
+    struct FakeTransport {
+        calls: Mutex<Vec<String>>,
+        inputs: Mutex<Vec<String>>,
+        fail_apply: std::sync::atomic::AtomicBool,
+        connect_failures: AtomicUsize,
+        /// Payload manifest the guest holds, as last uploaded.
+        manifest: Mutex<Option<String>>,
+    }
+
+    impl FakeTransport {
+        fn record(&self, call: String) -> ExecOutput {
+            self.calls.lock().unwrap().push(call.clone());
+            ExecOutput {
+                command: format!("fake {call}"),
+                ..ExecOutput::default()
+            }
+        }
+    }
+
+    impl Transport for FakeTransport {
+        fn name(&self) -> &'static str {
+            "fake"
+        }
+
+        fn connect(
+            &self,
+            ssh: &SshConfig,
+            _timeout: Option<Duration>,
+        ) -> std::result::Result<ExecOutput, TransportError> {
+            let output = self.record(format!("connect {}", ssh.target()));
+            let failures = &self.connect_failures;
+            if failures
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |left| {
+                    left.checked_sub(1)
+                })
+                .is_ok()
+            {
+                return Err(TransportError::Connect {
+                    target: ssh.target(),
+                    message: "Connection reset by peer".to_string(),
+                });
+            }
+            Ok(output)
+        }
+
+        fn exec_streaming(
+            &self,
+            _ssh: &SshConfig,
+            script: &str,
+            input: &str,
+            _timeout: Option<Duration>,
+            on_line: &mut dyn FnMut(OutputStream, &str),
+        ) -> std::result::Result<ExecOutput, TransportError> {
+            let mut output = self.record(format!("exec {script}"));
+            if !input.is_empty() {
+                self.inputs.lock().unwrap().push(input.to_string());
+            }
+            if script.contains(&format!("./{STAGED_SCRIPT_NAME}")) {
+                if self.fail_apply.load(std::sync::atomic::Ordering::SeqCst) {
+                    return Err(TransportError::Exit {
+                        command: output.command,
+                        code: Some(7),
+                        stdout: String::new(),
+                        // Echo the input, as a script tracing itself would.
+                        stderr: format!("{input}apt-get: not found\n"),
+                    });
+                }
+                on_line(OutputStream::Stdout, SENTINEL_NOOP);
+                output.stdout = format!("{SENTINEL_NOOP}\n");
+            }
+            if script.contains(&format!("cat {}", shell_quote(PAYLOAD_MANIFEST_NAME))) {
+                output.stdout = self.manifest.lock().unwrap().take().unwrap_or_default();
+            }
+            Ok(output)
+        }
+
+        fn upload(
+            &self,
+            _ssh: &SshConfig,
+            local: &Path,
+            remote: &str,
+            _timeout: Option<Duration>,
+        ) -> std::result::Result<ExecOutput, TransportError> {
+            let name = local.file_name().unwrap_or_default().to_string_lossy();
+            if name == PAYLOAD_MANIFEST_NAME {
+                *self.manifest.lock().unwrap() = Some(fs::read_to_string(local).unwrap());
+            }
+            Ok(self.record(format!("upload {name} {remote}")))
+        }
+
+        fn download(
+            &self,
+            _ssh: &SshConfig,
+            remote: &str,
+            _local: &Path,
+        ) -> std::result::Result<ExecOutput, TransportError> {
+            Ok(self.record(format!("download {remote}")))
+        }
+    }
+
+    /// Single-script `devbox` project whose handshake is already recorded.
+    fn injected_transport_fixture(
+        workspace: &Path,
+    ) -> std::result::Result<(RuntimeContext, ProjectConfig), Box<dyn std::error::Error>> {
+        let state_root = workspace.join("state");
+        fs::create_dir_all(state_root.join("handshakes"))?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        fs::write(
+            state_root.join("handshakes").join("devbox.json"),
+            serde_json::to_vec(&json!({ "timestamp": now }))?,
+        )?;
+
+        let project_root = workspace.join("project");
+        let script = project_root.join("bootstrap/devbox/run.sh");
+        fs::create_dir_all(script.with_file_name("payload"))?;
+        fs::write(&script, b"#!/bin/sh\necho devbox\n")?;
+        fs::write(script.with_file_name("payload").join("motd"), b"hello")?;
+        let base_image = workspace.join("base.img");
+        fs::write(&base_image, b"base-image")?;
+
+        let context = RuntimeContext {
+            state_root: state_root.clone(),
+            log_root: state_root.join("logs"),
+            qemu_system: workspace.join("qemu-system-x86_64"),
+            qemu_img: None,
+            accelerators: Vec::new(),
+            launch_mode: VmLaunchMode::Daemonize,
+            image_store: ImageStore::new(state_root.join("image-store")),
+            image_sources: ImageSources::default(),
+            image_trust: ImageTrust::default(),
+        };
+        let project = ProjectConfig {
+            file_path: project_root.join("castra.toml"),
+            project_root: project_root.clone(),
+            version: "0.2.0".to_string(),
+            project_name: "demo".to_string(),
+            features: ProjectFeatures::default(),
+            vms: vec![VmDefinition {
+                base_image: BaseImageSource::from_explicit(base_image),
+                overlay: state_root.join("overlays/devbox.qcow2"),
+                port_forwards: vec![PortForward {
+                    host: 2222,
+                    guest: 22,
+                    protocol: PortProtocol::Tcp,
+                }],
+                bootstrap: VmBootstrapConfig {
+                    mode: BootstrapMode::Always,
+                    script: Some(script.clone()),
+                    payload: Some(PayloadSource::Dir(script.with_file_name("payload"))),
+                    ..VmBootstrapConfig::default()
+                },
+                ..VmDefinition::for_test("devbox")
+            }],
+            state_root: state_root.clone(),
+            workflows: Workflows::default(),
+            lifecycle: LifecycleConfig::default(),
+            bootstrap: BootstrapConfig::default(),
+            images: ImagesConfig::default(),
+            warnings: Vec::new(),
+        };
+        Ok((context, project))
+    }
+
+    #[test]
+    fn pipeline_runs_against_injected_transport()
+    -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = TempDir::new()?;
+        let (context, project) = injected_transport_fixture(temp_dir.path())?;
+
+        let transport = FakeTransport::default();
+        let mut reporter = RecordingReporter::default();
+        let mut diagnostics: Vec<Diagnostic> = Vec::new();
+        let outcomes = run_selected_with(
+            &project,
+            &context,
+            &[0],
+            &transport,
+            &mut reporter,
+            &mut diagnostics,
+        )?;
+        assert_eq!(outcomes[0].status, BootstrapRunStatus::NoOp);
+
+        let calls = std::mem::take(&mut *transport.calls.lock().unwrap());
+        let remote_dir = "/tmp/castra-bootstrap/devbox";
+        let ops: Vec<&str> = calls
+            .iter()
+            .map(|call| call.split_whitespace().next().unwrap())
+            .collect();
+        assert_eq!(
+            ops,
+            [
+                "connect", "exec", "upload", "upload", "exec", "upload", "exec"
+            ],
+            "{calls:#?}"
+        );
+        assert_eq!(calls[0], "connect root@127.0.0.1:2222");
+        assert_eq!(calls[2], format!("upload run.sh {remote_dir}/run.sh"));
+        assert_eq!(calls[3], format!("upload payload {remote_dir}/payload"));
+        assert_eq!(
+            calls[5],
+            format!("upload {PAYLOAD_MANIFEST_NAME} {remote_dir}/{PAYLOAD_MANIFEST_NAME}")
+        );
+        assert!(calls[6].contains("export CASTRA_VM='devbox'"));
+
+        transport
+            .fail_apply
+            .store(true, std::sync::atomic::Ordering::SeqCst);
+        let mut reporter = RecordingReporter::default();
+        let result = run_selected_with(
+            &project,
+            &context,
+            &[0],
+            &transport,
+            &mut reporter,
+            &mut diagnostics,
+        );
+        assert!(result.is_err(), "failed apply should fail the run");
+        let detail = reporter
+            .take()
+            .into_iter()
+            .find_map(|event| match event {
+                Event::BootstrapStep {
+                    step: BootstrapStepKind::Apply,
+                    status: BootstrapStepStatus::Failed,
+                    detail,
+                    ..
+                } => detail,
+                _ => None,
+            })
+            .expect("failed apply step");
+        assert!(
+            detail.contains("exited with code Some(7)") && detail.contains("apt-get: not found"),
+            "{detail}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn connect_retries_transient_failures_and_reports_attempts()
+    -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = TempDir::new()?;
+        let (context, mut project) = injected_transport_fixture(temp_dir.path())?;
+        project.bootstrap.steps.connect.backoff = Duration::ZERO;
+
+        let transport = FakeTransport::default();
+        transport.connect_failures.store(2, Ordering::SeqCst);
+        let mut reporter = RecordingReporter::default();
+        let mut diagnostics: Vec<Diagnostic> = Vec::new();
+        run_selected_with(
+            &project,
+            &context,
+            &[0],
+            &transport,
+            &mut reporter,
+            &mut diagnostics,
+        )?;
+        let events = reporter.take();
+        let retries: Vec<&String> = events
+            .iter()
+            .filter_map(|event| match event {
+                Event::Message {
+                    severity: Severity::Warning,
+                    text,
+                } if text.contains("retrying") => Some(text),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(retries.len(), 2, "{retries:#?}");
+        assert!(
+            retries[0].contains("connect attempt 1/5 failed")
+                && retries[0].contains("Connection reset by peer"),
+            "{}",
+            retries[0]
+        );
+        let (attempts, detail) = events
+            .iter()
+            .find_map(|event| match event {
+                Event::BootstrapStep {
+                    step: BootstrapStepKind::Connect,
+                    status: BootstrapStepStatus::Success,
+                    attempts,
+                    detail,
+                    ..
+                } => Some((*attempts, detail.clone().unwrap_or_default())),
+                _ => None,
+            })
+            .expect("connect step");
+        assert_eq!(attempts, 3);
+        assert!(detail.ends_with("Succeeded on attempt 3."), "{detail}");
+
+        project.bootstrap.steps.connect.retries = 1;
+        transport.connect_failures.store(5, Ordering::SeqCst);
+        let mut reporter = RecordingReporter::default();
+        let result = run_selected_with(
+            &project,
+            &context,
+            &[0],
+            &transport,
+            &mut reporter,
+            &mut diagnostics,
+        );
+        let err = result.expect_err("connect never succeeds");
+        assert!(
+            err.to_string().contains("Gave up after 2 attempts."),
+            "{err}"
+        );
+        assert_eq!(transport.connect_failures.load(Ordering::SeqCst), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn rerun_on_running_vm_is_manual_and_keeps_vm_up_on_failure()
+    -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = TempDir::new()?;
+        let (context, project) = injected_transport_fixture(temp_dir.path())?;
+        let booted_at = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let targets = [(0, booted_at)];
+
+        let transport = FakeTransport::default();
+        let mut reporter = RecordingReporter::default();
+        let mut diagnostics: Vec<Diagnostic> = Vec::new();
+        let outcomes = rerun_selected_with(
+            &project,
+            &context.state_root,
+            &targets,
+            &RerunOptions::default(),
+            &transport,
+            &mut reporter,
+            &mut diagnostics,
+        )?;
+        assert_eq!(outcomes[0].status, BootstrapRunStatus::NoOp);
+        assert!(reporter.events.iter().any(|event| matches!(
+            event,
+            Event::BootstrapStarted {
+                trigger: BootstrapTrigger::Manual,
+                ..
+            }
+        )));
+
+        let err = rerun_selected_with(
+            &project,
+            &context.state_root,
+            &targets,
+            &RerunOptions {
+                stages: vec!["base".to_string()],
+                force: false,
+            },
+            &transport,
+            &mut reporter,
+            &mut diagnostics,
+        )
+        .expect_err("single-script VMs have no stages");
+        assert!(err.to_string().contains("single bootstrap script"), "{err}");
+
+        let mut project = project;
+        project.vms[0].collect = vec![CollectRule {
+            guest: "/var/log/app".to_string(),
+            host: temp_dir.path().join("artifacts"),
+        }];
+        transport.calls.lock().unwrap().clear();
+        transport
+            .fail_apply
+            .store(true, std::sync::atomic::Ordering::SeqCst);
+        let mut reporter = RecordingReporter::default();
+        let result = rerun_selected_with(
+            &project,
+            &context.state_root,
+            &targets,
+            &RerunOptions::default(),
+            &transport,
+            &mut reporter,
+            &mut diagnostics,
+        );
+        assert!(result.is_err(), "failed apply should fail the rerun");
+        let events = reporter.take();
+        assert!(
+            events.iter().any(|event| matches!(
+                event,
+                Event::Message { text, .. } if text.contains("the VM keeps running")
+            )),
+            "{events:#?}"
+        );
+        assert!(
+            !events
+                .iter()
+                .any(|event| matches!(event, Event::ShutdownRequested { .. })),
+            "{events:#?}"
+        );
+        // Artifacts are still collected from the failed run.
+        let calls = transport.calls.lock().unwrap().clone();
+        assert!(
+            calls.iter().any(|call| call == "download /var/log/app"),
+            "{calls:#?}"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn payload_transfer_sends_only_changed_files()
+    -> std::result::Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = TempDir::new()?;
+        let (context, project) = injected_transport_fixture(temp_dir.path())?;
+        let Some(PayloadSource::Dir(payload)) = project.vms[0].bootstrap.payload.clone() else {
+            panic!("default payload is a directory");
+        };
+        fs::write(payload.join("stale"), b"old")?;
+
+        let transport = FakeTransport::default();
+        let mut reporter = RecordingReporter::default();
+        let mut diagnostics: Vec<Diagnostic> = Vec::new();
+        let mut run = |reporter: &mut RecordingReporter| {
+            transport.calls.lock().unwrap().clear();
+            run_selected_with(
+                &project,
+                &context,
+                &[0],
+                &transport,
+                reporter,
+                &mut diagnostics,
+            )
+            .map(|_| transport.calls.lock().unwrap().clone())
+        };
+        run(&mut reporter)?;
+
+        fs::remove_file(payload.join("stale"))?;
+        fs::create_dir(payload.join("conf"))?;
+        fs::write(payload.join("conf/app.toml"), b"port = 80\n")?;
+        let calls = run(&mut reporter)?;
+
+        let remote_payload = "/tmp/castra-bootstrap/devbox/payload";
+        let uploads: Vec<&String> = calls
+            .iter()
+            .filter(|call| call.starts_with("upload") && call.contains("/payload"))
+            .collect();
+        assert_eq!(
+            uploads,
+            [&format!("upload app.toml {remote_payload}/conf/app.toml")],
+            "{calls:#?}"
+        );
+        assert!(
+            calls
+                .iter()
+                .any(|call| call.contains("rm -rf -- 'stale'")
+                    && call.contains("mkdir -p -- 'conf'")),
+            "{calls:#?}"
+        );
+        let skipped = reporter
+            .take()
+            .into_iter()
+            .rev()
+            .find_map(|event| match event {
+                Event::BootstrapStep {
+                    step: BootstrapStepKind::Transfer,
+                    bytes_skipped,
+                    ..
+                } => Some(bytes_skipped),
+                _ => None,
+            });
+        assert_eq!(skipped, Some(Some(5)), "motd is unchanged");
+
+        let staged = context.state_root.join("bootstrap/devbox/payload");
+        assert!(!staged.join("stale").exists());
+        assert_eq!(fs::read(staged.join("conf/app.toml"))?, b"port = 80\n");
+        Ok(())
+    }
+
+    #[test]
+    fn payload_manifest_diff_lists_only_changes() {
+        let file = |sha: &str, size: u64| ManifestEntry::File {
+            sha256: sha.to_string(),
+            size,
+            mode: 0o644,
+        };
+        let remote = PayloadManifest {
+            entries: BTreeMap::from([
+                ("bin".to_string(), ManifestEntry::Directory),
+                ("bin/tool".to_string(), file("a", 10)),
+                ("keep".to_string(), file("b", 4)),
+                ("old".to_string(), ManifestEntry::Directory),
+                ("old/x".to_string(), file("c", 1)),
+            ]),
+        };
+        let local = PayloadManifest {
+            entries: BTreeMap::from([
+                ("bin".to_string(), file("d", 3)),
+                ("keep".to_string(), file("b", 4)),
+                ("new".to_string(), ManifestEntry::Directory),
+                ("new/y".to_string(), file("e", 2)),
+            ]),
+        };
+        assert_eq!(
+            local.diff(&remote),
+            PayloadDiff {
+                remove: vec!["bin".to_string(), "old".to_string()],
+                create_dirs: vec!["new".to_string()],
+                upload: vec![("bin".to_string(), 0o644), ("new/y".to_string(), 0o644)],
+                bytes_uploaded: 5,
+                bytes_skipped: 4,
+            }
+        );
+        assert!(!local.diff(&local).has_changes());
+    }
+
+    #[test]
+    fn secret_env_reaches_the_guest_over_stdin_but_never_reports_or_logs()
+    -> std::result::Result<(), Box<dyn std::error::Error>> {
+        const TOKEN: &str = "hunter2-registry-token";
+        let temp_dir = TempDir::new()?;
+        let (context, mut project) = injected_transport_fixture(temp_dir.path())?;
+        let token_file = temp_dir.path().join("token");
+        fs::write(&token_file, format!("{TOKEN}\n"))?;
+        let vm = &mut project.vms[0];
+        vm.bootstrap
+            .env
+            .insert("TOKEN".to_string(), "shadowed".to_string());
+        vm.bootstrap
+            .env_secrets
+            .insert("TOKEN".to_string(), SecretSource::File(token_file.clone()));
+        vm.bootstrap.secret_salt = context.state_root.join("bootstrap/secret.salt");
+        let hash = artifact_hash_for_vm(vm).expect("artifact hash");
+
+        let transport = FakeTransport::default();
+        transport
+            .fail_apply
+            .store(true, std::sync::atomic::Ordering::SeqCst);
+        let mut reporter = RecordingReporter::default();
+        let mut diagnostics: Vec<Diagnostic> = Vec::new();
+        let result = run_selected_with(
+            &project,
+            &context,
+            &[0],
+            &transport,
+            &mut reporter,
+            &mut diagnostics,
+        );
+        assert!(result.is_err(), "failed apply should fail the run");
+
+        // The value travels over stdin, never on a command line.
+        let calls = transport.calls.lock().unwrap().join("\n");
+        assert!(!calls.contains(TOKEN), "{calls}");
+        assert!(calls.contains(READ_SECRET_EXPORTS), "{calls}");
+        assert!(!calls.contains("shadowed"), "{calls}");
+        let inputs = transport.inputs.lock().unwrap().clone();
+        assert_eq!(inputs, [format!("export TOKEN='{TOKEN}';\n")]);
+
+        let events = format!("{:?}", reporter.take());
+        assert!(events.contains("<redacted:TOKEN>"), "{events}");
+        assert!(!events.contains(TOKEN), "{events}");
+        assert!(!format!("{result:?} {diagnostics:?}").contains(TOKEN));
+
+        let mut logs = Vec::new();
+        for entry in fs::read_dir(context.log_root.join(LOG_SUBDIR))? {
+            logs.push(fs::read_to_string(entry?.path())?);
+        }
+        let logs = logs.join("\n");
+        assert!(logs.contains("\"env_secrets\""), "{logs}");
+        assert!(!logs.contains(TOKEN), "{logs}");
+        assert!(!fs::read_to_string(&project.vms[0].bootstrap.secret_salt)?.contains(TOKEN));
+
+        fs::write(&token_file, "rotated")?;
+        assert_ne!(artifact_hash_for_vm(&project.vms[0]), Some(hash));
+        Ok(())
+    }
 }
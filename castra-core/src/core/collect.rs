@@ -0,0 +1,355 @@
+//! `collect` rules: copy guest files to the host before ephemeral disks go
+//! away.
+//!
+//! Each rule copies one guest file or directory into its host directory under
+//! the guest name, like `scp -r <guest> <host>/`. The copy is downloaded next
+//! to the previous one and only replaces it once complete, so a failed
+//! collection keeps the last good artifacts. Failures are reported per rule
+//! and never abort the surrounding operation.
+
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use crate::config::{CollectRule, VmDefinition};
+
+use super::bootstrap;
+use super::events::Event;
+use super::outcome::CollectedArtifact;
+use super::reporter::Reporter;
+use super::transport::{SshConfig, Transport};
+
+/// Collect from each of `vms`, connecting the way bootstrap does.
+pub fn collect_vms(
+    vms: &[&VmDefinition],
+    transport: &dyn Transport,
+    reporter: &mut dyn Reporter,
+) -> Vec<CollectedArtifact> {
+    let mut emit_event = |event: Event| reporter.report(event);
+    let mut artifacts = Vec::new();
+    for vm in vms {
+        artifacts.extend(match bootstrap::ssh_for_vm(vm) {
+            Ok(ssh) => collect_vm(vm, &ssh, transport, &mut emit_event),
+            Err(err) => fail_vm(vm, &err, &mut emit_event),
+        });
+    }
+    artifacts
+}
+
+/// Run every `collect` rule of `vm` over `ssh`, in order.
+pub fn collect_vm(
+    vm: &VmDefinition,
+    ssh: &SshConfig,
+    transport: &dyn Transport,
+    emit_event: &mut dyn FnMut(Event),
+) -> Vec<CollectedArtifact> {
+    vm.collect
+        .iter()
+        .map(|rule| {
+            let artifact = collect_rule(&vm.name, rule, ssh, transport);
+            emit_event(match &artifact.error {
+                None => Event::ArtifactsCollected {
+                    vm: artifact.vm.clone(),
+                    guest: artifact.guest.clone(),
+                    path: artifact.path.clone(),
+                    files: artifact.files.clone(),
+                    bytes: artifact.bytes,
+                },
+                Some(error) => Event::ArtifactCollectionFailed {
+                    vm: artifact.vm.clone(),
+                    guest: artifact.guest.clone(),
+                    error: error.clone(),
+                },
+            });
+            artifact
+        })
+        .collect()
+}
+
+/// Report every rule of `vm` as failed with `error`, for VMs whose SSH
+/// settings could not be resolved.
+fn fail_vm(
+    vm: &VmDefinition,
+    error: &str,
+    emit_event: &mut dyn FnMut(Event),
+) -> Vec<CollectedArtifact> {
+    vm.collect
+        .iter()
+        .map(|rule| {
+            emit_event(Event::ArtifactCollectionFailed {
+                vm: vm.name.clone(),
+                guest: rule.guest.clone(),
+                error: error.to_string(),
+            });
+            CollectedArtifact {
+                vm: vm.name.clone(),
+                guest: rule.guest.clone(),
+                path: host_path(rule),
+                files: Vec::new(),
+                bytes: 0,
+                error: Some(error.to_string()),
+            }
+        })
+        .collect()
+}
+
+/// Where `rule` places its copy of the guest path.
+fn host_path(rule: &CollectRule) -> PathBuf {
+    let name = rule.guest.rsplit('/').next().unwrap_or_default();
+    rule.host.join(name)
+}
+
+fn collect_rule(
+    vm: &str,
+    rule: &CollectRule,
+    ssh: &SshConfig,
+    transport: &dyn Transport,
+) -> CollectedArtifact {
+    let path = host_path(rule);
+    let mut artifact = CollectedArtifact {
+        vm: vm.to_string(),
+        guest: rule.guest.clone(),
+        path: path.clone(),
+        files: Vec::new(),
+        bytes: 0,
+        error: None,
+    };
+    match fetch(rule, &path, ssh, transport) {
+        Ok(files) => {
+            artifact.bytes = files.iter().map(|(_, size)| size).sum();
+            artifact.files = files.into_iter().map(|(file, _)| file).collect();
+        }
+        Err(error) => artifact.error = Some(error),
+    }
+    artifact
+}
+
+/// Download `rule.guest` to `path`, returning the files it contained with
+/// their sizes. `path` must name a direct child of `rule.host`; it is removed
+/// and replaced wholesale.
+fn fetch(
+    rule: &CollectRule,
+    path: &Path,
+    ssh: &SshConfig,
+    transport: &dyn Transport,
+) -> Result<Vec<(PathBuf, u64)>, String> {
+    let mut components = path
+        .strip_prefix(&rule.host)
+        .into_iter()
+        .flat_map(Path::components);
+    let (Some(Component::Normal(name)), None) = (components.next(), components.next()) else {
+        return Err(format!(
+            "refusing to replace {}: not a direct child of {}",
+            path.display(),
+            rule.host.display()
+        ));
+    };
+    fs::create_dir_all(&rule.host)
+        .map_err(|err| format!("failed to create {}: {err}", rule.host.display()))?;
+    let name = name.to_string_lossy();
+    let partial = rule.host.join(format!(".{name}.partial"));
+    remove_path(&partial).map_err(|err| format!("failed to clear {}: {err}", partial.display()))?;
+
+    if let Err(err) = transport.download(ssh, &rule.guest, &partial) {
+        let _ = remove_path(&partial);
+        return Err(err.to_string());
+    }
+    remove_path(path)
+        .and_then(|()| fs::rename(&partial, path))
+        .map_err(|err| format!("failed to replace {}: {err}", path.display()))?;
+
+    let mut files = Vec::new();
+    list_files(path, &mut files)
+        .map_err(|err| format!("failed to list {}: {err}", path.display()))?;
+    Ok(files)
+}
+
+fn remove_path(path: &Path) -> io::Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(path),
+        Ok(_) => fs::remove_file(path),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+fn list_files(path: &Path, files: &mut Vec<(PathBuf, u64)>) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(path)?;
+    if !metadata.is_dir() {
+        files.push((path.to_path_buf(), metadata.len()));
+        return Ok(());
+    }
+    let mut entries = fs::read_dir(path)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        list_files(&entry.path(), files)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::transport::{ExecOutput, OutputStream, TransportError};
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    /// Transport whose guest filesystem is a host directory.
+    struct LocalGuest {
+        root: PathBuf,
+    }
+
+    fn copy_tree(from: &Path, to: &Path) -> io::Result<()> {
+        if !fs::metadata(from)?.is_dir() {
+            return fs::copy(from, to).map(|_| ());
+        }
+        fs::create_dir_all(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_tree(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        Ok(())
+    }
+
+    impl Transport for LocalGuest {
+        fn name(&self) -> &'static str {
+            "local"
+        }
+
+        fn connect(
+            &self,
+            _ssh: &SshConfig,
+            _timeout: Option<Duration>,
+        ) -> Result<ExecOutput, TransportError> {
+            Ok(ExecOutput::default())
+        }
+
+        fn exec_streaming(
+            &self,
+            _ssh: &SshConfig,
+            _script: &str,
+            _input: &str,
+            _timeout: Option<Duration>,
+            _on_line: &mut dyn FnMut(OutputStream, &str),
+        ) -> Result<ExecOutput, TransportError> {
+            Ok(ExecOutput::default())
+        }
+
+        fn upload(
+            &self,
+            _ssh: &SshConfig,
+            _local: &Path,
+            _remote: &str,
+            _timeout: Option<Duration>,
+        ) -> Result<ExecOutput, TransportError> {
+            Ok(ExecOutput::default())
+        }
+
+        fn download(
+            &self,
+            _ssh: &SshConfig,
+            remote: &str,
+            local: &Path,
+        ) -> Result<ExecOutput, TransportError> {
+            copy_tree(&self.root.join(remote.trim_start_matches('/')), local).map_err(
+                |source| TransportError::Io {
+                    context: format!("Failed to download {remote}"),
+                    source,
+                },
+            )?;
+            Ok(ExecOutput::default())
+        }
+    }
+
+    #[test]
+    fn collect_replaces_previous_copy_and_keeps_it_on_failure()
+    -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = TempDir::new()?;
+        let guest_logs = temp_dir.path().join("guest/var/log/app");
+        fs::create_dir_all(guest_logs.join("nested"))?;
+        fs::write(guest_logs.join("a.log"), b"alpha")?;
+        fs::write(guest_logs.join("nested/b.log"), b"beta!!")?;
+
+        let transport = LocalGuest {
+            root: temp_dir.path().join("guest"),
+        };
+        let ssh = SshConfig {
+            user: "root".to_string(),
+            host: "127.0.0.1".to_string(),
+            port: 2222,
+            identity: None,
+            options: Vec::new(),
+            known_hosts: None,
+        };
+        let host = temp_dir.path().join("artifacts/web");
+        let rule = CollectRule {
+            guest: "/var/log/app".to_string(),
+            host: host.clone(),
+        };
+
+        let first = collect_rule("web", &rule, &ssh, &transport);
+        assert_eq!(first.error, None);
+        assert_eq!(first.path, host.join("app"));
+        assert_eq!(
+            first.files,
+            [host.join("app/a.log"), host.join("app/nested/b.log")]
+        );
+        assert_eq!(first.bytes, 11);
+
+        fs::remove_file(guest_logs.join("a.log"))?;
+        let second = collect_rule("web", &rule, &ssh, &transport);
+        assert_eq!(second.files, [host.join("app/nested/b.log")]);
+        assert!(!host.join("app/a.log").exists());
+
+        fs::remove_dir_all(&guest_logs)?;
+        let failed = collect_rule("web", &rule, &ssh, &transport);
+        assert!(
+            failed
+                .error
+                .as_deref()
+                .is_some_and(|error| error.contains("/var/log/app")),
+            "{failed:?}"
+        );
+        assert!(failed.files.is_empty());
+        assert!(host.join("app/nested/b.log").exists());
+        assert!(!host.join(".app.partial").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn fetch_only_replaces_direct_children_of_the_host_directory()
+    -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = TempDir::new()?;
+        fs::create_dir_all(temp_dir.path().join("guest/var"))?;
+        let keep = temp_dir.path().join("artifacts/keep.txt");
+        fs::create_dir_all(keep.parent().unwrap())?;
+        fs::write(&keep, b"keep")?;
+
+        let transport = LocalGuest {
+            root: temp_dir.path().join("guest"),
+        };
+        let ssh = SshConfig {
+            user: "root".to_string(),
+            host: "127.0.0.1".to_string(),
+            port: 2222,
+            identity: None,
+            options: Vec::new(),
+            known_hosts: None,
+        };
+        let rule = CollectRule {
+            guest: "/var/..".to_string(),
+            host: temp_dir.path().join("artifacts/web"),
+        };
+
+        let artifact = collect_rule("web", &rule, &ssh, &transport);
+        assert!(
+            artifact
+                .error
+                .as_deref()
+                .is_some_and(|error| error.contains("not a direct child")),
+            "{artifact:?}"
+        );
+        assert_eq!(fs::read(&keep)?, b"keep");
+        Ok(())
+    }
+}
@@ -0,0 +1,569 @@
+//! Resumable HTTP downloads for managed images.
+//!
+//! Transfers stream into a `.partial` file so an interrupted `castra up` picks
+//! up where it left off via HTTP `Range` requests. Proxies follow the usual
+//! `HTTPS_PROXY`/`HTTP_PROXY`/`ALL_PROXY` and `NO_PROXY` variables, and
+//! `CASTRA_CA_BUNDLE` (or `SSL_CERT_FILE`) adds trust roots for intercepting
+//! proxies.
+
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rustls_pki_types::CertificateDer;
+use rustls_pki_types::pem::PemObject;
+use ureq::Error as UreqError;
+
+use crate::error::{Error, Result};
+
+use super::diagnostics::Severity;
+use super::events::Event;
+
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+const READ_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Retry schedule for interrupted downloads (exponential backoff).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 5,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(16),
+        }
+    }
+}
+
+impl RetryPolicy {
+    fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 1u32 << attempt.saturating_sub(1).min(16);
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+/// A single resumable transfer.
+#[derive(Debug, Clone)]
+pub struct DownloadRequest {
+    pub url: String,
+    /// File receiving the bytes; kept between runs so transfers can resume.
+    pub partial: PathBuf,
+    pub expected_size: Option<u64>,
+    pub retry: RetryPolicy,
+}
+
+impl DownloadRequest {
+    pub fn new(url: impl Into<String>, partial: impl Into<PathBuf>) -> Self {
+        Self {
+            url: url.into(),
+            partial: partial.into(),
+            expected_size: None,
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    pub fn with_expected_size(mut self, bytes: u64) -> Self {
+        self.expected_size = Some(bytes);
+        self
+    }
+}
+
+/// Conventional `.partial` sibling for a download target.
+pub fn partial_path(target: &Path) -> PathBuf {
+    match target.file_name().and_then(|name| name.to_str()) {
+        Some(name) => target.with_file_name(format!("{name}.partial")),
+        None => target.with_extension("partial"),
+    }
+}
+
+/// Fetch `request.url` into `request.partial`, resuming and retrying as needed.
+///
+/// Returns the number of bytes on disk once the server reports the transfer
+/// complete. Callers are responsible for verifying and renaming the partial file.
+pub fn download_resumable(request: &DownloadRequest, emit: &mut dyn FnMut(Event)) -> Result<u64> {
+    let agent = build_agent(&request.url)?;
+    let attempts = request.retry.attempts.max(1);
+    let mut failures = Vec::new();
+
+    for attempt in 1..=attempts {
+        match attempt_download(&agent, request, emit) {
+            Ok(bytes) => return Ok(bytes),
+            Err(AttemptError::Fatal(err)) => return Err(err),
+            Err(AttemptError::Retry(reason)) => {
+                if attempt < attempts {
+                    let delay = request.retry.backoff(attempt);
+                    emit(Event::Message {
+                        severity: Severity::Warning,
+                        text: format!(
+                            "Download of {} interrupted ({reason}); retrying in {:.1}s (attempt {}/{attempts}).",
+                            request.url,
+                            delay.as_secs_f64(),
+                            attempt + 1
+                        ),
+                    });
+                    thread::sleep(delay);
+                }
+                failures.push(format!("attempt {attempt}: {reason}"));
+            }
+        }
+    }
+
+    Err(Error::PreflightFailed {
+        message: format!(
+            "Failed to download {} after {attempts} attempt(s) ({}). Partial data is kept at {} and will resume on the next run.",
+            request.url,
+            failures.join("; "),
+            request.partial.display()
+        ),
+    })
+}
+
+enum AttemptError {
+    Retry(String),
+    Fatal(Error),
+}
+
+fn attempt_download(
+    agent: &ureq::Agent,
+    request: &DownloadRequest,
+    emit: &mut dyn FnMut(Event),
+) -> std::result::Result<u64, AttemptError> {
+    let mut offset = partial_len(&request.partial);
+    if let Some(expected) = request.expected_size {
+        if offset > expected {
+            truncate_partial(&request.partial)?;
+            offset = 0;
+        } else if offset == expected && expected > 0 {
+            return Ok(offset);
+        }
+    }
+
+    let mut call = agent.get(&request.url);
+    if offset > 0 {
+        call = call.set("Range", &format!("bytes={offset}-"));
+    }
+
+    let response = match call.call() {
+        Ok(response) => response,
+        Err(UreqError::Status(416, _)) if offset > 0 => {
+            truncate_partial(&request.partial)?;
+            return Err(AttemptError::Retry(
+                "server rejected the resume range; restarting from zero".to_string(),
+            ));
+        }
+        Err(UreqError::Status(code, _)) if is_retryable_status(code) => {
+            return Err(AttemptError::Retry(format!(
+                "server returned HTTP status {code}"
+            )));
+        }
+        Err(UreqError::Status(code, _)) => {
+            return Err(AttemptError::Fatal(Error::PreflightFailed {
+                message: format!(
+                    "Failed to download {}: server returned HTTP status {code}.",
+                    request.url
+                ),
+            }));
+        }
+        Err(UreqError::Transport(inner)) => return Err(AttemptError::Retry(inner.to_string())),
+    };
+
+    let resumed = offset > 0
+        && response.status() == 206
+        && response
+            .header("Content-Range")
+            .and_then(content_range_start)
+            == Some(offset);
+    if offset > 0 && !resumed {
+        offset = 0;
+    }
+
+    let total = response
+        .header("Content-Length")
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .map(|length| length + offset)
+        .or(request.expected_size);
+
+    if resumed {
+        emit(Event::Message {
+            severity: Severity::Info,
+            text: format!("Resuming download of {} at byte {offset}.", request.url),
+        });
+    }
+
+    let mut file = open_partial(&request.partial, resumed)?;
+    let mut reader = response.into_reader();
+    let mut buffer = [0u8; 64 * 1024];
+    let mut bytes = offset;
+    let started = Instant::now();
+    let mut last_report: Option<Instant> = None;
+
+    let interrupted = loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => break None,
+            Ok(read) => read,
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(err) => break Some(err.to_string()),
+        };
+        file.write_all(&buffer[..read]).map_err(|err| {
+            AttemptError::Fatal(Error::PreflightFailed {
+                message: format!(
+                    "Failed to write download data to {}: {err}",
+                    request.partial.display()
+                ),
+            })
+        })?;
+        bytes += read as u64;
+
+        if last_report.is_none_or(|at| at.elapsed() >= PROGRESS_INTERVAL) {
+            emit(progress_event(request, bytes, offset, total, started));
+            last_report = Some(Instant::now());
+        }
+    };
+
+    file.flush().map_err(|err| {
+        AttemptError::Fatal(Error::PreflightFailed {
+            message: format!(
+                "Failed to flush download data to {}: {err}",
+                request.partial.display()
+            ),
+        })
+    })?;
+    emit(progress_event(request, bytes, offset, total, started));
+
+    if let Some(reason) = interrupted {
+        return Err(AttemptError::Retry(format!(
+            "connection dropped after {bytes} bytes: {reason}"
+        )));
+    }
+    if let Some(total) = total.filter(|total| bytes < *total) {
+        return Err(AttemptError::Retry(format!(
+            "connection closed after {bytes} of {total} bytes"
+        )));
+    }
+
+    Ok(bytes)
+}
+
+fn progress_event(
+    request: &DownloadRequest,
+    bytes: u64,
+    offset: u64,
+    total: Option<u64>,
+    started: Instant,
+) -> Event {
+    let elapsed = started.elapsed().as_secs_f64();
+    let bytes_per_sec = if elapsed > 0.0 {
+        ((bytes - offset) as f64 / elapsed) as u64
+    } else {
+        0
+    };
+    Event::DownloadProgress {
+        url: request.url.clone(),
+        bytes,
+        total,
+        bytes_per_sec,
+    }
+}
+
+fn is_retryable_status(code: u16) -> bool {
+    matches!(code, 408 | 425 | 429) || code >= 500
+}
+
+fn content_range_start(value: &str) -> Option<u64> {
+    let range = value.trim().strip_prefix("bytes")?.trim_start();
+    let (start, _) = range.split_once('-')?;
+    start.trim().parse().ok()
+}
+
+fn partial_len(path: &Path) -> u64 {
+    fs::metadata(path).map(|meta| meta.len()).unwrap_or(0)
+}
+
+fn truncate_partial(path: &Path) -> std::result::Result<(), AttemptError> {
+    match fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(AttemptError::Fatal(Error::PreflightFailed {
+            message: format!(
+                "Failed to discard partial download {}: {err}",
+                path.display()
+            ),
+        })),
+    }
+}
+
+fn open_partial(path: &Path, append: bool) -> std::result::Result<File, AttemptError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|err| {
+            AttemptError::Fatal(Error::PreflightFailed {
+                message: format!(
+                    "Failed to prepare download directory {}: {err}",
+                    parent.display()
+                ),
+            })
+        })?;
+    }
+    let result = if append {
+        OpenOptions::new().append(true).open(path)
+    } else {
+        File::create(path)
+    };
+    result.map_err(|err| {
+        AttemptError::Fatal(Error::PreflightFailed {
+            message: format!("Failed to open download target {}: {err}", path.display()),
+        })
+    })
+}
+
+fn build_agent(url: &str) -> Result<ureq::Agent> {
+    let mut builder = ureq::AgentBuilder::new()
+        .timeout_connect(CONNECT_TIMEOUT)
+        .timeout_read(READ_TIMEOUT);
+
+    if let Some(proxy) = proxy_for_url(url, |key| env::var(key).ok()) {
+        let parsed = ureq::Proxy::new(&proxy).map_err(|err| Error::PreflightFailed {
+            message: format!(
+                "Invalid proxy `{proxy}`: {err}. Check HTTPS_PROXY/HTTP_PROXY/ALL_PROXY."
+            ),
+        })?;
+        builder = builder.proxy(parsed);
+    }
+
+    if let Some(bundle) = ca_bundle_path() {
+        builder = builder.tls_config(tls_config_with_bundle(&bundle)?);
+    }
+
+    Ok(builder.build())
+}
+
+/// Proxy URL for `url` based on the conventional environment variables.
+fn proxy_for_url(url: &str, lookup: impl Fn(&str) -> Option<String>) -> Option<String> {
+    let (scheme, host) = split_url(url)?;
+    let no_proxy = lookup("NO_PROXY")
+        .or_else(|| lookup("no_proxy"))
+        .unwrap_or_default();
+    if bypasses_proxy(&host, &no_proxy) {
+        return None;
+    }
+
+    let keys: &[&str] = if scheme == "https" {
+        &["HTTPS_PROXY", "https_proxy", "ALL_PROXY", "all_proxy"]
+    } else {
+        &["HTTP_PROXY", "http_proxy", "ALL_PROXY", "all_proxy"]
+    };
+    keys.iter()
+        .filter_map(|key| lookup(key))
+        .map(|value| value.trim().to_string())
+        .find(|value| !value.is_empty())
+}
+
+fn split_url(url: &str) -> Option<(String, String)> {
+    let (scheme, rest) = url.split_once("://")?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let authority = authority.rsplit('@').next()?;
+    let host = if let Some(stripped) = authority.strip_prefix('[') {
+        stripped.split(']').next()?
+    } else {
+        authority.split(':').next()?
+    };
+    Some((scheme.to_ascii_lowercase(), host.to_ascii_lowercase()))
+}
+
+fn bypasses_proxy(host: &str, no_proxy: &str) -> bool {
+    no_proxy
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .any(|entry| {
+            if entry == "*" {
+                return true;
+            }
+            let entry = entry.trim_start_matches("*.").trim_start_matches('.');
+            let entry = match entry.rsplit_once(':') {
+                Some((name, port)) if port.chars().all(|ch| ch.is_ascii_digit()) => name,
+                _ => entry,
+            };
+            let entry = entry.to_ascii_lowercase();
+            host == entry || host.ends_with(&format!(".{entry}"))
+        })
+}
+
+fn ca_bundle_path() -> Option<PathBuf> {
+    ["CASTRA_CA_BUNDLE", "SSL_CERT_FILE"]
+        .into_iter()
+        .filter_map(env::var_os)
+        .find(|value| !value.is_empty())
+        .map(PathBuf::from)
+}
+
+fn tls_config_with_bundle(path: &Path) -> Result<Arc<rustls::ClientConfig>> {
+    let invalid = |detail: String| Error::PreflightFailed {
+        message: format!(
+            "Failed to load CA bundle {}: {detail}. Point CASTRA_CA_BUNDLE at a PEM file.",
+            path.display()
+        ),
+    };
+
+    let certs = CertificateDer::pem_file_iter(path)
+        .map_err(|err| invalid(err.to_string()))?
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|err| invalid(err.to_string()))?;
+
+    let mut roots = rustls::RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    };
+    let (added, _) = roots.add_parsable_certificates(certs);
+    if added == 0 {
+        return Err(invalid("no usable certificates found".to_string()));
+    }
+
+    let config = rustls::ClientConfig::builder_with_provider(
+        rustls::crypto::ring::default_provider().into(),
+    )
+    .with_protocol_versions(&[&rustls::version::TLS12, &rustls::version::TLS13])
+    .map_err(|err| invalid(err.to_string()))?
+    .with_root_certificates(roots)
+    .with_no_client_auth();
+    Ok(Arc::new(config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+    use tempfile::tempdir;
+
+    fn lookup(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let map: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |key| map.get(key).cloned()
+    }
+
+    #[test]
+    fn proxy_selection_honours_scheme_and_no_proxy() {
+        let vars = lookup(&[
+            ("HTTPS_PROXY", "http://proxy:3128"),
+            ("http_proxy", "http://plain:8080"),
+            ("NO_PROXY", "internal.example, .corp:443"),
+        ]);
+        assert_eq!(
+            proxy_for_url("https://github.com/x", &vars).as_deref(),
+            Some("http://proxy:3128")
+        );
+        assert_eq!(
+            proxy_for_url("http://github.com/x", &vars).as_deref(),
+            Some("http://plain:8080")
+        );
+        assert_eq!(proxy_for_url("https://internal.example/x", &vars), None);
+        assert_eq!(proxy_for_url("https://mirror.corp:8443/x", &vars), None);
+        assert_eq!(
+            proxy_for_url("https://notinternal.example/x", &vars).as_deref(),
+            Some("http://proxy:3128")
+        );
+        assert_eq!(
+            proxy_for_url(
+                "https://host/x",
+                lookup(&[("HTTPS_PROXY", "p"), ("no_proxy", "*")])
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn content_range_start_parses_offsets() {
+        assert_eq!(content_range_start("bytes 100-199/200"), Some(100));
+        assert_eq!(content_range_start("bytes */200"), None);
+    }
+
+    fn read_request(stream: &mut std::net::TcpStream) -> Vec<String> {
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut lines = Vec::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            let line = line.trim_end().to_string();
+            if line.is_empty() {
+                break;
+            }
+            lines.push(line);
+        }
+        lines
+    }
+
+    #[test]
+    fn interrupted_download_resumes_with_range_request() {
+        let body: Vec<u8> = (0..4096u32).map(|i| (i % 251) as u8).collect();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server_body = body.clone();
+        let server = thread::spawn(move || {
+            let mut requests = Vec::new();
+
+            let (mut first, _) = listener.accept().unwrap();
+            requests.push(read_request(&mut first));
+            write!(
+                first,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                server_body.len()
+            )
+            .unwrap();
+            first.write_all(&server_body[..1000]).unwrap();
+            drop(first);
+
+            let (mut second, _) = listener.accept().unwrap();
+            requests.push(read_request(&mut second));
+            write!(
+                second,
+                "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes 1000-{}/{}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                server_body.len() - 1,
+                server_body.len(),
+                server_body.len() - 1000
+            )
+            .unwrap();
+            second.write_all(&server_body[1000..]).unwrap();
+            requests
+        });
+
+        let temp = tempdir().unwrap();
+        let mut request = DownloadRequest::new(
+            format!("http://127.0.0.1:{port}/image.qcow2"),
+            temp.path().join("image.qcow2.partial"),
+        )
+        .with_expected_size(body.len() as u64);
+        request.retry.initial_backoff = Duration::from_millis(10);
+
+        let mut events = Vec::new();
+        let bytes = temp_env::with_var("NO_PROXY", Some("127.0.0.1"), || {
+            download_resumable(&request, &mut |event| events.push(event)).expect("download")
+        });
+
+        assert_eq!(bytes, body.len() as u64);
+        assert_eq!(fs::read(&request.partial).unwrap(), body);
+        let requests = server.join().unwrap();
+        assert!(
+            requests[1]
+                .iter()
+                .any(|line| line.eq_ignore_ascii_case("range: bytes=1000-"))
+        );
+        assert!(events.iter().any(|event| matches!(
+            event,
+            Event::DownloadProgress { bytes, total: Some(4096), .. } if *bytes == 4096
+        )));
+    }
+}
@@ -1,8 +1,9 @@
 use std::path::PathBuf;
 
-use crate::config::BootstrapMode;
+use crate::config::{BootstrapMode, BootstrapRunIf, HookPoint};
 
 use super::diagnostics::Severity;
+use super::transport::OutputStream;
 
 /// Structured event emitted during long-running operations.
 #[derive(Debug, Clone)]
@@ -107,6 +108,8 @@ pub enum Event {
         script_path: Option<PathBuf>,
         /// Resolved payload directory when present.
         payload_path: Option<PathBuf>,
+        /// Commit a git payload was exported from.
+        payload_commit: Option<String>,
         /// Total payload bytes if the directory exists.
         payload_bytes: Option<u64>,
         /// Handshake wait in seconds when the plan would run.
@@ -125,6 +128,8 @@ pub enum Event {
         metadata_path: Option<PathBuf>,
         /// Non-fatal warnings associated with the plan.
         warnings: Vec<String>,
+        /// Per-stage breakdown for staged pipelines; empty for a single script.
+        stages: Vec<BootstrapPlanStage>,
     },
     /// Host-side bootstrap pipeline started for a VM.
     BootstrapStarted {
@@ -141,6 +146,9 @@ pub enum Event {
     BootstrapStep {
         /// Name of the VM.
         vm: String,
+        /// Pipeline stage the step belongs to; `None` for VM-wide steps and
+        /// single-script pipelines.
+        stage: Option<String>,
         /// Step within the bootstrap pipeline being reported.
         step: BootstrapStepKind,
         /// Outcome of the step execution.
@@ -149,6 +157,11 @@ pub enum Event {
         duration_ms: u64,
         /// Optional human-readable detail about the step outcome.
         detail: Option<String>,
+        /// Payload bytes a transfer found already present on the guest and
+        /// did not resend; `None` for other steps and full uploads.
+        bytes_skipped: Option<u64>,
+        /// Tries the step took, counting retries from `[bootstrap.steps]`.
+        attempts: u32,
     },
     /// Host-side bootstrap pipeline completed successfully or determined it was unnecessary.
     BootstrapCompleted {
@@ -158,7 +171,7 @@ pub enum Event {
         status: BootstrapStatus,
         /// Milliseconds spent across the bootstrap run.
         duration_ms: u64,
-        /// Legacy stamp identifier (reserved for compatibility; always `None`).
+        /// Identifier of the stamp recorded for this run, when one was persisted.
         stamp: Option<String>,
     },
     /// Host-side bootstrap pipeline failed.
@@ -170,6 +183,68 @@ pub enum Event {
         /// Error message describing the failure cause.
         error: String,
     },
+    /// A `[workflows]` host command is about to run.
+    HookStarted {
+        /// Lifecycle point the hook is attached to.
+        hook: HookPoint,
+        /// Name of the VM the hook runs for.
+        vm: String,
+        /// Command line after template rendering.
+        command: String,
+    },
+    /// A `[workflows]` host command exited successfully.
+    HookCompleted {
+        /// Lifecycle point the hook is attached to.
+        hook: HookPoint,
+        /// Name of the VM the hook ran for.
+        vm: String,
+        /// Command line after template rendering.
+        command: String,
+        /// Milliseconds spent running the command.
+        duration_ms: u64,
+    },
+    /// A `collect` rule copied a guest path to the host.
+    ArtifactsCollected {
+        /// Name of the VM the files came from.
+        vm: String,
+        /// Guest path named by the rule.
+        guest: String,
+        /// Host copy of the guest path.
+        path: PathBuf,
+        /// Files collected, as host paths.
+        files: Vec<PathBuf>,
+        /// Total size of `files`.
+        bytes: u64,
+    },
+    /// A `collect` rule could not copy its guest path.
+    ArtifactCollectionFailed {
+        /// Name of the VM.
+        vm: String,
+        /// Guest path named by the rule.
+        guest: String,
+        /// Error message describing the failure cause.
+        error: String,
+    },
+    /// A line a guest command printed during `exec`.
+    GuestOutput {
+        /// Name of the VM the command runs on.
+        vm: String,
+        /// Stream the line arrived on.
+        stream: OutputStream,
+        /// Line without its trailing newline.
+        line: String,
+    },
+    /// Progress emitted while downloading a managed image.
+    DownloadProgress {
+        /// Source URL being fetched.
+        url: String,
+        /// Bytes present locally, including any resumed partial download.
+        bytes: u64,
+        /// Total expected size when known.
+        total: Option<u64>,
+        /// Transfer rate for the current attempt in bytes per second.
+        bytes_per_sec: u64,
+    },
     /// Progress emitted during cleanup operations.
     CleanupProgress {
         /// Path targeted by the cleanup step.
@@ -190,6 +265,9 @@ pub enum BootstrapTrigger {
     Auto,
     /// Run explicitly requested regardless of automatic heuristics.
     Always,
+    /// Rerun requested through `castra bootstrap` on a VM that was already
+    /// running.
+    Manual,
 }
 
 /// Kind of step recorded during bootstrap execution.
@@ -234,6 +312,8 @@ pub enum BootstrapPlanAction {
     WouldRun,
     /// Pipeline would be skipped (skip mode or auto without a script).
     WouldSkip,
+    /// Pipeline inputs match the VM's durable stamp; nothing to do.
+    UpToDate,
     /// Pipeline would fail due to configuration errors.
     Error,
 }
@@ -244,6 +324,7 @@ impl BootstrapPlanAction {
         match self {
             BootstrapPlanAction::WouldRun => "would run",
             BootstrapPlanAction::WouldSkip => "would skip",
+            BootstrapPlanAction::UpToDate => "up to date",
             BootstrapPlanAction::Error => "would error",
         }
     }
@@ -325,6 +406,20 @@ fn shell_quote(input: &str) -> String {
     result
 }
 
+/// One stage of a staged bootstrap pipeline as surfaced in a plan.
+#[derive(Debug, Clone)]
+pub struct BootstrapPlanStage {
+    pub name: String,
+    pub script_path: PathBuf,
+    pub payload_path: Option<PathBuf>,
+    /// Commit a git payload was exported from.
+    pub payload_commit: Option<String>,
+    pub run_if: BootstrapRunIf,
+    pub artifact_hash: String,
+    /// Why the stage would be skipped because its effects already exist.
+    pub carried_over: Option<String>,
+}
+
 /// Verification configuration surfaced in a bootstrap plan.
 #[derive(Debug, Clone)]
 pub struct BootstrapPlanVerify {
@@ -409,6 +504,8 @@ pub enum CleanupKind {
     Overlay,
     /// Orchestrator pid files.
     PidFile,
+    /// Unreferenced blobs in the shared image store.
+    StoreImage,
 }
 
 impl CleanupKind {
@@ -420,6 +517,7 @@ impl CleanupKind {
             CleanupKind::Handshakes => "handshakes",
             CleanupKind::Overlay => "overlay",
             CleanupKind::PidFile => "pid-file",
+            CleanupKind::StoreImage => "store-image",
         }
     }
 }
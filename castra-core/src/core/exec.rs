@@ -0,0 +1,267 @@
+//! `exec`: run one command on several guests at once.
+//!
+//! Guests are reached the way bootstrap reaches them: the same resolved
+//! [`SshConfig`] and the `[bootstrap].transport` backend. Output lines are
+//! reported as [`Event::GuestOutput`] as they arrive, so callers can prefix
+//! and interleave them per VM.
+
+use std::time::{Duration, Instant};
+
+use crate::config::VmDefinition;
+
+use super::bootstrap;
+use super::events::Event;
+use super::outcome::GuestCommandResult;
+use super::reporter::Reporter;
+use super::transport::{Transport, TransportError};
+
+/// Run `command` on every VM in `vms` concurrently, at most `max_parallel` at
+/// a time like bootstrap, returning one result per VM in the same order.
+pub fn exec_vms(
+    vms: &[&VmDefinition],
+    command: &[String],
+    timeout: Option<Duration>,
+    max_parallel: Option<usize>,
+    transport: &dyn Transport,
+    reporter: &mut dyn Reporter,
+) -> Vec<GuestCommandResult> {
+    let script = guest_script(command);
+    let workers = bootstrap::worker_count(max_parallel, vms.len());
+    bootstrap::run_pool(vms, workers, reporter, |vm, emit_event| {
+        exec_vm(vm, &script, timeout, transport, emit_event)
+    })
+}
+
+/// Run `script` on `vm`, reporting each output line.
+pub fn exec_vm(
+    vm: &VmDefinition,
+    script: &str,
+    timeout: Option<Duration>,
+    transport: &dyn Transport,
+    emit_event: &mut dyn FnMut(Event),
+) -> GuestCommandResult {
+    let start = Instant::now();
+    let mut result = GuestCommandResult {
+        vm: vm.name.clone(),
+        exit_code: None,
+        duration_ms: 0,
+        error: None,
+        stdout: String::new(),
+        stderr: String::new(),
+    };
+    let ssh = match bootstrap::ssh_for_vm(vm) {
+        Ok(ssh) => ssh,
+        Err(err) => {
+            result.error = Some(err);
+            return result;
+        }
+    };
+    let outcome = transport.exec_streaming(&ssh, script, "", timeout, &mut |stream, line| {
+        emit_event(Event::GuestOutput {
+            vm: vm.name.clone(),
+            stream,
+            line: line.to_string(),
+        })
+    });
+    match outcome {
+        Ok(output) => {
+            result.exit_code = Some(0);
+            result.stdout = output.stdout;
+            result.stderr = output.stderr;
+        }
+        Err(TransportError::Exit {
+            code,
+            stdout,
+            stderr,
+            ..
+        }) => {
+            result.exit_code = code;
+            if code.is_none() {
+                result.error = Some("command was terminated by a signal".to_string());
+            }
+            result.stdout = stdout;
+            result.stderr = stderr;
+        }
+        Err(err) => result.error = Some(err.to_string()),
+    }
+    result.duration_ms = start.elapsed().as_millis() as u64;
+    result
+}
+
+/// `command` joined with spaces like `ssh` joins its arguments, escaped for
+/// the double quotes transports wrap their `sh -lc` script in, so the guest
+/// shell sees it unchanged.
+pub fn guest_script(command: &[String]) -> String {
+    let mut script = String::new();
+    for ch in command.join(" ").chars() {
+        if matches!(ch, '"' | '\\' | '$' | '`') {
+            script.push('\\');
+        }
+        script.push(ch);
+    }
+    script
+}
+
+/// Whether `name` matches `pattern`, where `*` stands for any run of
+/// characters and `?` for exactly one.
+pub fn matches_vm_pattern(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    // Position after the last `*` and the name position it was retried from.
+    let mut star: Option<(usize, usize)> = None;
+    let (mut p, mut n) = (0, 0);
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p + 1, n));
+                p += 1;
+            }
+            Some(&ch) if ch == '?' || ch == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((after_star, from)) => {
+                    p = after_star;
+                    n = from + 1;
+                    star = Some((after_star, from + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&ch| ch == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::transport::{ExecOutput, OutputStream, SshConfig};
+    use std::path::Path;
+    use std::process::Command;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Transport that records how many commands run at once.
+    #[derive(Default)]
+    struct CountingTransport {
+        running: AtomicUsize,
+        peak: AtomicUsize,
+    }
+
+    impl Transport for CountingTransport {
+        fn name(&self) -> &'static str {
+            "counting"
+        }
+
+        fn connect(
+            &self,
+            _ssh: &SshConfig,
+            _timeout: Option<Duration>,
+        ) -> Result<ExecOutput, TransportError> {
+            Ok(ExecOutput::default())
+        }
+
+        fn exec_streaming(
+            &self,
+            _ssh: &SshConfig,
+            _script: &str,
+            _input: &str,
+            _timeout: Option<Duration>,
+            on_line: &mut dyn FnMut(OutputStream, &str),
+        ) -> Result<ExecOutput, TransportError> {
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(running, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(20));
+            on_line(OutputStream::Stdout, "ok");
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            Ok(ExecOutput::default())
+        }
+
+        fn upload(
+            &self,
+            _ssh: &SshConfig,
+            _local: &Path,
+            _remote: &str,
+            _timeout: Option<Duration>,
+        ) -> Result<ExecOutput, TransportError> {
+            Ok(ExecOutput::default())
+        }
+
+        fn download(
+            &self,
+            _ssh: &SshConfig,
+            _remote: &str,
+            _local: &Path,
+        ) -> Result<ExecOutput, TransportError> {
+            Ok(ExecOutput::default())
+        }
+    }
+
+    #[test]
+    fn exec_vms_runs_at_most_max_parallel_at_once() {
+        let vms: Vec<VmDefinition> = (0..5)
+            .map(|index| VmDefinition::for_test(&format!("web-{index}")))
+            .collect();
+        let targets: Vec<&VmDefinition> = vms.iter().collect();
+        let transport = CountingTransport::default();
+        struct Collect(Vec<Event>);
+        impl Reporter for Collect {
+            fn report(&mut self, event: Event) {
+                self.0.push(event);
+            }
+        }
+        let mut events = Collect(Vec::new());
+
+        let results = exec_vms(
+            &targets,
+            &["true".to_string()],
+            None,
+            Some(2),
+            &transport,
+            &mut events,
+        );
+
+        assert_eq!(transport.peak.load(Ordering::SeqCst), 2);
+        let names: Vec<&str> = results.iter().map(|result| result.vm.as_str()).collect();
+        assert_eq!(names, ["web-0", "web-1", "web-2", "web-3", "web-4"]);
+        assert!(results.iter().all(|result| result.exit_code == Some(0)));
+        assert_eq!(events.0.len(), 5);
+    }
+
+    #[test]
+    fn vm_patterns_match_wildcards() {
+        for (pattern, name, expected) in [
+            ("web-*", "web-0", true),
+            ("web-*", "web-12", true),
+            ("web-*", "db-0", false),
+            ("web-?", "web-12", false),
+            ("*-1", "api-1", true),
+            ("*", "anything", true),
+            ("web", "web-0", false),
+            ("w*b*-*", "web-cache-3", true),
+            ("web-*-x", "web-1-y", false),
+        ] {
+            assert_eq!(
+                matches_vm_pattern(pattern, name),
+                expected,
+                "{pattern} vs {name}"
+            );
+        }
+    }
+
+    #[test]
+    fn guest_script_survives_the_transport_quoting() {
+        let command = [
+            "printf".to_string(),
+            "'%s|%s\\n'".to_string(),
+            "\"$0\" `echo ok`".to_string(),
+        ];
+        // Transports hand the guest `sh -lc "<script>"`.
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(format!("sh -lc \"{}\" castra", guest_script(&command)))
+            .output()
+            .expect("run sh");
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "castra|ok\n");
+    }
+}
@@ -0,0 +1,227 @@
+//! Bootstrap payloads exported from a local git repository.
+//!
+//! `rev` is resolved to a commit every time a bootstrap is planned, and the
+//! requested tree is exported with `git archive` into a cache keyed by its
+//! git tree id. Only committed files reach the guest, and a tree that did not
+//! change between commits is exported once.
+
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::config::GitPayload;
+
+/// A payload tree exported at a pinned commit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportedPayload {
+    /// Commit `rev` resolved to.
+    pub commit: String,
+    /// Host directory holding the exported tree.
+    pub dir: PathBuf,
+}
+
+/// Resolve `payload.rev` and export its tree unless it is already cached.
+pub fn export(payload: &GitPayload) -> Result<ExportedPayload, String> {
+    let repo = &payload.repo;
+    let fail = |err: String| format!("Git payload {}: {err}", repo.display());
+    let commit = git(
+        repo,
+        &[
+            "rev-parse",
+            "--verify",
+            &format!("{}^{{commit}}", payload.rev),
+        ],
+    )
+    .map_err(|err| fail(format!("cannot resolve `{}`: {err}", payload.rev)))?;
+    let tree_spec = match &payload.subdir {
+        Some(subdir) => format!("{commit}:{}", tree_path(subdir)),
+        None => format!("{commit}^{{tree}}"),
+    };
+    let tree = git(repo, &["rev-parse", "--verify", &tree_spec]).map_err(|err| {
+        fail(match &payload.subdir {
+            Some(subdir) => format!("`{}` not found at {commit}: {err}", subdir.display()),
+            None => err,
+        })
+    })?;
+
+    let dir = payload.cache_dir.join(&tree);
+    if !dir.is_dir() {
+        extract(repo, &tree, &payload.cache_dir, &dir).map_err(fail)?;
+    }
+    Ok(ExportedPayload { commit, dir })
+}
+
+/// `subdir` as a git tree path: `/`-separated, without `.` components.
+fn tree_path(subdir: &Path) -> String {
+    subdir
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part.to_string_lossy()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Unpack `tree` into `dir` through a partial directory, so an interrupted
+/// export is never mistaken for a cached one.
+fn extract(repo: &Path, tree: &str, cache_dir: &Path, dir: &Path) -> Result<(), String> {
+    let partial = cache_dir.join(format!(".{tree}.partial"));
+    let _ = fs::remove_dir_all(&partial);
+    fs::create_dir_all(&partial)
+        .map_err(|err| format!("failed to create {}: {err}", partial.display()))?;
+
+    let result = archive_into(repo, tree, &partial).and_then(|()| {
+        match fs::rename(&partial, dir) {
+            Ok(()) => Ok(()),
+            // Another run exported the same tree first.
+            Err(_) if dir.is_dir() => Ok(()),
+            Err(err) => Err(format!("failed to move export to {}: {err}", dir.display())),
+        }
+    });
+    let _ = fs::remove_dir_all(&partial);
+    result
+}
+
+/// `git archive <tree> | tar -x -C <dest>`.
+fn archive_into(repo: &Path, tree: &str, dest: &Path) -> Result<(), String> {
+    let mut archive = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(["archive", "--format=tar", tree])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(spawn_error("git"))?;
+    let stdout = archive.stdout.take().expect("piped stdout");
+    let tar = Command::new("tar")
+        .arg("-x")
+        .arg("-C")
+        .arg(dest)
+        .stdin(stdout)
+        .stderr(Stdio::piped())
+        .output();
+    let mut archive_stderr = String::new();
+    if let Some(mut stderr) = archive.stderr.take() {
+        let _ = stderr.read_to_string(&mut archive_stderr);
+    }
+    let archive_status = archive
+        .wait()
+        .map_err(|err| format!("failed to wait for `git archive`: {err}"))?;
+    if !archive_status.success() {
+        return Err(format!("`git archive` failed: {}", archive_stderr.trim()));
+    }
+    let tar = tar.map_err(spawn_error("tar"))?;
+    if !tar.status.success() {
+        return Err(format!(
+            "`tar` failed to unpack the export: {}",
+            String::from_utf8_lossy(&tar.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+/// Run `git -C repo <args>` and return its trimmed stdout.
+fn git(repo: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .output()
+        .map_err(spawn_error("git"))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn spawn_error(program: &'static str) -> impl Fn(io::Error) -> String {
+    move |err| match err.kind() {
+        io::ErrorKind::NotFound => format!("`{program}` not found in PATH"),
+        _ => format!("failed to run `{program}`: {err}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn run_git(repo: &Path, args: &[&str]) -> String {
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(repo)
+            .args([
+                "-c",
+                "user.name=castra",
+                "-c",
+                "user.email=castra@example.com",
+            ])
+            .args(args)
+            .output()
+            .expect("run git");
+        assert!(output.status.success(), "git {args:?}: {output:?}");
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    }
+
+    #[test]
+    fn export_pins_committed_subdir_and_reuses_unchanged_trees()
+    -> Result<(), Box<dyn std::error::Error>> {
+        let temp_dir = TempDir::new()?;
+        let repo = temp_dir.path().join("service");
+        fs::create_dir_all(repo.join("deploy/conf"))?;
+        run_git(&repo, &["init", "-q", "-b", "main"]);
+        fs::write(repo.join("deploy/conf/app.conf"), "port = 80\n")?;
+        fs::write(repo.join("README"), "service\n")?;
+        run_git(&repo, &["add", "."]);
+        run_git(&repo, &["commit", "-q", "-m", "first"]);
+        let first_commit = run_git(&repo, &["rev-parse", "HEAD"]);
+        fs::write(repo.join("deploy/junk.tmp"), "uncommitted\n")?;
+        fs::write(repo.join("deploy/conf/app.conf"), "port = 81\n")?;
+
+        let payload = GitPayload {
+            repo: repo.clone(),
+            rev: "main".to_string(),
+            subdir: Some(PathBuf::from("deploy")),
+            cache_dir: temp_dir.path().join("cache"),
+        };
+        let first = export(&payload)?;
+        assert_eq!(first.commit, first_commit);
+        assert_eq!(
+            fs::read_to_string(first.dir.join("conf/app.conf"))?,
+            "port = 80\n"
+        );
+        assert!(!first.dir.join("junk.tmp").exists());
+        assert!(!first.dir.join("README").exists());
+
+        // A commit that leaves `deploy` alone reuses the cached export.
+        fs::write(repo.join("README"), "service v2\n")?;
+        run_git(&repo, &["commit", "-q", "-m", "docs", "README"]);
+        let second = export(&payload)?;
+        assert_ne!(second.commit, first.commit);
+        assert_eq!(second.dir, first.dir);
+
+        run_git(
+            &repo,
+            &["commit", "-q", "-m", "port", "deploy/conf/app.conf"],
+        );
+        let third = export(&payload)?;
+        assert_ne!(third.dir, first.dir);
+        assert_eq!(
+            fs::read_to_string(third.dir.join("conf/app.conf"))?,
+            "port = 81\n"
+        );
+
+        let missing = export(&GitPayload {
+            rev: "no-such-branch".to_string(),
+            ..payload.clone()
+        })
+        .unwrap_err();
+        assert!(
+            missing.contains("cannot resolve `no-such-branch`"),
+            "{missing}"
+        );
+        Ok(())
+    }
+}
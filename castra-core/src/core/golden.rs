@@ -0,0 +1,280 @@
+//! Golden images: bootstrapped overlays flattened into reusable base images.
+//!
+//! A golden image is keyed by the digest of the base image it was built from
+//! plus the bootstrap `artifact_hash`, and recorded in the shared image store
+//! under the `golden/<base>-<artifact>` tag. When a later `up` finds a matching
+//! tag the VM boots straight from the golden image, and a durable bootstrap
+//! stamp lets `auto` mode report it up-to-date instead of rerunning.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::config::{
+    BaseImageProvenance, BaseImageSource, BootstrapMode, DEFAULT_ALPINE_IMAGE_FILENAME,
+    VmDefinition,
+};
+use crate::error::{Error, Result};
+
+use super::bootstrap::{artifact_hash_for_vm, record_durable_stamp};
+use super::image_store::{ImageLinkKind, ImageStore, compute_sha256_hex};
+
+const GOLDEN_TAG_PREFIX: &str = "golden";
+
+/// Inputs identifying the golden image a VM bakes into.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GoldenKey {
+    /// Store digest (sha256) of the base image the overlay was layered on.
+    pub base_digest: String,
+    /// Bootstrap artifact hash the image was provisioned with.
+    pub artifact_hash: String,
+}
+
+impl GoldenKey {
+    pub fn new(base_digest: impl Into<String>, artifact_hash: impl Into<String>) -> Self {
+        Self {
+            base_digest: base_digest.into(),
+            artifact_hash: artifact_hash.into(),
+        }
+    }
+
+    /// Image store tag recording the golden image for this key.
+    pub fn tag(&self) -> String {
+        format!(
+            "{GOLDEN_TAG_PREFIX}/{}-{}",
+            self.base_digest, self.artifact_hash
+        )
+    }
+}
+
+/// Compute the golden key for `vm`.
+///
+/// Returns `None` when the VM has no resolvable bootstrap script or its base
+/// image is not available yet (e.g. the default Alpine image was never fetched).
+pub fn key_for_vm(vm: &VmDefinition, store: &ImageStore) -> Result<Option<GoldenKey>> {
+    let Some(artifact_hash) = artifact_hash_for_vm(vm) else {
+        return Ok(None);
+    };
+    let base_digest = match vm.base_image.provenance() {
+        BaseImageProvenance::DefaultAlpine => store.resolve_tag(DEFAULT_ALPINE_IMAGE_FILENAME)?,
+        BaseImageProvenance::Explicit => {
+            let path = vm.base_image.path();
+            if path.is_file() {
+                Some(compute_sha256_hex(path)?)
+            } else {
+                None
+            }
+        }
+    };
+    Ok(base_digest.map(|base_digest| GoldenKey::new(base_digest, artifact_hash)))
+}
+
+/// Golden image digest previously baked for `vm`, when auto-reuse applies.
+///
+/// Only VMs running bootstrap in `auto` mode reuse golden images; `always`
+/// explicitly asks for a fresh run and `skip` never provisions.
+pub fn lookup_for_vm(vm: &VmDefinition, store: &ImageStore) -> Result<Option<(GoldenKey, String)>> {
+    if vm.bootstrap.mode != BootstrapMode::Auto {
+        return Ok(None);
+    }
+    let Some(key) = key_for_vm(vm, store)? else {
+        return Ok(None);
+    };
+    Ok(store.resolve_tag(&key.tag())?.map(|digest| (key, digest)))
+}
+
+/// Workspace path a VM's golden image is linked to.
+pub fn golden_link_path(state_root: &Path, vm_name: &str) -> PathBuf {
+    state_root
+        .join("images")
+        .join(GOLDEN_TAG_PREFIX)
+        .join(format!("{vm_name}.qcow2"))
+}
+
+/// Point `vm` at the golden image `digest` and stamp it as already bootstrapped
+/// so `auto` mode reports it up-to-date.
+pub fn adopt(
+    vm: &mut VmDefinition,
+    store: &ImageStore,
+    state_root: &Path,
+    digest: &str,
+) -> Result<ImageLinkKind> {
+    let link = golden_link_path(state_root, &vm.name);
+    let kind = store.link_into(digest, &link)?;
+    vm.base_image = BaseImageSource::from_explicit(link);
+    record_durable_stamp(state_root, vm, digest)?;
+    Ok(kind)
+}
+
+/// Flatten the overlay of a stopped VM into a standalone qcow2 held by the
+/// store, recording each of `tags` against the resulting digest.
+pub fn bake(
+    vm: &VmDefinition,
+    qemu_img: &Path,
+    store: &ImageStore,
+    tags: &[String],
+) -> Result<String> {
+    if !vm.overlay.is_file() {
+        return Err(Error::PreflightFailed {
+            message: format!(
+                "Overlay {} for VM `{}` does not exist; launch the VM before baking it.",
+                vm.overlay.display(),
+                vm.name
+            ),
+        });
+    }
+
+    let staging = store.staging_path(&format!("golden-{}.qcow2", vm.name));
+    if let Some(parent) = staging.parent() {
+        fs::create_dir_all(parent).map_err(|err| Error::PreflightFailed {
+            message: format!(
+                "Failed to prepare image store staging directory {}: {err}",
+                parent.display()
+            ),
+        })?;
+    }
+    let _ = fs::remove_file(&staging);
+
+    let output = Command::new(qemu_img)
+        .arg("convert")
+        .arg("-O")
+        .arg("qcow2")
+        .arg(&vm.overlay)
+        .arg(&staging)
+        .output()
+        .map_err(|err| Error::PreflightFailed {
+            message: format!(
+                "Failed to invoke `{}` while flattening overlay for VM `{}`: {err}",
+                qemu_img.display(),
+                vm.name
+            ),
+        })?;
+    if !output.status.success() {
+        let _ = fs::remove_file(&staging);
+        return Err(Error::PreflightFailed {
+            message: format!(
+                "`{}` exited with code {} while flattening {} for VM `{}`: {}",
+                qemu_img.display(),
+                output.status.code().unwrap_or(-1),
+                vm.overlay.display(),
+                vm.name,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        });
+    }
+
+    let digest = store.import(&staging, None, true)?;
+    for tag in tags {
+        store.tag(tag, &digest)?;
+    }
+    Ok(digest)
+}
+
+/// Validate a user-supplied image name used as a store tag.
+pub fn validate_image_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || matches!(ch, '-' | '_' | '.'));
+    if !valid {
+        return Err(Error::PreflightFailed {
+            message: format!(
+                "Invalid image name `{name}`; use letters, digits, `-`, `_` or `.` (not starting with `.`)."
+            ),
+        });
+    }
+    if name == DEFAULT_ALPINE_IMAGE_FILENAME {
+        return Err(Error::PreflightFailed {
+            message: format!("Image name `{name}` is reserved for the managed Alpine base image."),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::VmBootstrapConfig;
+    use crate::core::bootstrap::load_stamp;
+    use tempfile::tempdir;
+
+    fn vm_with_script(root: &Path, base: PathBuf) -> VmDefinition {
+        let script = root.join("bootstrap.sh");
+        fs::write(&script, "#!/bin/sh\necho hi\n").unwrap();
+        VmDefinition {
+            base_image: BaseImageSource::from_explicit(base),
+            overlay: root.join("devbox.qcow2"),
+            bootstrap: VmBootstrapConfig {
+                mode: BootstrapMode::Auto,
+                script: Some(script),
+                bake: true,
+                ..VmBootstrapConfig::default()
+            },
+            ..VmDefinition::for_test("devbox")
+        }
+    }
+
+    #[test]
+    fn golden_lookup_keys_on_base_digest_and_artifact_hash() {
+        let temp = tempdir().unwrap();
+        let base = temp.path().join("base.qcow2");
+        fs::write(&base, b"base image").unwrap();
+        let store = ImageStore::new(temp.path().join("store"));
+        let vm = vm_with_script(temp.path(), base.clone());
+
+        let key = key_for_vm(&vm, &store).unwrap().expect("key");
+        assert_eq!(key.base_digest, compute_sha256_hex(&base).unwrap());
+        assert!(key.tag().starts_with("golden/"));
+        assert!(lookup_for_vm(&vm, &store).unwrap().is_none());
+
+        let baked = temp.path().join("baked.qcow2");
+        fs::write(&baked, b"baked image").unwrap();
+        let digest = store.import(&baked, Some(&key.tag()), false).unwrap();
+        let (_, found) = lookup_for_vm(&vm, &store).unwrap().expect("golden hit");
+        assert_eq!(found, digest);
+
+        let mut always = vm.clone();
+        always.bootstrap.mode = BootstrapMode::Always;
+        assert!(lookup_for_vm(&always, &store).unwrap().is_none());
+
+        let script = vm.bootstrap.script.clone().unwrap();
+        fs::write(&script, "#!/bin/sh\necho changed\n").unwrap();
+        assert!(lookup_for_vm(&vm, &store).unwrap().is_none());
+    }
+
+    #[test]
+    fn adopt_links_golden_image_and_stamps_it_durable() {
+        let temp = tempdir().unwrap();
+        let base = temp.path().join("base.qcow2");
+        fs::write(&base, b"base image").unwrap();
+        let store = ImageStore::new(temp.path().join("store"));
+        let baked = temp.path().join("baked.qcow2");
+        fs::write(&baked, b"baked image").unwrap();
+        let digest = store.import(&baked, None, false).unwrap();
+
+        let mut vm = vm_with_script(temp.path(), base);
+        let state_root = temp.path().join("state");
+        adopt(&mut vm, &store, &state_root, &digest).unwrap();
+
+        let stamp = load_stamp(&state_root, "devbox").expect("stamp");
+        assert!(stamp.durable);
+        assert_eq!(stamp.base_hash, digest);
+        assert_eq!(Some(stamp.artifact_hash), artifact_hash_for_vm(&vm));
+        assert_eq!(vm.base_image.provenance(), BaseImageProvenance::Explicit);
+        assert_eq!(
+            vm.base_image.path(),
+            golden_link_path(&state_root, "devbox")
+        );
+        assert_eq!(fs::read(vm.base_image.path()).unwrap(), b"baked image");
+    }
+
+    #[test]
+    fn image_names_reject_paths_and_reserved_tags() {
+        assert!(validate_image_name("web-base_1.2").is_ok());
+        assert!(validate_image_name("").is_err());
+        assert!(validate_image_name("../escape").is_err());
+        assert!(validate_image_name("golden/x").is_err());
+        assert!(validate_image_name(DEFAULT_ALPINE_IMAGE_FILENAME).is_err());
+    }
+}
@@ -0,0 +1,634 @@
+//! Bootstrap run history: the JSON log every run writes to
+//! `<log_root>/bootstrap/<vm>-<timestamp>.json`, listed, loaded back and
+//! compared.
+//!
+//! Logs written before script and payload digests were recorded still load;
+//! comparisons simply skip what they cannot know.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+use super::diagnostics::{Diagnostic, Severity};
+
+pub(crate) const LOG_SUBDIR: &str = "bootstrap";
+/// Minimum slowdown, in milliseconds, for a step to count as regressed.
+const REGRESSION_MIN_MS: u64 = 1000;
+/// Minimum after/before duration ratio for a step to count as regressed.
+const REGRESSION_RATIO: (u64, u64) = (3, 2);
+
+/// One bootstrap run as recorded on disk.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BootstrapRunLog {
+    pub vm: String,
+    pub artifact_hash: String,
+    pub base_hash: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stamp: Option<String>,
+    /// `success`, `noop` or `failed`.
+    pub status: String,
+    pub duration_ms: u64,
+    pub steps: Vec<StepRecord>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stages: Vec<StageRecord>,
+    pub script_source: String,
+    pub staged_script: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script_sha256: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payload_source: Option<String>,
+    /// Commit a git payload was exported from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payload_commit: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub staged_payload: Option<String>,
+    pub payload_bytes: u64,
+    /// sha256 of every payload file, keyed by payload-relative path.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub payload_files: BTreeMap<String, String>,
+    pub remote_dir: String,
+    pub ssh_user: String,
+    pub ssh_host: String,
+    pub ssh_port: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ssh_identity: Option<String>,
+    pub env: BTreeMap<String, String>,
+    /// Salted digests standing in for secret values.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env_secrets: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata_path: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StepRecord {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stage: Option<String>,
+    pub step: String,
+    pub status: String,
+    pub duration_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bytes_skipped: Option<u64>,
+    /// Tries the step took; only recorded when it was retried.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attempts: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StageRecord {
+    pub name: String,
+    pub run_if: String,
+    pub status: String,
+    pub artifact_hash: String,
+    pub script_source: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub script_sha256: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payload_source: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payload_commit: Option<String>,
+    pub payload_bytes: u64,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub payload_files: BTreeMap<String, String>,
+    pub remote_dir: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+}
+
+/// Where a run log lives and what it concluded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BootstrapRunSummary {
+    /// File stem, e.g. `web-1700000000`; accepted by [`find_run`].
+    pub id: String,
+    pub vm: String,
+    /// Unix seconds at which the log was written.
+    pub timestamp: u64,
+    pub status: String,
+    pub duration_ms: u64,
+    pub path: PathBuf,
+}
+
+/// How one entry differs between two runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Added,
+    Removed,
+    Modified,
+}
+
+/// What changed from one run of a VM to another.
+#[derive(Debug, Clone, Default)]
+pub struct BootstrapRunDiff {
+    pub artifact_hash_changed: bool,
+    pub base_hash_changed: bool,
+    pub scripts: Vec<ScriptChange>,
+    pub payload: Vec<PayloadChange>,
+    pub env: Vec<EnvChange>,
+    /// Every step of either run, in the order the later run took them.
+    pub steps: Vec<StepTiming>,
+    pub before_ms: u64,
+    pub after_ms: u64,
+}
+
+/// A script whose hash differs; `None` when the stage is absent from that run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptChange {
+    pub stage: Option<String>,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PayloadChange {
+    pub stage: Option<String>,
+    pub path: String,
+    pub change: Change,
+}
+
+/// A changed environment key. Secret values are never recorded, so only
+/// the fact that their digest moved is reported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvChange {
+    pub key: String,
+    pub change: Change,
+    pub secret: bool,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+/// Time spent in one step by each run; `None` when the run never took it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepTiming {
+    pub stage: Option<String>,
+    pub step: String,
+    pub before_ms: Option<u64>,
+    pub after_ms: Option<u64>,
+}
+
+impl StepTiming {
+    /// The step took at least a second longer and half again as long.
+    pub fn regressed(&self) -> bool {
+        match (self.before_ms, self.after_ms) {
+            (Some(before), Some(after)) => {
+                after.saturating_sub(before) >= REGRESSION_MIN_MS
+                    && after * REGRESSION_RATIO.1 >= before * REGRESSION_RATIO.0
+            }
+            _ => false,
+        }
+    }
+}
+
+impl BootstrapRunDiff {
+    pub fn regressions(&self) -> impl Iterator<Item = &StepTiming> {
+        self.steps.iter().filter(|step| step.regressed())
+    }
+}
+
+pub(crate) fn write_run_log(dir: &Path, log: &BootstrapRunLog) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_else(|_| Duration::from_secs(0))
+        .as_secs();
+    let filename = format!("{}-{}.json", log.vm, timestamp);
+    let path = dir.join(filename);
+    let payload = serde_json::to_vec_pretty(log).map_err(|err| {
+        io::Error::new(
+            io::ErrorKind::Other,
+            format!("Failed to serialize bootstrap log: {err}"),
+        )
+    })?;
+    fs::write(&path, payload)?;
+    Ok(path)
+}
+
+pub fn load_run(path: &Path) -> Result<BootstrapRunLog> {
+    let contents = fs::read(path).map_err(|source| Error::LogReadFailed {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    serde_json::from_slice(&contents).map_err(|err| Error::LogReadFailed {
+        path: path.to_path_buf(),
+        source: io::Error::new(io::ErrorKind::InvalidData, err),
+    })
+}
+
+/// Runs recorded under `log_root`, oldest first, limited to `vms` unless
+/// empty. Unreadable logs are skipped with a warning.
+pub fn list_runs(
+    log_root: &Path,
+    vms: &[String],
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<Vec<BootstrapRunSummary>> {
+    let mut runs = Vec::new();
+    for (id, vm, timestamp, path) in log_files(log_root)? {
+        if !vms.is_empty() && !vms.contains(&vm) {
+            continue;
+        }
+        match load_run(&path) {
+            Ok(log) => runs.push(BootstrapRunSummary {
+                id,
+                vm,
+                timestamp,
+                status: log.status,
+                duration_ms: log.duration_ms,
+                path,
+            }),
+            Err(err) => diagnostics.push(
+                Diagnostic::new(Severity::Warning, format!("Skipping bootstrap log: {err}"))
+                    .with_path(path),
+            ),
+        }
+    }
+    Ok(runs)
+}
+
+/// Resolve `selector` to a run: a run id, a VM name for its latest run, or
+/// `<vm>~<n>` for the run `n` before the latest.
+pub fn find_run(log_root: &Path, selector: &str) -> Result<(BootstrapRunSummary, BootstrapRunLog)> {
+    let files = log_files(log_root)?;
+    let found = match files.iter().find(|(id, ..)| id == selector) {
+        Some(file) => Some(file),
+        None => {
+            let (vm, back) = match selector.rsplit_once('~') {
+                Some((vm, back)) => (vm, back.parse::<usize>().ok()),
+                None => (selector, Some(0)),
+            };
+            back.and_then(|back| {
+                files
+                    .iter()
+                    .rev()
+                    .filter(|(_, name, ..)| name == vm)
+                    .nth(back)
+            })
+        }
+    };
+    let Some((id, vm, timestamp, path)) = found else {
+        return Err(Error::PreflightFailed {
+            message: format!(
+                "No bootstrap run matches `{selector}` in {}. Use a run id from `castra bootstrap history`, a VM name for its latest run, or `<vm>~<n>` for an earlier one.",
+                log_root.join(LOG_SUBDIR).display()
+            ),
+        });
+    };
+    let log = load_run(path)?;
+    let summary = BootstrapRunSummary {
+        id: id.clone(),
+        vm: vm.clone(),
+        timestamp: *timestamp,
+        status: log.status.clone(),
+        duration_ms: log.duration_ms,
+        path: path.clone(),
+    };
+    Ok((summary, log))
+}
+
+/// Compare two runs, usually of the same VM with `before` the older.
+pub fn diff_runs(before: &BootstrapRunLog, after: &BootstrapRunLog) -> BootstrapRunDiff {
+    let mut diff = BootstrapRunDiff {
+        artifact_hash_changed: before.artifact_hash != after.artifact_hash,
+        base_hash_changed: before.base_hash != after.base_hash,
+        before_ms: before.duration_ms,
+        after_ms: after.duration_ms,
+        ..BootstrapRunDiff::default()
+    };
+
+    let before_units = units(before);
+    let after_units = units(after);
+    let stages: Vec<&Option<String>> = after_units
+        .keys()
+        .chain(
+            before_units
+                .keys()
+                .filter(|stage| !after_units.contains_key(*stage)),
+        )
+        .collect();
+    for stage in stages {
+        match (before_units.get(stage), after_units.get(stage)) {
+            (Some((Some(old), old_files)), Some((Some(new), new_files))) => {
+                if old != new {
+                    diff.scripts.push(ScriptChange {
+                        stage: stage.clone(),
+                        before: Some(old.to_string()),
+                        after: Some(new.to_string()),
+                    });
+                }
+                for (path, change) in diff_maps(old_files, new_files) {
+                    diff.payload.push(PayloadChange {
+                        stage: stage.clone(),
+                        path: path.clone(),
+                        change,
+                    });
+                }
+            }
+            (None, Some((new, _))) => diff.scripts.push(ScriptChange {
+                stage: stage.clone(),
+                before: None,
+                after: new.map(str::to_string),
+            }),
+            (Some((old, _)), None) => diff.scripts.push(ScriptChange {
+                stage: stage.clone(),
+                before: old.map(str::to_string),
+                after: None,
+            }),
+            // Logs from before digests were recorded.
+            _ => {}
+        }
+    }
+
+    for (key, change) in diff_maps(&before.env, &after.env) {
+        diff.env.push(EnvChange {
+            key: key.clone(),
+            change,
+            secret: false,
+            before: before.env.get(key).cloned(),
+            after: after.env.get(key).cloned(),
+        });
+    }
+    for (key, change) in diff_maps(&before.env_secrets, &after.env_secrets) {
+        diff.env.push(EnvChange {
+            key: key.clone(),
+            change,
+            secret: true,
+            before: None,
+            after: None,
+        });
+    }
+
+    let old_steps = step_durations(before);
+    let new_steps = step_durations(after);
+    for (key, after_ms) in &new_steps {
+        diff.steps.push(StepTiming {
+            stage: key.0.clone(),
+            step: key.1.clone(),
+            before_ms: lookup(&old_steps, key),
+            after_ms: Some(*after_ms),
+        });
+    }
+    for (key, before_ms) in &old_steps {
+        if lookup(&new_steps, key).is_none() {
+            diff.steps.push(StepTiming {
+                stage: key.0.clone(),
+                step: key.1.clone(),
+                before_ms: Some(*before_ms),
+                after_ms: None,
+            });
+        }
+    }
+    diff
+}
+
+type StepKey = (Option<String>, String);
+/// Script hash and payload digests of one stage.
+type Unit<'a> = (Option<&'a str>, &'a BTreeMap<String, String>);
+
+/// `(run id, vm, timestamp, path)` of every log file, oldest first.
+fn log_files(log_root: &Path) -> Result<Vec<(String, String, u64, PathBuf)>> {
+    let dir = log_root.join(LOG_SUBDIR);
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(source) => return Err(Error::LogReadFailed { path: dir, source }),
+    };
+    let mut files = Vec::new();
+    for entry in entries {
+        let path = entry
+            .map_err(|source| Error::LogReadFailed {
+                path: dir.clone(),
+                source,
+            })?
+            .path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+            continue;
+        }
+        let Some(id) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+        };
+        let Some((vm, timestamp)) = id
+            .rsplit_once('-')
+            .and_then(|(vm, timestamp)| Some((vm, timestamp.parse::<u64>().ok()?)))
+        else {
+            continue;
+        };
+        files.push((id.to_string(), vm.to_string(), timestamp, path.clone()));
+    }
+    files.sort_by(|a, b| (a.2, &a.0).cmp(&(b.2, &b.0)));
+    Ok(files)
+}
+
+/// Units keyed by stage name; single-script runs have one unnamed unit.
+fn units(log: &BootstrapRunLog) -> BTreeMap<Option<String>, Unit<'_>> {
+    if log.stages.is_empty() {
+        return BTreeMap::from([(None, (log.script_sha256.as_deref(), &log.payload_files))]);
+    }
+    log.stages
+        .iter()
+        .map(|stage| {
+            (
+                Some(stage.name.clone()),
+                (stage.script_sha256.as_deref(), &stage.payload_files),
+            )
+        })
+        .collect()
+}
+
+fn diff_maps<'a>(
+    before: &'a BTreeMap<String, String>,
+    after: &'a BTreeMap<String, String>,
+) -> Vec<(&'a String, Change)> {
+    let keys: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+    keys.into_iter()
+        .filter_map(|key| match (before.get(key), after.get(key)) {
+            (None, Some(_)) => Some((key, Change::Added)),
+            (Some(_), None) => Some((key, Change::Removed)),
+            (Some(old), Some(new)) if old != new => Some((key, Change::Modified)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Total time per step in the order steps were first taken; the synthetic
+/// `error` record is left out.
+fn step_durations(log: &BootstrapRunLog) -> Vec<(StepKey, u64)> {
+    let mut steps: Vec<(StepKey, u64)> = Vec::new();
+    for record in log.steps.iter().filter(|record| record.step != "error") {
+        let key = (record.stage.clone(), record.step.clone());
+        match steps.iter_mut().find(|(existing, _)| *existing == key) {
+            Some((_, total)) => *total += record.duration_ms,
+            None => steps.push((key, record.duration_ms)),
+        }
+    }
+    steps
+}
+
+fn lookup(steps: &[(StepKey, u64)], key: &StepKey) -> Option<u64> {
+    steps
+        .iter()
+        .find(|(existing, _)| existing == key)
+        .map(|(_, duration)| *duration)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn step(step: &str, duration_ms: u64) -> StepRecord {
+        StepRecord {
+            stage: None,
+            step: step.to_string(),
+            status: "success".to_string(),
+            duration_ms,
+            detail: None,
+            bytes_skipped: None,
+            attempts: None,
+        }
+    }
+
+    fn run(vm: &str, script: &str, files: &[(&str, &str)], apply_ms: u64) -> BootstrapRunLog {
+        BootstrapRunLog {
+            vm: vm.to_string(),
+            artifact_hash: format!("artifact-{script}"),
+            base_hash: "base".to_string(),
+            stamp: None,
+            status: "success".to_string(),
+            duration_ms: 500 + apply_ms,
+            steps: vec![
+                step("connect", 200),
+                step("transfer", 300),
+                step("apply", apply_ms),
+            ],
+            stages: Vec::new(),
+            script_source: "bootstrap/run.sh".to_string(),
+            staged_script: "state/bootstrap/run.sh".to_string(),
+            script_sha256: Some(script.to_string()),
+            payload_source: None,
+            payload_commit: None,
+            staged_payload: None,
+            payload_bytes: 0,
+            payload_files: files
+                .iter()
+                .map(|(path, sha)| (path.to_string(), sha.to_string()))
+                .collect(),
+            remote_dir: "/tmp/castra-bootstrap".to_string(),
+            ssh_user: "root".to_string(),
+            ssh_host: "127.0.0.1".to_string(),
+            ssh_port: 2222,
+            ssh_identity: None,
+            env: BTreeMap::new(),
+            env_secrets: BTreeMap::new(),
+            metadata_path: None,
+        }
+    }
+
+    fn write(dir: &Path, id: &str, log: &BootstrapRunLog) -> io::Result<()> {
+        fs::create_dir_all(dir)?;
+        fs::write(dir.join(format!("{id}.json")), serde_json::to_vec(log)?)
+    }
+
+    #[test]
+    fn lists_and_selects_runs_per_vm() -> Result<()> {
+        let temp_dir = TempDir::new().unwrap();
+        let log_root = temp_dir.path();
+        let dir = log_root.join(LOG_SUBDIR);
+        write(&dir, "web-100", &run("web", "a", &[], 1000)).unwrap();
+        write(&dir, "web-api-150", &run("web-api", "a", &[], 1000)).unwrap();
+        write(&dir, "web-200", &run("web", "b", &[], 3000)).unwrap();
+        fs::write(dir.join("web-300.json"), "not json").unwrap();
+
+        let mut diagnostics = Vec::new();
+        let runs = list_runs(log_root, &["web".to_string()], &mut diagnostics)?;
+        let ids: Vec<&str> = runs.iter().map(|run| run.id.as_str()).collect();
+        assert_eq!(ids, ["web-100", "web-200"]);
+        assert_eq!(runs[1].duration_ms, 3500);
+        assert_eq!(diagnostics.len(), 1, "{diagnostics:?}");
+
+        assert_eq!(find_run(log_root, "web-api")?.0.id, "web-api-150");
+        assert_eq!(find_run(log_root, "web-100")?.0.id, "web-100");
+        // The latest `web` log is unreadable.
+        assert!(matches!(
+            find_run(log_root, "web"),
+            Err(Error::LogReadFailed { .. })
+        ));
+        assert_eq!(
+            find_run(log_root, "web~1")?.1.script_sha256.as_deref(),
+            Some("b")
+        );
+        assert!(find_run(log_root, "web~5").is_err());
+        assert!(find_run(log_root, "db").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn diff_reports_inputs_and_slow_steps() {
+        let mut before = run("web", "a", &[("app.conf", "1"), ("old.txt", "2")], 1000);
+        before.env.insert("MODE".to_string(), "dev".to_string());
+        before
+            .env_secrets
+            .insert("TOKEN".to_string(), "d1".to_string());
+        let mut after = run("web", "b", &[("app.conf", "9"), ("new.txt", "3")], 2500);
+        after.env.insert("MODE".to_string(), "prod".to_string());
+        after
+            .env_secrets
+            .insert("TOKEN".to_string(), "d2".to_string());
+        after.steps.push(StepRecord {
+            detail: Some("boom".to_string()),
+            ..step("error", 0)
+        });
+
+        let diff = diff_runs(&before, &after);
+        assert!(diff.artifact_hash_changed);
+        assert!(!diff.base_hash_changed);
+        assert_eq!(
+            diff.scripts,
+            [ScriptChange {
+                stage: None,
+                before: Some("a".to_string()),
+                after: Some("b".to_string()),
+            }]
+        );
+        let payload: Vec<(&str, Change)> = diff
+            .payload
+            .iter()
+            .map(|change| (change.path.as_str(), change.change))
+            .collect();
+        assert_eq!(
+            payload,
+            [
+                ("app.conf", Change::Modified),
+                ("new.txt", Change::Added),
+                ("old.txt", Change::Removed),
+            ]
+        );
+        assert_eq!(diff.env.len(), 2);
+        assert_eq!(diff.env[0].after.as_deref(), Some("prod"));
+        assert!(diff.env[1].secret && diff.env[1].before.is_none());
+
+        let steps: Vec<&str> = diff.steps.iter().map(|step| step.step.as_str()).collect();
+        assert_eq!(steps, ["connect", "transfer", "apply"]);
+        let regressed: Vec<&str> = diff.regressions().map(|step| step.step.as_str()).collect();
+        assert_eq!(regressed, ["apply"]);
+    }
+
+    #[test]
+    fn old_logs_without_digests_load_and_compare() {
+        let mut old = serde_json::to_value(run("web", "a", &[], 100)).unwrap();
+        let object = old.as_object_mut().unwrap();
+        object.remove("script_sha256");
+        object.remove("payload_files");
+        let old: BootstrapRunLog = serde_json::from_value(old).unwrap();
+        assert!(old.script_sha256.is_none());
+
+        let diff = diff_runs(&old, &run("web", "b", &[("app.conf", "1")], 100));
+        assert!(diff.scripts.is_empty());
+        assert!(diff.payload.is_empty());
+    }
+}
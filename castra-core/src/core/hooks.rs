@@ -0,0 +1,324 @@
+//! `[workflows]` lifecycle hooks: host commands run around `up` and `down`.
+//!
+//! Each hook command runs once per VM through `sh -c` from the project root.
+//! `{{ name }}` placeholders are rendered from [`HOOK_TEMPLATE_VARIABLES`] as
+//! single-quoted shell words and the same values are exported unquoted as
+//! `CASTRA_*` environment variables. A
+//! non-zero exit aborts the surrounding operation with [`Error::HookFailed`].
+
+use std::borrow::Borrow;
+use std::collections::BTreeMap;
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::Instant;
+
+use crate::config::{HookPoint, PortProtocol, ProjectConfig, VmDefinition};
+use crate::error::{Error, Result};
+
+use super::bootstrap::shell_quote;
+use super::events::Event;
+use super::reporter::Reporter;
+
+/// Template variables describing `vm` at hook point `point`.
+///
+/// `ssh_port` is the host port forwarded to guest port 22 and is empty when
+/// the VM declares no such forward.
+pub fn template_variables(
+    point: HookPoint,
+    project: &ProjectConfig,
+    state_root: &Path,
+    vm: &VmDefinition,
+) -> BTreeMap<&'static str, String> {
+    let ssh_port = vm
+        .port_forwards
+        .iter()
+        .find(|forward| forward.protocol == PortProtocol::Tcp && forward.guest == 22)
+        .map(|forward| forward.host.to_string())
+        .unwrap_or_default();
+
+    BTreeMap::from([
+        ("vm", vm.name.clone()),
+        ("project", project.project_name.clone()),
+        ("state_root", state_root.display().to_string()),
+        ("overlay", vm.overlay.display().to_string()),
+        ("base_image", vm.base_image.path().display().to_string()),
+        ("ssh_port", ssh_port),
+        ("hook", point.as_str().to_string()),
+    ])
+}
+
+/// Replace `{{ name }}` placeholders in `template` with their values.
+///
+/// Placeholders are validated when the config loads, so unknown names are
+/// left untouched rather than reported here.
+pub fn render<K: Borrow<str> + Ord>(template: &str, variables: &BTreeMap<K, String>) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else {
+            break;
+        };
+        rendered.push_str(&rest[..start]);
+        match variables.get(after[..end].trim()) {
+            Some(value) => rendered.push_str(value),
+            None => rendered.push_str(&rest[start..start + 2 + end + 2]),
+        }
+        rest = &after[end + 2..];
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+/// Run every command configured for `point` against `vm`, stopping at the
+/// first failure.
+pub fn run_hooks(
+    point: HookPoint,
+    project: &ProjectConfig,
+    state_root: &Path,
+    vm: &VmDefinition,
+    reporter: &mut dyn Reporter,
+) -> Result<()> {
+    let commands = project.workflows.commands(point);
+    if commands.is_empty() {
+        return Ok(());
+    }
+
+    let variables = template_variables(point, project, state_root, vm);
+    let quoted: BTreeMap<&str, String> = variables
+        .iter()
+        .map(|(name, value)| (*name, shell_quote(value)))
+        .collect();
+    for template in commands {
+        let command = render(template, &quoted);
+        reporter.report(Event::HookStarted {
+            hook: point,
+            vm: vm.name.clone(),
+            command: command.clone(),
+        });
+
+        let started = Instant::now();
+        let mut process = Command::new("sh");
+        process
+            .arg("-c")
+            .arg(&command)
+            .current_dir(&project.project_root)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        for (name, value) in &variables {
+            process.env(format!("CASTRA_{}", name.to_ascii_uppercase()), value);
+        }
+
+        let output = process.output().map_err(|err| Error::HookFailed {
+            hook: point.as_str().to_string(),
+            vm: vm.name.clone(),
+            message: match err.kind() {
+                io::ErrorKind::NotFound => "`sh` not found in PATH.".to_string(),
+                _ => format!("failed to execute `{command}`: {err}"),
+            },
+        })?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let detail = if stderr.trim().is_empty() {
+                stdout.trim().to_string()
+            } else {
+                stderr.trim().to_string()
+            };
+            return Err(Error::HookFailed {
+                hook: point.as_str().to_string(),
+                vm: vm.name.clone(),
+                message: match output.status.code() {
+                    Some(code) => format!("`{command}` exited with code {code}: {detail}"),
+                    None => format!("`{command}` was terminated by a signal: {detail}"),
+                },
+            });
+        }
+
+        reporter.report(Event::HookCompleted {
+            hook: point,
+            vm: vm.name.clone(),
+            command,
+            duration_ms: started.elapsed().as_millis().min(u128::from(u64::MAX)) as u64,
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{
+        BaseImageSource, BootstrapConfig, ImagesConfig, LifecycleConfig, PortForward,
+        ProjectFeatures, Workflows,
+    };
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn project_with_workflows(root: &Path, workflows: Workflows) -> ProjectConfig {
+        let vm = VmDefinition {
+            base_image: BaseImageSource::from_explicit(root.join("base.qcow2")),
+            overlay: root.join("devbox-overlay.qcow2"),
+            port_forwards: vec![PortForward {
+                host: 2222,
+                guest: 22,
+                protocol: PortProtocol::Tcp,
+            }],
+            ..VmDefinition::for_test("devbox")
+        };
+        ProjectConfig {
+            file_path: root.join("castra.toml"),
+            project_root: root.to_path_buf(),
+            version: "0.1.0".to_string(),
+            project_name: "demo".to_string(),
+            features: ProjectFeatures,
+            vms: vec![vm],
+            state_root: root.join(".castra"),
+            workflows,
+            lifecycle: LifecycleConfig::default(),
+            bootstrap: BootstrapConfig::default(),
+            images: ImagesConfig::default(),
+            warnings: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn render_substitutes_known_placeholders() {
+        let temp = tempdir().unwrap();
+        let project = project_with_workflows(temp.path(), Workflows::default());
+        let vars = template_variables(
+            HookPoint::PostLaunch,
+            &project,
+            &project.state_root,
+            &project.vms[0],
+        );
+        assert_eq!(
+            render(
+                "ssh -p {{ssh_port}} {{ vm }}@{{ project }} ({{ hook }})",
+                &vars
+            ),
+            "ssh -p 2222 devbox@demo (post_launch)"
+        );
+        assert_eq!(render("echo {{ nope }} }}", &vars), "echo {{ nope }} }}");
+    }
+
+    #[test]
+    fn run_hooks_exports_environment_and_reports_events() {
+        let temp = tempdir().unwrap();
+        let project = project_with_workflows(
+            temp.path(),
+            Workflows {
+                pre_up: vec![
+                    "echo \"$CASTRA_VM:$CASTRA_SSH_PORT:\"{{ hook }} > hook.out".to_string(),
+                ],
+                ..Workflows::default()
+            },
+        );
+        let mut events = Vec::new();
+        struct Collect<'a>(&'a mut Vec<Event>);
+        impl Reporter for Collect<'_> {
+            fn report(&mut self, event: Event) {
+                self.0.push(event);
+            }
+        }
+
+        run_hooks(
+            HookPoint::PreUp,
+            &project,
+            &project.state_root,
+            &project.vms[0],
+            &mut Collect(&mut events),
+        )
+        .unwrap();
+
+        let written = fs::read_to_string(temp.path().join("hook.out")).unwrap();
+        assert_eq!(written.trim(), "devbox:2222:pre_up");
+        assert!(matches!(
+            events[0],
+            Event::HookStarted {
+                hook: HookPoint::PreUp,
+                ..
+            }
+        ));
+        assert!(matches!(
+            events[1],
+            Event::HookCompleted {
+                hook: HookPoint::PreUp,
+                ..
+            }
+        ));
+
+        run_hooks(
+            HookPoint::PostDown,
+            &project,
+            &project.state_root,
+            &project.vms[0],
+            &mut (),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn run_hooks_quotes_placeholders_as_single_shell_words() {
+        let temp = tempdir().unwrap();
+        let project = project_with_workflows(
+            temp.path(),
+            Workflows {
+                pre_up: vec!["printf '%s\\n' {{ state_root }} > hook.out".to_string()],
+                ..Workflows::default()
+            },
+        );
+        let state_root = temp.path().join("state root; touch injected");
+
+        run_hooks(
+            HookPoint::PreUp,
+            &project,
+            &state_root,
+            &project.vms[0],
+            &mut (),
+        )
+        .unwrap();
+
+        let written = fs::read_to_string(temp.path().join("hook.out")).unwrap();
+        assert_eq!(written, format!("{}\n", state_root.display()));
+        assert!(!temp.path().join("injected").exists());
+    }
+
+    #[test]
+    fn failing_hook_aborts_with_hook_failed() {
+        let temp = tempdir().unwrap();
+        let project = project_with_workflows(
+            temp.path(),
+            Workflows {
+                pre_down: vec![
+                    "echo refusing >&2; exit 3".to_string(),
+                    "touch never-ran".to_string(),
+                ],
+                ..Workflows::default()
+            },
+        );
+
+        let err = run_hooks(
+            HookPoint::PreDown,
+            &project,
+            &project.state_root,
+            &project.vms[0],
+            &mut (),
+        )
+        .unwrap_err();
+        match err {
+            Error::HookFailed { hook, vm, message } => {
+                assert_eq!(hook, "pre_down");
+                assert_eq!(vm, "devbox");
+                assert!(message.contains("exited with code 3"), "{message}");
+                assert!(message.contains("refusing"), "{message}");
+            }
+            other => panic!("unexpected error: {other:?}"),
+        }
+        assert!(!temp.path().join("never-ran").exists());
+    }
+}
@@ -0,0 +1,430 @@
+//! Content-addressed image store shared by every workspace on the host.
+//!
+//! Images live under `<root>/sha256/<digest>` and are exposed to workspaces as
+//! hard links (falling back to symlinks or copies) beneath `<state_root>/images`.
+//! Named tags under `<root>/tags/` map well-known image names to digests so the
+//! default Alpine image can be located without rehashing it on every `up`.
+//! Tagged blobs are never garbage-collected.
+
+use std::collections::BTreeSet;
+use std::env;
+use std::fs;
+use std::io::{ErrorKind, Read};
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::error::{Error, Result};
+
+const BLOB_SUBDIR: &str = "sha256";
+const TAG_SUBDIR: &str = "tags";
+const STAGING_SUBDIR: &str = "tmp";
+
+/// Shared store root, honouring `CASTRA_IMAGE_STORE` before `~/.castra/images`.
+pub fn default_image_store_root() -> PathBuf {
+    if let Some(raw) = env::var_os("CASTRA_IMAGE_STORE").filter(|raw| !raw.is_empty()) {
+        return PathBuf::from(raw);
+    }
+    crate::config::user_home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(".castra")
+        .join("images")
+}
+
+/// Handle to a content-addressed image store rooted at a directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageStore {
+    root: PathBuf,
+}
+
+/// Image blob recorded in the store.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredImage {
+    pub digest: String,
+    pub path: PathBuf,
+    pub bytes: u64,
+}
+
+/// How a workspace path was attached to a store blob.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageLinkKind {
+    /// Workspace path already referenced the blob.
+    Existing,
+    HardLink,
+    SymLink,
+    /// Filesystem refused links; the blob was copied.
+    Copy,
+}
+
+impl ImageLinkKind {
+    /// Human-friendly label for rendering.
+    pub fn describe(self) -> &'static str {
+        match self {
+            ImageLinkKind::Existing => "existing link",
+            ImageLinkKind::HardLink => "hard link",
+            ImageLinkKind::SymLink => "symlink",
+            ImageLinkKind::Copy => "copy",
+        }
+    }
+}
+
+impl ImageStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn open_default() -> Self {
+        Self::new(default_image_store_root())
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn blob_dir(&self) -> PathBuf {
+        self.root.join(BLOB_SUBDIR)
+    }
+
+    pub fn blob_path(&self, digest: &str) -> PathBuf {
+        self.blob_dir().join(digest.to_ascii_lowercase())
+    }
+
+    /// Scratch location for downloads that will later be imported.
+    pub fn staging_path(&self, name: &str) -> PathBuf {
+        self.root.join(STAGING_SUBDIR).join(name)
+    }
+
+    fn tag_path(&self, tag: &str) -> PathBuf {
+        self.root.join(TAG_SUBDIR).join(tag)
+    }
+
+    /// Resolve a named tag to a digest whose blob is still present.
+    pub fn resolve_tag(&self, tag: &str) -> Result<Option<String>> {
+        let path = self.tag_path(tag);
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => {
+                return Err(Error::PreflightFailed {
+                    message: format!("Failed to read image store tag {}: {err}", path.display()),
+                });
+            }
+        };
+        let digest = contents.trim().to_ascii_lowercase();
+        if !is_valid_digest(&digest) || !self.blob_path(&digest).is_file() {
+            return Ok(None);
+        }
+        Ok(Some(digest))
+    }
+
+    fn write_tag(&self, tag: &str, digest: &str) -> Result<()> {
+        let path = self.tag_path(tag);
+        create_parent(&path)?;
+        fs::write(&path, format!("{digest}\n")).map_err(|err| Error::PreflightFailed {
+            message: format!("Failed to write image store tag {}: {err}", path.display()),
+        })
+    }
+
+    /// Record `tag` for a blob already held by the store.
+    pub fn tag(&self, tag: &str, digest: &str) -> Result<()> {
+        if !self.blob_path(digest).is_file() {
+            return Err(Error::PreflightFailed {
+                message: format!(
+                    "Image sha256:{digest} is missing from the store at {}.",
+                    self.root.display()
+                ),
+            });
+        }
+        self.write_tag(tag, digest)
+    }
+
+    /// Import `source` into the store, optionally recording it under `tag`.
+    ///
+    /// When `consume` is set the source is moved into place; otherwise it is
+    /// hard linked (or copied) so the original path stays valid.
+    pub fn import(&self, source: &Path, tag: Option<&str>, consume: bool) -> Result<String> {
+        let digest = compute_sha256_hex(source)?;
+        let blob = self.blob_path(&digest);
+        create_parent(&blob)?;
+
+        if blob.is_file() {
+            if consume {
+                let _ = fs::remove_file(source);
+            }
+        } else if consume {
+            fs::rename(source, &blob)
+                .or_else(|_| fs::copy(source, &blob).and_then(|_| fs::remove_file(source)))
+                .map_err(|err| Error::PreflightFailed {
+                    message: format!(
+                        "Failed to move {} into the image store at {}: {err}",
+                        source.display(),
+                        blob.display()
+                    ),
+                })?;
+        } else {
+            fs::hard_link(source, &blob)
+                .or_else(|_| fs::copy(source, &blob).map(|_| ()))
+                .map_err(|err| Error::PreflightFailed {
+                    message: format!(
+                        "Failed to import {} into the image store at {}: {err}",
+                        source.display(),
+                        blob.display()
+                    ),
+                })?;
+        }
+
+        if let Some(tag) = tag {
+            self.write_tag(tag, &digest)?;
+        }
+        Ok(digest)
+    }
+
+    /// Make `target` refer to the blob for `digest`, replacing any stale file.
+    pub fn link_into(&self, digest: &str, target: &Path) -> Result<ImageLinkKind> {
+        let blob = self.blob_path(digest);
+        if !blob.is_file() {
+            return Err(Error::PreflightFailed {
+                message: format!(
+                    "Image sha256:{digest} is missing from the store at {}.",
+                    self.root.display()
+                ),
+            });
+        }
+
+        if references_blob(target, &blob) {
+            return Ok(ImageLinkKind::Existing);
+        }
+
+        match fs::symlink_metadata(target) {
+            Ok(_) => fs::remove_file(target).map_err(|err| Error::PreflightFailed {
+                message: format!(
+                    "Failed to replace workspace image {}: {err}",
+                    target.display()
+                ),
+            })?,
+            Err(err) if err.kind() == ErrorKind::NotFound => {}
+            Err(err) => {
+                return Err(Error::PreflightFailed {
+                    message: format!(
+                        "Failed to inspect workspace image {}: {err}",
+                        target.display()
+                    ),
+                });
+            }
+        }
+        create_parent(target)?;
+
+        if fs::hard_link(&blob, target).is_ok() {
+            return Ok(ImageLinkKind::HardLink);
+        }
+        #[cfg(unix)]
+        if std::os::unix::fs::symlink(&blob, target).is_ok() {
+            return Ok(ImageLinkKind::SymLink);
+        }
+        fs::copy(&blob, target).map_err(|err| Error::PreflightFailed {
+            message: format!(
+                "Failed to link image sha256:{digest} into {}: {err}",
+                target.display()
+            ),
+        })?;
+        Ok(ImageLinkKind::Copy)
+    }
+
+    /// List blobs currently held by the store.
+    pub fn entries(&self) -> Result<Vec<StoredImage>> {
+        let dir = self.blob_dir();
+        let listing = match fs::read_dir(&dir) {
+            Ok(listing) => listing,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => {
+                return Err(Error::PreflightFailed {
+                    message: format!("Failed to list image store {}: {err}", dir.display()),
+                });
+            }
+        };
+
+        let mut entries = Vec::new();
+        for entry in listing.flatten() {
+            let path = entry.path();
+            let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            if !is_valid_digest(name) {
+                continue;
+            }
+            let bytes = entry.metadata().map(|meta| meta.len()).unwrap_or(0);
+            entries.push(StoredImage {
+                digest: name.to_string(),
+                path,
+                bytes,
+            });
+        }
+        entries.sort_by(|a, b| a.digest.cmp(&b.digest));
+        Ok(entries)
+    }
+
+    /// Digests named by any tag, including nested ones such as `golden/*`.
+    pub fn tagged_digests(&self) -> Result<BTreeSet<String>> {
+        let mut digests = BTreeSet::new();
+        let mut pending = vec![self.root.join(TAG_SUBDIR)];
+        while let Some(dir) = pending.pop() {
+            let listing = match fs::read_dir(&dir) {
+                Ok(listing) => listing,
+                Err(err) if err.kind() == ErrorKind::NotFound => continue,
+                Err(err) => {
+                    return Err(Error::PreflightFailed {
+                        message: format!(
+                            "Failed to list image store tags {}: {err}",
+                            dir.display()
+                        ),
+                    });
+                }
+            };
+            for entry in listing.flatten() {
+                let path = entry.path();
+                if path.is_dir() {
+                    pending.push(path);
+                } else if let Ok(contents) = fs::read_to_string(&path) {
+                    let digest = contents.trim().to_ascii_lowercase();
+                    if is_valid_digest(&digest) {
+                        digests.insert(digest);
+                    }
+                }
+            }
+        }
+        Ok(digests)
+    }
+
+    /// Digest of the store blob `path` resolves to, e.g. an explicit
+    /// `base_image` pointing at a committed image.
+    pub fn digest_for_blob_path(&self, path: &Path) -> Option<String> {
+        let path = path.canonicalize().ok()?;
+        let blob_dir = self.blob_dir().canonicalize().ok()?;
+        if path.parent()? != blob_dir {
+            return None;
+        }
+        let name = path.file_name()?.to_str()?;
+        is_valid_digest(name).then(|| name.to_ascii_lowercase())
+    }
+
+    /// Sidecar files (e.g. cached sha512 digests) stored next to a blob.
+    pub fn sidecars(&self, digest: &str) -> Vec<PathBuf> {
+        let prefix = format!("{}.", digest.to_ascii_lowercase());
+        let mut sidecars = Vec::new();
+        if let Ok(listing) = fs::read_dir(self.blob_dir()) {
+            for entry in listing.flatten() {
+                let matches = entry
+                    .file_name()
+                    .to_str()
+                    .map(|name| name.starts_with(&prefix))
+                    .unwrap_or(false);
+                if matches {
+                    sidecars.push(entry.path());
+                }
+            }
+        }
+        sidecars.sort();
+        sidecars
+    }
+}
+
+pub fn is_valid_digest(value: &str) -> bool {
+    value.len() == 64 && value.chars().all(|ch| ch.is_ascii_hexdigit())
+}
+
+fn references_blob(target: &Path, blob: &Path) -> bool {
+    if let Ok(link) = fs::read_link(target) {
+        return link == blob;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        if let (Ok(a), Ok(b)) = (fs::metadata(target), fs::metadata(blob)) {
+            return a.dev() == b.dev() && a.ino() == b.ino();
+        }
+    }
+    false
+}
+
+fn create_parent(path: &Path) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|err| Error::PreflightFailed {
+            message: format!(
+                "Failed to prepare image store directory {}: {err}",
+                parent.display()
+            ),
+        })?;
+    }
+    Ok(())
+}
+
+/// Hex-encoded sha256 of the file at `path`, i.e. its store digest.
+pub fn compute_sha256_hex(path: &Path) -> Result<String> {
+    let mut file = fs::File::open(path).map_err(|err| Error::PreflightFailed {
+        message: format!("Failed to open {} for hashing: {err}", path.display()),
+    })?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .map_err(|err| Error::PreflightFailed {
+                message: format!("Failed while hashing {}: {err}", path.display()),
+            })?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn import_and_link_share_a_single_blob() {
+        let temp = tempdir().expect("tempdir");
+        let store = ImageStore::new(temp.path().join("store"));
+        let source = temp.path().join("download.qcow2");
+        fs::write(&source, b"image-bytes").expect("write source");
+
+        let digest = store
+            .import(&source, Some("alpine"), true)
+            .expect("import image");
+        assert!(!source.exists());
+        assert_eq!(store.resolve_tag("alpine").unwrap(), Some(digest.clone()));
+
+        let first = temp.path().join("ws-a/images/alpine.qcow2");
+        let second = temp.path().join("ws-b/images/alpine.qcow2");
+        assert_ne!(
+            store.link_into(&digest, &first).unwrap(),
+            ImageLinkKind::Existing
+        );
+        store.link_into(&digest, &second).unwrap();
+        assert_eq!(
+            store.link_into(&digest, &first).unwrap(),
+            ImageLinkKind::Existing
+        );
+        assert_eq!(fs::read(&second).unwrap(), b"image-bytes");
+
+        let entries = store.entries().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].digest, digest);
+    }
+
+    #[test]
+    fn resolve_tag_ignores_missing_blobs() {
+        let temp = tempdir().expect("tempdir");
+        let store = ImageStore::new(temp.path());
+        let source = temp.path().join("image");
+        fs::write(&source, b"bytes").unwrap();
+        let digest = store.import(&source, Some("tag"), false).unwrap();
+        assert!(source.exists());
+
+        fs::remove_file(store.blob_path(&digest)).unwrap();
+        assert_eq!(store.resolve_tag("tag").unwrap(), None);
+    }
+}
@@ -7,26 +7,48 @@ pub mod outcome;
 pub mod reporter;
 
 pub mod bootstrap;
+pub mod collect;
+pub mod download;
+pub mod exec;
+pub mod git_payload;
+pub mod golden;
+pub mod history;
+pub mod hooks;
+pub mod image_store;
 pub mod logs;
 pub mod operations;
 pub mod ports;
 pub mod project;
+pub mod provision;
 pub mod runtime;
+pub mod secrets;
+pub mod signature;
+pub mod ssh_keys;
 pub mod status;
+pub mod transport;
 pub mod workspace_registry;
 
 pub use diagnostics::{Diagnostic, Severity};
 pub use events::{CleanupKind, Event};
-pub use operations::{clean, down, init, logs, ports, status, up};
+pub use operations::{
+    bootstrap, bootstrap_diff, bootstrap_history, bootstrap_show, clean, collect, config_migrate,
+    config_show, config_validate, down, exec, image_commit, init, logs, ports, ssh, status, up,
+};
 pub use options::{
-    CleanOptions, CleanScope, ConfigLoadOptions, ConfigSource, DownOptions, InitOptions,
-    LogsOptions, PortsOptions, PortsView, ProjectSelector, StatusOptions, UpOptions, VmLaunchMode,
+    BootstrapDiffOptions, BootstrapHistoryOptions, BootstrapOptions, BootstrapShowOptions,
+    CleanOptions, CleanScope, CollectOptions, ConfigLoadOptions, ConfigMigrateOptions,
+    ConfigShowOptions, ConfigSource, ConfigValidateOptions, DownOptions, ExecOptions,
+    ImageCommitOptions, InitOptions, LogsOptions, PortsOptions, PortsView, ProjectSelector,
+    SshOptions, StatusOptions, UpOptions, VmLaunchMode,
 };
 pub use outcome::{
-    BootstrapRunOutcome, BootstrapRunStatus, CleanOutcome, CleanupAction, DownOutcome, InitOutcome,
-    LogEntry, LogFollower, LogSection, LogSectionState, LogsOutcome, OperationOutput,
-    OperationResult, PortConflictRow, PortForwardRow, PortForwardStatus, PortInactiveReason,
-    PortsOutcome, ProjectPortsOutcome, SkipReason, StateRootCleanup, StatusOutcome, UpOutcome,
+    BootstrapDiffOutcome, BootstrapHistoryOutcome, BootstrapOutcome, BootstrapRunOutcome,
+    BootstrapRunStatus, BootstrapShowOutcome, CleanOutcome, CleanupAction, CollectOutcome,
+    CollectedArtifact, ConfigMigrateOutcome, ConfigShowOutcome, ConfigValidateOutcome, DownOutcome,
+    ExecOutcome, GuestCommandResult, ImageCommitOutcome, ImageStoreCleanup, InitOutcome, LogEntry,
+    LogFollower, LogSection, LogSectionState, LogsOutcome, OperationOutput, OperationResult,
+    PortConflictRow, PortForwardRow, PortForwardStatus, PortInactiveReason, PortsOutcome,
+    ProjectPortsOutcome, SkipReason, SshOutcome, StateRootCleanup, StatusOutcome, UpOutcome,
     VmLaunchOutcome, VmPortDetail, VmShutdownOutcome,
 };
 pub use reporter::Reporter;
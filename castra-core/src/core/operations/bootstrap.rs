@@ -0,0 +1,120 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::core::bootstrap::{self, RerunOptions};
+use crate::core::history;
+use crate::core::options::{
+    BootstrapDiffOptions, BootstrapHistoryOptions, BootstrapOptions, BootstrapShowOptions,
+};
+use crate::core::outcome::{
+    BootstrapDiffOutcome, BootstrapHistoryOutcome, BootstrapOutcome, BootstrapShowOutcome,
+    OperationOutput, OperationResult,
+};
+use crate::core::project::config_state_root;
+use crate::core::reporter::Reporter;
+
+use super::{ReporterProxy, resolve_single_project, running_vms};
+
+/// Rerun bootstrap pipelines on VMs that are already running.
+pub(super) fn bootstrap(
+    options: BootstrapOptions,
+    reporter: Option<&mut dyn Reporter>,
+) -> OperationResult<BootstrapOutcome> {
+    let mut diagnostics = Vec::new();
+    let mut events = Vec::new();
+    let mut reporter = ReporterProxy::new(reporter, &mut events);
+
+    let project = resolve_single_project(
+        options.workspace.as_ref(),
+        &options.config,
+        &mut diagnostics,
+    )?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0);
+    let targets: Vec<(usize, u64)> =
+        running_vms(&project, &options.vms, "bootstrap", &mut diagnostics)?
+            .into_iter()
+            // Without a known uptime no earlier stamp is trusted to describe the guest.
+            .map(|(index, uptime)| {
+                let booted_at = uptime.map_or(now, |uptime| now.saturating_sub(uptime.as_secs()));
+                (index, booted_at)
+            })
+            .collect();
+
+    let state_root = config_state_root(&project);
+    let rerun = RerunOptions {
+        stages: options.stages,
+        force: options.force,
+    };
+    let runs = bootstrap::rerun_selected(
+        &project,
+        &state_root,
+        &targets,
+        &rerun,
+        &mut reporter,
+        &mut diagnostics,
+    )?;
+
+    Ok(OperationOutput::new(BootstrapOutcome { state_root, runs })
+        .with_diagnostics(diagnostics)
+        .with_events(events))
+}
+
+/// List recorded bootstrap runs, oldest first.
+pub(super) fn bootstrap_history(
+    options: BootstrapHistoryOptions,
+) -> OperationResult<BootstrapHistoryOutcome> {
+    let mut diagnostics = Vec::new();
+    let project = resolve_single_project(
+        options.workspace.as_ref(),
+        &options.config,
+        &mut diagnostics,
+    )?;
+    let log_root = config_state_root(&project).join("logs");
+    let runs = history::list_runs(&log_root, &options.vms, &mut diagnostics)?;
+
+    Ok(
+        OperationOutput::new(BootstrapHistoryOutcome { log_root, runs })
+            .with_diagnostics(diagnostics),
+    )
+}
+
+/// Load one recorded bootstrap run.
+pub(super) fn bootstrap_show(
+    options: BootstrapShowOptions,
+) -> OperationResult<BootstrapShowOutcome> {
+    let mut diagnostics = Vec::new();
+    let project = resolve_single_project(
+        options.workspace.as_ref(),
+        &options.config,
+        &mut diagnostics,
+    )?;
+    let log_root = config_state_root(&project).join("logs");
+    let (run, log) = history::find_run(&log_root, &options.run)?;
+
+    Ok(OperationOutput::new(BootstrapShowOutcome { run, log }).with_diagnostics(diagnostics))
+}
+
+/// Compare two recorded bootstrap runs.
+pub(super) fn bootstrap_diff(
+    options: BootstrapDiffOptions,
+) -> OperationResult<BootstrapDiffOutcome> {
+    let mut diagnostics = Vec::new();
+    let project = resolve_single_project(
+        options.workspace.as_ref(),
+        &options.config,
+        &mut diagnostics,
+    )?;
+    let log_root = config_state_root(&project).join("logs");
+    let (before, before_log) = history::find_run(&log_root, &options.before)?;
+    let (after, after_log) = history::find_run(&log_root, &options.after)?;
+    let diff = history::diff_runs(&before_log, &after_log);
+
+    Ok(OperationOutput::new(BootstrapDiffOutcome {
+        before,
+        after,
+        diff,
+    })
+    .with_diagnostics(diagnostics))
+}
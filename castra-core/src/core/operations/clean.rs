@@ -7,14 +7,17 @@ use crate::error::{Error, Result};
 
 use crate::core::diagnostics::{Diagnostic, Severity};
 use crate::core::events::{CleanupKind, Event};
+use crate::core::image_store::ImageStore;
 use crate::core::options::{CleanOptions, CleanScope, ConfigLoadOptions, ProjectSelector};
 use crate::core::outcome::{
-    CleanOutcome, CleanupAction, OperationOutput, OperationResult, SkipReason, StateRootCleanup,
+    CleanOutcome, CleanupAction, ImageStoreCleanup, OperationOutput, OperationResult, SkipReason,
+    StateRootCleanup,
 };
 use crate::core::project::config_state_root;
 use crate::core::reporter::Reporter;
 use crate::core::runtime::inspect_vm_state;
 use crate::core::status;
+use crate::core::workspace_registry::WorkspaceRegistry;
 
 use super::{ReporterProxy, load_project_for_operation};
 
@@ -27,6 +30,7 @@ pub(super) fn clean(
     let mut reporter = ReporterProxy::new(reporter, &mut events);
 
     let mut state_root_results = Vec::new();
+    let mut image_store_result = None;
 
     match options.scope.clone() {
         CleanScope::Workspace(selector) => {
@@ -35,7 +39,10 @@ pub(super) fn clean(
                 state_root_results.push(result);
             }
         }
-        CleanScope::Global { projects_root } => {
+        CleanScope::Global {
+            projects_root,
+            image_store_root,
+        } => {
             if !projects_root.exists() {
                 diagnostics.push(
                     Diagnostic::new(
@@ -80,20 +87,36 @@ pub(super) fn clean(
                     }
                 }
             }
+
+            image_store_result = Some(collect_unreferenced_images(
+                &ImageStore::new(image_store_root),
+                &projects_root,
+                &state_root_results,
+                &options,
+                &mut reporter,
+                &mut diagnostics,
+            )?);
         }
     }
 
     let outcome = CleanOutcome {
         dry_run: options.dry_run,
         state_roots: state_root_results,
+        image_store: image_store_result,
     };
 
     let total_reclaimed: u64 = outcome
         .state_roots
         .iter()
         .map(|cleanup| cleanup.reclaimed_bytes)
+        .chain(
+            outcome
+                .image_store
+                .iter()
+                .map(|store| store.reclaimed_bytes),
+        )
         .sum();
-    if outcome.state_roots.is_empty() {
+    if outcome.state_roots.is_empty() && outcome.image_store.is_none() {
         reporter.emit(Event::Message {
             severity: Severity::Info,
             text: "No matching state roots found.".to_string(),
@@ -355,6 +378,79 @@ fn clean_state_root(
     })
 }
 
+/// Remove store blobs that no discovered workspace still links to and no
+/// store tag names.
+///
+/// Workspaces cleaned in this run no longer count as holders, so a dry run
+/// reports the same images a real run would collect.
+fn collect_unreferenced_images(
+    store: &ImageStore,
+    projects_root: &Path,
+    cleaned: &[StateRootCleanup],
+    options: &CleanOptions,
+    reporter: &mut ReporterProxy<'_, '_>,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<ImageStoreCleanup> {
+    let registry = WorkspaceRegistry::discover_with_roots(&[projects_root.to_path_buf()])?;
+    diagnostics.extend(registry.diagnostics().iter().cloned());
+
+    let released: HashSet<PathBuf> = cleaned
+        .iter()
+        .filter(|cleanup| {
+            cleanup.actions.iter().any(|action| match action {
+                CleanupAction::Removed { kind, .. } => *kind == CleanupKind::Images,
+                CleanupAction::Skipped { kind, reason, .. } => {
+                    *kind == CleanupKind::Images && *reason == SkipReason::DryRun
+                }
+            })
+        })
+        .map(|cleanup| canonicalize_or_self(&cleanup.state_root))
+        .collect();
+
+    let mut referenced: HashSet<String> = registry
+        .image_references()
+        .into_iter()
+        .filter(|reference| !released.contains(&canonicalize_or_self(&reference.state_root)))
+        .map(|reference| reference.digest)
+        .collect();
+    referenced.extend(store.tagged_digests()?);
+
+    let mut actions = Vec::new();
+    let mut reclaimed = 0u64;
+    let mut retained = 0usize;
+    for image in store.entries()? {
+        if referenced.contains(&image.digest) {
+            retained += 1;
+            continue;
+        }
+        let removed = process_target(
+            &image.path,
+            CleanupKind::StoreImage,
+            options,
+            reporter,
+            &mut actions,
+            true,
+        )?;
+        if !options.dry_run && !image.path.exists() {
+            for sidecar in store.sidecars(&image.digest) {
+                let _ = fs::remove_file(sidecar);
+            }
+        }
+        reclaimed += removed;
+    }
+
+    Ok(ImageStoreCleanup {
+        root: store.root().to_path_buf(),
+        reclaimed_bytes: reclaimed,
+        retained,
+        actions,
+    })
+}
+
+fn canonicalize_or_self(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
 fn collect_pid_paths(
     state_root: &Path,
     overlays: &[PathBuf],
@@ -488,6 +584,11 @@ fn measure_path(path: &Path) -> io::Result<u64> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::{
+        BaseImageSource, BootstrapConfig, ImagesConfig, LifecycleConfig, ProjectConfig,
+        ProjectFeatures, VmDefinition, Workflows,
+    };
+    use crate::core::runtime::{AssetPreparation, ResolvedVmAssets};
     use tempfile::tempdir;
 
     fn base_options(scope: CleanScope) -> CleanOptions {
@@ -556,4 +657,162 @@ mod tests {
         assert!(!result.value.state_roots.is_empty());
         assert!(result.value.state_roots[0].reclaimed_bytes > 0);
     }
+
+    fn write_workspace(state_root: &Path, image_path: &Path, digest: &str) {
+        fs::create_dir_all(state_root.join("metadata")).expect("metadata dir");
+        let metadata = serde_json::json!({
+            "metadata_version": "1",
+            "recorded_at": "2024-01-01T00:00:00Z",
+            "project": { "name": "demo", "version": "0.2.0", "root": state_root },
+            "workspace": { "id": digest.get(..8).unwrap(), "state_root": state_root },
+            "config": {
+                "path": state_root.join("castra.toml"),
+                "source": { "kind": "discover", "allow_synthetic": false }
+            },
+            "bootstrap": { "global_mode": "auto", "overrides": {} },
+            "invocation": { "plan": false, "force": false, "bootstrap_overrides_applied": false },
+            "images": [{ "digest": digest, "path": image_path }]
+        });
+        fs::write(
+            state_root.join("metadata").join("workspace.json"),
+            serde_json::to_vec_pretty(&metadata).unwrap(),
+        )
+        .expect("workspace metadata");
+    }
+
+    #[test]
+    fn global_clean_collects_only_unreferenced_store_images() {
+        let temp = tempdir().expect("tempdir");
+        let store = ImageStore::new(temp.path().join("images"));
+        let projects_root = temp.path().join("projects");
+        let extra_root = temp.path().join("extra");
+
+        let seed = |name: &str, contents: &[u8]| {
+            let source = temp.path().join(name);
+            fs::write(&source, contents).expect("seed image");
+            store.import(&source, None, true).expect("import image")
+        };
+        let kept = seed("kept.qcow2", b"kept-image");
+        let released = seed("released.qcow2", b"released-image");
+        let orphan = seed("orphan.qcow2", b"orphan-image");
+
+        let outside = extra_root.join("outside");
+        let outside_image = outside.join("images").join("kept.qcow2");
+        store.link_into(&kept, &outside_image).expect("link kept");
+        write_workspace(&outside, &outside_image, &kept);
+
+        let managed = projects_root.join("managed");
+        let managed_image = managed.join("images").join("released.qcow2");
+        store
+            .link_into(&released, &managed_image)
+            .expect("link released");
+        write_workspace(&managed, &managed_image, &released);
+
+        let options = base_options(CleanScope::Global {
+            projects_root: projects_root.clone(),
+            image_store_root: store.root().to_path_buf(),
+        });
+        let result = temp_env::with_vars(
+            [
+                ("HOME", Some(temp.path().as_os_str())),
+                ("CASTRA_WORKSPACE_ROOTS", Some(extra_root.as_os_str())),
+            ],
+            || clean(options, None).expect("clean result"),
+        );
+
+        assert!(store.blob_path(&kept).exists());
+        assert!(!store.blob_path(&released).exists());
+        assert!(!store.blob_path(&orphan).exists());
+        let summary = result.value.image_store.expect("image store summary");
+        assert_eq!(summary.retained, 1);
+        assert_eq!(
+            summary
+                .actions
+                .iter()
+                .filter(|action| matches!(
+                    action,
+                    CleanupAction::Removed {
+                        kind: CleanupKind::StoreImage,
+                        ..
+                    }
+                ))
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn global_clean_retains_tagged_and_explicitly_referenced_images() {
+        let temp = tempdir().expect("tempdir");
+        let store = ImageStore::new(temp.path().join("images"));
+        let projects_root = temp.path().join("projects");
+        let extra_root = temp.path().join("extra");
+
+        let seed = |name: &str, contents: &[u8]| {
+            let source = temp.path().join(name);
+            fs::write(&source, contents).expect("seed image");
+            store.import(&source, None, true).expect("import image")
+        };
+        // `castra image commit` imports the flattened overlay, then tags it
+        // under its name and its golden key.
+        let committed = seed("committed.qcow2", b"committed-image");
+        store.tag("devbox-ready", &committed).expect("tag name");
+        let golden = seed("golden.qcow2", b"golden-image");
+        store
+            .tag(&format!("golden/{committed}-abc"), &golden)
+            .expect("tag golden");
+        let explicit = seed("explicit.qcow2", b"explicit-image");
+        let orphan = seed("orphan.qcow2", b"orphan-image");
+
+        // A workspace booting straight from the blob path, as `image commit`
+        // suggests for `base_image`.
+        let workspace = extra_root.join("explicit");
+        let project = ProjectConfig {
+            file_path: workspace.join("castra.toml"),
+            project_root: workspace.clone(),
+            version: "0.2.0".to_string(),
+            project_name: "explicit".to_string(),
+            features: ProjectFeatures,
+            vms: vec![VmDefinition {
+                base_image: BaseImageSource::from_explicit(store.blob_path(&explicit)),
+                ..VmDefinition::for_test("devbox")
+            }],
+            state_root: workspace.clone(),
+            workflows: Workflows::default(),
+            lifecycle: LifecycleConfig::default(),
+            bootstrap: BootstrapConfig::default(),
+            images: ImagesConfig::default(),
+            warnings: Vec::new(),
+        };
+        let preparations = [AssetPreparation {
+            assets: ResolvedVmAssets { boot: None },
+            overlay_created: false,
+            overlay_reclaimed_bytes: None,
+            base_image_digest: None,
+            events: Vec::new(),
+        }];
+        let images = super::super::collect_image_references(&project, &preparations, &store);
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[0].digest, explicit);
+        write_workspace(&workspace, &images[0].path, &images[0].digest);
+
+        let options = base_options(CleanScope::Global {
+            projects_root: projects_root.clone(),
+            image_store_root: store.root().to_path_buf(),
+        });
+        let result = temp_env::with_vars(
+            [
+                ("HOME", Some(temp.path().as_os_str())),
+                ("CASTRA_WORKSPACE_ROOTS", Some(extra_root.as_os_str())),
+            ],
+            || clean(options, None).expect("clean result"),
+        );
+
+        assert!(store.blob_path(&committed).exists());
+        assert!(store.blob_path(&golden).exists());
+        assert!(store.blob_path(&explicit).exists());
+        assert!(!store.blob_path(&orphan).exists());
+        let summary = result.value.image_store.expect("image store summary");
+        assert_eq!(summary.retained, 3);
+    }
 }
@@ -0,0 +1,44 @@
+use crate::config::VmDefinition;
+use crate::error::Error;
+
+use crate::core::collect as collect_core;
+use crate::core::options::CollectOptions;
+use crate::core::outcome::{CollectOutcome, OperationOutput, OperationResult};
+use crate::core::reporter::Reporter;
+use crate::core::transport;
+
+use super::{ReporterProxy, resolve_single_project, running_vms};
+
+/// Copy the `collect` paths of running VMs to the host.
+pub(super) fn collect(
+    options: CollectOptions,
+    reporter: Option<&mut dyn Reporter>,
+) -> OperationResult<CollectOutcome> {
+    let mut diagnostics = Vec::new();
+    let mut events = Vec::new();
+    let mut reporter = ReporterProxy::new(reporter, &mut events);
+
+    let project = resolve_single_project(
+        options.workspace.as_ref(),
+        &options.config,
+        &mut diagnostics,
+    )?;
+    let targets: Vec<&VmDefinition> =
+        running_vms(&project, &options.vms, "collect", &mut diagnostics)?
+            .into_iter()
+            .map(|(index, _)| &project.vms[index])
+            .filter(|vm| !vm.collect.is_empty())
+            .collect();
+    if targets.is_empty() {
+        return Err(Error::PreflightFailed {
+            message: "None of the running VMs declare `collect` rules. Add e.g. `collect = [{ guest = \"/var/log/app\", host = \"artifacts/{vm}\" }]` to a `[[vms]]` entry.".to_string(),
+        });
+    }
+
+    let transport = transport::for_kind(project.bootstrap.transport);
+    let artifacts = collect_core::collect_vms(&targets, transport.as_ref(), &mut reporter);
+
+    Ok(OperationOutput::new(CollectOutcome { artifacts })
+        .with_diagnostics(diagnostics)
+        .with_events(events))
+}
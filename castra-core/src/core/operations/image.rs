@@ -0,0 +1,132 @@
+use std::fs;
+use std::io::ErrorKind;
+
+use crate::config::BootstrapMode;
+use crate::error::Error;
+
+use crate::core::diagnostics::{Diagnostic, Severity};
+use crate::core::events::{EphemeralCleanupReason, Event, ShutdownOutcome};
+use crate::core::golden;
+use crate::core::image_store::ImageStore;
+use crate::core::options::ImageCommitOptions;
+use crate::core::outcome::{ImageCommitOutcome, OperationOutput, OperationResult};
+use crate::core::project::config_state_root;
+use crate::core::reporter::Reporter;
+use crate::core::runtime::{ShutdownTimeouts, locate_qemu_img, shutdown_vm_retaining_overlay};
+
+use super::{ReporterProxy, load_project_for_operation};
+
+pub(super) fn image_commit(
+    options: ImageCommitOptions,
+    reporter: Option<&mut dyn Reporter>,
+) -> OperationResult<ImageCommitOutcome> {
+    let mut diagnostics = Vec::new();
+    let mut events = Vec::new();
+    let mut reporter = ReporterProxy::new(reporter, &mut events);
+
+    golden::validate_image_name(&options.name)?;
+    let (project, _) = load_project_for_operation(&options.config, &mut diagnostics)?;
+    let Some(vm) = project.vms.iter().find(|vm| vm.name == options.vm) else {
+        let known: Vec<&str> = project.vms.iter().map(|vm| vm.name.as_str()).collect();
+        return Err(Error::PreflightFailed {
+            message: format!(
+                "Unknown VM `{}`. Configured VMs: {}.",
+                options.vm,
+                known.join(", ")
+            ),
+        });
+    };
+
+    let Some(qemu_img) = locate_qemu_img() else {
+        return Err(Error::PreflightFailed {
+            message: "Committing an image requires `qemu-img` but it was not found in PATH. Install QEMU tooling (e.g. `brew install qemu` or `sudo apt install qemu-utils`).".to_string(),
+        });
+    };
+
+    let state_root = config_state_root(&project);
+    let timeouts = ShutdownTimeouts::new(
+        project.lifecycle.graceful_wait(),
+        project.lifecycle.sigterm_wait(),
+        project.lifecycle.sigkill_wait(),
+    );
+    let report = shutdown_vm_retaining_overlay(vm, &state_root, timeouts, None)?;
+    for event in report.events {
+        reporter.emit(event);
+    }
+    diagnostics.extend(report.diagnostics);
+
+    if report.outcome != ShutdownOutcome::Graceful {
+        diagnostics.push(
+            Diagnostic::new(
+                Severity::Warning,
+                format!(
+                    "VM `{}` required signals to stop; the committed image may contain unflushed writes.",
+                    vm.name
+                ),
+            )
+            .with_help("Shut the guest down cleanly (e.g. `poweroff` over SSH) and commit again if the image misbehaves."),
+        );
+    }
+
+    let store = ImageStore::open_default();
+    let golden_tag = if vm.bootstrap.mode == BootstrapMode::Skip {
+        None
+    } else {
+        golden::key_for_vm(vm, &store)?.map(|key| key.tag())
+    };
+    let mut tags = vec![options.name.clone()];
+    tags.extend(golden_tag.clone());
+
+    let digest = golden::bake(vm, &qemu_img, &store, &tags)?;
+    reporter.emit(Event::Message {
+        severity: Severity::Info,
+        text: format!(
+            "Committed VM `{}` as image `{}` (sha256:{}).",
+            vm.name,
+            options.name,
+            &digest[..12]
+        ),
+    });
+
+    match fs::metadata(&vm.overlay) {
+        Ok(metadata) => {
+            fs::remove_file(&vm.overlay).map_err(|err| Error::PreflightFailed {
+                message: format!(
+                    "Failed to remove overlay {} for VM `{}` after committing: {err}",
+                    vm.overlay.display(),
+                    vm.name
+                ),
+            })?;
+            reporter.emit(Event::EphemeralLayerDiscarded {
+                vm: vm.name.clone(),
+                overlay_path: vm.overlay.clone(),
+                reclaimed_bytes: metadata.len(),
+                reason: EphemeralCleanupReason::Shutdown,
+            });
+        }
+        Err(err) if err.kind() == ErrorKind::NotFound => {}
+        Err(err) => {
+            diagnostics.push(Diagnostic::new(
+                Severity::Warning,
+                format!(
+                    "Unable to inspect overlay {} for VM `{}`: {err}",
+                    vm.overlay.display(),
+                    vm.name
+                ),
+            ));
+        }
+    }
+
+    let outcome = ImageCommitOutcome {
+        vm: vm.name.clone(),
+        name: options.name,
+        image_path: store.blob_path(&digest),
+        digest,
+        golden_tag,
+        stopped_vm: report.changed,
+    };
+
+    Ok(OperationOutput::new(outcome)
+        .with_diagnostics(diagnostics)
+        .with_events(events))
+}
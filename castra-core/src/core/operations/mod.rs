@@ -3,21 +3,36 @@ use std::panic;
 use std::path::{Path, PathBuf};
 use std::sync::mpsc;
 use std::thread;
+use std::time::Duration;
 
+mod bootstrap;
 mod clean;
+mod collect;
+mod image;
+mod project_config;
+mod remote;
 
-use super::bootstrap;
+use super::bootstrap as bootstrap_core;
+use super::collect as collect_core;
 use super::diagnostics::{Diagnostic, Severity};
 use super::events::{EphemeralCleanupReason, Event, ShutdownOutcome};
+use super::golden;
+use super::hooks;
+use super::image_store::ImageStore;
 use super::logs as logs_core;
 use super::options::{
-    BootstrapOverrides, CleanOptions, ConfigLoadOptions, DownOptions, InitOptions, LogsOptions,
-    PortsOptions, StatusOptions, UpOptions,
+    BootstrapDiffOptions, BootstrapHistoryOptions, BootstrapOptions, BootstrapOverrides,
+    BootstrapShowOptions, CleanOptions, CollectOptions, ConfigLoadOptions, ConfigMigrateOptions,
+    ConfigShowOptions, ConfigValidateOptions, DownOptions, ExecOptions, ImageCommitOptions,
+    InitOptions, LogsOptions, PortsOptions, SshOptions, StatusOptions, UpOptions,
 };
 use super::outcome::{
-    BootstrapRunStatus, CleanOutcome, DownOutcome, InitOutcome, LogsOutcome, OperationOutput,
-    OperationResult, PortsOutcome, ProjectPortsOutcome, ProjectStatusOutcome, StatusOutcome,
-    UpOutcome, VmLaunchOutcome, VmShutdownOutcome, VmStatusRow,
+    BootstrapDiffOutcome, BootstrapHistoryOutcome, BootstrapOutcome, BootstrapRunOutcome,
+    BootstrapRunStatus, BootstrapShowOutcome, CleanOutcome, CollectOutcome, ConfigMigrateOutcome,
+    ConfigShowOutcome, ConfigValidateOutcome, DownOutcome, ExecOutcome, ImageCommitOutcome,
+    InitOutcome, LogsOutcome, OperationOutput, OperationResult, PortsOutcome, ProjectPortsOutcome,
+    ProjectStatusOutcome, SshOutcome, StatusOutcome, UpOutcome, VmLaunchOutcome, VmShutdownOutcome,
+    VmStatusRow,
 };
 use super::ports as ports_core;
 use super::project::{
@@ -26,12 +41,20 @@ use super::project::{
 };
 use super::reporter::Reporter;
 use super::runtime::{
-    CheckOutcome, ShutdownTimeouts, check_disk_space, check_host_capacity, ensure_ports_available,
-    ensure_vm_assets, launch_vm, prepare_runtime_context, shutdown_vm,
+    AssetPreparation, CheckOutcome, RuntimeContext, ShutdownTimeouts, check_disk_space,
+    check_host_capacity, ensure_ports_available, ensure_vm_assets, launch_vm,
+    prepare_runtime_context, shutdown_vm, shutdown_vm_retaining_overlay,
 };
 use super::status as status_core;
-use super::workspace_registry::{WorkspaceHandle, WorkspaceRegistry, persist_workspace_metadata};
-use crate::config::{self, BaseImageProvenance, BaseImageSource, ProjectConfig};
+use super::transport;
+use super::workspace_registry::{
+    WorkspaceHandle, WorkspaceImageMetadata, WorkspaceRegistry, persist_workspace_metadata,
+    record_workspace_images,
+};
+use crate::config::{
+    self, BaseImageProvenance, BaseImageSource, DependencyCondition, HookPoint, ProjectConfig,
+    VmDefinition,
+};
 use crate::error::{Error, Result};
 
 fn resolve_qcow_override_path(raw: &Path) -> Result<PathBuf> {
@@ -104,6 +127,38 @@ fn apply_alpine_qcow_override(
     Ok(())
 }
 
+/// Store images the project's VMs boot from: managed and golden images, plus
+/// explicit `base_image` paths that resolve to a store blob.
+fn collect_image_references(
+    project: &ProjectConfig,
+    preparations: &[AssetPreparation],
+    store: &ImageStore,
+) -> Vec<WorkspaceImageMetadata> {
+    let mut images: Vec<WorkspaceImageMetadata> = Vec::new();
+    for (vm, prep) in project.vms.iter().zip(preparations) {
+        let Some(digest) = prep
+            .base_image_digest
+            .clone()
+            .or_else(|| store.digest_for_blob_path(vm.base_image.path()))
+        else {
+            continue;
+        };
+        let path = vm.base_image.path().to_path_buf();
+        if images.iter().any(|image| image.path == path) {
+            continue;
+        }
+        images.push(WorkspaceImageMetadata {
+            digest,
+            tag: path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .map(ToString::to_string),
+            path,
+        });
+    }
+    images
+}
+
 pub fn init(
     mut options: InitOptions,
     reporter: Option<&mut dyn Reporter>,
@@ -190,7 +245,7 @@ fn up_internal(
     let mut reporter_proxy = ReporterProxy::new(reporter, &mut events);
 
     if options.plan {
-        let plans = bootstrap::plan_all(&project, &mut reporter_proxy, &mut diagnostics)?;
+        let plans = bootstrap_core::plan_all(&project, &mut reporter_proxy, &mut diagnostics)?;
         reporter_proxy.emit(Event::Message {
             severity: Severity::Info,
             text: "Plan mode only – no VMs were launched.".to_string(),
@@ -238,7 +293,8 @@ fn up_internal(
             "Rerun with `castra up --force` to override.",
         )?;
 
-        let context = prepare_runtime_context(&project, options.launch_mode)?;
+        let mut context = prepare_runtime_context(&project, options.launch_mode)?;
+        context.image_sources.offline = options.offline;
 
         persist_workspace_metadata(
             &project,
@@ -259,10 +315,25 @@ fn up_internal(
         ensure_ports_available(&project)?;
 
         let mut preparations = Vec::new();
-        for vm in &project.vms {
-            let prep = ensure_vm_assets(vm, &context)?;
-            for event in prep.events.iter().cloned() {
-                reporter.emit(event);
+        for vm in &mut project.vms {
+            let golden_digest = match golden::lookup_for_vm(vm, &context.image_store)? {
+                Some((_, digest)) => {
+                    golden::adopt(vm, &context.image_store, &context.state_root, &digest)?;
+                    reporter.emit(Event::Message {
+                        severity: Severity::Info,
+                        text: format!(
+                            "Booting VM `{}` from golden image sha256:{}; bootstrap already baked in.",
+                            vm.name,
+                            &digest[..12]
+                        ),
+                    });
+                    Some(digest)
+                }
+                None => None,
+            };
+            let mut prep = ensure_vm_assets(vm, &context, Some(&mut reporter))?;
+            if golden_digest.is_some() {
+                prep.base_image_digest = golden_digest;
             }
             if let Some(bytes) = prep.overlay_reclaimed_bytes {
                 reporter.emit(Event::EphemeralLayerDiscarded {
@@ -281,37 +352,200 @@ fn up_internal(
             preparations.push(prep);
         }
 
-        let mut launched_vms = Vec::new();
-        for (vm, prep) in project.vms.iter().zip(preparations.iter()) {
-            let pid = reporter
-                .with_event_buffer(|events| launch_vm(vm, &prep.assets, &context, events))?;
-            launched_vms.push(VmLaunchOutcome {
-                name: vm.name.clone(),
-                pid,
-                base_image: vm.base_image.path().to_path_buf(),
-                base_image_provenance: vm.base_image.provenance(),
-                overlay_created: prep.overlay_created,
-                port_forwards: vm.port_forwards.clone(),
+        record_workspace_images(
+            &context.state_root,
+            collect_image_references(&project, &preparations, &context.image_store),
+            &mut diagnostics,
+        )?;
+
+        for vm in &project.vms {
+            hooks::run_hooks(
+                HookPoint::PreUp,
+                &project,
+                &context.state_root,
+                vm,
+                &mut reporter,
+            )?;
+        }
+
+        let waves = project.startup_waves();
+        if waves.len() > 1 {
+            reporter.emit(Event::Message {
+                severity: Severity::Info,
+                text: format!(
+                    "Starting {} VM(s) in {} dependency waves.",
+                    project.vms.len(),
+                    waves.len()
+                ),
             });
         }
 
+        let mut launch_slots: Vec<Option<VmLaunchOutcome>> =
+            project.vms.iter().map(|_| None).collect();
+        let mut bootstrap_slots: Vec<Option<BootstrapRunOutcome>> =
+            project.vms.iter().map(|_| None).collect();
+        let mut healthy = vec![false; project.vms.len()];
+        let health_transport = transport::for_kind(project.bootstrap.transport);
+        for (wave_index, wave) in waves.iter().enumerate() {
+            let mut needs_bootstrap = false;
+            let mut needs_health = Vec::new();
+            for &index in wave {
+                for dependency in &project.vms[index].depends_on {
+                    let Some(dep_index) =
+                        project.vms.iter().position(|vm| vm.name == dependency.vm)
+                    else {
+                        continue;
+                    };
+                    if dependency.condition >= DependencyCondition::Bootstrapped
+                        && bootstrap_slots[dep_index].is_none()
+                    {
+                        needs_bootstrap = true;
+                    }
+                    if dependency.condition == DependencyCondition::Healthy
+                        && !healthy[dep_index]
+                        && !needs_health.contains(&dep_index)
+                    {
+                        needs_health.push(dep_index);
+                    }
+                }
+            }
+
+            if needs_bootstrap {
+                bootstrap_pending_waves(
+                    &project,
+                    &context,
+                    &waves,
+                    &launch_slots,
+                    &mut bootstrap_slots,
+                    &mut reporter,
+                    &mut diagnostics,
+                )?;
+            }
+            for dep_index in needs_health {
+                let dependency = &project.vms[dep_index];
+                reporter.emit(Event::Message {
+                    severity: Severity::Info,
+                    text: format!("Waiting for VM `{}` to become healthy.", dependency.name),
+                });
+                let timeout = Duration::from_secs(dependency.bootstrap.handshake_timeout_secs);
+                match bootstrap_core::wait_until_healthy(
+                    dependency,
+                    timeout,
+                    health_transport.as_ref(),
+                ) {
+                    Ok(elapsed) => {
+                        healthy[dep_index] = true;
+                        reporter.emit(Event::Message {
+                            severity: Severity::Info,
+                            text: format!(
+                                "VM `{}` is healthy ({}s).",
+                                dependency.name,
+                                elapsed.as_secs()
+                            ),
+                        });
+                    }
+                    Err(message) => {
+                        let dependents: Vec<&str> = wave
+                            .iter()
+                            .map(|&index| &project.vms[index])
+                            .filter(|vm| vm.depends_on.iter().any(|dep| dep.vm == dependency.name))
+                            .map(|vm| vm.name.as_str())
+                            .collect();
+                        return Err(Error::LaunchFailed {
+                            vm: dependents.join(", "),
+                            message: format!("dependency `{}` is {message}", dependency.name),
+                        });
+                    }
+                }
+            }
+
+            if waves.len() > 1 {
+                let names: Vec<&str> = wave
+                    .iter()
+                    .map(|&index| project.vms[index].name.as_str())
+                    .collect();
+                reporter.emit(Event::Message {
+                    severity: Severity::Info,
+                    text: format!(
+                        "Wave {}/{}: launching {}.",
+                        wave_index + 1,
+                        waves.len(),
+                        names.join(", ")
+                    ),
+                });
+            }
+            for &index in wave {
+                let vm = &project.vms[index];
+                let prep = &preparations[index];
+                let pid = reporter
+                    .with_event_buffer(|events| launch_vm(vm, &prep.assets, &context, events))?;
+                hooks::run_hooks(
+                    HookPoint::PostLaunch,
+                    &project,
+                    &context.state_root,
+                    vm,
+                    &mut reporter,
+                )?;
+                launch_slots[index] = Some(VmLaunchOutcome {
+                    name: vm.name.clone(),
+                    pid,
+                    base_image: vm.base_image.path().to_path_buf(),
+                    base_image_provenance: vm.base_image.provenance(),
+                    overlay_created: prep.overlay_created,
+                    port_forwards: vm.port_forwards.clone(),
+                });
+            }
+        }
+
         reporter.emit(Event::Message {
             severity: Severity::Info,
-            text: format!("Launched {} VM(s).", launched_vms.len()),
+            text: format!("Launched {} VM(s).", launch_slots.iter().flatten().count()),
         });
 
         reporter.emit(Event::Message {
                 severity: Severity::Info,
-                text: "Guest disk changes are ephemeral; export via SSH before running `castra down` if you need to retain data.".to_string(),
+                text: "Guest disk changes are ephemeral; list paths to keep under a VM's `collect = [...]` (copied during `castra down`, before shutdown) or run `castra collect`.".to_string(),
             });
 
-        let bootstrap_runs = bootstrap::run_all(
+        bootstrap_pending_waves(
             &project,
             &context,
-            &preparations,
+            &waves,
+            &launch_slots,
+            &mut bootstrap_slots,
             &mut reporter,
             &mut diagnostics,
         )?;
+        let mut launched_vms: Vec<VmLaunchOutcome> = launch_slots.into_iter().flatten().collect();
+        let bootstrap_runs: Vec<BootstrapRunOutcome> =
+            bootstrap_slots.into_iter().flatten().collect();
+
+        let baked = bake_golden_images(
+            &mut project,
+            &context,
+            &mut preparations,
+            &bootstrap_runs,
+            &mut launched_vms,
+            &mut reporter,
+            &mut diagnostics,
+        )?;
+        if baked {
+            record_workspace_images(
+                &context.state_root,
+                collect_image_references(&project, &preparations, &context.image_store),
+                &mut diagnostics,
+            )?;
+        }
+
+        for &index in waves.iter().flatten() {
+            hooks::run_hooks(
+                HookPoint::PostBootstrap,
+                &project,
+                &context.state_root,
+                &project.vms[index],
+                &mut reporter,
+            )?;
+        }
 
         if !bootstrap_runs.is_empty() {
             let success = bootstrap_runs
@@ -353,6 +587,133 @@ fn up_internal(
         .with_events(events))
 }
 
+/// Bootstrap every launched VM that has not been bootstrapped yet, one
+/// dependency wave at a time so dependencies finish before their dependents.
+fn bootstrap_pending_waves(
+    project: &ProjectConfig,
+    context: &RuntimeContext,
+    waves: &[Vec<usize>],
+    launched: &[Option<VmLaunchOutcome>],
+    bootstraps: &mut [Option<BootstrapRunOutcome>],
+    reporter: &mut dyn Reporter,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<()> {
+    for wave in waves {
+        let pending: Vec<usize> = wave
+            .iter()
+            .copied()
+            .filter(|&index| launched[index].is_some() && bootstraps[index].is_none())
+            .collect();
+        if pending.is_empty() {
+            continue;
+        }
+        let runs = bootstrap_core::run_selected(project, context, &pending, reporter, diagnostics)?;
+        for (index, run) in pending.into_iter().zip(runs) {
+            bootstraps[index] = Some(run);
+        }
+    }
+    Ok(())
+}
+
+/// Flatten freshly bootstrapped overlays of `bake = true` VMs into golden
+/// images and relaunch those VMs on top of the result.
+fn bake_golden_images(
+    project: &mut ProjectConfig,
+    context: &RuntimeContext,
+    preparations: &mut [AssetPreparation],
+    bootstrap_runs: &[BootstrapRunOutcome],
+    launched_vms: &mut [VmLaunchOutcome],
+    reporter: &mut ReporterProxy<'_, '_>,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<bool> {
+    let Some(qemu_img) = context.qemu_img.clone() else {
+        return Ok(false);
+    };
+    let timeouts = ShutdownTimeouts::new(
+        project.lifecycle.graceful_wait(),
+        project.lifecycle.sigterm_wait(),
+        project.lifecycle.sigkill_wait(),
+    );
+
+    let mut baked_any = false;
+    for (index, vm) in project.vms.iter_mut().enumerate() {
+        if !vm.bootstrap.bake {
+            continue;
+        }
+        let succeeded = bootstrap_runs
+            .iter()
+            .any(|run| run.vm == vm.name && matches!(run.status, BootstrapRunStatus::Success));
+        if !succeeded {
+            continue;
+        }
+        let Some(key) = golden::key_for_vm(vm, &context.image_store)? else {
+            continue;
+        };
+
+        reporter.emit(Event::Message {
+            severity: Severity::Info,
+            text: format!(
+                "Baking VM `{}` into a golden image; it will restart once the overlay is flattened.",
+                vm.name
+            ),
+        });
+        let report = shutdown_vm_retaining_overlay(vm, &context.state_root, timeouts, None)?;
+        for event in report.events {
+            reporter.emit(event);
+        }
+        diagnostics.extend(report.diagnostics);
+
+        if report.outcome != ShutdownOutcome::Graceful {
+            diagnostics.push(
+                Diagnostic::new(
+                    Severity::Warning,
+                    format!(
+                        "VM `{}` did not shut down cleanly; skipped baking a golden image to avoid capturing an inconsistent disk.",
+                        vm.name
+                    ),
+                )
+                .with_help("Run `castra image commit` once the guest is idle to bake it manually."),
+            );
+        } else {
+            match golden::bake(vm, &qemu_img, &context.image_store, &[key.tag()]) {
+                Ok(digest) => {
+                    golden::adopt(vm, &context.image_store, &context.state_root, &digest)?;
+                    let mut prep = ensure_vm_assets(vm, context, Some(&mut *reporter))?;
+                    prep.base_image_digest = Some(digest.clone());
+                    preparations[index] = prep;
+                    reporter.emit(Event::Message {
+                        severity: Severity::Info,
+                        text: format!(
+                            "Baked VM `{}` into golden image sha256:{}; later runs boot from it and skip bootstrap.",
+                            vm.name,
+                            &digest[..12]
+                        ),
+                    });
+                    baked_any = true;
+                }
+                Err(err) => {
+                    diagnostics.push(
+                        Diagnostic::new(
+                            Severity::Warning,
+                            format!("Failed to bake golden image for VM `{}`: {err}", vm.name),
+                        )
+                        .with_help("The VM restarts from its existing overlay; bootstrap will run again on the next `up`."),
+                    );
+                }
+            }
+        }
+
+        let assets = &preparations[index].assets;
+        let pid = reporter.with_event_buffer(|events| launch_vm(vm, assets, context, events))?;
+        let launched = &mut launched_vms[index];
+        launched.pid = pid;
+        launched.base_image = vm.base_image.path().to_path_buf();
+        launched.base_image_provenance = vm.base_image.provenance();
+    }
+
+    Ok(baked_any)
+}
+
 pub fn down(
     options: DownOptions,
     reporter: Option<&mut dyn Reporter>,
@@ -376,6 +737,7 @@ pub fn down(
         StatusTarget::Workspaces(workspaces) => {
             let multi = workspaces.len() > 1;
             let mut combined_vm_results = Vec::new();
+            let mut combined_collected = Vec::new();
 
             for WorkspaceProject { handle, project } in workspaces {
                 if multi {
@@ -397,10 +759,12 @@ pub fn down(
 
                 let outcome = down_project(project, &options, &mut reporter, &mut diagnostics)?;
                 combined_vm_results.extend(outcome.vm_results);
+                combined_collected.extend(outcome.collected);
             }
 
             DownOutcome {
                 vm_results: combined_vm_results,
+                collected: combined_collected,
             }
         }
     };
@@ -503,6 +867,95 @@ fn project_status_from_components(
     }
 }
 
+/// Resolve exactly one project for commands that act on live VMs, refusing
+/// to guess between several active workspaces.
+fn resolve_single_project(
+    workspace: Option<&String>,
+    config: &ConfigLoadOptions,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<ProjectConfig> {
+    match resolve_project_targets(workspace, config, config.allow_synthetic, diagnostics)? {
+        StatusTarget::Single { project, .. } => Ok(project),
+        StatusTarget::Workspaces(mut workspaces) if workspaces.len() == 1 => {
+            Ok(workspaces.remove(0).project)
+        }
+        StatusTarget::Workspaces(workspaces) => {
+            let ids: Vec<&str> = workspaces
+                .iter()
+                .map(|workspace| workspace.handle.workspace_id.as_str())
+                .collect();
+            Err(Error::PreflightFailed {
+                message: format!(
+                    "Several workspaces are active: {}. Select one with --workspace or --config.",
+                    ids.join(", ")
+                ),
+            })
+        }
+    }
+}
+
+/// Indices (into `project.vms`) and uptimes of the running VMs among
+/// `names`, or among all VMs when `names` is empty. Naming an unknown or
+/// stopped VM, or finding none running, fails preflight.
+fn running_vms(
+    project: &ProjectConfig,
+    names: &[String],
+    command: &str,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<Vec<(usize, Option<Duration>)>> {
+    for name in names {
+        if !project.vms.iter().any(|vm| vm.name == *name) {
+            let known: Vec<&str> = project.vms.iter().map(|vm| vm.name.as_str()).collect();
+            return Err(Error::PreflightFailed {
+                message: format!("Unknown VM `{name}`. Configured VMs: {}.", known.join(", ")),
+            });
+        }
+    }
+
+    let status_core::StatusSnapshot {
+        rows,
+        diagnostics: mut status_diags,
+        ..
+    } = status_core::collect_status(project);
+    diagnostics.append(&mut status_diags);
+
+    let mut running = Vec::new();
+    let mut stopped = Vec::new();
+    for (index, (vm, row)) in project.vms.iter().zip(&rows).enumerate() {
+        if !names.is_empty() && !names.contains(&vm.name) {
+            continue;
+        }
+        if row.state == "running" {
+            running.push((index, row.uptime));
+        } else {
+            stopped.push(vm.name.as_str());
+        }
+    }
+
+    if !names.is_empty() && !stopped.is_empty() {
+        return Err(Error::PreflightFailed {
+            message: format!(
+                "VMs not running: {}. Start them with `castra up` before running `castra {command}`.",
+                stopped.join(", ")
+            ),
+        });
+    }
+    if running.is_empty() {
+        return Err(Error::PreflightFailed {
+            message: format!(
+                "No VMs are running. Start them with `castra up` before running `castra {command}`."
+            ),
+        });
+    }
+    if !stopped.is_empty() {
+        diagnostics.push(Diagnostic::new(
+            Severity::Info,
+            format!("Skipping VMs that are not running: {}.", stopped.join(", ")),
+        ));
+    }
+    Ok(running)
+}
+
 fn resolve_project_targets(
     workspace: Option<&String>,
     config: &ConfigLoadOptions,
@@ -618,6 +1071,33 @@ fn down_project(
             .unwrap_or_else(|| project.lifecycle.sigkill_wait()),
     );
 
+    let running: Vec<String> = status_core::collect_status(&project)
+        .rows
+        .into_iter()
+        .filter(|row| row.state == "running")
+        .map(|row| row.name)
+        .collect();
+    // Dependents stop before the VMs they depend on.
+    let waves = project.startup_waves();
+    for &index in waves.iter().rev().flatten() {
+        let vm = &project.vms[index];
+        if running.contains(&vm.name) {
+            hooks::run_hooks(HookPoint::PreDown, &project, &state_root, vm, reporter)?;
+        }
+    }
+    // Guest disks are discarded on shutdown; copy artifacts off first.
+    let collecting: Vec<&VmDefinition> = project
+        .vms
+        .iter()
+        .filter(|vm| running.contains(&vm.name) && !vm.collect.is_empty())
+        .collect();
+    let collected = if collecting.is_empty() {
+        Vec::new()
+    } else {
+        let transport = transport::for_kind(project.bootstrap.transport);
+        collect_core::collect_vms(&collecting, transport.as_ref(), reporter)
+    };
+
     struct VmShutdownThreadResult {
         index: usize,
         name: String,
@@ -626,55 +1106,61 @@ fn down_project(
         diagnostics: Vec<Diagnostic>,
     }
 
-    let (event_tx, event_rx) = mpsc::channel::<Event>();
-    let mut handles = Vec::new();
-
     let cooperative = shutdown_timeouts.cooperative;
     let sigterm = shutdown_timeouts.sigterm;
     let sigkill = shutdown_timeouts.sigkill;
 
-    for (index, vm) in project.vms.iter().cloned().enumerate() {
-        let tx_clone = event_tx.clone();
-        let vm_name = vm.name.clone();
-        let vm_state_root = state_root.clone();
-        handles.push(thread::spawn(move || -> Result<VmShutdownThreadResult> {
-            let timeouts = ShutdownTimeouts::new(cooperative, sigterm, sigkill);
-            let report = shutdown_vm(&vm, &vm_state_root, timeouts, Some(&tx_clone))?;
-
-            Ok(VmShutdownThreadResult {
-                index,
-                name: vm_name,
-                changed: report.changed,
-                outcome: report.outcome,
-                diagnostics: report.diagnostics,
-            })
-        }));
-    }
-    drop(event_tx);
-
-    while let Ok(event) = event_rx.recv() {
-        reporter.emit(event);
-    }
-
     let mut first_error: Option<Error> = None;
     let mut vm_slots: Vec<Option<VmShutdownOutcome>> = vec![None; project.vms.len()];
 
-    for handle in handles {
-        match handle.join() {
-            Ok(Ok(result)) => {
-                diagnostics.extend(result.diagnostics);
-                vm_slots[result.index] = Some(VmShutdownOutcome {
-                    name: result.name,
-                    changed: result.changed,
-                    outcome: result.outcome,
-                });
-            }
-            Ok(Err(err)) => {
-                if first_error.is_none() {
-                    first_error = Some(err);
+    for wave in waves.iter().rev() {
+        if first_error.is_some() {
+            break;
+        }
+        let (event_tx, event_rx) = mpsc::channel::<Event>();
+        let mut handles = Vec::new();
+
+        for &index in wave {
+            let vm = project.vms[index].clone();
+            let tx_clone = event_tx.clone();
+            let vm_name = vm.name.clone();
+            let vm_state_root = state_root.clone();
+            handles.push(thread::spawn(move || -> Result<VmShutdownThreadResult> {
+                let timeouts = ShutdownTimeouts::new(cooperative, sigterm, sigkill);
+                let report = shutdown_vm(&vm, &vm_state_root, timeouts, Some(&tx_clone))?;
+
+                Ok(VmShutdownThreadResult {
+                    index,
+                    name: vm_name,
+                    changed: report.changed,
+                    outcome: report.outcome,
+                    diagnostics: report.diagnostics,
+                })
+            }));
+        }
+        drop(event_tx);
+
+        while let Ok(event) = event_rx.recv() {
+            reporter.emit(event);
+        }
+
+        for handle in handles {
+            match handle.join() {
+                Ok(Ok(result)) => {
+                    diagnostics.extend(result.diagnostics);
+                    vm_slots[result.index] = Some(VmShutdownOutcome {
+                        name: result.name,
+                        changed: result.changed,
+                        outcome: result.outcome,
+                    });
+                }
+                Ok(Err(err)) => {
+                    if first_error.is_none() {
+                        first_error = Some(err);
+                    }
                 }
+                Err(payload) => panic::resume_unwind(payload),
             }
-            Err(payload) => panic::resume_unwind(payload),
         }
     }
 
@@ -691,7 +1177,22 @@ fn down_project(
         })
         .collect::<Vec<_>>();
 
-    let outcome = DownOutcome { vm_results };
+    for &index in waves.iter().rev().flatten() {
+        if vm_results[index].changed {
+            hooks::run_hooks(
+                HookPoint::PostDown,
+                &project,
+                &state_root,
+                &project.vms[index],
+                reporter,
+            )?;
+        }
+    }
+
+    let outcome = DownOutcome {
+        vm_results,
+        collected,
+    };
 
     let any_vm = outcome.vm_results.iter().any(|vm| vm.changed);
     if any_vm {
@@ -775,6 +1276,64 @@ pub fn clean(
     clean::clean(options, reporter)
 }
 
+pub fn config_show(options: ConfigShowOptions) -> OperationResult<ConfigShowOutcome> {
+    project_config::config_show(options)
+}
+
+pub fn config_validate(options: ConfigValidateOptions) -> OperationResult<ConfigValidateOutcome> {
+    project_config::config_validate(options)
+}
+
+pub fn config_migrate(options: ConfigMigrateOptions) -> OperationResult<ConfigMigrateOutcome> {
+    project_config::config_migrate(options)
+}
+
+pub fn bootstrap(
+    options: BootstrapOptions,
+    reporter: Option<&mut dyn Reporter>,
+) -> OperationResult<BootstrapOutcome> {
+    bootstrap::bootstrap(options, reporter)
+}
+
+pub fn bootstrap_history(
+    options: BootstrapHistoryOptions,
+) -> OperationResult<BootstrapHistoryOutcome> {
+    bootstrap::bootstrap_history(options)
+}
+
+pub fn bootstrap_show(options: BootstrapShowOptions) -> OperationResult<BootstrapShowOutcome> {
+    bootstrap::bootstrap_show(options)
+}
+
+pub fn bootstrap_diff(options: BootstrapDiffOptions) -> OperationResult<BootstrapDiffOutcome> {
+    bootstrap::bootstrap_diff(options)
+}
+
+pub fn collect(
+    options: CollectOptions,
+    reporter: Option<&mut dyn Reporter>,
+) -> OperationResult<CollectOutcome> {
+    collect::collect(options, reporter)
+}
+
+pub fn ssh(options: SshOptions) -> OperationResult<SshOutcome> {
+    remote::ssh(options)
+}
+
+pub fn exec(
+    options: ExecOptions,
+    reporter: Option<&mut dyn Reporter>,
+) -> OperationResult<ExecOutcome> {
+    remote::exec(options, reporter)
+}
+
+pub fn image_commit(
+    options: ImageCommitOptions,
+    reporter: Option<&mut dyn Reporter>,
+) -> OperationResult<ImageCommitOutcome> {
+    image::image_commit(options, reporter)
+}
+
 pub(super) fn load_project_for_operation(
     options: &ConfigLoadOptions,
     diagnostics: &mut Vec<Diagnostic>,
@@ -906,10 +1465,8 @@ impl Reporter for ReporterProxy<'_, '_> {
 mod tests {
     use super::*;
     use crate::config::{
-        BootstrapConfig, BootstrapMode, DEFAULT_BOOTSTRAP_HANDSHAKE_WAIT_SECS, LifecycleConfig,
-        MemorySpec, ProjectFeatures, VmBootstrapConfig, VmDefinition, Workflows,
+        BootstrapConfig, ImagesConfig, LifecycleConfig, ProjectFeatures, VmDefinition, Workflows,
     };
-    use std::collections::HashMap;
     use std::path::{Path, PathBuf};
     use tempfile::NamedTempFile;
 
@@ -921,31 +1478,17 @@ mod tests {
             project_name: "demo".to_string(),
             features: ProjectFeatures::default(),
             vms: vec![VmDefinition {
-                name: "vm".to_string(),
-                role_name: "vm".to_string(),
-                replica_index: 0,
-                description: None,
                 base_image: BaseImageSource::from_default_alpine(PathBuf::from(
                     "/tmp/state/images/alpine-x86_64.qcow2",
                 )),
                 overlay: PathBuf::from("/tmp/state/overlays/vm-overlay.qcow2"),
-                cpus: 1,
-                memory: MemorySpec::new("512MiB", Some(512 * 1024 * 1024)),
-                port_forwards: Vec::new(),
-                bootstrap: VmBootstrapConfig {
-                    mode: BootstrapMode::Skip,
-                    script: None,
-                    payload: None,
-                    handshake_timeout_secs: DEFAULT_BOOTSTRAP_HANDSHAKE_WAIT_SECS,
-                    remote_dir: PathBuf::from("/tmp/castra-bootstrap"),
-                    env: HashMap::new(),
-                    verify: None,
-                },
+                ..VmDefinition::for_test("vm")
             }],
             state_root: PathBuf::from("/tmp/state"),
-            workflows: Workflows { init: Vec::new() },
+            workflows: Workflows::default(),
             lifecycle: LifecycleConfig::default(),
             bootstrap: BootstrapConfig::default(),
+            images: ImagesConfig::default(),
             warnings: Vec::new(),
         }
     }
@@ -0,0 +1,244 @@
+use std::fs;
+
+use crate::config::{
+    load_project_config_with_profile, migrate_config_source, resolve_config_source,
+};
+use crate::error::Error;
+
+use crate::core::diagnostics::{Diagnostic, Severity};
+use crate::core::options::{ConfigMigrateOptions, ConfigShowOptions, ConfigValidateOptions};
+use crate::core::outcome::{
+    ConfigMigrateOutcome, ConfigShowOutcome, ConfigValidateOutcome, OperationOutput,
+    OperationResult,
+};
+use crate::core::project::resolve_config_path;
+
+use super::load_project_for_operation;
+
+pub(super) fn config_show(options: ConfigShowOptions) -> OperationResult<ConfigShowOutcome> {
+    let mut diagnostics = Vec::new();
+    let (project, synthetic) = load_project_for_operation(&options.config, &mut diagnostics)?;
+
+    let source = if synthetic {
+        None
+    } else {
+        Some(resolve_config_source(
+            &project.file_path,
+            options.config.profile.as_deref(),
+        )?)
+    };
+
+    let outcome = ConfigShowOutcome {
+        config_path: project.file_path.clone(),
+        profile: options.config.profile,
+        synthetic,
+        source,
+        project,
+    };
+    Ok(OperationOutput::new(outcome).with_diagnostics(diagnostics))
+}
+
+pub(super) fn config_validate(
+    options: ConfigValidateOptions,
+) -> OperationResult<ConfigValidateOutcome> {
+    let config_path =
+        resolve_config_path(&options.config.source, options.config.search_root.as_ref())?;
+    let profile = options.config.profile;
+
+    let mut issues = Vec::new();
+    match load_project_config_with_profile(&config_path, profile.as_deref()) {
+        Ok(project) => {
+            for warning in &project.warnings {
+                issues.push(
+                    Diagnostic::new(Severity::Warning, warning).with_path(config_path.clone()),
+                );
+            }
+            for conflict in project.port_conflicts() {
+                issues.push(
+                    Diagnostic::new(
+                        Severity::Error,
+                        format!(
+                            "Host port {} is declared by VMs: {}.",
+                            conflict.port,
+                            conflict.vm_names.join(", ")
+                        ),
+                    )
+                    .with_path(config_path.clone())
+                    .with_help("`castra up` refuses to launch until each host port is unique."),
+                );
+            }
+        }
+        Err(
+            err @ (Error::ReadConfig { .. }
+            | Error::ParseConfig { .. }
+            | Error::InvalidConfig { .. }
+            | Error::DeprecatedConfig { .. }),
+        ) => {
+            let help = match &err {
+                Error::DeprecatedConfig { doc, .. } => Some(format!("See {doc}.")),
+                _ => None,
+            };
+            let mut diagnostic =
+                Diagnostic::new(Severity::Error, err.to_string()).with_path(config_path.clone());
+            if let Some(help) = help {
+                diagnostic = diagnostic.with_help(help);
+            }
+            issues.push(diagnostic);
+        }
+        Err(err) => return Err(err),
+    }
+
+    let valid = !issues.iter().any(|issue| issue.severity == Severity::Error);
+    Ok(OperationOutput::new(ConfigValidateOutcome {
+        config_path,
+        profile,
+        valid,
+        issues,
+    }))
+}
+
+pub(super) fn config_migrate(
+    options: ConfigMigrateOptions,
+) -> OperationResult<ConfigMigrateOutcome> {
+    let config_path =
+        resolve_config_path(&options.config.source, options.config.search_root.as_ref())?;
+    let original = fs::read_to_string(&config_path).map_err(|source| Error::ReadConfig {
+        path: config_path.clone(),
+        source,
+    })?;
+    let migration = migrate_config_source(&config_path, &original)?;
+
+    let written = options.write && !migration.is_noop();
+    if written {
+        fs::write(&config_path, &migration.source).map_err(|source| Error::WriteConfig {
+            path: config_path.clone(),
+            source,
+        })?;
+    }
+
+    let diagnostics = migration
+        .notes
+        .iter()
+        .map(|note| Diagnostic::new(Severity::Warning, note).with_path(config_path.clone()))
+        .collect();
+    Ok(OperationOutput::new(ConfigMigrateOutcome {
+        config_path,
+        original,
+        migrated: migration.source,
+        changes: migration.changes,
+        written,
+    })
+    .with_diagnostics(diagnostics))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::options::ConfigLoadOptions;
+    use std::fs;
+    use tempfile::tempdir;
+
+    const CONFIG: &str = r#"
+version = "0.2.0"
+
+[project]
+name = "demo"
+state_dir = ".castra"
+
+[[vms]]
+name = "web"
+base_image = "images/web.qcow2"
+count = 2
+mystery = true
+
+  [[vms.port_forwards]]
+  host = 8080
+  guest = 80
+"#;
+
+    #[test]
+    fn validate_reports_warnings_and_port_conflicts_as_issues() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("castra.toml");
+        fs::write(&path, CONFIG).unwrap();
+
+        let output = config_validate(ConfigValidateOptions {
+            config: ConfigLoadOptions::explicit(path.clone()),
+        })
+        .unwrap();
+        let outcome = output.value;
+        assert!(!outcome.valid);
+        assert!(outcome.issues.iter().any(|issue| {
+            issue.severity == Severity::Warning && issue.message.contains("mystery")
+        }));
+        assert!(outcome.issues.iter().any(|issue| {
+            issue.severity == Severity::Error && issue.message.contains("Host port 8080")
+        }));
+    }
+
+    #[test]
+    fn validate_turns_load_errors_into_issues() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("castra.toml");
+        fs::write(&path, "version = \"0.1.0\"\n[[vms]]\nname = \n").unwrap();
+
+        let outcome = config_validate(ConfigValidateOptions {
+            config: ConfigLoadOptions::explicit(path.clone()),
+        })
+        .unwrap()
+        .value;
+        assert!(!outcome.valid);
+        assert_eq!(outcome.issues.len(), 1);
+        assert_eq!(outcome.issues[0].path.as_deref(), Some(path.as_path()));
+    }
+
+    #[test]
+    fn migrate_writes_only_when_requested() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("castra.toml");
+        let legacy = "version = \"0.1.0\"\n\n[project]\nname = \"demo\"\n\n[[vms]]\nname = \"devbox\"\nmanaged_image = { name = \"alpine\" }\n";
+        fs::write(&path, legacy).unwrap();
+
+        let dry_run = config_migrate(ConfigMigrateOptions {
+            config: ConfigLoadOptions::explicit(path.clone()),
+            write: false,
+        })
+        .unwrap();
+        assert!(!dry_run.value.written);
+        assert_eq!(dry_run.value.changes.len(), 2);
+        assert_eq!(dry_run.diagnostics.len(), 1);
+        assert_eq!(fs::read_to_string(&path).unwrap(), legacy);
+
+        let applied = config_migrate(ConfigMigrateOptions {
+            config: ConfigLoadOptions::explicit(path.clone()),
+            write: true,
+        })
+        .unwrap()
+        .value;
+        assert!(applied.written);
+        assert_eq!(fs::read_to_string(&path).unwrap(), applied.migrated);
+        load_project_config_with_profile(&path, None).expect("migrated config loads");
+    }
+
+    #[test]
+    fn show_returns_expanded_replicas_and_resolved_source() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("castra.toml");
+        fs::write(&path, CONFIG).unwrap();
+
+        let outcome = config_show(ConfigShowOptions {
+            config: ConfigLoadOptions::explicit(path.clone()),
+        })
+        .unwrap()
+        .value;
+        let names: Vec<&str> = outcome
+            .project
+            .vms
+            .iter()
+            .map(|vm| vm.name.as_str())
+            .collect();
+        assert_eq!(names, ["web-0", "web-1"]);
+        assert!(!outcome.synthetic);
+        assert!(outcome.source.unwrap().contains("count = 2"));
+    }
+}
@@ -0,0 +1,120 @@
+use crate::config::{ProjectConfig, VmDefinition};
+use crate::error::{Error, Result};
+
+use crate::core::bootstrap;
+use crate::core::exec as exec_core;
+use crate::core::options::{ExecOptions, SshOptions};
+use crate::core::outcome::{ExecOutcome, OperationOutput, OperationResult, SshOutcome};
+use crate::core::reporter::Reporter;
+use crate::core::transport;
+
+use super::{ReporterProxy, resolve_single_project, running_vms};
+
+/// Resolve the system `ssh` invocation for a running VM.
+pub(super) fn ssh(options: SshOptions) -> OperationResult<SshOutcome> {
+    let mut diagnostics = Vec::new();
+    let project = resolve_single_project(
+        options.workspace.as_ref(),
+        &options.config,
+        &mut diagnostics,
+    )?;
+    let (index, _) = running_vms(
+        &project,
+        std::slice::from_ref(&options.vm),
+        "ssh",
+        &mut diagnostics,
+    )?[0];
+    let vm = &project.vms[index];
+    let ssh = bootstrap::ssh_for_vm(vm).map_err(|message| Error::PreflightFailed {
+        message: format!(
+            "Cannot resolve SSH settings for VM `{}`: {message}",
+            vm.name
+        ),
+    })?;
+
+    let args = transport::ssh_command_args(&ssh, &options.command);
+    Ok(OperationOutput::new(SshOutcome {
+        vm: vm.name.clone(),
+        ssh,
+        program: "ssh".to_string(),
+        args,
+    })
+    .with_diagnostics(diagnostics))
+}
+
+/// Run a command on every running VM matching `options.vms`.
+pub(super) fn exec(
+    options: ExecOptions,
+    reporter: Option<&mut dyn Reporter>,
+) -> OperationResult<ExecOutcome> {
+    let mut diagnostics = Vec::new();
+    let mut events = Vec::new();
+    let mut reporter = ReporterProxy::new(reporter, &mut events);
+
+    if options.command.is_empty() {
+        return Err(Error::PreflightFailed {
+            message: "No command given. Pass it after `--`, e.g. `castra exec web -- uptime`."
+                .to_string(),
+        });
+    }
+    let project = resolve_single_project(
+        options.workspace.as_ref(),
+        &options.config,
+        &mut diagnostics,
+    )?;
+    let names = expand_vm_patterns(&project, &options.vms)?;
+    let targets: Vec<&VmDefinition> = running_vms(&project, &names, "exec", &mut diagnostics)?
+        .into_iter()
+        .map(|(index, _)| &project.vms[index])
+        .collect();
+
+    let transport = transport::for_kind(project.bootstrap.transport);
+    let results = exec_core::exec_vms(
+        &targets,
+        &options.command,
+        options.timeout,
+        project.bootstrap.max_parallel,
+        transport.as_ref(),
+        &mut reporter,
+    );
+
+    Ok(OperationOutput::new(ExecOutcome { results })
+        .with_diagnostics(diagnostics)
+        .with_events(events))
+}
+
+/// VM names selected by `patterns`, in configuration order. A pattern that
+/// matches no VM fails preflight.
+fn expand_vm_patterns(project: &ProjectConfig, patterns: &[String]) -> Result<Vec<String>> {
+    if patterns.is_empty() {
+        return Err(Error::PreflightFailed {
+            message:
+                "No VM selected. Name one, or pass --vm with a name or pattern such as `web-*`."
+                    .to_string(),
+        });
+    }
+    if let Some(unmatched) = patterns.iter().find(|pattern| {
+        !project
+            .vms
+            .iter()
+            .any(|vm| exec_core::matches_vm_pattern(pattern, &vm.name))
+    }) {
+        let known: Vec<&str> = project.vms.iter().map(|vm| vm.name.as_str()).collect();
+        return Err(Error::PreflightFailed {
+            message: format!(
+                "No VM matches `{unmatched}`. Configured VMs: {}.",
+                known.join(", ")
+            ),
+        });
+    }
+    Ok(project
+        .vms
+        .iter()
+        .filter(|vm| {
+            patterns
+                .iter()
+                .any(|pattern| exec_core::matches_vm_pattern(pattern, &vm.name))
+        })
+        .map(|vm| vm.name.clone())
+        .collect())
+}
@@ -47,6 +47,8 @@ pub struct ConfigLoadOptions {
     pub allow_synthetic: bool,
     /// Optional override for the discovery root (defaults to the process CWD).
     pub search_root: Option<PathBuf>,
+    /// `[profiles.<name>]` overlay to apply on top of the base configuration.
+    pub profile: Option<String>,
 }
 
 impl ConfigLoadOptions {
@@ -56,6 +58,7 @@ impl ConfigLoadOptions {
             source: ConfigSource::Explicit(path),
             allow_synthetic: false,
             search_root: None,
+            profile: None,
         }
     }
 
@@ -65,8 +68,15 @@ impl ConfigLoadOptions {
             source: ConfigSource::Discover,
             allow_synthetic,
             search_root: None,
+            profile: None,
         }
     }
+
+    /// Select a `[profiles.<name>]` overlay.
+    pub fn with_profile(mut self, profile: Option<String>) -> Self {
+        self.profile = profile;
+        self
+    }
 }
 
 /// Options accepted by the `init` operation.
@@ -108,6 +118,8 @@ pub struct UpOptions {
     pub plan: bool,
     /// Override for the bundled Alpine qcow2 used by default VM definitions.
     pub alpine_qcow_override: Option<PathBuf>,
+    /// Refuse network fetches; missing managed images fail preflight instead.
+    pub offline: bool,
 }
 
 impl Default for UpOptions {
@@ -119,6 +131,7 @@ impl Default for UpOptions {
             bootstrap: BootstrapOverrides::default(),
             plan: false,
             alpine_qcow_override: None,
+            offline: false,
         }
     }
 }
@@ -232,6 +245,161 @@ impl Default for LogsOptions {
     }
 }
 
+/// Options for the `image commit` operation.
+#[derive(Debug, Clone)]
+pub struct ImageCommitOptions {
+    /// Configuration lookup parameters.
+    pub config: ConfigLoadOptions,
+    /// VM whose overlay should be flattened.
+    pub vm: String,
+    /// Name recorded for the resulting image in the shared store.
+    pub name: String,
+}
+
+/// Options for the `bootstrap` operation.
+#[derive(Debug, Clone)]
+pub struct BootstrapOptions {
+    /// Configuration lookup parameters.
+    pub config: ConfigLoadOptions,
+    /// Optional workspace identifier resolved via the registry.
+    pub workspace: Option<String>,
+    /// VMs to bootstrap; empty selects every running VM.
+    pub vms: Vec<String>,
+    /// Pipeline stages to run; empty runs the whole pipeline.
+    pub stages: Vec<String>,
+    /// Rerun selected stages even when their inputs are unchanged.
+    pub force: bool,
+}
+
+impl Default for BootstrapOptions {
+    fn default() -> Self {
+        Self {
+            config: ConfigLoadOptions::discover(true),
+            workspace: None,
+            vms: Vec::new(),
+            stages: Vec::new(),
+            force: false,
+        }
+    }
+}
+
+/// Options for the `bootstrap_history` operation.
+#[derive(Debug, Clone)]
+pub struct BootstrapHistoryOptions {
+    /// Configuration lookup parameters.
+    pub config: ConfigLoadOptions,
+    /// Optional workspace identifier resolved via the registry.
+    pub workspace: Option<String>,
+    /// VMs whose runs to list; empty lists every VM.
+    pub vms: Vec<String>,
+}
+
+impl Default for BootstrapHistoryOptions {
+    fn default() -> Self {
+        Self {
+            config: ConfigLoadOptions::discover(true),
+            workspace: None,
+            vms: Vec::new(),
+        }
+    }
+}
+
+/// Options for the `bootstrap_show` operation.
+#[derive(Debug, Clone)]
+pub struct BootstrapShowOptions {
+    /// Configuration lookup parameters.
+    pub config: ConfigLoadOptions,
+    /// Optional workspace identifier resolved via the registry.
+    pub workspace: Option<String>,
+    /// Run id, VM name (its latest run) or `<vm>~<n>`.
+    pub run: String,
+}
+
+/// Options for the `bootstrap_diff` operation.
+#[derive(Debug, Clone)]
+pub struct BootstrapDiffOptions {
+    /// Configuration lookup parameters.
+    pub config: ConfigLoadOptions,
+    /// Optional workspace identifier resolved via the registry.
+    pub workspace: Option<String>,
+    /// Earlier run, selected like [`BootstrapShowOptions::run`].
+    pub before: String,
+    /// Later run, selected like [`BootstrapShowOptions::run`].
+    pub after: String,
+}
+
+/// Options for the `collect` operation.
+#[derive(Debug, Clone)]
+pub struct CollectOptions {
+    /// Configuration lookup parameters.
+    pub config: ConfigLoadOptions,
+    /// Optional workspace identifier resolved via the registry.
+    pub workspace: Option<String>,
+    /// VMs to collect from; empty selects every running VM.
+    pub vms: Vec<String>,
+}
+
+impl Default for CollectOptions {
+    fn default() -> Self {
+        Self {
+            config: ConfigLoadOptions::discover(true),
+            workspace: None,
+            vms: Vec::new(),
+        }
+    }
+}
+
+/// Options for the `ssh` operation.
+#[derive(Debug, Clone)]
+pub struct SshOptions {
+    /// Configuration lookup parameters.
+    pub config: ConfigLoadOptions,
+    /// Optional workspace identifier resolved via the registry.
+    pub workspace: Option<String>,
+    /// VM to connect to; it must be running.
+    pub vm: String,
+    /// Command to run instead of a login shell, passed to `ssh` as given.
+    pub command: Vec<String>,
+}
+
+/// Options for the `exec` operation.
+#[derive(Debug, Clone)]
+pub struct ExecOptions {
+    /// Configuration lookup parameters.
+    pub config: ConfigLoadOptions,
+    /// Optional workspace identifier resolved via the registry.
+    pub workspace: Option<String>,
+    /// VM names or `*`/`?` patterns; every match must be running.
+    pub vms: Vec<String>,
+    /// Command run on each VM, joined with spaces like `ssh` does.
+    pub command: Vec<String>,
+    /// Stop the command on a VM once this elapses.
+    pub timeout: Option<Duration>,
+}
+
+/// Options for the `config show` operation.
+#[derive(Debug, Clone)]
+pub struct ConfigShowOptions {
+    /// Configuration lookup parameters.
+    pub config: ConfigLoadOptions,
+}
+
+/// Options for the `config validate` operation.
+#[derive(Debug, Clone)]
+pub struct ConfigValidateOptions {
+    /// Configuration lookup parameters.
+    pub config: ConfigLoadOptions,
+}
+
+/// Options for the `config migrate` operation.
+#[derive(Debug, Clone)]
+pub struct ConfigMigrateOptions {
+    /// Configuration lookup parameters.
+    pub config: ConfigLoadOptions,
+    /// Rewrite the file in place instead of only computing the migration.
+    pub write: bool,
+}
+
 /// Options for the `clean` operation.
 #[derive(Debug, Clone)]
 pub struct CleanOptions {
@@ -252,8 +420,12 @@ pub struct CleanOptions {
 /// Scope selector for the clean command.
 #[derive(Debug, Clone)]
 pub enum CleanScope {
-    /// Operate on all state roots under the shared projects directory.
-    Global { projects_root: PathBuf },
+    /// Operate on all state roots under the shared projects directory and
+    /// garbage-collect unreferenced images from the shared image store.
+    Global {
+        projects_root: PathBuf,
+        image_store_root: PathBuf,
+    },
     /// Operate on a single workspace, resolved via config or explicit state root.
     Workspace(ProjectSelector),
 }
@@ -1,14 +1,16 @@
 use std::path::PathBuf;
 use std::time::Duration;
 
-use crate::config::{BaseImageProvenance, BootstrapMode, PortForward};
+use crate::config::{BaseImageProvenance, BootstrapMode, PortForward, ProjectConfig};
 
 use super::diagnostics::Diagnostic;
 use super::events::{
-    BootstrapPlanAction, BootstrapPlanSsh, BootstrapPlanVerify, BootstrapTrigger, CleanupKind,
-    Event, ShutdownOutcome,
+    BootstrapPlanAction, BootstrapPlanSsh, BootstrapPlanStage, BootstrapPlanVerify,
+    BootstrapTrigger, CleanupKind, Event, ShutdownOutcome,
 };
+use super::history::{BootstrapRunDiff, BootstrapRunLog, BootstrapRunSummary};
 use super::options::PortsView;
+use super::transport::SshConfig;
 
 /// Result wrapper returned by high-level operations.
 pub type OperationResult<T> = crate::error::Result<OperationOutput<T>>;
@@ -84,6 +86,8 @@ pub struct BootstrapRunOutcome {
     pub stamp: Option<String>,
     pub log_path: Option<PathBuf>,
     pub ssh: Option<BootstrapPlanSsh>,
+    /// Artifacts copied off the guest after the run.
+    pub collected: Vec<CollectedArtifact>,
 }
 
 #[derive(Debug, Clone)]
@@ -95,6 +99,7 @@ pub struct BootstrapPlanOutcome {
     pub reason: String,
     pub script_path: Option<PathBuf>,
     pub payload_path: Option<PathBuf>,
+    pub payload_commit: Option<String>,
     pub payload_bytes: Option<u64>,
     pub handshake_timeout_secs: Option<u64>,
     pub remote_dir: Option<String>,
@@ -104,6 +109,7 @@ pub struct BootstrapPlanOutcome {
     pub artifact_hash: Option<String>,
     pub metadata_path: Option<PathBuf>,
     pub warnings: Vec<String>,
+    pub stages: Vec<BootstrapPlanStage>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -117,6 +123,8 @@ pub enum BootstrapRunStatus {
 #[derive(Debug)]
 pub struct DownOutcome {
     pub vm_results: Vec<VmShutdownOutcome>,
+    /// Artifacts copied off running VMs before they shut down.
+    pub collected: Vec<CollectedArtifact>,
 }
 
 #[derive(Debug, Clone)]
@@ -126,6 +134,63 @@ pub struct VmShutdownOutcome {
     pub outcome: ShutdownOutcome,
 }
 
+/// Outcome of `collect`.
+#[derive(Debug, Clone)]
+pub struct CollectOutcome {
+    pub artifacts: Vec<CollectedArtifact>,
+}
+
+/// Result of one `collect` rule on one VM.
+#[derive(Debug, Clone)]
+pub struct CollectedArtifact {
+    pub vm: String,
+    pub guest: String,
+    /// Host copy of the guest path.
+    pub path: PathBuf,
+    /// Files collected, as host paths.
+    pub files: Vec<PathBuf>,
+    pub bytes: u64,
+    /// Why the copy failed; nothing was collected when set.
+    pub error: Option<String>,
+}
+
+/// Outcome of `ssh`: the system `ssh` invocation that reaches the VM.
+#[derive(Debug, Clone)]
+pub struct SshOutcome {
+    pub vm: String,
+    /// Connection settings, as bootstrap resolves them.
+    pub ssh: SshConfig,
+    pub program: String,
+    pub args: Vec<String>,
+}
+
+/// Outcome of `exec`.
+#[derive(Debug, Clone)]
+pub struct ExecOutcome {
+    /// One result per VM, in configuration order.
+    pub results: Vec<GuestCommandResult>,
+}
+
+/// How a command ended on one VM.
+#[derive(Debug, Clone)]
+pub struct GuestCommandResult {
+    pub vm: String,
+    /// Exit status; `None` when the command never completed.
+    pub exit_code: Option<i32>,
+    pub duration_ms: u64,
+    /// Why the command could not run or finish (unreachable guest, timeout,
+    /// signal).
+    pub error: Option<String>,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl GuestCommandResult {
+    pub fn success(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+}
+
 /// Outcome of `status`.
 #[derive(Debug, Clone)]
 pub struct StatusOutcome {
@@ -153,6 +218,8 @@ pub struct VmStatusRow {
     pub memory: String,
     pub uptime: Option<Duration>,
     pub forwards: String,
+    /// Time since the last successful bootstrap recorded for the VM.
+    pub bootstrap_age: Option<Duration>,
 }
 
 /// Outcome of `ports`.
@@ -257,6 +324,96 @@ struct LogSourceState {
     offset: u64,
 }
 
+/// Outcome of `bootstrap`.
+#[derive(Debug)]
+pub struct BootstrapOutcome {
+    pub state_root: PathBuf,
+    /// One entry per VM that was bootstrapped, in configuration order.
+    pub runs: Vec<BootstrapRunOutcome>,
+}
+
+/// Outcome of `bootstrap_history`.
+#[derive(Debug, Clone)]
+pub struct BootstrapHistoryOutcome {
+    pub log_root: PathBuf,
+    /// Recorded runs, oldest first.
+    pub runs: Vec<BootstrapRunSummary>,
+}
+
+/// Outcome of `bootstrap_show`.
+#[derive(Debug, Clone)]
+pub struct BootstrapShowOutcome {
+    pub run: BootstrapRunSummary,
+    pub log: BootstrapRunLog,
+}
+
+/// Outcome of `bootstrap_diff`.
+#[derive(Debug, Clone)]
+pub struct BootstrapDiffOutcome {
+    pub before: BootstrapRunSummary,
+    pub after: BootstrapRunSummary,
+    pub diff: BootstrapRunDiff,
+}
+
+/// Outcome of `image commit`.
+#[derive(Debug)]
+pub struct ImageCommitOutcome {
+    pub vm: String,
+    pub name: String,
+    /// Store digest of the flattened image.
+    pub digest: String,
+    /// Location of the image blob in the shared store.
+    pub image_path: PathBuf,
+    /// Golden image tag recorded for automatic reuse, when bootstrap inputs resolved.
+    pub golden_tag: Option<String>,
+    /// Whether the VM was running and had to be stopped first.
+    pub stopped_vm: bool,
+}
+
+/// Outcome of `config show`.
+#[derive(Debug)]
+pub struct ConfigShowOutcome {
+    /// Configuration file that was loaded.
+    pub config_path: PathBuf,
+    /// Profile applied on top of the base configuration.
+    pub profile: Option<String>,
+    /// Whether the project was synthesized because no config exists.
+    pub synthetic: bool,
+    /// Resolved TOML (includes merged, environment expanded, profile applied).
+    /// Absent for synthetic projects.
+    pub source: Option<String>,
+    /// Fully expanded project, including replicas and derived defaults.
+    pub project: ProjectConfig,
+}
+
+/// Outcome of `config validate`.
+#[derive(Debug)]
+pub struct ConfigValidateOutcome {
+    /// Configuration file that was validated.
+    pub config_path: PathBuf,
+    /// Profile applied on top of the base configuration.
+    pub profile: Option<String>,
+    /// Whether no error-level issues were found.
+    pub valid: bool,
+    /// Errors and warnings found while loading the configuration.
+    pub issues: Vec<Diagnostic>,
+}
+
+/// Outcome of `config migrate`.
+#[derive(Debug)]
+pub struct ConfigMigrateOutcome {
+    /// Configuration file that was migrated.
+    pub config_path: PathBuf,
+    /// Source before migration.
+    pub original: String,
+    /// Source after migration; equal to `original` when nothing changed.
+    pub migrated: String,
+    /// Rewrites that were applied.
+    pub changes: Vec<String>,
+    /// Whether the migrated source was written back to `config_path`.
+    pub written: bool,
+}
+
 /// Outcome of `clean`.
 #[derive(Debug)]
 pub struct CleanOutcome {
@@ -264,6 +421,21 @@ pub struct CleanOutcome {
     pub dry_run: bool,
     /// Cleanup results for each processed state root.
     pub state_roots: Vec<StateRootCleanup>,
+    /// Garbage-collection results for the shared image store (global scope only).
+    pub image_store: Option<ImageStoreCleanup>,
+}
+
+/// Summary for shared image store garbage collection.
+#[derive(Debug)]
+pub struct ImageStoreCleanup {
+    /// Root directory of the image store.
+    pub root: PathBuf,
+    /// Total bytes reclaimed (0 during dry runs).
+    pub reclaimed_bytes: u64,
+    /// Number of images kept because at least one workspace references them.
+    pub retained: usize,
+    /// Individual actions taken or skipped.
+    pub actions: Vec<CleanupAction>,
 }
 
 /// Summary for a single state root cleanup.
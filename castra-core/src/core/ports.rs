@@ -273,11 +273,10 @@ fn inspect_udp_port(port: u16) -> io::Result<ForwardRuntimeState> {
 mod tests {
     use super::*;
     use crate::config::{
-        BaseImageSource, BootstrapConfig, BootstrapMode, DEFAULT_BOOTSTRAP_HANDSHAKE_WAIT_SECS,
-        LifecycleConfig, MemorySpec, PortForward, PortProtocol, ProjectConfig, ProjectFeatures,
+        BaseImageSource, BootstrapConfig, BootstrapMode, ImagesConfig, LifecycleConfig,
+        PayloadSource, PortForward, PortProtocol, ProjectConfig, ProjectFeatures,
         VmBootstrapConfig, VmDefinition, Workflows,
     };
-    use std::collections::HashMap;
     use std::net::TcpListener;
     use std::path::PathBuf;
     use tempfile::tempdir;
@@ -286,14 +285,8 @@ mod tests {
         let project_root = state_root.to_path_buf();
         let bootstrap_dir = project_root.join("bootstrap").join("devbox");
         let vm = VmDefinition {
-            name: "devbox".to_string(),
-            role_name: "devbox".to_string(),
-            replica_index: 0,
-            description: None,
             base_image: BaseImageSource::from_explicit(PathBuf::from("base.qcow2")),
             overlay: state_root.join("overlays/devbox.qcow2"),
-            cpus: 2,
-            memory: MemorySpec::new("2048 MiB", Some(2048 * 1024 * 1024)),
             port_forwards: vec![PortForward {
                 host: 2222,
                 guest: 22,
@@ -302,12 +295,10 @@ mod tests {
             bootstrap: VmBootstrapConfig {
                 mode: BootstrapMode::Auto,
                 script: Some(bootstrap_dir.join("run.sh")),
-                payload: Some(bootstrap_dir.join("payload")),
-                handshake_timeout_secs: DEFAULT_BOOTSTRAP_HANDSHAKE_WAIT_SECS,
-                remote_dir: PathBuf::from("/tmp/castra-bootstrap"),
-                env: HashMap::new(),
-                verify: None,
+                payload: Some(PayloadSource::Dir(bootstrap_dir.join("payload"))),
+                ..VmBootstrapConfig::default()
             },
+            ..VmDefinition::for_test("devbox")
         };
 
         ProjectConfig {
@@ -318,9 +309,10 @@ mod tests {
             features: ProjectFeatures::default(),
             vms: vec![vm],
             state_root: state_root.to_path_buf(),
-            workflows: Workflows { init: Vec::new() },
+            workflows: Workflows::default(),
             lifecycle: LifecycleConfig::default(),
             bootstrap: BootstrapConfig::default(),
+            images: ImagesConfig::default(),
             warnings: Vec::new(),
         }
     }
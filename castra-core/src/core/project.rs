@@ -1,13 +1,13 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::ffi::OsStr;
 use std::fmt::Write as _;
 use std::path::{Path, PathBuf};
 
 use crate::config::{
     BaseImageProvenance, BaseImageSource, BootstrapConfig, BootstrapMode,
-    DEFAULT_BOOTSTRAP_HANDSHAKE_WAIT_SECS, LifecycleConfig, MemorySpec, PortConflict,
-    ProjectConfig, ProjectFeatures, VmBootstrapConfig, VmDefinition, Workflows,
-    default_alpine_base_image_path, default_overlay_base_path,
+    DEFAULT_BOOTSTRAP_HANDSHAKE_WAIT_SECS, ImagesConfig, LifecycleConfig, MemorySpec,
+    PayloadSource, PortConflict, ProjectConfig, ProjectFeatures, VmBootstrapConfig, VmDefinition,
+    Workflows, default_alpine_base_image_path, default_overlay_base_path,
 };
 use crate::error::{Error, Result};
 
@@ -41,12 +41,19 @@ pub fn preferred_init_target(options: &InitOptions) -> PathBuf {
 pub fn load_project(options: &ConfigLoadOptions) -> Result<ProjectLoad> {
     match resolve_config_path(&options.source, options.search_root.as_ref()) {
         Ok(path) => {
-            let config = crate::config::load_project_config(&path)?;
-            let diagnostics = config
+            let config =
+                crate::config::load_project_config_with_profile(&path, options.profile.as_deref())?;
+            let mut diagnostics: Vec<Diagnostic> = config
                 .warnings
                 .iter()
                 .map(|warning| Diagnostic::new(Severity::Warning, warning).with_path(path.clone()))
                 .collect();
+            if let Some(profile) = &options.profile {
+                diagnostics.push(Diagnostic::new(
+                    Severity::Info,
+                    format!("Using configuration profile `{profile}`."),
+                ));
+            }
             Ok(ProjectLoad {
                 config,
                 diagnostics,
@@ -128,8 +135,15 @@ memory = "2048 MiB"
   guest = 80
   protocol = "tcp"
 
+# Host commands run once per VM around `castra up` / `castra down`.
+# Placeholders: {{{{ vm }}}}, {{{{ project }}}}, {{{{ state_root }}}}, {{{{ overlay }}}},
+# {{{{ base_image }}}}, {{{{ ssh_port }}}}, {{{{ hook }}}} (also exported as CASTRA_*).
 [workflows]
-init = ["qemu-img create -f qcow2 -b {{{{ base_image }}}} {{{{ overlay }}}}"]
+# pre_up = []
+# post_launch = []
+# post_bootstrap = ["echo {{{{ vm }}}} ready on port {{{{ ssh_port }}}}"]
+# pre_down = []
+# post_down = []
 "#
     )
 }
@@ -186,12 +200,21 @@ fn synthesize_default_project(search_root: PathBuf) -> ProjectConfig {
         bootstrap: VmBootstrapConfig {
             mode: BootstrapMode::Auto,
             script: Some(bootstrap_dir.join("run.sh")),
-            payload: Some(bootstrap_dir.join("payload")),
+            payload: Some(PayloadSource::Dir(bootstrap_dir.join("payload"))),
             handshake_timeout_secs: DEFAULT_BOOTSTRAP_HANDSHAKE_WAIT_SECS,
             remote_dir: PathBuf::from("/tmp/castra-bootstrap"),
             env: HashMap::new(),
             verify: None,
+            bake: false,
+            stages: Vec::new(),
+            managed_keys: None,
+            template_vars: BTreeMap::new(),
+            env_secrets: HashMap::new(),
+            secret_salt: PathBuf::new(),
         },
+        depends_on: Vec::new(),
+        provision: None,
+        collect: Vec::new(),
     };
 
     ProjectConfig {
@@ -202,9 +225,10 @@ fn synthesize_default_project(search_root: PathBuf) -> ProjectConfig {
         features: ProjectFeatures::default(),
         vms: vec![vm],
         state_root,
-        workflows: Workflows { init: Vec::new() },
+        workflows: Workflows::default(),
         lifecycle: LifecycleConfig::default(),
         bootstrap: BootstrapConfig::default(),
+        images: ImagesConfig::default(),
         warnings: vec![],
     }
 }
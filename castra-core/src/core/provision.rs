@@ -0,0 +1,401 @@
+//! `[vms.provision]` rendering: declarative packages, files, users, services
+//! and sysctls turned into an idempotent POSIX shell script.
+//!
+//! The script runs as the `provision` bootstrap stage through the regular
+//! transfer/apply steps. Every action checks the current guest state first,
+//! and a run that changes nothing reports `Castra:noop`.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+
+use crate::config::{
+    ProvisionConfig, ProvisionFile, ProvisionFileContents, ProvisionPackage, VmDefinition,
+};
+
+use super::bootstrap::{render_template_text, shell_quote as quote};
+
+/// Guest file persisting `sysctls` across reboots.
+const SYSCTL_CONF: &str = "/etc/sysctl.d/99-castra.conf";
+
+const PRELUDE: &str = r#"#!/bin/sh
+# Rendered by castra from [vms.provision]; edits are overwritten.
+set -eu
+castra_changed=0
+
+castra_put() {
+  mkdir -p "$(dirname "$1")"
+  printf '%s' "$2" > "$1.castra-new"
+  if [ -f "$1" ] && cmp -s "$1.castra-new" "$1"; then
+    rm -f "$1.castra-new"
+  else
+    mv "$1.castra-new" "$1"
+    castra_changed=1
+  fi
+}
+
+castra_mode() {
+  if [ "$(stat -c %a "$1")" != "$2" ]; then
+    chmod "$2" "$1"
+    castra_changed=1
+  fi
+}
+
+castra_owner() {
+  if [ "$(stat -c "$3" "$1")" != "$2" ]; then
+    chown "$2" "$1"
+    castra_changed=1
+  fi
+}
+"#;
+
+const PACKAGES: &str = r#"
+if command -v apk >/dev/null 2>&1; then
+  castra_pm=apk
+elif command -v apt-get >/dev/null 2>&1; then
+  castra_pm=apt
+elif command -v dnf >/dev/null 2>&1; then
+  castra_pm=dnf
+else
+  echo 'Castra:error:no supported package manager (apk, apt-get or dnf) found'
+  exit 1
+fi
+castra_missing=""
+castra_want() {
+  case "$castra_pm" in
+    apk) apk info -e "$1" >/dev/null 2>&1 ;;
+    apt) dpkg-query -W -f='${Status}' "$1" 2>/dev/null | grep -q 'install ok installed' ;;
+    dnf) rpm -q "$1" >/dev/null 2>&1 ;;
+  esac || castra_missing="$castra_missing $1"
+}
+"#;
+
+const INSTALL: &str = r#"if [ -n "$castra_missing" ]; then
+  case "$castra_pm" in
+    apk) apk add --no-cache $castra_missing ;;
+    apt) apt-get update && DEBIAN_FRONTEND=noninteractive apt-get install -y $castra_missing ;;
+    dnf) dnf install -y $castra_missing ;;
+  esac
+  castra_changed=1
+fi
+"#;
+
+const USERS: &str = r#"
+castra_group() {
+  grep -q "^$1:" /etc/group && return 0
+  if command -v groupadd >/dev/null 2>&1; then groupadd "$1"; else addgroup "$1"; fi
+  castra_changed=1
+}
+
+castra_member() {
+  id -nG "$1" | tr ' ' '\n' | grep -qx "$2" && return 0
+  if command -v usermod >/dev/null 2>&1; then usermod -aG "$2" "$1"; else addgroup "$1" "$2"; fi
+  castra_changed=1
+}
+
+castra_home() {
+  awk -F: -v user="$1" '$1 == user { print $6 }' /etc/passwd
+}
+"#;
+
+const SERVICES: &str = r#"
+castra_service() {
+  if command -v rc-update >/dev/null 2>&1; then
+    if ! rc-update show default | awk -v svc="$1" '$1 == svc { found = 1 } END { exit !found }'; then
+      rc-update add "$1" default
+      castra_changed=1
+    fi
+    if ! rc-service "$1" status >/dev/null 2>&1; then
+      rc-service "$1" start
+      castra_changed=1
+    fi
+  elif command -v systemctl >/dev/null 2>&1; then
+    if ! systemctl is-enabled --quiet "$1" 2>/dev/null; then
+      systemctl enable "$1"
+      castra_changed=1
+    fi
+    if ! systemctl is-active --quiet "$1"; then
+      systemctl start "$1"
+      castra_changed=1
+    fi
+  else
+    echo "Castra:error:no supported service manager (OpenRC or systemd) to enable $1"
+    exit 1
+  fi
+}
+"#;
+
+/// Render `provision` into the script run by the `provision` stage of `vm`.
+///
+/// Host `source` files are read here, so the script (and with it the
+/// stage's artifact hash) changes whenever one of them does. File contents
+/// are rendered with `vm.bootstrap.template_vars`, like `.tmpl` payloads.
+pub fn render_script(vm: &VmDefinition, provision: &ProvisionConfig) -> Result<String, String> {
+    let variables = &vm.bootstrap.template_vars;
+    let mut script = String::from(PRELUDE);
+
+    if !provision.packages.is_empty() {
+        script.push_str(PACKAGES);
+        script.push_str("case \"$castra_pm\" in\n");
+        for manager in ["apk", "apt", "dnf"] {
+            let _ = writeln!(script, "  {manager})");
+            for name in provision
+                .packages
+                .iter()
+                .filter_map(|package| package_name(package, manager))
+            {
+                let _ = writeln!(script, "    castra_want {}", quote(name));
+            }
+            script.push_str("    ;;\n");
+        }
+        script.push_str("esac\n");
+        script.push_str(INSTALL);
+    }
+
+    if !provision.users.is_empty() {
+        script.push_str(USERS);
+        for user in &provision.users {
+            let name = quote(&user.name);
+            let shell = user
+                .shell
+                .as_deref()
+                .map(|shell| format!(" -s {}", quote(shell)))
+                .unwrap_or_default();
+            let _ = write!(
+                script,
+                "\nif ! id -u {name} >/dev/null 2>&1; then\n  if command -v useradd >/dev/null 2>&1; then useradd -m{shell} {name}; else adduser -D{shell} {name}; fi\n  castra_changed=1\nfi\n"
+            );
+            for group in &user.groups {
+                let group = quote(group);
+                let _ = writeln!(script, "castra_group {group}");
+                let _ = writeln!(script, "castra_member {name} {group}");
+            }
+            if !user.authorized_keys.is_empty() {
+                let mut keys = user.authorized_keys.join("\n");
+                keys.push('\n');
+                let _ = writeln!(script, "castra_dir=\"$(castra_home {name})/.ssh\"");
+                let _ = writeln!(
+                    script,
+                    "castra_put \"$castra_dir/authorized_keys\" {}",
+                    quote(&keys)
+                );
+                script.push_str("castra_mode \"$castra_dir\" 700\n");
+                script.push_str("castra_mode \"$castra_dir/authorized_keys\" 600\n");
+                let _ = writeln!(script, "castra_owner \"$castra_dir\" {name} %U");
+                let _ = writeln!(
+                    script,
+                    "castra_owner \"$castra_dir/authorized_keys\" {name} %U"
+                );
+            }
+        }
+    }
+
+    if !provision.files.is_empty() {
+        script.push('\n');
+        for file in &provision.files {
+            render_file(&mut script, file, variables)?;
+        }
+    }
+
+    if !provision.sysctls.is_empty() {
+        let mut conf = String::new();
+        for (key, value) in &provision.sysctls {
+            let _ = writeln!(conf, "{key} = {value}");
+        }
+        let _ = write!(
+            script,
+            "\ncastra_put {} {}\n",
+            quote(SYSCTL_CONF),
+            quote(&conf)
+        );
+        for (key, value) in &provision.sysctls {
+            let (key, value) = (quote(key), quote(value));
+            let _ = writeln!(
+                script,
+                "if [ \"$(sysctl -n {key})\" != {value} ]; then\n  sysctl -w {key}={value} >/dev/null\n  castra_changed=1\nfi"
+            );
+        }
+    }
+
+    if !provision.services.is_empty() {
+        script.push_str(SERVICES);
+        for service in &provision.services {
+            let _ = writeln!(script, "castra_service {}", quote(service));
+        }
+    }
+
+    script.push_str("\nif [ \"$castra_changed\" = 0 ]; then\n  echo 'Castra:noop'\nfi\n");
+    Ok(script)
+}
+
+fn package_name<'a>(package: &'a ProvisionPackage, manager: &str) -> Option<&'a String> {
+    match manager {
+        "apk" => package.apk.as_ref(),
+        "apt" => package.apt.as_ref(),
+        _ => package.dnf.as_ref(),
+    }
+}
+
+fn render_file(
+    script: &mut String,
+    file: &ProvisionFile,
+    variables: &BTreeMap<String, String>,
+) -> Result<(), String> {
+    let template = match &file.contents {
+        ProvisionFileContents::Inline(content) => content.clone(),
+        ProvisionFileContents::Source(source) => {
+            let content = fs::read_to_string(source).map_err(|err| {
+                format!(
+                    "Failed to read provision source {} for `{}`: {err}",
+                    source.display(),
+                    file.path
+                )
+            })?;
+            if content.contains('\0') {
+                return Err(format!(
+                    "Provision source {} for `{}` must be a text file.",
+                    source.display(),
+                    file.path
+                ));
+            }
+            content
+        }
+    };
+    let content = render_template_text(
+        &format!("Provision file `{}`", file.path),
+        &template,
+        variables,
+    )?;
+    let path = quote(&file.path);
+    let _ = writeln!(script, "castra_put {path} {}", quote(&content));
+    if let Some(mode) = file.mode {
+        let _ = writeln!(script, "castra_mode {path} {mode:o}");
+    }
+    if let Some(owner) = &file.owner {
+        let format = if owner.contains(':') { "%U:%G" } else { "%U" };
+        let _ = writeln!(script, "castra_owner {path} {} {format}", quote(owner));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{
+        BootstrapMode, PortForward, PortProtocol, ProvisionUser, VmBootstrapConfig,
+    };
+    use std::path::PathBuf;
+
+    fn vm() -> VmDefinition {
+        VmDefinition {
+            role_name: "web".to_string(),
+            replica_index: 1,
+            port_forwards: vec![PortForward {
+                host: 2223,
+                guest: 22,
+                protocol: PortProtocol::Tcp,
+            }],
+            bootstrap: VmBootstrapConfig {
+                mode: BootstrapMode::Auto,
+                template_vars: BTreeMap::from(
+                    [
+                        ("vm", "web-1"),
+                        ("role", "web"),
+                        ("replica", "1"),
+                        ("ssh_port", "2223"),
+                    ]
+                    .map(|(name, value)| (name.to_string(), value.to_string())),
+                ),
+                ..VmBootstrapConfig::default()
+            },
+            ..VmDefinition::for_test("web-1")
+        }
+    }
+
+    #[test]
+    fn render_script_covers_each_primitive() {
+        let provision = ProvisionConfig {
+            source: PathBuf::from("/project/castra.toml"),
+            packages: vec![
+                ProvisionPackage {
+                    apk: Some("git".to_string()),
+                    apt: Some("git".to_string()),
+                    dnf: Some("git".to_string()),
+                },
+                ProvisionPackage {
+                    apk: Some("openssh".to_string()),
+                    apt: Some("openssh-server".to_string()),
+                    dnf: None,
+                },
+            ],
+            files: vec![ProvisionFile {
+                path: "/etc/motd".to_string(),
+                contents: ProvisionFileContents::Inline(
+                    "{{ vm }} ({{ role }} #{{ replica }}) on {{ ssh_port }}, it's {{vm}}\n"
+                        .to_string(),
+                ),
+                mode: Some(0o644),
+                owner: Some("root:root".to_string()),
+            }],
+            users: vec![ProvisionUser {
+                name: "dev".to_string(),
+                groups: vec!["wheel".to_string()],
+                shell: Some("/bin/ash".to_string()),
+                authorized_keys: vec!["ssh-ed25519 AAAA dev@host".to_string()],
+            }],
+            services: vec!["sshd".to_string()],
+            sysctls: BTreeMap::from([("net.ipv4.ip_forward".to_string(), "1".to_string())]),
+        };
+
+        let script = render_script(&vm(), &provision).expect("render");
+        assert!(script.contains("  apk)\n    castra_want 'git'\n    castra_want 'openssh'\n"));
+        assert!(script.contains("  dnf)\n    castra_want 'git'\n    ;;"));
+        assert!(
+            script.contains("castra_put '/etc/motd' 'web-1 (web #1) on 2223, it'\\''s web-1\n'")
+        );
+        assert!(script.contains("castra_mode '/etc/motd' 644"));
+        assert!(script.contains("castra_owner '/etc/motd' 'root:root' %U:%G"));
+        assert!(script.contains("useradd -m -s '/bin/ash' 'dev'"));
+        assert!(script.contains("castra_member 'dev' 'wheel'"));
+        assert!(script.contains("'ssh-ed25519 AAAA dev@host\n'"));
+        assert!(
+            script
+                .contains("castra_put '/etc/sysctl.d/99-castra.conf' 'net.ipv4.ip_forward = 1\n'")
+        );
+        assert!(script.contains("castra_service 'sshd'"));
+        assert!(script.trim_end().ends_with("echo 'Castra:noop'\nfi"));
+    }
+
+    #[test]
+    fn render_script_reports_missing_source() {
+        let provision = ProvisionConfig {
+            files: vec![ProvisionFile {
+                path: "/etc/app.conf".to_string(),
+                contents: ProvisionFileContents::Source(PathBuf::from("/nonexistent/app.conf")),
+                mode: None,
+                owner: None,
+            }],
+            ..ProvisionConfig::default()
+        };
+        let err = render_script(&vm(), &provision).expect_err("missing source");
+        assert!(err.contains("/nonexistent/app.conf"), "{err}");
+    }
+
+    #[test]
+    fn render_script_rejects_unknown_placeholders() {
+        let provision = ProvisionConfig {
+            files: vec![ProvisionFile {
+                path: "/etc/motd".to_string(),
+                contents: ProvisionFileContents::Inline("{{ vm }} runs {{ other }}\n".to_string()),
+                mode: None,
+                owner: None,
+            }],
+            ..ProvisionConfig::default()
+        };
+        let err = render_script(&vm(), &provision).expect_err("unknown placeholder");
+        assert!(
+            err.contains("Provision file `/etc/motd` references unknown variable `{{ other }}`"),
+            "{err}"
+        );
+    }
+}
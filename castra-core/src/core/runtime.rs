@@ -1,5 +1,6 @@
 use std::cmp;
 use std::collections::HashSet;
+use std::env;
 use std::fs;
 use std::io::{self, ErrorKind, Read, Write};
 #[cfg(unix)]
@@ -18,17 +19,24 @@ use sysinfo::{Disks, System};
 
 use sha2::{Digest, Sha512};
 
-use crate::config::{BaseImageProvenance, PortForward, PortProtocol, ProjectConfig, VmDefinition};
+use crate::config::{
+    BaseImageProvenance, DEFAULT_ALPINE_IMAGE_FILENAME, PortForward, PortProtocol, ProjectConfig,
+    SignatureScheme, VmDefinition,
+};
 use crate::error::{Error, Result};
 use serde_json::{Value, json};
-use ureq::Error as UreqError;
 
 use super::diagnostics::{Diagnostic, Severity};
+use super::download::{DownloadRequest, download_resumable, partial_path};
 use super::events::{
     CooperativeMethod, CooperativeTimeoutReason, EphemeralCleanupReason, Event, ShutdownOutcome,
     ShutdownSignal,
 };
+use super::image_store::{ImageLinkKind, ImageStore};
 use super::options::VmLaunchMode;
+use super::reporter::Reporter;
+use super::signature::{ImageTrust, SignatureVerdict, signature_path};
+use super::ssh_keys;
 
 #[derive(Debug)]
 pub struct RuntimeContext {
@@ -38,6 +46,63 @@ pub struct RuntimeContext {
     pub qemu_img: Option<PathBuf>,
     pub accelerators: Vec<String>,
     pub launch_mode: VmLaunchMode,
+    pub image_store: ImageStore,
+    pub image_sources: ImageSources,
+    pub image_trust: ImageTrust,
+}
+
+/// Where managed base images may be fetched from when they are not cached.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImageSources {
+    /// Refuse network fetches; missing images fail preflight instead.
+    pub offline: bool,
+    /// Mirror base URLs tried in order before the upstream release URL.
+    pub mirrors: Vec<String>,
+}
+
+impl ImageSources {
+    /// Mirrors from `CASTRA_IMAGE_MIRROR` (comma separated) followed by `[images] mirrors`.
+    /// Environment entries must be http(s) URLs, like configured ones.
+    pub fn for_project(project: &ProjectConfig) -> Result<Self> {
+        let mut mirrors: Vec<String> = Vec::new();
+        let from_env = env::var("CASTRA_IMAGE_MIRROR").unwrap_or_default();
+        let env_mirrors: Vec<&str> = from_env
+            .split(',')
+            .map(|entry| entry.trim().trim_end_matches('/'))
+            .filter(|entry| !entry.is_empty())
+            .collect();
+        if let Some(entry) = env_mirrors
+            .iter()
+            .find(|entry| !(entry.starts_with("http://") || entry.starts_with("https://")))
+        {
+            return Err(Error::PreflightFailed {
+                message: format!(
+                    "`CASTRA_IMAGE_MIRROR` entry `{entry}` must be an http:// or https:// base URL. Example: `CASTRA_IMAGE_MIRROR=https://mirror.internal/castra`."
+                ),
+            });
+        }
+        let candidates = env_mirrors
+            .into_iter()
+            .chain(project.images.mirrors.iter().map(String::as_str));
+        for mirror in candidates {
+            if !mirrors.iter().any(|known| known == mirror) {
+                mirrors.push(mirror.to_string());
+            }
+        }
+        Ok(Self {
+            offline: false,
+            mirrors,
+        })
+    }
+
+    /// Candidate URLs for `file_name`: each mirror in order, then `upstream`.
+    pub fn urls_for(&self, file_name: &str, upstream: &str) -> Vec<String> {
+        self.mirrors
+            .iter()
+            .map(|mirror| format!("{mirror}/{file_name}"))
+            .chain(std::iter::once(upstream.to_string()))
+            .collect()
+    }
 }
 
 const DISK_WARN_THRESHOLD: u64 = 2 * 1024 * 1024 * 1024;
@@ -54,6 +119,8 @@ pub struct AssetPreparation {
     pub assets: ResolvedVmAssets,
     pub overlay_created: bool,
     pub overlay_reclaimed_bytes: Option<u64>,
+    /// Shared image store digest backing the base image, when store-managed.
+    pub base_image_digest: Option<String>,
     pub events: Vec<Event>,
 }
 
@@ -108,7 +175,7 @@ pub fn prepare_runtime_context(
         }
     })?;
 
-    let qemu_img = find_executable(&["qemu-img", "qemu-img.exe"]);
+    let qemu_img = locate_qemu_img();
 
     let image_storage_root = state_root.join("images");
     fs::create_dir_all(&image_storage_root).map_err(|err| Error::PreflightFailed {
@@ -127,9 +194,17 @@ pub fn prepare_runtime_context(
         qemu_img,
         accelerators,
         launch_mode,
+        image_store: ImageStore::open_default(),
+        image_sources: ImageSources::for_project(project)?,
+        image_trust: ImageTrust::from_config(&project.images)?,
     })
 }
 
+/// Path to `qemu-img`, when it is installed on `PATH`.
+pub fn locate_qemu_img() -> Option<PathBuf> {
+    find_executable(&["qemu-img", "qemu-img.exe"])
+}
+
 pub fn check_host_capacity(project: &ProjectConfig) -> CheckOutcome {
     let mut outcome = CheckOutcome::default();
 
@@ -266,8 +341,21 @@ pub fn ensure_ports_available(project: &ProjectConfig) -> Result<()> {
 
     Ok(())
 }
-pub fn ensure_vm_assets(vm: &VmDefinition, context: &RuntimeContext) -> Result<AssetPreparation> {
-    let mut events = Vec::new();
+/// Resolve base images and overlays for `vm`.
+///
+/// When `reporter` is provided, events (including live download progress) are
+/// reported as they happen and `AssetPreparation::events` stays empty;
+/// otherwise they are buffered there.
+pub fn ensure_vm_assets(
+    vm: &VmDefinition,
+    context: &RuntimeContext,
+    reporter: Option<&mut dyn Reporter>,
+) -> Result<AssetPreparation> {
+    let mut events = AssetEvents {
+        reporter,
+        buffered: Vec::new(),
+    };
+    let mut base_image_digest = None;
     let base_image_path = vm.base_image.path();
 
     match vm.base_image.provenance() {
@@ -283,7 +371,13 @@ pub fn ensure_vm_assets(vm: &VmDefinition, context: &RuntimeContext) -> Result<A
             }
         }
         BaseImageProvenance::DefaultAlpine => {
-            ensure_default_alpine_image(base_image_path, &mut events)?;
+            base_image_digest = Some(ensure_default_alpine_image(
+                base_image_path,
+                &context.image_store,
+                &context.image_sources,
+                &context.image_trust,
+                &mut events,
+            )?);
         }
     }
 
@@ -293,17 +387,41 @@ pub fn ensure_vm_assets(vm: &VmDefinition, context: &RuntimeContext) -> Result<A
         assets: ResolvedVmAssets { boot: None },
         overlay_created,
         overlay_reclaimed_bytes,
-        events,
+        base_image_digest,
+        events: events.buffered,
     })
 }
 
+/// Routes asset preparation events to a live reporter when one is attached.
+struct AssetEvents<'a> {
+    reporter: Option<&'a mut dyn Reporter>,
+    buffered: Vec<Event>,
+}
+
+impl AssetEvents<'_> {
+    fn push(&mut self, event: Event) {
+        match self.reporter.as_mut() {
+            Some(reporter) => reporter.report(event),
+            // Progress snapshots are only useful live; keep the buffer compact.
+            None if matches!(event, Event::DownloadProgress { .. }) => {}
+            None => self.buffered.push(event),
+        }
+    }
+}
+
 #[derive(Debug)]
 enum AlpineCacheStatus {
     Valid,
     NeedsDownload { reason: String },
 }
 
-fn ensure_default_alpine_image(target: &Path, events: &mut Vec<Event>) -> Result<()> {
+fn ensure_default_alpine_image(
+    target: &Path,
+    store: &ImageStore,
+    sources: &ImageSources,
+    trust: &ImageTrust,
+    events: &mut AssetEvents<'_>,
+) -> Result<String> {
     let parent = target.parent().ok_or_else(|| Error::PreflightFailed {
         message: format!(
             "Unable to determine cache directory for default base image {}.",
@@ -317,60 +435,155 @@ fn ensure_default_alpine_image(target: &Path, events: &mut Vec<Event>) -> Result
         ),
     })?;
 
-    match assess_alpine_cache(target)? {
-        AlpineCacheStatus::Valid => return Ok(()),
-        AlpineCacheStatus::NeedsDownload { reason } => {
-            events.push(Event::Message {
-                severity: Severity::Info,
-                text: format!("{reason} Downloading a fresh copy from {DEFAULT_ALPINE_URL}."),
-            });
+    let stored = match store.resolve_tag(DEFAULT_ALPINE_IMAGE_FILENAME)? {
+        Some(digest) => match assess_alpine_cache(&store.blob_path(&digest), trust)? {
+            AlpineCacheStatus::Valid => Some(digest),
+            AlpineCacheStatus::NeedsDownload { .. } => None,
+        },
+        None => None,
+    };
 
-            if let Err(err) = fs::remove_file(target) {
-                if err.kind() != ErrorKind::NotFound {
+    let digest = match stored {
+        Some(digest) => digest,
+        None => match assess_alpine_cache(target, trust)? {
+            AlpineCacheStatus::Valid => {
+                let digest = store.import(target, Some(DEFAULT_ALPINE_IMAGE_FILENAME), false)?;
+                copy_verification_sidecars(target, &store.blob_path(&digest))?;
+                events.push(Event::Message {
+                    severity: Severity::Info,
+                    text: format!(
+                        "Imported cached Alpine base image at {} into the shared image store (sha256:{}).",
+                        target.display(),
+                        short_digest(&digest)
+                    ),
+                });
+                digest
+            }
+            AlpineCacheStatus::NeedsDownload { reason } => {
+                if sources.offline {
                     return Err(Error::PreflightFailed {
                         message: format!(
-                            "Failed to remove cached Alpine image at {}: {err}",
-                            target.display()
+                            "{reason} Offline mode forbids downloading the default Alpine base image. \
+                             Seed the shared image store at {} by running `castra up` once with network access, \
+                             pass `--qcow <PATH>` with a pre-downloaded copy, or set an explicit `base_image` in castra.toml.",
+                            store.root().display()
                         ),
                     });
                 }
-            }
 
-            let digest_path = cached_digest_path(target);
-            if let Err(err) = fs::remove_file(&digest_path) {
-                if err.kind() != ErrorKind::NotFound {
-                    return Err(Error::PreflightFailed {
-                        message: format!(
-                            "Failed to remove cached digest {}: {err}",
-                            digest_path.display()
-                        ),
-                    });
+                let urls = sources.urls_for(DEFAULT_ALPINE_IMAGE_FILENAME, DEFAULT_ALPINE_URL);
+                events.push(Event::Message {
+                    severity: Severity::Info,
+                    text: format!("{reason} Downloading a fresh copy from {}.", urls[0]),
+                });
+
+                if let Err(err) = fs::remove_file(target) {
+                    if err.kind() != ErrorKind::NotFound {
+                        return Err(Error::PreflightFailed {
+                            message: format!(
+                                "Failed to remove cached Alpine image at {}: {err}",
+                                target.display()
+                            ),
+                        });
+                    }
+                }
+
+                let digest_path = cached_digest_path(target);
+                if let Err(err) = fs::remove_file(&digest_path) {
+                    if err.kind() != ErrorKind::NotFound {
+                        return Err(Error::PreflightFailed {
+                            message: format!(
+                                "Failed to remove cached digest {}: {err}",
+                                digest_path.display()
+                            ),
+                        });
+                    }
                 }
+
+                download_into_store(store, &urls, trust, events)?
             }
-        }
+        },
+    };
+
+    let link = store.link_into(&digest, target)?;
+    if link != ImageLinkKind::Existing {
+        events.push(Event::Message {
+            severity: Severity::Info,
+            text: format!(
+                "Linked {} to shared image sha256:{} ({}).",
+                target.display(),
+                short_digest(&digest),
+                link.describe()
+            ),
+        });
+    }
+
+    Ok(digest)
+}
+
+fn download_into_store(
+    store: &ImageStore,
+    urls: &[String],
+    trust: &ImageTrust,
+    events: &mut AssetEvents<'_>,
+) -> Result<String> {
+    let staging = store.staging_path(DEFAULT_ALPINE_IMAGE_FILENAME);
+    if let Some(parent) = staging.parent() {
+        fs::create_dir_all(parent).map_err(|err| Error::PreflightFailed {
+            message: format!(
+                "Failed to prepare image store staging directory {}: {err}",
+                parent.display()
+            ),
+        })?;
     }
 
     events.push(Event::Message {
         severity: Severity::Info,
-        text: format!("Downloading Alpine base image to {}...", target.display()),
+        text: format!(
+            "Downloading Alpine base image into the shared image store at {}...",
+            store.root().display()
+        ),
     });
 
-    let digest = download_default_alpine_image(target)?;
-    write_cached_digest(&cached_digest_path(target), &digest)?;
+    let (sha512, verdict) = download_default_alpine_image(&staging, urls, trust, events)?;
+    let digest = store.import(&staging, Some(DEFAULT_ALPINE_IMAGE_FILENAME), true)?;
+    let blob = store.blob_path(&digest);
+    write_cached_digest(&cached_digest_path(&blob), &sha512, verdict.as_ref())?;
+    if let Some(verdict) = &verdict {
+        let staged = signature_path(&staging, verdict.scheme);
+        let stored = signature_path(&blob, verdict.scheme);
+        fs::rename(&staged, &stored).map_err(|err| Error::PreflightFailed {
+            message: format!(
+                "Failed to store image signature {}: {err}",
+                stored.display()
+            ),
+        })?;
+    }
 
     events.push(Event::Message {
         severity: Severity::Info,
         text: format!(
             "Cached Alpine base image at {} ({}).",
-            target.display(),
+            blob.display(),
             format_bytes(DEFAULT_ALPINE_SIZE_BYTES)
         ),
     });
 
-    Ok(())
+    Ok(digest)
 }
 
-fn assess_alpine_cache(path: &Path) -> Result<AlpineCacheStatus> {
+fn failure_message(err: Error) -> String {
+    match err {
+        Error::PreflightFailed { message } => message,
+        other => other.to_string(),
+    }
+}
+
+fn short_digest(digest: &str) -> &str {
+    digest.get(..12).unwrap_or(digest)
+}
+
+fn assess_alpine_cache(path: &Path, trust: &ImageTrust) -> Result<AlpineCacheStatus> {
     if !path.is_file() {
         return Ok(AlpineCacheStatus::NeedsDownload {
             reason: format!("Default Alpine base image not found at {}.", path.display()),
@@ -396,21 +609,55 @@ fn assess_alpine_cache(path: &Path) -> Result<AlpineCacheStatus> {
     }
 
     let digest_path = cached_digest_path(path);
-    if let Some(stored) = read_cached_digest(&digest_path)? {
-        if stored.eq_ignore_ascii_case(DEFAULT_ALPINE_SHA512) {
-            return Ok(AlpineCacheStatus::Valid);
+    let cached = read_cached_digest(&digest_path)?
+        .filter(|cached| cached.sha512.eq_ignore_ascii_case(DEFAULT_ALPINE_SHA512));
+
+    if cached.is_none() {
+        let computed = compute_sha512_hex(path)?;
+        if !computed.eq_ignore_ascii_case(DEFAULT_ALPINE_SHA512) {
+            return Ok(AlpineCacheStatus::NeedsDownload {
+                reason: format!(
+                    "Cached Alpine base image at {} failed checksum verification.",
+                    path.display()
+                ),
+            });
         }
+        if trust.is_empty() {
+            write_cached_digest(&digest_path, &computed, None)?;
+        }
+    }
+
+    if trust.is_empty() {
+        return Ok(AlpineCacheStatus::Valid);
     }
 
-    let computed = compute_sha512_hex(path)?;
-    if computed.eq_ignore_ascii_case(DEFAULT_ALPINE_SHA512) {
-        write_cached_digest(&digest_path, &computed)?;
+    let recorded = cached
+        .as_ref()
+        .and_then(|cached| cached.signature.as_deref())
+        .and_then(|fingerprint| trust.trusted_name(fingerprint));
+    if recorded.is_some() {
         return Ok(AlpineCacheStatus::Valid);
     }
 
+    for scheme in trust.schemes() {
+        let signature = signature_path(path, scheme);
+        if !signature.is_file() {
+            continue;
+        }
+        return Ok(match trust.verify(path, &signature, scheme) {
+            Ok(verdict) => {
+                write_cached_digest(&digest_path, DEFAULT_ALPINE_SHA512, Some(&verdict))?;
+                AlpineCacheStatus::Valid
+            }
+            Err(err) => AlpineCacheStatus::NeedsDownload {
+                reason: failure_message(err),
+            },
+        });
+    }
+
     Ok(AlpineCacheStatus::NeedsDownload {
         reason: format!(
-            "Cached Alpine base image at {} failed checksum verification.",
+            "Cached Alpine base image at {} has no signature from a trusted key.",
             path.display()
         ),
     })
@@ -423,22 +670,87 @@ fn cached_digest_path(path: &Path) -> PathBuf {
     }
 }
 
-fn read_cached_digest(path: &Path) -> Result<Option<String>> {
-    match fs::read_to_string(path) {
-        Ok(contents) => Ok(Some(contents.trim().to_string())),
-        Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
-        Err(err) => Err(Error::PreflightFailed {
-            message: format!("Failed to read cached digest {}: {err}", path.display()),
-        }),
-    }
+/// Verification record kept in the `.sha512` sidecar next to an image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct CachedDigest {
+    sha512: String,
+    /// Fingerprint of the trusted key whose signature was verified, if any.
+    signature: Option<String>,
+}
+
+const SIGNATURE_RECORD_PREFIX: &str = "signature:";
+
+fn read_cached_digest(path: &Path) -> Result<Option<CachedDigest>> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(err) => {
+            return Err(Error::PreflightFailed {
+                message: format!("Failed to read cached digest {}: {err}", path.display()),
+            });
+        }
+    };
+    let mut lines = contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty());
+    let Some(sha512) = lines.next() else {
+        return Ok(None);
+    };
+    // Record format: `signature: <scheme> <fingerprint> <key name>`.
+    let signature = lines
+        .filter_map(|line| line.strip_prefix(SIGNATURE_RECORD_PREFIX))
+        .find_map(|record| record.split_whitespace().nth(1))
+        .map(str::to_string);
+    Ok(Some(CachedDigest {
+        sha512: sha512.to_string(),
+        signature,
+    }))
 }
 
-fn write_cached_digest(path: &Path, digest: &str) -> Result<()> {
-    fs::write(path, format!("{digest}\n")).map_err(|err| Error::PreflightFailed {
+fn write_cached_digest(
+    path: &Path,
+    digest: &str,
+    verdict: Option<&SignatureVerdict>,
+) -> Result<()> {
+    let mut contents = format!("{digest}\n");
+    if let Some(verdict) = verdict {
+        contents.push_str(&format!(
+            "{SIGNATURE_RECORD_PREFIX} {} {} {}\n",
+            verdict.scheme.as_str(),
+            verdict.fingerprint,
+            verdict.key_name
+        ));
+    }
+    fs::write(path, contents).map_err(|err| Error::PreflightFailed {
         message: format!("Failed to persist digest file {}: {err}", path.display()),
     })
 }
 
+/// Carry the digest record and any detached signatures from `source` to `target`.
+fn copy_verification_sidecars(source: &Path, target: &Path) -> Result<()> {
+    let mut pairs = vec![(cached_digest_path(source), cached_digest_path(target))];
+    for scheme in [SignatureScheme::Minisign, SignatureScheme::Ed25519] {
+        pairs.push((
+            signature_path(source, scheme),
+            signature_path(target, scheme),
+        ));
+    }
+    for (from, to) in pairs {
+        if !from.is_file() {
+            continue;
+        }
+        fs::copy(&from, &to).map_err(|err| Error::PreflightFailed {
+            message: format!(
+                "Failed to copy {} to {}: {err}",
+                from.display(),
+                to.display()
+            ),
+        })?;
+    }
+    Ok(())
+}
+
 fn compute_sha512_hex(path: &Path) -> Result<String> {
     let mut file = fs::File::open(path).map_err(|err| Error::PreflightFailed {
         message: format!(
@@ -467,97 +779,133 @@ fn compute_sha512_hex(path: &Path) -> Result<String> {
     Ok(hex::encode(hasher.finalize()))
 }
 
-fn download_default_alpine_image(target: &Path) -> Result<String> {
-    let temp_path = target.with_extension("download");
-    if temp_path.exists() {
-        let _ = fs::remove_file(&temp_path);
-    }
-
-    let response = ureq::get(DEFAULT_ALPINE_URL).call().map_err(|err| {
-        let detail = match &err {
-            UreqError::Status(code, _) => format!("server returned HTTP status {code}"),
-            UreqError::Transport(inner) => inner.to_string(),
+/// Try each candidate URL in order, falling back to the next source on failure.
+///
+/// Returns the verified SHA-512 and, when trusted keys are configured, the
+/// signature verdict. Signatures are fetched from the same source as the image.
+fn download_default_alpine_image(
+    target: &Path,
+    urls: &[String],
+    trust: &ImageTrust,
+    events: &mut AssetEvents<'_>,
+) -> Result<(String, Option<SignatureVerdict>)> {
+    let mut failures = Vec::new();
+    for (idx, url) in urls.iter().enumerate() {
+        let message = match fetch_default_alpine_image(url, target, trust, events) {
+            Ok(verified) => return Ok(verified),
+            Err(err) => failure_message(err),
         };
-        Error::PreflightFailed {
-            message: format!(
-                "Failed to download Alpine base image from {DEFAULT_ALPINE_URL}: {detail}. \
-                 Check network connectivity or set an explicit `base_image` in castra.toml."
-            ),
+        if let Some(next) = urls.get(idx + 1) {
+            events.push(Event::Message {
+                severity: Severity::Warning,
+                text: format!("{message} Falling back to {next}."),
+            });
         }
-    })?;
+        failures.push(message);
+    }
 
-    let mut reader = response.into_reader();
-    let mut file = fs::File::create(&temp_path).map_err(|err| Error::PreflightFailed {
+    Err(Error::PreflightFailed {
         message: format!(
-            "Failed to create download target {}: {err}",
-            temp_path.display()
+            "{} Check network connectivity, configure `[images] mirrors` (or CASTRA_IMAGE_MIRROR), or set an explicit `base_image` in castra.toml.",
+            failures.join(" ")
         ),
-    })?;
-    let mut hasher = Sha512::new();
-    let mut total = 0u64;
-    let mut buffer = [0u8; 64 * 1024];
+    })
+}
 
-    loop {
-        let read = reader
-            .read(&mut buffer)
-            .map_err(|err| Error::PreflightFailed {
-                message: format!(
-                    "Failed while downloading {DEFAULT_ALPINE_URL}: {err}. \
-                     Verify network connectivity or supply an explicit `base_image`."
-                ),
-            })?;
-        if read == 0 {
-            break;
-        }
-        file.write_all(&buffer[..read])
-            .map_err(|err| Error::PreflightFailed {
-                message: format!(
-                    "Failed to write cached Alpine image {}: {err}",
-                    temp_path.display()
-                ),
-            })?;
-        hasher.update(&buffer[..read]);
-        total += read as u64;
-    }
+fn fetch_default_alpine_image(
+    url: &str,
+    target: &Path,
+    trust: &ImageTrust,
+    events: &mut AssetEvents<'_>,
+) -> Result<(String, Option<SignatureVerdict>)> {
+    let request = DownloadRequest::new(url, partial_path(target))
+        .with_expected_size(DEFAULT_ALPINE_SIZE_BYTES);
+    let partial = request.partial.clone();
 
-    file.flush().map_err(|err| Error::PreflightFailed {
-        message: format!(
-            "Failed to flush cached Alpine image {}: {err}",
-            temp_path.display()
-        ),
-    })?;
+    let total = download_resumable(&request, &mut |event| events.push(event))?;
 
     if total != DEFAULT_ALPINE_SIZE_BYTES {
-        let _ = fs::remove_file(&temp_path);
+        let _ = fs::remove_file(&partial);
         return Err(Error::PreflightFailed {
             message: format!(
-                "Downloaded Alpine base image has size {} bytes but expected {} bytes. \
-                 Try running `castra clean --workspace` and retry, or set an explicit `base_image`.",
+                "Alpine base image from {url} has size {} bytes but expected {} bytes.",
                 total, DEFAULT_ALPINE_SIZE_BYTES
             ),
         });
     }
 
-    let digest = hex::encode(hasher.finalize());
+    let digest = compute_sha512_hex(&partial)?;
     if !digest.eq_ignore_ascii_case(DEFAULT_ALPINE_SHA512) {
-        let _ = fs::remove_file(&temp_path);
+        let _ = fs::remove_file(&partial);
         return Err(Error::PreflightFailed {
             message: format!(
-                "Downloaded Alpine base image checksum mismatch (got {digest}). \
-                 Remove {} and retry, or configure `base_image` manually.",
-                target.display()
+                "Alpine base image from {url} failed checksum verification (got sha512 {digest})."
             ),
         });
     }
 
-    fs::rename(&temp_path, target).map_err(|err| Error::PreflightFailed {
+    // A failed signature keeps the verified partial so the next source can reuse it.
+    let verdict = if trust.is_empty() {
+        None
+    } else {
+        let verdict = fetch_and_verify_signature(url, &partial, target, trust, events)?;
+        events.push(Event::Message {
+            severity: Severity::Info,
+            text: format!("Verified {} for {url}.", verdict.describe()),
+        });
+        Some(verdict)
+    };
+
+    fs::rename(&partial, target).map_err(|err| Error::PreflightFailed {
         message: format!(
             "Failed to finalize Alpine base image download to {}: {err}",
             target.display()
         ),
     })?;
 
-    Ok(digest)
+    Ok((digest, verdict))
+}
+
+fn fetch_and_verify_signature(
+    url: &str,
+    image: &Path,
+    target: &Path,
+    trust: &ImageTrust,
+    events: &mut AssetEvents<'_>,
+) -> Result<SignatureVerdict> {
+    let mut failures = Vec::new();
+    for scheme in trust.schemes() {
+        let signature_url = format!("{url}.{}", scheme.extension());
+        let signature = signature_path(target, scheme);
+        let request = DownloadRequest::new(&signature_url, partial_path(&signature));
+        let _ = fs::remove_file(&request.partial);
+
+        if let Err(err) = download_resumable(&request, &mut |event| events.push(event)) {
+            failures.push(failure_message(err));
+            continue;
+        }
+        fs::rename(&request.partial, &signature).map_err(|err| Error::PreflightFailed {
+            message: format!(
+                "Failed to store image signature {}: {err}",
+                signature.display()
+            ),
+        })?;
+
+        match trust.verify(image, &signature, scheme) {
+            Ok(verdict) => return Ok(verdict),
+            Err(err) => {
+                let _ = fs::remove_file(&signature);
+                failures.push(failure_message(err));
+            }
+        }
+    }
+
+    Err(Error::PreflightFailed {
+        message: format!(
+            "No signature from a trusted key found for {url} ({}).",
+            failures.join("; ")
+        ),
+    })
 }
 
 pub fn launch_vm(
@@ -696,6 +1044,34 @@ pub fn launch_vm(
         command.arg("-cpu").arg("host");
     }
 
+    if let Some(keys) = &vm.bootstrap.managed_keys {
+        match ssh_keys::ensure_keypair(keys) {
+            Ok(created) => {
+                if created {
+                    events.push(Event::Message {
+                        severity: Severity::Info,
+                        text: format!("Generated workspace SSH key {}.", keys.identity.display()),
+                    });
+                }
+                if let Some(forward) = vm
+                    .port_forwards
+                    .iter()
+                    .find(|pf| pf.protocol == PortProtocol::Tcp && pf.guest == 22)
+                {
+                    let _ = ssh_keys::forget_host_keys(&keys.known_hosts, forward.host);
+                }
+                command.arg("-fw_cfg").arg(ssh_keys::fw_cfg_arg(keys));
+            }
+            Err(message) => events.push(Event::Message {
+                severity: Severity::Warning,
+                text: format!(
+                    "Workspace SSH key not injected into VM `{}`: {message}",
+                    vm.name
+                ),
+            }),
+        }
+    }
+
     let read_pidfile = || -> Result<u32> {
         let pid_contents = fs::read_to_string(&pidfile).map_err(|err| Error::LaunchFailed {
             vm: vm.name.clone(),
@@ -852,6 +1228,27 @@ pub fn shutdown_vm(
     state_root: &Path,
     timeouts: ShutdownTimeouts,
     event_tx: Option<&Sender<Event>>,
+) -> Result<VmShutdownReport> {
+    shutdown_vm_with_overlay(vm, state_root, timeouts, event_tx, false)
+}
+
+/// Shut a VM down but keep its overlay on disk so it can be flattened into a
+/// golden image.
+pub fn shutdown_vm_retaining_overlay(
+    vm: &VmDefinition,
+    state_root: &Path,
+    timeouts: ShutdownTimeouts,
+    event_tx: Option<&Sender<Event>>,
+) -> Result<VmShutdownReport> {
+    shutdown_vm_with_overlay(vm, state_root, timeouts, event_tx, true)
+}
+
+fn shutdown_vm_with_overlay(
+    vm: &VmDefinition,
+    state_root: &Path,
+    timeouts: ShutdownTimeouts,
+    event_tx: Option<&Sender<Event>>,
+    retain_overlay: bool,
 ) -> Result<VmShutdownReport> {
     let mut events = Vec::new();
     let mut diagnostics = Vec::new();
@@ -877,12 +1274,14 @@ pub fn shutdown_vm(
             total_ms,
             changed: false,
         });
-        cleanup_ephemeral_layer(
-            vm,
-            &mut events,
-            &mut diagnostics,
-            EphemeralCleanupReason::Orphan,
-        );
+        if !retain_overlay {
+            cleanup_ephemeral_layer(
+                vm,
+                &mut events,
+                &mut diagnostics,
+                EphemeralCleanupReason::Orphan,
+            );
+        }
         return Ok(VmShutdownReport::new(
             events,
             diagnostics,
@@ -919,12 +1318,14 @@ pub fn shutdown_vm(
             total_ms,
             changed: false,
         });
-        cleanup_ephemeral_layer(
-            vm,
-            &mut events,
-            &mut diagnostics,
-            EphemeralCleanupReason::Orphan,
-        );
+        if !retain_overlay {
+            cleanup_ephemeral_layer(
+                vm,
+                &mut events,
+                &mut diagnostics,
+                EphemeralCleanupReason::Orphan,
+            );
+        }
         return Ok(VmShutdownReport::new(
             events,
             diagnostics,
@@ -1032,12 +1433,14 @@ pub fn shutdown_vm(
                         total_ms,
                         changed: true,
                     });
-                    cleanup_ephemeral_layer(
-                        vm,
-                        &mut events,
-                        &mut diagnostics,
-                        EphemeralCleanupReason::Shutdown,
-                    );
+                    if !retain_overlay {
+                        cleanup_ephemeral_layer(
+                            vm,
+                            &mut events,
+                            &mut diagnostics,
+                            EphemeralCleanupReason::Shutdown,
+                        );
+                    }
                     return Ok(VmShutdownReport::new(
                         events,
                         diagnostics,
@@ -1103,12 +1506,14 @@ pub fn shutdown_vm(
                 total_ms,
                 changed: false,
             });
-            cleanup_ephemeral_layer(
-                vm,
-                &mut events,
-                &mut diagnostics,
-                EphemeralCleanupReason::Orphan,
-            );
+            if !retain_overlay {
+                cleanup_ephemeral_layer(
+                    vm,
+                    &mut events,
+                    &mut diagnostics,
+                    EphemeralCleanupReason::Orphan,
+                );
+            }
             return Ok(VmShutdownReport::new(
                 events,
                 diagnostics,
@@ -1181,12 +1586,14 @@ pub fn shutdown_vm(
         total_ms,
         changed: true,
     });
-    cleanup_ephemeral_layer(
-        vm,
-        &mut events,
-        &mut diagnostics,
-        EphemeralCleanupReason::Shutdown,
-    );
+    if !retain_overlay {
+        cleanup_ephemeral_layer(
+            vm,
+            &mut events,
+            &mut diagnostics,
+            EphemeralCleanupReason::Shutdown,
+        );
+    }
 
     Ok(VmShutdownReport::new(events, diagnostics, true, outcome))
 }
@@ -1560,6 +1967,13 @@ fn ensure_overlay(
         });
     };
 
+    // A staged bootstrap that failed part-way keeps its overlay so the next
+    // run can pick up at the failed stage instead of starting over.
+    if super::bootstrap::resumable_progress(&context.state_root, vm).is_some() {
+        return Ok((false, None));
+    }
+    super::bootstrap::clear_progress(&context.state_root, &vm.name);
+
     let reclaimed = match discard_overlay_file(&vm.overlay) {
         Ok(result) => result,
         Err(err) => {
@@ -1838,21 +2252,19 @@ fn find_executable(candidates: &[&str]) -> Option<PathBuf> {
 mod tests {
     use super::*;
     use crate::config::{
-        BaseImageSource, BootstrapConfig, BootstrapMode, DEFAULT_BOOTSTRAP_HANDSHAKE_WAIT_SECS,
-        LifecycleConfig, MemorySpec, ProjectConfig, ProjectFeatures, VmBootstrapConfig,
-        VmDefinition, Workflows,
+        BaseImageSource, BootstrapConfig, ImagesConfig, LifecycleConfig, ProjectConfig,
+        ProjectFeatures, VmDefinition, Workflows,
     };
     use crate::error::Error;
-    use std::collections::HashMap;
     use std::fs;
     use std::net::TcpListener;
-    use std::path::{Path, PathBuf};
+    use std::path::Path;
     use tempfile::tempdir;
     #[test]
     fn assess_cache_reports_missing_image() {
         let dir = tempdir().unwrap();
         let path = dir.path().join("alpine-minimal.qcow2");
-        let status = assess_alpine_cache(&path).expect("cache check");
+        let status = assess_alpine_cache(&path, &ImageTrust::default()).expect("cache check");
         match status {
             AlpineCacheStatus::NeedsDownload { reason } => {
                 assert!(reason.contains("not found"));
@@ -1866,7 +2278,7 @@ mod tests {
         let dir = tempdir().unwrap();
         let path = dir.path().join("alpine-minimal.qcow2");
         fs::write(&path, b"stub").unwrap();
-        let status = assess_alpine_cache(&path).expect("cache check");
+        let status = assess_alpine_cache(&path, &ImageTrust::default()).expect("cache check");
         match status {
             AlpineCacheStatus::NeedsDownload { reason } => {
                 assert!(reason.contains("expected"), "{reason}");
@@ -1954,13 +2366,109 @@ mod tests {
             features: ProjectFeatures::default(),
             vms: Vec::new(),
             state_root: state_root.to_path_buf(),
-            workflows: Workflows { init: Vec::new() },
+            workflows: Workflows::default(),
             lifecycle: LifecycleConfig::default(),
             bootstrap: BootstrapConfig::default(),
+            images: ImagesConfig::default(),
             warnings: Vec::new(),
         }
     }
 
+    #[test]
+    fn image_sources_try_env_then_config_mirrors_before_upstream() {
+        let dir = tempdir().unwrap();
+        let mut project = empty_project(dir.path());
+        project.images.mirrors = vec![
+            "https://config.example/castra".to_string(),
+            "https://env.example/images".to_string(),
+        ];
+
+        let sources = temp_env::with_var(
+            "CASTRA_IMAGE_MIRROR",
+            Some(" https://env.example/images/ ,"),
+            || ImageSources::for_project(&project),
+        )
+        .expect("valid mirrors");
+        assert_eq!(
+            sources.urls_for("alpine.qcow2", "https://upstream.example/alpine.qcow2"),
+            vec![
+                "https://env.example/images/alpine.qcow2".to_string(),
+                "https://config.example/castra/alpine.qcow2".to_string(),
+                "https://upstream.example/alpine.qcow2".to_string(),
+            ]
+        );
+
+        let err = temp_env::with_var(
+            "CASTRA_IMAGE_MIRROR",
+            Some("https://env.example/images,mirror.internal/castra"),
+            || ImageSources::for_project(&project),
+        )
+        .unwrap_err();
+        assert!(
+            matches!(&err, Error::PreflightFailed { message }
+                if message.contains("`CASTRA_IMAGE_MIRROR` entry `mirror.internal/castra`")),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn cached_digest_round_trips_signature_record() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("image.qcow2.sha512");
+        let verdict = SignatureVerdict {
+            scheme: SignatureScheme::Minisign,
+            key_name: "release key".to_string(),
+            fingerprint: "0011223344556677".to_string(),
+        };
+        write_cached_digest(&path, "abc123", Some(&verdict)).unwrap();
+        assert_eq!(
+            read_cached_digest(&path).unwrap(),
+            Some(CachedDigest {
+                sha512: "abc123".to_string(),
+                signature: Some("0011223344556677".to_string()),
+            })
+        );
+
+        write_cached_digest(&path, "abc123", None).unwrap();
+        assert_eq!(read_cached_digest(&path).unwrap().unwrap().signature, None);
+    }
+
+    #[test]
+    fn offline_mode_refuses_to_download_missing_image() {
+        let dir = tempdir().unwrap();
+        let store = ImageStore::new(dir.path().join("store"));
+        let sources = ImageSources {
+            offline: true,
+            mirrors: Vec::new(),
+        };
+        let mut events = AssetEvents {
+            reporter: None,
+            buffered: Vec::new(),
+        };
+        let target = dir
+            .path()
+            .join("images")
+            .join(DEFAULT_ALPINE_IMAGE_FILENAME);
+
+        let err = ensure_default_alpine_image(
+            &target,
+            &store,
+            &sources,
+            &ImageTrust::default(),
+            &mut events,
+        )
+        .expect_err("offline mode must not download");
+        match err {
+            Error::PreflightFailed { message } => {
+                assert!(message.contains("Offline mode"), "{message}");
+                assert!(message.contains("--qcow"), "{message}");
+            }
+            other => panic!("unexpected error: {other:?}"),
+        }
+        assert!(events.buffered.is_empty());
+        assert!(!store.staging_path(DEFAULT_ALPINE_IMAGE_FILENAME).exists());
+    }
+
     #[test]
     fn prepare_runtime_context_skips_legacy_directories() {
         let temp = tempdir().unwrap();
@@ -1982,24 +2490,9 @@ mod tests {
         let overlay = state_root.join("overlay.qcow2");
         fs::write(&overlay, b"overlay image").unwrap();
         VmDefinition {
-            name: "devbox".to_string(),
-            role_name: "devbox".to_string(),
-            replica_index: 0,
-            description: None,
             base_image: BaseImageSource::from_explicit(base),
             overlay,
-            cpus: 1,
-            memory: MemorySpec::new("512 MiB", Some(512_u64 * 1024 * 1024)),
-            port_forwards: Vec::new(),
-            bootstrap: VmBootstrapConfig {
-                mode: BootstrapMode::Skip,
-                script: None,
-                payload: None,
-                handshake_timeout_secs: DEFAULT_BOOTSTRAP_HANDSHAKE_WAIT_SECS,
-                remote_dir: PathBuf::from("/tmp/castra-bootstrap"),
-                env: HashMap::new(),
-                verify: None,
-            },
+            ..VmDefinition::for_test("devbox")
         }
     }
 
@@ -2062,6 +2555,9 @@ while True:
             qemu_img: None,
             accelerators: Vec::new(),
             launch_mode: VmLaunchMode::Attached,
+            image_store: ImageStore::new(state_root.join("image-store")),
+            image_sources: ImageSources::default(),
+            image_trust: ImageTrust::default(),
         };
 
         let assets = ResolvedVmAssets { boot: None };
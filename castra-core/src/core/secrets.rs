@@ -0,0 +1,245 @@
+//! Secret bootstrap variables.
+//!
+//! Values declared under `env_secrets` are read from the host only when a
+//! pipeline resolves, reach the guest as `export` statements on the remote
+//! command's standard input, and never touch disk or appear in a command
+//! line that `ps` could show. Artifact hashes and run logs see a digest
+//! salted with a per-workspace random value instead, so a rotated secret
+//! still invalidates stamps without the stored hash leaking a guessable
+//! value. Everything castra reports passes through [`SecretEnv::redact`].
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+use ring::rand::{SecureRandom, SystemRandom};
+use sha2::{Digest, Sha256};
+
+use crate::config::SecretSource;
+use crate::core::transport::ExecOutput;
+
+const SALT_BYTES: usize = 32;
+/// Shorter fragments of a multi-line secret are not worth masking.
+const MIN_REDACTED_LINE: usize = 4;
+
+/// Resolved secret variables for one pipeline stage.
+#[derive(Default)]
+pub struct SecretEnv {
+    values: BTreeMap<String, String>,
+    digests: BTreeMap<String, String>,
+}
+
+impl SecretEnv {
+    /// Read every source, salting digests with the workspace salt at
+    /// `salt_path` (created on first use).
+    pub fn resolve(
+        sources: &HashMap<String, SecretSource>,
+        salt_path: &Path,
+    ) -> Result<Self, String> {
+        if sources.is_empty() {
+            return Ok(Self::default());
+        }
+        let salt = load_or_create_salt(salt_path)?;
+        let mut secrets = Self::default();
+        for (key, source) in sources {
+            let value = source
+                .resolve()
+                .map_err(|err| format!("Secret `{key}` ({}): {err}", source.describe()))?;
+            secrets
+                .digests
+                .insert(key.clone(), salted_digest(&salt, key, &value));
+            secrets.values.insert(key.clone(), value);
+        }
+        Ok(secrets)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &String> {
+        self.values.keys()
+    }
+
+    /// Name/value pairs in name order, for exporting to the guest.
+    pub fn values(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.values
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+
+    /// Salted digest per name, safe to hash and persist.
+    pub fn digests(&self) -> &BTreeMap<String, String> {
+        &self.digests
+    }
+
+    /// Replace every secret value in `text` with `<redacted:NAME>`.
+    pub fn redact(&self, text: &str) -> String {
+        let mut patterns: Vec<(String, &str)> = Vec::new();
+        for (key, value) in &self.values {
+            if value.is_empty() {
+                continue;
+            }
+            patterns.push((value.clone(), key));
+            // How the value appears once shell-quoted on a command line.
+            let quoted = value.replace('\'', "'\\''");
+            if quoted != *value {
+                patterns.push((quoted, key));
+            }
+            if value.contains('\n') {
+                for line in value.lines().map(str::trim) {
+                    if line.len() >= MIN_REDACTED_LINE {
+                        patterns.push((line.to_string(), key));
+                    }
+                }
+            }
+        }
+        patterns.sort_by_key(|(pattern, _)| Reverse(pattern.len()));
+
+        let mut redacted = text.to_string();
+        for (pattern, key) in patterns {
+            if redacted.contains(&pattern) {
+                redacted = redacted.replace(&pattern, &format!("<redacted:{key}>"));
+            }
+        }
+        redacted
+    }
+
+    /// Redact the command line and captured streams of `output`.
+    pub fn redact_output(&self, output: ExecOutput) -> ExecOutput {
+        if self.is_empty() {
+            return output;
+        }
+        ExecOutput {
+            command: self.redact(&output.command),
+            stdout: self.redact(&output.stdout),
+            stderr: self.redact(&output.stderr),
+        }
+    }
+}
+
+impl fmt::Debug for SecretEnv {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.values.keys()).finish()
+    }
+}
+
+fn salted_digest(salt: &[u8], key: &str, value: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(salt);
+    hasher.update(key.as_bytes());
+    hasher.update(b"\0");
+    hasher.update(value.as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+fn load_or_create_salt(path: &Path) -> Result<Vec<u8>, String> {
+    let read = |path: &Path| {
+        fs::read_to_string(path)
+            .map_err(|err| format!("Failed to read secret salt {}: {err}", path.display()))
+            .and_then(|contents| {
+                hex::decode(contents.trim())
+                    .map_err(|err| format!("Secret salt {} is corrupt: {err}", path.display()))
+            })
+    };
+    if path.is_file() {
+        return read(path);
+    }
+
+    let mut salt = [0u8; SALT_BYTES];
+    SystemRandom::new()
+        .fill(&mut salt)
+        .map_err(|_| "Failed to generate a secret salt.".to_string())?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|err| format!("Failed to create {}: {err}", parent.display()))?;
+    }
+    let written = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)
+        .and_then(|mut file| writeln!(file, "{}", hex::encode(salt)));
+    match written {
+        Ok(()) => Ok(salt.to_vec()),
+        // Another run created it first; use theirs.
+        Err(err) if err.kind() == io::ErrorKind::AlreadyExists => read(path),
+        Err(err) => Err(format!(
+            "Failed to write secret salt {}: {err}",
+            path.display()
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn resolves_sources_with_stable_salted_digests() {
+        let dir = tempdir().unwrap();
+        let token = dir.path().join("token");
+        fs::write(&token, "s3cr3t-value\n").unwrap();
+        let salt = dir.path().join("state/secret.salt");
+        let sources = HashMap::from([("TOKEN".to_string(), SecretSource::File(token.clone()))]);
+
+        let first = SecretEnv::resolve(&sources, &salt).unwrap();
+        assert_eq!(
+            first.values().collect::<Vec<_>>(),
+            [("TOKEN", "s3cr3t-value")]
+        );
+        let digest = first.digests()["TOKEN"].clone();
+        assert!(!digest.contains("s3cr3t"));
+        assert_eq!(
+            SecretEnv::resolve(&sources, &salt).unwrap().digests()["TOKEN"],
+            digest
+        );
+
+        fs::write(&token, "rotated").unwrap();
+        assert_ne!(
+            SecretEnv::resolve(&sources, &salt).unwrap().digests()["TOKEN"],
+            digest
+        );
+
+        let other_salt = dir.path().join("other/secret.salt");
+        fs::write(&token, "s3cr3t-value\n").unwrap();
+        assert_ne!(
+            SecretEnv::resolve(&sources, &other_salt).unwrap().digests()["TOKEN"],
+            digest
+        );
+        assert_eq!(format!("{first:?}"), "{\"TOKEN\"}");
+
+        let missing = HashMap::from([(
+            "REGISTRY".to_string(),
+            SecretSource::Env("CASTRA_TEST_UNSET_SECRET".to_string()),
+        )]);
+        let err = SecretEnv::resolve(&missing, &salt).unwrap_err();
+        assert!(err.contains("REGISTRY"), "{err}");
+        assert!(err.contains("CASTRA_TEST_UNSET_SECRET"), "{err}");
+    }
+
+    #[test]
+    fn redacts_plain_quoted_and_multiline_values() {
+        let secrets = SecretEnv {
+            values: BTreeMap::from([
+                ("PASS".to_string(), "it's-secret".to_string()),
+                (
+                    "KEY".to_string(),
+                    "-----BEGIN-----\nAAAAbody\n-----END-----".to_string(),
+                ),
+            ]),
+            digests: BTreeMap::new(),
+        };
+        assert_eq!(
+            secrets.redact("export PASS='it'\\''s-secret'; echo it's-secret"),
+            "export PASS='<redacted:PASS>'; echo <redacted:PASS>"
+        );
+        assert_eq!(secrets.redact("line: AAAAbody"), "line: <redacted:KEY>");
+        assert_eq!(secrets.redact("nothing here"), "nothing here");
+    }
+}
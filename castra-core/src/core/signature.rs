@@ -0,0 +1,402 @@
+//! Detached signature verification for managed images.
+//!
+//! Trusted keys come from `[[images.trusted_keys]]`. Minisign signatures
+//! (`<image>.minisig`) are verified in streaming mode; raw ed25519 signatures
+//! (`<image>.sig`, binary or base64/hex text) cover the 64-byte SHA-512
+//! digest of the image, so neither scheme reads the image into memory.
+
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use minisign_verify::{PublicKey as MinisignPublicKey, Signature as MinisignSignature};
+use ring::signature::{ED25519, UnparsedPublicKey};
+use sha2::{Digest, Sha256, Sha512};
+
+use crate::config::{ImagesConfig, SignatureScheme, TrustedKeyConfig};
+use crate::error::{Error, Result};
+
+/// Keys allowed to sign managed images.
+#[derive(Debug, Clone, Default)]
+pub struct ImageTrust {
+    keys: Vec<TrustedKey>,
+}
+
+#[derive(Debug, Clone)]
+struct TrustedKey {
+    name: String,
+    fingerprint: String,
+    material: KeyMaterial,
+}
+
+#[derive(Debug, Clone)]
+enum KeyMaterial {
+    Minisign(Box<MinisignPublicKey>),
+    Ed25519([u8; 32]),
+}
+
+impl KeyMaterial {
+    fn scheme(&self) -> SignatureScheme {
+        match self {
+            KeyMaterial::Minisign(_) => SignatureScheme::Minisign,
+            KeyMaterial::Ed25519(_) => SignatureScheme::Ed25519,
+        }
+    }
+}
+
+/// Successful verification of an image against a trusted key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignatureVerdict {
+    pub scheme: SignatureScheme,
+    pub key_name: String,
+    /// Stable identifier for the key, recorded in digest sidecars.
+    pub fingerprint: String,
+}
+
+impl SignatureVerdict {
+    pub fn describe(&self) -> String {
+        format!(
+            "{} signature from trusted key `{}` ({})",
+            self.scheme.as_str(),
+            self.key_name,
+            self.fingerprint
+        )
+    }
+}
+
+impl ImageTrust {
+    /// Decode the keys declared in `[images]`, rejecting malformed entries.
+    pub fn from_config(config: &ImagesConfig) -> Result<Self> {
+        let keys = config
+            .trusted_keys
+            .iter()
+            .map(TrustedKey::parse)
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { keys })
+    }
+
+    /// Whether signature verification is required at all.
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Signature schemes covered by at least one trusted key, minisign first.
+    pub fn schemes(&self) -> Vec<SignatureScheme> {
+        [SignatureScheme::Minisign, SignatureScheme::Ed25519]
+            .into_iter()
+            .filter(|scheme| self.keys.iter().any(|key| key.material.scheme() == *scheme))
+            .collect()
+    }
+
+    /// Name of the trusted key with `fingerprint`, if it is still trusted.
+    pub fn trusted_name(&self, fingerprint: &str) -> Option<&str> {
+        self.keys
+            .iter()
+            .find(|key| key.fingerprint == fingerprint)
+            .map(|key| key.name.as_str())
+    }
+
+    /// Verify `image` against the detached `signature` using any trusted key of `scheme`.
+    pub fn verify(
+        &self,
+        image: &Path,
+        signature: &Path,
+        scheme: SignatureScheme,
+    ) -> Result<SignatureVerdict> {
+        let candidates: Vec<&TrustedKey> = self
+            .keys
+            .iter()
+            .filter(|key| key.material.scheme() == scheme)
+            .collect();
+        if candidates.is_empty() {
+            return Err(Error::PreflightFailed {
+                message: format!(
+                    "No trusted {} keys are configured to verify {}.",
+                    scheme.as_str(),
+                    signature.display()
+                ),
+            });
+        }
+
+        let mut failures = Vec::new();
+        match scheme {
+            SignatureScheme::Minisign => {
+                let encoded = read_signature_text(signature)?;
+                let decoded =
+                    MinisignSignature::decode(&encoded).map_err(|err| Error::PreflightFailed {
+                        message: format!(
+                            "Minisign signature {} is malformed: {err}",
+                            signature.display()
+                        ),
+                    })?;
+                for key in candidates {
+                    let KeyMaterial::Minisign(public) = &key.material else {
+                        continue;
+                    };
+                    match verify_minisign(public, &decoded, image) {
+                        Ok(()) => return Ok(key.verdict()),
+                        Err(reason) => failures.push(format!("`{}`: {reason}", key.name)),
+                    }
+                }
+            }
+            SignatureScheme::Ed25519 => {
+                let raw = read_ed25519_signature(signature)?;
+                let digest = sha512_digest(image).map_err(|err| Error::PreflightFailed {
+                    message: format!(
+                        "Failed to read {} for signature verification: {err}",
+                        image.display()
+                    ),
+                })?;
+                for key in candidates {
+                    let KeyMaterial::Ed25519(public) = &key.material else {
+                        continue;
+                    };
+                    match UnparsedPublicKey::new(&ED25519, public).verify(&digest, &raw) {
+                        Ok(()) => return Ok(key.verdict()),
+                        Err(_) => failures.push(format!("`{}`: signature mismatch", key.name)),
+                    }
+                }
+            }
+        }
+
+        Err(Error::PreflightFailed {
+            message: format!(
+                "Signature {} for {} does not match any trusted {} key ({}).",
+                signature.display(),
+                image.display(),
+                scheme.as_str(),
+                failures.join("; ")
+            ),
+        })
+    }
+}
+
+impl TrustedKey {
+    fn parse(config: &TrustedKeyConfig) -> Result<Self> {
+        let invalid = |reason: String| Error::PreflightFailed {
+            message: format!(
+                "`[[images.trusted_keys]]` entry `{}` has an invalid {} key: {reason}",
+                config.name,
+                config.scheme.as_str()
+            ),
+        };
+
+        let (material, canonical) = match config.scheme {
+            SignatureScheme::Minisign => {
+                // Accept either the bare key or the full contents of a `minisign.pub` file.
+                let line = config
+                    .key
+                    .lines()
+                    .map(str::trim)
+                    .rfind(|line| !line.is_empty())
+                    .unwrap_or_default();
+                let key =
+                    MinisignPublicKey::from_base64(line).map_err(|err| invalid(err.to_string()))?;
+                (KeyMaterial::Minisign(Box::new(key)), line.to_string())
+            }
+            SignatureScheme::Ed25519 => {
+                let bytes = decode_key_bytes(&config.key).ok_or_else(|| {
+                    invalid("expected 32 bytes encoded as base64 or hex".to_string())
+                })?;
+                let key: [u8; 32] = bytes.try_into().map_err(|bytes: Vec<u8>| {
+                    invalid(format!("expected 32 bytes, found {}", bytes.len()))
+                })?;
+                (KeyMaterial::Ed25519(key), hex::encode(key))
+            }
+        };
+
+        let mut hasher = Sha256::new();
+        hasher.update(config.scheme.as_str().as_bytes());
+        hasher.update(b":");
+        hasher.update(canonical.as_bytes());
+        let fingerprint = hex::encode(&hasher.finalize()[..8]);
+
+        Ok(Self {
+            name: config.name.clone(),
+            fingerprint,
+            material,
+        })
+    }
+
+    fn verdict(&self) -> SignatureVerdict {
+        SignatureVerdict {
+            scheme: self.material.scheme(),
+            key_name: self.name.clone(),
+            fingerprint: self.fingerprint.clone(),
+        }
+    }
+}
+
+/// Location of the detached signature for `image` in the given scheme.
+pub fn signature_path(image: &Path, scheme: SignatureScheme) -> PathBuf {
+    match image.file_name().and_then(|name| name.to_str()) {
+        Some(name) => image.with_file_name(format!("{name}.{}", scheme.extension())),
+        None => image.with_extension(scheme.extension()),
+    }
+}
+
+fn verify_minisign(
+    public: &MinisignPublicKey,
+    signature: &MinisignSignature,
+    image: &Path,
+) -> std::result::Result<(), String> {
+    let mut verifier = public
+        .verify_stream(signature)
+        .map_err(|err| err.to_string())?;
+    let mut file = fs::File::open(image).map_err(|err| err.to_string())?;
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer).map_err(|err| err.to_string())?;
+        if read == 0 {
+            break;
+        }
+        verifier.update(&buffer[..read]);
+    }
+    verifier.finalize().map_err(|err| err.to_string())
+}
+
+/// SHA-512 digest of `image`, read in chunks.
+fn sha512_digest(image: &Path) -> std::io::Result<[u8; 64]> {
+    let mut file = fs::File::open(image)?;
+    let mut hasher = Sha512::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher.finalize().into())
+}
+
+fn read_signature_text(path: &Path) -> Result<String> {
+    fs::read_to_string(path).map_err(|err| Error::PreflightFailed {
+        message: format!("Failed to read signature {}: {err}", path.display()),
+    })
+}
+
+fn read_ed25519_signature(path: &Path) -> Result<Vec<u8>> {
+    let raw = fs::read(path).map_err(|err| Error::PreflightFailed {
+        message: format!("Failed to read signature {}: {err}", path.display()),
+    })?;
+    if raw.len() == 64 {
+        return Ok(raw);
+    }
+    std::str::from_utf8(&raw)
+        .ok()
+        .and_then(decode_key_bytes)
+        .filter(|bytes| bytes.len() == 64)
+        .ok_or_else(|| Error::PreflightFailed {
+            message: format!(
+                "Signature {} is not a 64-byte ed25519 signature (raw, base64 or hex).",
+                path.display()
+            ),
+        })
+}
+
+fn decode_key_bytes(encoded: &str) -> Option<Vec<u8>> {
+    let trimmed = encoded.trim();
+    hex::decode(trimmed)
+        .ok()
+        .or_else(|| BASE64.decode(trimmed).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::rand::SystemRandom;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+    use tempfile::tempdir;
+
+    fn ed25519_trust(name: &str, public: &[u8]) -> ImageTrust {
+        ImageTrust::from_config(&ImagesConfig {
+            mirrors: Vec::new(),
+            trusted_keys: vec![TrustedKeyConfig {
+                name: name.to_string(),
+                scheme: SignatureScheme::Ed25519,
+                key: BASE64.encode(public),
+            }],
+        })
+        .expect("trust")
+    }
+
+    #[test]
+    fn ed25519_signatures_verify_only_with_trusted_keys() {
+        let dir = tempdir().unwrap();
+        let image = dir.path().join("image.qcow2");
+        fs::write(&image, b"image-bytes").unwrap();
+
+        let rng = SystemRandom::new();
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&rng).unwrap();
+        let pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        let signature = signature_path(&image, SignatureScheme::Ed25519);
+        let digest = Sha512::digest(b"image-bytes");
+        fs::write(&signature, BASE64.encode(pair.sign(&digest).as_ref())).unwrap();
+
+        let trust = ed25519_trust("release", pair.public_key().as_ref());
+        let verdict = trust
+            .verify(&image, &signature, SignatureScheme::Ed25519)
+            .expect("signature verifies");
+        assert_eq!(verdict.key_name, "release");
+        assert_eq!(trust.trusted_name(&verdict.fingerprint), Some("release"));
+
+        fs::write(&image, b"tampered").unwrap();
+        assert!(
+            trust
+                .verify(&image, &signature, SignatureScheme::Ed25519)
+                .is_err()
+        );
+
+        let other =
+            Ed25519KeyPair::from_pkcs8(Ed25519KeyPair::generate_pkcs8(&rng).unwrap().as_ref())
+                .unwrap();
+        let untrusted = ed25519_trust("other", other.public_key().as_ref());
+        fs::write(&image, b"image-bytes").unwrap();
+        assert!(
+            untrusted
+                .verify(&image, &signature, SignatureScheme::Ed25519)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn minisign_signatures_verify_in_streaming_mode() {
+        // Fixture from the minisign-verify test suite.
+        let public = "RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3";
+        let signature_text = "untrusted comment: signature from minisign secret key
+RUQf6LRCGA9i559r3g7V1qNyJDApGip8MfqcadIgT9CuhV3EMhHoN1mGTkUidF/z7SrlQgXdy8ofjb7bNJJylDOocrCo8KLzZwo=
+trusted comment: timestamp:1556193335\tfile:test
+y/rUw2y8/hOUYjZU71eHp/Wo1KZ40fGy2VJEDl34XMJM+TX48Ss/17u3IvIfbVR1FkZZSNCisQbuQY+bHwhEBg==
+";
+        let dir = tempdir().unwrap();
+        let image = dir.path().join("test");
+        fs::write(&image, b"test").unwrap();
+        let signature = signature_path(&image, SignatureScheme::Minisign);
+        fs::write(&signature, signature_text).unwrap();
+
+        let trust = ImageTrust::from_config(&ImagesConfig {
+            mirrors: Vec::new(),
+            trusted_keys: vec![TrustedKeyConfig {
+                name: "upstream".to_string(),
+                scheme: SignatureScheme::Minisign,
+                key: format!("untrusted comment: minisign public key\n{public}\n"),
+            }],
+        })
+        .expect("trust");
+        assert_eq!(trust.schemes(), vec![SignatureScheme::Minisign]);
+        let verdict = trust
+            .verify(&image, &signature, SignatureScheme::Minisign)
+            .expect("minisign verifies");
+        assert_eq!(verdict.scheme, SignatureScheme::Minisign);
+
+        fs::write(&image, b"nope").unwrap();
+        assert!(
+            trust
+                .verify(&image, &signature, SignatureScheme::Minisign)
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,144 @@
+//! Workspace SSH keypairs and the guest host keys recorded for them.
+//!
+//! Castra generates one ed25519 keypair per workspace and hands the public
+//! half to every guest at boot through QEMU's fw_cfg, under the name systemd
+//! imports as the `ssh.authorized_keys.root` credential. Guests without
+//! systemd can read the same entry from
+//! `/sys/firmware/qemu_fw_cfg/by_name/<AUTHORIZED_KEYS_FW_CFG>/raw`.
+//! Host keys are learned on the first connection after each launch and
+//! enforced from then on.
+
+use std::fs;
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::process::Command;
+
+use crate::config::ManagedSshKeys;
+
+/// fw_cfg entry carrying the workspace public key.
+pub const AUTHORIZED_KEYS_FW_CFG: &str = "opt/io.systemd.credentials/ssh.authorized_keys.root";
+
+/// Create the workspace keypair unless it already exists, returning whether
+/// a new one was generated.
+pub fn ensure_keypair(keys: &ManagedSshKeys) -> Result<bool, String> {
+    let public_key = keys.public_key();
+    if keys.identity.is_file() && public_key.is_file() {
+        return Ok(false);
+    }
+    let dir = keys
+        .identity
+        .parent()
+        .ok_or_else(|| format!("{} has no parent directory", keys.identity.display()))?;
+    fs::create_dir_all(dir)
+        .and_then(|()| fs::set_permissions(dir, fs::Permissions::from_mode(0o700)))
+        .map_err(|err| format!("Failed to create {}: {err}", dir.display()))?;
+    // ssh-keygen refuses to overwrite, so clear a half-written pair first.
+    let _ = fs::remove_file(&keys.identity);
+    let _ = fs::remove_file(&public_key);
+
+    let workspace = dir
+        .parent()
+        .and_then(Path::file_name)
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "workspace".to_string());
+    let output = Command::new("ssh-keygen")
+        .args(["-q", "-t", "ed25519", "-N", "", "-C"])
+        .arg(format!("castra@{workspace}"))
+        .arg("-f")
+        .arg(&keys.identity)
+        .output()
+        .map_err(|err| match err.kind() {
+            io::ErrorKind::NotFound => "`ssh-keygen` not found in PATH".to_string(),
+            _ => format!("Failed to run `ssh-keygen`: {err}"),
+        })?;
+    if !output.status.success() {
+        return Err(format!(
+            "`ssh-keygen` exited with code {:?}: {}",
+            output.status.code(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(true)
+}
+
+/// QEMU `-fw_cfg` value that exposes the workspace public key to the guest.
+pub fn fw_cfg_arg(keys: &ManagedSshKeys) -> String {
+    format!(
+        "name={AUTHORIZED_KEYS_FW_CFG},file={}",
+        keys.public_key().display()
+    )
+}
+
+/// OpenSSH options that pin guest host keys in the workspace known_hosts.
+pub fn ssh_options(keys: &ManagedSshKeys) -> Vec<String> {
+    vec![
+        "StrictHostKeyChecking=accept-new".to_string(),
+        format!("UserKnownHostsFile={}", keys.known_hosts.display()),
+        "HashKnownHosts=no".to_string(),
+    ]
+}
+
+/// Drop every host key recorded for `port`. Called before a VM boots, since
+/// a fresh overlay may come up with newly generated host keys.
+pub fn forget_host_keys(known_hosts: &Path, port: u16) -> io::Result<()> {
+    let contents = match fs::read_to_string(known_hosts) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+    let suffix = format!("]:{port}");
+    let kept: String = contents
+        .lines()
+        .filter(|line| {
+            let hosts = line.split_whitespace().next().unwrap_or_default();
+            !hosts.split(',').any(|host| host.ends_with(&suffix))
+        })
+        .map(|line| format!("{line}\n"))
+        .collect();
+    if kept != contents {
+        fs::write(known_hosts, kept)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn forget_host_keys_drops_only_the_matching_port() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("known_hosts");
+        fs::write(
+            &path,
+            "[127.0.0.1]:2222 ssh-ed25519 AAAAfirst\n\
+             [127.0.0.1]:2223 ssh-ed25519 AAAAsecond\n\
+             [localhost]:22220,[127.0.0.1]:2222 ssh-rsa AAAAthird\n",
+        )
+        .unwrap();
+
+        forget_host_keys(&path, 2222).unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "[127.0.0.1]:2223 ssh-ed25519 AAAAsecond\n"
+        );
+        forget_host_keys(&dir.path().join("missing"), 2222).unwrap();
+    }
+
+    #[test]
+    fn ensure_keypair_generates_once() {
+        if Command::new("ssh-keygen").arg("-?").output().is_err() {
+            return;
+        }
+        let dir = tempdir().unwrap();
+        let keys = ManagedSshKeys::for_state_root(dir.path());
+        assert!(ensure_keypair(&keys).unwrap());
+        let public_key = fs::read_to_string(keys.public_key()).unwrap();
+        assert!(public_key.starts_with("ssh-ed25519 "), "{public_key}");
+        assert!(!ensure_keypair(&keys).unwrap());
+        assert_eq!(fs::read_to_string(keys.public_key()).unwrap(), public_key);
+        assert!(fw_cfg_arg(&keys).ends_with("ssh/id_ed25519.pub"));
+    }
+}
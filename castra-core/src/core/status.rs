@@ -5,6 +5,7 @@ use std::time::Duration;
 
 use crate::config::{PortForward, PortProtocol, ProjectConfig};
 
+use super::bootstrap::load_stamp;
 use super::diagnostics::{Diagnostic, Severity};
 use super::outcome::VmStatusRow;
 use super::project::config_state_root;
@@ -44,6 +45,7 @@ pub fn collect_status(project: &ProjectConfig) -> StatusSnapshot {
             memory: vm.memory.original().replace(' ', ""),
             uptime,
             forwards: format_port_forwards(&vm.port_forwards),
+            bootstrap_age: load_stamp(&state_root, &vm.name).map(|stamp| stamp.age()),
         });
     }
 
@@ -98,7 +100,7 @@ fn cleanup_orphan_overlay(vm_name: &str, overlay_path: &Path, diagnostics: &mut
                         format_bytes(bytes)
                     ),
                 )
-                .with_help("Guest changes are discarded on shutdown. Export via SSH before stopping if you need to retain data."),
+                .with_help("Guest changes are discarded on shutdown. Keep guest files with a VM's `collect = [...]` rules, which run during `castra down`, or with `castra collect`."),
             );
         }
         Ok(None) => {}
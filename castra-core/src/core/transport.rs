@@ -0,0 +1,550 @@
+//! Transports that reach guests over SSH for bootstrap runs and health probes.
+//!
+//! [`OpenSshTransport`] drives the system `ssh`/`scp` binaries and is the
+//! default. With the `native-ssh` feature, [`NativeTransport`] speaks SSH
+//! in-process through libssh2 and keeps one session per guest, so pipeline
+//! steps no longer pay for a process spawn and key exchange each.
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+use thiserror::Error;
+
+use crate::config::BootstrapTransport;
+use crate::core::bootstrap::shell_quote;
+
+#[cfg(feature = "native-ssh")]
+mod native;
+#[cfg(feature = "native-ssh")]
+pub use native::NativeTransport;
+
+/// Exit status OpenSSH reserves for its own failures (connection refused,
+/// authentication rejected, ...), as opposed to the remote command's.
+const SSH_FAILURE_EXIT_CODE: i32 = 255;
+
+/// Guest address and credentials a transport connects with.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SshConfig {
+    pub user: String,
+    pub host: String,
+    pub port: u16,
+    pub identity: Option<PathBuf>,
+    /// OpenSSH `-o` options. The native transport ignores them.
+    pub options: Vec<String>,
+    /// known_hosts file guest host keys are checked against and recorded in
+    /// on first contact. OpenSSH receives it through `options`.
+    pub known_hosts: Option<PathBuf>,
+}
+
+impl SshConfig {
+    /// `user@host:port`, for messages.
+    pub fn target(&self) -> String {
+        format!("{}@{}:{}", self.user, self.host, self.port)
+    }
+}
+
+/// Stream a line of remote output arrived on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+/// Output captured from a successful transport operation.
+#[derive(Debug, Clone, Default)]
+pub struct ExecOutput {
+    /// Description of what ran, for logs (e.g. the `ssh` command line).
+    pub command: String,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// Why a transport operation failed.
+#[derive(Debug, Error)]
+pub enum TransportError {
+    #[error("Command `{program}` not found in PATH while executing bootstrap step.")]
+    MissingProgram { program: String },
+    #[error("Failed to execute `{program}`: {source}")]
+    Spawn {
+        program: String,
+        #[source]
+        source: io::Error,
+    },
+    #[error("Could not reach {target}: {message}")]
+    Connect { target: String, message: String },
+    #[error("`{command}` did not finish within {}s and was stopped.", timeout.as_secs())]
+    Timeout { command: String, timeout: Duration },
+    #[error("`{command}` exited with code {code:?}. stdout: {} stderr: {}", stdout.trim(), stderr.trim())]
+    Exit {
+        command: String,
+        code: Option<i32>,
+        stdout: String,
+        stderr: String,
+    },
+    #[error("{context}: {source}")]
+    Io {
+        context: String,
+        #[source]
+        source: io::Error,
+    },
+}
+
+/// A way of running commands on, and copying files to and from, a guest.
+///
+/// Implementations must be shareable across the threads bootstrapping
+/// different VMs.
+pub trait Transport: Send + Sync {
+    /// Backend label, e.g. `openssh`.
+    fn name(&self) -> &'static str;
+
+    /// Confirm the guest accepts SSH sessions, giving up once `timeout`
+    /// elapses.
+    fn connect(
+        &self,
+        ssh: &SshConfig,
+        timeout: Option<Duration>,
+    ) -> Result<ExecOutput, TransportError>;
+
+    /// Run `script` through `sh -lc` on the guest, handing every output line
+    /// to `on_line` as it arrives. `input` is written to the command's
+    /// standard input, which is then closed; unlike `script`, it never shows
+    /// up in a process listing on the host or the guest. The command is
+    /// stopped once `timeout` elapses.
+    fn exec_streaming(
+        &self,
+        ssh: &SshConfig,
+        script: &str,
+        input: &str,
+        timeout: Option<Duration>,
+        on_line: &mut dyn FnMut(OutputStream, &str),
+    ) -> Result<ExecOutput, TransportError>;
+
+    /// Like [`Transport::exec_streaming`], without observing output early.
+    fn exec_with_input(
+        &self,
+        ssh: &SshConfig,
+        script: &str,
+        input: &str,
+        timeout: Option<Duration>,
+    ) -> Result<ExecOutput, TransportError> {
+        self.exec_streaming(ssh, script, input, timeout, &mut |_, _| {})
+    }
+
+    /// Like [`Transport::exec_with_input`], with nothing on standard input.
+    fn exec(
+        &self,
+        ssh: &SshConfig,
+        script: &str,
+        timeout: Option<Duration>,
+    ) -> Result<ExecOutput, TransportError> {
+        self.exec_with_input(ssh, script, "", timeout)
+    }
+
+    /// Copy the local file or directory `local` to the guest path `remote`.
+    /// Directories are copied recursively; `remote` must not exist yet. The
+    /// copy is stopped once `timeout` elapses.
+    fn upload(
+        &self,
+        ssh: &SshConfig,
+        local: &Path,
+        remote: &str,
+        timeout: Option<Duration>,
+    ) -> Result<ExecOutput, TransportError>;
+
+    /// Copy the guest file or directory `remote` to the local path `local`.
+    fn download(
+        &self,
+        ssh: &SshConfig,
+        remote: &str,
+        local: &Path,
+    ) -> Result<ExecOutput, TransportError>;
+
+    /// Make the existing guest directory `remote` an exact copy of the local
+    /// directory `local` with a delta-transfer tool. Returns `None` when the
+    /// backend has no such tool, in which case callers copy changed files
+    /// themselves.
+    fn sync_dir(
+        &self,
+        _ssh: &SshConfig,
+        _local: &Path,
+        _remote: &str,
+        _timeout: Option<Duration>,
+    ) -> Option<Result<ExecOutput, TransportError>> {
+        None
+    }
+}
+
+/// Transport selected by `[bootstrap].transport`.
+pub fn for_kind(kind: BootstrapTransport) -> Box<dyn Transport> {
+    match kind {
+        BootstrapTransport::OpenSsh => Box::new(OpenSshTransport),
+        #[cfg(feature = "native-ssh")]
+        BootstrapTransport::Native => Box::new(NativeTransport::new()),
+        // Configuration loading rejects `native` when the feature is off.
+        #[cfg(not(feature = "native-ssh"))]
+        BootstrapTransport::Native => Box::new(OpenSshTransport),
+    }
+}
+
+/// Spawns the system `ssh` and `scp` binaries for every operation.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OpenSshTransport;
+
+impl OpenSshTransport {
+    fn ssh(
+        &self,
+        ssh: &SshConfig,
+        remote_args: &[String],
+        input: &str,
+        timeout: Option<Duration>,
+        on_line: &mut dyn FnMut(OutputStream, &str),
+    ) -> Result<ExecOutput, TransportError> {
+        let args = ssh_command_args(ssh, remote_args);
+        run_streaming("ssh", &args, input, timeout, on_line).map_err(|err| match err {
+            TransportError::Exit { code, stderr, .. } if code == Some(SSH_FAILURE_EXIT_CODE) => {
+                TransportError::Connect {
+                    target: ssh.target(),
+                    message: stderr.trim().to_string(),
+                }
+            }
+            err => err,
+        })
+    }
+
+    fn scp(
+        &self,
+        ssh: &SshConfig,
+        source: String,
+        destination: String,
+        recursive: bool,
+        timeout: Option<Duration>,
+    ) -> Result<ExecOutput, TransportError> {
+        let mut args = connection_args(ssh, "-P");
+        if recursive {
+            args.push(String::from("-r"));
+        }
+        args.push(source);
+        args.push(destination);
+        run_streaming("scp", &args, "", timeout, &mut |_, _| {})
+    }
+}
+
+impl Transport for OpenSshTransport {
+    fn name(&self) -> &'static str {
+        "openssh"
+    }
+
+    fn connect(
+        &self,
+        ssh: &SshConfig,
+        timeout: Option<Duration>,
+    ) -> Result<ExecOutput, TransportError> {
+        self.ssh(ssh, &[String::from("true")], "", timeout, &mut |_, _| {})
+    }
+
+    fn exec_streaming(
+        &self,
+        ssh: &SshConfig,
+        script: &str,
+        input: &str,
+        timeout: Option<Duration>,
+        on_line: &mut dyn FnMut(OutputStream, &str),
+    ) -> Result<ExecOutput, TransportError> {
+        let remote_args = [
+            String::from("sh"),
+            String::from("-lc"),
+            format!("\"{}\"", script),
+        ];
+        self.ssh(ssh, &remote_args, input, timeout, on_line)
+    }
+
+    fn upload(
+        &self,
+        ssh: &SshConfig,
+        local: &Path,
+        remote: &str,
+        timeout: Option<Duration>,
+    ) -> Result<ExecOutput, TransportError> {
+        self.scp(
+            ssh,
+            local.display().to_string(),
+            format!("{}@{}:{}", ssh.user, ssh.host, remote),
+            local.is_dir(),
+            timeout,
+        )
+    }
+
+    fn download(
+        &self,
+        ssh: &SshConfig,
+        remote: &str,
+        local: &Path,
+    ) -> Result<ExecOutput, TransportError> {
+        self.scp(
+            ssh,
+            format!("{}@{}:{}", ssh.user, ssh.host, remote),
+            local.display().to_string(),
+            true,
+            None,
+        )
+    }
+
+    /// `rsync` over the same `ssh` invocation; the guest's `rsync` must be
+    /// checked by the caller.
+    fn sync_dir(
+        &self,
+        ssh: &SshConfig,
+        local: &Path,
+        remote: &str,
+        timeout: Option<Duration>,
+    ) -> Option<Result<ExecOutput, TransportError>> {
+        let args = vec![
+            String::from("-a"),
+            String::from("--delete"),
+            String::from("-e"),
+            format_cli("ssh", &connection_args(ssh, "-p")),
+            format!("{}/", local.display()),
+            format!("{}@{}:{}/", ssh.user, ssh.host, remote),
+        ];
+        match run_streaming("rsync", &args, "", timeout, &mut |_, _| {}) {
+            Err(TransportError::MissingProgram { .. }) => None,
+            result => Some(result),
+        }
+    }
+}
+
+/// Arguments for the system `ssh` to reach `ssh` and run `remote_args`, or
+/// open a login shell when there are none.
+pub fn ssh_command_args(ssh: &SshConfig, remote_args: &[String]) -> Vec<String> {
+    let mut args = connection_args(ssh, "-p");
+    args.push(format!("{}@{}", ssh.user, ssh.host));
+    args.extend(remote_args.iter().cloned());
+    args
+}
+
+/// Identity, `-o` options and port flags shared by `ssh` and `scp`.
+fn connection_args(ssh: &SshConfig, port_flag: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    if let Some(identity) = ssh.identity.as_ref() {
+        args.push(String::from("-i"));
+        args.push(identity.display().to_string());
+    }
+    for option in &ssh.options {
+        args.push(String::from("-o"));
+        args.push(option.clone());
+    }
+    args.push(port_flag.to_string());
+    args.push(ssh.port.to_string());
+    args
+}
+
+/// Run `program` with `input` on its standard input, forwarding its output
+/// line by line and killing it once `timeout` elapses.
+fn run_streaming(
+    program: &str,
+    args: &[String],
+    input: &str,
+    timeout: Option<Duration>,
+    on_line: &mut dyn FnMut(OutputStream, &str),
+) -> Result<ExecOutput, TransportError> {
+    let command = format_cli(program, args);
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(if input.is_empty() {
+            Stdio::null()
+        } else {
+            Stdio::piped()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|source| match source.kind() {
+            io::ErrorKind::NotFound => TransportError::MissingProgram {
+                program: program.to_string(),
+            },
+            _ => TransportError::Spawn {
+                program: program.to_string(),
+                source,
+            },
+        })?;
+
+    // Written from its own thread so a large input cannot stall on a full
+    // pipe while the output goes unread; dropping the handle closes stdin.
+    let writer = child.stdin.take().map(|mut stdin| {
+        let input = input.to_string();
+        std::thread::spawn(move || {
+            let _ = stdin.write_all(input.as_bytes());
+        })
+    });
+    let (tx, rx) = mpsc::channel();
+    let readers = [
+        forward_lines(child.stdout.take(), OutputStream::Stdout, tx.clone()),
+        forward_lines(child.stderr.take(), OutputStream::Stderr, tx),
+    ];
+
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let mut stdout = String::new();
+    let mut stderr = String::new();
+    loop {
+        let received = match deadline {
+            Some(deadline) => rx.recv_timeout(deadline.saturating_duration_since(Instant::now())),
+            None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match received {
+            Ok((stream, line)) => {
+                on_line(stream, &line);
+                let buffer = match stream {
+                    OutputStream::Stdout => &mut stdout,
+                    OutputStream::Stderr => &mut stderr,
+                };
+                buffer.push_str(&line);
+                buffer.push('\n');
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+            Err(RecvTimeoutError::Timeout) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(TransportError::Timeout {
+                    command,
+                    timeout: timeout.unwrap_or_default(),
+                });
+            }
+        }
+    }
+
+    let status = child.wait().map_err(|source| TransportError::Io {
+        context: format!("Failed to wait for `{program}`"),
+        source,
+    })?;
+    for reader in readers {
+        let _ = reader.join();
+    }
+    if let Some(writer) = writer {
+        let _ = writer.join();
+    }
+    if status.success() {
+        Ok(ExecOutput {
+            command,
+            stdout,
+            stderr,
+        })
+    } else {
+        Err(TransportError::Exit {
+            command,
+            code: status.code(),
+            stdout,
+            stderr,
+        })
+    }
+}
+
+fn forward_lines<R: Read + Send + 'static>(
+    pipe: Option<R>,
+    stream: OutputStream,
+    tx: mpsc::Sender<(OutputStream, String)>,
+) -> std::thread::JoinHandle<()> {
+    std::thread::spawn(move || {
+        let Some(pipe) = pipe else {
+            return;
+        };
+        let mut reader = BufReader::new(pipe);
+        let mut line = Vec::new();
+        while matches!(reader.read_until(b'\n', &mut line), Ok(read) if read > 0) {
+            if line.last() == Some(&b'\n') {
+                line.pop();
+            }
+            if tx
+                .send((stream, String::from_utf8_lossy(&line).into_owned()))
+                .is_err()
+            {
+                return;
+            }
+            line.clear();
+        }
+    })
+}
+
+fn format_cli(program: &str, args: &[String]) -> String {
+    let mut parts = Vec::with_capacity(args.len() + 1);
+    parts.push(program.to_string());
+    for arg in args {
+        if arg.chars().all(|ch| {
+            ch.is_ascii_alphanumeric()
+                || matches!(ch, '-' | '_' | '/' | '.' | ':' | '=' | ',' | '@')
+        }) {
+            parts.push(arg.clone());
+        } else {
+            parts.push(shell_quote(arg));
+        }
+    }
+    parts.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_streaming_forwards_lines_and_classifies_failures() {
+        let mut seen = Vec::new();
+        let output = run_streaming(
+            "sh",
+            &[
+                String::from("-c"),
+                String::from("echo one; echo two >&2; echo three"),
+            ],
+            "",
+            None,
+            &mut |stream, line| seen.push((stream, line.to_string())),
+        )
+        .expect("command succeeds");
+        assert_eq!(output.stdout, "one\nthree\n");
+        assert_eq!(output.stderr, "two\n");
+        assert!(seen.contains(&(OutputStream::Stdout, "one".to_string())));
+        assert!(seen.contains(&(OutputStream::Stderr, "two".to_string())));
+
+        let err = run_streaming(
+            "sh",
+            &[String::from("-c"), String::from("echo oops >&2; exit 3")],
+            "",
+            None,
+            &mut |_, _| {},
+        )
+        .unwrap_err();
+        assert!(
+            matches!(&err, TransportError::Exit { code: Some(3), stderr, .. } if stderr == "oops\n"),
+            "{err:?}"
+        );
+
+        let err = run_streaming(
+            "sh",
+            &[String::from("-c"), String::from("sleep 5")],
+            "",
+            Some(Duration::from_millis(200)),
+            &mut |_, _| {},
+        )
+        .unwrap_err();
+        assert!(matches!(err, TransportError::Timeout { .. }), "{err:?}");
+
+        let output = run_streaming(
+            "sh",
+            &[
+                String::from("-c"),
+                String::from("eval \"$(cat)\"; echo $GREETING"),
+            ],
+            "export GREETING='from stdin';\n",
+            None,
+            &mut |_, _| {},
+        )
+        .expect("command reads its input");
+        assert_eq!(output.stdout, "from stdin\n");
+        assert!(!output.command.contains("from stdin"));
+
+        let err =
+            run_streaming("castra-missing-binary", &[], "", None, &mut |_, _| {}).unwrap_err();
+        assert!(matches!(err, TransportError::MissingProgram { .. }));
+    }
+}
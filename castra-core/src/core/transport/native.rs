@@ -0,0 +1,461 @@
+//! In-process SSH transport backed by libssh2.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use ssh2::{CheckResult, KnownHostFileKind, OpenFlags, OpenType, Session, Sftp};
+
+use super::{ExecOutput, OutputStream, SshConfig, Transport, TransportError};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+const DEFAULT_IDENTITIES: [&str; 3] = ["id_ed25519", "id_ecdsa", "id_rsa"];
+
+/// Keeps one authenticated libssh2 session per guest and reuses it for every
+/// operation.
+///
+/// Host keys are checked against [`SshConfig::known_hosts`] the way
+/// OpenSSH's `StrictHostKeyChecking=accept-new` does, and not at all when no
+/// file is set. Authentication uses the configured identity, or else the SSH
+/// agent and the default keys under `~/.ssh`.
+#[derive(Default)]
+pub struct NativeTransport {
+    sessions: Mutex<HashMap<SshConfig, Arc<Mutex<Session>>>>,
+}
+
+impl NativeTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cached session for `ssh`, opening one when none exists.
+    fn session(&self, ssh: &SshConfig) -> Result<Arc<Mutex<Session>>, TransportError> {
+        let mut sessions = self.sessions.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(session) = sessions.get(ssh) {
+            return Ok(Arc::clone(session));
+        }
+        let session = Arc::new(Mutex::new(open_session(ssh, CONNECT_TIMEOUT)?));
+        sessions.insert(ssh.clone(), Arc::clone(&session));
+        Ok(session)
+    }
+
+    /// Run `op` on the session for `ssh`. Sessions are dropped after
+    /// anything but a remote command failing, so the next operation
+    /// reconnects.
+    fn with_session<T>(
+        &self,
+        ssh: &SshConfig,
+        op: impl FnOnce(&Session) -> Result<T, TransportError>,
+    ) -> Result<T, TransportError> {
+        let session = self.session(ssh)?;
+        let result = {
+            let session = session.lock().unwrap_or_else(|err| err.into_inner());
+            op(&session)
+        };
+        if matches!(&result, Err(err) if !matches!(err, TransportError::Exit { .. })) {
+            self.forget(ssh);
+        }
+        result
+    }
+
+    fn forget(&self, ssh: &SshConfig) {
+        self.sessions
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .remove(ssh);
+    }
+}
+
+impl Transport for NativeTransport {
+    fn name(&self) -> &'static str {
+        "native"
+    }
+
+    fn connect(
+        &self,
+        ssh: &SshConfig,
+        timeout: Option<Duration>,
+    ) -> Result<ExecOutput, TransportError> {
+        // Always start from a fresh session: the guest may have rebooted
+        // since the cached one was opened.
+        self.forget(ssh);
+        let session = open_session(ssh, timeout.unwrap_or(CONNECT_TIMEOUT))?;
+        let session = Arc::new(Mutex::new(session));
+        self.sessions
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .insert(ssh.clone(), session);
+        self.with_session(ssh, |session| {
+            Ok(ExecOutput {
+                command: format!("native ssh {}", ssh.target()),
+                stdout: String::new(),
+                stderr: session.banner().unwrap_or_default().to_string(),
+            })
+        })
+    }
+
+    fn exec_streaming(
+        &self,
+        ssh: &SshConfig,
+        script: &str,
+        input: &str,
+        timeout: Option<Duration>,
+        on_line: &mut dyn FnMut(OutputStream, &str),
+    ) -> Result<ExecOutput, TransportError> {
+        let command = format!("sh -lc \"{}\"", script);
+        let description = format!("native ssh {}: {}", ssh.target(), command);
+        self.with_session(ssh, |session| {
+            let io_error = |context: &str, err: ssh2::Error| TransportError::Io {
+                context: format!("{context} on {}", ssh.target()),
+                source: err.into(),
+            };
+            let mut channel = session
+                .channel_session()
+                .map_err(|err| io_error("Failed to open a channel", err))?;
+            channel
+                .exec(&command)
+                .map_err(|err| io_error("Failed to start the command", err))?;
+            if !input.is_empty() {
+                channel
+                    .write_all(input.as_bytes())
+                    .map_err(|source| TransportError::Io {
+                        context: format!("Failed to send input to {}", ssh.target()),
+                        source,
+                    })?;
+            }
+            channel
+                .send_eof()
+                .map_err(|err| io_error("Failed to close the command's input", err))?;
+
+            let deadline = timeout.map(|timeout| Instant::now() + timeout);
+            let mut stdout = LineBuffer::new(OutputStream::Stdout);
+            let mut stderr = LineBuffer::new(OutputStream::Stderr);
+            let mut buffer = [0u8; 8192];
+            session.set_blocking(false);
+            let pumped = 'pump: loop {
+                let mut progressed = false;
+                for stream in [OutputStream::Stdout, OutputStream::Stderr] {
+                    let (read, lines) = match stream {
+                        OutputStream::Stdout => (channel.read(&mut buffer), &mut stdout),
+                        OutputStream::Stderr => (channel.stderr().read(&mut buffer), &mut stderr),
+                    };
+                    match read {
+                        Ok(0) => {}
+                        Ok(count) => {
+                            progressed = true;
+                            lines.push(&buffer[..count], on_line);
+                        }
+                        Err(err) if err.kind() == io::ErrorKind::WouldBlock => {}
+                        Err(source) => {
+                            break 'pump Err(TransportError::Io {
+                                context: format!("Failed to read output from {}", ssh.target()),
+                                source,
+                            });
+                        }
+                    }
+                }
+                if !progressed && channel.eof() {
+                    break Ok(());
+                }
+                if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                    break Err(TransportError::Timeout {
+                        command: description.clone(),
+                        timeout: timeout.unwrap_or_default(),
+                    });
+                }
+                if !progressed {
+                    std::thread::sleep(POLL_INTERVAL);
+                }
+            };
+            session.set_blocking(true);
+            pumped?;
+
+            stdout.finish(on_line);
+            stderr.finish(on_line);
+            channel
+                .wait_close()
+                .map_err(|err| io_error("Failed to close the channel", err))?;
+            let code = channel
+                .exit_status()
+                .map_err(|err| io_error("Failed to read the exit status", err))?;
+            if code == 0 {
+                Ok(ExecOutput {
+                    command: description,
+                    stdout: stdout.captured,
+                    stderr: stderr.captured,
+                })
+            } else {
+                Err(TransportError::Exit {
+                    command: description,
+                    code: Some(code),
+                    stdout: stdout.captured,
+                    stderr: stderr.captured,
+                })
+            }
+        })
+    }
+
+    fn upload(
+        &self,
+        ssh: &SshConfig,
+        local: &Path,
+        remote: &str,
+        timeout: Option<Duration>,
+    ) -> Result<ExecOutput, TransportError> {
+        let command = format!("sftp {} -> {}:{remote}", local.display(), ssh.target());
+        self.with_session(ssh, |session| {
+            // libssh2 bounds each blocking call rather than the whole copy.
+            session.set_timeout(timeout.map_or(0, timeout_millis));
+            let uploaded = open_sftp(session, ssh).and_then(|sftp| {
+                upload_path(&sftp, local, Path::new(remote)).map_err(|source| {
+                    match (source.kind(), timeout) {
+                        (io::ErrorKind::TimedOut, Some(timeout)) => TransportError::Timeout {
+                            command: command.clone(),
+                            timeout,
+                        },
+                        _ => TransportError::Io {
+                            context: format!(
+                                "Failed to upload {} to {}:{remote}",
+                                local.display(),
+                                ssh.target()
+                            ),
+                            source,
+                        },
+                    }
+                })
+            });
+            session.set_timeout(0);
+            uploaded?;
+            Ok(ExecOutput {
+                command: command.clone(),
+                ..ExecOutput::default()
+            })
+        })
+    }
+
+    fn download(
+        &self,
+        ssh: &SshConfig,
+        remote: &str,
+        local: &Path,
+    ) -> Result<ExecOutput, TransportError> {
+        self.with_session(ssh, |session| {
+            let sftp = open_sftp(session, ssh)?;
+            download_path(&sftp, Path::new(remote), local).map_err(|source| {
+                TransportError::Io {
+                    context: format!(
+                        "Failed to download {}:{remote} to {}",
+                        ssh.target(),
+                        local.display()
+                    ),
+                    source,
+                }
+            })?;
+            Ok(ExecOutput {
+                command: format!("sftp {}:{remote} -> {}", ssh.target(), local.display()),
+                ..ExecOutput::default()
+            })
+        })
+    }
+}
+
+fn open_session(ssh: &SshConfig, timeout: Duration) -> Result<Session, TransportError> {
+    let connect_error = |message: String| TransportError::Connect {
+        target: ssh.target(),
+        message,
+    };
+    let addresses = (ssh.host.as_str(), ssh.port)
+        .to_socket_addrs()
+        .map_err(|err| connect_error(format!("failed to resolve host: {err}")))?;
+    let mut last_error = None;
+    let mut stream = None;
+    for address in addresses {
+        match TcpStream::connect_timeout(&address, timeout) {
+            Ok(connected) => {
+                stream = Some(connected);
+                break;
+            }
+            Err(err) => last_error = Some(err),
+        }
+    }
+    let stream = stream.ok_or_else(|| {
+        connect_error(match last_error {
+            Some(err) => err.to_string(),
+            None => "host resolved to no addresses".to_string(),
+        })
+    })?;
+
+    let mut session = Session::new().map_err(|err| connect_error(err.to_string()))?;
+    session.set_tcp_stream(stream);
+    session.set_timeout(timeout_millis(timeout));
+    session
+        .handshake()
+        .map_err(|err| connect_error(format!("SSH handshake failed: {err}")))?;
+    if let Some(known_hosts) = ssh.known_hosts.as_ref() {
+        verify_host_key(&session, ssh, known_hosts).map_err(connect_error)?;
+    }
+    authenticate(&session, ssh).map_err(connect_error)?;
+    session.set_timeout(0);
+    Ok(session)
+}
+
+/// `timeout` as libssh2 expects it; zero would mean no limit.
+fn timeout_millis(timeout: Duration) -> u32 {
+    timeout.as_millis().clamp(1, u32::MAX as u128) as u32
+}
+
+/// Accept the guest's host key if `known_hosts` has no entry for it yet,
+/// recording it there; reject it if a different key is on record.
+fn verify_host_key(session: &Session, ssh: &SshConfig, known_hosts: &Path) -> Result<(), String> {
+    let (key, kind) = session
+        .host_key()
+        .ok_or_else(|| "server presented no host key".to_string())?;
+    let mut known = session.known_hosts().map_err(|err| err.to_string())?;
+    if known_hosts.exists() {
+        known
+            .read_file(known_hosts, KnownHostFileKind::OpenSSH)
+            .map_err(|err| format!("failed to read {}: {err}", known_hosts.display()))?;
+    }
+    match known.check_port(&ssh.host, ssh.port, key) {
+        CheckResult::Match => Ok(()),
+        CheckResult::NotFound => {
+            let host = if ssh.port == 22 {
+                ssh.host.clone()
+            } else {
+                format!("[{}]:{}", ssh.host, ssh.port)
+            };
+            known
+                .add(&host, key, "", kind.into())
+                .and_then(|()| known.write_file(known_hosts, KnownHostFileKind::OpenSSH))
+                .map_err(|err| {
+                    format!(
+                        "failed to record host key in {}: {err}",
+                        known_hosts.display()
+                    )
+                })
+        }
+        CheckResult::Mismatch => Err(format!(
+            "host key does not match the one recorded in {}",
+            known_hosts.display()
+        )),
+        CheckResult::Failure => Err("host key check failed".to_string()),
+    }
+}
+
+fn authenticate(session: &Session, ssh: &SshConfig) -> Result<(), String> {
+    if let Some(identity) = ssh.identity.as_ref() {
+        return session
+            .userauth_pubkey_file(&ssh.user, None, identity, None)
+            .map_err(|err| format!("authentication with {} rejected: {err}", identity.display()));
+    }
+    if session.userauth_agent(&ssh.user).is_ok() && session.authenticated() {
+        return Ok(());
+    }
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    for name in DEFAULT_IDENTITIES {
+        let Some(path) = home.as_ref().map(|home| home.join(".ssh").join(name)) else {
+            break;
+        };
+        if path.is_file()
+            && session
+                .userauth_pubkey_file(&ssh.user, None, &path, None)
+                .is_ok()
+        {
+            return Ok(());
+        }
+    }
+    Err(format!(
+        "no identity accepted for `{}` (tried the SSH agent and ~/.ssh/{{{}}})",
+        ssh.user,
+        DEFAULT_IDENTITIES.join(",")
+    ))
+}
+
+fn open_sftp(session: &Session, ssh: &SshConfig) -> Result<Sftp, TransportError> {
+    session.sftp().map_err(|err| TransportError::Io {
+        context: format!("Failed to start SFTP on {}", ssh.target()),
+        source: err.into(),
+    })
+}
+
+fn upload_path(sftp: &Sftp, local: &Path, remote: &Path) -> io::Result<()> {
+    let metadata = fs::metadata(local)?;
+    let mode = (metadata.permissions().mode() & 0o7777) as i32;
+    if metadata.is_dir() {
+        sftp.mkdir(remote, mode)?;
+        let mut entries = fs::read_dir(local)?.collect::<io::Result<Vec<_>>>()?;
+        entries.sort_by_key(|entry| entry.file_name());
+        for entry in entries {
+            upload_path(sftp, &entry.path(), &remote.join(entry.file_name()))?;
+        }
+    } else {
+        let mut target = sftp.open_mode(
+            remote,
+            OpenFlags::WRITE | OpenFlags::CREATE | OpenFlags::TRUNCATE,
+            mode,
+            OpenType::File,
+        )?;
+        io::copy(&mut File::open(local)?, &mut target)?;
+    }
+    Ok(())
+}
+
+fn download_path(sftp: &Sftp, remote: &Path, local: &Path) -> io::Result<()> {
+    if sftp.stat(remote)?.is_dir() {
+        fs::create_dir_all(local)?;
+        for (path, _) in sftp.readdir(remote)? {
+            if let Some(name) = path.file_name() {
+                download_path(sftp, &path, &local.join(name))?;
+            }
+        }
+    } else {
+        io::copy(&mut sftp.open(remote)?, &mut File::create(local)?)?;
+    }
+    Ok(())
+}
+
+/// Splits a byte stream into lines for [`Transport::exec_streaming`]
+/// callbacks while keeping the full text.
+struct LineBuffer {
+    stream: OutputStream,
+    pending: Vec<u8>,
+    captured: String,
+}
+
+impl LineBuffer {
+    fn new(stream: OutputStream) -> Self {
+        Self {
+            stream,
+            pending: Vec::new(),
+            captured: String::new(),
+        }
+    }
+
+    fn push(&mut self, bytes: &[u8], on_line: &mut dyn FnMut(OutputStream, &str)) {
+        self.pending.extend_from_slice(bytes);
+        while let Some(end) = self.pending.iter().position(|&byte| byte == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=end).collect();
+            self.emit(&line[..end], on_line);
+        }
+    }
+
+    fn finish(&mut self, on_line: &mut dyn FnMut(OutputStream, &str)) {
+        if !self.pending.is_empty() {
+            let line = std::mem::take(&mut self.pending);
+            self.emit(&line, on_line);
+        }
+    }
+
+    fn emit(&mut self, line: &[u8], on_line: &mut dyn FnMut(OutputStream, &str)) {
+        let text = String::from_utf8_lossy(line);
+        on_line(self.stream, &text);
+        self.captured.push_str(&text);
+        self.captured.push('\n');
+    }
+}
@@ -31,14 +31,17 @@ pub fn persist_workspace_metadata(
         ),
     })?;
 
-    let config_contents = match fs::read_to_string(&project.file_path) {
+    let config_contents = match config::resolve_config_source(
+        &project.file_path,
+        options.config.profile.as_deref(),
+    ) {
         Ok(contents) => Some(contents),
         Err(err) => {
             diagnostics.push(
                 Diagnostic::new(
                     Severity::Warning,
                     format!(
-                        "Unable to read configuration at {} for snapshotting: {err}",
+                        "Unable to resolve configuration at {} for snapshotting: {err}",
                         project.file_path.display()
                     ),
                 )
@@ -80,7 +83,8 @@ pub fn persist_workspace_metadata(
         }
     }
 
-    let metadata = build_workspace_metadata(
+    let workspace_json = metadata_dir.join("workspace.json");
+    let mut metadata = build_workspace_metadata(
         project,
         synthetic_config,
         options,
@@ -88,6 +92,8 @@ pub fn persist_workspace_metadata(
         config_digest.clone(),
         snapshot_ref.clone(),
     );
+    // Keep image references from the previous run until assets are re-resolved.
+    metadata.images = recorded_images(&workspace_json);
 
     let metadata_json =
         serde_json::to_string_pretty(&metadata).map_err(|err| Error::PreflightFailed {
@@ -97,7 +103,6 @@ pub fn persist_workspace_metadata(
             ),
         })?;
 
-    let workspace_json = metadata_dir.join("workspace.json");
     fs::write(&workspace_json, &metadata_json).map_err(|err| Error::PreflightFailed {
         message: format!(
             "Failed to write workspace metadata at {}: {err}",
@@ -136,6 +141,67 @@ pub fn persist_workspace_metadata(
     Ok(())
 }
 
+/// Record the shared-store images a workspace links to so the registry can
+/// keep reference counts for `castra clean --global`.
+pub fn record_workspace_images(
+    state_root: &Path,
+    images: Vec<WorkspaceImageMetadata>,
+    diagnostics: &mut Vec<Diagnostic>,
+) -> Result<()> {
+    let metadata_dir = state_root.join("metadata");
+    let workspace_json = metadata_dir.join("workspace.json");
+    let contents = match fs::read_to_string(&workspace_json) {
+        Ok(contents) => contents,
+        Err(err) => {
+            diagnostics.push(
+                Diagnostic::new(
+                    Severity::Warning,
+                    format!(
+                        "Unable to read workspace metadata at {} to record image references: {err}",
+                        workspace_json.display()
+                    ),
+                )
+                .with_help("`castra clean --global` may treat this workspace's images as unreferenced until `castra up` succeeds again."),
+            );
+            return Ok(());
+        }
+    };
+    let mut metadata: WorkspaceMetadata =
+        serde_json::from_str(&contents).map_err(|err| Error::PreflightFailed {
+            message: format!(
+                "Failed to parse workspace metadata at {}: {err}",
+                workspace_json.display()
+            ),
+        })?;
+    metadata.images = images;
+
+    let metadata_json =
+        serde_json::to_string_pretty(&metadata).map_err(|err| Error::PreflightFailed {
+            message: format!(
+                "Failed to serialize workspace metadata for project {}: {err}",
+                metadata.project.name
+            ),
+        })?;
+    for path in [workspace_json, metadata_dir.join("config_metadata.json")] {
+        fs::write(&path, &metadata_json).map_err(|err| Error::PreflightFailed {
+            message: format!(
+                "Failed to write workspace metadata at {}: {err}",
+                path.display()
+            ),
+        })?;
+    }
+
+    Ok(())
+}
+
+fn recorded_images(workspace_json: &Path) -> Vec<WorkspaceImageMetadata> {
+    fs::read_to_string(workspace_json)
+        .ok()
+        .and_then(|contents| serde_json::from_str::<WorkspaceMetadata>(&contents).ok())
+        .map(|metadata| metadata.images)
+        .unwrap_or_default()
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WorkspaceMetadata {
     pub metadata_version: String,
@@ -148,6 +214,8 @@ pub struct WorkspaceMetadata {
     #[serde(default)]
     pub vms: Vec<WorkspaceVmMetadata>,
     #[serde(default)]
+    pub images: Vec<WorkspaceImageMetadata>,
+    #[serde(default)]
     pub notes: Vec<String>,
 }
 
@@ -176,6 +244,8 @@ pub struct ConfigMetadata {
     pub snapshot_path: Option<PathBuf>,
     #[serde(default)]
     pub synthetic: bool,
+    #[serde(default)]
+    pub profile: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -208,6 +278,8 @@ pub struct InvocationMetadata {
     pub force: bool,
     #[serde(default)]
     pub alpine_qcow_override: Option<PathBuf>,
+    #[serde(default)]
+    pub offline: bool,
     pub bootstrap_overrides_applied: bool,
     #[serde(default = "default_vm_launch_mode_descriptor")]
     pub vm_launch_mode: String,
@@ -227,6 +299,24 @@ pub struct WorkspaceVmMetadata {
     pub base_image: String,
 }
 
+/// Reference from a workspace path to a blob in the shared image store.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct WorkspaceImageMetadata {
+    pub digest: String,
+    pub path: PathBuf,
+    #[serde(default)]
+    pub tag: Option<String>,
+}
+
+/// Live image reference discovered through the registry.
+#[derive(Debug, Clone)]
+pub struct ImageReference {
+    pub digest: String,
+    pub workspace_id: String,
+    pub state_root: PathBuf,
+    pub path: PathBuf,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ConfigSnapshotSource {
     ConfigFile,
@@ -264,7 +354,11 @@ impl WorkspaceRuntimeState {
 impl WorkspaceHandle {
     pub fn load_project_config(&self) -> Result<ProjectConfig> {
         if let Some(path) = self.config_path.as_ref().filter(|path| path.is_file()) {
-            let mut project = config::load_project_config(path)?;
+            let profile = self
+                .metadata
+                .as_ref()
+                .and_then(|metadata| metadata.config.profile.as_deref());
+            let mut project = config::load_project_config_with_profile(path, profile)?;
             project.state_root = self.state_root.clone();
             return Ok(project);
         }
@@ -307,9 +401,15 @@ pub struct WorkspaceRegistry {
 
 impl WorkspaceRegistry {
     pub fn discover() -> Result<Self> {
-        let roots = collect_workspace_roots();
+        Self::discover_with_roots(&[])
+    }
+
+    /// Discover workspaces from the default roots plus `extra_roots`.
+    pub fn discover_with_roots(extra_roots: &[PathBuf]) -> Result<Self> {
+        let mut roots = collect_workspace_roots();
+        roots.extend(extra_roots.iter().cloned());
         let mut registry = Self {
-            roots,
+            roots: deduplicate_paths(roots),
             entries: Vec::new(),
             diagnostics: Vec::new(),
         };
@@ -350,6 +450,28 @@ impl WorkspaceRegistry {
         self.entries.iter().filter(|entry| entry.active).collect()
     }
 
+    /// Shared-store references whose workspace path still exists.
+    pub fn image_references(&self) -> Vec<ImageReference> {
+        let mut references = Vec::new();
+        for entry in &self.entries {
+            let Some(metadata) = entry.metadata.as_ref() else {
+                continue;
+            };
+            for image in &metadata.images {
+                if fs::symlink_metadata(&image.path).is_err() {
+                    continue;
+                }
+                references.push(ImageReference {
+                    digest: image.digest.clone(),
+                    workspace_id: entry.workspace_id.clone(),
+                    state_root: entry.state_root.clone(),
+                    path: image.path.clone(),
+                });
+            }
+        }
+        references
+    }
+
     pub fn find_by_config(&self, config_path: &Path) -> Option<&WorkspaceHandle> {
         self.entries.iter().find(|entry| {
             entry
@@ -399,7 +521,7 @@ fn build_workspace_metadata(
         })
         .collect();
 
-    let mut notes = vec!["Guest disk changes are ephemeral; export via SSH before running `castra down` if you need to retain data.".to_string()];
+    let mut notes = vec!["Guest disk changes are ephemeral; list paths to keep under a VM's `collect = [...]` (copied during `castra down`, before shutdown) or run `castra collect`.".to_string()];
     if synthetic_config {
         notes.push(
             "Workspace seeded from a synthetic configuration; run `castra init` to persist castra.toml."
@@ -426,6 +548,7 @@ fn build_workspace_metadata(
             digest_sha256: config_digest,
             snapshot_path,
             synthetic: synthetic_config,
+            profile: options.config.profile.clone(),
         },
         bootstrap: BootstrapMetadata {
             global_mode: project.bootstrap.mode.as_str().to_string(),
@@ -441,11 +564,13 @@ fn build_workspace_metadata(
             plan: options.plan,
             force: options.force,
             alpine_qcow_override: options.alpine_qcow_override.clone(),
+            offline: options.offline,
             bootstrap_overrides_applied: options.bootstrap.global.is_some()
                 || !options.bootstrap.per_vm.is_empty(),
             vm_launch_mode: options.launch_mode.as_str().to_string(),
         },
         vms: vm_entries,
+        images: Vec::new(),
         notes,
     }
 }
@@ -82,6 +82,21 @@ pub enum Error {
     ShutdownFailed { vm: String, message: String },
     #[error("Failed to bootstrap VM `{vm}`: {message}")]
     BootstrapFailed { vm: String, message: String },
+    #[error("Failed to collect artifacts from VM `{vm}`: {message}")]
+    CollectFailed { vm: String, message: String },
+    #[error("Command failed on VM `{vm}`: {message}")]
+    GuestCommandFailed {
+        vm: String,
+        /// Exit status to pass on to the caller.
+        code: u8,
+        message: String,
+    },
+    #[error("Hook `{hook}` failed for VM `{vm}`: {message}")]
+    HookFailed {
+        hook: String,
+        vm: String,
+        message: String,
+    },
     #[error("Failed to read logs at {path}: {source}")]
     LogReadFailed {
         path: PathBuf,
@@ -20,7 +20,12 @@ fn main() -> ExitCode {
         }
     };
 
-    let Cli { config, command } = cli;
+    let Cli {
+        config,
+        profile,
+        command,
+    } = cli;
+    let profile = profile.as_deref();
 
     let command = match command {
         Some(cmd) => cmd,
@@ -34,12 +39,18 @@ fn main() -> ExitCode {
 
     let exit = match command {
         Commands::Init(args) => app::handle_init(args, config.as_ref()),
-        Commands::Up(args) => app::handle_up(args, config.as_ref()),
-        Commands::Down(args) => app::handle_down(args, config.as_ref()),
-        Commands::Status(args) => app::handle_status(args, config.as_ref()),
-        Commands::Ports(args) => app::handle_ports(args, config.as_ref()),
-        Commands::Logs(args) => app::handle_logs(args, config.as_ref()),
-        Commands::Clean(args) => app::handle_clean(args, config.as_ref()),
+        Commands::Up(args) => app::handle_up(args, config.as_ref(), profile),
+        Commands::Bootstrap(args) => app::handle_bootstrap(args, config.as_ref(), profile),
+        Commands::Down(args) => app::handle_down(args, config.as_ref(), profile),
+        Commands::Status(args) => app::handle_status(args, config.as_ref(), profile),
+        Commands::Ports(args) => app::handle_ports(args, config.as_ref(), profile),
+        Commands::Logs(args) => app::handle_logs(args, config.as_ref(), profile),
+        Commands::Collect(args) => app::handle_collect(args, config.as_ref(), profile),
+        Commands::Ssh(args) => app::handle_ssh(args, config.as_ref(), profile),
+        Commands::Exec(args) => app::handle_exec(args, config.as_ref(), profile),
+        Commands::Clean(args) => app::handle_clean(args, config.as_ref(), profile),
+        Commands::Config(args) => app::handle_config(args, config.as_ref(), profile),
+        Commands::Image(args) => app::handle_image(args, config.as_ref(), profile),
         Commands::Bus(args) => app::handle_bus(args, config.as_ref()),
         Commands::Broker(args) => app::handle_broker(args),
     };
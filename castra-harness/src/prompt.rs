@@ -14,7 +14,7 @@ Dispatch remote work through the harness-generated wrappers listed under `script
   vm_commands.sh list
   vm_commands.sh view-output <run_id> [stdout|stderr|both]
 
-Wrappers set `SSH_TARGET` (required) along with optional `SSH_PORT`, `SSH_EXTRA_OPTS`, and `SSH_STRICT=1` when strict host key checking is needed. If a wrapper is unavailable or you must retarget, export these variables yourself before invoking `./vm_commands.sh`. Each run captures stdout/stderr artifacts keyed by `run_id`; revisit them with `vm_commands.sh view-output` when reporting status or verifying results.
+Wrappers set `SSH_TARGET` (required) along with optional `SSH_PORT`, `SSH_EXTRA_OPTS`, `SSH_IDENTITY` (a private key, shown as `-i` in the operational context when one applies), `SSH_KNOWN_HOSTS` (enforces recorded guest host keys), and `SSH_STRICT=1` when strict host key checking is needed. If a wrapper is unavailable or you must retarget, export these variables yourself before invoking `./vm_commands.sh`. Each run captures stdout/stderr artifacts keyed by `run_id`; revisit them with `vm_commands.sh view-output` when reporting status or verifying results.
 
 Assume complete administrative control over every VM placed under your supervision—and only those VMs. You may install packages, reshape configuration, and spawn or retire long-lived processes at will, but do not alter hosts outside the declared set. Prefer canonical UNIX session primitives—systemd-run, tmux, screen, nohup, journalctl, systemctl, rsync, scp—for managing concurrent tasks and inspecting their state. Use them to keep multiple agents running simultaneously without disturbing one another, and to surface their session details for later inspection.
 
@@ -38,6 +38,8 @@ pub struct VmEndpoint {
     host: String,
     port: u16,
     auth_hint: Option<String>,
+    identity: Option<String>,
+    known_hosts: Option<String>,
     status: Option<String>,
     wrapper_script: Option<String>,
 }
@@ -56,6 +58,8 @@ impl VmEndpoint {
             host: host.into(),
             port: 22,
             auth_hint: None,
+            identity: None,
+            known_hosts: None,
             status: None,
             wrapper_script: None,
         }
@@ -73,6 +77,19 @@ impl VmEndpoint {
         self
     }
 
+    /// Authenticate with the given private key (typically the workspace key).
+    pub fn with_identity<S: Into<String>>(mut self, identity: S) -> Self {
+        self.identity = Some(identity.into());
+        self
+    }
+
+    /// Check host keys strictly against the given known_hosts file instead of
+    /// disabling verification.
+    pub fn with_known_hosts<S: Into<String>>(mut self, known_hosts: S) -> Self {
+        self.known_hosts = Some(known_hosts.into());
+        self
+    }
+
     /// Attach an operational status note.
     pub fn with_status<S: Into<String>>(mut self, status: S) -> Self {
         self.status = Some(status.into());
@@ -132,11 +149,29 @@ impl PromptBuilder {
                 let mut line = String::new();
                 write!(
                     &mut line,
-                    "- {}: ssh {}@{} -p {} -o StrictHostKeyChecking=no -o UserKnownHostsFile=/dev/null",
+                    "- {}: ssh {}@{} -p {}",
                     endpoint.name, endpoint.user, endpoint.host, endpoint.port
                 )
                 .expect("writing to string should not fail");
 
+                if let Some(identity) = endpoint.identity.as_ref() {
+                    write!(&mut line, " -i {}", identity)
+                        .expect("writing to string should not fail");
+                }
+
+                match endpoint.known_hosts.as_ref() {
+                    Some(known_hosts) => write!(
+                        &mut line,
+                        " -o StrictHostKeyChecking=yes -o UserKnownHostsFile={}",
+                        known_hosts
+                    ),
+                    None => write!(
+                        &mut line,
+                        " -o StrictHostKeyChecking=no -o UserKnownHostsFile=/dev/null"
+                    ),
+                }
+                .expect("writing to string should not fail");
+
                 if let Some(auth_hint) = endpoint.auth_hint.as_ref() {
                     write!(&mut line, " [{}]", auth_hint)
                         .expect("writing to string should not fail");
@@ -238,6 +273,24 @@ mod tests {
         assert!(lines[2].contains("; script=/tmp/castra/vm-gamma.sh"));
     }
 
+    #[test]
+    fn renders_managed_identity_with_strict_host_keys() {
+        let endpoint = VmEndpoint::new("vm-alpha", "root", "127.0.0.1")
+            .with_port(2222)
+            .with_identity("/work/.castra/ssh/id_ed25519")
+            .with_known_hosts("/work/.castra/ssh/known_hosts");
+
+        let prompt = PromptBuilder::new()
+            .with_operational_context(vec![endpoint])
+            .build();
+
+        assert!(prompt.contains(
+            "- vm-alpha: ssh root@127.0.0.1 -p 2222 -i /work/.castra/ssh/id_ed25519 \
+             -o StrictHostKeyChecking=yes -o UserKnownHostsFile=/work/.castra/ssh/known_hosts\n"
+        ));
+        assert!(!prompt.contains("UserKnownHostsFile=/dev/null"));
+    }
+
     #[test]
     fn renders_bootstrap_scripts_section_sorted_by_vm() {
         let scripts = vec![
@@ -965,12 +965,56 @@ impl Default for UpLifecycle {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DownloadStatus {
+    pub bytes: u64,
+    pub total: Option<u64>,
+    pub bytes_per_sec: u64,
+}
+
+impl DownloadStatus {
+    fn is_complete(&self) -> bool {
+        self.total.is_some_and(|total| self.bytes >= total)
+    }
+
+    fn summary(&self) -> String {
+        let rate = format!("{}/s", format_bytes(self.bytes_per_sec));
+        match self.total {
+            Some(total) if total > 0 => {
+                let percent = (self.bytes as f64 / total as f64) * 100.0;
+                format!(
+                    "downloading image {percent:.0}% ({} / {}) @ {rate}",
+                    format_bytes(self.bytes),
+                    format_bytes(total)
+                )
+            }
+            _ => format!("downloading image {} @ {rate}", format_bytes(self.bytes)),
+        }
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} {}", UNITS[unit])
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
 pub struct UpState {
     lifecycle: UpLifecycle,
     vm_fleet: VmFleetState,
     last_error: Option<String>,
     runtime_paths: Option<RuntimePaths>,
     shutdown_in_progress: bool,
+    download: Option<DownloadStatus>,
 }
 
 impl Default for UpState {
@@ -981,6 +1025,7 @@ impl Default for UpState {
             last_error: None,
             runtime_paths: None,
             shutdown_in_progress: false,
+            download: None,
         }
     }
 }
@@ -1005,6 +1050,7 @@ impl UpState {
         self.last_error = None;
         self.runtime_paths = None;
         self.shutdown_in_progress = false;
+        self.download = None;
         true
     }
 
@@ -1053,6 +1099,10 @@ impl UpState {
         self.last_error = Some(message.into());
     }
 
+    pub fn note_download(&mut self, status: DownloadStatus) {
+        self.download = (!status.is_complete()).then_some(status);
+    }
+
     pub fn counts(&self) -> VmCounts {
         self.vm_fleet.counts()
     }
@@ -1099,6 +1149,9 @@ impl UpState {
                 if in_progress > 0 {
                     parts.push(format!("{in_progress} in progress"));
                 }
+                if let Some(download) = &self.download {
+                    parts.push(download.summary());
+                }
                 if counts.failed > 0 {
                     parts.push(format!("{} failed", counts.failed));
                 }
@@ -1553,6 +1606,19 @@ impl AppState {
                 }
                 Some(format!("{vm}: bootstrap plan {}", action.describe()))
             }
+            Event::DownloadProgress {
+                bytes,
+                total,
+                bytes_per_sec,
+                ..
+            } => {
+                self.up.note_download(DownloadStatus {
+                    bytes: *bytes,
+                    total: *total,
+                    bytes_per_sec: *bytes_per_sec,
+                });
+                None
+            }
             Event::OverlayPrepared { vm, overlay_path } => {
                 self.up.vm_fleet_mut().update_vm(
                     vm,
@@ -1583,12 +1649,18 @@ impl AppState {
             }
             Event::BootstrapStep {
                 vm,
+                stage,
                 step,
                 status,
                 duration_ms,
                 detail,
+                ..
             } => {
                 let text = format_step(step, status, *duration_ms, detail.as_deref());
+                let text = match stage {
+                    Some(stage) => format!("[{stage}] {text}"),
+                    None => text,
+                };
                 let attention = if matches!(status, BootstrapStepStatus::Failed) {
                     self.up.note_error(text.clone());
                     Error
@@ -1686,6 +1758,7 @@ fn format_trigger(trigger: &BootstrapTrigger) -> String {
     match trigger {
         BootstrapTrigger::Always => "always".to_string(),
         BootstrapTrigger::Auto => "auto".to_string(),
+        BootstrapTrigger::Manual => "manual".to_string(),
     }
 }
 
@@ -1787,6 +1860,28 @@ mod tests {
         );
     }
 
+    #[test]
+    fn download_progress_surfaces_in_up_status_line() {
+        let mut state = AppState::new();
+        state.begin_up_operation().expect("up should start");
+
+        let progress = |bytes| Event::DownloadProgress {
+            url: "https://example.invalid/alpine.qcow2".to_string(),
+            bytes,
+            total: Some(4 * 1024 * 1024),
+            bytes_per_sec: 1024 * 1024,
+        };
+        assert!(state.handle_up_event(&progress(1024 * 1024)).is_none());
+        let line = state.up_status_line();
+        assert!(
+            line.contains("downloading image 25% (1.0 MiB / 4.0 MiB) @ 1.0 MiB/s"),
+            "unexpected status line: {line}"
+        );
+
+        state.handle_up_event(&progress(4 * 1024 * 1024));
+        assert!(!state.up_status_line().contains("downloading image"));
+    }
+
     #[test]
     fn catalog_selection_survives_refresh() {
         let temp_home = TempDir::new().expect("temp dir should exist");
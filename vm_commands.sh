@@ -14,6 +14,8 @@ Environment:
   SSH_TARGET      Remote ssh target (e.g. user@host). Required.
   SSH_PORT        Optional ssh port (defaults to 22).
   SSH_EXTRA_OPTS  Additional ssh options (space-separated).
+  SSH_IDENTITY    Optional private key (e.g. the workspace key under <state_root>/ssh).
+  SSH_KNOWN_HOSTS Optional known_hosts file; enables strict host key checking against it.
   SSH_STRICT=1    Keep strict host key checking (default disables).
 Flags:
   --wait          Stream command output and wait for completion.
@@ -30,10 +32,16 @@ build_ssh_command() {
 
     SSH_CMD=(ssh)
 
-    if [[ "${SSH_STRICT:-0}" != "1" ]]; then
+    if [[ -n "${SSH_KNOWN_HOSTS:-}" ]]; then
+        SSH_CMD+=(-o StrictHostKeyChecking=yes -o "UserKnownHostsFile=${SSH_KNOWN_HOSTS}")
+    elif [[ "${SSH_STRICT:-0}" != "1" ]]; then
         SSH_CMD+=(-o StrictHostKeyChecking=no -o UserKnownHostsFile=/dev/null)
     fi
 
+    if [[ -n "${SSH_IDENTITY:-}" ]]; then
+        SSH_CMD+=(-i "${SSH_IDENTITY}")
+    fi
+
     if [[ -n "${SSH_PORT:-}" ]]; then
         SSH_CMD+=(-p "${SSH_PORT}")
     fi